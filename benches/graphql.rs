@@ -1,12 +1,12 @@
 #![feature(test)]
 extern crate test;
 
-extern crate graphql_parser;
+extern crate edb_graphql_parser;
 
 use std::io::Read;
 use std::fs::File;
 
-use graphql_parser::parse_query;
+use edb_graphql_parser::parse_query;
 
 fn load_file(name: &str) -> String {
     let mut buf = String::with_capacity(1024);
@@ -0,0 +1,416 @@
+//! Macros parsing GraphQL queries at compile time
+//!
+//! `graphql!` takes a string literal and `include_graphql!` a path to a
+//! file, relative to the directory of the crate's `Cargo.toml`. The query
+//! is parsed when the crate is built: syntax errors fail the build, and the
+//! macro expands to the constructors of the AST, which is exactly what
+//! `parse_query::<&str>` of the same source returns, positions included.
+//!
+//! ```rust
+//! # extern crate edb_graphql_parser;
+//! # extern crate edb_graphql_parser_macros;
+//! use edb_graphql_parser::parse_query;
+//! use edb_graphql_parser_macros::graphql;
+//!
+//! let doc = graphql!("query User($id: ID!) { user(id: $id) { name } }");
+//! let expected = parse_query::<&str>(
+//!     "query User($id: ID!) { user(id: $id) { name } }").unwrap();
+//! assert_eq!(doc, expected);
+//! ```
+//!
+//! ```rust,compile_fail
+//! # extern crate edb_graphql_parser_macros;
+//! use edb_graphql_parser_macros::graphql;
+//!
+//! let doc = graphql!("{ user { id }");
+//! ```
+//!
+//! The macros live in a crate of their own, as they use
+//! `edb-graphql-parser` to parse the queries, so the expanded code needs
+//! `edb-graphql-parser` as a dependency too.
+#![warn(missing_debug_implementations)]
+
+extern crate proc_macro;
+
+use std::convert::TryFrom;
+use std::fs;
+use std::path::Path;
+
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{parse_macro_input, LitStr};
+
+use edb_graphql_parser::Pos;
+use edb_graphql_parser::common::DirectiveArgument;
+use edb_graphql_parser::parse_query;
+use edb_graphql_parser::query::*;
+
+
+/// Parses the query at compile time, expands to
+/// `query::Document<'static, &'static str>`
+#[proc_macro]
+pub fn graphql(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let source = parse_macro_input!(input as LitStr);
+    expand(&source.value(), source.span(), None).into()
+}
+
+/// Parses the file at compile time, expands to
+/// `query::Document<'static, &'static str>`
+///
+/// The path is relative to the directory containing `Cargo.toml`, the
+/// crate is rebuilt when the file changes.
+#[proc_macro]
+pub fn include_graphql(input: proc_macro::TokenStream)
+    -> proc_macro::TokenStream
+{
+    let path = parse_macro_input!(input as LitStr);
+    let root = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let full = Path::new(&root).join(path.value());
+    match fs::read_to_string(&full) {
+        Ok(source) => expand(&source, path.span(), Some(&full)).into(),
+        Err(e) => {
+            let message = format!("can't read {:?}: {}", full, e);
+            syn::Error::new(path.span(), message).to_compile_error().into()
+        }
+    }
+}
+
+fn expand(source: &str, span: Span, file: Option<&Path>) -> TokenStream {
+    let doc = match parse_query::<&str>(source) {
+        Ok(doc) => doc,
+        Err(e) => return syn::Error::new(span, e).to_compile_error(),
+    };
+    let track = file.map(|path| {
+        let path = path.to_string_lossy();
+        quote!(const _: &str = ::std::include_str!(#path);)
+    });
+    let definitions = doc.definitions.iter().map(definition);
+    quote!({
+        #track
+        ::edb_graphql_parser::query::Document::<'static, &'static str> {
+            definitions: ::std::vec![#(#definitions),*],
+        }
+    })
+}
+
+fn definition<'a>(def: &Definition<'a, &'a str>) -> TokenStream {
+    match *def {
+        Definition::Operation(ref op) => {
+            let op = operation(op);
+            quote!(::edb_graphql_parser::query::Definition::Operation(#op))
+        }
+        Definition::Fragment(ref frag) => {
+            let frag = fragment(frag);
+            quote!(::edb_graphql_parser::query::Definition::Fragment(#frag))
+        }
+    }
+}
+
+fn operation<'a>(op: &Operation<'a, &'a str>) -> TokenStream {
+    let kind = match op.kind {
+        OperationKind::ImplicitQuery => quote!(ImplicitQuery),
+        OperationKind::Query => quote!(Query),
+        OperationKind::Mutation => quote!(Mutation),
+        OperationKind::Subscription => quote!(Subscription),
+    };
+    let position = pos(&op.position);
+    let name = optional(op.name.map(|name| quote!(#name)));
+    let variables = op.variable_definitions.iter().map(variable);
+    let insert_kind = match op.insert_variables.kind {
+        InsertVarsKind::Query => quote!(Query),
+        InsertVarsKind::Parens => quote!(Parens),
+        InsertVarsKind::Normal => quote!(Normal),
+    };
+    let insert_position = pos(&op.insert_variables.position);
+    let directives = directives(&op.directives);
+    let selection_set = selection_set(&op.selection_set);
+    quote!(::edb_graphql_parser::query::Operation {
+        kind: ::edb_graphql_parser::query::OperationKind::#kind,
+        position: #position,
+        name: #name,
+        variable_definitions: ::std::vec![#(#variables),*],
+        insert_variables: ::edb_graphql_parser::query::InsertVars {
+            kind: ::edb_graphql_parser::query::InsertVarsKind::#insert_kind,
+            position: #insert_position,
+        },
+        directives: #directives,
+        selection_set: #selection_set,
+    })
+}
+
+fn fragment<'a>(frag: &FragmentDefinition<'a, &'a str>) -> TokenStream {
+    let position = pos(&frag.position);
+    let name = frag.name;
+    let variables = frag.variable_definitions.iter().map(variable);
+    let on_position = pos(&frag.on_position);
+    let type_condition = type_condition(&frag.type_condition);
+    let directives = directives(&frag.directives);
+    let selection_set = selection_set(&frag.selection_set);
+    quote!(::edb_graphql_parser::query::FragmentDefinition {
+        position: #position,
+        name: #name,
+        variable_definitions: ::std::vec![#(#variables),*],
+        on_position: #on_position,
+        type_condition: #type_condition,
+        directives: #directives,
+        selection_set: #selection_set,
+    })
+}
+
+fn variable<'a>(var: &VariableDefinition<'a, &'a str>) -> TokenStream {
+    let position = pos(&var.position);
+    let name = var.name;
+    let var_type = var_type(&var.var_type);
+    let default_value = optional(var.default_value.as_ref().map(|default| {
+        let (start, end) = (pos(&default.span.0), pos(&default.span.1));
+        let value = value(&default.value);
+        quote!(::edb_graphql_parser::query::DefaultValue {
+            span: (#start, #end),
+            value: #value,
+        })
+    }));
+    let directives = directives(&var.directives);
+    quote!(::edb_graphql_parser::query::VariableDefinition {
+        position: #position,
+        name: #name,
+        var_type: #var_type,
+        default_value: #default_value,
+        directives: #directives,
+    })
+}
+
+fn selection_set<'a>(set: &SelectionSet<'a, &'a str>) -> TokenStream {
+    let (start, end) = (pos(&set.span.0), pos(&set.span.1));
+    let items = set.items.iter().map(selection);
+    quote!(::edb_graphql_parser::query::SelectionSet {
+        span: (#start, #end),
+        items: ::std::vec![#(#items),*],
+    })
+}
+
+fn selection<'a>(item: &Selection<'a, &'a str>) -> TokenStream {
+    match *item {
+        Selection::Field(ref field) => {
+            let position = pos(&field.position);
+            let alias = optional(field.alias.map(|alias| quote!(#alias)));
+            let name = field.name;
+            let arguments = arguments(&field.arguments);
+            let arguments_syntax = arguments_syntax(&field.arguments_syntax);
+            let nullability = optional(field.nullability.as_ref()
+                .map(nullability));
+            let directives = directives(&field.directives);
+            let selection_set = selection_set(&field.selection_set);
+            quote!(::edb_graphql_parser::query::Selection::Field(
+                ::edb_graphql_parser::query::Field {
+                    position: #position,
+                    alias: #alias,
+                    name: #name,
+                    arguments: #arguments,
+                    arguments_syntax: #arguments_syntax,
+                    nullability: #nullability,
+                    directives: #directives,
+                    selection_set: #selection_set,
+                }
+            ))
+        }
+        Selection::FragmentSpread(ref spread) => {
+            let position = pos(&spread.position);
+            let fragment_name = spread.fragment_name;
+            let arguments = arguments(&spread.arguments);
+            let arguments_syntax = arguments_syntax(&spread.arguments_syntax);
+            let directives = directives(&spread.directives);
+            quote!(::edb_graphql_parser::query::Selection::FragmentSpread(
+                ::edb_graphql_parser::query::FragmentSpread {
+                    position: #position,
+                    fragment_name: #fragment_name,
+                    arguments: #arguments,
+                    arguments_syntax: #arguments_syntax,
+                    directives: #directives,
+                }
+            ))
+        }
+        Selection::InlineFragment(ref frag) => {
+            let position = pos(&frag.position);
+            let on_position = optional(frag.on_position.as_ref().map(pos));
+            let type_condition = optional(frag.type_condition.as_ref()
+                .map(type_condition));
+            let directives = directives(&frag.directives);
+            let selection_set = selection_set(&frag.selection_set);
+            quote!(::edb_graphql_parser::query::Selection::InlineFragment(
+                ::edb_graphql_parser::query::InlineFragment {
+                    position: #position,
+                    on_position: #on_position,
+                    type_condition: #type_condition,
+                    directives: #directives,
+                    selection_set: #selection_set,
+                }
+            ))
+        }
+    }
+}
+
+fn type_condition<'a>(cond: &TypeCondition<'a, &'a str>) -> TokenStream {
+    match *cond {
+        TypeCondition::On(name) => {
+            quote!(::edb_graphql_parser::query::TypeCondition::On(#name))
+        }
+    }
+}
+
+fn nullability(value: &Nullability) -> TokenStream {
+    match *value {
+        Nullability::Designator(designator) => {
+            let designator = nullability_designator(designator);
+            quote!(::edb_graphql_parser::query::Nullability::Designator(
+                #designator))
+        }
+        Nullability::List(ref inner, designator) => {
+            let inner = optional(inner.as_ref().map(|inner| {
+                let inner = nullability(inner);
+                quote!(::std::boxed::Box::new(#inner))
+            }));
+            let designator = optional(designator.map(nullability_designator));
+            quote!(::edb_graphql_parser::query::Nullability::List(
+                #inner, #designator))
+        }
+    }
+}
+
+fn nullability_designator(designator: NullabilityDesignator) -> TokenStream {
+    match designator {
+        NullabilityDesignator::Required => quote!(
+            ::edb_graphql_parser::query::NullabilityDesignator::Required),
+        NullabilityDesignator::Optional => quote!(
+            ::edb_graphql_parser::query::NullabilityDesignator::Optional),
+    }
+}
+
+fn directives<'a>(directives: &[Directive<'a, &'a str>]) -> TokenStream {
+    let directives = directives.iter().map(|dir| {
+        let position = pos(&dir.position);
+        let name = dir.name;
+        let arguments = dir.arguments.iter().map(directive_argument);
+        let arguments_syntax = arguments_syntax(&dir.arguments_syntax);
+        quote!(::edb_graphql_parser::query::Directive {
+            position: #position,
+            name: #name,
+            arguments: ::std::vec![#(#arguments),*],
+            arguments_syntax: #arguments_syntax,
+        })
+    });
+    quote!(::std::vec![#(#directives),*])
+}
+
+fn directive_argument<'a>(arg: &DirectiveArgument<'a, &'a str>)
+    -> TokenStream
+{
+    let name = arg.name;
+    let value = value(&arg.value);
+    let value_position = pos(&arg.value_position);
+    quote!(::edb_graphql_parser::common::DirectiveArgument {
+        name: #name,
+        value: #value,
+        value_position: #value_position,
+    })
+}
+
+fn arguments<'a>(arguments: &[(&'a str, Value<'a, &'a str>)])
+    -> TokenStream
+{
+    let arguments = arguments.iter().map(|(name, val)| {
+        let val = value(val);
+        quote!((#name, #val))
+    });
+    quote!(::std::vec![#(#arguments),*])
+}
+
+fn value<'a>(val: &Value<'a, &'a str>) -> TokenStream {
+    match *val {
+        Value::Variable(name) => {
+            quote!(::edb_graphql_parser::query::Value::Variable(#name))
+        }
+        Value::Int(ref number) => {
+            let number = match number.as_i64().map(i32::try_from) {
+                Some(Ok(number)) => quote!(
+                    ::edb_graphql_parser::query::Number::from(#number)),
+                _ => {
+                    let digits = number.as_bigint().to_string();
+                    quote!(#digits
+                        .parse::<::edb_graphql_parser::query::Number>()
+                        .expect("valid integer"))
+                }
+            };
+            quote!(::edb_graphql_parser::query::Value::Int(#number))
+        }
+        Value::Float(ref float) => quote!(
+            ::edb_graphql_parser::query::Value::Float(
+                ::std::string::String::from(#float))),
+        Value::String(ref string) => {
+            let string = string.as_ref();
+            quote!(::edb_graphql_parser::query::Value::String(
+                ::std::borrow::Cow::Borrowed(#string)))
+        }
+        Value::Boolean(boolean) => {
+            quote!(::edb_graphql_parser::query::Value::Boolean(#boolean))
+        }
+        Value::Null => quote!(::edb_graphql_parser::query::Value::Null),
+        Value::Enum(name) => {
+            quote!(::edb_graphql_parser::query::Value::Enum(#name))
+        }
+        Value::List(ref items) => {
+            let items = items.iter().map(value);
+            quote!(::edb_graphql_parser::query::Value::List(
+                ::std::vec![#(#items),*]))
+        }
+        Value::Object(ref fields) => {
+            let fields = arguments(fields);
+            quote!(::edb_graphql_parser::query::Value::Object(#fields))
+        }
+    }
+}
+
+fn var_type<'a>(typ: &Type<'a, &'a str>) -> TokenStream {
+    match *typ {
+        Type::NamedType(name) => {
+            quote!(::edb_graphql_parser::query::Type::NamedType(#name))
+        }
+        Type::ListType(ref inner) => {
+            let inner = var_type(inner);
+            quote!(::edb_graphql_parser::query::Type::ListType(
+                ::std::boxed::Box::new(#inner)))
+        }
+        Type::NonNullType(ref inner) => {
+            let inner = var_type(inner);
+            quote!(::edb_graphql_parser::query::Type::NonNullType(
+                ::std::boxed::Box::new(#inner)))
+        }
+    }
+}
+
+fn arguments_syntax(syntax: &Option<ArgumentsSyntax>) -> TokenStream {
+    optional(syntax.as_ref().map(|syntax| {
+        let (open, close) = (pos(&syntax.parens.0), pos(&syntax.parens.1));
+        let colons = syntax.colons.iter().map(pos);
+        quote!(::edb_graphql_parser::query::ArgumentsSyntax {
+            parens: (#open, #close),
+            colons: ::std::vec![#(#colons),*],
+        })
+    }))
+}
+
+fn optional(value: Option<TokenStream>) -> TokenStream {
+    match value {
+        Some(value) => quote!(::std::option::Option::Some(#value)),
+        None => quote!(::std::option::Option::None),
+    }
+}
+
+fn pos(position: &Pos) -> TokenStream {
+    let Pos { line, column, character, token } = *position;
+    quote!(::edb_graphql_parser::Pos {
+        line: #line,
+        column: #column,
+        character: #character,
+        token: #token,
+    })
+}
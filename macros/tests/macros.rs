@@ -0,0 +1,41 @@
+extern crate edb_graphql_parser;
+extern crate edb_graphql_parser_macros;
+
+use edb_graphql_parser::parse_query;
+use edb_graphql_parser_macros::{graphql, include_graphql};
+
+const QUERY: &str = "\
+query Q($a: [Int!]! = [1, 2], $b: In = {x: null, y: E}) @dir(v: 1.5) {
+  big: value(n: 123456789012345678901234567890, s: \"a\\nb\", t: true)
+  ... on T @skip(if: $a) { x }
+  ...F
+}
+
+fragment F on T @d { y { z } }
+
+{ implicit }
+";
+
+#[test]
+fn graphql() {
+    let doc = graphql!("\
+query Q($a: [Int!]! = [1, 2], $b: In = {x: null, y: E}) @dir(v: 1.5) {
+  big: value(n: 123456789012345678901234567890, s: \"a\\nb\", t: true)
+  ... on T @skip(if: $a) { x }
+  ...F
+}
+
+fragment F on T @d { y { z } }
+
+{ implicit }
+");
+    assert_eq!(doc, parse_query::<&str>(QUERY).unwrap());
+}
+
+#[test]
+fn include() {
+    let doc = include_graphql!("tests/ops/user.graphql");
+    let source = include_str!("ops/user.graphql");
+    assert_eq!(doc, parse_query::<&str>(source).unwrap());
+    assert_eq!(doc.to_string(), source);
+}
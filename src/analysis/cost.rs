@@ -0,0 +1,458 @@
+use std::borrow::Borrow;
+use std::collections::BTreeMap;
+
+use crate::common::{Directive, Text, Value};
+use crate::query::{self, Definition, FragmentDefinition, Operation};
+use crate::query::{OperationKind, Selection, SelectionSet, TypeCondition};
+use crate::schema::{self, SchemaIndex, TypeKind};
+
+
+/// Cost of operations as defined by the `@cost` and `@listSize`
+/// directives of the schema
+///
+/// This implements the [Cost Analysis] draft specification:
+///
+/// * `@cost(weight: String!)` sets the weight of a field, an argument, an
+///   input field or a type (the weight of a type is used for fields of
+///   that type which have no weight of their own). Composite types weigh
+///   1 by default and leaf types 0.
+/// * `@listSize(assumedSize: Int, slicingArguments: [String!],
+///   sizedFields: [String!])` estimates the size of the list returned by
+///   the field: the largest of slicing arguments passed to the field, its
+///   assumed size, or `default_list_size`. With `sizedFields` the size
+///   applies to the named subfields (e.g. `edges` of a connection) instead.
+///
+/// Each fragment is computed once for a list size applied to its fields,
+/// so spreading fragments many times doesn't multiply the work.
+///
+/// Cost of a field is the weight of the field plus the weights of its
+/// arguments (and input fields) that are passed, plus the cost of its
+/// selections; for list fields the weight and selections are multiplied by
+/// the size of the list. Fragments on different types are all counted,
+/// so the cost of abstract selections is an upper bound.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// # use std::collections::BTreeMap;
+/// use edb_graphql_parser::{parse_query, parse_schema};
+/// use edb_graphql_parser::analysis::CostAnalysis;
+/// use edb_graphql_parser::query::Value;
+///
+/// let schema = parse_schema::<&str>(r#"
+///     type Query {
+///         users(first: Int): [User] @listSize(slicingArguments: ["first"])
+///     }
+///     type User { name: String, avatar: String @cost(weight: "2.5") }
+/// "#).unwrap();
+/// let doc = parse_query::<&str>(
+///     "query Q($n: Int) { users(first: $n) { name avatar } }",
+/// ).unwrap();
+/// let op = doc.operations().next().unwrap();
+/// let mut variables = BTreeMap::new();
+/// variables.insert("n", Value::<&str>::Int(10.into()));
+/// let analysis = CostAnalysis::new(&schema);
+/// assert_eq!(analysis.cost(&doc, op, &variables), 35.0);
+/// ```
+///
+/// [Cost Analysis]: https://ibm.github.io/graphql-specs/cost-spec.html
+#[derive(Debug)]
+pub struct CostAnalysis<'x, 's, S>
+    where S: Text<'s>
+{
+    index: SchemaIndex<'x, 's, S>,
+    default_list_size: u64,
+}
+
+struct Coster<'c, 'x, 's, 'q, 'v, S, Q, K, V>
+    where S: Text<'s>,
+          Q: Text<'q>,
+          V: Text<'v>,
+{
+    analysis: &'c CostAnalysis<'x, 's, S>,
+    fragments: BTreeMap<&'c str, &'c FragmentDefinition<'q, Q>>,
+    operation: &'c Operation<'q, Q>,
+    variables: &'c BTreeMap<K, Value<'v, V>>,
+    stack: Vec<&'c str>,
+    /// Costs of the fragments already computed
+    cache: BTreeMap<FragmentKey<'c>, f64>,
+}
+
+/// Name of the fragment and the list size applied to its subfields
+type FragmentKey<'c> = (&'c str, Option<(Vec<String>, u64)>);
+
+/// List size applied to the subfields
+struct Sized<'x> {
+    fields: Vec<&'x str>,
+    size: u64,
+}
+
+fn directive<'x, 'a, T>(directives: &'x [Directive<'a, T>], name: &str)
+    -> Option<&'x Directive<'a, T>>
+    where T: Text<'a>,
+{
+    directives.iter().find(|dir| dir.name.as_ref() == name)
+}
+
+fn weight<'a, T>(directives: &[Directive<'a, T>]) -> Option<f64>
+    where T: Text<'a>,
+{
+    let arg = directive(directives, "cost")?.argument("weight")?;
+    match arg.value {
+        Value::String(ref s) => s.as_ref().trim().parse().ok(),
+        Value::Float(ref s) => s.parse().ok(),
+        Value::Int(ref n) => n.as_i64().map(|n| n as f64),
+        _ => None,
+    }
+}
+
+fn strings<'x, 'a, T>(dir: &'x Directive<'a, T>, name: &str) -> Vec<&'x str>
+    where T: Text<'a>,
+{
+    match dir.argument(name).map(|arg| &arg.value) {
+        Some(Value::List(items)) => items.iter()
+            .filter_map(|item| match *item {
+                Value::String(ref s) => Some(s.as_ref()),
+                _ => None,
+            })
+            .collect(),
+        Some(Value::String(s)) => vec![s.as_ref()],
+        _ => Vec::new(),
+    }
+}
+
+impl<'x, 's, S> CostAnalysis<'x, 's, S>
+    where S: Text<'s>
+{
+    pub fn new(schema: &'x schema::Document<'s, S>) -> Self {
+        CostAnalysis {
+            index: SchemaIndex::new(schema),
+            default_list_size: 1,
+        }
+    }
+
+    /// Set the size of lists which have no `@listSize` directive, or
+    /// neither slicing arguments passed nor assumed size
+    pub fn default_list_size(&mut self, size: u64) -> &mut Self {
+        self.default_list_size = size;
+        self
+    }
+
+    /// Returns the estimated cost of the operation
+    ///
+    /// The `doc` is used for looking up fragments spread in the operation.
+    /// Slicing arguments may be variables, which are looked up in
+    /// `variables`, then in the defaults of the variable definitions.
+    /// Fields and types unknown to the schema cost nothing.
+    pub fn cost<'q, 'v, Q, K, V>(&self, doc: &query::Document<'q, Q>,
+        operation: &Operation<'q, Q>, variables: &BTreeMap<K, Value<'v, V>>)
+        -> f64
+        where Q: Text<'q>,
+              K: Borrow<str> + Ord,
+              V: Text<'v>,
+    {
+        let mut fragments = BTreeMap::new();
+        for def in &doc.definitions {
+            if let Definition::Fragment(ref frag) = *def {
+                fragments.entry(frag.name.as_ref()).or_insert(frag);
+            }
+        }
+        let root = match operation.kind {
+            OperationKind::ImplicitQuery | OperationKind::Query => {
+                self.index.query_type()
+            }
+            OperationKind::Mutation => self.index.mutation_type(),
+            OperationKind::Subscription => self.index.subscription_type(),
+        };
+        let mut coster = Coster {
+            analysis: self,
+            fragments,
+            operation,
+            variables,
+            stack: Vec::new(),
+            cache: BTreeMap::new(),
+        };
+        match root {
+            Some(root) => {
+                coster.selection_set(&operation.selection_set, root, None)
+            }
+            None => 0.0,
+        }
+    }
+
+    /// Weight of the type, used for fields without `@cost`
+    fn type_weight(&self, name: &str) -> f64 {
+        let entry = match self.index.type_by_name(name) {
+            Some(entry) => entry,
+            None => return 0.0,
+        };
+        let own = entry.definition.and_then(|def| weight(def.directives()))
+            .or_else(|| entry.extensions.iter()
+                     .find_map(|ext| weight(ext.directives())));
+        match own {
+            Some(weight) => weight,
+            None if entry.kind.is_composite() => 1.0,
+            None => 0.0,
+        }
+    }
+}
+
+impl<'c, 'x, 's, 'q, 'v, S, Q, K, V> Coster<'c, 'x, 's, 'q, 'v, S, Q, K, V>
+    where S: Text<'s>,
+          Q: Text<'q>,
+          K: Borrow<str> + Ord,
+          V: Text<'v>,
+{
+    fn selection_set(&mut self, set: &'c SelectionSet<'q, Q>,
+        parent: &str, sized: Option<&Sized>)
+        -> f64
+    {
+        let mut cost = 0.0;
+        for item in &set.items {
+            cost += match *item {
+                Selection::Field(ref field) => {
+                    self.field(field, parent, sized)
+                }
+                Selection::InlineFragment(ref frag) => {
+                    let typ = match frag.type_condition {
+                        Some(TypeCondition::On(ref name)) => name.as_ref(),
+                        None => parent,
+                    };
+                    self.selection_set(&frag.selection_set, typ, sized)
+                }
+                Selection::FragmentSpread(ref spread) => {
+                    self.fragment(spread.fragment_name.as_ref(), sized)
+                }
+            };
+        }
+        cost
+    }
+
+    fn fragment(&mut self, name: &'c str, sized: Option<&Sized>) -> f64 {
+        let key = (name, sized.map(|s| {
+            (s.fields.iter().map(|f| f.to_string()).collect(), s.size)
+        }));
+        if let Some(&cost) = self.cache.get(&key) {
+            return cost;
+        }
+        // cycles are reported by validation
+        if self.stack.contains(&name) {
+            return 0.0;
+        }
+        let frag = match self.fragments.get(name) {
+            Some(frag) => *frag,
+            None => return 0.0,
+        };
+        let TypeCondition::On(ref typ) = frag.type_condition;
+        self.stack.push(name);
+        let cost = self.selection_set(&frag.selection_set, typ.as_ref(),
+                                      sized);
+        self.stack.pop();
+        self.cache.insert(key, cost);
+        cost
+    }
+
+    fn field(&mut self, field: &'c query::Field<'q, Q>, parent: &str,
+        sized: Option<&Sized>)
+        -> f64
+    {
+        let def = match self.analysis.index.field(parent, field.name.as_ref())
+        {
+            Some(def) => def,
+            None => return 0.0,
+        };
+        let typ = def.field_type.innermost_name();
+        let weight = weight(&def.directives)
+            .unwrap_or_else(|| self.analysis.type_weight(typ));
+        let mut arguments = 0.0;
+        for (name, value) in &field.arguments {
+            let arg = def.arguments.iter()
+                .find(|arg| arg.name.as_ref() == name.as_ref());
+            if let Some(arg) = arg {
+                arguments += self.argument(arg, value);
+            }
+        }
+        let list_size = directive(&def.directives, "listSize");
+        let sized_fields = list_size.map(|dir| strings(dir, "sizedFields"))
+            .unwrap_or_default();
+        let size = list_size.and_then(|dir| self.list_size(dir, field));
+        let children_sized = if sized_fields.is_empty() {
+            None
+        } else {
+            Some(Sized {
+                fields: sized_fields,
+                size: size.unwrap_or(self.analysis.default_list_size),
+            })
+        };
+        let children = if field.selection_set.items.is_empty() {
+            0.0
+        } else {
+            self.selection_set(&field.selection_set, typ,
+                               children_sized.as_ref())
+        };
+        if !def.field_type.is_list() {
+            return weight + arguments + children;
+        }
+        let sized = sized.filter(|s| s.fields.contains(&def.name.as_ref()));
+        let size = match (sized, children_sized) {
+            (Some(sized), _) => sized.size,
+            (None, Some(_)) => self.analysis.default_list_size,
+            (None, None) => size.unwrap_or(self.analysis.default_list_size),
+        };
+        arguments + size as f64 * (weight + children)
+    }
+
+    /// Returns the largest slicing argument, or the assumed size
+    fn list_size(&self, dir: &Directive<'s, S>, field: &query::Field<'q, Q>)
+        -> Option<u64>
+    {
+        let slicing = strings(dir, "slicingArguments");
+        let passed = field.arguments.iter()
+            .filter(|(name, _)| slicing.contains(&name.as_ref()))
+            .filter_map(|(_, value)| self.int(value))
+            .max();
+        passed.or_else(|| match dir.argument("assumedSize")?.value {
+            Value::Int(ref n) => n.as_i64().map(|n| n.max(0) as u64),
+            _ => None,
+        })
+    }
+
+    fn int(&self, value: &Value<'q, Q>) -> Option<u64> {
+        let n = match *value {
+            Value::Int(ref n) => n.as_i64(),
+            Value::Variable(ref var) => {
+                match self.variables.get(var.as_ref()) {
+                    Some(Value::Int(n)) => n.as_i64(),
+                    Some(_) => None,
+                    None => self.operation.variable_definitions.iter()
+                        .find(|v| v.name.as_ref() == var.as_ref())
+                        .and_then(|v| v.default_value.as_ref())
+                        .and_then(|d| match d.value {
+                            Value::Int(ref n) => n.as_i64(),
+                            _ => None,
+                        }),
+                }
+            }
+            _ => None,
+        };
+        n.map(|n| n.max(0) as u64)
+    }
+
+    /// Weight of the passed argument and its input fields
+    fn argument(&self, arg: &schema::InputValue<'s, S>, value: &Value<'q, Q>)
+        -> f64
+    {
+        if let Value::Null = *value {
+            return 0.0;
+        }
+        let own = weight(&arg.directives).unwrap_or(0.0);
+        own + self.input(arg.value_type.innermost_name(), value)
+    }
+
+    fn input(&self, type_name: &str, value: &Value<'q, Q>) -> f64 {
+        match *value {
+            Value::List(ref items) => {
+                items.iter().map(|item| self.input(type_name, item)).sum()
+            }
+            Value::Object(ref fields) => {
+                let entry = match self.analysis.index.type_by_name(type_name)
+                {
+                    Some(entry) if entry.kind == TypeKind::InputObject => {
+                        entry
+                    }
+                    _ => return 0.0,
+                };
+                fields.iter().map(|(name, value)| {
+                    entry.input_fields.iter()
+                        .find(|f| f.name.as_ref() == name.as_ref())
+                        .map_or(0.0, |f| self.argument(f, value))
+                }).sum()
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use crate::{parse_query, parse_schema};
+    use crate::query::Value;
+    use super::CostAnalysis;
+
+    const SCHEMA: &str = r#"
+        type Query {
+            user(id: ID!): User
+            users(first: Int, last: Int): UserConnection
+                @listSize(slicingArguments: ["first", "last"],
+                          sizedFields: ["edges"])
+            search(filter: Filter @cost(weight: "3")): [User]
+                @listSize(assumedSize: 20)
+            tags: [String]
+        }
+        type Mutation { ping: Int @cost(weight: 10) }
+        type UserConnection { edges: [Edge] total: Int }
+        type Edge { node: User }
+        type User @cost(weight: "2") { id: ID name: String }
+        input Filter { name: String @cost(weight: "0.5") nested: [Filter] }
+    "#;
+
+    fn cost(query: &str, variables: &[(&str, i32)]) -> f64 {
+        let schema = parse_schema::<&str>(SCHEMA).unwrap();
+        let mut analysis = CostAnalysis::new(&schema);
+        analysis.default_list_size(5);
+        let doc = parse_query::<&str>(query).unwrap();
+        let variables = variables.iter()
+            .map(|&(name, value)| (name, Value::<&str>::Int(value.into())))
+            .collect::<BTreeMap<_, _>>();
+        let op = doc.operations().next().unwrap();
+        analysis.cost(&doc, op, &variables)
+    }
+
+    #[test]
+    fn fields() {
+        assert_eq!(cost("{ user(id: 1) { id name } }", &[]), 2.0);
+        assert_eq!(cost("{ tags }", &[]), 0.0);
+        assert_eq!(cost("{ unknown { x } }", &[]), 0.0);
+        assert_eq!(cost("mutation { ping }", &[]), 10.0);
+        assert_eq!(cost("{ user(id: 1) { ...F } } fragment F on User {
+                           ... on User { id } }", &[]), 2.0);
+    }
+
+    #[test]
+    fn fragments_computed_once() {
+        let mut query = String::from("{ ...F0 }");
+        for i in 0..64 {
+            query.push_str(&format!(
+                " fragment F{} on Query {{ ...F{} ...F{} }}", i, i + 1, i + 1));
+        }
+        query.push_str(" fragment F64 on Query { user(id: 1) { id } }");
+        assert_eq!(cost(&query, &[]), 2f64.powi(65));
+        // list size of the `edges` is a part of the key
+        assert_eq!(cost("{ a: users(first: 2) { ...C }
+                           b: users(first: 3) { ...C } }
+                         fragment C on UserConnection {
+                           edges { node { id } } }",
+                        &[]), 17.0);
+    }
+
+    #[test]
+    fn lists() {
+        // connection: 1 + edges 10 * (1 + node 2)
+        assert_eq!(cost("{ users(first: 10) { edges { node { id } } } }",
+                        &[]), 31.0);
+        assert_eq!(cost("query Q($n: Int = 3) {
+                           users(first: 2, last: $n) { edges { node { id } } }
+                         }", &[]), 10.0);
+        assert_eq!(cost("query Q($n: Int = 3) {
+                           users(last: $n) { edges { node { id } } } }",
+                        &[("n", 100)]), 301.0);
+        assert_eq!(cost("{ users { total edges { node { id } } } }", &[]),
+                   16.0);
+        assert_eq!(cost("{ search { id } }", &[]), 40.0);
+        assert_eq!(cost("{ search(filter: null) { id } }", &[]), 40.0);
+        assert_eq!(cost(r#"{ search(filter: {name: "a",
+                              nested: [{name: "b"}, {nested: []}]}) { id } }"#,
+                        &[]), 44.0);
+    }
+}
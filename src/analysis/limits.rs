@@ -0,0 +1,345 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use thiserror::Error;
+
+use crate::analysis::{Estimator, ListArguments};
+use crate::common::Text;
+use crate::position::Pos;
+use crate::query::{self, Definition, FragmentDefinition, Operation};
+use crate::query::{OperationKind, Selection, SelectionSet, TypeCondition};
+use crate::schema;
+use crate::schema::index::{type_name, SchemaIndex};
+
+
+/// Limits on selections of specific types
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::{parse_query, parse_schema};
+/// use edb_graphql_parser::analysis::SelectionLimits;
+///
+/// let schema = parse_schema::<&str>("
+///     type Query { users(first: Int): [User] }
+///     type User { id: ID, name: String, email: String }
+/// ").unwrap();
+/// let mut limits = SelectionLimits::default();
+/// limits.max_fields("User", 2).max_items("User", 100);
+///
+/// let doc = parse_query::<&str>("{ users(first: 10) { id name } }").unwrap();
+/// assert!(limits.check(&schema, &doc).is_ok());
+/// let doc = parse_query::<&str>("{ users(first: 1000) { id } }").unwrap();
+/// assert_eq!(limits.check(&schema, &doc).unwrap_err().len(), 1);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SelectionLimits {
+    types: BTreeMap<String, TypeLimits>,
+    list_arguments: ListArguments,
+}
+
+#[derive(Debug, Clone, Default)]
+struct TypeLimits {
+    max_fields: Option<usize>,
+    max_items: Option<u64>,
+}
+
+/// Kind of the limit that was exceeded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitKind {
+    /// Too many distinct fields selected on the type
+    Fields,
+    /// Too many items of the type requested via the pagination argument
+    Items,
+}
+
+/// Selection exceeding the configured limit
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("{} of type {type_name:?} exceeds the limit: {actual} > {limit}",
+        match kind { LimitKind::Fields => "Selection", _ => "List size" })]
+pub struct LimitViolation {
+    pub kind: LimitKind,
+    pub type_name: String,
+    pub limit: u64,
+    pub actual: u64,
+    /// Position of the selection set or the field exceeding the limit
+    pub position: Pos,
+}
+
+struct Checker<'l, 'x, 's, 'q, S, Q>
+    where S: Text<'s>,
+          Q: Text<'q>,
+{
+    limits: &'l SelectionLimits,
+    index: SchemaIndex<'x, 's, S>,
+    fragments: BTreeMap<&'x str, &'x FragmentDefinition<'q, Q>>,
+    stack: Vec<&'x str>,
+    /// Fields selected by the fragments already checked in the operation
+    cache: BTreeMap<&'x str, Selected<'x>>,
+    violations: Vec<LimitViolation>,
+}
+
+/// Response names of the fields selected on each type
+type Selected<'x> = BTreeMap<&'x str, BTreeSet<&'x str>>;
+
+impl SelectionLimits {
+    /// Set maximum number of distinct fields selected on the type
+    ///
+    /// Fields selected via fragments are counted together with the fields
+    /// selected directly. Fields with the same response name are counted
+    /// once.
+    pub fn max_fields(&mut self, type_name: &str, limit: usize) -> &mut Self {
+        self.types.entry(type_name.into()).or_default().max_fields =
+            Some(limit);
+        self
+    }
+
+    /// Set maximum number of items of the type fetched by a single field
+    ///
+    /// The number is determined from the pagination arguments, see
+    /// `list_arguments`.
+    pub fn max_items(&mut self, type_name: &str, limit: u64) -> &mut Self {
+        self.types.entry(type_name.into()).or_default().max_items =
+            Some(limit);
+        self
+    }
+
+    /// Set the estimator of the number of items returned by the field
+    ///
+    /// By default `first`, `last` and `limit` arguments are used.
+    pub fn list_arguments(&mut self, estimator: ListArguments) -> &mut Self {
+        self.list_arguments = estimator;
+        self
+    }
+
+    /// Checks all the operations of the document
+    ///
+    /// Fields and types unknown to the schema are skipped, use validation
+    /// to report them. Each fragment is checked once per operation, so a
+    /// violation in a fragment spread multiple times is reported once.
+    pub fn check<'s, 'q, S, Q>(&self, schema: &schema::Document<'s, S>,
+        doc: &query::Document<'q, Q>)
+        -> Result<(), Vec<LimitViolation>>
+        where S: Text<'s>,
+              Q: Text<'q>,
+    {
+        let mut checker = Checker {
+            limits: self,
+            index: SchemaIndex::new(schema),
+            fragments: BTreeMap::new(),
+            stack: Vec::new(),
+            cache: BTreeMap::new(),
+            violations: Vec::new(),
+        };
+        for def in &doc.definitions {
+            if let Definition::Fragment(ref frag) = *def {
+                checker.fragments.entry(frag.name.as_ref()).or_insert(frag);
+            }
+        }
+        for def in &doc.definitions {
+            if let Definition::Operation(ref op) = *def {
+                checker.operation(op);
+            }
+        }
+        if checker.violations.is_empty() {
+            Ok(())
+        } else {
+            Err(checker.violations)
+        }
+    }
+}
+
+impl<'l, 'x, 's, 'q, S, Q> Checker<'l, 'x, 's, 'q, S, Q>
+    where S: Text<'s>,
+          Q: Text<'q>,
+{
+    fn operation(&mut self, op: &'x Operation<'q, Q>) {
+        let root = match op.kind {
+            OperationKind::ImplicitQuery | OperationKind::Query => {
+                self.index.query_type()
+            }
+            OperationKind::Mutation => self.index.mutation_type(),
+            OperationKind::Subscription => self.index.subscription_type(),
+        };
+        // multipliers depend on the variables of the operation
+        self.cache.clear();
+        if let Some(root) = root {
+            self.selection_set(op, root, &op.selection_set);
+        }
+    }
+
+    /// Checks field count of the selection set and all nested selections
+    fn selection_set(&mut self, op: &'x Operation<'q, Q>,
+        parent: &'x str, set: &'x SelectionSet<'q, Q>)
+    {
+        let mut selected = BTreeMap::new();
+        self.collect(op, parent, set, &mut selected);
+        for (type_name, keys) in selected {
+            let limit = self.limits.types.get(type_name)
+                .and_then(|t| t.max_fields);
+            if let Some(limit) = limit {
+                if keys.len() > limit {
+                    self.violations.push(LimitViolation {
+                        kind: LimitKind::Fields,
+                        type_name: type_name.into(),
+                        limit: limit as u64,
+                        actual: keys.len() as u64,
+                        position: set.span.0,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Collects response names of fields selected on each type
+    fn collect(&mut self, op: &'x Operation<'q, Q>, parent: &'x str,
+        set: &'x SelectionSet<'q, Q>, selected: &mut Selected<'x>)
+    {
+        for item in &set.items {
+            match *item {
+                Selection::Field(ref field) => {
+                    let key = field.alias.as_ref().unwrap_or(&field.name);
+                    selected.entry(parent).or_default().insert(key.as_ref());
+                    self.field(op, parent, field);
+                }
+                Selection::InlineFragment(ref frag) => {
+                    let cond = match frag.type_condition {
+                        Some(TypeCondition::On(ref cond)) => cond.as_ref(),
+                        None => parent,
+                    };
+                    self.collect(op, cond, &frag.selection_set, selected);
+                }
+                Selection::FragmentSpread(ref spread) => {
+                    let name = spread.fragment_name.as_ref();
+                    if let Some(fields) = self.fragment(op, name) {
+                        for (type_name, keys) in fields {
+                            selected.entry(type_name).or_default()
+                                .extend(keys);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn fragment(&mut self, op: &'x Operation<'q, Q>, name: &'x str)
+        -> Option<Selected<'x>>
+    {
+        if let Some(fields) = self.cache.get(name) {
+            return Some(fields.clone());
+        }
+        let frag = *self.fragments.get(name)?;
+        if self.stack.contains(&name) {
+            return None;
+        }
+        let TypeCondition::On(ref cond) = frag.type_condition;
+        let mut fields = Selected::new();
+        self.stack.push(name);
+        self.collect(op, cond.as_ref(), &frag.selection_set, &mut fields);
+        self.stack.pop();
+        self.cache.insert(name, fields.clone());
+        Some(fields)
+    }
+
+    fn field(&mut self, op: &'x Operation<'q, Q>, parent: &'x str,
+        field: &'x query::Field<'q, Q>)
+    {
+        let def = match self.index.type_by_name(parent)
+            .and_then(|t| t.field(field.name.as_ref()))
+        {
+            Some(def) => def,
+            None => return,
+        };
+        let field_type = type_name(&def.field_type);
+        let limit = self.limits.types.get(field_type)
+            .and_then(|t| t.max_items);
+        if let Some(limit) = limit {
+            let items = self.limits.list_arguments.multiplier(field, op);
+            if items > limit {
+                self.violations.push(LimitViolation {
+                    kind: LimitKind::Items,
+                    type_name: field_type.into(),
+                    limit,
+                    actual: items,
+                    position: field.position,
+                });
+            }
+        }
+        if !field.selection_set.items.is_empty() {
+            self.selection_set(op, field_type, &field.selection_set);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{parse_query, parse_schema};
+    use super::{SelectionLimits, LimitKind, LimitViolation};
+
+    const SCHEMA: &str = "
+        type Query { users(first: Int): [User] node: Node }
+        interface Node { id: ID }
+        type User implements Node {
+            id: ID name: String email: String friends(limit: Int): [User]
+        }
+    ";
+
+    fn check(limits: &SelectionLimits, query: &str) -> Vec<LimitViolation> {
+        let schema = parse_schema::<&str>(SCHEMA).unwrap();
+        let doc = parse_query::<&str>(query).unwrap();
+        limits.check(&schema, &doc).err().unwrap_or_default()
+    }
+
+    #[test]
+    fn fields() {
+        let mut limits = SelectionLimits::default();
+        limits.max_fields("User", 2);
+        assert_eq!(check(&limits, "{ users { id name name } }"), vec![]);
+        let errs = check(&limits, "{ users { id name x: email } }");
+        assert_eq!(errs.len(), 1);
+        assert_eq!(errs[0].kind, LimitKind::Fields);
+        assert_eq!(errs[0].type_name, "User");
+        assert_eq!((errs[0].limit, errs[0].actual), (2, 3));
+        assert_eq!(errs[0].position.to_string(), "1:9");
+        // fragments are merged into the selection
+        assert_eq!(check(&limits, "
+            { users { id ...F ... on User { email } } }
+            fragment F on User { name }
+        ").len(), 1);
+        // but fields of other types are not counted
+        assert_eq!(check(&limits,
+            "{ node { id ... on User { name email } } }"), vec![]);
+    }
+
+    #[test]
+    fn items() {
+        let mut limits = SelectionLimits::default();
+        limits.max_items("User", 10);
+        assert_eq!(check(&limits, "{ users(first: 10) { id } }"), vec![]);
+        let errs = check(&limits, "query Q($n: Int = 50) {
+            users(first: 5) { friends(limit: $n) { id } }
+        }");
+        assert_eq!(errs.len(), 1);
+        assert_eq!(errs[0].kind, LimitKind::Items);
+        assert_eq!(errs[0].actual, 50);
+        assert_eq!(errs[0].to_string(),
+            "List size of type \"User\" exceeds the limit: 50 > 10");
+    }
+
+    #[test]
+    fn fragments_checked_once() {
+        let mut limits = SelectionLimits::default();
+        limits.max_items("User", 10).max_fields("User", 1);
+        let mut query = String::from("{ users { ...F0 } }");
+        for i in 0..64 {
+            query.push_str(&format!(
+                " fragment F{} on User {{ friends(limit: 20) {{ ...F{} }} \
+                                         friends(limit: 20) {{ ...F{} }} }}",
+                i, i + 1, i + 1));
+        }
+        query.push_str(" fragment F64 on User { id name }");
+        let errs = check(&limits, &query);
+        assert_eq!(errs.iter().filter(|e| e.kind == LimitKind::Items).count(),
+                   128);
+        assert_eq!(errs.iter().filter(|e| e.kind == LimitKind::Fields).count(),
+                   2);
+    }
+}
@@ -0,0 +1,546 @@
+//! Static analysis of operations
+//!
+//! This module computes metrics that are usually used to limit the cost of
+//! queries accepted by a server: maximum depth of the selections, number of
+//! fields and aliases and an estimated complexity.
+//!
+//! ```rust
+//! # extern crate edb_graphql_parser;
+//! use edb_graphql_parser::parse_query;
+//! use edb_graphql_parser::analysis::analyze;
+//! use edb_graphql_parser::query::Definition;
+//!
+//! let doc = parse_query::<&str>("{ users(first: 10) { name } }").unwrap();
+//! let op = match doc.definitions[0] {
+//!     Definition::Operation(ref op) => op,
+//!     _ => unreachable!(),
+//! };
+//! let metrics = analyze(&doc, op);
+//! assert_eq!(metrics.depth, 2);
+//! assert_eq!(metrics.fields, 2);
+//! assert_eq!(metrics.complexity, 11);
+//! ```
+//!
+//! To find out which fields contribute to the complexity, use `explain`
+//! which returns the tree of per-field estimates. To enforce limits
+//! specific to certain types use `SelectionLimits`. Costs declared in the
+//! schema with `@cost` and `@listSize` directives are computed by
+//! `CostAnalysis`.
+#[cfg(feature = "schema")] mod cost;
+#[cfg(feature = "schema")] mod limits;
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::common::{Text, Value};
+use crate::position::Pos;
+use crate::query::{Definition, Document, Field, FragmentDefinition};
+use crate::query::{Operation, Selection, SelectionSet};
+
+#[cfg(feature = "schema")]
+pub use self::cost::CostAnalysis;
+#[cfg(feature = "schema")]
+pub use self::limits::{SelectionLimits, LimitKind, LimitViolation};
+
+
+/// Metrics of a single operation
+///
+/// Fragments are expanded at every place they are spread, so fields of
+/// the fragment spread twice are counted twice. Each fragment is analyzed
+/// only once though, so the time doesn't grow with the expanded size.
+/// Counts saturate rather than overflow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Metrics {
+    /// Maximum nesting of the fields, `{ a { b } }` has depth of 2
+    pub depth: usize,
+    /// Total number of fields, including `__typename`
+    pub fields: usize,
+    /// Number of fields that have an alias
+    pub aliases: usize,
+    /// Estimated complexity as computed by `Estimator`
+    pub complexity: u64,
+}
+
+/// Computes the complexity of the fields
+///
+/// Complexity of the field is `cost + multiplier * complexity of children`,
+/// where the complexity of the children is a sum of the complexities of
+/// all the nested fields.
+pub trait Estimator {
+    /// Returns the cost of the field itself
+    fn field_cost<'a, T>(&self, _field: &Field<'a, T>,
+        _operation: &Operation<'a, T>)
+        -> u64
+        where T: Text<'a>,
+    {
+        1
+    }
+    /// Returns the estimated number of items returned by the field
+    fn multiplier<'a, T>(&self, field: &Field<'a, T>,
+        operation: &Operation<'a, T>)
+        -> u64
+        where T: Text<'a>;
+    /// Describes where the multiplier comes from, used by `explain`
+    fn multiplier_source<'a, T>(&self, _field: &Field<'a, T>,
+        _operation: &Operation<'a, T>)
+        -> Option<String>
+        where T: Text<'a>,
+    {
+        None
+    }
+}
+
+/// Estimated complexity of a single field, as returned by `explain`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldEstimate {
+    /// Response name of the field, i.e. alias if there is one
+    pub name: String,
+    pub position: Pos,
+    /// Cost of the field itself
+    pub cost: u64,
+    pub multiplier: u64,
+    /// Description of the multiplier, e.g. an argument it's taken from
+    pub multiplier_source: Option<String>,
+    /// Fields selected in this one, including ones from fragments
+    pub children: Vec<FieldEstimate>,
+    /// Complexity of the field including the children
+    pub total: u64,
+}
+
+/// The tree of complexity estimates of an operation
+///
+/// `Display` implementation prints it as an indented tree:
+///
+/// ```text
+/// complexity: 61
+/// users: 61 = 1 + 10 * 6 (first: 10)
+///   friends: 6 = 1 + 5 * 1 (last: $n = 5)
+///     id: 1
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Explanation {
+    /// Top level fields of the operation
+    pub fields: Vec<FieldEstimate>,
+    /// Complexity of the operation, same as `Metrics::complexity`
+    pub total: u64,
+}
+
+/// Estimates list sizes from the pagination arguments
+///
+/// By default `first`, `last` and `limit` arguments are considered.
+/// Arguments can be either literals or variables with default values.
+/// When the value of the variable is not known, `default_list_size` is
+/// used.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListArguments {
+    arguments: Vec<String>,
+    default_list_size: u64,
+}
+
+struct Analyzer<'x, 'a, T, E>
+    where T: Text<'a>
+{
+    fragments: BTreeMap<&'x str, &'x FragmentDefinition<'a, T>>,
+    operation: &'x Operation<'a, T>,
+    estimator: &'x E,
+    stack: Vec<&'x str>,
+    /// Estimates of the fragments already analyzed
+    cache: BTreeMap<&'x str, Estimate>,
+    explain: bool,
+}
+
+/// Estimated result of the selection set
+#[derive(Clone, Default)]
+struct Estimate {
+    depth: usize,
+    complexity: u64,
+    /// Number of the fields and of the aliases
+    fields: usize,
+    aliases: usize,
+    /// Only filled when explaining
+    estimates: Vec<FieldEstimate>,
+}
+
+impl Default for ListArguments {
+    fn default() -> ListArguments {
+        ListArguments {
+            arguments: vec!["first".into(), "last".into(), "limit".into()],
+            default_list_size: 1,
+        }
+    }
+}
+
+impl ListArguments {
+    /// Set the names of arguments that limit the list size
+    pub fn arguments<I, S>(&mut self, names: I) -> &mut Self
+        where I: IntoIterator<Item=S>,
+              S: Into<String>,
+    {
+        self.arguments = names.into_iter().map(Into::into).collect();
+        self
+    }
+    /// Set the size of the list used when argument is a variable without
+    /// default value
+    pub fn default_list_size(&mut self, size: u64) -> &mut Self {
+        self.default_list_size = size;
+        self
+    }
+
+    /// Returns the list size and the argument it's taken from
+    fn list_size<'a, T>(&self, field: &Field<'a, T>,
+        operation: &Operation<'a, T>)
+        -> Option<(u64, String)>
+        where T: Text<'a>,
+    {
+        let mut result: Option<(u64, String)> = None;
+        for (name, value) in &field.arguments {
+            if !self.arguments.iter().any(|a| a == name.as_ref()) {
+                continue;
+            }
+            let size = match *value {
+                Value::Int(ref num) => num.as_i64().map(|n| {
+                    (n.max(0) as u64, format!("{}: {}", name.as_ref(), n))
+                }),
+                Value::Variable(ref var) => {
+                    operation.variable_definitions.iter()
+                    .find(|v| v.name.as_ref() == var.as_ref())
+                    .and_then(|v| v.default_value.as_ref())
+                    .and_then(|d| match d.value {
+                        Value::Int(ref num) => num.as_i64(),
+                        _ => None,
+                    })
+                    .map(|n| (n.max(0) as u64, format!("{}: ${} = {}",
+                        name.as_ref(), var.as_ref(), n)))
+                    .or_else(|| Some((self.default_list_size, format!(
+                        "{}: ${}, default list size",
+                        name.as_ref(), var.as_ref()))))
+                }
+                _ => None,
+            };
+            if let Some(size) = size {
+                if result.as_ref().map(|r| size.0 > r.0).unwrap_or(true) {
+                    result = Some(size);
+                }
+            }
+        }
+        result
+    }
+}
+
+impl Estimator for ListArguments {
+    fn multiplier<'a, T>(&self, field: &Field<'a, T>,
+        operation: &Operation<'a, T>)
+        -> u64
+        where T: Text<'a>,
+    {
+        self.list_size(field, operation).map_or(1, |(size, _)| size)
+    }
+
+    fn multiplier_source<'a, T>(&self, field: &Field<'a, T>,
+        operation: &Operation<'a, T>)
+        -> Option<String>
+        where T: Text<'a>,
+    {
+        self.list_size(field, operation).map(|(_, source)| source)
+    }
+}
+
+impl<'x, 'a, T, E> Analyzer<'x, 'a, T, E>
+    where T: Text<'a>,
+          E: Estimator,
+{
+    fn selection_set(&mut self, set: &'x SelectionSet<'a, T>) -> Estimate {
+        let mut result = Estimate::default();
+        for item in &set.items {
+            let est = match *item {
+                Selection::Field(ref field) => self.field(field),
+                Selection::InlineFragment(ref frag) => {
+                    self.selection_set(&frag.selection_set)
+                }
+                Selection::FragmentSpread(ref spread) => {
+                    match self.fragment(spread.fragment_name.as_ref()) {
+                        Some(est) => est,
+                        None => continue,
+                    }
+                }
+            };
+            result.depth = result.depth.max(est.depth);
+            result.complexity = result.complexity.saturating_add(est.complexity);
+            result.fields = result.fields.saturating_add(est.fields);
+            result.aliases = result.aliases.saturating_add(est.aliases);
+            result.estimates.extend(est.estimates);
+        }
+        result
+    }
+
+    fn fragment(&mut self, name: &'x str) -> Option<Estimate> {
+        if let Some(est) = self.cache.get(name) {
+            return Some(est.clone());
+        }
+        // cycles are reported by validation, here we just don't recurse
+        // infinitely
+        if self.stack.contains(&name) {
+            return None;
+        }
+        let frag = *self.fragments.get(name)?;
+        self.stack.push(name);
+        let est = self.selection_set(&frag.selection_set);
+        self.stack.pop();
+        self.cache.insert(name, est.clone());
+        Some(est)
+    }
+
+    fn field(&mut self, field: &'x Field<'a, T>) -> Estimate {
+        let children = self.selection_set(&field.selection_set);
+        let cost = self.estimator.field_cost(field, self.operation);
+        let multiplier = self.estimator.multiplier(field, self.operation);
+        let complexity = cost.saturating_add(
+            multiplier.saturating_mul(children.complexity));
+        let mut estimates = Vec::new();
+        if self.explain {
+            estimates.push(FieldEstimate {
+                name: field.alias.as_ref().unwrap_or(&field.name)
+                    .as_ref().to_string(),
+                position: field.position,
+                cost,
+                multiplier,
+                multiplier_source: self.estimator
+                    .multiplier_source(field, self.operation),
+                children: children.estimates,
+                total: complexity,
+            });
+        }
+        Estimate {
+            depth: children.depth + 1,
+            complexity,
+            fields: children.fields.saturating_add(1),
+            aliases: children.aliases
+                .saturating_add(field.alias.is_some() as usize),
+            estimates,
+        }
+    }
+}
+
+/// Computes metrics of the operation using default estimator
+///
+/// The `doc` is used for looking up fragments spread in the operation.
+pub fn analyze<'a, T>(doc: &Document<'a, T>, operation: &Operation<'a, T>)
+    -> Metrics
+    where T: Text<'a>,
+{
+    analyze_with(doc, operation, &ListArguments::default())
+}
+
+/// Computes metrics of the operation using custom estimator
+pub fn analyze_with<'a, T, E>(doc: &Document<'a, T>,
+    operation: &Operation<'a, T>, estimator: &E)
+    -> Metrics
+    where T: Text<'a>,
+          E: Estimator,
+{
+    run(doc, operation, estimator, false).0
+}
+
+/// Returns per-field complexity estimates using default estimator
+///
+/// The tree has a node for every field of the operation with fragments
+/// expanded, so check the `Metrics` of untrusted operations before
+/// explaining them.
+pub fn explain<'a, T>(doc: &Document<'a, T>, operation: &Operation<'a, T>)
+    -> Explanation
+    where T: Text<'a>,
+{
+    explain_with(doc, operation, &ListArguments::default())
+}
+
+/// Returns per-field complexity estimates using custom estimator
+pub fn explain_with<'a, T, E>(doc: &Document<'a, T>,
+    operation: &Operation<'a, T>, estimator: &E)
+    -> Explanation
+    where T: Text<'a>,
+          E: Estimator,
+{
+    let (metrics, fields) = run(doc, operation, estimator, true);
+    Explanation { fields, total: metrics.complexity }
+}
+
+fn run<'a, T, E>(doc: &Document<'a, T>, operation: &Operation<'a, T>,
+    estimator: &E, explain: bool)
+    -> (Metrics, Vec<FieldEstimate>)
+    where T: Text<'a>,
+          E: Estimator,
+{
+    let mut fragments = BTreeMap::new();
+    for def in &doc.definitions {
+        if let Definition::Fragment(ref frag) = *def {
+            fragments.entry(frag.name.as_ref()).or_insert(frag);
+        }
+    }
+    let mut analyzer = Analyzer {
+        fragments,
+        operation,
+        estimator,
+        stack: Vec::new(),
+        cache: BTreeMap::new(),
+        explain,
+    };
+    let est = analyzer.selection_set(&operation.selection_set);
+    let metrics = Metrics {
+        depth: est.depth,
+        fields: est.fields,
+        aliases: est.aliases,
+        complexity: est.complexity,
+    };
+    (metrics, est.estimates)
+}
+
+impl FieldEstimate {
+    fn write(&self, f: &mut fmt::Formatter, level: usize) -> fmt::Result {
+        write!(f, "{:width$}{}: {}", "", self.name, self.total,
+               width = level * 2)?;
+        if !self.children.is_empty() {
+            let children = self.children.iter()
+                .fold(0u64, |sum, c| sum.saturating_add(c.total));
+            write!(f, " = {} + {} * {}", self.cost, self.multiplier, children)?;
+        }
+        if let Some(ref source) = self.multiplier_source {
+            write!(f, " ({})", source)?;
+        }
+        writeln!(f)?;
+        for child in &self.children {
+            child.write(f, level + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "complexity: {}", self.total)?;
+        for field in &self.fields {
+            field.write(f, 0)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse_query;
+    use crate::query::{Definition, Field, Operation};
+    use crate::common::Text;
+    use super::{analyze, analyze_with, explain, Estimator, ListArguments};
+    use super::Metrics;
+
+    fn metrics(s: &str) -> Metrics {
+        let doc = parse_query::<&str>(s).unwrap();
+        let op = doc.definitions.iter().find_map(|d| match *d {
+            Definition::Operation(ref op) => Some(op),
+            _ => None,
+        }).unwrap();
+        analyze(&doc, op)
+    }
+
+    #[test]
+    fn simple() {
+        assert_eq!(metrics("{ a b { c d: e } }"), Metrics {
+            depth: 2,
+            fields: 4,
+            aliases: 1,
+            complexity: 4,
+        });
+    }
+
+    #[test]
+    fn fragments() {
+        let m = metrics("
+            { a { ...F ... on T { x { y } } } b { ...F } }
+            fragment F on T { f { g } }
+        ");
+        assert_eq!(m.depth, 3);
+        assert_eq!(m.fields, 8);
+        assert_eq!(m.complexity, 8);
+        // cycle doesn't hang
+        assert_eq!(metrics("{ ...A } fragment A on T { a ...A }").fields, 1);
+    }
+
+    #[test]
+    fn exponential_fragments() {
+        let mut query = String::from("{ ...F0 }");
+        for i in 0..64 {
+            query.push_str(&format!(
+                " fragment F{} on T {{ a {{ ...F{} ...F{} }} }}",
+                i, i + 1, i + 1));
+        }
+        query.push_str(" fragment F64 on T { b }");
+        let m = metrics(&query);
+        assert_eq!(m.depth, 65);
+        assert_eq!(m.fields, usize::MAX);
+        assert_eq!(m.complexity, u64::MAX);
+    }
+
+    #[test]
+    fn list_multiplier() {
+        assert_eq!(metrics("{ users(first: 10) { friends(last: 5) { id } } }")
+            .complexity, 1 + 10 * (1 + 5));
+        assert_eq!(metrics("query Q($n: Int = 3) { users(limit: $n) { id } }")
+            .complexity, 4);
+        assert_eq!(metrics("query Q($n: Int) { users(limit: $n) { id } }")
+            .complexity, 2);
+    }
+
+    #[test]
+    fn custom_estimator() {
+        struct Flat;
+        impl Estimator for Flat {
+            fn field_cost<'a, T>(&self, _: &Field<'a, T>, _: &Operation<'a, T>)
+                -> u64
+                where T: Text<'a>,
+            {
+                2
+            }
+            fn multiplier<'a, T>(&self, _: &Field<'a, T>, _: &Operation<'a, T>)
+                -> u64
+                where T: Text<'a>,
+            {
+                1
+            }
+        }
+        let doc = parse_query::<&str>("{ a(first: 100) { b } }").unwrap();
+        let op = match doc.definitions[0] {
+            Definition::Operation(ref op) => op,
+            _ => unreachable!(),
+        };
+        assert_eq!(analyze_with(&doc, op, &Flat).complexity, 4);
+        let mut args = ListArguments::default();
+        args.arguments(vec!["count"]);
+        assert_eq!(analyze_with(&doc, op, &args).complexity, 2);
+    }
+
+    #[test]
+    fn explanation() {
+        let doc = parse_query::<&str>("
+            query Q($n: Int = 5) {
+                users(first: 10) { friends(last: $n) { id } ...F }
+                me { id }
+            }
+            fragment F on User { name }
+        ").unwrap();
+        let op = match doc.definitions[0] {
+            Definition::Operation(ref op) => op,
+            _ => unreachable!(),
+        };
+        let explanation = explain(&doc, op);
+        assert_eq!(explanation.total, analyze(&doc, op).complexity);
+        assert_eq!(explanation.fields[0].children[1].name, "name");
+        assert_eq!(explanation.fields[0].position.to_string(), "3:17");
+        assert_eq!(explanation.to_string(), "\
+            complexity: 73\n\
+            users: 71 = 1 + 10 * 7 (first: 10)\n\
+            \x20 friends: 6 = 1 + 5 * 1 (last: $n = 5)\n\
+            \x20   id: 1\n\
+            \x20 name: 1\n\
+            me: 2 = 1 + 1 * 1\n\
+            \x20 id: 1\n");
+    }
+}
@@ -0,0 +1,780 @@
+//! Implementations of `arbitrary::Arbitrary` for the AST
+//!
+//! Generated trees are always grammatically valid: printing and parsing
+//! them produces the same tree (except positions), so they can be used for
+//! fuzzing transforms and the printer:
+//!
+//! ```rust
+//! # extern crate edb_graphql_parser;
+//! # extern crate arbitrary;
+//! use arbitrary::{Arbitrary, Unstructured};
+//! use edb_graphql_parser::query::Document;
+//! use edb_graphql_parser::roundtrip::assert_roundtrip;
+//!
+//! let mut data = Unstructured::new(b"fuzzer provided bytes");
+//! let doc = Document::<String>::arbitrary(&mut data).unwrap();
+//! assert_roundtrip(&doc).unwrap();
+//! ```
+//!
+//! Names are picked from a small set, so that generated documents refer to
+//! the same fields, types and variables often. Experimental syntax
+//! (fragment arguments and client controlled nullability) is never
+//! generated. Nothing but the grammar is respected, so the documents
+//! usually fail validation.
+use ::arbitrary::{Arbitrary, Result, Unstructured};
+use num_bigint::BigInt;
+
+use crate::common::{Directive, DirectiveArgument, Number, Text, Type, Value};
+use crate::position::Pos;
+use crate::query::{self, InsertVars, InsertVarsKind, OperationKind};
+use crate::query::{SelectionSet, Selection, TypeCondition, DefaultValue};
+use crate::query::{FragmentSpread, InlineFragment, VariableDefinition};
+use crate::schema::{self, Description, DirectiveLocation, EnumValue};
+use crate::schema::{InputValue, TypeDefinition, TypeExtension};
+use crate::schema::{ScalarType, ObjectType, InterfaceType, UnionType};
+use crate::schema::{EnumType, InputObjectType, ScalarTypeExtension};
+use crate::schema::{ObjectTypeExtension, InterfaceTypeExtension};
+use crate::schema::{UnionTypeExtension, EnumTypeExtension};
+use crate::schema::{InputObjectTypeExtension, DirectiveDefinition};
+use crate::schema::{SchemaDefinition, SchemaExtension};
+
+
+/// Nesting of selection sets, values and types
+const MAX_DEPTH: usize = 4;
+/// Maximum length of lists of nodes
+const MAX_LEN: usize = 3;
+/// Valid names for every kind of node, except `on` for fragments
+const NAMES: &[&str] = &[
+    "a", "b", "id", "name", "user", "on", "query", "type", "fragment",
+    "Query", "User", "_x", "__typename", "value2",
+];
+
+fn list<'u, X>(u: &mut Unstructured<'u>, min: usize,
+    mut item: impl FnMut(&mut Unstructured<'u>) -> Result<X>)
+    -> Result<Vec<X>>
+{
+    let len = u.int_in_range(min..=MAX_LEN)?;
+    (0..len).map(|_| item(u)).collect()
+}
+
+fn option<'u, X>(u: &mut Unstructured<'u>,
+    item: impl FnOnce(&mut Unstructured<'u>) -> Result<X>)
+    -> Result<Option<X>>
+{
+    if u.arbitrary()? {
+        item(u).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+fn name<'a, T>(u: &mut Unstructured) -> Result<T::Value>
+    where T: Text<'a>,
+{
+    Ok((*u.choose(NAMES)?).into())
+}
+
+fn names<'a, T>(u: &mut Unstructured, min: usize) -> Result<Vec<T::Value>>
+    where T: Text<'a>,
+{
+    list(u, min, name::<T>)
+}
+
+fn float(u: &mut Unstructured) -> Result<String> {
+    let mut value = format!("{}.{}", u.arbitrary::<i32>()?,
+                            u.arbitrary::<u16>()?);
+    if u.arbitrary()? {
+        value.push_str(&format!("e{:+}", u.int_in_range(-20..=20)?));
+    }
+    Ok(value)
+}
+
+/// Literal value, variables are only generated if `constant` is false
+fn value<'a, T>(u: &mut Unstructured, depth: usize, constant: bool)
+    -> Result<Value<'a, T>>
+    where T: Text<'a>,
+{
+    let kinds = if depth == 0 { 7 } else { 9 };
+    Ok(match u.choose_index(kinds)? {
+        0 if !constant => Value::Variable(name::<T>(u)?),
+        0 | 1 => Value::Int(Number(BigInt::from(u.arbitrary::<i64>()?))),
+        2 => Value::Float(float(u)?),
+        3 => Value::String(u.arbitrary::<String>()?.into()),
+        4 => Value::Boolean(u.arbitrary()?),
+        5 => Value::Null,
+        6 => Value::Enum(name::<T>(u)?),
+        7 => Value::List(list(u, 0, |u| value(u, depth - 1, constant))?),
+        _ => Value::Object(list(u, 0, |u| {
+            Ok((name::<T>(u)?, value(u, depth - 1, constant)?))
+        })?),
+    })
+}
+
+fn typ<'a, T>(u: &mut Unstructured, depth: usize) -> Result<Type<'a, T>>
+    where T: Text<'a>,
+{
+    let inner = if depth > 0 && u.ratio(1, 3)? {
+        Type::ListType(Box::new(typ(u, depth - 1)?))
+    } else {
+        Type::NamedType(name::<T>(u)?)
+    };
+    if u.arbitrary()? {
+        Ok(Type::NonNullType(Box::new(inner)))
+    } else {
+        Ok(inner)
+    }
+}
+
+fn directive<'a, T>(u: &mut Unstructured, depth: usize, constant: bool)
+    -> Result<Directive<'a, T>>
+    where T: Text<'a>,
+{
+    Ok(Directive {
+        position: Pos::default(),
+        name: name::<T>(u)?,
+        arguments: list(u, 0, |u| Ok(DirectiveArgument {
+            name: name::<T>(u)?,
+            value: value(u, depth, constant)?,
+            value_position: Pos::default(),
+        }))?,
+        arguments_syntax: None,
+    })
+}
+
+fn directives<'a, T>(u: &mut Unstructured, constant: bool)
+    -> Result<Vec<Directive<'a, T>>>
+    where T: Text<'a>,
+{
+    list(u, 0, |u| directive(u, 1, constant))
+}
+
+fn arguments<'a, T>(u: &mut Unstructured, depth: usize)
+    -> Result<Vec<(T::Value, Value<'a, T>)>>
+    where T: Text<'a>,
+{
+    list(u, 0, |u| Ok((name::<T>(u)?, value(u, depth, false)?)))
+}
+
+fn selection_set<'a, T>(u: &mut Unstructured, depth: usize)
+    -> Result<SelectionSet<'a, T>>
+    where T: Text<'a>,
+{
+    Ok(SelectionSet {
+        span: (Pos::default(), Pos::default()),
+        items: list(u, 1, |u| selection(u, depth))?,
+    })
+}
+
+fn selection<'a, T>(u: &mut Unstructured, depth: usize)
+    -> Result<Selection<'a, T>>
+    where T: Text<'a>,
+{
+    let kinds = if depth == 0 { 2 } else { 3 };
+    Ok(match u.choose_index(kinds)? {
+        0 => Selection::Field(field(u, depth)?),
+        1 => Selection::FragmentSpread(fragment_spread(u, depth)?),
+        _ => Selection::InlineFragment(inline_fragment(u, depth)?),
+    })
+}
+
+fn field<'a, T>(u: &mut Unstructured, depth: usize)
+    -> Result<query::Field<'a, T>>
+    where T: Text<'a>,
+{
+    Ok(query::Field {
+        position: Pos::default(),
+        alias: option(u, name::<T>)?,
+        name: name::<T>(u)?,
+        arguments: arguments(u, depth)?,
+        arguments_syntax: None,
+        nullability: None,
+        directives: directives(u, false)?,
+        selection_set: if depth > 0 && u.arbitrary()? {
+            selection_set(u, depth - 1)?
+        } else {
+            SelectionSet {
+                span: (Pos::default(), Pos::default()),
+                items: Vec::new(),
+            }
+        },
+    })
+}
+
+fn fragment_name<'a, T>(u: &mut Unstructured) -> Result<T::Value>
+    where T: Text<'a>,
+{
+    match *u.choose(NAMES)? {
+        "on" => Ok("On".into()),
+        name => Ok(name.into()),
+    }
+}
+
+fn fragment_spread<'a, T>(u: &mut Unstructured, _depth: usize)
+    -> Result<FragmentSpread<'a, T>>
+    where T: Text<'a>,
+{
+    Ok(FragmentSpread {
+        position: Pos::default(),
+        fragment_name: fragment_name::<T>(u)?,
+        arguments: Vec::new(),
+        arguments_syntax: None,
+        directives: directives(u, false)?,
+    })
+}
+
+fn type_condition<'a, T>(u: &mut Unstructured, _depth: usize)
+    -> Result<TypeCondition<'a, T>>
+    where T: Text<'a>,
+{
+    Ok(TypeCondition::On(name::<T>(u)?))
+}
+
+fn inline_fragment<'a, T>(u: &mut Unstructured, depth: usize)
+    -> Result<InlineFragment<'a, T>>
+    where T: Text<'a>,
+{
+    Ok(InlineFragment {
+        position: Pos::default(),
+        on_position: None,
+        type_condition: option(u, |u| type_condition(u, depth))?,
+        directives: directives(u, false)?,
+        selection_set: selection_set(u, depth.saturating_sub(1))?,
+    })
+}
+
+fn variable_definition<'a, T>(u: &mut Unstructured, depth: usize)
+    -> Result<VariableDefinition<'a, T>>
+    where T: Text<'a>,
+{
+    Ok(VariableDefinition {
+        position: Pos::default(),
+        name: name::<T>(u)?,
+        var_type: typ(u, depth)?,
+        default_value: option(u, |u| Ok(DefaultValue {
+            span: (Pos::default(), Pos::default()),
+            value: value(u, depth, true)?,
+        }))?,
+        directives: directives(u, true)?,
+    })
+}
+
+fn operation_kind(u: &mut Unstructured) -> Result<OperationKind> {
+    Ok(match u.choose_index(4)? {
+        0 => OperationKind::ImplicitQuery,
+        1 => OperationKind::Query,
+        2 => OperationKind::Mutation,
+        _ => OperationKind::Subscription,
+    })
+}
+
+fn operation<'a, T>(u: &mut Unstructured, depth: usize)
+    -> Result<query::Operation<'a, T>>
+    where T: Text<'a>,
+{
+    let kind = operation_kind(u)?;
+    if kind == OperationKind::ImplicitQuery {
+        return Ok(query::Operation {
+            kind,
+            position: Pos::default(),
+            name: None,
+            variable_definitions: Vec::new(),
+            insert_variables: InsertVars {
+                kind: InsertVarsKind::Query,
+                position: Pos::default(),
+            },
+            directives: Vec::new(),
+            selection_set: selection_set(u, depth)?,
+        });
+    }
+    let name = option(u, name::<T>)?;
+    let variable_definitions = list(u, 0, |u| variable_definition(u, 2))?;
+    Ok(query::Operation {
+        kind,
+        position: Pos::default(),
+        name,
+        insert_variables: InsertVars {
+            kind: if variable_definitions.is_empty() {
+                InsertVarsKind::Parens
+            } else {
+                InsertVarsKind::Normal
+            },
+            position: Pos::default(),
+        },
+        variable_definitions,
+        directives: directives(u, false)?,
+        selection_set: selection_set(u, depth)?,
+    })
+}
+
+fn fragment_definition<'a, T>(u: &mut Unstructured, depth: usize)
+    -> Result<query::FragmentDefinition<'a, T>>
+    where T: Text<'a>,
+{
+    Ok(query::FragmentDefinition {
+        position: Pos::default(),
+        name: fragment_name::<T>(u)?,
+        variable_definitions: Vec::new(),
+        on_position: Pos::default(),
+        type_condition: type_condition(u, depth)?,
+        directives: directives(u, false)?,
+        selection_set: selection_set(u, depth)?,
+    })
+}
+
+fn query_definition<'a, T>(u: &mut Unstructured, depth: usize)
+    -> Result<query::Definition<'a, T>>
+    where T: Text<'a>,
+{
+    if u.arbitrary()? {
+        Ok(query::Definition::Operation(operation(u, depth)?))
+    } else {
+        Ok(query::Definition::Fragment(fragment_definition(u, depth)?))
+    }
+}
+
+fn query_document<'a, T>(u: &mut Unstructured, depth: usize)
+    -> Result<query::Document<'a, T>>
+    where T: Text<'a>,
+{
+    Ok(query::Document {
+        definitions: list(u, 1, |u| query_definition(u, depth))?,
+    })
+}
+
+fn description(u: &mut Unstructured) -> Result<Option<Description>> {
+    if u.ratio(1, 4)? {
+        Ok(Some(u.arbitrary::<String>()?.into()))
+    } else {
+        Ok(None)
+    }
+}
+
+fn input_value<'a, T>(u: &mut Unstructured, depth: usize)
+    -> Result<InputValue<'a, T>>
+    where T: Text<'a>,
+{
+    Ok(InputValue {
+        position: Pos::default(),
+        description: description(u)?,
+        name: name::<T>(u)?,
+        value_type: typ(u, depth)?,
+        default_value: option(u, |u| value(u, depth, true))?,
+        directives: directives(u, true)?,
+    })
+}
+
+fn schema_field<'a, T>(u: &mut Unstructured, depth: usize)
+    -> Result<schema::Field<'a, T>>
+    where T: Text<'a>,
+{
+    Ok(schema::Field {
+        position: Pos::default(),
+        description: description(u)?,
+        name: name::<T>(u)?,
+        arguments: list(u, 0, |u| input_value(u, depth))?,
+        field_type: typ(u, depth)?,
+        directives: directives(u, true)?,
+    })
+}
+
+fn enum_value<'a, T>(u: &mut Unstructured, _depth: usize)
+    -> Result<EnumValue<'a, T>>
+    where T: Text<'a>,
+{
+    Ok(EnumValue {
+        position: Pos::default(),
+        description: description(u)?,
+        name: name::<T>(u)?,
+        directives: directives(u, true)?,
+    })
+}
+
+fn scalar_type<'a, T>(u: &mut Unstructured, _depth: usize)
+    -> Result<ScalarType<'a, T>>
+    where T: Text<'a>,
+{
+    Ok(ScalarType {
+        position: Pos::default(),
+        description: description(u)?,
+        name: name::<T>(u)?,
+        directives: directives(u, true)?,
+    })
+}
+
+fn object_type<'a, T>(u: &mut Unstructured, depth: usize)
+    -> Result<ObjectType<'a, T>>
+    where T: Text<'a>,
+{
+    Ok(ObjectType {
+        position: Pos::default(),
+        description: description(u)?,
+        name: name::<T>(u)?,
+        implements_interfaces: names::<T>(u, 0)?,
+        directives: directives(u, true)?,
+        fields: list(u, 0, |u| schema_field(u, depth))?,
+    })
+}
+
+fn interface_type<'a, T>(u: &mut Unstructured, depth: usize)
+    -> Result<InterfaceType<'a, T>>
+    where T: Text<'a>,
+{
+    Ok(InterfaceType {
+        position: Pos::default(),
+        description: description(u)?,
+        name: name::<T>(u)?,
+        implements_interfaces: names::<T>(u, 0)?,
+        directives: directives(u, true)?,
+        fields: list(u, 0, |u| schema_field(u, depth))?,
+    })
+}
+
+fn union_type<'a, T>(u: &mut Unstructured, _depth: usize)
+    -> Result<UnionType<'a, T>>
+    where T: Text<'a>,
+{
+    Ok(UnionType {
+        position: Pos::default(),
+        description: description(u)?,
+        name: name::<T>(u)?,
+        directives: directives(u, true)?,
+        types: names::<T>(u, 0)?,
+    })
+}
+
+fn enum_type<'a, T>(u: &mut Unstructured, depth: usize)
+    -> Result<EnumType<'a, T>>
+    where T: Text<'a>,
+{
+    Ok(EnumType {
+        position: Pos::default(),
+        description: description(u)?,
+        name: name::<T>(u)?,
+        directives: directives(u, true)?,
+        values: list(u, 0, |u| enum_value(u, depth))?,
+    })
+}
+
+fn input_object_type<'a, T>(u: &mut Unstructured, depth: usize)
+    -> Result<InputObjectType<'a, T>>
+    where T: Text<'a>,
+{
+    Ok(InputObjectType {
+        position: Pos::default(),
+        description: description(u)?,
+        name: name::<T>(u)?,
+        directives: directives(u, true)?,
+        fields: list(u, 0, |u| input_value(u, depth))?,
+    })
+}
+
+fn type_definition<'a, T>(u: &mut Unstructured, depth: usize)
+    -> Result<TypeDefinition<'a, T>>
+    where T: Text<'a>,
+{
+    Ok(match u.choose_index(6)? {
+        0 => TypeDefinition::Scalar(scalar_type(u, depth)?),
+        1 => TypeDefinition::Object(object_type(u, depth)?),
+        2 => TypeDefinition::Interface(interface_type(u, depth)?),
+        3 => TypeDefinition::Union(union_type(u, depth)?),
+        4 => TypeDefinition::Enum(enum_type(u, depth)?),
+        _ => TypeDefinition::InputObject(input_object_type(u, depth)?),
+    })
+}
+
+/// Directives of an extension, at least one if the extension is empty
+/// otherwise
+fn extension_directives<'a, T>(u: &mut Unstructured, empty: bool)
+    -> Result<Vec<Directive<'a, T>>>
+    where T: Text<'a>,
+{
+    let mut result = directives(u, true)?;
+    if empty && result.is_empty() {
+        result.push(directive(u, 1, true)?);
+    }
+    Ok(result)
+}
+
+fn scalar_type_extension<'a, T>(u: &mut Unstructured, _depth: usize)
+    -> Result<ScalarTypeExtension<'a, T>>
+    where T: Text<'a>,
+{
+    Ok(ScalarTypeExtension {
+        position: Pos::default(),
+        name: name::<T>(u)?,
+        directives: extension_directives(u, true)?,
+    })
+}
+
+fn object_type_extension<'a, T>(u: &mut Unstructured, depth: usize)
+    -> Result<ObjectTypeExtension<'a, T>>
+    where T: Text<'a>,
+{
+    let name = name::<T>(u)?;
+    let implements_interfaces = names::<T>(u, 0)?;
+    let fields = list(u, 0, |u| schema_field(u, depth))?;
+    let empty = implements_interfaces.is_empty() && fields.is_empty();
+    Ok(ObjectTypeExtension {
+        position: Pos::default(),
+        name, implements_interfaces, fields,
+        directives: extension_directives(u, empty)?,
+    })
+}
+
+fn interface_type_extension<'a, T>(u: &mut Unstructured, depth: usize)
+    -> Result<InterfaceTypeExtension<'a, T>>
+    where T: Text<'a>,
+{
+    let name = name::<T>(u)?;
+    let implements_interfaces = names::<T>(u, 0)?;
+    let fields = list(u, 0, |u| schema_field(u, depth))?;
+    let empty = implements_interfaces.is_empty() && fields.is_empty();
+    Ok(InterfaceTypeExtension {
+        position: Pos::default(),
+        name, implements_interfaces, fields,
+        directives: extension_directives(u, empty)?,
+    })
+}
+
+fn union_type_extension<'a, T>(u: &mut Unstructured, _depth: usize)
+    -> Result<UnionTypeExtension<'a, T>>
+    where T: Text<'a>,
+{
+    let name = name::<T>(u)?;
+    let types = names::<T>(u, 0)?;
+    Ok(UnionTypeExtension {
+        position: Pos::default(),
+        directives: extension_directives(u, types.is_empty())?,
+        name, types,
+    })
+}
+
+fn enum_type_extension<'a, T>(u: &mut Unstructured, depth: usize)
+    -> Result<EnumTypeExtension<'a, T>>
+    where T: Text<'a>,
+{
+    let name = name::<T>(u)?;
+    let values = list(u, 0, |u| enum_value(u, depth))?;
+    Ok(EnumTypeExtension {
+        position: Pos::default(),
+        directives: extension_directives(u, values.is_empty())?,
+        name, values,
+    })
+}
+
+fn input_object_type_extension<'a, T>(u: &mut Unstructured, depth: usize)
+    -> Result<InputObjectTypeExtension<'a, T>>
+    where T: Text<'a>,
+{
+    let name = name::<T>(u)?;
+    let fields = list(u, 0, |u| input_value(u, depth))?;
+    Ok(InputObjectTypeExtension {
+        position: Pos::default(),
+        directives: extension_directives(u, fields.is_empty())?,
+        name, fields,
+    })
+}
+
+fn type_extension<'a, T>(u: &mut Unstructured, depth: usize)
+    -> Result<TypeExtension<'a, T>>
+    where T: Text<'a>,
+{
+    Ok(match u.choose_index(6)? {
+        0 => TypeExtension::Scalar(scalar_type_extension(u, depth)?),
+        1 => TypeExtension::Object(object_type_extension(u, depth)?),
+        2 => TypeExtension::Interface(interface_type_extension(u, depth)?),
+        3 => TypeExtension::Union(union_type_extension(u, depth)?),
+        4 => TypeExtension::Enum(enum_type_extension(u, depth)?),
+        _ => TypeExtension::InputObject(
+            input_object_type_extension(u, depth)?),
+    })
+}
+
+fn directive_definition<'a, T>(u: &mut Unstructured, depth: usize)
+    -> Result<DirectiveDefinition<'a, T>>
+    where T: Text<'a>,
+{
+    Ok(DirectiveDefinition {
+        position: Pos::default(),
+        description: description(u)?,
+        name: name::<T>(u)?,
+        arguments: list(u, 0, |u| input_value(u, depth))?,
+        repeatable: u.arbitrary()?,
+        locations: list(u, 1, |u| u.choose(DirectiveLocation::ALL).cloned())?,
+    })
+}
+
+fn schema_definition<'a, T>(u: &mut Unstructured, _depth: usize)
+    -> Result<SchemaDefinition<'a, T>>
+    where T: Text<'a>,
+{
+    Ok(SchemaDefinition {
+        position: Pos::default(),
+        directives: directives(u, true)?,
+        query: option(u, name::<T>)?,
+        mutation: option(u, name::<T>)?,
+        subscription: option(u, name::<T>)?,
+    })
+}
+
+fn schema_extension<'a, T>(u: &mut Unstructured, _depth: usize)
+    -> Result<SchemaExtension<'a, T>>
+    where T: Text<'a>,
+{
+    let query = option(u, name::<T>)?;
+    let mutation = option(u, name::<T>)?;
+    let subscription = option(u, name::<T>)?;
+    let empty = query.is_none() && mutation.is_none() &&
+        subscription.is_none();
+    Ok(SchemaExtension {
+        position: Pos::default(),
+        directives: extension_directives(u, empty)?,
+        query, mutation, subscription,
+    })
+}
+
+fn schema_definition_item<'a, T>(u: &mut Unstructured, depth: usize)
+    -> Result<schema::Definition<'a, T>>
+    where T: Text<'a>,
+{
+    use crate::schema::Definition::*;
+    Ok(match u.choose_index(5)? {
+        0 => SchemaDefinition(schema_definition(u, depth)?),
+        1 => SchemaExtension(schema_extension(u, depth)?),
+        2 => TypeExtension(type_extension(u, depth)?),
+        3 => DirectiveDefinition(directive_definition(u, depth)?),
+        _ => TypeDefinition(type_definition(u, depth)?),
+    })
+}
+
+fn schema_document<'a, T>(u: &mut Unstructured, depth: usize)
+    -> Result<schema::Document<'a, T>>
+    where T: Text<'a>,
+{
+    Ok(schema::Document {
+        definitions: list(u, 1, |u| schema_definition_item(u, depth))?,
+    })
+}
+
+macro_rules! arbitrary {
+    ($($typ:ty => $gen:ident,)*) => {$(
+        impl<'u, 'a, T> Arbitrary<'u> for $typ
+            where T: Text<'a>,
+        {
+            fn arbitrary(u: &mut Unstructured<'u>) -> Result<Self> {
+                $gen(u, MAX_DEPTH)
+            }
+        }
+    )*}
+}
+
+arbitrary! {
+    Value<'a, T> => any_value,
+    Type<'a, T> => typ,
+    Directive<'a, T> => any_directive,
+    query::Document<'a, T> => query_document,
+    query::Definition<'a, T> => query_definition,
+    query::Operation<'a, T> => operation,
+    query::FragmentDefinition<'a, T> => fragment_definition,
+    VariableDefinition<'a, T> => variable_definition,
+    SelectionSet<'a, T> => selection_set,
+    Selection<'a, T> => selection,
+    query::Field<'a, T> => field,
+    FragmentSpread<'a, T> => fragment_spread,
+    InlineFragment<'a, T> => inline_fragment,
+    TypeCondition<'a, T> => type_condition,
+    schema::Document<'a, T> => schema_document,
+    schema::Definition<'a, T> => schema_definition_item,
+    SchemaDefinition<'a, T> => schema_definition,
+    SchemaExtension<'a, T> => schema_extension,
+    TypeDefinition<'a, T> => type_definition,
+    TypeExtension<'a, T> => type_extension,
+    ScalarType<'a, T> => scalar_type,
+    ObjectType<'a, T> => object_type,
+    InterfaceType<'a, T> => interface_type,
+    UnionType<'a, T> => union_type,
+    EnumType<'a, T> => enum_type,
+    InputObjectType<'a, T> => input_object_type,
+    ScalarTypeExtension<'a, T> => scalar_type_extension,
+    ObjectTypeExtension<'a, T> => object_type_extension,
+    InterfaceTypeExtension<'a, T> => interface_type_extension,
+    UnionTypeExtension<'a, T> => union_type_extension,
+    EnumTypeExtension<'a, T> => enum_type_extension,
+    InputObjectTypeExtension<'a, T> => input_object_type_extension,
+    schema::Field<'a, T> => schema_field,
+    InputValue<'a, T> => input_value,
+    EnumValue<'a, T> => enum_value,
+    DirectiveDefinition<'a, T> => directive_definition,
+}
+
+fn any_value<'a, T>(u: &mut Unstructured, depth: usize)
+    -> Result<Value<'a, T>>
+    where T: Text<'a>,
+{
+    value(u, depth, false)
+}
+
+fn any_directive<'a, T>(u: &mut Unstructured, depth: usize)
+    -> Result<Directive<'a, T>>
+    where T: Text<'a>,
+{
+    directive(u, depth, false)
+}
+
+impl<'u> Arbitrary<'u> for Number {
+    fn arbitrary(u: &mut Unstructured<'u>) -> Result<Self> {
+        Ok(Number(BigInt::from(u.arbitrary::<i64>()?)))
+    }
+}
+
+impl<'u> Arbitrary<'u> for OperationKind {
+    fn arbitrary(u: &mut Unstructured<'u>) -> Result<Self> {
+        operation_kind(u)
+    }
+}
+
+impl<'u> Arbitrary<'u> for DirectiveLocation {
+    fn arbitrary(u: &mut Unstructured<'u>) -> Result<Self> {
+        u.choose(DirectiveLocation::ALL).cloned()
+    }
+}
+
+impl<'u> Arbitrary<'u> for Description {
+    fn arbitrary(u: &mut Unstructured<'u>) -> Result<Self> {
+        Ok(u.arbitrary::<String>()?.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use arbitrary::{Arbitrary, Unstructured};
+
+    use crate::{query, schema};
+    use crate::roundtrip::assert_roundtrip;
+
+    /// Pseudo-random bytes, so the test is reproducible
+    fn data(seed: u64) -> Vec<u8> {
+        let mut state = seed.wrapping_mul(6364136223846793005) | 1;
+        (0..4096).map(|_| {
+            state = state.wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 56) as u8
+        }).collect()
+    }
+
+    #[test]
+    fn roundtrip() {
+        for seed in 0..500 {
+            let data = data(seed);
+            let mut u = Unstructured::new(&data);
+            let doc = query::Document::<String>::arbitrary(&mut u).unwrap();
+            if let Err(e) = assert_roundtrip(&doc) {
+                panic!("query, seed {}: {}", seed, e);
+            }
+            let doc = schema::Document::<&str>::arbitrary(&mut u).unwrap();
+            if let Err(e) = assert_roundtrip(&doc) {
+                panic!("schema, seed {}: {}", seed, e);
+            }
+        }
+    }
+}
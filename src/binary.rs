@@ -0,0 +1,824 @@
+//! Compact binary encoding of executable documents
+//!
+//! Parsing thousands of persisted operations at startup is slow compared
+//! to reading them back from a cache prepared in advance. `encode` writes
+//! the document into a compact binary form keeping all the positions, and
+//! `decode` restores the same document without tokenizing the text.
+//!
+//! The data starts with a header containing the `FORMAT_VERSION`, so
+//! caches written by an incompatible version of the crate are rejected and
+//! can be rebuilt. Names are stored once in a table, so repeated field and
+//! type names don't take space.
+//!
+//! ```rust
+//! # extern crate edb_graphql_parser;
+//! use edb_graphql_parser::parse_query;
+//! use edb_graphql_parser::binary::{decode, encode};
+//!
+//! let doc = parse_query::<&str>("query Q($id: ID!) { user(id: $id) { id } }")
+//!     .unwrap();
+//! let bytes = encode(&doc);
+//! let decoded = decode(&bytes).unwrap();
+//! assert_eq!(decoded, doc.map_text(String::from));
+//! ```
+//!
+//! Decoding recurses into the nested values and selections, same as
+//! `Clone` and `Drop` of the AST, so the data is expected to be produced by
+//! `encode` rather than received from untrusted sources.
+use std::collections::BTreeMap;
+
+use num_bigint::BigInt;
+use thiserror::Error;
+
+use crate::common::{Directive, DirectiveArgument, Number, Text, Type, Value};
+use crate::position::Pos;
+use crate::query::*;
+
+
+/// Version of the encoding, incremented on every incompatible change
+pub const FORMAT_VERSION: u8 = 2;
+
+const MAGIC: &[u8; 4] = b"GQLB";
+
+/// Error decoding the binary document
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    #[error("data is not a binary graphql document")]
+    InvalidHeader,
+    #[error("unsupported format version {0}, expected {}", FORMAT_VERSION)]
+    UnsupportedVersion(u8),
+    #[error("unexpected end of data")]
+    UnexpectedEnd,
+    #[error("invalid data at offset {0}")]
+    InvalidData(usize),
+    #[error("unexpected data after the document at offset {0}")]
+    TrailingData(usize),
+}
+
+/// Encodes the document, see the module documentation
+pub fn encode<'a, T>(doc: &Document<'a, T>) -> Vec<u8>
+    where T: Text<'a>,
+{
+    let mut encoder = Encoder {
+        body: Vec::new(),
+        names: BTreeMap::new(),
+        table: Vec::new(),
+    };
+    encoder.len(doc.definitions.len());
+    for def in &doc.definitions {
+        encoder.definition(def);
+    }
+    let mut buf = Vec::with_capacity(encoder.body.len() + 64);
+    buf.extend_from_slice(MAGIC);
+    buf.push(FORMAT_VERSION);
+    write_varint(&mut buf, encoder.table.len() as u64);
+    for name in &encoder.table {
+        write_varint(&mut buf, name.len() as u64);
+        buf.extend_from_slice(name.as_bytes());
+    }
+    buf.extend_from_slice(&encoder.body);
+    buf
+}
+
+/// Decodes the document written by `encode`
+pub fn decode(data: &[u8]) -> Result<Document<'static, String>, DecodeError>
+{
+    if data.len() < MAGIC.len() + 1 || &data[..MAGIC.len()] != MAGIC {
+        return Err(DecodeError::InvalidHeader);
+    }
+    let version = data[MAGIC.len()];
+    if version != FORMAT_VERSION {
+        return Err(DecodeError::UnsupportedVersion(version));
+    }
+    let mut decoder = Decoder {
+        data,
+        offset: MAGIC.len() + 1,
+        table: Vec::new(),
+    };
+    let names = decoder.len()?;
+    for _ in 0..names {
+        let name = decoder.string()?;
+        decoder.table.push(name);
+    }
+    let definitions = decoder.list(Decoder::definition)?;
+    if decoder.offset != data.len() {
+        return Err(DecodeError::TrailingData(decoder.offset));
+    }
+    Ok(Document { definitions })
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+struct Encoder<'x> {
+    body: Vec<u8>,
+    names: BTreeMap<&'x str, u64>,
+    table: Vec<&'x str>,
+}
+
+impl<'x> Encoder<'x> {
+    fn byte(&mut self, value: u8) {
+        self.body.push(value);
+    }
+
+    fn len(&mut self, value: usize) {
+        write_varint(&mut self.body, value as u64);
+    }
+
+    fn bytes(&mut self, value: &[u8]) {
+        self.len(value.len());
+        self.body.extend_from_slice(value);
+    }
+
+    fn pos(&mut self, pos: Pos) {
+        self.len(pos.line);
+        self.len(pos.column);
+        self.len(pos.character);
+        self.len(pos.token);
+    }
+
+    fn span(&mut self, span: (Pos, Pos)) {
+        self.pos(span.0);
+        self.pos(span.1);
+    }
+
+    fn opt_pos(&mut self, pos: Option<Pos>) {
+        match pos {
+            Some(pos) => {
+                self.byte(1);
+                self.pos(pos);
+            }
+            None => self.byte(0),
+        }
+    }
+
+    /// Writes positions of the punctuators, only if there are arguments
+    fn arguments_syntax(&mut self, arguments: usize,
+        syntax: &Option<ArgumentsSyntax>)
+    {
+        if arguments == 0 {
+            return;
+        }
+        match *syntax {
+            Some(ref syntax) => {
+                self.byte(1);
+                self.span(syntax.parens);
+                self.len(syntax.colons.len());
+                for &colon in &syntax.colons {
+                    self.pos(colon);
+                }
+            }
+            None => self.byte(0),
+        }
+    }
+
+    fn name<N: AsRef<str>>(&mut self, name: &'x N) {
+        let name = name.as_ref();
+        let next = self.table.len() as u64;
+        let idx = *self.names.entry(name).or_insert(next);
+        if idx == next {
+            self.table.push(name);
+        }
+        write_varint(&mut self.body, idx);
+    }
+
+    fn opt_name<N: AsRef<str>>(&mut self, name: &'x Option<N>) {
+        match *name {
+            Some(ref name) => {
+                self.byte(1);
+                self.name(name);
+            }
+            None => self.byte(0),
+        }
+    }
+
+    fn definition<'a, T>(&mut self, def: &'x Definition<'a, T>)
+        where T: Text<'a>,
+    {
+        match *def {
+            Definition::Operation(ref op) => {
+                self.byte(0);
+                self.byte(match op.kind {
+                    OperationKind::ImplicitQuery => 0,
+                    OperationKind::Query => 1,
+                    OperationKind::Mutation => 2,
+                    OperationKind::Subscription => 3,
+                });
+                self.pos(op.position);
+                self.opt_name(&op.name);
+                self.variable_definitions(&op.variable_definitions);
+                self.byte(match op.insert_variables.kind {
+                    InsertVarsKind::Query => 0,
+                    InsertVarsKind::Parens => 1,
+                    InsertVarsKind::Normal => 2,
+                });
+                self.pos(op.insert_variables.position);
+                self.directives(&op.directives);
+                self.selection_set(&op.selection_set);
+            }
+            Definition::Fragment(ref frag) => {
+                self.byte(1);
+                self.pos(frag.position);
+                self.name(&frag.name);
+                self.variable_definitions(&frag.variable_definitions);
+                self.pos(frag.on_position);
+                let TypeCondition::On(ref cond) = frag.type_condition;
+                self.name(cond);
+                self.directives(&frag.directives);
+                self.selection_set(&frag.selection_set);
+            }
+        }
+    }
+
+    fn variable_definitions<'a, T>(&mut self,
+        vars: &'x [VariableDefinition<'a, T>])
+        where T: Text<'a>,
+    {
+        self.len(vars.len());
+        for var in vars {
+            self.pos(var.position);
+            self.name(&var.name);
+            self.type_(&var.var_type);
+            match var.default_value {
+                Some(ref default) => {
+                    self.byte(1);
+                    self.span(default.span);
+                    self.value(&default.value);
+                }
+                None => self.byte(0),
+            }
+            self.directives(&var.directives);
+        }
+    }
+
+    fn directives<'a, T>(&mut self, directives: &'x [Directive<'a, T>])
+        where T: Text<'a>,
+    {
+        self.len(directives.len());
+        for dir in directives {
+            self.pos(dir.position);
+            self.name(&dir.name);
+            self.len(dir.arguments.len());
+            for arg in &dir.arguments {
+                self.name(&arg.name);
+                self.value(&arg.value);
+                self.pos(arg.value_position);
+            }
+            self.arguments_syntax(dir.arguments.len(), &dir.arguments_syntax);
+        }
+    }
+
+    fn arguments<'a, T>(&mut self, args: &'x [(T::Value, Value<'a, T>)])
+        where T: Text<'a>,
+    {
+        self.len(args.len());
+        for (name, value) in args {
+            self.name(name);
+            self.value(value);
+        }
+    }
+
+    fn selection_set<'a, T>(&mut self, set: &'x SelectionSet<'a, T>)
+        where T: Text<'a>,
+    {
+        self.span(set.span);
+        self.len(set.items.len());
+        for item in &set.items {
+            match *item {
+                Selection::Field(ref field) => {
+                    self.byte(0);
+                    self.pos(field.position);
+                    self.opt_name(&field.alias);
+                    self.name(&field.name);
+                    self.arguments(&field.arguments);
+                    self.arguments_syntax(field.arguments.len(),
+                        &field.arguments_syntax);
+                    match field.nullability {
+                        Some(ref nullability) => {
+                            self.byte(1);
+                            self.nullability(nullability);
+                        }
+                        None => self.byte(0),
+                    }
+                    self.directives(&field.directives);
+                    self.selection_set(&field.selection_set);
+                }
+                Selection::FragmentSpread(ref spread) => {
+                    self.byte(1);
+                    self.pos(spread.position);
+                    self.name(&spread.fragment_name);
+                    self.arguments(&spread.arguments);
+                    self.arguments_syntax(spread.arguments.len(),
+                        &spread.arguments_syntax);
+                    self.directives(&spread.directives);
+                }
+                Selection::InlineFragment(ref frag) => {
+                    self.byte(2);
+                    self.pos(frag.position);
+                    self.opt_pos(frag.on_position);
+                    match frag.type_condition {
+                        Some(TypeCondition::On(ref cond)) => {
+                            self.byte(1);
+                            self.name(cond);
+                        }
+                        None => self.byte(0),
+                    }
+                    self.directives(&frag.directives);
+                    self.selection_set(&frag.selection_set);
+                }
+            }
+        }
+    }
+
+    fn nullability(&mut self, nullability: &Nullability) {
+        match *nullability {
+            Nullability::Designator(designator) => {
+                self.byte(designator_tag(Some(designator)));
+            }
+            Nullability::List(ref element, designator) => {
+                self.byte(3 + designator_tag(designator));
+                match *element {
+                    Some(ref element) => {
+                        self.byte(1);
+                        self.nullability(element);
+                    }
+                    None => self.byte(0),
+                }
+            }
+        }
+    }
+
+    fn type_<'a, T>(&mut self, typ: &'x Type<'a, T>)
+        where T: Text<'a>,
+    {
+        match *typ {
+            Type::NamedType(ref name) => {
+                self.byte(0);
+                self.name(name);
+            }
+            Type::ListType(ref inner) => {
+                self.byte(1);
+                self.type_(inner);
+            }
+            Type::NonNullType(ref inner) => {
+                self.byte(2);
+                self.type_(inner);
+            }
+        }
+    }
+
+    fn value<'a, T>(&mut self, value: &'x Value<'a, T>)
+        where T: Text<'a>,
+    {
+        match *value {
+            Value::Variable(ref name) => {
+                self.byte(0);
+                self.name(name);
+            }
+            Value::Int(ref num) => {
+                self.byte(1);
+                self.bytes(&num.as_bigint().to_signed_bytes_le());
+            }
+            Value::Float(ref num) => {
+                self.byte(2);
+                self.bytes(num.as_bytes());
+            }
+            Value::String(ref s) => {
+                self.byte(3);
+                self.bytes(s.as_ref().as_bytes());
+            }
+            Value::Boolean(false) => self.byte(4),
+            Value::Boolean(true) => self.byte(5),
+            Value::Null => self.byte(6),
+            Value::Enum(ref name) => {
+                self.byte(7);
+                self.name(name);
+            }
+            Value::List(ref items) => {
+                self.byte(8);
+                self.len(items.len());
+                for item in items {
+                    self.value(item);
+                }
+            }
+            Value::Object(ref fields) => {
+                self.byte(9);
+                self.arguments(fields);
+            }
+        }
+    }
+}
+
+fn designator_tag(designator: Option<NullabilityDesignator>) -> u8 {
+    match designator {
+        None => 0,
+        Some(NullabilityDesignator::Required) => 1,
+        Some(NullabilityDesignator::Optional) => 2,
+    }
+}
+
+struct Decoder<'d> {
+    data: &'d [u8],
+    offset: usize,
+    table: Vec<String>,
+}
+
+impl<'d> Decoder<'d> {
+    fn invalid<R>(&self) -> Result<R, DecodeError> {
+        Err(DecodeError::InvalidData(self.offset))
+    }
+
+    fn byte(&mut self) -> Result<u8, DecodeError> {
+        let value = *self.data.get(self.offset)
+            .ok_or(DecodeError::UnexpectedEnd)?;
+        self.offset += 1;
+        Ok(value)
+    }
+
+    fn flag(&mut self) -> Result<bool, DecodeError> {
+        match self.byte()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => self.invalid(),
+        }
+    }
+
+    fn varint(&mut self) -> Result<u64, DecodeError> {
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            if shift > 63 || (shift == 63 && byte > 1) {
+                return self.invalid();
+            }
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+            shift += 7;
+        }
+    }
+
+    fn len(&mut self) -> Result<usize, DecodeError> {
+        let value = self.varint()?;
+        // every element takes at least one byte, so larger lengths are
+        // invalid and aren't used to preallocate memory
+        if value > (self.data.len() - self.offset) as u64 {
+            return self.invalid();
+        }
+        Ok(value as usize)
+    }
+
+    fn bytes(&mut self) -> Result<&'d [u8], DecodeError> {
+        let len = self.len()?;
+        let value = &self.data[self.offset..self.offset + len];
+        self.offset += len;
+        Ok(value)
+    }
+
+    fn string(&mut self) -> Result<String, DecodeError> {
+        let start = self.offset;
+        let bytes = self.bytes()?;
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| DecodeError::InvalidData(start))
+    }
+
+    fn list<R, F>(&mut self, mut item: F) -> Result<Vec<R>, DecodeError>
+        where F: FnMut(&mut Self) -> Result<R, DecodeError>,
+    {
+        let len = self.len()?;
+        let mut items = Vec::with_capacity(len);
+        for _ in 0..len {
+            items.push(item(self)?);
+        }
+        Ok(items)
+    }
+
+    fn pos(&mut self) -> Result<Pos, DecodeError> {
+        Ok(Pos {
+            line: self.varint()? as usize,
+            column: self.varint()? as usize,
+            character: self.varint()? as usize,
+            token: self.varint()? as usize,
+        })
+    }
+
+    fn span(&mut self) -> Result<(Pos, Pos), DecodeError> {
+        Ok((self.pos()?, self.pos()?))
+    }
+
+    fn opt_pos(&mut self) -> Result<Option<Pos>, DecodeError> {
+        if self.flag()? {
+            self.pos().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn arguments_syntax(&mut self, arguments: usize)
+        -> Result<Option<ArgumentsSyntax>, DecodeError>
+    {
+        if arguments > 0 && self.flag()? {
+            Ok(Some(ArgumentsSyntax {
+                parens: self.span()?,
+                colons: self.list(Decoder::pos)?,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn name(&mut self) -> Result<String, DecodeError> {
+        let idx = self.varint()?;
+        match self.table.get(idx as usize) {
+            Some(name) => Ok(name.clone()),
+            None => self.invalid(),
+        }
+    }
+
+    fn opt_name(&mut self) -> Result<Option<String>, DecodeError> {
+        if self.flag()? {
+            self.name().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn definition(&mut self)
+        -> Result<Definition<'static, String>, DecodeError>
+    {
+        match self.byte()? {
+            0 => {
+                let kind = match self.byte()? {
+                    0 => OperationKind::ImplicitQuery,
+                    1 => OperationKind::Query,
+                    2 => OperationKind::Mutation,
+                    3 => OperationKind::Subscription,
+                    _ => return self.invalid(),
+                };
+                let position = self.pos()?;
+                let name = self.opt_name()?;
+                let variable_definitions =
+                    self.list(Decoder::variable_definition)?;
+                let insert_kind = match self.byte()? {
+                    0 => InsertVarsKind::Query,
+                    1 => InsertVarsKind::Parens,
+                    2 => InsertVarsKind::Normal,
+                    _ => return self.invalid(),
+                };
+                Ok(Definition::Operation(Operation {
+                    kind,
+                    position,
+                    name,
+                    variable_definitions,
+                    insert_variables: InsertVars {
+                        kind: insert_kind,
+                        position: self.pos()?,
+                    },
+                    directives: self.list(Decoder::directive)?,
+                    selection_set: self.selection_set()?,
+                }))
+            }
+            1 => Ok(Definition::Fragment(FragmentDefinition {
+                position: self.pos()?,
+                name: self.name()?,
+                variable_definitions:
+                    self.list(Decoder::variable_definition)?,
+                on_position: self.pos()?,
+                type_condition: TypeCondition::On(self.name()?),
+                directives: self.list(Decoder::directive)?,
+                selection_set: self.selection_set()?,
+            })),
+            _ => self.invalid(),
+        }
+    }
+
+    fn variable_definition(&mut self)
+        -> Result<VariableDefinition<'static, String>, DecodeError>
+    {
+        Ok(VariableDefinition {
+            position: self.pos()?,
+            name: self.name()?,
+            var_type: self.type_()?,
+            default_value: if self.flag()? {
+                Some(DefaultValue {
+                    span: self.span()?,
+                    value: self.value()?,
+                })
+            } else {
+                None
+            },
+            directives: self.list(Decoder::directive)?,
+        })
+    }
+
+    fn directive(&mut self)
+        -> Result<Directive<'static, String>, DecodeError>
+    {
+        let position = self.pos()?;
+        let name = self.name()?;
+        let arguments = self.list(|d| Ok(DirectiveArgument {
+            name: d.name()?,
+            value: d.value()?,
+            value_position: d.pos()?,
+        }))?;
+        Ok(Directive {
+            position, name,
+            arguments_syntax: self.arguments_syntax(arguments.len())?,
+            arguments,
+        })
+    }
+
+    fn arguments(&mut self)
+        -> Result<Vec<(String, Value<'static, String>)>, DecodeError>
+    {
+        self.list(|d| Ok((d.name()?, d.value()?)))
+    }
+
+    fn selection_set(&mut self)
+        -> Result<SelectionSet<'static, String>, DecodeError>
+    {
+        Ok(SelectionSet {
+            span: self.span()?,
+            items: self.list(Decoder::selection)?,
+        })
+    }
+
+    fn selection(&mut self)
+        -> Result<Selection<'static, String>, DecodeError>
+    {
+        match self.byte()? {
+            0 => {
+                let position = self.pos()?;
+                let alias = self.opt_name()?;
+                let name = self.name()?;
+                let arguments = self.arguments()?;
+                let arguments_syntax = self.arguments_syntax(arguments.len())?;
+                Ok(Selection::Field(Field {
+                    position, alias, name, arguments, arguments_syntax,
+                    nullability: if self.flag()? {
+                        Some(self.nullability()?)
+                    } else {
+                        None
+                    },
+                    directives: self.list(Decoder::directive)?,
+                    selection_set: self.selection_set()?,
+                }))
+            }
+            1 => {
+                let position = self.pos()?;
+                let fragment_name = self.name()?;
+                let arguments = self.arguments()?;
+                let arguments_syntax = self.arguments_syntax(arguments.len())?;
+                Ok(Selection::FragmentSpread(FragmentSpread {
+                    position, fragment_name, arguments, arguments_syntax,
+                    directives: self.list(Decoder::directive)?,
+                }))
+            }
+            2 => Ok(Selection::InlineFragment(InlineFragment {
+                position: self.pos()?,
+                on_position: self.opt_pos()?,
+                type_condition: if self.flag()? {
+                    Some(TypeCondition::On(self.name()?))
+                } else {
+                    None
+                },
+                directives: self.list(Decoder::directive)?,
+                selection_set: self.selection_set()?,
+            })),
+            _ => self.invalid(),
+        }
+    }
+
+    fn designator(&self, tag: u8)
+        -> Result<Option<NullabilityDesignator>, DecodeError>
+    {
+        match tag {
+            0 => Ok(None),
+            1 => Ok(Some(NullabilityDesignator::Required)),
+            2 => Ok(Some(NullabilityDesignator::Optional)),
+            _ => self.invalid(),
+        }
+    }
+
+    fn nullability(&mut self) -> Result<Nullability, DecodeError> {
+        match self.byte()? {
+            tag @ 1..=2 => {
+                let designator = self.designator(tag)?
+                    .expect("tag is a designator");
+                Ok(Nullability::Designator(designator))
+            }
+            tag @ 3..=5 => {
+                let designator = self.designator(tag - 3)?;
+                let element = if self.flag()? {
+                    Some(Box::new(self.nullability()?))
+                } else {
+                    None
+                };
+                Ok(Nullability::List(element, designator))
+            }
+            _ => self.invalid(),
+        }
+    }
+
+    fn type_(&mut self) -> Result<Type<'static, String>, DecodeError> {
+        match self.byte()? {
+            0 => Ok(Type::NamedType(self.name()?)),
+            1 => Ok(Type::ListType(Box::new(self.type_()?))),
+            2 => Ok(Type::NonNullType(Box::new(self.type_()?))),
+            _ => self.invalid(),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value<'static, String>, DecodeError> {
+        match self.byte()? {
+            0 => Ok(Value::Variable(self.name()?)),
+            1 => {
+                let bytes = self.bytes()?;
+                Ok(Value::Int(Number(BigInt::from_signed_bytes_le(bytes))))
+            }
+            2 => Ok(Value::Float(self.string()?)),
+            3 => Ok(Value::String(self.string()?)),
+            4 => Ok(Value::Boolean(false)),
+            5 => Ok(Value::Boolean(true)),
+            6 => Ok(Value::Null),
+            7 => Ok(Value::Enum(self.name()?)),
+            8 => Ok(Value::List(self.list(Decoder::value)?)),
+            9 => Ok(Value::Object(self.arguments()?)),
+            _ => self.invalid(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse_query;
+    use crate::options::ParserOptions;
+    use crate::query::parse_query_with_options;
+    use super::{decode, encode, DecodeError, FORMAT_VERSION};
+
+    #[test]
+    fn roundtrip() {
+        let mut options = ParserOptions::default();
+        options.client_controlled_nullability(true)
+            .fragment_arguments(true);
+        let doc = parse_query_with_options::<&str>(r#"
+            query Q($a: [Int!]! = [1, -2, 123456789012345678901234567890])
+                @op(x: {y: "z\n", w: null}) {
+                a: b(c: $a, d: ENUM, e: 1.5e3, f: true, g: false) @skip(if: $a)
+                c! d[?]! e[[!]]
+                ... on T { ... { f } }
+                ...F(x: 1) @include(if: true)
+            }
+            { g }
+            subscription S { h }
+            mutation { i }
+            fragment F($x: Int) on T @d { j(k: """block""") }
+        "#, &options).unwrap();
+        let bytes = encode(&doc);
+        assert_eq!(decode(&bytes).unwrap(), doc.map_text(String::from));
+    }
+
+    #[test]
+    fn names_are_shared() {
+        let one = parse_query::<&str>("{ items { id } }").unwrap();
+        let many = parse_query::<&str>(
+            "{ items { id } items { id } items { id } }").unwrap();
+        let one = encode(&one);
+        let many = encode(&many);
+        assert!(many.windows(5).filter(|w| w == b"items").count() == 1);
+        assert!(many.len() < one.len() * 2);
+    }
+
+    #[test]
+    fn errors() {
+        let bytes = encode(&parse_query::<&str>("{ a(x: [1]) }").unwrap());
+        assert_eq!(decode(b"").unwrap_err(), DecodeError::InvalidHeader);
+        assert_eq!(decode(b"{ a }").unwrap_err(), DecodeError::InvalidHeader);
+        let mut other = bytes.clone();
+        other[4] = FORMAT_VERSION + 1;
+        assert_eq!(decode(&other).unwrap_err(),
+                   DecodeError::UnsupportedVersion(FORMAT_VERSION + 1));
+        assert_eq!(decode(&other).unwrap_err().to_string(),
+                   format!("unsupported format version {}, expected {}",
+                           FORMAT_VERSION + 1, FORMAT_VERSION));
+        for len in 5..bytes.len() {
+            assert!(decode(&bytes[..len]).is_err(), "truncated at {}", len);
+        }
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(decode(&trailing).unwrap_err(),
+                   DecodeError::TrailingData(bytes.len()));
+        // corrupted data is either rejected or decoded, but never panics
+        for idx in 5..bytes.len() {
+            let mut corrupted = bytes.clone();
+            corrupted[idx] ^= 0xff;
+            let _ = decode(&corrupted);
+        }
+    }
+}
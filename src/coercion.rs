@@ -0,0 +1,565 @@
+//! Coercion of input values against the schema
+//!
+//! Coercion resolves variables, checks the value against the input type and
+//! converts it to the form expected by the type, as described in the
+//! "Input Coercion" sections of the specification: single values are
+//! wrapped into lists where lists are expected, defaults of input object
+//! fields are filled in, integers are converted into floats and IDs.
+//! `InputCoercion::coerce_variables` resolves the values of all the
+//! variables of an operation, filling in their defaults.
+//!
+//! ```rust
+//! # extern crate edb_graphql_parser;
+//! # use std::collections::BTreeMap;
+//! use edb_graphql_parser::{parse_schema, Pos};
+//! use edb_graphql_parser::coercion::InputCoercion;
+//! use edb_graphql_parser::query::{Type, Value};
+//!
+//! let schema = parse_schema::<&str>("
+//!     input Filter { ids: [ID!], first: Int = 10 }
+//! ").unwrap();
+//! let coercion = InputCoercion::new(&schema);
+//! let mut variables = BTreeMap::new();
+//! variables.insert("id".to_string(), Value::<String>::Int(7.into()));
+//! let literal = Value::<&str>::Object(vec![("ids", Value::Variable("id"))]);
+//! let filter = Type::<&str>::NamedType("Filter");
+//! let value = coercion.coerce(&literal, &filter, &variables,
+//!                             Pos::default());
+//! assert_eq!(value.unwrap().to_string(), r#"{ids: ["7"], first: 10}"#);
+//! ```
+use std::borrow::Borrow;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use thiserror::Error;
+
+use crate::common::{Text, Type, Value};
+use crate::position::Pos;
+use crate::query::Operation;
+use crate::schema;
+use crate::schema::index::{SchemaIndex, TypeKind};
+
+
+/// Error coercing the input value
+#[derive(Error, Debug, Clone, PartialEq)]
+pub struct CoercionError {
+    /// Position of the value, as passed to `InputCoercion::coerce`
+    pub position: Pos,
+    /// Path to the invalid part of the value, e.g. `filter.ids[1]`, empty
+    /// if the whole value is invalid; paths of the variables coerced by
+    /// `InputCoercion::coerce_variables` start with the variable, e.g.
+    /// `$filter.ids[1]`
+    pub path: String,
+    pub message: String,
+}
+
+/// Coerces input values against the types of the schema
+///
+/// Types are looked up in the schema document, including its extensions;
+/// built-in scalars don't need to be defined. Custom scalars accept any
+/// value.
+#[derive(Debug)]
+pub struct InputCoercion<'x, 's, S: Text<'s>> {
+    index: SchemaIndex<'x, 's, S>,
+}
+
+struct Coercer<'c, 'x, 's, 'v, S: Text<'s>, K, V: Text<'v>> {
+    index: &'c SchemaIndex<'x, 's, S>,
+    variables: &'c BTreeMap<K, Value<'v, V>>,
+    position: Pos,
+    errors: Vec<CoercionError>,
+}
+
+impl<'x, 's, S: Text<'s>> InputCoercion<'x, 's, S> {
+    pub fn new(schema: &'x schema::Document<'s, S>) -> Self {
+        InputCoercion { index: SchemaIndex::new(schema) }
+    }
+
+    /// Coerces the value (usually a literal from the query) against the
+    /// expected type
+    ///
+    /// Variables are replaced by their values from `variables`, which are
+    /// coerced the same way, except that strings are also accepted as
+    /// enum values (as variables usually come from JSON). Variables that
+    /// are not provided are treated as missing input object fields, and as
+    /// `null` elsewhere. Returns all the errors found, with the `position`
+    /// passed in.
+    pub fn coerce<'a, 'v, 't, T, K, V, U>(&self, value: &Value<'a, T>,
+        expected: &Type<'t, U>, variables: &BTreeMap<K, Value<'v, V>>,
+        position: Pos)
+        -> Result<Value<'static, String>, Vec<CoercionError>>
+        where T: Text<'a>,
+              V: Text<'v>,
+              U: Text<'t>,
+              K: Borrow<str> + Ord,
+    {
+        coerce(&self.index, value, expected, variables, position)
+    }
+
+    /// Resolves the effective values of the variables of the operation
+    ///
+    /// Provided values are coerced against the types of the variable
+    /// definitions, the same way as variables of `coerce`; variables that
+    /// are not provided get their default values. Variables that have
+    /// neither a value nor a default are left out of the result, unless
+    /// they are of a non-null type, which is an error. Errors have the
+    /// position of the variable definition.
+    ///
+    /// ```rust
+    /// # extern crate edb_graphql_parser;
+    /// # use std::collections::BTreeMap;
+    /// use edb_graphql_parser::{parse_query, parse_schema};
+    /// use edb_graphql_parser::coercion::InputCoercion;
+    /// use edb_graphql_parser::query::{Definition, Value};
+    ///
+    /// let schema = parse_schema::<&str>("enum Sort { ASC DESC }").unwrap();
+    /// let doc = parse_query::<&str>("
+    ///     query($first: Int = 10, $sort: Sort, $after: String) { a }
+    /// ").unwrap();
+    /// let op = match doc.definitions[0] {
+    ///     Definition::Operation(ref op) => op,
+    ///     _ => unreachable!(),
+    /// };
+    /// let mut variables = BTreeMap::new();
+    /// variables.insert("sort", Value::<String>::String("ASC".into()));
+    /// let values = InputCoercion::new(&schema)
+    ///     .coerce_variables(op, &variables).unwrap();
+    /// assert_eq!(values["first"].to_string(), "10");
+    /// assert_eq!(values["sort"].to_string(), "ASC");
+    /// assert!(!values.contains_key("after"));
+    /// ```
+    pub fn coerce_variables<'a, 'v, T, K, V>(&self,
+        operation: &Operation<'a, T>, variables: &BTreeMap<K, Value<'v, V>>)
+        -> Result<BTreeMap<String, Value<'static, String>>,
+                  Vec<CoercionError>>
+        where T: Text<'a>,
+              V: Text<'v>,
+              K: Borrow<str> + Ord,
+    {
+        let mut coercer = Coercer {
+            index: &self.index,
+            variables,
+            position: Pos::default(),
+            errors: Vec::new(),
+        };
+        let mut result = BTreeMap::new();
+        for var in &operation.variable_definitions {
+            let name = var.name.as_ref();
+            coercer.position = var.position;
+            let mut path = format!("${}", name);
+            let value = match (variables.get(name), &var.default_value) {
+                (Some(value), _) => {
+                    coercer.value(value, &var.var_type, true, &mut path)
+                }
+                (None, Some(default)) => {
+                    coercer.value(&default.value, &var.var_type, false,
+                                  &mut path)
+                }
+                (None, None) if var.var_type.is_non_null() => {
+                    coercer.error("", format!(
+                        "Variable \"${}\" of required type \"{}\" was not \
+                         provided.", name, var.var_type));
+                    continue;
+                }
+                (None, None) => continue,
+            };
+            result.insert(name.to_string(), value);
+        }
+        if coercer.errors.is_empty() {
+            Ok(result)
+        } else {
+            Err(coercer.errors)
+        }
+    }
+}
+
+/// Coerces the value against the types of the index, see
+/// `InputCoercion::coerce`
+pub(crate) fn coerce<'x, 's, 'a, 'v, 't, S, T, K, V, U>(
+    index: &SchemaIndex<'x, 's, S>, value: &Value<'a, T>,
+    expected: &Type<'t, U>, variables: &BTreeMap<K, Value<'v, V>>,
+    position: Pos)
+    -> Result<Value<'static, String>, Vec<CoercionError>>
+    where S: Text<'s>,
+          T: Text<'a>,
+          V: Text<'v>,
+          U: Text<'t>,
+          K: Borrow<str> + Ord,
+{
+    let mut coercer = Coercer {
+        index,
+        variables,
+        position,
+        errors: Vec::new(),
+    };
+    let mut path = String::new();
+    let result = coercer.value(value, expected, false, &mut path);
+    if coercer.errors.is_empty() {
+        Ok(result)
+    } else {
+        Err(coercer.errors)
+    }
+}
+
+impl<'c, 'x, 's, 'v, S, K, V> Coercer<'c, 'x, 's, 'v, S, K, V>
+    where S: Text<'s>,
+          V: Text<'v>,
+          K: Borrow<str> + Ord,
+{
+    fn error(&mut self, path: &str, message: String) -> Value<'static, String>
+    {
+        self.errors.push(CoercionError {
+            position: self.position,
+            path: path.into(),
+            message,
+        });
+        Value::Null
+    }
+
+    /// Returns the value of the variable if it's provided
+    fn variable<'a, T>(&self, value: &Value<'a, T>)
+        -> Option<Option<&'c Value<'v, V>>>
+        where T: Text<'a>,
+    {
+        match *value {
+            Value::Variable(ref name) => {
+                Some(self.variables.get(name.as_ref()))
+            }
+            _ => None,
+        }
+    }
+
+    fn value<'a, 't, T, U>(&mut self, value: &Value<'a, T>,
+        expected: &Type<'t, U>, from_variable: bool, path: &mut String)
+        -> Value<'static, String>
+        where T: Text<'a>,
+              U: Text<'t>,
+    {
+        match self.variable(value) {
+            Some(Some(value)) => return self.value(value, expected, true, path),
+            Some(None) => return self.value(&Value::<String>::Null, expected,
+                                            from_variable, path),
+            None => {}
+        }
+        match (expected, value) {
+            (Type::NonNullType(inner), Value::Null) => {
+                self.error(path, format!(
+                    "Expected non-nullable type \"{}!\" not to be null.",
+                    inner))
+            }
+            (Type::NonNullType(inner), _) => {
+                self.value(value, inner, from_variable, path)
+            }
+            (_, Value::Null) => Value::Null,
+            (Type::ListType(inner), Value::List(items)) => {
+                let len = path.len();
+                let items = items.iter().enumerate().map(|(idx, item)| {
+                    path.push_str(&format!("[{}]", idx));
+                    let item = self.value(item, inner, from_variable, path);
+                    path.truncate(len);
+                    item
+                }).collect();
+                Value::List(items)
+            }
+            (Type::ListType(inner), _) => {
+                Value::List(vec![self.value(value, inner, from_variable, path)])
+            }
+            (Type::NamedType(name), _) => {
+                self.named(value, name.as_ref(), from_variable, path)
+            }
+        }
+    }
+
+    fn named<'a, T>(&mut self, value: &Value<'a, T>, name: &str,
+        from_variable: bool, path: &mut String)
+        -> Value<'static, String>
+        where T: Text<'a>,
+    {
+        let entry = match self.index.type_by_name(name) {
+            Some(entry) => entry,
+            None => return self.error(path,
+                format!("Unknown type \"{}\".", name)),
+        };
+        let invalid = |value: &Value<'a, T>| {
+            format!("{} cannot represent value: {}", name, value)
+        };
+        match entry.kind {
+            TypeKind::Scalar => match (name, value) {
+                ("Int", Value::Int(num))
+                    if num.as_i64().map(|v| v as i32 as i64 == v)
+                        .unwrap_or(false)
+                => Value::Int(num.clone()),
+                ("Float", Value::Int(num)) => {
+                    Value::Float(format!("{}.0", num.as_bigint()))
+                }
+                ("Float", Value::Float(num)) => Value::Float(num.clone()),
+                ("String", Value::String(s)) => {
+                    Value::String(s.as_ref().into())
+                }
+                ("Boolean", Value::Boolean(b)) => Value::Boolean(*b),
+                ("ID", Value::String(s)) => Value::String(s.as_ref().into()),
+                ("ID", Value::Int(num)) => {
+                    Value::String(num.as_bigint().to_string())
+                }
+                ("Int", _) | ("Float", _) | ("String", _) | ("Boolean", _)
+                | ("ID", _) => self.error(path, invalid(value)),
+                // custom scalars accept any value with variables resolved
+                _ => self.any(value),
+            },
+            TypeKind::Enum => {
+                let val = match *value {
+                    Value::Enum(ref val) => val.as_ref(),
+                    Value::String(ref val) if from_variable => val.as_ref(),
+                    _ => return self.error(path, format!(
+                        "Enum \"{}\" cannot represent non-enum value: {}.",
+                        name, value)),
+                };
+                if entry.values.contains(&val) {
+                    Value::Enum(val.to_string())
+                } else {
+                    self.error(path, format!(
+                        "Value \"{}\" does not exist in \"{}\" enum.",
+                        val, name))
+                }
+            }
+            TypeKind::InputObject => {
+                let fields = match *value {
+                    Value::Object(ref fields) => fields,
+                    _ => return self.error(path, format!(
+                        "Expected type \"{}\" to be an object.", name)),
+                };
+                let mut result = Vec::new();
+                let len = path.len();
+                for (i, (field_name, _)) in fields.iter().enumerate() {
+                    if fields[..i].iter().any(|(k, _)| k == field_name) {
+                        self.error(path, format!(
+                            "There can be only one input field named {:?}.",
+                            field_name.as_ref()));
+                        continue;
+                    }
+                    let defined = entry.input_fields.iter()
+                        .any(|f| f.name.as_ref() == field_name.as_ref());
+                    if !defined {
+                        self.error(path, format!(
+                            "Field \"{}\" is not defined by type \"{}\".",
+                            field_name.as_ref(), name));
+                    }
+                }
+                for def in &entry.input_fields {
+                    let field_name = def.name.as_ref();
+                    let provided = fields.iter()
+                        .find(|(k, _)| k.as_ref() == field_name)
+                        .map(|(_, v)| v)
+                        .filter(|v| !matches!(self.variable(v), Some(None)));
+                    if !path.is_empty() {
+                        path.push('.');
+                    }
+                    path.push_str(field_name);
+                    let value = match (provided, &def.default_value) {
+                        (Some(value), _) => {
+                            Some(self.value(value, &def.value_type,
+                                            from_variable, path))
+                        }
+                        (None, Some(default)) => {
+                            Some(self.value(default, &def.value_type,
+                                            false, path))
+                        }
+                        (None, None)
+                        if matches!(def.value_type, Type::NonNullType(_))
+                        => {
+                            path.truncate(len);
+                            Some(self.error(path, format!(
+                                "Field \"{}.{}\" of required type \"{}\" \
+                                 was not provided.",
+                                name, field_name, def.value_type)))
+                        }
+                        (None, None) => None,
+                    };
+                    path.truncate(len);
+                    if let Some(value) = value {
+                        result.push((field_name.to_string(), value));
+                    }
+                }
+                Value::Object(result)
+            }
+            TypeKind::Object | TypeKind::Interface | TypeKind::Union => {
+                self.error(path, format!(
+                    "Type \"{}\" is not an input type.", name))
+            }
+        }
+    }
+
+    /// Converts the value of a custom scalar, resolving variables
+    fn any<'a, T>(&self, value: &Value<'a, T>)
+        -> Value<'static, String>
+        where T: Text<'a>,
+    {
+        match *value {
+            Value::Variable(_) => match self.variable(value) {
+                Some(Some(value)) => self.any(value),
+                _ => Value::Null,
+            },
+            Value::Int(ref num) => Value::Int(num.clone()),
+            Value::Float(ref num) => Value::Float(num.clone()),
+            Value::String(ref s) => Value::String(s.as_ref().into()),
+            Value::Boolean(b) => Value::Boolean(b),
+            Value::Null => Value::Null,
+            Value::Enum(ref name) => Value::Enum(name.as_ref().to_string()),
+            Value::List(ref items) => {
+                Value::List(items.iter().map(|v| self.any(v)).collect())
+            }
+            Value::Object(ref fields) => {
+                Value::Object(fields.iter()
+                    .map(|(k, v)| (k.as_ref().to_string(), self.any(v)))
+                    .collect())
+            }
+        }
+    }
+}
+
+impl fmt::Display for CoercionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.path.is_empty() {
+            write!(f, "{}: ", self.path)?;
+        }
+        write!(f, "{} at {}", self.message, self.position)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use crate::{parse_query, parse_schema, Pos};
+    use crate::query::{Definition, Selection, Type, Value};
+    use super::InputCoercion;
+
+    const SCHEMA: &str = "
+        enum Role { ADMIN, USER }
+        input Filter {
+            role: Role = USER
+            name: String!
+            tags: [String!]
+            score: Float
+            nested: Filter
+        }
+        scalar JSON
+    ";
+
+    fn coerce(literal: &str, typ: &str, variables: &[(&str, Value<String>)])
+        -> Result<String, Vec<String>>
+    {
+        let schema = parse_schema::<&str>(SCHEMA).unwrap();
+        let query = format!("query($t: {}) {{ f(a: {}) }}", typ, literal);
+        let doc = parse_query::<&str>(&query).unwrap();
+        let op = match doc.definitions[0] {
+            Definition::Operation(ref op) => op,
+            _ => unreachable!(),
+        };
+        let value = match op.selection_set.items[0] {
+            Selection::Field(ref f) => &f.arguments[0].1,
+            _ => unreachable!(),
+        };
+        let typ = &op.variable_definitions[0].var_type;
+        let variables = variables.iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect::<BTreeMap<_, _>>();
+        InputCoercion::new(&schema)
+            .coerce(value, typ, &variables,
+                    Pos { line: 1, column: 1, character: 0, token: 0 })
+            .map(|v| v.to_string())
+            .map_err(|e| e.into_iter().map(|e| e.to_string()).collect())
+    }
+
+    #[test]
+    fn scalars_and_lists() {
+        assert_eq!(coerce("1", "Float", &[]).unwrap(), "1.0");
+        assert_eq!(coerce("1", "ID!", &[]).unwrap(), "\"1\"");
+        assert_eq!(coerce("\"x\"", "[String]", &[]).unwrap(), "[\"x\"]");
+        assert_eq!(coerce("[1, $v]", "[Int]", &[("v", Value::Int(2.into()))])
+                   .unwrap(), "[1, 2]");
+        assert_eq!(coerce("{a: [$v]}", "JSON", &[("v", Value::Boolean(true))])
+                   .unwrap(), "{a: [true]}");
+        assert_eq!(coerce("$v", "Int", &[]).unwrap(), "null");
+        assert_eq!(coerce("$v", "Int!", &[]).unwrap_err(), [
+            "Expected non-nullable type \"Int!\" not to be null. at 1:1"]);
+        assert_eq!(coerce("10000000000", "Int", &[]).unwrap_err(), [
+            "Int cannot represent value: 10000000000 at 1:1"]);
+        assert_eq!(coerce("[1, \"2\"]", "[Int]", &[]).unwrap_err(), [
+            "[1]: Int cannot represent value: \"2\" at 1:1"]);
+    }
+
+    #[test]
+    fn input_objects() {
+        assert_eq!(coerce("{name: \"x\", score: 1, tags: \"a\"}", "Filter", &[])
+                   .unwrap(),
+                   "{role: USER, name: \"x\", tags: [\"a\"], score: 1.0}");
+        assert_eq!(coerce("{name: \"x\", role: $r}", "Filter",
+                          &[("r", Value::String("ADMIN".into()))]).unwrap(),
+                   "{role: ADMIN, name: \"x\"}");
+        assert_eq!(coerce("{name: \"x\", role: $r}", "Filter", &[]).unwrap(),
+                   "{role: USER, name: \"x\"}");
+        assert_eq!(coerce("{name: \"x\", name: \"y\"}", "Filter", &[])
+                   .unwrap_err(), [
+            "There can be only one input field named \"name\". at 1:1"]);
+        assert_eq!(coerce("{nested: {role: \"ADMIN\"}, x: 1}", "Filter", &[])
+                   .unwrap_err(), [
+            "Field \"x\" is not defined by type \"Filter\". at 1:1",
+            "Field \"Filter.name\" of required type \"String!\" \
+             was not provided. at 1:1",
+            "nested.role: Enum \"Role\" cannot represent non-enum value: \
+             \"ADMIN\". at 1:1",
+            "nested: Field \"Filter.name\" of required type \"String!\" \
+             was not provided. at 1:1",
+        ]);
+        assert_eq!(coerce("1", "Filter", &[]).unwrap_err(), [
+            "Expected type \"Filter\" to be an object. at 1:1"]);
+    }
+
+    #[test]
+    fn unknown_type() {
+        let schema = parse_schema::<&str>(SCHEMA).unwrap();
+        let variables = BTreeMap::<String, Value<String>>::new();
+        let err = InputCoercion::new(&schema)
+            .coerce(&Value::<&str>::Int(1.into()),
+                    &Type::<&str>::NamedType("Missing"), &variables,
+                    Pos { line: 2, column: 3, character: 0, token: 0 })
+            .unwrap_err();
+        assert_eq!(err[0].to_string(), "Unknown type \"Missing\". at 2:3");
+    }
+
+    #[test]
+    fn variables() {
+        let schema = parse_schema::<&str>(SCHEMA).unwrap();
+        let doc = parse_query::<&str>("query(
+            $f: Filter = {name: \"x\"}, $r: Role, $ids: [ID!]!, $n: Int
+        ) { a }").unwrap();
+        let op = match doc.definitions[0] {
+            Definition::Operation(ref op) => op,
+            _ => unreachable!(),
+        };
+        let coercion = InputCoercion::new(&schema);
+        let mut variables = BTreeMap::new();
+        variables.insert("r", Value::<&str>::String("ADMIN".into()));
+        variables.insert("ids", Value::Int(1.into()));
+        variables.insert("n", Value::Null);
+        let values = coercion.coerce_variables(op, &variables).unwrap()
+            .into_iter().map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>();
+        assert_eq!(values, [
+            "f={role: USER, name: \"x\"}", "ids=[\"1\"]", "n=null",
+            "r=ADMIN",
+        ]);
+
+        let mut variables = BTreeMap::new();
+        variables.insert("f",
+            Value::<&str>::Object(vec![("name", Value::Null)]));
+        assert_eq!(coercion.coerce_variables(op, &variables).unwrap_err()
+                   .into_iter().map(|e| e.to_string()).collect::<Vec<_>>(), [
+            "$f.name: Expected non-nullable type \"String!\" not to be null. \
+             at 2:13",
+            "Variable \"$ids\" of required type \"[ID!]!\" was not provided. \
+             at 2:49",
+        ]);
+    }
+}
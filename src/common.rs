@@ -1,11 +1,17 @@
-use std::{fmt, collections::BTreeMap};
+use std::borrow::Borrow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Deref;
+use std::str::FromStr;
 
 use combine::{parser, ParseResult, Parser};
+use combine::error::{Consumed, Tracked};
 use combine::easy::Error;
 use combine::error::StreamError;
 use combine::combinator::{many, many1, optional, position, choice};
 use num_bigint::BigInt;
 use num_traits::cast::ToPrimitive;
+use thiserror::Error as ThisError;
 
 use crate::tokenizer::{Kind as T, Token, TokenStream};
 use crate::helpers::{punct, ident, kind, name};
@@ -15,18 +21,25 @@ use crate::position::Pos;
 /// It is used to make the AST generic over the string type.
 pub trait Text<'a>: 'a {
     type Value: 'a + From<&'a str> + AsRef<str> + std::borrow::Borrow<str> + PartialEq + Eq + PartialOrd + Ord + fmt::Debug + Clone;
+    /// Type of string values, which borrow from the source unless they
+    /// contain escape sequences or are block strings
+    type StringValue: 'a + From<&'a str> + From<String> + AsRef<str>
+        + PartialEq + fmt::Debug + Clone;
 }
 
 impl<'a> Text<'a> for &'a str {
     type Value = Self;
+    type StringValue = std::borrow::Cow<'a, str>;
 }
 
 impl<'a> Text<'a> for String {
     type Value = String;
+    type StringValue = String;
 }
 
 impl<'a> Text<'a> for std::borrow::Cow<'a, str> {
     type Value = Self;
+    type StringValue = Self;
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -41,6 +54,162 @@ pub struct Directive<'a, T: Text<'a>> {
     pub position: Pos,
     pub name: T::Value,
     pub arguments: Vec<DirectiveArgument<'a, T>>,
+    /// Punctuators of the parsed argument list, `None` if there is no
+    /// argument list or the node is constructed
+    pub arguments_syntax: Option<ArgumentsSyntax>,
+}
+
+/// Positions of the punctuators of an argument list
+///
+/// Kept by the parser for tools that need exact anchors, like folding
+/// ranges or inserting an argument.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct ArgumentsSyntax {
+    /// Positions of `(` and `)`
+    pub parens: (Pos, Pos),
+    /// Position of the `:` of each argument, in order of the arguments
+    pub colons: Vec<Pos>,
+}
+
+/// Argument of a directive having a value of the wrong type, see
+/// `Directive::bool_argument`
+#[derive(ThisError, Debug, Clone, PartialEq, Eq)]
+#[error("{position}: argument {argument:?} of directive @{directive} \
+         must be {expected}")]
+pub struct ArgumentError {
+    /// Position of the argument value
+    pub position: Pos,
+    pub directive: String,
+    pub argument: String,
+    /// Expected type, e.g. `Boolean`
+    pub expected: &'static str,
+}
+
+impl<'a, T> Directive<'a, T>
+    where T: Text<'a>,
+{
+    /// Returns the argument with the name
+    pub fn argument(&self, name: &str) -> Option<&DirectiveArgument<'a, T>> {
+        self.arguments.iter().find(|arg| arg.name.as_ref() == name)
+    }
+
+    /// Returns the value of the boolean argument
+    ///
+    /// Variables are replaced with their values. Argument which is
+    /// missing, `null`, or refers to a variable that isn't provided is
+    /// `None`, argument of other types is an error. Typical use is the
+    /// condition of `@skip` and `@include`:
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "query")] {
+    /// # extern crate edb_graphql_parser;
+    /// # use std::collections::BTreeMap;
+    /// use edb_graphql_parser::parse_query;
+    /// use edb_graphql_parser::query::Value;
+    ///
+    /// let doc = parse_query::<&str>("
+    ///     query Q($hide: Boolean) { a @skip(if: $hide) b @skip(if: 1) }
+    /// ").unwrap();
+    /// let fields = doc.operations().next().unwrap()
+    ///     .selection_set.fields().collect::<Vec<_>>();
+    /// let mut variables = BTreeMap::new();
+    /// variables.insert("hide", Value::<&str>::Boolean(true));
+    /// let skip = &fields[0].directives[0];
+    /// assert_eq!(skip.bool_argument("if", &variables), Ok(Some(true)));
+    /// let empty = BTreeMap::<&str, Value<&str>>::new();
+    /// assert_eq!(skip.bool_argument("if", &empty), Ok(None));
+    /// let err = fields[1].directives[0].bool_argument("if", &variables);
+    /// assert_eq!(err.unwrap_err().to_string(),
+    ///     "2:62: argument \"if\" of directive @skip must be Boolean");
+    /// # }
+    /// ```
+    pub fn bool_argument<'v, K, V>(&self, name: &str,
+        variables: &BTreeMap<K, Value<'v, V>>)
+        -> Result<Option<bool>, ArgumentError>
+        where K: Borrow<str> + Ord,
+              V: Text<'v>,
+    {
+        self.typed_argument(name, variables, "Boolean",
+            |value| match *value {
+                Value::Boolean(value) => Some(value),
+                _ => None,
+            },
+            |value| match *value {
+                Value::Boolean(value) => Some(value),
+                _ => None,
+            })
+    }
+
+    /// Returns the value of the string argument, see `bool_argument`
+    pub fn string_argument<'r, 'v, K, V>(&'r self, name: &str,
+        variables: &'r BTreeMap<K, Value<'v, V>>)
+        -> Result<Option<&'r str>, ArgumentError>
+        where K: Borrow<str> + Ord,
+              V: Text<'v>,
+    {
+        self.typed_argument(name, variables, "String",
+            |value| match *value {
+                Value::String(ref value) => Some(value.as_ref()),
+                _ => None,
+            },
+            |value| match *value {
+                Value::String(ref value) => Some(value.as_ref()),
+                _ => None,
+            })
+    }
+
+    /// Returns the value of the enum argument, see `bool_argument`
+    ///
+    /// Values of variables may also be strings, as variables usually come
+    /// from JSON.
+    pub fn enum_argument<'r, 'v, K, V>(&'r self, name: &str,
+        variables: &'r BTreeMap<K, Value<'v, V>>)
+        -> Result<Option<&'r str>, ArgumentError>
+        where K: Borrow<str> + Ord,
+              V: Text<'v>,
+    {
+        self.typed_argument(name, variables, "enum value",
+            |value| match *value {
+                Value::Enum(ref value) => Some(value.as_ref()),
+                _ => None,
+            },
+            |value| match *value {
+                Value::Enum(ref value) => Some(value.as_ref()),
+                Value::String(ref value) => Some(value.as_ref()),
+                _ => None,
+            })
+    }
+
+    fn typed_argument<'r, 'v, K, V, R>(&'r self, name: &str,
+        variables: &'r BTreeMap<K, Value<'v, V>>, expected: &'static str,
+        literal: impl Fn(&'r Value<'a, T>) -> Option<R>,
+        variable: impl Fn(&'r Value<'v, V>) -> Option<R>)
+        -> Result<Option<R>, ArgumentError>
+        where K: Borrow<str> + Ord,
+              V: Text<'v>,
+    {
+        let arg = match self.argument(name) {
+            Some(arg) => arg,
+            None => return Ok(None),
+        };
+        let result = match arg.value {
+            Value::Null => return Ok(None),
+            Value::Variable(ref var) => match variables.get(var.as_ref()) {
+                None | Some(&Value::Null) => return Ok(None),
+                Some(value) => variable(value),
+            },
+            ref value => literal(value),
+        };
+        match result {
+            Some(result) => Ok(Some(result)),
+            None => Err(ArgumentError {
+                position: arg.value_position,
+                directive: self.name.as_ref().to_string(),
+                argument: name.to_string(),
+                expected,
+            }),
+        }
+    }
 }
 
 /// This represents integer number
@@ -49,29 +218,259 @@ pub struct Directive<'a, T: Text<'a>> {
 /// (only in implemetation), we do a trick similar to the one
 /// in `serde_json`: encapsulate value in new-type, allowing type
 /// to be extended later.
+///
+/// Integers of any size are parsed without loss, so documents with numbers
+/// out of the range of `i64` pass through unchanged (`as_i64` returns
+/// `None` for them). Floats are kept as written in `Value::Float`.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Number(pub(crate) BigInt);
 
-#[derive(Debug, Clone, PartialEq)]
+/// Value of an argument, a variable default or a directive argument
+///
+/// Values are cloned and compared without recursion, so lists and objects
+/// nested arbitrarily deep don't overflow the stack.
+#[derive(Debug)]
 pub enum Value<'a, T: Text<'a>> {
     Variable(T::Value),
     Int(Number),
     Float(String),
-    String(String),
+    String(T::StringValue),
     Boolean(bool),
     Null,
     Enum(T::Value),
     List(Vec<Value<'a, T>>),
-    Object(BTreeMap<T::Value, Value<'a, T>>),
+    Object(Vec<(T::Value, Value<'a, T>)>),
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// Type reference, e.g. `[Int!]`, cloned and compared without recursion
+#[derive(Debug)]
 pub enum Type<'a, T: Text<'a>> {
     NamedType(T::Value),
     ListType(Box<Type<'a, T>>),
     NonNullType(Box<Type<'a, T>>),
 }
 
+impl<'a, T> Type<'a, T>
+    where T: Text<'a>,
+{
+    /// Returns the name of the type with list and non-null wrappers removed
+    pub fn innermost_name(&self) -> &str {
+        match *self {
+            Type::NamedType(ref name) => name.as_ref(),
+            Type::ListType(ref inner) | Type::NonNullType(ref inner) => {
+                inner.innermost_name()
+            }
+        }
+    }
+
+    pub fn is_non_null(&self) -> bool {
+        matches!(*self, Type::NonNullType(_))
+    }
+
+    /// Returns true for lists, including non-null ones
+    pub fn is_list(&self) -> bool {
+        match *self {
+            Type::NamedType(_) => false,
+            Type::ListType(_) => true,
+            Type::NonNullType(ref inner) => inner.is_list(),
+        }
+    }
+
+    /// Returns the number of nested lists, e.g. 2 for `[[Int!]]!`
+    pub fn list_depth(&self) -> usize {
+        match *self {
+            Type::NamedType(_) => 0,
+            Type::ListType(ref inner) => inner.list_depth() + 1,
+            Type::NonNullType(ref inner) => inner.list_depth(),
+        }
+    }
+
+    /// Checks whether a variable of this type can be used where the
+    /// `location` type is expected ("AreTypesCompatible" of the spec)
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "query")] {
+    /// # extern crate edb_graphql_parser;
+    /// use edb_graphql_parser::query::Type;
+    ///
+    /// let int = || Box::new(Type::<&str>::NamedType("Int"));
+    /// let non_null = Type::NonNullType(int());
+    /// assert!(non_null.is_compatible_with(&Type::<&str>::NamedType("Int")));
+    /// assert!(!Type::ListType(int()).is_compatible_with(&non_null));
+    /// # }
+    /// ```
+    pub fn is_compatible_with<'l, L>(&self, location: &Type<'l, L>) -> bool
+        where L: Text<'l>,
+    {
+        match (self, location) {
+            (Type::NonNullType(v), Type::NonNullType(l)) => {
+                v.is_compatible_with(l)
+            }
+            (_, Type::NonNullType(_)) => false,
+            (Type::NonNullType(v), _) => v.is_compatible_with(location),
+            (Type::ListType(v), Type::ListType(l)) => v.is_compatible_with(l),
+            (_, Type::ListType(_)) | (Type::ListType(_), _) => false,
+            (Type::NamedType(v), Type::NamedType(l)) => {
+                v.as_ref() == l.as_ref()
+            }
+        }
+    }
+}
+
+/// Items of a list or object value that are left to clone
+enum CloneItems<'x, 'a, T: Text<'a>> {
+    List(std::slice::Iter<'x, Value<'a, T>>),
+    Object(std::slice::Iter<'x, (T::Value, Value<'a, T>)>),
+}
+
+impl<'a, T: Text<'a>> Value<'a, T> {
+    /// Clones a scalar, or returns an empty list or object along with
+    /// the items to clone into it
+    fn clone_start<'x>(&'x self)
+        -> (Value<'a, T>, Option<CloneItems<'x, 'a, T>>)
+    {
+        let value = match *self {
+            Value::Variable(ref name) => Value::Variable(name.clone()),
+            Value::Int(ref num) => Value::Int(num.clone()),
+            Value::Float(ref num) => Value::Float(num.clone()),
+            Value::String(ref s) => Value::String(s.clone()),
+            Value::Boolean(b) => Value::Boolean(b),
+            Value::Null => Value::Null,
+            Value::Enum(ref name) => Value::Enum(name.clone()),
+            Value::List(ref items) => {
+                return (Value::List(Vec::with_capacity(items.len())),
+                        Some(CloneItems::List(items.iter())));
+            }
+            Value::Object(ref fields) => {
+                return (Value::Object(Vec::with_capacity(fields.len())),
+                        Some(CloneItems::Object(fields.iter())));
+            }
+        };
+        (value, None)
+    }
+
+    /// Adds a cloned item to the list or object being cloned
+    fn push_item(&mut self, key: Option<T::Value>, value: Value<'a, T>) {
+        match (self, key) {
+            (Value::List(items), None) => items.push(value),
+            (Value::Object(fields), Some(key)) => fields.push((key, value)),
+            _ => unreachable!("items are cloned into their container"),
+        }
+    }
+}
+
+impl<'a, T: Text<'a>> Clone for Value<'a, T> {
+    fn clone(&self) -> Self {
+        let mut stack = match self.clone_start() {
+            (value, None) => return value,
+            (value, Some(items)) => vec![(None, value, items)],
+        };
+        loop {
+            let top = stack.last_mut().expect("stack is never empty");
+            let next = match top.2 {
+                CloneItems::List(ref mut iter) => {
+                    iter.next().map(|value| (None, value))
+                }
+                CloneItems::Object(ref mut iter) => {
+                    iter.next().map(|(key, value)| (Some(key.clone()), value))
+                }
+            };
+            match next {
+                Some((key, item)) => match item.clone_start() {
+                    (value, None) => top.1.push_item(key, value),
+                    (value, Some(items)) => stack.push((key, value, items)),
+                },
+                None => {
+                    let (key, value, _) = stack.pop()
+                        .expect("stack is never empty");
+                    match stack.last_mut() {
+                        Some(parent) => parent.1.push_item(key, value),
+                        None => return value,
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<'a, T: Text<'a>> PartialEq for Value<'a, T> {
+    fn eq(&self, other: &Self) -> bool {
+        let mut stack = vec![(self, other)];
+        while let Some(pair) = stack.pop() {
+            let equal = match pair {
+                (Value::Variable(a), Value::Variable(b)) => a == b,
+                (Value::Int(a), Value::Int(b)) => a == b,
+                (Value::Float(a), Value::Float(b)) => a == b,
+                (Value::String(a), Value::String(b)) => a == b,
+                (Value::Boolean(a), Value::Boolean(b)) => a == b,
+                (Value::Null, Value::Null) => true,
+                (Value::Enum(a), Value::Enum(b)) => a == b,
+                (Value::List(a), Value::List(b)) if a.len() == b.len() => {
+                    stack.extend(a.iter().zip(b));
+                    true
+                }
+                (Value::Object(a), Value::Object(b)) if a.len() == b.len() => {
+                    for ((a_key, a_value), (b_key, b_value)) in a.iter().zip(b)
+                    {
+                        if a_key != b_key {
+                            return false;
+                        }
+                        stack.push((a_value, b_value));
+                    }
+                    true
+                }
+                _ => false,
+            };
+            if !equal {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl<'a, T: Text<'a>> Clone for Type<'a, T> {
+    fn clone(&self) -> Self {
+        let mut wrappers = Vec::new();
+        let mut inner = self;
+        let mut typ = loop {
+            match *inner {
+                Type::NamedType(ref name) => {
+                    break Type::NamedType(name.clone());
+                }
+                Type::ListType(ref next) | Type::NonNullType(ref next) => {
+                    wrappers.push(inner);
+                    inner = next;
+                }
+            }
+        };
+        for wrapper in wrappers.into_iter().rev() {
+            typ = match *wrapper {
+                Type::ListType(_) => Type::ListType(Box::new(typ)),
+                _ => Type::NonNullType(Box::new(typ)),
+            };
+        }
+        typ
+    }
+}
+
+impl<'a, T: Text<'a>> PartialEq for Type<'a, T> {
+    fn eq(&self, other: &Self) -> bool {
+        let (mut a, mut b) = (self, other);
+        loop {
+            match (a, b) {
+                (Type::NamedType(a), Type::NamedType(b)) => return a == b,
+                (Type::ListType(a_inner), Type::ListType(b_inner)) |
+                (Type::NonNullType(a_inner), Type::NonNullType(b_inner)) => {
+                    a = a_inner;
+                    b = b_inner;
+                }
+                _ => return false,
+            }
+        }
+    }
+}
+
 impl Number {
     /// Returns a number as i64 if it fits the type
     pub fn as_i64(&self) -> Option<i64> {
@@ -88,6 +487,277 @@ impl From<i32> for Number {
     }
 }
 
+impl FromStr for Number {
+    type Err = num_bigint::ParseBigIntError;
+
+    /// Parses the decimal digits of an integer, with an optional sign
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Number)
+    }
+}
+
+/// Description of a schema element
+///
+/// Dereferences to the value of the string, with block strings dedented.
+/// The source of the string is also kept, so documentation tools can render
+/// it with its original formatting. Descriptions are equal if their values
+/// are equal, and only the value is shown in the `Debug` output.
+#[derive(Clone, Default)]
+pub struct Description {
+    /// Value of the string
+    pub value: String,
+    /// Source of the string including the quotes, `None` if the
+    /// description is not parsed
+    pub raw: Option<String>,
+    /// Indentation removed from the lines of the block string
+    pub indent: usize,
+}
+
+impl Description {
+    /// Returns whether the description is written as a block string
+    pub fn is_block(&self) -> bool {
+        self.raw.as_ref().is_some_and(|raw| raw.starts_with("\"\"\""))
+    }
+}
+
+impl Deref for Description {
+    type Target = str;
+    fn deref(&self) -> &str {
+        &self.value
+    }
+}
+
+impl AsRef<str> for Description {
+    fn as_ref(&self) -> &str {
+        &self.value
+    }
+}
+
+impl PartialEq for Description {
+    fn eq(&self, other: &Description) -> bool {
+        self.value == other.value
+    }
+}
+
+impl PartialEq<str> for Description {
+    fn eq(&self, other: &str) -> bool {
+        self.value == other
+    }
+}
+
+impl<'a> PartialEq<&'a str> for Description {
+    fn eq(&self, other: &&'a str) -> bool {
+        self.value == *other
+    }
+}
+
+impl From<String> for Description {
+    fn from(value: String) -> Description {
+        Description { value, raw: None, indent: 0 }
+    }
+}
+
+impl<'a> From<&'a str> for Description {
+    fn from(value: &'a str) -> Description {
+        value.to_string().into()
+    }
+}
+
+impl fmt::Debug for Description {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Description")
+            .field("value", &self.value)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for Description {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.value)
+    }
+}
+
+/// Location where a directive may be used, as listed in its definition
+///
+/// Parsed from and printed as the names of the grammar:
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::common::DirectiveLocation;
+///
+/// let loc = "VARIABLE_DEFINITION".parse::<DirectiveLocation>().unwrap();
+/// assert_eq!(loc, DirectiveLocation::VariableDefinition);
+/// assert!(loc.is_executable());
+/// assert_eq!(loc.to_string(), "VARIABLE_DEFINITION");
+/// assert!("variable_definition".parse::<DirectiveLocation>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DirectiveLocation {
+    // executable
+    Query,
+    Mutation,
+    Subscription,
+    Field,
+    FragmentDefinition,
+    FragmentSpread,
+    InlineFragment,
+    VariableDefinition,
+
+    // type_system
+    Schema,
+    Scalar,
+    Object,
+    FieldDefinition,
+    ArgumentDefinition,
+    Interface,
+    Union,
+    Enum,
+    EnumValue,
+    InputObject,
+    InputFieldDefinition,
+}
+
+impl DirectiveLocation {
+    /// All the locations, executable ones first, in the order of the
+    /// specification
+    pub const ALL: &'static [DirectiveLocation] = &[
+        DirectiveLocation::Query,
+        DirectiveLocation::Mutation,
+        DirectiveLocation::Subscription,
+        DirectiveLocation::Field,
+        DirectiveLocation::FragmentDefinition,
+        DirectiveLocation::FragmentSpread,
+        DirectiveLocation::InlineFragment,
+        DirectiveLocation::VariableDefinition,
+        DirectiveLocation::Schema,
+        DirectiveLocation::Scalar,
+        DirectiveLocation::Object,
+        DirectiveLocation::FieldDefinition,
+        DirectiveLocation::ArgumentDefinition,
+        DirectiveLocation::Interface,
+        DirectiveLocation::Union,
+        DirectiveLocation::Enum,
+        DirectiveLocation::EnumValue,
+        DirectiveLocation::InputObject,
+        DirectiveLocation::InputFieldDefinition,
+    ];
+
+    /// Returns GraphQL syntax compatible name of the directive
+    pub fn as_str(&self) -> &'static str {
+        use self::DirectiveLocation::*;
+        match *self {
+            Query => "QUERY",
+            Mutation => "MUTATION",
+            Subscription => "SUBSCRIPTION",
+            Field => "FIELD",
+            FragmentDefinition => "FRAGMENT_DEFINITION",
+            FragmentSpread => "FRAGMENT_SPREAD",
+            InlineFragment => "INLINE_FRAGMENT",
+            VariableDefinition => "VARIABLE_DEFINITION",
+            Schema => "SCHEMA",
+            Scalar => "SCALAR",
+            Object => "OBJECT",
+            FieldDefinition => "FIELD_DEFINITION",
+            ArgumentDefinition => "ARGUMENT_DEFINITION",
+            Interface => "INTERFACE",
+            Union => "UNION",
+            Enum => "ENUM",
+            EnumValue => "ENUM_VALUE",
+            InputObject => "INPUT_OBJECT",
+            InputFieldDefinition => "INPUT_FIELD_DEFINITION",
+        }
+    }
+
+    /// Returns `true` if this location is for queries (execution)
+    pub fn is_query(&self) -> bool {
+        use self::DirectiveLocation::*;
+        match *self {
+            Query
+            | Mutation
+            | Subscription
+            | Field
+            | FragmentDefinition
+            | FragmentSpread
+            | InlineFragment
+            | VariableDefinition
+                => true,
+
+            Schema
+            | Scalar
+            | Object
+            | FieldDefinition
+            | ArgumentDefinition
+            | Interface
+            | Union
+            | Enum
+            | EnumValue
+            | InputObject
+            | InputFieldDefinition
+                => false,
+        }
+    }
+
+    /// Returns `true` if this location is for schema
+    pub fn is_schema(&self) -> bool {
+        !self.is_query()
+    }
+
+    /// Returns `true` for the locations in executable documents, same as
+    /// `is_query`
+    pub fn is_executable(&self) -> bool {
+        self.is_query()
+    }
+
+    /// Returns `true` for the locations in schema documents, same as
+    /// `is_schema`
+    pub fn is_type_system(&self) -> bool {
+        self.is_schema()
+    }
+}
+
+impl fmt::Display for DirectiveLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, ThisError, Clone, PartialEq, Eq)]
+#[error("invalid directive location")]
+pub struct InvalidDirectiveLocation;
+
+
+impl FromStr for DirectiveLocation {
+    type Err = InvalidDirectiveLocation;
+    fn from_str(s: &str) -> Result<DirectiveLocation, InvalidDirectiveLocation>
+    {
+        use self::DirectiveLocation::*;
+        let val = match s {
+            "QUERY" => Query,
+            "MUTATION" => Mutation,
+            "SUBSCRIPTION" => Subscription,
+            "FIELD" => Field,
+            "FRAGMENT_DEFINITION" => FragmentDefinition,
+            "FRAGMENT_SPREAD" => FragmentSpread,
+            "INLINE_FRAGMENT" => InlineFragment,
+            "VARIABLE_DEFINITION" => VariableDefinition,
+            "SCHEMA" => Schema,
+            "SCALAR" => Scalar,
+            "OBJECT" => Object,
+            "FIELD_DEFINITION" => FieldDefinition,
+            "ARGUMENT_DEFINITION" => ArgumentDefinition,
+            "INTERFACE" => Interface,
+            "UNION" => Union,
+            "ENUM" => Enum,
+            "ENUM_VALUE" => EnumValue,
+            "INPUT_OBJECT" => InputObject,
+            "INPUT_FIELD_DEFINITION" => InputFieldDefinition,
+            _ => return Err(InvalidDirectiveLocation),
+        };
+
+        Ok(val)
+    }
+}
+
 pub fn directives<'a, T>(input: &mut TokenStream<'a>)
     -> ParseResult<Vec<Directive<'a, T>>, TokenStream<'a>>
     where T: Text<'a>,
@@ -96,44 +766,78 @@ pub fn directives<'a, T>(input: &mut TokenStream<'a>)
         .skip(punct("@"))
         .and(name::<'a, T>())
         .and(parser(directive_arguments))
-        .map(|((position, name), arguments)| {
-            Directive { position, name, arguments }
+        .map(|((position, name), (arguments, arguments_syntax))| {
+            Directive { position, name, arguments, arguments_syntax }
         }))
     .parse_stream(input)
+    .map_err(|e| input.rule_failed("Directives", e))
 }
 
+#[allow(clippy::type_complexity)]
 pub fn directive_arguments<'a, T>(input: &mut TokenStream<'a>)
-    -> ParseResult<Vec<DirectiveArgument<'a, T>>, TokenStream<'a>>
+    -> ParseResult<(Vec<DirectiveArgument<'a, T>>, Option<ArgumentsSyntax>),
+                   TokenStream<'a>>
     where T: Text<'a>,
 {
-    optional(
-        punct("(")
-        .with(many1(name::<'a, T>()
-            .skip(punct(":"))
-            .and(position())
-            .and(parser(value))
-            .map(|((name, value_position), value)| {
+    parser(arguments).map(|(arguments, syntax)| {
+        let arguments = arguments.into_iter()
+            .map(|(name, value_position, value)| {
                 DirectiveArgument { name, value, value_position }
-            })))
-        .skip(punct(")")))
-    .map(|opt| {
-        opt.unwrap_or_else(Vec::new)
+            })
+            .collect();
+        (arguments, syntax)
     })
     .parse_stream(input)
 }
 
-pub fn arguments<'a, T>(input: &mut TokenStream<'a>)
-    -> ParseResult<Vec<(T::Value, Value<'a, T>)>, TokenStream<'a>>
+/// Parses arguments, returning the position of the value of each argument
+/// along with its name and value
+#[allow(clippy::type_complexity)]
+fn arguments<'a, T>(input: &mut TokenStream<'a>)
+    -> ParseResult<(Vec<(T::Value, Pos, Value<'a, T>)>,
+                    Option<ArgumentsSyntax>),
+                   TokenStream<'a>>
     where T: Text<'a>,
 {
-    optional(
-        punct("(")
-        .with(many1(name::<'a, T>()
-            .skip(punct(":"))
-            .and(parser(value))))
-        .skip(punct(")")))
-    .map(|opt| {
-        opt.unwrap_or_else(Vec::new)
+    optional((
+        position().skip(punct("(")),
+        many1::<Vec<_>, _>((
+            name::<'a, T>(),
+            position().skip(punct(":")),
+            position(),
+            parser(value),
+        )),
+        position().skip(punct(")")),
+    ))
+    .map(|opt| match opt {
+        Some((open, arguments, close)) => {
+            let mut colons = Vec::with_capacity(arguments.len());
+            let arguments = arguments.into_iter()
+                .map(|(name, colon, value_position, value)| {
+                    colons.push(colon);
+                    (name, value_position, value)
+                })
+                .collect();
+            (arguments, Some(ArgumentsSyntax { parens: (open, close), colons }))
+        }
+        None => (Vec::new(), None),
+    })
+    .parse_stream(input)
+    .map_err(|e| input.rule_failed("Arguments", e))
+}
+
+/// Parses arguments of fields and fragment spreads
+#[allow(clippy::type_complexity)]
+pub fn field_arguments<'a, T>(input: &mut TokenStream<'a>)
+    -> ParseResult<(Vec<(T::Value, Value<'a, T>)>, Option<ArgumentsSyntax>),
+                   TokenStream<'a>>
+    where T: Text<'a>,
+{
+    parser(arguments).map(|(arguments, syntax)| {
+        let arguments = arguments.into_iter()
+            .map(|(name, _, value)| (name, value))
+            .collect();
+        (arguments, syntax)
     })
     .parse_stream(input)
 }
@@ -156,11 +860,11 @@ pub fn float_value<'a, S>(input: &mut TokenStream<'a>)
     .parse_stream(input)
 }
 
-pub fn unquote_block_string<'a>(src: &'a str)
-    -> Result<String, Error<Token<'a>, Token<'a>>>
-{
+/// Returns the common indentation of the lines of the block string (except
+/// the first one), which is removed when unquoting it
+pub fn block_string_indent(src: &str) -> usize {
     debug_assert!(src.starts_with("\"\"\"") && src.ends_with("\"\"\""));
-    let indent = src[3..src.len()-3].lines().skip(1)
+    src[3..src.len()-3].lines().skip(1)
         .filter_map(|line| {
             let trimmed = line.trim_start().len();
             if trimmed > 0 {
@@ -169,7 +873,13 @@ pub fn unquote_block_string<'a>(src: &'a str)
                 None  // skip whitespace-only lines
             }
         })
-        .min().unwrap_or(0);
+        .min().unwrap_or(0)
+}
+
+pub fn unquote_block_string<'a>(src: &'a str)
+    -> Result<String, Error<Token<'a>, Token<'a>>>
+{
+    let indent = block_string_indent(src);
     let mut result = String::with_capacity(src.len()-6);
     let mut lines = src[3..src.len()-3].lines();
     if let Some(first) = lines.next() {
@@ -194,12 +904,11 @@ pub fn unquote_block_string<'a>(src: &'a str)
     Ok(result)
 }
 
-pub fn unquote_string<'a>(s: &'a str) -> Result<String, Error<Token, Token>>
+pub fn unquote_string(s: &str) -> Result<String, Error<Token<'_>, Token<'_>>>
 {
     let mut res = String::with_capacity(s.len());
     debug_assert!(s.starts_with('"') && s.ends_with('"'));
     let mut chars = s[1..s.len()-1].chars();
-    let mut temp_code_point = String::with_capacity(4);
     while let Some(c) = chars.next() {
         match c {
             '\\' => {
@@ -210,26 +919,7 @@ pub fn unquote_string<'a>(s: &'a str) -> Result<String, Error<Token, Token>>
                     'n' => res.push('\n'),
                     'r' => res.push('\r'),
                     't' => res.push('\t'),
-                    'u' => {
-                        temp_code_point.clear();
-                        for _ in 0..4 {
-                            match chars.next() {
-                                Some(inner_c) => temp_code_point.push(inner_c),
-                                None => return Err(Error::unexpected_message(
-                                    format_args!("\\u must have 4 characters after it, only found '{}'", temp_code_point)
-                                )),
-                            }
-                        }
-
-                        // convert our hex string into a u32, then convert that into a char
-                        match u32::from_str_radix(&temp_code_point, 16).map(std::char::from_u32) {
-                            Ok(Some(unicode_char)) => res.push(unicode_char),
-                            _ => {
-                                return Err(Error::unexpected_message(
-                                    format_args!("{} is not a valid unicode code point", temp_code_point)))
-                            }
-                        }
-                    },
+                    'u' => res.push(unicode_escape(&mut chars)?),
                     c => {
                         return Err(Error::unexpected_message(
                             format_args!("bad escaped char {:?}", c)));
@@ -243,6 +933,82 @@ pub fn unquote_string<'a>(s: &'a str) -> Result<String, Error<Token, Token>>
     Ok(res)
 }
 
+/// Parses the code point of the escape sequence following `\u`
+///
+/// It's either a variable-width `{1F600}` or a fixed-width `D83D` (when
+/// it's a leading surrogate, it must be followed by `\uXXXX` with the
+/// trailing one).
+fn unicode_escape<'a>(chars: &mut std::str::Chars)
+    -> Result<char, Error<Token<'a>, Token<'a>>>
+{
+    let rest = chars.as_str();
+    if let Some(body) = rest.strip_prefix('{') {
+        let end = body.find('}').ok_or_else(|| Error::unexpected_message(
+            "\\u{ must be closed by }"))?;
+        let hex = &body[..end];
+        *chars = body[end+1..].chars();
+        return code_point(hex).and_then(|code| {
+            if (0xD800..=0xDFFF).contains(&code) {
+                Err(Error::unexpected_message(format_args!(
+                    "surrogate {} is not a valid unicode code point, \
+                     only fixed-width surrogate pairs are allowed", hex)))
+            } else {
+                char(hex, code)
+            }
+        });
+    }
+    let hex = fixed_width(chars)?;
+    let code = code_point(&hex)?;
+    match code {
+        0xD800..=0xDBFF => {
+            let trailing = match chars.as_str().strip_prefix("\\u") {
+                Some(tail) => {
+                    *chars = tail.chars();
+                    let hex = fixed_width(chars)?;
+                    Some(code_point(&hex)?)
+                }
+                None => None,
+            };
+            match trailing {
+                Some(low @ 0xDC00..=0xDFFF) => {
+                    char(&hex, 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00))
+                }
+                _ => Err(Error::unexpected_message(format_args!(
+                    "leading surrogate {} must be followed by \
+                     a trailing surrogate", hex))),
+            }
+        }
+        0xDC00..=0xDFFF => Err(Error::unexpected_message(format_args!(
+            "trailing surrogate {} must follow a leading surrogate", hex))),
+        _ => char(&hex, code),
+    }
+}
+
+fn fixed_width<'a>(chars: &mut std::str::Chars)
+    -> Result<String, Error<Token<'a>, Token<'a>>>
+{
+    let hex = chars.by_ref().take(4).collect::<String>();
+    if hex.chars().count() < 4 {
+        return Err(Error::unexpected_message(format_args!(
+            "\\u must have 4 characters after it, only found '{}'", hex)));
+    }
+    Ok(hex)
+}
+
+fn code_point<'a>(hex: &str) -> Result<u32, Error<Token<'a>, Token<'a>>> {
+    if hex.is_empty() || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(Error::unexpected_message(format_args!(
+            "{} is not a valid unicode code point", hex)));
+    }
+    u32::from_str_radix(hex, 16).map_err(|_| Error::unexpected_message(
+        format_args!("{} is not a valid unicode code point", hex)))
+}
+
+fn char<'a>(hex: &str, code: u32) -> Result<char, Error<Token<'a>, Token<'a>>> {
+    std::char::from_u32(code).ok_or_else(|| Error::unexpected_message(
+        format_args!("{} is not a valid unicode code point", hex)))
+}
+
 pub fn string<'a>(input: &mut TokenStream<'a>)
     -> ParseResult<String, TokenStream<'a>>
 {
@@ -252,11 +1018,43 @@ pub fn string<'a>(input: &mut TokenStream<'a>)
     )).parse_stream(input)
 }
 
+/// Parses a string keeping its source, for descriptions in the schema
+pub fn description<'a>(input: &mut TokenStream<'a>)
+    -> ParseResult<Description, TokenStream<'a>>
+{
+    choice((
+        kind(T::StringValue).and_then(|tok| {
+            unquote_string(tok.value).map(|value| Description {
+                value, raw: Some(tok.value.into()), indent: 0,
+            })
+        }),
+        kind(T::BlockString).and_then(|tok| {
+            unquote_block_string(tok.value).map(|value| Description {
+                value,
+                raw: Some(tok.value.into()),
+                indent: block_string_indent(tok.value),
+            })
+        }),
+    )).parse_stream(input)
+}
+
+/// Borrows the contents of the string unless there are escape sequences
+fn string_lexeme<'a, S>(s: &'a str)
+    -> Result<S::StringValue, Error<Token<'a>, Token<'a>>>
+    where S: Text<'a>,
+{
+    if s.contains('\\') {
+        unquote_string(s).map(S::StringValue::from)
+    } else {
+        Ok(S::StringValue::from(&s[1..s.len()-1]))
+    }
+}
+
 pub fn string_value<'a, S>(input: &mut TokenStream<'a>)
     -> ParseResult<Value<'a, S>, TokenStream<'a>>
     where S: Text<'a>,
 {
-    kind(T::StringValue).and_then(|tok| unquote_string(tok.value))
+    kind(T::StringValue).and_then(|tok| string_lexeme::<S>(tok.value))
         .map(Value::String)
     .parse_stream(input)
 }
@@ -266,7 +1064,7 @@ pub fn block_string_value<'a, S>(input: &mut TokenStream<'a>)
     where S: Text<'a>,
 {
     kind(T::BlockString).and_then(|tok| unquote_block_string(tok.value))
-        .map(Value::String)
+        .map(|s| Value::String(s.into()))
     .parse_stream(input)
 }
 
@@ -289,50 +1087,176 @@ pub fn value<'a, T>(input: &mut TokenStream<'a>)
     -> ParseResult<Value<'a, T>, TokenStream<'a>>
     where T: Text<'a>,
 {
-    parser(plain_value)
-    .or(punct("$").with(name::<'a, T>()).map(Value::Variable))
-    .or(punct("[").with(many(parser(value))).skip(punct("]"))
-        .map(Value::List))
-    .or(punct("{")
-        .with(many(name::<'a, T>().skip(punct(":")).and(parser(value))))
-        .skip(punct("}"))
-        .map(Value::Object))
-    .parse_stream(input)
+    nested_value(input, false)
 }
 
 pub fn default_value<'a, T>(input: &mut TokenStream<'a>)
     -> ParseResult<Value<'a, T>, TokenStream<'a>>
     where T: Text<'a>,
 {
-    parser(plain_value)
-    .or(punct("[").with(many(parser(default_value))).skip(punct("]"))
-        .map(Value::List))
-    .or(punct("{")
-        .with(many(name::<'a, T>().skip(punct(":")).and(parser(default_value))))
-        .skip(punct("}"))
-        .map(Value::Object))
-    .parse_stream(input)
+    nested_value(input, true)
 }
 
+/// Start of a value, items of lists and objects are parsed separately
+enum ValueStart<'a, T: Text<'a>> {
+    Scalar(Value<'a, T>),
+    List,
+    Object,
+}
+
+/// List or object value with the items parsed so far, objects also keep
+/// the name of the field whose value is being parsed
+enum Frame<'a, T: Text<'a>> {
+    List(Vec<Value<'a, T>>),
+    Object(Vec<(T::Value, Value<'a, T>)>, Option<T::Value>),
+}
+
+fn value_start<'a, T>(input: &mut TokenStream<'a>, constant: bool)
+    -> ParseResult<ValueStart<'a, T>, TokenStream<'a>>
+    where T: Text<'a>,
+{
+    if constant {
+        parser(plain_value).map(ValueStart::Scalar)
+        .or(punct("[").map(|_| ValueStart::List))
+        .or(punct("{").map(|_| ValueStart::Object))
+        .parse_stream(input)
+    } else {
+        parser(plain_value).map(ValueStart::Scalar)
+        .or(punct("$").with(name::<'a, T>())
+            .map(|name| ValueStart::Scalar(Value::Variable(name))))
+        .or(punct("[").map(|_| ValueStart::List))
+        .or(punct("{").map(|_| ValueStart::Object))
+        .parse_stream(input)
+    }
+}
+
+/// Parses the value keeping unfinished lists and objects on the heap
+/// rather than recursing, so nesting is limited by memory, not the stack
+///
+/// Variables are rejected when `constant` is set.
+fn nested_value<'a, T>(input: &mut TokenStream<'a>, constant: bool)
+    -> ParseResult<Value<'a, T>, TokenStream<'a>>
+    where T: Text<'a>,
+{
+    let mut stack = Vec::new();
+    let mut start = match value_start(input, constant) {
+        Ok(start) => start,
+        Err(e) => return Err(input.rule_failed("Value", e)),
+    };
+    loop {
+        let mut value = match start {
+            (ValueStart::Scalar(value), _) => Some(value),
+            (ValueStart::List, _) => {
+                stack.push(Frame::List(Vec::new()));
+                None
+            }
+            (ValueStart::Object, _) => {
+                stack.push(Frame::Object(Vec::new(), None));
+                None
+            }
+        };
+        // add finished values to their containers, until there is
+        // a container expecting the next item
+        let next = loop {
+            match (value.take(), stack.last_mut()) {
+                (Some(value), None) => {
+                    return Ok((value, Consumed::Consumed(())));
+                }
+                (Some(value), Some(&mut Frame::List(ref mut items))) => {
+                    items.push(value);
+                }
+                (Some(value), Some(&mut Frame::Object(ref mut fields,
+                                                     ref mut key))) => {
+                    let key = key.take().expect("field name is parsed");
+                    fields.push((key, value));
+                }
+                (None, _) => {}
+            }
+            let closed = match stack.last_mut() {
+                Some(&mut Frame::List(_)) => {
+                    match value_start(input, constant) {
+                        Err(Consumed::Empty(_)) => {
+                            punct("]").parse_stream(input).map(|_| ())
+                        }
+                        next => break next,
+                    }
+                }
+                Some(&mut Frame::Object(_, ref mut key)) => {
+                    match name::<'a, T>().skip(punct(":")).parse_stream(input)
+                    {
+                        Ok((name, _)) => {
+                            *key = Some(name);
+                            break value_start(input, constant);
+                        }
+                        Err(Consumed::Empty(field_err)) => {
+                            punct("}").parse_stream(input).map(|_| ())
+                            .map_err(|e| e.map(|e| Tracked {
+                                error: field_err.error.merge(e.error),
+                                offset: e.offset,
+                            }))
+                        }
+                        Err(e) => Err(e),
+                    }
+                }
+                None => unreachable!("value is returned on empty stack"),
+            };
+            if let Err(e) = closed {
+                return Err(input.rule_failed("Value", e.into_consumed()));
+            }
+            value = match stack.pop() {
+                Some(Frame::List(items)) => Some(Value::List(items)),
+                Some(Frame::Object(fields, _)) => Some(Value::Object(fields)),
+                None => unreachable!("container is on the stack"),
+            };
+        };
+        start = match next {
+            Ok(start) => start,
+            Err(e) => {
+                return Err(input.rule_failed("Value", e.into_consumed()));
+            }
+        };
+    }
+}
+
+/// Parses the type without recursion: the opening brackets of the list
+/// types are counted before the name, and closed after it
 pub fn parse_type<'a, T>(input: &mut TokenStream<'a>)
     -> ParseResult<Type<'a, T>, TokenStream<'a>>
     where T: Text<'a>,
 {
-    name::<'a, T>().map(Type::NamedType)
-    .or(punct("[")
-        .with(parser(parse_type))
-        .skip(punct("]"))
-        .map(Box::new)
-        .map(Type::ListType))
-    .and(optional(punct("!")).map(|v| v.is_some()))
-    .map(|(typ, strict)|
-        if strict {
-            Type::NonNullType(Box::new(typ))
-        } else {
-            typ
+    let mut lists = 0;
+    let name = loop {
+        let result = name::<'a, T>().map(Some)
+            .or(punct("[").map(|_| None))
+            .parse_stream(input);
+        match result {
+            Ok((Some(name), _)) => break name,
+            Ok((None, _)) => lists += 1,
+            Err(e) if lists == 0 => return Err(input.rule_failed("Type", e)),
+            Err(e) => {
+                return Err(input.rule_failed("Type", e.into_consumed()));
+            }
         }
-    )
-    .parse_stream(input)
+    };
+    let mut typ = non_null(input, Type::NamedType(name));
+    for _ in 0..lists {
+        if let Err(e) = punct("]").parse_stream(input) {
+            return Err(input.rule_failed("Type", e.into_consumed()));
+        }
+        typ = non_null(input, Type::ListType(Box::new(typ)));
+    }
+    Ok((typ, Consumed::Consumed(())))
+}
+
+/// Wraps the type into `NonNullType` if it's followed by `!`
+fn non_null<'a, T>(input: &mut TokenStream<'a>, typ: Type<'a, T>)
+    -> Type<'a, T>
+    where T: Text<'a>,
+{
+    match punct("!").parse_stream(input) {
+        Ok(_) => Type::NonNullType(Box::new(typ)),
+        Err(_) => typ,
+    }
 }
 
 #[cfg(test)]
@@ -340,12 +1264,126 @@ mod tests {
     use super::Number;
     use super::unquote_string;
 
+    #[cfg(feature = "query")]
+    #[test]
+    fn type_references() {
+        use crate::parse_query;
+        use crate::query::Type;
+
+        let parse = |s: &str| {
+            let q = format!("query($v: {}) {{ a }}", s);
+            let doc = parse_query::<String>(&q).unwrap().into_static();
+            let typ = doc.operations().next().unwrap()
+                .variable_definitions[0].var_type.clone();
+            typ
+        };
+        let typ: Type<String> = parse("[[Int!]]!");
+        assert_eq!(typ.innermost_name(), "Int");
+        assert!(typ.is_non_null());
+        assert!(typ.is_list());
+        assert_eq!(typ.list_depth(), 2);
+        assert!(!parse("Int!").is_list());
+        assert!(!parse("[Int]").is_non_null());
+        let compatible = |var: &str, loc: &str| {
+            parse(var).is_compatible_with(&parse(loc))
+        };
+        assert!(compatible("Int", "Int"));
+        assert!(compatible("Int!", "Int"));
+        assert!(compatible("[Int!]!", "[Int]"));
+        assert!(!compatible("Int", "Int!"));
+        assert!(!compatible("[Int]", "[Int!]"));
+        assert!(!compatible("Int", "[Int]"));
+        assert!(!compatible("[Int]", "Int"));
+        assert!(!compatible("Int", "Float"));
+    }
+
+    #[cfg(feature = "query")]
+    #[test]
+    fn directive_arguments() {
+        use std::collections::BTreeMap;
+        use crate::parse_query;
+        use crate::query::Value;
+
+        let doc = parse_query::<&str>(r#"
+            { a @d(flag: false, text: "t", kind: ASC, var: $v, null: null) }
+        "#).unwrap();
+        let field = doc.operations().next().unwrap()
+            .selection_set.fields().next().unwrap();
+        let dir = &field.directives[0];
+        let mut vars = BTreeMap::new();
+        vars.insert("v".to_string(), Value::<String>::String("DESC".into()));
+        assert_eq!(dir.bool_argument("flag", &vars), Ok(Some(false)));
+        assert_eq!(dir.string_argument("text", &vars), Ok(Some("t")));
+        assert_eq!(dir.enum_argument("kind", &vars), Ok(Some("ASC")));
+        assert_eq!(dir.enum_argument("var", &vars), Ok(Some("DESC")));
+        assert_eq!(dir.string_argument("var", &vars), Ok(Some("DESC")));
+        assert_eq!(dir.bool_argument("null", &vars), Ok(None));
+        assert_eq!(dir.bool_argument("missing", &vars), Ok(None));
+        assert!(dir.argument("missing").is_none());
+        let err = dir.bool_argument("var", &vars).unwrap_err();
+        assert_eq!(err.expected, "Boolean");
+        assert_eq!(err.position.column, 60);
+        assert!(dir.enum_argument("text", &vars).is_err());
+        assert!(dir.string_argument("kind", &vars).is_err());
+    }
+
+    #[test]
+    fn deep_clone_and_compare() {
+        use super::{Type, Value};
+
+        let depth = 1_000_000;
+        let nest = |leaf: i32| {
+            let mut value = Value::<&str>::Int(Number::from(leaf));
+            for i in 0..depth {
+                value = if i % 2 == 0 {
+                    Value::List(vec![Value::Null, value])
+                } else {
+                    Value::Object(vec![("a", value)])
+                };
+            }
+            value
+        };
+        let mut typ = Type::<&str>::NamedType("Int");
+        for i in 0..depth {
+            typ = if i % 2 == 0 {
+                Type::ListType(Box::new(typ))
+            } else {
+                Type::NonNullType(Box::new(typ))
+            };
+        }
+        let value = nest(1);
+        let mut values = vec![value.clone(), value, nest(2)];
+        let mut types = vec![typ.clone(), typ];
+        assert!(values[0] == values[1]);
+        assert!(values[1] != values[2]);
+        assert!(types[0] == types[1]);
+
+        // drop is recursive, so the values are taken apart in a loop
+        while let Some(mut value) = values.pop() {
+            match value {
+                Value::List(ref mut items) => values.append(items),
+                Value::Object(ref mut fields) => {
+                    values.extend(fields.drain(..).map(|(_, v)| v))
+                }
+                _ => {}
+            }
+        }
+        while let Some(typ) = types.pop() {
+            match typ {
+                Type::ListType(inner) | Type::NonNullType(inner) => {
+                    types.push(*inner)
+                }
+                Type::NamedType(_) => {}
+            }
+        }
+    }
+
     #[test]
     fn number_from_i32_and_to_i64_conversion() {
         assert_eq!(Number::from(1).as_i64(), Some(1));
         assert_eq!(Number::from(584).as_i64(), Some(584));
-        assert_eq!(Number::from(i32::min_value()).as_i64(), Some(i32::min_value() as i64));
-        assert_eq!(Number::from(i32::max_value()).as_i64(), Some(i32::max_value() as i64));
+        assert_eq!(Number::from(i32::MIN).as_i64(), Some(i32::MIN as i64));
+        assert_eq!(Number::from(i32::MAX).as_i64(), Some(i32::MAX as i64));
     }
 
     #[test]
@@ -360,4 +1398,41 @@ mod tests {
         // a more complex string
         assert_eq!(unquote_string(r#""\u0009 hello \u000A there""#).expect(""), "\u{0009} hello \u{000A} there");
     }
+
+    #[test]
+    fn unquote_variable_width_and_surrogates() {
+        assert_eq!(unquote_string(r#""\u{1F600} \u{0041}\u{d}""#).unwrap(),
+                   "\u{1F600} A\r");
+        assert_eq!(unquote_string(r#""\uD83D\uDE00!""#).unwrap(), "\u{1F600}!");
+        assert_eq!(unquote_string(r#""\ud83d\ude00""#).unwrap(), "\u{1F600}");
+        for bad in &[
+            r#""\u{}""#, r#""\u{110000}""#, r#""\u{D83D}""#, r#""\u{41""#,
+            r#""\u{FFFFFFFFF}""#, r#""\uD83D""#, r#""\uD83Dx""#,
+            r#""\uD83D\u0041""#, r#""\uDE00""#, r#""\u+041""#, r#""\u12""#,
+        ] {
+            assert!(unquote_string(bad).is_err(), "{} must fail", bad);
+        }
+    }
+
+    #[cfg(feature = "query")]
+    #[test]
+    fn borrowed_strings() {
+        use std::borrow::Cow;
+        use crate::query::{parse_query, Definition, Selection, Value};
+
+        let doc = parse_query::<&str>(r#"{ a(x: "plain", y: "esc\"", z: """
+            block
+        """) }"#).unwrap();
+        let args = match doc.definitions[0] {
+            Definition::Operation(ref op) => match op.selection_set.items[0] {
+                Selection::Field(ref f) => &f.arguments,
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        };
+        assert!(matches!(args[0].1, Value::String(Cow::Borrowed("plain"))));
+        assert!(matches!(args[1].1, Value::String(Cow::Owned(ref s))
+                         if s == "esc\""));
+        assert!(matches!(args[2].1, Value::String(Cow::Owned(_))));
+    }
 }
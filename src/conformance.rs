@@ -0,0 +1,261 @@
+//! Conformance test suite
+//!
+//! This module exposes the corpus of documents the crate is tested on, so
+//! that code using custom `ParserOptions` (or a fork of the parser) can
+//! check that standard documents are still parsed and formatted the same
+//! way:
+//!
+//! ```rust
+//! # extern crate edb_graphql_parser;
+//! use edb_graphql_parser::{conformance, ParserOptions};
+//!
+//! let report = conformance::run(&ParserOptions::default());
+//! assert!(report.is_ok(), "{}", report);
+//! ```
+use std::fmt;
+
+use crate::options::ParserOptions;
+use crate::query::parse_query;
+use crate::schema::parse_schema_with_options;
+
+
+/// Kind of the document in the test case
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Query,
+    Schema,
+}
+
+/// Expected result of parsing the document
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expected {
+    /// Document is parsed and formatted into this text
+    Output(&'static str),
+    /// Document fails to parse with this error message
+    Error(&'static str),
+}
+
+/// A single test case of the suite
+#[derive(Debug, Clone, Copy)]
+pub struct Case {
+    pub name: &'static str,
+    pub kind: Kind,
+    data: &'static str,
+    canonical: Option<&'static str>,
+    error: bool,
+}
+
+/// Test case that didn't pass
+#[derive(Debug, Clone)]
+pub struct Failure {
+    pub case: &'static Case,
+    pub message: String,
+}
+
+/// Results of running the suite
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub passed: usize,
+    pub failures: Vec<Failure>,
+}
+
+macro_rules! roundtrip {
+    ($kind:ident, $dir:expr, $name:expr) => {
+        Case {
+            name: $name,
+            kind: Kind::$kind,
+            data: include_str!(
+                concat!("../tests/", $dir, "/", $name, ".graphql")),
+            canonical: None,
+            error: false,
+        }
+    };
+    ($kind:ident, $dir:expr, $name:expr, canonical) => {
+        Case {
+            name: $name,
+            kind: Kind::$kind,
+            data: include_str!(
+                concat!("../tests/", $dir, "/", $name, ".graphql")),
+            canonical: Some(include_str!(
+                concat!("../tests/", $dir, "/", $name, "_canonical.graphql"))),
+            error: false,
+        }
+    };
+}
+
+macro_rules! query_error {
+    ($name:expr) => {
+        Case {
+            name: $name,
+            kind: Kind::Query,
+            data: include_str!(
+                concat!("../tests/query_errors/", $name, ".txt")),
+            canonical: None,
+            error: true,
+        }
+    };
+}
+
+/// All the test cases of the suite
+pub static CASES: &[Case] = &[
+    roundtrip!(Query, "queries", "directive_args"),
+    roundtrip!(Query, "queries", "fragment"),
+    roundtrip!(Query, "queries", "fragment_spread"),
+    roundtrip!(Query, "queries", "inline_fragment"),
+    roundtrip!(Query, "queries", "inline_fragment_dir"),
+    roundtrip!(Query, "queries", "kitchen-sink", canonical),
+    roundtrip!(Query, "queries", "minimal"),
+    roundtrip!(Query, "queries", "minimal_mutation"),
+    roundtrip!(Query, "queries", "minimal_query"),
+    roundtrip!(Query, "queries", "mutation_directive"),
+    roundtrip!(Query, "queries", "named_query"),
+    roundtrip!(Query, "queries", "nested_selection"),
+    roundtrip!(Query, "queries", "query_aliases"),
+    roundtrip!(Query, "queries", "query_arguments"),
+    roundtrip!(Query, "queries", "query_big_numbers"),
+    roundtrip!(Query, "queries", "query_directive"),
+    roundtrip!(Query, "queries", "query_list_argument"),
+    roundtrip!(Query, "queries", "query_object_argument"),
+    roundtrip!(Query, "queries", "query_var_default_float"),
+    roundtrip!(Query, "queries", "query_var_default_list"),
+    roundtrip!(Query, "queries", "query_var_default_object"),
+    roundtrip!(Query, "queries", "query_var_default_string"),
+    roundtrip!(Query, "queries", "query_var_defaults"),
+    roundtrip!(Query, "queries", "query_var_directives"),
+    roundtrip!(Query, "queries", "query_vars"),
+    roundtrip!(Query, "queries", "string_literal"),
+    roundtrip!(Query, "queries", "subscription_directive"),
+    roundtrip!(Query, "queries", "triple_quoted_literal"),
+    query_error!("bad_args"),
+    query_error!("invalid_curly_brace"),
+    roundtrip!(Schema, "schemas", "directive"),
+    roundtrip!(Schema, "schemas", "directive_repeatable"),
+    roundtrip!(Schema, "schemas", "directive_descriptions", canonical),
+    roundtrip!(Schema, "schemas", "default_values"),
+    roundtrip!(Schema, "schemas", "empty_union"),
+    roundtrip!(Schema, "schemas", "enum"),
+    roundtrip!(Schema, "schemas", "extend_enum"),
+    roundtrip!(Schema, "schemas", "extend_input", canonical),
+    roundtrip!(Schema, "schemas", "extend_interface"),
+    roundtrip!(Schema, "schemas", "extend_object"),
+    roundtrip!(Schema, "schemas", "extend_scalar"),
+    roundtrip!(Schema, "schemas", "extend_schema"),
+    roundtrip!(Schema, "schemas", "implements"),
+    roundtrip!(Schema, "schemas", "implements_amp", canonical),
+    roundtrip!(Schema, "schemas", "input_type"),
+    roundtrip!(Schema, "schemas", "interface"),
+    roundtrip!(Schema, "schemas", "interface_implements"),
+    roundtrip!(Schema, "schemas", "kitchen-sink", canonical),
+    roundtrip!(Schema, "schemas", "minimal"),
+    roundtrip!(Schema, "schemas", "minimal_type"),
+    roundtrip!(Schema, "schemas", "scalar_type"),
+    roundtrip!(Schema, "schemas", "simple_object"),
+    roundtrip!(Schema, "schemas", "union"),
+    roundtrip!(Schema, "schemas", "union_extension"),
+];
+
+impl Case {
+    /// Returns the text of the document
+    pub fn source(&self) -> &'static str {
+        if self.error {
+            self.data.split("\n---\n").next().unwrap()
+        } else {
+            self.data
+        }
+    }
+
+    /// Returns the expected result of parsing the document
+    pub fn expected(&self) -> Expected {
+        if self.error {
+            Expected::Error(self.data.split_once("\n---\n")
+                .map_or("", |(_, message)| message))
+        } else {
+            Expected::Output(self.canonical.unwrap_or(self.data))
+        }
+    }
+
+    /// Runs the test case returning description of the failure if any
+    pub fn run(&self, options: &ParserOptions) -> Result<(), String> {
+        let source = self.source();
+        let expected = self.expected();
+        let result = match self.kind {
+            Kind::Query => parse_query::<String>(source)
+                .map(|doc| doc.to_string())
+                .map_err(|e| e.to_string()),
+            Kind::Schema => parse_schema_with_options::<String>(source, options)
+                .map(|doc| doc.to_string())
+                .map_err(|e| e.to_string()),
+        };
+        match (result, expected) {
+            (Ok(ref out), Expected::Output(exp)) if out == exp => Ok(()),
+            (Err(ref err), Expected::Error(exp)) if err == exp => Ok(()),
+            (Ok(out), Expected::Output(exp)) => {
+                Err(format!("expected output:\n{}\ngot:\n{}", exp, out))
+            }
+            (Err(err), Expected::Error(exp)) => {
+                Err(format!("expected error:\n{}\ngot:\n{}", exp, err))
+            }
+            (Ok(out), Expected::Error(exp)) => {
+                Err(format!("expected error:\n{}\ngot output:\n{}", exp, out))
+            }
+            (Err(err), Expected::Output(_)) => {
+                Err(format!("unexpected error: {}", err))
+            }
+        }
+    }
+}
+
+impl Report {
+    /// Returns true if all test cases passed
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} passed, {} failed",
+                 self.passed, self.failures.len())?;
+        for failure in &self.failures {
+            writeln!(f, "{:?} {}: {}",
+                     failure.case.kind, failure.case.name, failure.message)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs all the test cases of the suite with the specified options
+///
+/// Options are used for parsing schema documents, queries have no options
+/// yet.
+pub fn run(options: &ParserOptions) -> Report {
+    let mut report = Report::default();
+    for case in CASES {
+        match case.run(options) {
+            Ok(()) => report.passed += 1,
+            Err(message) => report.failures.push(Failure { case, message }),
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ParserOptions;
+    use super::{run, CASES, Expected};
+
+    #[test]
+    fn default_options() {
+        let report = run(&ParserOptions::default());
+        assert!(report.is_ok(), "{}", report);
+        assert_eq!(report.passed, CASES.len());
+    }
+
+    #[test]
+    fn error_case() {
+        let case = CASES.iter().find(|c| c.name == "bad_args").unwrap();
+        assert!(case.source().starts_with("query MyQuery {"));
+        assert!(matches!(case.expected(),
+            Expected::Error(e) if e.starts_with("query parse error")));
+    }
+}
@@ -0,0 +1,257 @@
+//! Reporting errors with `miette`
+//!
+//! Parse and validation errors implement `miette::Diagnostic` with a code
+//! and help text. Errors don't keep the source, so wrap them into
+//! `SourceDiagnostic` to render the source with labels at the positions:
+//!
+//! ```rust
+//! # extern crate edb_graphql_parser;
+//! # extern crate miette;
+//! use edb_graphql_parser::diagnostic::SourceDiagnostic;
+//! use edb_graphql_parser::parse_query;
+//!
+//! let source = "query Q($a: Int, b: Int) { a }";
+//! let err = parse_query::<&str>(source).unwrap_err();
+//! let report = miette::Report::new(
+//!     SourceDiagnostic::new(err, "query.graphql", source));
+//! assert_eq!(report.to_string(), "unexpected `b`");
+//! ```
+use std::error::Error;
+use std::fmt;
+
+use miette::{Diagnostic, LabeledSpan, NamedSource, SourceCode};
+
+use crate::position::{LineIndex, Pos};
+#[cfg(feature = "query")]
+use crate::query;
+#[cfg(feature = "schema")]
+use crate::schema;
+use crate::validation::ValidationError;
+
+
+/// Error that refers to positions in the source
+pub trait Located: Diagnostic {
+    /// Message without positions
+    fn summary(&self) -> String;
+    /// Spans in the source with optional label text
+    fn spans(&self) -> Vec<(Pos, Pos, Option<String>)>;
+}
+
+/// Error along with the source it refers to
+///
+/// Displays the short message of the error, and reports the source with
+/// labels on the positions of the error.
+#[derive(Debug)]
+pub struct SourceDiagnostic<E> {
+    error: E,
+    summary: String,
+    source: NamedSource<String>,
+    labels: Vec<LabeledSpan>,
+}
+
+impl<E: Located> SourceDiagnostic<E> {
+    pub fn new(error: E, name: impl AsRef<str>, source: impl Into<String>)
+        -> SourceDiagnostic<E>
+    {
+        let source = source.into();
+        let index = LineIndex::new(&source);
+        let labels = error.spans().into_iter().enumerate()
+            .filter_map(|(idx, (start, end, label))| {
+                let start = index.character_offset(start)?;
+                let end = index.character_offset(end)?.max(start);
+                Some(if idx == 0 {
+                    LabeledSpan::new_primary_with_span(label,
+                        (start, end - start))
+                } else {
+                    LabeledSpan::new_with_span(label, (start, end - start))
+                })
+            })
+            .collect();
+        SourceDiagnostic {
+            summary: error.summary(),
+            source: NamedSource::new(name, source),
+            error, labels,
+        }
+    }
+
+    /// Returns the wrapped error
+    pub fn error(&self) -> &E {
+        &self.error
+    }
+
+    pub fn into_error(self) -> E {
+        self.error
+    }
+}
+
+impl<E> fmt::Display for SourceDiagnostic<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.summary)
+    }
+}
+
+impl<E: fmt::Debug> Error for SourceDiagnostic<E> {}
+
+impl<E: Located> Diagnostic for SourceDiagnostic<E> {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.error.code()
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.error.help()
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        Some(&self.source)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        Some(Box::new(self.labels.iter().cloned()))
+    }
+}
+
+/// Help text of a parse error: expected tokens and the rule being parsed
+#[cfg(any(feature = "query", feature = "schema"))]
+fn parse_help(expected: &[String], rule: Option<&str>) -> Option<String> {
+    let mut help = match *expected {
+        [] => String::new(),
+        [ref single] => format!("expected `{}`", single),
+        [ref init @ .., ref last] => {
+            let init = init.iter().map(|e| format!("`{}`", e))
+                .collect::<Vec<_>>();
+            format!("expected {} or `{}`", init.join(", "), last)
+        }
+    };
+    if let Some(rule) = rule {
+        if help.is_empty() {
+            help.push_str("error");
+        }
+        help.push_str(" while parsing ");
+        help.push_str(rule);
+    }
+    if help.is_empty() { None } else { Some(help) }
+}
+
+#[cfg(any(feature = "query", feature = "schema"))]
+fn parse_label(found: Option<&str>) -> Option<String> {
+    found.map(|value| format!("unexpected `{}`", value))
+}
+
+#[cfg(feature = "query")]
+impl Diagnostic for query::ParseError {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        Some(Box::new(format!("graphql::parse::{}", self.code().as_str())))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        parse_help(self.expected(), self.rule())
+            .map(|h| Box::new(h) as Box<dyn fmt::Display>)
+    }
+}
+
+#[cfg(feature = "query")]
+impl Located for query::ParseError {
+    fn summary(&self) -> String {
+        self.description().to_string()
+    }
+
+    fn spans(&self) -> Vec<(Pos, Pos, Option<String>)> {
+        let (start, end) = self.span();
+        vec![(start, end, parse_label(self.found().map(|(_, v)| v)))]
+    }
+}
+
+#[cfg(feature = "schema")]
+impl Diagnostic for schema::ParseError {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        Some(Box::new(format!("graphql::parse::{}", self.code().as_str())))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        parse_help(self.expected(), self.rule())
+            .map(|h| Box::new(h) as Box<dyn fmt::Display>)
+    }
+}
+
+#[cfg(feature = "schema")]
+impl Located for schema::ParseError {
+    fn summary(&self) -> String {
+        self.description().to_string()
+    }
+
+    fn spans(&self) -> Vec<(Pos, Pos, Option<String>)> {
+        let (start, end) = self.span();
+        vec![(start, end, parse_label(self.found().map(|(_, v)| v)))]
+    }
+}
+
+impl Diagnostic for ValidationError {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        Some(Box::new(format!("graphql::validation::{}",
+                              self.rule.as_str())))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        Some(Box::new(format!("see the {} rule of the GraphQL specification",
+                              self.rule.as_str())))
+    }
+}
+
+impl Located for ValidationError {
+    fn summary(&self) -> String {
+        self.message.clone()
+    }
+
+    fn spans(&self) -> Vec<(Pos, Pos, Option<String>)> {
+        self.positions.iter().map(|&pos| (pos, pos, None)).collect()
+    }
+}
+
+#[cfg(all(test, feature = "query", feature = "schema"))]
+mod test {
+    use miette::{Diagnostic, Report};
+
+    use crate::{parse_query, parse_schema};
+    use crate::validation::validate;
+    use super::SourceDiagnostic;
+
+    fn labels(diag: &dyn Diagnostic) -> Vec<(usize, usize, Option<String>)> {
+        diag.labels().unwrap().map(|l| {
+            (l.offset(), l.len(), l.label().map(|s| s.to_string()))
+        }).collect()
+    }
+
+    #[test]
+    fn parse_errors() {
+        let source = "query Q {\n  a(s: \"é\", x: [1, 2) }";
+        let err = parse_query::<&str>(source).unwrap_err();
+        assert_eq!(Diagnostic::code(&err).unwrap().to_string(),
+                   "graphql::parse::unexpected_token");
+        let diag = SourceDiagnostic::new(err, "q.graphql", source);
+        assert_eq!(diag.to_string(), "unexpected `)`");
+        assert_eq!(diag.help().unwrap().to_string(),
+                   "expected `]` while parsing Value");
+        assert_eq!(labels(&diag),
+                   vec![(31, 1, Some("unexpected `)`".into()))]);
+
+        let source = "type A { f: \"unterminated }";
+        let err = parse_schema::<&str>(source).unwrap_err();
+        let diag = SourceDiagnostic::new(err, "s.graphql", source);
+        assert_eq!(diag.to_string(), "unterminated string value");
+        assert_eq!(diag.code().unwrap().to_string(),
+                   "graphql::parse::unterminated_string");
+        assert_eq!(labels(&diag), vec![(12, 0, None)]);
+        assert!(Report::new(diag).source_code().is_some());
+    }
+
+    #[test]
+    fn validation_errors() {
+        let source = "query A { a } query A { b }";
+        let doc = parse_query::<&str>(source).unwrap();
+        let err = validate(&doc).unwrap_err().remove(0);
+        let diag = SourceDiagnostic::new(err, "q.graphql", source);
+        assert_eq!(diag.code().unwrap().to_string(),
+                   "graphql::validation::UniqueOperationNames");
+        assert_eq!(labels(&diag), vec![(0, 0, None), (14, 0, None)]);
+    }
+}
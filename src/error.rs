@@ -0,0 +1,216 @@
+//! Details of parse errors shared by query and schema parsers
+#[cfg(any(feature = "query", feature = "schema"))]
+use std::fmt;
+
+#[cfg(any(feature = "query", feature = "schema"))]
+use combine::easy::{Error, Errors, Info};
+
+#[cfg(any(feature = "query", feature = "schema"))]
+use crate::tokenizer::{Kind, Token};
+#[cfg(any(feature = "query", feature = "schema"))]
+use crate::position::Pos;
+
+
+/// Category of a parse error
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    /// A token that doesn't fit the grammar
+    UnexpectedToken,
+    /// Source ended in the middle of a definition
+    UnexpectedEndOfInput,
+    /// A character that can't start any token
+    UnexpectedCharacter,
+    /// String or block string without the closing quotes
+    UnterminatedString,
+    /// Malformed integer or float literal
+    InvalidNumber,
+    /// Bad escape sequence in a string literal
+    InvalidEscape,
+    /// Definition that is well-formed syntactically but violates a rule of
+    /// the grammar, like duplicate operation types in a schema definition
+    InvalidDefinition,
+    /// Syntax that must be enabled in the `ParserOptions`
+    ExperimentalSyntax,
+    /// Anonymous operation which is not the only one in the document
+    AnonymousOperation,
+    /// Operation or fragment with the same name as a previous one
+    DuplicateName,
+}
+
+#[cfg(any(feature = "query", feature = "schema"))]
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SyntaxError {
+    pub code: ErrorCode,
+    pub span: (Pos, Pos),
+    pub found: Option<(Kind, String)>,
+    pub expected: Vec<String>,
+    pub rule: Option<&'static str>,
+    /// Message without the position and expected tokens
+    pub description: String,
+    /// Name of the parsed source, see `ParserOptions::source_name`
+    pub source: Option<String>,
+    /// Other positions the error refers to
+    pub related: Vec<Pos>,
+    message: String,
+}
+
+impl ErrorCode {
+    /// Returns the name of the code in snake case, e.g. `unexpected_token`
+    pub fn as_str(&self) -> &'static str {
+        use self::ErrorCode::*;
+        match *self {
+            UnexpectedToken => "unexpected_token",
+            UnexpectedEndOfInput => "unexpected_end_of_input",
+            UnexpectedCharacter => "unexpected_character",
+            UnterminatedString => "unterminated_string",
+            InvalidNumber => "invalid_number",
+            InvalidEscape => "invalid_escape",
+            InvalidDefinition => "invalid_definition",
+            ExperimentalSyntax => "experimental_syntax",
+            AnonymousOperation => "anonymous_operation",
+            DuplicateName => "duplicate_name",
+        }
+    }
+}
+
+#[cfg(any(feature = "query", feature = "schema"))]
+impl SyntaxError {
+    #[cfg(feature = "query")]
+    pub fn at(code: ErrorCode, position: Pos, message: &str) -> SyntaxError {
+        SyntaxError {
+            code,
+            span: (position, position),
+            found: None,
+            expected: Vec::new(),
+            rule: None,
+            description: message.to_string(),
+            source: None,
+            related: Vec::new(),
+            message: format!("Parse error at {}\n{}\n", position, message),
+        }
+    }
+
+    /// Converts combine errors, `rule` is the innermost grammar rule that
+    /// failed after consuming input (if it failed at the same position)
+    pub fn from_errors(e: Errors<Token, Token, Pos>,
+        rule: Option<&'static str>)
+        -> SyntaxError
+    {
+        let mut code = None;
+        let mut found = None;
+        let mut description = None;
+        let mut expected = Vec::new();
+        for err in &e.errors {
+            match *err {
+                Error::Unexpected(Info::Token(tok)) => {
+                    found = Some((tok.kind, tok.value.to_string()));
+                }
+                Error::Unexpected(ref info) | Error::Message(ref info) => {
+                    let text = info_text(info);
+                    code = code.or_else(|| classify(&text));
+                    if text != "end of input" {
+                        description = description.or(Some(text));
+                    }
+                }
+                Error::Expected(ref info) => {
+                    let text = info_text(info);
+                    if !expected.contains(&text) {
+                        expected.push(text);
+                    }
+                }
+                Error::Other(_) => {}
+            }
+        }
+        let end = match found {
+            Some((_, ref value)) => advance(e.position, value),
+            None => e.position,
+        };
+        let description = match (description, &found) {
+            (Some(text), _) => text,
+            (None, &Some((_, ref value))) => format!("unexpected `{}`", value),
+            (None, &None) => "unexpected end of input".to_string(),
+        };
+        SyntaxError {
+            code: code.unwrap_or(if found.is_some() {
+                ErrorCode::UnexpectedToken
+            } else {
+                ErrorCode::InvalidDefinition
+            }),
+            span: (e.position, end),
+            found,
+            expected,
+            rule,
+            description,
+            source: None,
+            related: Vec::new(),
+            message: e.to_string(),
+        }
+    }
+
+    /// Sets the name of the source and adds it to the position in the
+    /// message
+    pub fn set_source(&mut self, name: &str) {
+        let position = self.span.0;
+        self.message = self.message.replacen(
+            &format!("Parse error at {}", position),
+            &format!("Parse error at {}:{}", name, position), 1);
+        self.source = Some(name.to_string());
+    }
+}
+
+#[cfg(any(feature = "query", feature = "schema"))]
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+#[cfg(any(feature = "query", feature = "schema"))]
+fn info_text(info: &Info<Token, Token>) -> String {
+    match *info {
+        Info::Token(tok) | Info::Range(tok) => tok.value.to_string(),
+        Info::Owned(ref s) => s.clone(),
+        Info::Borrowed(s) => s.to_string(),
+    }
+}
+
+/// Recognizes messages of the tokenizer and of string unescaping
+#[cfg(any(feature = "query", feature = "schema"))]
+fn classify(message: &str) -> Option<ErrorCode> {
+    use self::ErrorCode::*;
+
+    if message == "end of input" {
+        Some(UnexpectedEndOfInput)
+    } else if message.starts_with("unterminated") {
+        Some(UnterminatedString)
+    } else if message.starts_with("unsupported") {
+        Some(InvalidNumber)
+    } else if message.starts_with("unexpected character") ||
+        message.starts_with("bare dot")
+    {
+        Some(UnexpectedCharacter)
+    } else if message.starts_with("bad escaped char") ||
+        message.starts_with("\\u") ||
+        message.contains("surrogate") ||
+        message.ends_with("is not a valid unicode code point")
+    {
+        Some(InvalidEscape)
+    } else {
+        None
+    }
+}
+
+/// Position after the token starting at `pos`
+#[cfg(any(feature = "query", feature = "schema"))]
+fn advance(mut pos: Pos, value: &str) -> Pos {
+    for c in value.chars() {
+        if c == '\n' {
+            pos.line += 1;
+            pos.column = 1;
+        } else {
+            pos.column += 1;
+        }
+        pos.character += 1;
+    }
+    pos
+}
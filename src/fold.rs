@@ -0,0 +1,776 @@
+//! Converting the AST to another `Text` type, see `Document::map_text`
+use crate::common::{ArgumentsSyntax, Directive, DirectiveArgument};
+use crate::common::{Text, Type, Value};
+use crate::position::Pos;
+#[cfg(feature = "query")]
+use crate::query::{self, DefaultValue, FragmentSpread, InlineFragment};
+#[cfg(feature = "query")]
+use crate::query::{Selection, SelectionSet, TypeCondition};
+#[cfg(feature = "query")]
+use crate::query::VariableDefinition;
+#[cfg(feature = "schema")]
+use crate::schema::{self, CustomDefinition, DirectiveDefinition, EnumValue};
+#[cfg(feature = "schema")]
+use crate::schema::{InputValue, TypeDefinition, TypeExtension};
+#[cfg(feature = "schema")]
+use crate::schema::{ScalarType, ObjectType, InterfaceType, UnionType};
+#[cfg(feature = "schema")]
+use crate::schema::{EnumType, InputObjectType, ScalarTypeExtension};
+#[cfg(feature = "schema")]
+use crate::schema::{ObjectTypeExtension, InterfaceTypeExtension};
+#[cfg(feature = "schema")]
+use crate::schema::{UnionTypeExtension, EnumTypeExtension};
+#[cfg(feature = "schema")]
+use crate::schema::{InputObjectTypeExtension};
+#[cfg(feature = "schema")]
+use crate::schema::{SchemaDefinition, SchemaExtension};
+
+
+/// Function mapping names to the values of the new text type
+type NameFn<'f, 'x, 'b, U> = dyn FnMut(&'x str) -> <U as Text<'b>>::Value + 'f;
+
+/// Conversion of the names, and optionally of the positions
+struct Names<'f, 'x, 'b, U: Text<'b>> {
+    map: &'f mut NameFn<'f, 'x, 'b, U>,
+    /// Positions are replaced by the default ones and the syntax details
+    /// are dropped when `false`, so the nodes can be compared ignoring them
+    positions: bool,
+}
+
+impl<'f, 'x, 'b, U: Text<'b>> Names<'f, 'x, 'b, U> {
+    fn pos(&self, pos: Pos) -> Pos {
+        if self.positions { pos } else { Pos::default() }
+    }
+
+    #[cfg(feature = "query")]
+    fn span(&self, (start, end): (Pos, Pos)) -> (Pos, Pos) {
+        (self.pos(start), self.pos(end))
+    }
+
+    fn syntax(&self, syntax: &Option<ArgumentsSyntax>)
+        -> Option<ArgumentsSyntax>
+    {
+        syntax.as_ref().filter(|_| self.positions).cloned()
+    }
+}
+
+/// Node that can be converted to the text type `U`
+trait MapText<'x, 'b, U: Text<'b>> {
+    type Output;
+    fn convert(&'x self, names: &mut Names<'_, 'x, 'b, U>) -> Self::Output;
+}
+
+impl<'x, 'b, U, X> MapText<'x, 'b, U> for Vec<X>
+    where U: Text<'b>,
+          X: MapText<'x, 'b, U>,
+{
+    type Output = Vec<X::Output>;
+    fn convert(&'x self, names: &mut Names<'_, 'x, 'b, U>) -> Self::Output {
+        self.iter().map(|item| item.convert(names)).collect()
+    }
+}
+
+impl<'x, 'b, U, X> MapText<'x, 'b, U> for Option<X>
+    where U: Text<'b>,
+          X: MapText<'x, 'b, U>,
+{
+    type Output = Option<X::Output>;
+    fn convert(&'x self, names: &mut Names<'_, 'x, 'b, U>) -> Self::Output {
+        self.as_ref().map(|item| item.convert(names))
+    }
+}
+
+/// Names are stored as bare `T::Value`, so they are converted by these
+/// helpers rather than by the trait
+fn name<'x, 'b, N, U>(name: &'x N, names: &mut Names<'_, 'x, 'b, U>)
+    -> U::Value
+    where N: AsRef<str>,
+          U: Text<'b>,
+{
+    (names.map)(name.as_ref())
+}
+
+#[cfg(any(feature = "query", feature = "schema"))]
+fn opt_name<'x, 'b, N, U>(name: &'x Option<N>,
+    names: &mut Names<'_, 'x, 'b, U>)
+    -> Option<U::Value>
+    where N: AsRef<str>,
+          U: Text<'b>,
+{
+    name.as_ref().map(|n| (names.map)(n.as_ref()))
+}
+
+#[cfg(feature = "schema")]
+fn name_list<'x, 'b, N, U>(list: &'x [N], names: &mut Names<'_, 'x, 'b, U>)
+    -> Vec<U::Value>
+    where N: AsRef<str>,
+          U: Text<'b>,
+{
+    list.iter().map(|n| (names.map)(n.as_ref())).collect()
+}
+
+fn arguments<'x, 'a, 'b, T, U>(args: &'x [(T::Value, Value<'a, T>)],
+    names: &mut Names<'_, 'x, 'b, U>)
+    -> Vec<(U::Value, Value<'b, U>)>
+    where T: Text<'a>,
+          U: Text<'b>,
+{
+    args.iter()
+        .map(|(key, value)| (name(key, names), value.convert(names)))
+        .collect()
+}
+
+impl<'x, 'a, 'b, T, U> MapText<'x, 'b, U> for Value<'a, T>
+    where T: Text<'a>,
+          U: Text<'b>,
+{
+    type Output = Value<'b, U>;
+    fn convert(&'x self, names: &mut Names<'_, 'x, 'b, U>) -> Value<'b, U> {
+        match *self {
+            Value::Variable(ref var) => Value::Variable(name(var, names)),
+            Value::Int(ref num) => Value::Int(num.clone()),
+            Value::Float(ref num) => Value::Float(num.clone()),
+            Value::String(ref s) => {
+                Value::String(s.as_ref().to_string().into())
+            }
+            Value::Boolean(val) => Value::Boolean(val),
+            Value::Null => Value::Null,
+            Value::Enum(ref val) => Value::Enum(name(val, names)),
+            Value::List(ref items) => Value::List(items.convert(names)),
+            Value::Object(ref fields) => {
+                Value::Object(arguments(fields, names))
+            }
+        }
+    }
+}
+
+impl<'x, 'a, 'b, T, U> MapText<'x, 'b, U> for Type<'a, T>
+    where T: Text<'a>,
+          U: Text<'b>,
+{
+    type Output = Type<'b, U>;
+    fn convert(&'x self, names: &mut Names<'_, 'x, 'b, U>) -> Type<'b, U> {
+        match *self {
+            Type::NamedType(ref n) => Type::NamedType(name(n, names)),
+            Type::ListType(ref inner) => {
+                Type::ListType(Box::new(inner.convert(names)))
+            }
+            Type::NonNullType(ref inner) => {
+                Type::NonNullType(Box::new(inner.convert(names)))
+            }
+        }
+    }
+}
+
+impl<'x, 'a, 'b, T, U> MapText<'x, 'b, U> for Directive<'a, T>
+    where T: Text<'a>,
+          U: Text<'b>,
+{
+    type Output = Directive<'b, U>;
+    fn convert(&'x self, names: &mut Names<'_, 'x, 'b, U>) -> Directive<'b, U> {
+        Directive {
+            position: names.pos(self.position),
+            name: name(&self.name, names),
+            arguments: self.arguments.iter().map(|arg| DirectiveArgument {
+                name: name(&arg.name, names),
+                value: arg.value.convert(names),
+                value_position: names.pos(arg.value_position),
+            }).collect(),
+            arguments_syntax: names.syntax(&self.arguments_syntax),
+        }
+    }
+}
+
+#[cfg(feature = "query")]
+impl<'x, 'a, 'b, T, U> MapText<'x, 'b, U> for query::Definition<'a, T>
+    where T: Text<'a>,
+          U: Text<'b>,
+{
+    type Output = query::Definition<'b, U>;
+    fn convert(&'x self, names: &mut Names<'_, 'x, 'b, U>) -> Self::Output {
+        use crate::query::Definition::*;
+        match *self {
+            Operation(ref op) => Operation(op.convert(names)),
+            Fragment(ref frag) => Fragment(frag.convert(names)),
+        }
+    }
+}
+
+#[cfg(feature = "query")]
+impl<'x, 'a, 'b, T, U> MapText<'x, 'b, U> for query::Operation<'a, T>
+    where T: Text<'a>,
+          U: Text<'b>,
+{
+    type Output = query::Operation<'b, U>;
+    fn convert(&'x self, names: &mut Names<'_, 'x, 'b, U>) -> Self::Output {
+        query::Operation {
+            kind: self.kind.clone(),
+            position: names.pos(self.position),
+            name: opt_name(&self.name, names),
+            variable_definitions: self.variable_definitions.convert(names),
+            insert_variables: query::InsertVars {
+                kind: self.insert_variables.kind.clone(),
+                position: names.pos(self.insert_variables.position),
+            },
+            directives: self.directives.convert(names),
+            selection_set: self.selection_set.convert(names),
+        }
+    }
+}
+
+#[cfg(feature = "query")]
+impl<'x, 'a, 'b, T, U> MapText<'x, 'b, U> for query::FragmentDefinition<'a, T>
+    where T: Text<'a>,
+          U: Text<'b>,
+{
+    type Output = query::FragmentDefinition<'b, U>;
+    fn convert(&'x self, names: &mut Names<'_, 'x, 'b, U>) -> Self::Output {
+        query::FragmentDefinition {
+            position: names.pos(self.position),
+            name: name(&self.name, names),
+            variable_definitions: self.variable_definitions.convert(names),
+            on_position: names.pos(self.on_position),
+            type_condition: self.type_condition.convert(names),
+            directives: self.directives.convert(names),
+            selection_set: self.selection_set.convert(names),
+        }
+    }
+}
+
+#[cfg(feature = "query")]
+impl<'x, 'a, 'b, T, U> MapText<'x, 'b, U> for VariableDefinition<'a, T>
+    where T: Text<'a>,
+          U: Text<'b>,
+{
+    type Output = VariableDefinition<'b, U>;
+    fn convert(&'x self, names: &mut Names<'_, 'x, 'b, U>) -> Self::Output {
+        VariableDefinition {
+            position: names.pos(self.position),
+            name: name(&self.name, names),
+            var_type: self.var_type.convert(names),
+            default_value: self.default_value.as_ref().map(|d| DefaultValue {
+                span: names.span(d.span),
+                value: d.value.convert(names),
+            }),
+            directives: self.directives.convert(names),
+        }
+    }
+}
+
+#[cfg(feature = "query")]
+impl<'x, 'a, 'b, T, U> MapText<'x, 'b, U> for SelectionSet<'a, T>
+    where T: Text<'a>,
+          U: Text<'b>,
+{
+    type Output = SelectionSet<'b, U>;
+    fn convert(&'x self, names: &mut Names<'_, 'x, 'b, U>) -> Self::Output {
+        SelectionSet {
+            span: names.span(self.span),
+            items: self.items.convert(names),
+        }
+    }
+}
+
+#[cfg(feature = "query")]
+impl<'x, 'a, 'b, T, U> MapText<'x, 'b, U> for Selection<'a, T>
+    where T: Text<'a>,
+          U: Text<'b>,
+{
+    type Output = Selection<'b, U>;
+    fn convert(&'x self, names: &mut Names<'_, 'x, 'b, U>) -> Self::Output {
+        match *self {
+            Selection::Field(ref field) => {
+                Selection::Field(field.convert(names))
+            }
+            Selection::FragmentSpread(ref spread) => {
+                Selection::FragmentSpread(FragmentSpread {
+                    position: names.pos(spread.position),
+                    fragment_name: name(&spread.fragment_name, names),
+                    arguments: arguments(&spread.arguments, names),
+                    arguments_syntax: names.syntax(&spread.arguments_syntax),
+                    directives: spread.directives.convert(names),
+                })
+            }
+            Selection::InlineFragment(ref frag) => {
+                Selection::InlineFragment(InlineFragment {
+                    position: names.pos(frag.position),
+                    on_position: frag.on_position.filter(|_| names.positions),
+                    type_condition: frag.type_condition.convert(names),
+                    directives: frag.directives.convert(names),
+                    selection_set: frag.selection_set.convert(names),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(feature = "query")]
+impl<'x, 'a, 'b, T, U> MapText<'x, 'b, U> for query::Field<'a, T>
+    where T: Text<'a>,
+          U: Text<'b>,
+{
+    type Output = query::Field<'b, U>;
+    fn convert(&'x self, names: &mut Names<'_, 'x, 'b, U>) -> Self::Output {
+        query::Field {
+            position: names.pos(self.position),
+            alias: opt_name(&self.alias, names),
+            name: name(&self.name, names),
+            arguments: arguments(&self.arguments, names),
+            arguments_syntax: names.syntax(&self.arguments_syntax),
+            nullability: self.nullability.clone(),
+            directives: self.directives.convert(names),
+            selection_set: self.selection_set.convert(names),
+        }
+    }
+}
+
+#[cfg(feature = "query")]
+impl<'x, 'a, 'b, T, U> MapText<'x, 'b, U> for TypeCondition<'a, T>
+    where T: Text<'a>,
+          U: Text<'b>,
+{
+    type Output = TypeCondition<'b, U>;
+    fn convert(&'x self, names: &mut Names<'_, 'x, 'b, U>) -> Self::Output {
+        let TypeCondition::On(ref typ) = *self;
+        TypeCondition::On(name(typ, names))
+    }
+}
+
+#[cfg(feature = "schema")]
+impl<'x, 'a, 'b, T, U> MapText<'x, 'b, U> for schema::Definition<'a, T>
+    where T: Text<'a>,
+          U: Text<'b>,
+{
+    type Output = schema::Definition<'b, U>;
+    fn convert(&'x self, names: &mut Names<'_, 'x, 'b, U>) -> Self::Output {
+        use crate::schema::Definition::*;
+        match *self {
+            SchemaDefinition(ref def) => SchemaDefinition(def.convert(names)),
+            SchemaExtension(ref ext) => SchemaExtension(ext.convert(names)),
+            TypeDefinition(ref def) => TypeDefinition(def.convert(names)),
+            TypeExtension(ref ext) => TypeExtension(ext.convert(names)),
+            DirectiveDefinition(ref def) => {
+                DirectiveDefinition(def.convert(names))
+            }
+            Custom(ref custom) => Custom(CustomDefinition {
+                position: names.pos(custom.position),
+                keyword: name(&custom.keyword, names),
+                source: name(&custom.source, names),
+                node: custom.node.clone(),
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "schema")]
+impl<'x, 'a, 'b, T, U> MapText<'x, 'b, U> for SchemaDefinition<'a, T>
+    where T: Text<'a>,
+          U: Text<'b>,
+{
+    type Output = SchemaDefinition<'b, U>;
+    fn convert(&'x self, names: &mut Names<'_, 'x, 'b, U>) -> Self::Output {
+        SchemaDefinition {
+            position: names.pos(self.position),
+            directives: self.directives.convert(names),
+            query: opt_name(&self.query, names),
+            mutation: opt_name(&self.mutation, names),
+            subscription: opt_name(&self.subscription, names),
+        }
+    }
+}
+
+#[cfg(feature = "schema")]
+impl<'x, 'a, 'b, T, U> MapText<'x, 'b, U> for SchemaExtension<'a, T>
+    where T: Text<'a>,
+          U: Text<'b>,
+{
+    type Output = SchemaExtension<'b, U>;
+    fn convert(&'x self, names: &mut Names<'_, 'x, 'b, U>) -> Self::Output {
+        SchemaExtension {
+            position: names.pos(self.position),
+            directives: self.directives.convert(names),
+            query: opt_name(&self.query, names),
+            mutation: opt_name(&self.mutation, names),
+            subscription: opt_name(&self.subscription, names),
+        }
+    }
+}
+
+#[cfg(feature = "schema")]
+impl<'x, 'a, 'b, T, U> MapText<'x, 'b, U> for TypeDefinition<'a, T>
+    where T: Text<'a>,
+          U: Text<'b>,
+{
+    type Output = TypeDefinition<'b, U>;
+    fn convert(&'x self, names: &mut Names<'_, 'x, 'b, U>) -> Self::Output {
+        use crate::schema::TypeDefinition::*;
+        match *self {
+            Scalar(ref t) => Scalar(ScalarType {
+                position: names.pos(t.position),
+                description: t.description.clone(),
+                name: name(&t.name, names),
+                directives: t.directives.convert(names),
+            }),
+            Object(ref t) => Object(ObjectType {
+                position: names.pos(t.position),
+                description: t.description.clone(),
+                name: name(&t.name, names),
+                implements_interfaces:
+                    name_list(&t.implements_interfaces, names),
+                directives: t.directives.convert(names),
+                fields: t.fields.convert(names),
+            }),
+            Interface(ref t) => Interface(InterfaceType {
+                position: names.pos(t.position),
+                description: t.description.clone(),
+                name: name(&t.name, names),
+                implements_interfaces:
+                    name_list(&t.implements_interfaces, names),
+                directives: t.directives.convert(names),
+                fields: t.fields.convert(names),
+            }),
+            Union(ref t) => Union(UnionType {
+                position: names.pos(t.position),
+                description: t.description.clone(),
+                name: name(&t.name, names),
+                directives: t.directives.convert(names),
+                types: name_list(&t.types, names),
+            }),
+            Enum(ref t) => Enum(EnumType {
+                position: names.pos(t.position),
+                description: t.description.clone(),
+                name: name(&t.name, names),
+                directives: t.directives.convert(names),
+                values: t.values.convert(names),
+            }),
+            InputObject(ref t) => InputObject(InputObjectType {
+                position: names.pos(t.position),
+                description: t.description.clone(),
+                name: name(&t.name, names),
+                directives: t.directives.convert(names),
+                fields: t.fields.convert(names),
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "schema")]
+impl<'x, 'a, 'b, T, U> MapText<'x, 'b, U> for TypeExtension<'a, T>
+    where T: Text<'a>,
+          U: Text<'b>,
+{
+    type Output = TypeExtension<'b, U>;
+    fn convert(&'x self, names: &mut Names<'_, 'x, 'b, U>) -> Self::Output {
+        use crate::schema::TypeExtension::*;
+        match *self {
+            Scalar(ref t) => Scalar(ScalarTypeExtension {
+                position: names.pos(t.position),
+                name: name(&t.name, names),
+                directives: t.directives.convert(names),
+            }),
+            Object(ref t) => Object(ObjectTypeExtension {
+                position: names.pos(t.position),
+                name: name(&t.name, names),
+                implements_interfaces:
+                    name_list(&t.implements_interfaces, names),
+                directives: t.directives.convert(names),
+                fields: t.fields.convert(names),
+            }),
+            Interface(ref t) => Interface(InterfaceTypeExtension {
+                position: names.pos(t.position),
+                name: name(&t.name, names),
+                implements_interfaces:
+                    name_list(&t.implements_interfaces, names),
+                directives: t.directives.convert(names),
+                fields: t.fields.convert(names),
+            }),
+            Union(ref t) => Union(UnionTypeExtension {
+                position: names.pos(t.position),
+                name: name(&t.name, names),
+                directives: t.directives.convert(names),
+                types: name_list(&t.types, names),
+            }),
+            Enum(ref t) => Enum(EnumTypeExtension {
+                position: names.pos(t.position),
+                name: name(&t.name, names),
+                directives: t.directives.convert(names),
+                values: t.values.convert(names),
+            }),
+            InputObject(ref t) => InputObject(InputObjectTypeExtension {
+                position: names.pos(t.position),
+                name: name(&t.name, names),
+                directives: t.directives.convert(names),
+                fields: t.fields.convert(names),
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "schema")]
+impl<'x, 'a, 'b, T, U> MapText<'x, 'b, U> for schema::Field<'a, T>
+    where T: Text<'a>,
+          U: Text<'b>,
+{
+    type Output = schema::Field<'b, U>;
+    fn convert(&'x self, names: &mut Names<'_, 'x, 'b, U>) -> Self::Output {
+        schema::Field {
+            position: names.pos(self.position),
+            description: self.description.clone(),
+            name: name(&self.name, names),
+            arguments: self.arguments.convert(names),
+            field_type: self.field_type.convert(names),
+            directives: self.directives.convert(names),
+        }
+    }
+}
+
+#[cfg(feature = "schema")]
+impl<'x, 'a, 'b, T, U> MapText<'x, 'b, U> for InputValue<'a, T>
+    where T: Text<'a>,
+          U: Text<'b>,
+{
+    type Output = InputValue<'b, U>;
+    fn convert(&'x self, names: &mut Names<'_, 'x, 'b, U>) -> Self::Output {
+        InputValue {
+            position: names.pos(self.position),
+            description: self.description.clone(),
+            name: name(&self.name, names),
+            value_type: self.value_type.convert(names),
+            default_value: self.default_value.convert(names),
+            directives: self.directives.convert(names),
+        }
+    }
+}
+
+#[cfg(feature = "schema")]
+impl<'x, 'a, 'b, T, U> MapText<'x, 'b, U> for EnumValue<'a, T>
+    where T: Text<'a>,
+          U: Text<'b>,
+{
+    type Output = EnumValue<'b, U>;
+    fn convert(&'x self, names: &mut Names<'_, 'x, 'b, U>) -> Self::Output {
+        EnumValue {
+            position: names.pos(self.position),
+            description: self.description.clone(),
+            name: name(&self.name, names),
+            directives: self.directives.convert(names),
+        }
+    }
+}
+
+#[cfg(feature = "schema")]
+impl<'x, 'a, 'b, T, U> MapText<'x, 'b, U> for DirectiveDefinition<'a, T>
+    where T: Text<'a>,
+          U: Text<'b>,
+{
+    type Output = DirectiveDefinition<'b, U>;
+    fn convert(&'x self, names: &mut Names<'_, 'x, 'b, U>) -> Self::Output {
+        DirectiveDefinition {
+            position: names.pos(self.position),
+            description: self.description.clone(),
+            name: name(&self.name, names),
+            arguments: self.arguments.convert(names),
+            repeatable: self.repeatable,
+            locations: self.locations.clone(),
+        }
+    }
+}
+
+#[cfg(feature = "query")]
+impl<'a, T> query::Document<'a, T>
+    where T: Text<'a>,
+{
+    /// Returns a copy of the document with another text type
+    ///
+    /// Every name (including variables, enum values and text of custom
+    /// definitions) is converted by `names`, string values are converted
+    /// through `String`. Names passed to the function borrow from this
+    /// document, so the new one may borrow them too (e.g. a `&str` view of
+    /// a `Document<String>`). Positions are kept, so the new document can
+    /// be used for error reporting on the same source.
+    ///
+    /// ```rust
+    /// # extern crate edb_graphql_parser;
+    /// use std::collections::BTreeSet;
+    /// use std::rc::Rc;
+    /// use edb_graphql_parser::parse_query;
+    /// use edb_graphql_parser::query::{Document, Text};
+    ///
+    /// let source = String::from("query Q($id: ID) { user(id: $id) { id } }");
+    /// let doc = parse_query::<&str>(&source).unwrap();
+    ///
+    /// // owned copy, not borrowing the source
+    /// let owned: Document<'static, String> = doc.map_text(String::from);
+    /// drop(source);
+    /// assert_eq!(owned.to_string(),
+    ///     "query Q($id: ID) {\n  user(id: $id) {\n    id\n  }\n}\n");
+    ///
+    /// // every name is stored once
+    /// #[derive(Debug)]
+    /// struct Interned;
+    /// impl<'a> Text<'a> for Interned {
+    ///     type Value = Rc<str>;
+    ///     type StringValue = String;
+    /// }
+    /// let mut symbols = BTreeSet::<Rc<str>>::new();
+    /// let interned = owned.map_text::<Interned, _>(|name| {
+    ///     symbols.get(name).cloned().unwrap_or_else(|| {
+    ///         symbols.insert(name.into());
+    ///         symbols.get(name).cloned().unwrap()
+    ///     })
+    /// });
+    /// assert_eq!(symbols.len(), 4);
+    /// assert_eq!(interned.to_string(), owned.to_string());
+    /// ```
+    pub fn map_text<'x, 'b, U, F>(&'x self, mut names: F)
+        -> query::Document<'b, U>
+        where U: Text<'b>,
+              F: FnMut(&'x str) -> U::Value,
+    {
+        query::Document {
+            definitions: self.definitions.convert(&mut Names {
+                map: &mut names,
+                positions: true,
+            }),
+        }
+    }
+
+    /// Returns an owned copy of the document without positions, which is
+    /// equal to any other copy of the same document
+    pub(crate) fn without_positions(&self)
+        -> query::Document<'static, String>
+    {
+        query::Document {
+            definitions: self.definitions.convert(&mut Names {
+                map: &mut |name| name.to_string(),
+                positions: false,
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "schema")]
+impl<'a, T> schema::Document<'a, T>
+    where T: Text<'a>,
+{
+    /// Returns a copy of the document with another text type, see
+    /// `query::Document::map_text`
+    pub fn map_text<'x, 'b, U, F>(&'x self, mut names: F)
+        -> schema::Document<'b, U>
+        where U: Text<'b>,
+              F: FnMut(&'x str) -> U::Value,
+    {
+        schema::Document {
+            definitions: self.definitions.convert(&mut Names {
+                map: &mut names,
+                positions: true,
+            }),
+        }
+    }
+
+    /// Returns an owned copy of the document without positions, see
+    /// `query::Document::without_positions`
+    pub(crate) fn without_positions(&self)
+        -> schema::Document<'static, String>
+    {
+        schema::Document {
+            definitions: self.definitions.convert(&mut Names {
+                map: &mut |name| name.to_string(),
+                positions: false,
+            }),
+        }
+    }
+}
+
+impl<'a, T> Value<'a, T>
+    where T: Text<'a>,
+{
+    /// Returns a copy of the value with another text type, see
+    /// `query::Document::map_text`
+    pub fn map_text<'x, 'b, U, F>(&'x self, mut names: F) -> Value<'b, U>
+        where U: Text<'b>,
+              F: FnMut(&'x str) -> U::Value,
+    {
+        self.convert(&mut Names { map: &mut names, positions: true })
+    }
+}
+
+impl<'a, T> Type<'a, T>
+    where T: Text<'a>,
+{
+    /// Returns a copy of the type with another text type, see
+    /// `query::Document::map_text`
+    pub fn map_text<'x, 'b, U, F>(&'x self, mut names: F) -> Type<'b, U>
+        where U: Text<'b>,
+              F: FnMut(&'x str) -> U::Value,
+    {
+        self.convert(&mut Names { map: &mut names, positions: true })
+    }
+}
+
+#[cfg(all(test, feature = "query", feature = "schema"))]
+mod test {
+    use std::borrow::Cow;
+
+    use crate::{parse_query, parse_schema};
+    use crate::query::{Definition, Document, Value};
+    use crate::schema;
+
+    #[test]
+    fn query() {
+        let doc = parse_query::<&str>(r#"
+            query Q($a: [Int!] = [1]) @d(x: {y: "z"}) { a: b(c: $a) ...F }
+            fragment F on T { ... on U { e(f: ENUM) } }
+        "#).unwrap();
+        let upper: Document<String> = doc.map_text(|s| s.to_uppercase());
+        assert_eq!(upper.to_string(),
+                   r#"query Q($A: [INT!] = [1]) @D(X: {Y: "z"}) {
+  A: B(C: $A)
+  ...F
+}
+
+fragment F on T {
+  ... on U {
+    E(F: ENUM)
+  }
+}
+"#);
+        match (&upper.definitions[0], &doc.definitions[0]) {
+            (Definition::Operation(op), Definition::Operation(orig)) => {
+                assert_eq!(op.position, orig.position);
+                assert_eq!(op.selection_set.span, orig.selection_set.span);
+            }
+            _ => unreachable!(),
+        }
+        let same: Document<Cow<str>> = doc.map_text(Cow::Borrowed);
+        assert_eq!(same.to_string(), doc.to_string());
+    }
+
+    #[test]
+    fn schema() {
+        let doc = parse_schema::<String>(r#"
+            schema { query: Query }
+            extend schema @a
+            "Desc" type Query implements I @key(f: "x") {
+                f(a: Int = 1 @b): [T!]!
+            }
+            extend type Query { g: U }
+            union U = A | B
+            enum E { X @deprecated }
+            input In { x: Int = null }
+            directive @d(a: [In]) repeatable on FIELD | OBJECT
+        "#).unwrap();
+        let view: schema::Document<&str> = doc.map_text(|s| s);
+        assert_eq!(view.to_string(), doc.to_string());
+        let copy: schema::Document<&str> = doc.map_text(|_| "n");
+        let value = Value::<String>::Variable("x".into());
+        assert_eq!(value.map_text::<&str, _>(|_| "y"),
+                   Value::Variable("y"));
+        let source = doc.to_string();
+        let expected = doc.map_text::<String, _>(|_| "n".into());
+        assert_eq!(copy.to_string(), expected.to_string());
+        assert!(copy.to_string().contains("\"Desc\"\ntype n implements n"));
+        assert_eq!(doc.map_text::<String, _>(String::from).to_string(),
+                   source);
+    }
+}
@@ -1,7 +1,22 @@
 //! Formatting graphql
+use std::collections::BTreeSet;
 use std::default::Default;
+use std::fmt;
+#[cfg(any(feature = "query", feature = "schema"))]
+use std::io;
+use std::sync::Arc;
 
-use crate::common::Directive;
+use combine::{Positioned, StreamOnce};
+
+#[cfg(feature = "schema")]
+use crate::common::Description;
+use crate::common::{Directive, DirectiveArgument, Text, Type, Value};
+use crate::position::Pos;
+#[cfg(any(feature = "query", feature = "schema"))]
+use crate::position::{LineIndex, TextEdit};
+use crate::tokenizer::{Kind, TokenStream};
+#[cfg(any(feature = "query", feature = "schema"))]
+use crate::tokenizer::Token;
 
 
 #[derive(Debug, PartialEq)]
@@ -9,109 +24,918 @@ pub(crate) struct Formatter<'a> {
     buf: String,
     style: &'a Style,
     indent: u32,
+    /// In minified mode: whitespace was skipped since the last character
+    separate: bool,
+    /// Literals are printed in normalized form
+    normalize: bool,
+    /// Names of the query fields enclosing the current one (only tracked
+    /// when annotating)
+    path: Vec<String>,
+    /// Annotation to print at the end of the current line
+    comment: Option<String>,
+    /// Source comment to print at the end of the current line
+    trailing: Option<usize>,
+    /// Source comment printed at the end of the last line, and its byte
+    /// range in the buffer
+    last_trailing: Option<(usize, usize, usize)>,
+    /// Source comments that are already printed
+    printed: Vec<bool>,
+}
+
+/// Node passed to the annotation callback, see `Style::annotate`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Annotated<'x> {
+    /// Query field, by the names of the fields from the root of the
+    /// operation or fragment, e.g. `["user", "friends"]`
+    Field { path: &'x [String], position: Pos },
+    /// Schema element, by its schema coordinate: `User`, `User.name`,
+    /// `Color.RED` or `@deprecated`
+    Schema { coordinate: &'x str, position: Pos },
+}
+
+/// Form of the string values and descriptions, see `Style::strings`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StringStyle {
+    /// Characters other than quotes, backslashes and control characters
+    /// are printed as UTF-8, and multi-line values are printed as block
+    /// strings if that keeps the value exactly (default)
+    Utf8,
+    /// Non-ASCII characters are escaped as `\uXXXX` (as a surrogate pair
+    /// outside of the basic plane), so the output is pure ASCII
+    ///
+    /// Block strings can't contain escape sequences, so values with
+    /// non-ASCII characters are printed as regular strings.
+    Ascii,
+    /// Every value containing a line break is printed as a block string
+    ///
+    /// Block strings can't represent all the values, so the value changes
+    /// when reparsed if it doesn't end with a line break, has lines of
+    /// whitespace only, or has leading whitespace common to all lines.
+    /// Values with control characters other than tab and line feed are
+    /// still printed as regular strings.
+    Block,
+}
+
+type AnnotateFn = dyn Fn(&Annotated) -> Option<String> + Send + Sync;
+
+#[derive(Clone)]
+struct Annotator(Arc<AnnotateFn>);
+
+/// Comment of the source, see `Style::preserve_comments`
+#[derive(Debug, PartialEq)]
+struct Comment {
+    text: String,
+    /// Line of the comment if there is a token before it on the line
+    trailing: Option<usize>,
+    /// Character offset of the token before the comment
+    after: usize,
+    /// Character offsets of the following token, and of the token after it
+    /// if the following one is a string (i.e. a description)
+    next: usize,
+    after_description: Option<usize>,
 }
 
 /// A configuration of formatting style
 ///
-/// Currently we only have indentation configured, other things might be
-/// added later (such as minification).
+/// By default documents are pretty-printed with the indentation of two
+/// spaces, without limit on the line width. In minified mode no redundant
+/// whitespace is printed.
 #[derive(Debug, PartialEq, Clone)]
 pub struct Style {
     indent: u32,
+    indent_char: char,
+    max_width: Option<usize>,
+    block_descriptions: bool,
+    raw_descriptions: bool,
+    minified: bool,
+    sorted: bool,
+    omit_federation_builtins: bool,
+    omit_builtin_scalars: bool,
+    omit_builtin_directives: bool,
+    omit_default_schema: bool,
+    extend_types: BTreeSet<String>,
+    omit_null_defaults: bool,
+    normalize_defaults: bool,
+    trim_descriptions: bool,
+    strings: StringStyle,
+    annotator: Option<Annotator>,
+    comments: Option<Arc<Vec<Comment>>>,
 }
 
 impl Default for Style {
     fn default() -> Style {
         Style {
             indent: 2,
+            indent_char: ' ',
+            max_width: None,
+            block_descriptions: true,
+            raw_descriptions: false,
+            minified: false,
+            sorted: false,
+            omit_federation_builtins: false,
+            omit_builtin_scalars: false,
+            omit_builtin_directives: false,
+            omit_default_schema: false,
+            extend_types: BTreeSet::new(),
+            omit_null_defaults: false,
+            normalize_defaults: false,
+            trim_descriptions: false,
+            strings: StringStyle::Utf8,
+            annotator: None,
+            comments: None,
         }
     }
 }
 
 impl Style {
-    /// Change the number of spaces used for indentation
+    /// Change the number of characters used for indentation
     pub fn indent(&mut self, indent: u32) -> &mut Self {
         self.indent = indent;
         self
     }
+
+    /// Change the character used for indentation (space by default)
+    pub fn indent_char(&mut self, c: char) -> &mut Self {
+        self.indent_char = c;
+        self
+    }
+
+    /// Set the maximum width of the line
+    ///
+    /// If arguments, argument definitions or variable definitions don't
+    /// fit into the line, they are wrapped with one item per line. Other
+    /// constructs are never wrapped, so lines may still be longer than the
+    /// limit.
+    pub fn max_width(&mut self, width: Option<usize>) -> &mut Self {
+        self.max_width = width;
+        self
+    }
+
+    /// Choose whether multi-line descriptions in schemas are printed as
+    /// block strings (default) or as single-line strings
+    pub fn block_descriptions(&mut self, value: bool) -> &mut Self {
+        self.block_descriptions = value;
+        self
+    }
+
+    /// Print descriptions as written in the source, if they are parsed
+    ///
+    /// Lines of block strings keep their relative indentation and are
+    /// reindented to the nesting of the element. Escape sequences and line
+    /// breaks of the source are kept too, which avoids churn when
+    /// reprinting schemas. This doesn't apply in minified mode.
+    pub fn raw_descriptions(&mut self, value: bool) -> &mut Self {
+        self.raw_descriptions = value;
+        self
+    }
+
+    /// Enable or disable minified mode
+    ///
+    /// In minified mode newlines, indentation and commas are omitted, and a
+    /// single space is only printed between tokens that would otherwise be
+    /// merged (e.g. between two names). Block strings are printed as
+    /// regular strings.
+    pub fn minified(&mut self, value: bool) -> &mut Self {
+        self.minified = value;
+        self
+    }
+
+    /// Enable or disable sorting of schema definitions
+    ///
+    /// When enabled, type definitions (followed by their extensions),
+    /// fields, arguments, input fields, enum values, union members and
+    /// implemented interfaces are printed in lexicographical order, so the
+    /// output doesn't depend on the order of definitions in the source.
+    /// Schema definition is printed first, followed by directive
+    /// definitions. Directives applied to the definitions are kept in
+    /// original order as it can be significant.
+    ///
+    /// This doesn't affect queries.
+    pub fn sorted(&mut self, value: bool) -> &mut Self {
+        self.sorted = value;
+        self
+    }
+
+    /// Omit definitions added to the schema by federation
+    ///
+    /// This is useful for printing SDL of a subgraph, which must not
+    /// contain types (`_Entity`, `_Service`, `_Any`, `link__Import`...),
+    /// directive definitions (`@key`, `@external`, `@link`...) and root
+    /// fields (`_entities` and `_service`) provided by the federation
+    /// library. `@link` imports on the schema definition are omitted too.
+    /// Applications of federation directives (like `@key` on types) are
+    /// kept intact.
+    pub fn omit_federation_builtins(&mut self, value: bool) -> &mut Self {
+        self.omit_federation_builtins = value;
+        self
+    }
+
+    /// Omit definitions of the scalars existing in every schema: `Int`,
+    /// `Float`, `String`, `Boolean` and `ID`
+    ///
+    /// Extensions of these scalars are kept.
+    pub fn omit_builtin_scalars(&mut self, value: bool) -> &mut Self {
+        self.omit_builtin_scalars = value;
+        self
+    }
+
+    /// Omit definitions of the directives defined by the specification:
+    /// `@skip`, `@include`, `@deprecated` and `@specifiedBy`
+    pub fn omit_builtin_directives(&mut self, value: bool) -> &mut Self {
+        self.omit_builtin_directives = value;
+        self
+    }
+
+    /// Omit the schema definition if it is implied by the names of the
+    /// types
+    ///
+    /// The definition is omitted if it has no directives, and each root
+    /// operation type is either named `Query`, `Mutation` and
+    /// `Subscription` respectively, or is missing while no type with that
+    /// name is defined in the document.
+    pub fn omit_default_schema(&mut self, value: bool) -> &mut Self {
+        self.omit_default_schema = value;
+        self
+    }
+
+    /// Print definitions of the object types and interfaces with the name
+    /// as `extend type` (`extend interface`)
+    ///
+    /// This is the form expected by subgraph composition tools for root
+    /// types and entities owned by other services. Descriptions of
+    /// extended types are omitted, as extensions can't have them.
+    pub fn extend_types<I, S>(&mut self, names: I) -> &mut Self
+        where I: IntoIterator<Item=S>,
+              S: Into<String>,
+    {
+        self.extend_types = names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Omit `= null` default values of arguments and input fields
+    ///
+    /// Nullable arguments without default are mostly equivalent to ones
+    /// with `null` default, and some tools (e.g. introspection-based
+    /// printers) don't print them. This doesn't affect queries.
+    pub fn omit_null_defaults(&mut self, value: bool) -> &mut Self {
+        self.omit_null_defaults = value;
+        self
+    }
+
+    /// Print default values of arguments and input fields in the
+    /// normalized form
+    ///
+    /// Floats are printed in the shortest form representing the same
+    /// number (`1.50e3` as `1500.0`), and strings are always printed as
+    /// regular strings rather than block strings. Object fields are always
+    /// sorted by name. This doesn't affect queries.
+    pub fn normalize_defaults(&mut self, value: bool) -> &mut Self {
+        self.normalize_defaults = value;
+        self
+    }
+
+    /// Print descriptions as single-line strings without the leading and
+    /// trailing whitespace, so the ones that differ only in the form of the
+    /// string are the same
+    #[cfg(all(feature = "apq", feature = "schema"))]
+    pub(crate) fn trim_descriptions(&mut self, value: bool) -> &mut Self {
+        self.trim_descriptions = value;
+        self
+    }
+
+    /// Choose how string values and descriptions are escaped, see
+    /// `StringStyle`
+    ///
+    /// Minified mode and `normalize_defaults` still print regular strings
+    /// in `Block` style. Raw descriptions with non-ASCII characters are
+    /// reprinted in `Ascii` style.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "query")] {
+    /// # extern crate edb_graphql_parser;
+    /// use edb_graphql_parser::{parse_query, Style};
+    /// use edb_graphql_parser::format::StringStyle;
+    ///
+    /// let doc = parse_query::<&str>(r#"{ user(name: "Zoë") { id } }"#)
+    ///     .unwrap();
+    /// let mut style = Style::default();
+    /// style.minified(true).strings(StringStyle::Ascii);
+    /// assert_eq!(doc.format(&style), r#"{user(name:"Zo\u00eb"){id}}"#);
+    /// # }
+    /// ```
+    pub fn strings(&mut self, value: StringStyle) -> &mut Self {
+        self.strings = value;
+        self
+    }
+
+    /// Print the text returned by the callback as a trailing comment of
+    /// the node
+    ///
+    /// The callback is called for query fields and for elements of the
+    /// schema, the comment is printed at the end of the line where the node
+    /// starts (after `{` if it has a block). Line breaks in the comment are
+    /// replaced by spaces. Annotations are not printed in minified mode.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "query")] {
+    /// # extern crate edb_graphql_parser;
+    /// use edb_graphql_parser::{parse_query, Style};
+    /// use edb_graphql_parser::format::Annotated;
+    ///
+    /// let doc = parse_query::<&str>("{ users { id } }").unwrap();
+    /// let mut style = Style::default();
+    /// style.annotate(|node| match *node {
+    ///     Annotated::Field { path, .. } => Some(path.join(".")),
+    ///     _ => None,
+    /// });
+    /// assert_eq!(doc.format(&style), "\
+    /// {
+    ///   users { # users
+    ///     id # users.id
+    ///   }
+    /// }
+    /// ");
+    /// # }
+    /// ```
+    pub fn annotate<F>(&mut self, callback: F) -> &mut Self
+        where F: Fn(&Annotated) -> Option<String> + Send + Sync + 'static,
+    {
+        self.annotator = Some(Annotator(Arc::new(callback)));
+        self
+    }
+
+    /// Print comments of the source the document is parsed from
+    ///
+    /// A comment on its own line is printed before the node following it
+    /// in the source, indented as the node (a comment between a description
+    /// and its node is printed above the description), and a comment
+    /// following a node on the same line is printed at the end of the line
+    /// where the node starts. Comments that don't precede a node (e.g.
+    /// before closing brace) are printed before the next node or at the end
+    /// of the document. When sorting, only comments directly preceding a
+    /// node move along with the node, others are printed at the end.
+    /// Comments are not printed in minified mode.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "query")] {
+    /// # extern crate edb_graphql_parser;
+    /// use edb_graphql_parser::{parse_query, Style};
+    ///
+    /// let source = "# users\n{ users { id # primary key\n} }";
+    /// let doc = parse_query::<&str>(source).unwrap();
+    /// let mut style = Style::default();
+    /// style.preserve_comments(source);
+    /// assert_eq!(doc.format(&style),
+    ///     "# users\n{\n  users {\n    id # primary key\n  }\n}\n");
+    /// # }
+    /// ```
+    pub fn preserve_comments(&mut self, source: &str) -> &mut Self {
+        self.comments = Some(Arc::new(collect_comments(source)));
+        self
+    }
+}
+
+fn collect_comments(source: &str) -> Vec<Comment> {
+    let mut tokens = TokenStream::with_comments(source);
+    // kind and start of other tokens, comments with the following token
+    let mut starts = Vec::new();
+    let mut comments = Vec::new();
+    let mut last_line = None;
+    while let Ok(token) = {
+        let pos = tokens.position();
+        tokens.uncons().map(|token| (pos, token))
+    } {
+        let (pos, token) = token;
+        if token.kind == Kind::Comment {
+            comments.push((token.value.trim_end(), starts.len(),
+                           last_line.filter(|&line| line == pos.line)));
+        } else {
+            starts.push((token.kind, pos.character));
+            last_line = Some(pos.line + token.value.matches('\n').count());
+        }
+    }
+    let start = |idx: usize| starts.get(idx).map(|&(_, start)| start);
+    comments.into_iter().map(|(text, next, trailing)| Comment {
+        text: text.to_string(),
+        trailing,
+        after: next.checked_sub(1).and_then(start).unwrap_or(0),
+        next: start(next).unwrap_or(usize::MAX),
+        after_description: match starts.get(next) {
+            Some(&(Kind::StringValue, _)) | Some(&(Kind::BlockString, _)) => {
+                start(next + 1)
+            }
+            _ => None,
+        },
+    }).collect()
+}
+
+impl fmt::Debug for Annotator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Annotator")
+    }
+}
+
+impl PartialEq for Annotator {
+    fn eq(&self, other: &Annotator) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Returns true if there must be a space between the two characters
+/// for them to be parsed as distinct tokens
+fn needs_space(prev: char, next: char) -> bool {
+    fn is_word(c: char) -> bool {
+        c.is_ascii_alphanumeric() || c == '_'
+    }
+    // numbers are scanned up to the next punctuator, and `""` followed by
+    // a string would be parsed as a block string
+    is_word(prev) && is_word(next) ||
+        prev.is_ascii_digit() && matches!(next, '-' | '"' | '.') ||
+        prev == '"' && next == '"'
 }
 
 pub(crate) trait Displayable {
     fn display(&self, f: &mut Formatter);
+
+    /// Formats the node writing the output as soon as possible
+    ///
+    /// Nodes which consist of many independent items (documents) flush
+    /// output after each item, so that the whole text is never kept in
+    /// memory.
+    #[cfg(any(feature = "query", feature = "schema"))]
+    fn stream(&self, f: &mut Formatter, out: &mut dyn fmt::Write)
+        -> fmt::Result
+    {
+        self.display(f);
+        f.flush(out)
+    }
 }
 
 impl<'a> Formatter<'a> {
-    pub fn new(style: &Style) -> Formatter {
+    pub fn new(style: &Style) -> Formatter<'_> {
         Formatter {
             buf: String::with_capacity(1024),
             style,
             indent: 0,
+            separate: false,
+            normalize: false,
+            path: Vec::new(),
+            comment: None,
+            trailing: None,
+            last_trailing: None,
+            printed: match style.comments {
+                Some(ref comments) => vec![false; comments.len()],
+                None => Vec::new(),
+            },
         }
     }
 
     pub fn indent(&mut self) {
+        if self.style.minified {
+            self.separate = true;
+            return;
+        }
         for _ in 0..self.indent {
-            self.buf.push(' ');
+            self.buf.push(self.style.indent_char);
         }
     }
 
     pub fn endline(&mut self) {
+        if self.style.minified {
+            self.separate = true;
+            return;
+        }
+        if let Some(idx) = self.trailing.take() {
+            let start = self.buf.len();
+            self.buf.push(' ');
+            self.buf.push_str(&self.comments()[idx].text);
+            self.last_trailing = Some((idx, start, self.buf.len()));
+        }
+        if let Some(comment) = self.comment.take() {
+            self.buf.push_str(" # ");
+            self.buf.extend(comment.chars()
+                .map(|c| if c == '\n' || c == '\r' { ' ' } else { c }));
+        }
         self.buf.push('\n');
     }
 
+    #[cfg(any(feature = "query", feature = "schema"))]
     pub fn start_block(&mut self) {
-        self.buf.push('{');
+        self.write("{");
         self.endline();
         self.indent += self.style.indent;
     }
 
+    #[cfg(any(feature = "query", feature = "schema"))]
     pub fn end_block(&mut self) {
         self.indent = self.indent.checked_sub(self.style.indent)
             .expect("negative indent");
         self.indent();
-        self.buf.push('}');
+        self.write("}");
         self.endline();
     }
 
+    #[cfg(any(feature = "query", feature = "schema"))]
     pub fn margin(&mut self) {
+        if self.style.minified {
+            self.separate = true;
+            return;
+        }
         if !self.buf.is_empty() {
             self.buf.push('\n');
         }
     }
 
     pub fn write(&mut self, s: &str) {
-        self.buf.push_str(s);
+        if !self.style.minified {
+            self.buf.push_str(s);
+            return;
+        }
+        for c in s.chars() {
+            if c.is_whitespace() || c == ',' {
+                self.separate = true;
+            } else {
+                self.push_minified(c);
+            }
+        }
+    }
+
+    fn push_minified(&mut self, c: char) {
+        if self.separate {
+            if let Some(prev) = self.buf.chars().next_back() {
+                if needs_space(prev, c) {
+                    self.buf.push(' ');
+                }
+            }
+            self.separate = false;
+        }
+        self.buf.push(c);
     }
 
     pub fn into_string(self) -> String {
         self.buf
     }
 
-    pub fn write_quoted(&mut self, s: &str) {
-        let mut has_newline = false;
-        let mut has_nonprintable = false;
-        for c in s.chars() {
-            match c {
-                '\n' => has_newline = true,
-                '\r' | '\t' | '\u{0020}'..='\u{FFFF}' => {}
-                _ => has_nonprintable = true,
+    /// Returns items in print order
+    ///
+    /// That's the original order, unless sorting is enabled in the style.
+    #[cfg(feature = "schema")]
+    pub fn ordered<'x, I, K, F>(&self, items: &'x [I], key: F) -> Vec<&'x I>
+        where K: Ord,
+              F: Fn(&'x I) -> K,
+    {
+        let mut items = items.iter().collect::<Vec<_>>();
+        if self.style.sorted {
+            items.sort_by_key(|item| key(item));
+        }
+        items
+    }
+
+    #[cfg(feature = "schema")]
+    pub fn omit_federation_builtins(&self) -> bool {
+        self.style.omit_federation_builtins
+    }
+
+    #[cfg(feature = "schema")]
+    pub fn omit_builtin_scalars(&self) -> bool {
+        self.style.omit_builtin_scalars
+    }
+
+    #[cfg(feature = "schema")]
+    pub fn omit_builtin_directives(&self) -> bool {
+        self.style.omit_builtin_directives
+    }
+
+    #[cfg(feature = "schema")]
+    pub fn omit_default_schema(&self) -> bool {
+        self.style.omit_default_schema
+    }
+
+    /// Returns true if type definition must be printed as an extension
+    #[cfg(feature = "schema")]
+    pub fn is_extended(&self, type_name: &str) -> bool {
+        self.style.extend_types.contains(type_name)
+    }
+
+    #[cfg(any(feature = "query", feature = "schema"))]
+    fn annotator(&self) -> Option<&AnnotateFn> {
+        match self.style.annotator {
+            Some(ref annotator) if !self.style.minified => Some(&*annotator.0),
+            _ => None,
+        }
+    }
+
+    /// Enters the query field, must be paired with `leave_field`
+    #[cfg(feature = "query")]
+    pub fn enter_field(&mut self, name: &str) {
+        if self.annotator().is_some() {
+            self.path.push(name.to_string());
+        }
+    }
+
+    #[cfg(feature = "query")]
+    pub fn leave_field(&mut self) {
+        self.path.pop();
+    }
+
+    fn comments(&self) -> &'a [Comment] {
+        match self.style.comments {
+            // formatters of a single line have no comments
+            Some(ref comments)
+            if !self.style.minified && !self.printed.is_empty()
+            => comments,
+            _ => &[],
+        }
+    }
+
+    /// Prints source comments preceding the node, must be called before
+    /// the node (and its description) is printed
+    #[cfg(any(feature = "query", feature = "schema"))]
+    pub fn leading_comments(&mut self, position: Pos) {
+        let sorted = self.style.sorted;
+        for (idx, comment) in self.comments().iter().enumerate() {
+            if self.printed[idx] || comment.trailing == Some(position.line) {
+                continue;
+            }
+            if comment.next == position.character ||
+                comment.after_description == Some(position.character) ||
+                !sorted && comment.next < position.character
+            {
+                self.printed[idx] = true;
+                self.indent();
+                self.buf.push_str(&comment.text);
+                self.buf.push('\n');
             }
         }
-        if !has_newline || has_nonprintable {
-            use std::fmt::Write;
-            self.buf.push('"');
-            for c in s.chars() {
-                match c {
-                    '\r' => self.write(r"\r"),
-                    '\n' => self.write(r"\n"),
-                    '\t' => self.write(r"\t"),
-                    '"' => self.write("\\\""),
-                    '\\' => self.write(r"\\"),
-                    '\u{0020}'..='\u{FFFF}' => self.buf.push(c),
-                    _ => write!(&mut self.buf, "\\u{:04}", c as u32).unwrap(),
+    }
+
+    /// Prints the source comment following the node on the same line at
+    /// the end of the current line
+    ///
+    /// If the comment is already printed after a preceding node on the same
+    /// line (e.g. a parent with the opening brace), it moves to this line.
+    #[cfg(any(feature = "query", feature = "schema"))]
+    pub fn trailing_comment(&mut self, position: Pos) {
+        for (idx, comment) in self.comments().iter().enumerate() {
+            if comment.trailing != Some(position.line) ||
+                comment.after < position.character
+            {
+                continue;
+            }
+            if self.printed[idx] {
+                match self.last_trailing {
+                    Some((last, start, end)) if last == idx => {
+                        self.buf.replace_range(start..end, "");
+                        self.last_trailing = None;
+                    }
+                    _ => continue,
+                }
+            }
+            self.printed[idx] = true;
+            self.trailing = Some(idx);
+            break;
+        }
+    }
+
+    /// Prints source comments that weren't printed before any node
+    #[cfg(any(feature = "query", feature = "schema"))]
+    pub fn remaining_comments(&mut self) {
+        let mut first = true;
+        for (idx, comment) in self.comments().iter().enumerate() {
+            if !self.printed[idx] {
+                if first && !self.buf.is_empty() {
+                    self.buf.push('\n');
                 }
+                first = false;
+                self.buf.push_str(&comment.text);
+                self.buf.push('\n');
+            }
+        }
+        for flag in &mut self.printed {
+            *flag = true;
+        }
+    }
+
+    /// Annotates the current query field, the comment is printed at the
+    /// end of the line
+    #[cfg(feature = "query")]
+    pub fn annotate_field(&mut self, position: Pos) {
+        self.trailing_comment(position);
+        if let Some(annotator) = self.annotator() {
+            let path = &self.path;
+            self.comment = annotator(&Annotated::Field { path, position });
+        }
+    }
+
+    /// Annotates the schema element, coordinate is concatenated from parts
+    #[cfg(feature = "schema")]
+    pub fn annotate_schema(&mut self, parts: &[&str], position: Pos) {
+        self.trailing_comment(position);
+        if let Some(annotator) = self.annotator() {
+            let coordinate = &parts.concat();
+            self.comment = annotator(&Annotated::Schema { coordinate, position });
+        }
+    }
+
+    #[cfg(feature = "schema")]
+    pub fn omit_null_defaults(&self) -> bool {
+        self.style.omit_null_defaults
+    }
+
+    /// Whether literals are printed in normalized form
+    pub fn normalizing(&self) -> bool {
+        self.normalize
+    }
+
+    /// Writes default value of an argument or input field
+    #[cfg(feature = "schema")]
+    pub fn write_default<D: Displayable>(&mut self, value: &D) {
+        self.normalize = self.style.normalize_defaults;
+        value.display(self);
+        self.normalize = false;
+    }
+
+    /// Writes float value as is, or in the shortest form if normalizing
+    pub fn write_float(&mut self, s: &str) {
+        match s.parse::<f64>() {
+            // debug formatting always includes a dot or an exponent
+            Ok(value) if self.normalize && value.is_finite() => {
+                self.write(&format!("{:?}", value));
             }
+            _ => self.write(s),
+        }
+    }
+
+    /// Moves formatted text to the output
+    ///
+    /// The last character is kept in the buffer, as formatting of the
+    /// following text depends on it.
+    #[cfg(any(feature = "query", feature = "schema"))]
+    pub fn flush(&mut self, out: &mut dyn fmt::Write) -> fmt::Result {
+        if let Some((last, _)) = self.buf.char_indices().next_back() {
+            out.write_str(&self.buf[..last])?;
+            self.buf.drain(..last);
+            self.last_trailing = None;
+        }
+        Ok(())
+    }
+
+    /// Writes the rest of the formatted text to the output
+    #[cfg(any(feature = "query", feature = "schema"))]
+    pub fn finish(self, out: &mut dyn fmt::Write) -> fmt::Result {
+        out.write_str(&self.buf)
+    }
+
+    /// Writes a parenthesized list of items
+    ///
+    /// Items are separated by a comma if they fit into the line width,
+    /// otherwise each item is written on its own line.
+    pub fn write_list<F>(&mut self, count: usize, mut item: F)
+        where F: FnMut(usize, &mut Formatter),
+    {
+        if count == 0 {
+            return;
+        }
+        let width = match self.style.max_width {
+            Some(width) if !self.style.minified => width,
+            _ => {
+                self.write_single_line(count, &mut item);
+                return;
+            }
+        };
+        let mut single = Formatter {
+            buf: String::new(),
+            style: self.style,
+            indent: self.indent,
+            separate: false,
+            normalize: false,
+            path: Vec::new(),
+            comment: None,
+            trailing: None,
+            last_trailing: None,
+            printed: Vec::new(),
+        };
+        single.write_single_line(count, &mut item);
+        let line_start = self.buf.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let line = self.buf[line_start..].chars().count();
+        if !single.buf.contains('\n') &&
+            line + single.buf.chars().count() <= width
+        {
+            self.buf.push_str(&single.buf);
+            return;
+        }
+        self.write("(");
+        self.endline();
+        self.indent += self.style.indent;
+        for idx in 0..count {
+            self.indent();
+            item(idx, self);
+            self.endline();
+        }
+        self.indent -= self.style.indent;
+        self.indent();
+        self.write(")");
+    }
+
+    fn write_single_line<F>(&mut self, count: usize, item: &mut F)
+        where F: FnMut(usize, &mut Formatter),
+    {
+        self.write("(");
+        for idx in 0..count {
+            if idx > 0 {
+                self.write(", ");
+            }
+            item(idx, self);
+        }
+        self.write(")");
+    }
+
+    /// Writes a description of the schema element
+    #[cfg(feature = "schema")]
+    pub fn write_description(&mut self, descr: &Description) {
+        if self.style.trim_descriptions {
+            return self.write_escaped(descr.trim());
+        }
+        match descr.raw {
+            Some(ref raw) if self.style.raw_descriptions &&
+                             !self.style.minified &&
+                             (self.style.strings != StringStyle::Ascii ||
+                              raw.is_ascii())
+            => self.write_raw_description(raw, descr.indent),
+            _ if self.style.block_descriptions => self.write_quoted(descr),
+            _ => self.write_escaped(descr),
+        }
+    }
+
+    #[cfg(feature = "schema")]
+    fn write_raw_description(&mut self, raw: &str, indent: usize) {
+        let mut lines = raw.lines();
+        self.write(lines.next().unwrap_or(""));
+        let mut lines = lines.peekable();
+        while let Some(line) = lines.next() {
+            self.endline();
+            let last = lines.peek().is_none();
+            if last && line.trim_start().starts_with(r#"""""#) {
+                self.indent();
+                self.write(line.trim_start());
+            } else if !line.trim().is_empty() {
+                self.indent += self.style.indent;
+                self.indent();
+                self.indent -= self.style.indent;
+                let skip = line.len() - line.trim_start().len();
+                self.write(&line[skip.min(indent)..]);
+            }
+        }
+    }
+
+    fn write_escaped(&mut self, s: &str) {
+        use std::fmt::Write;
+        if self.style.minified {
+            self.push_minified('"');
+        } else {
             self.buf.push('"');
+        }
+        for c in s.chars() {
+            match c {
+                '\r' => self.write(r"\r"),
+                '\n' => self.write(r"\n"),
+                '\t' => self.write(r"\t"),
+                '"' => self.write("\\\""),
+                '\\' => self.write(r"\\"),
+                '\u{0000}'..='\u{001F}' => {
+                    write!(&mut self.buf, "\\u{:04x}", c as u32).unwrap()
+                }
+                _ if !c.is_ascii() &&
+                     self.style.strings == StringStyle::Ascii
+                => {
+                    let mut units = [0; 2];
+                    for unit in c.encode_utf16(&mut units) {
+                        write!(&mut self.buf, "\\u{:04x}", unit).unwrap()
+                    }
+                }
+                _ => self.buf.push(c),
+            }
+        }
+        self.buf.push('"');
+    }
+
+    /// Writes string value, using block string if it can represent the
+    /// value exactly
+    pub fn write_quoted(&mut self, s: &str) {
+        // block strings always end with a newline (as parsed by this
+        // library) and lose common indentation and whitespace-only lines
+        let representable = !s.contains(r#"\""""#) &&
+            !s.chars().any(|c| c < ' ' && c != '\n' && c != '\t');
+        let block = match self.style.strings {
+            StringStyle::Utf8 => representable && s.ends_with('\n') &&
+                s.lines().all(|l| l.is_empty() || !l.trim().is_empty()) &&
+                s.lines().any(|l| !l.starts_with(char::is_whitespace) &&
+                                  !l.is_empty()),
+            StringStyle::Ascii => false,
+            StringStyle::Block => representable && s.contains('\n'),
+        };
+        if !block || self.style.minified || self.normalize {
+            self.write_escaped(s);
         } else {
             self.buf.push_str(r#"""""#);
             self.endline();
@@ -130,6 +954,7 @@ impl<'a> Formatter<'a> {
     }
 }
 
+#[cfg(any(feature = "query", feature = "schema"))]
 pub(crate) fn format_directives<'a, T>(dirs: &[Directive<'a, T>], f: &mut Formatter) 
     where T: crate::common::Text<'a>,
 {
@@ -162,3 +987,250 @@ macro_rules! impl_display {
         )+
     };
 }
+
+/// Formats the node into the `fmt::Write`
+#[cfg(any(feature = "query", feature = "schema"))]
+pub(crate) fn fmt_to<D: Displayable>(node: &D, out: &mut dyn fmt::Write,
+    style: &Style)
+    -> fmt::Result
+{
+    let mut formatter = Formatter::new(style);
+    node.stream(&mut formatter, out)?;
+    formatter.finish(out)
+}
+
+/// Formats the node into the `io::Write`
+#[cfg(any(feature = "query", feature = "schema"))]
+pub(crate) fn write_to<D: Displayable>(node: &D, out: &mut dyn io::Write,
+    style: &Style)
+    -> io::Result<()>
+{
+    struct Adapter<'w> {
+        inner: &'w mut dyn io::Write,
+        error: Option<io::Error>,
+    }
+    impl fmt::Write for Adapter<'_> {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.inner.write_all(s.as_bytes()).map_err(|e| {
+                self.error = Some(e);
+                fmt::Error
+            })
+        }
+    }
+    let mut adapter = Adapter { inner: out, error: None };
+    fmt_to(node, &mut adapter, style).map_err(|_| {
+        adapter.error.take()
+            .unwrap_or_else(|| io::Error::other("formatting error"))
+    })
+}
+
+/// Returns the edits replacing the definitions intersecting the range by
+/// their formatted text, see `query::format_range`
+///
+/// Definitions start at the token indices `starts`, each ends at the token
+/// before the next one.
+#[cfg(any(feature = "query", feature = "schema"))]
+pub(crate) fn range_edits<F>(source: &str, tokens: &[(Token, Pos)],
+    starts: &[usize], range: (Pos, Pos), mut format: F)
+    -> Vec<TextEdit>
+    where F: FnMut(&str) -> String,
+{
+    let index = LineIndex::new(source);
+    let line_column = |pos: Pos| (pos.line, pos.column);
+    let mut edits = Vec::new();
+    let ranges = token_ranges(&index, tokens, starts);
+    for (&start, (start_offset, end_offset)) in starts.iter().zip(ranges) {
+        let (_, start_pos) = tokens[start];
+        let end_pos = index.position(end_offset).expect("end of the token");
+        if line_column(start_pos) > line_column(range.1) ||
+            line_column(end_pos) < line_column(range.0)
+        {
+            continue;
+        }
+        let text = &source[start_offset..end_offset];
+        let formatted = format(text);
+        let formatted = formatted.trim_end_matches('\n');
+        if formatted != text {
+            edits.push(TextEdit {
+                span: (start_pos, end_pos),
+                replacement: formatted.into(),
+            });
+        }
+    }
+    edits
+}
+
+/// Returns the byte ranges of the definitions starting at the token indices
+/// `starts`, each ends at the end of the token before the next one
+#[cfg(any(feature = "query", feature = "schema"))]
+pub(crate) fn token_ranges(index: &LineIndex, tokens: &[(Token, Pos)],
+    starts: &[usize])
+    -> Vec<(usize, usize)>
+{
+    starts.iter().enumerate().map(|(i, &start)| {
+        let last = starts.get(i + 1).map_or(tokens.len(), |&next| next) - 1;
+        let (_, start_pos) = tokens[start];
+        let (ref last_token, last_pos) = tokens[last];
+        let offset = |pos| index.character_offset(pos)
+            .expect("token is in the source");
+        (offset(start_pos), offset(last_pos) + last_token.value.len())
+    }).collect()
+}
+
+#[cfg(any(feature = "query", feature = "schema"))]
+macro_rules! impl_write {
+    ('a $($typ: ident, )+) => {
+        $(
+            impl<'a, T> $typ<'a, T>
+                where T: Text<'a>,
+            {
+                /// Writes the node formatted according to style
+                ///
+                /// Documents are written definition by definition, without
+                /// building the whole text in memory.
+                pub fn fmt_to<W: std::fmt::Write>(&self, out: &mut W,
+                    style: &Style)
+                    -> std::fmt::Result
+                {
+                    crate::format::fmt_to(self, out, style)
+                }
+
+                /// Writes the node formatted according to style
+                ///
+                /// Same as `fmt_to` but for byte streams, like files and
+                /// sockets. Consider wrapping the stream into `BufWriter`.
+                pub fn write_to<W: std::io::Write>(&self, out: &mut W,
+                    style: &Style)
+                    -> std::io::Result<()>
+                {
+                    crate::format::write_to(self, out, style)
+                }
+            }
+        )+
+    };
+}
+
+fn to_string<T: Displayable>(v: &T) -> String {
+    let style = Style::default();
+    let mut formatter = Formatter::new(&style);
+    v.display(&mut formatter);
+    formatter.into_string()
+}
+
+fn format_directive_arguments<'a, T>(
+    arguments: &[DirectiveArgument<'a, T>], f: &mut Formatter)
+    where T: Text<'a>,
+{
+    f.write_list(arguments.len(), |idx, f| {
+        f.write(arguments[idx].name.as_ref());
+        f.write(": ");
+        arguments[idx].value.display(f);
+    });
+}
+
+impl<'a, T: Text<'a>> Displayable for Type<'a, T>
+    where T: Text<'a>,
+{
+    fn display(&self, f: &mut Formatter) {
+        match *self {
+            Type::NamedType(ref name) => f.write(name.as_ref()),
+            Type::ListType(ref typ) => {
+                f.write("[");
+                typ.display(f);
+                f.write("]");
+            }
+            Type::NonNullType(ref typ) => {
+                typ.display(f);
+                f.write("!");
+            }
+        }
+    }
+}
+
+/// Part of the value left to format
+enum ValuePart<'x, 'a, T: Text<'a>> {
+    Value(&'x Value<'a, T>),
+    Field(&'x str),
+    Punct(&'static str),
+}
+
+impl<'a, T: Text<'a>> Displayable for Value<'a, T>
+    where T: Text<'a>,
+{
+    /// Formats the value keeping the nested items on the heap rather than
+    /// recursing
+    fn display(&self, f: &mut Formatter) {
+        let mut stack = vec![ValuePart::Value(self)];
+        while let Some(part) = stack.pop() {
+            let value = match part {
+                ValuePart::Value(value) => value,
+                ValuePart::Field(name) => {
+                    f.write(name);
+                    f.write(": ");
+                    continue;
+                }
+                ValuePart::Punct(punct) => {
+                    f.write(punct);
+                    continue;
+                }
+            };
+            match *value {
+                Value::Variable(ref name) => {
+                    f.write("$");
+                    f.write(name.as_ref());
+                },
+                Value::Int(ref num) => f.write(&format!("{}", num.0)),
+                Value::Float(ref val) => f.write_float(val),
+                Value::String(ref val) => f.write_quoted(val.as_ref()),
+                Value::Boolean(true) => f.write("true"),
+                Value::Boolean(false) => f.write("false"),
+                Value::Null => f.write("null"),
+                Value::Enum(ref name) => f.write(name.as_ref()),
+                Value::List(ref items) => {
+                    f.write("[");
+                    stack.push(ValuePart::Punct("]"));
+                    for (idx, item) in items.iter().enumerate().rev() {
+                        stack.push(ValuePart::Value(item));
+                        if idx > 0 {
+                            stack.push(ValuePart::Punct(", "));
+                        }
+                    }
+                }
+                Value::Object(ref items) => {
+                    f.write("{");
+                    let mut items = items.iter().collect::<Vec<_>>();
+                    if f.normalizing() {
+                        items.sort_by(|a, b| a.0.cmp(&b.0));
+                    }
+                    stack.push(ValuePart::Punct("}"));
+                    for (idx, (name, value)) in items.into_iter().enumerate()
+                        .rev()
+                    {
+                        stack.push(ValuePart::Value(value));
+                        stack.push(ValuePart::Field(name.as_ref()));
+                        if idx > 0 {
+                            stack.push(ValuePart::Punct(", "));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<'a, T: Text<'a>> Displayable for Directive<'a, T>
+    where T: Text<'a>,
+{
+    fn display(&self, f: &mut Formatter) {
+        f.write("@");
+        f.write(self.name.as_ref());
+        format_directive_arguments(self.arguments.as_slice(), f);
+    }
+}
+
+impl_display!(
+    'a
+    Type,
+    Value,
+    Directive,
+);
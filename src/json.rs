@@ -0,0 +1,160 @@
+//! Conversions between values and `serde_json::Value`
+use std::convert::TryFrom;
+
+use serde_json::{Number as JsonNumber, Value as Json};
+use thiserror::Error;
+
+use crate::common::{Number, Text, Value};
+
+
+/// Error converting `Value` into JSON
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ValueToJsonError {
+    /// Variables must be replaced by their values before conversion,
+    /// e.g. by `coercion::InputCoercion`
+    #[error("unresolved variable ${}", _0)]
+    UnresolvedVariable(String),
+    /// Integer doesn't fit into 64 bits
+    #[error("integer {} is out of range", _0)]
+    IntOutOfRange(String),
+    /// Float can't be represented in JSON
+    #[error("invalid float {}", _0)]
+    InvalidFloat(String),
+}
+
+/// Converts JSON, usually the variables of the request
+///
+/// Strings are converted into `Value::String`, even for enum types, and
+/// numbers without fraction and exponent into `Value::Int`, as JSON makes
+/// no difference. Both are accepted by `coercion::InputCoercion` where
+/// enums and floats are expected.
+impl<'a, T> From<Json> for Value<'a, T>
+    where T: Text<'a>,
+          T::Value: From<String>,
+{
+    fn from(json: Json) -> Value<'a, T> {
+        match json {
+            Json::Null => Value::Null,
+            Json::Bool(value) => Value::Boolean(value),
+            Json::Number(num) => {
+                if let Some(value) = num.as_i64() {
+                    Value::Int(Number(value.into()))
+                } else if let Some(value) = num.as_u64() {
+                    Value::Int(Number(value.into()))
+                } else {
+                    Value::Float(num.to_string())
+                }
+            }
+            Json::String(value) => Value::String(value.into()),
+            Json::Array(items) => {
+                Value::List(items.into_iter().map(Value::from).collect())
+            }
+            Json::Object(fields) => {
+                Value::Object(fields.into_iter()
+                    .map(|(name, value)| (name.into(), Value::from(value)))
+                    .collect())
+            }
+        }
+    }
+}
+
+/// Converts a value without variables into JSON
+///
+/// Enum values are converted into strings.
+impl<'x, 'a, T> TryFrom<&'x Value<'a, T>> for Json
+    where T: Text<'a>,
+{
+    type Error = ValueToJsonError;
+    fn try_from(value: &'x Value<'a, T>) -> Result<Json, ValueToJsonError> {
+        use num_traits::ToPrimitive;
+
+        let json = match *value {
+            Value::Variable(ref name) => {
+                return Err(ValueToJsonError::UnresolvedVariable(
+                    name.as_ref().into()));
+            }
+            Value::Int(ref num) => {
+                let num = num.as_bigint();
+                if let Some(value) = num.to_i64() {
+                    Json::from(value)
+                } else if let Some(value) = num.to_u64() {
+                    Json::from(value)
+                } else {
+                    return Err(ValueToJsonError::IntOutOfRange(
+                        num.to_string()));
+                }
+            }
+            Value::Float(ref num) => {
+                num.parse().ok().and_then(JsonNumber::from_f64)
+                    .map(Json::Number)
+                    .ok_or_else(|| ValueToJsonError::InvalidFloat(num.clone()))?
+            }
+            Value::String(ref value) => Json::String(value.as_ref().into()),
+            Value::Boolean(value) => Json::Bool(value),
+            Value::Null => Json::Null,
+            Value::Enum(ref name) => Json::String(name.as_ref().into()),
+            Value::List(ref items) => {
+                Json::Array(items.iter().map(Json::try_from)
+                    .collect::<Result<_, _>>()?)
+            }
+            Value::Object(ref fields) => {
+                Json::Object(fields.iter()
+                    .map(|(name, value)| {
+                        Ok((name.as_ref().into(), Json::try_from(value)?))
+                    })
+                    .collect::<Result<_, _>>()?)
+            }
+        };
+        Ok(json)
+    }
+}
+
+impl<'a, T> TryFrom<Value<'a, T>> for Json
+    where T: Text<'a>,
+{
+    type Error = ValueToJsonError;
+    fn try_from(value: Value<'a, T>) -> Result<Json, ValueToJsonError> {
+        Json::try_from(&value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryFrom;
+
+    use serde_json::{json, Value as Json};
+
+    use num_bigint::BigInt;
+
+    use crate::common::Number;
+    use crate::common::Value;
+
+    #[test]
+    fn from_json() {
+        let value = Value::<String>::from(json!({
+            "int": -1, "big": u64::MAX, "float": 1.5, "exp": 1e100,
+            "list": [null, true, "ADMIN"],
+        }));
+        assert_eq!(value.to_string(),
+            "{big: 18446744073709551615, exp: 1e+100, float: 1.5, int: -1, \
+             list: [null, true, \"ADMIN\"]}");
+    }
+
+    #[test]
+    fn to_json() {
+        let value = Value::<String>::Object(vec![
+            ("a".into(), Value::Enum("ADMIN".into())),
+            ("b".into(), Value::List(vec![
+                Value::Float("2.5".into()), Value::Int(3.into()), Value::Null,
+            ])),
+        ]);
+        assert_eq!(Json::try_from(&value).unwrap(),
+                   json!({"a": "ADMIN", "b": [2.5, 3, null]}));
+        assert_eq!(Json::try_from(Value::<&str>::Variable("x"))
+                   .unwrap_err().to_string(), "unresolved variable $x");
+        let big = Value::<&str>::Int(
+            Number(BigInt::from(u64::MAX) * 2));
+        assert_eq!(Json::try_from(big).unwrap_err().to_string(),
+                   "integer 36893488147419103230 is out of range");
+    }
+}
@@ -16,13 +16,66 @@
 //! 3. Schema definition language a/k/a IDL (which is still in RFC)
 //!
 //!
+//! Module Layout
+//! -------------
+//!
+//! * `query` -- AST and parser of executable documents
+//! * `schema` -- AST and parser of schema definition language
+//! * `visit` -- iterators over the AST and the statistics collector (also
+//!   available as `visitor`)
+//! * `format` -- formatting style of the printed documents
+//! * `analysis` -- depth and complexity metrics of operations
+//! * `validate` -- validation of executable documents (also available as
+//!   `validation`)
+//! * `typed` -- executable documents with the fields bound to the schema
+//! * `name` -- checks and sanitizing of names
+//! * `references` -- finding the usages of fragments, variables and types
+//! * `rename` -- renaming fragments, variables and types as text edits
+//! * `roundtrip` -- checking that printed documents parse back unchanged,
+//!   and reporting the formatting of the source lost in printing
+//! * `prelude` -- most commonly used types and functions, for glob imports
+//!
+//! These paths are considered stable, new functionality is added as new
+//! items or submodules of them.
+//!
+//!
+//! Platform Support
+//! ----------------
+//!
+//! The crate requires `std`, and `no_std` support is declined: the parser
+//! is built on `combine`'s `easy` errors and on `num-bigint` 0.2, neither
+//! of which supports `no_std`, and replacing them would change the public
+//! error and `Number` types. The crate builds for `wasm32-unknown-unknown`
+//! with all the features except `nightly` (which only enables the
+//! benchmarks), where `std` works with a custom `#[global_allocator]`.
+//!
+//!
+//! Cargo Features
+//! --------------
+//!
+//! The `query` and `schema` features, both enabled by default, build the
+//! parsers, ASTs and formatters of the query language and of the schema
+//! definition language. Clients that only work with queries can disable
+//! the default features and enable `query` alone, which leaves out schema
+//! parsing and everything that needs a schema (`typed`, `coercion`,
+//! schema-based validation and others), and vice versa.
+//!
+//! All features are additive. In particular, there is no feature storing
+//! arguments, directives or variable definitions of the AST in `SmallVec`:
+//! these are public `Vec` fields, and a feature changing their type would
+//! break every other crate in the dependency graph that builds or matches
+//! on the AST as soon as any crate enabled it.
+//!
+//!
 //! Example: Parse and Format Query
 //! -------------------------------
 //!
 //! ```rust
 //! # extern crate edb_graphql_parser;
+//! # #[cfg(feature = "query")]
 //! use edb_graphql_parser::query::{parse_query, ParseError};
 //!
+//! # #[cfg(feature = "query")]
 //! # fn parse() -> Result<(), ParseError> {
 //! let ast = parse_query::<&str>("query MyQuery { field1, field2 }")?;
 //! // Format canonical representation
@@ -35,6 +88,7 @@
 //! # Ok(())
 //! # }
 //! # fn main() {
+//! #    #[cfg(feature = "query")]
 //! #    parse().unwrap()
 //! # }
 //! ```
@@ -44,8 +98,10 @@
 //!
 //! ```rust
 //! # extern crate edb_graphql_parser;
+//! # #[cfg(feature = "schema")]
 //! use edb_graphql_parser::schema::{parse_schema, ParseError};
 //!
+//! # #[cfg(feature = "schema")]
 //! # fn parse() -> Result<(), ParseError> {
 //! let ast = parse_schema::<String>(r#"
 //!     schema {
@@ -85,6 +141,7 @@
 //! # Ok(())
 //! # }
 //! # fn main() {
+//! #    #[cfg(feature = "schema")]
 //! #    parse().unwrap()
 //! # }
 //! ```
@@ -94,17 +151,43 @@
 #[cfg(test)] #[macro_use] extern crate pretty_assertions;
 
 
+#[cfg(feature = "query")] pub mod analysis;
+#[cfg(all(feature = "arbitrary", feature = "query", feature = "schema"))]
+mod arbitrary;
+#[cfg(feature = "binary")] pub mod binary;
+#[cfg(all(feature = "query", feature = "schema"))] pub mod coercion;
 pub mod common;
+#[cfg(all(feature = "query", feature = "schema"))] pub mod conformance;
+#[cfg(feature = "miette")] pub mod diagnostic;
+pub mod error;
 #[macro_use]
-mod format;
+pub mod format;
+mod fold;
+#[cfg(feature = "query")] pub mod lint;
+#[cfg(feature = "lsp")] pub mod lsp;
+pub mod name;
+pub mod options;
 pub mod position;
 pub mod tokenizer;
 mod helpers;
-pub mod query;
-pub mod schema;
-pub mod visitor;
+#[cfg(feature = "serde_json")] mod json;
+#[cfg(feature = "query")] pub mod query;
+#[cfg(all(feature = "query", feature = "schema"))] pub mod references;
+#[cfg(all(feature = "query", feature = "schema"))] pub mod rename;
+#[cfg(feature = "serde")] pub mod request;
+pub mod roundtrip;
+#[cfg(feature = "schema")] pub mod schema;
+#[cfg(all(feature = "query", feature = "schema"))] pub mod summary;
+#[cfg(all(feature = "query", feature = "schema"))] pub mod typed;
+#[cfg(feature = "query")] pub mod visitor;
+pub mod validation;
+pub mod prelude;
+
+#[cfg(feature = "query")] pub use crate::visitor as visit;
+pub use crate::validation as validate;
 
-pub use crate::query::parse_query;
-pub use crate::schema::parse_schema;
+#[cfg(feature = "query")] pub use crate::query::parse_query;
+#[cfg(feature = "schema")] pub use crate::schema::parse_schema;
 pub use crate::position::Pos;
 pub use crate::format::Style;
+pub use crate::options::ParserOptions;
@@ -0,0 +1,304 @@
+//! Lints of executable documents
+//!
+//! Lints flag documents that are valid but most likely not what the
+//! author intended or hard to operate in production, e.g. anonymous
+//! operations that can't be told apart in the server logs. Unlike the
+//! rules of `validation`, lints are opinionated, so each of them can be
+//! allowed separately:
+//!
+//! ```rust
+//! # extern crate edb_graphql_parser;
+//! use edb_graphql_parser::parse_query;
+//! use edb_graphql_parser::lint::{Lint, Linter};
+//!
+//! let doc = parse_query::<&str>("{ id: id name @include(if: true) }")
+//!     .unwrap();
+//! let warnings = Linter::default().check(&doc);
+//! assert_eq!(warnings.iter().map(|w| w.lint).collect::<Vec<_>>(), [
+//!     Lint::AnonymousOperation,
+//!     Lint::RedundantAlias,
+//!     Lint::LiteralCondition,
+//! ]);
+//! let mut linter = Linter::default();
+//! linter.allow(Lint::AnonymousOperation);
+//! assert_eq!(linter.check(&doc)[0].to_string(),
+//!            "1:3: alias of the field \"id\" is the same as its name");
+//! ```
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::common::{Directive, Text, Value};
+use crate::position::Pos;
+use crate::query::{Document, Definition, Operation, OperationKind};
+use crate::query::{Selection, SelectionSet};
+use crate::validation::rules::same_arguments;
+
+
+/// Kind of the lint warning
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Lint {
+    /// Same field with the same arguments is selected twice in the
+    /// selection set
+    DuplicateField,
+    /// Alias equal to the name of the field: `id: id`
+    RedundantAlias,
+    /// Operation without a name
+    AnonymousOperation,
+    /// Operation name longer than `Linter::max_operation_name`
+    LongOperationName,
+    /// Operation name starting with the operation type:
+    /// `query QueryUsers`
+    OperationTypePrefix,
+    /// `@include` or `@skip` with a literal boolean
+    LiteralCondition,
+}
+
+/// Warning found by the `Linter`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintWarning {
+    pub lint: Lint,
+    pub message: String,
+    pub position: Pos,
+}
+
+/// Configuration of the lints
+///
+/// By default all lints are enabled, and operation names are limited to
+/// 64 characters.
+#[derive(Debug, Clone)]
+pub struct Linter {
+    allowed: BTreeSet<Lint>,
+    max_operation_name: usize,
+}
+
+struct Checker<'l> {
+    linter: &'l Linter,
+    warnings: Vec<LintWarning>,
+}
+
+impl Lint {
+    /// Returns the name of the lint in snake case, e.g. `redundant_alias`
+    pub fn as_str(&self) -> &'static str {
+        use self::Lint::*;
+        match *self {
+            DuplicateField => "duplicate_field",
+            RedundantAlias => "redundant_alias",
+            AnonymousOperation => "anonymous_operation",
+            LongOperationName => "long_operation_name",
+            OperationTypePrefix => "operation_type_prefix",
+            LiteralCondition => "literal_condition",
+        }
+    }
+}
+
+impl fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.position, self.message)
+    }
+}
+
+impl Default for Linter {
+    fn default() -> Linter {
+        Linter {
+            allowed: BTreeSet::new(),
+            max_operation_name: 64,
+        }
+    }
+}
+
+impl Linter {
+    /// Disable the lint
+    pub fn allow(&mut self, lint: Lint) -> &mut Self {
+        self.allowed.insert(lint);
+        self
+    }
+
+    /// Enable the lint disabled by `allow`
+    pub fn warn(&mut self, lint: Lint) -> &mut Self {
+        self.allowed.remove(&lint);
+        self
+    }
+
+    /// Set maximum length of operation names in characters
+    pub fn max_operation_name(&mut self, length: usize) -> &mut Self {
+        self.max_operation_name = length;
+        self
+    }
+
+    /// Returns warnings in the order of the document
+    pub fn check<'a, T>(&self, doc: &Document<'a, T>) -> Vec<LintWarning>
+        where T: Text<'a>,
+    {
+        let mut checker = Checker { linter: self, warnings: Vec::new() };
+        for def in &doc.definitions {
+            match *def {
+                Definition::Operation(ref op) => {
+                    checker.operation(op);
+                    checker.directives(&op.directives);
+                    checker.selection_set(&op.selection_set);
+                }
+                Definition::Fragment(ref frag) => {
+                    checker.directives(&frag.directives);
+                    checker.selection_set(&frag.selection_set);
+                }
+            }
+        }
+        checker.warnings
+    }
+}
+
+impl<'l> Checker<'l> {
+    fn warn(&mut self, lint: Lint, message: String, position: Pos) {
+        if !self.linter.allowed.contains(&lint) {
+            self.warnings.push(LintWarning { lint, message, position });
+        }
+    }
+
+    fn operation<'a, T>(&mut self, op: &Operation<'a, T>)
+        where T: Text<'a>,
+    {
+        let name = match op.name {
+            Some(ref name) => name.as_ref(),
+            None => {
+                self.warn(Lint::AnonymousOperation,
+                    "operation should have a name".into(), op.position);
+                return;
+            }
+        };
+        let max = self.linter.max_operation_name;
+        if name.chars().count() > max {
+            self.warn(Lint::LongOperationName,
+                format!("operation name {:?} is longer than {} characters",
+                        name, max),
+                op.position);
+        }
+        let prefix = match op.kind {
+            OperationKind::ImplicitQuery | OperationKind::Query => "query",
+            OperationKind::Mutation => "mutation",
+            OperationKind::Subscription => "subscription",
+        };
+        let starts = name.get(..prefix.len())
+            .is_some_and(|start| start.eq_ignore_ascii_case(prefix));
+        if starts && name.len() > prefix.len() {
+            self.warn(Lint::OperationTypePrefix,
+                format!("operation name {:?} repeats the operation type",
+                        name),
+                op.position);
+        }
+    }
+
+    fn directives<'a, T>(&mut self, directives: &[Directive<'a, T>])
+        where T: Text<'a>,
+    {
+        for dir in directives {
+            let name = dir.name.as_ref();
+            if name != "include" && name != "skip" {
+                continue;
+            }
+            if let Some(arg) = dir.argument("if") {
+                if let Value::Boolean(value) = arg.value {
+                    self.warn(Lint::LiteralCondition,
+                        format!("condition of @{} is always {}",
+                                name, value),
+                        dir.position);
+                }
+            }
+        }
+    }
+
+    fn selection_set<'a, T>(&mut self, set: &SelectionSet<'a, T>)
+        where T: Text<'a>,
+    {
+        let fields = set.fields().collect::<Vec<_>>();
+        for (idx, field) in fields.iter().enumerate() {
+            let duplicate = fields[..idx].iter().any(|prev| {
+                prev.response_key() == field.response_key() &&
+                prev.name == field.name &&
+                same_arguments(&prev.arguments, &field.arguments)
+            });
+            if duplicate {
+                self.warn(Lint::DuplicateField,
+                    format!("field {:?} is already selected",
+                            field.response_key()),
+                    field.position);
+            }
+        }
+        for item in &set.items {
+            match *item {
+                Selection::Field(ref field) => {
+                    if let Some(ref alias) = field.alias {
+                        if alias == &field.name {
+                            self.warn(Lint::RedundantAlias,
+                                format!("alias of the field {:?} is the \
+                                         same as its name", alias.as_ref()),
+                                field.position);
+                        }
+                    }
+                    self.directives(&field.directives);
+                    self.selection_set(&field.selection_set);
+                }
+                Selection::FragmentSpread(ref spread) => {
+                    self.directives(&spread.directives);
+                }
+                Selection::InlineFragment(ref frag) => {
+                    self.directives(&frag.directives);
+                    self.selection_set(&frag.selection_set);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse_query;
+    use super::{Lint, Linter};
+
+    fn lints(linter: &Linter, query: &str) -> Vec<(Lint, String)> {
+        let doc = parse_query::<&str>(query).unwrap();
+        linter.check(&doc).into_iter()
+            .map(|w| (w.lint, w.to_string())).collect()
+    }
+
+    #[test]
+    fn all() {
+        let linter = Linter::default();
+        assert_eq!(lints(&linter, "
+            query Users { id a: b a: b(x: 1) ... { id } id }
+            mutation mutationCreate { x @skip(if: $v) @include(if: false) }
+            query QueryLimits { Query }
+            fragment F on T { ...G @skip(if: true) query: query }
+        "), [
+            (Lint::DuplicateField,
+             "2:57: field \"id\" is already selected".into()),
+            (Lint::OperationTypePrefix,
+             "3:13: operation name \"mutationCreate\" repeats the \
+              operation type".into()),
+            (Lint::LiteralCondition,
+             "3:55: condition of @include is always false".into()),
+            (Lint::OperationTypePrefix,
+             "4:13: operation name \"QueryLimits\" repeats the operation \
+              type".into()),
+            (Lint::LiteralCondition,
+             "5:36: condition of @skip is always true".into()),
+            (Lint::RedundantAlias,
+             "5:52: alias of the field \"query\" is the same as its name"
+             .into()),
+        ]);
+        assert_eq!(lints(&linter, "query Query { a }"), []);
+    }
+
+    #[test]
+    fn configure() {
+        let mut linter = Linter::default();
+        linter.max_operation_name(5).allow(Lint::AnonymousOperation);
+        assert_eq!(lints(&linter, "{ a } query Users { a } query Abcdef { a }"),
+            [(Lint::LongOperationName,
+              "1:25: operation name \"Abcdef\" is longer than 5 characters"
+              .into())]);
+        linter.warn(Lint::AnonymousOperation).allow(Lint::LongOperationName);
+        assert_eq!(lints(&linter, "{ a } query Abcdef { a }"),
+            [(Lint::AnonymousOperation,
+              "1:1: operation should have a name".into())]);
+    }
+}
@@ -0,0 +1,565 @@
+//! Diagnostics and document outlines for language servers
+//!
+//! The types mirror `Diagnostic` and `DocumentSymbol` of the Language Server
+//! Protocol field by field, so converting them into `lsp-types` is
+//! a plain struct literal. Positions are zero-based lines and UTF-16 code
+//! units as required by the protocol, they are computed by `LspIndex` from
+//! the source of the document:
+//!
+//! ```rust
+//! # #[cfg(feature = "query")] {
+//! # extern crate edb_graphql_parser;
+//! use edb_graphql_parser::lsp::{LspIndex, ToDiagnostic};
+//! use edb_graphql_parser::parse_query;
+//!
+//! let source = "query Q($a: Int, b: Int) { a }";
+//! let index = LspIndex::new(source);
+//! let diagnostic = parse_query::<&str>(source).unwrap_err()
+//!     .to_diagnostic(&index);
+//! assert_eq!(diagnostic.message, "unexpected `b`");
+//! assert_eq!(diagnostic.code, "unexpected_token");
+//! assert_eq!(diagnostic.range.start.character, 17);
+//! # }
+//! ```
+use crate::position::{LineIndex, Pos};
+#[cfg(any(feature = "query", feature = "schema"))]
+use crate::tokenizer::{positioned_tokens, Token};
+use crate::validation::ValidationError;
+#[cfg(feature = "query")]
+use crate::lint::LintWarning;
+#[cfg(feature = "query")]
+use crate::query;
+#[cfg(feature = "schema")]
+use crate::schema;
+
+
+/// Zero-based line and UTF-16 offset in the line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Position {
+    pub line: u32,
+    pub character: u32,
+}
+
+/// Range of the source, the end is exclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+/// Severity of the diagnostic, values are the ones of the protocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiagnosticSeverity {
+    Error = 1,
+    Warning = 2,
+    Information = 3,
+    Hint = 4,
+}
+
+/// Error or warning at a range of the source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub range: Range,
+    pub severity: DiagnosticSeverity,
+    /// Error code, rule or lint name
+    pub code: String,
+    /// Always `graphql`
+    pub source: String,
+    pub message: String,
+    /// Other ranges the diagnostic refers to, with a message for each
+    pub related_information: Vec<(Range, String)>,
+}
+
+/// Kind of the symbol, values are the ones of the protocol
+///
+/// Only the kinds used for GraphQL definitions are listed, see
+/// `DocumentSymbol::detail` for the exact kind of the definition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SymbolKind {
+    /// Schema definition and extension
+    Module = 2,
+    /// Object type
+    Class = 5,
+    /// Field and input field
+    Field = 8,
+    /// Enum and union type
+    Enum = 10,
+    Interface = 11,
+    /// Operation, fragment and directive definition
+    Function = 12,
+    EnumMember = 22,
+    /// Input object type
+    Struct = 23,
+    /// Scalar type
+    TypeParameter = 26,
+}
+
+/// Definition in the outline of a document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocumentSymbol {
+    pub name: String,
+    /// Keywords of the definition, e.g. `extend type` or `fragment on User`
+    pub detail: Option<String>,
+    pub kind: SymbolKind,
+    /// Whole definition including its description
+    pub range: Range,
+    /// The name of the definition
+    pub selection_range: Range,
+    /// Fields and enum values
+    pub children: Vec<DocumentSymbol>,
+}
+
+/// Converts positions of the parser into positions of the protocol
+///
+/// Built once for the source the positions refer to.
+#[derive(Debug, Clone)]
+pub struct LspIndex<'a> {
+    source: &'a str,
+    index: LineIndex<'a>,
+}
+
+/// Error or warning convertible into a `Diagnostic`
+pub trait ToDiagnostic {
+    fn to_diagnostic(&self, index: &LspIndex) -> Diagnostic;
+}
+
+/// Symbol before its ranges are computed: token indices of the start of the
+/// definition and of its name
+#[cfg(any(feature = "query", feature = "schema"))]
+struct Outline {
+    name: String,
+    detail: String,
+    kind: SymbolKind,
+    start: usize,
+    name_token: usize,
+    children: Vec<Outline>,
+}
+
+impl<'a> LspIndex<'a> {
+    pub fn new(source: &'a str) -> LspIndex<'a> {
+        LspIndex { source, index: LineIndex::new(source) }
+    }
+
+    /// Returns the protocol position of the parser position
+    ///
+    /// Positions past the end of the source are clamped to the end.
+    pub fn position(&self, pos: Pos) -> Position {
+        let offset = self.index.character_offset(pos)
+            .unwrap_or(self.source.len());
+        self.offset_position(offset)
+    }
+
+    /// Returns the range between the parser positions
+    pub fn range(&self, start: Pos, end: Pos) -> Range {
+        Range { start: self.position(start), end: self.position(end) }
+    }
+
+    fn offset_position(&self, offset: usize) -> Position {
+        let line_start = self.source[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let line = self.index.position(offset).expect("valid offset").line;
+        Position {
+            line: (line - 1) as u32,
+            character: self.source[line_start..offset].encode_utf16().count()
+                as u32,
+        }
+    }
+
+    /// Returns the outline of the query document parsed from the source
+    ///
+    /// Operations and fragments are listed in the order of the source,
+    /// anonymous operations are named by their kind.
+    ///
+    /// ```rust
+    /// # extern crate edb_graphql_parser;
+    /// use edb_graphql_parser::lsp::{LspIndex, SymbolKind};
+    /// use edb_graphql_parser::parse_query;
+    ///
+    /// let source = "query Users { users { ...F } }\n\
+    ///               fragment F on User { id }";
+    /// let doc = parse_query::<&str>(source).unwrap();
+    /// let symbols = LspIndex::new(source).query_symbols(&doc);
+    /// assert_eq!(symbols[0].name, "Users");
+    /// assert_eq!(symbols[0].kind, SymbolKind::Function);
+    /// assert_eq!(symbols[1].detail.as_deref(), Some("fragment on User"));
+    /// assert_eq!(symbols[1].range.start.line, 1);
+    /// assert_eq!(symbols[1].selection_range.start.character, 9);
+    /// ```
+    #[cfg(feature = "query")]
+    pub fn query_symbols<'b, T>(&self, doc: &query::Document<'b, T>)
+        -> Vec<DocumentSymbol>
+        where T: query::Text<'b>,
+    {
+        use crate::query::{Definition, OperationKind, TypeCondition};
+
+        let outlines = doc.definitions.iter().map(|def| match *def {
+            Definition::Operation(ref op) => {
+                let start = op.position.token;
+                let kind = match op.kind {
+                    OperationKind::ImplicitQuery |
+                    OperationKind::Query => "query",
+                    OperationKind::Mutation => "mutation",
+                    OperationKind::Subscription => "subscription",
+                };
+                let (name, name_token) = match op.name {
+                    Some(ref name) => (name.as_ref().to_string(), start + 1),
+                    None => (kind.to_string(), start),
+                };
+                Outline {
+                    name, name_token, start,
+                    detail: kind.to_string(),
+                    kind: SymbolKind::Function,
+                    children: Vec::new(),
+                }
+            }
+            Definition::Fragment(ref frag) => {
+                let TypeCondition::On(ref on) = frag.type_condition;
+                Outline {
+                    name: frag.name.as_ref().to_string(),
+                    detail: format!("fragment on {}", on.as_ref()),
+                    kind: SymbolKind::Function,
+                    start: frag.position.token,
+                    name_token: frag.position.token + 1,
+                    children: Vec::new(),
+                }
+            }
+        }).collect();
+        self.symbols(outlines)
+    }
+
+    /// Returns the outline of the schema document parsed from the source
+    ///
+    /// Type definitions have their fields, input fields or enum values as
+    /// children. Ranges of definitions and children include descriptions.
+    ///
+    /// ```rust
+    /// # extern crate edb_graphql_parser;
+    /// use edb_graphql_parser::lsp::{LspIndex, SymbolKind};
+    /// use edb_graphql_parser::parse_schema;
+    ///
+    /// let source = "\"User\" type User {\n  id: ID\n  name: String\n}";
+    /// let doc = parse_schema::<&str>(source).unwrap();
+    /// let symbols = LspIndex::new(source).schema_symbols(&doc);
+    /// assert_eq!(symbols[0].kind, SymbolKind::Class);
+    /// assert_eq!(symbols[0].range.start.character, 0);
+    /// assert_eq!(symbols[0].range.end.line, 3);
+    /// assert_eq!(symbols[0].children[1].name, "name");
+    /// assert_eq!(symbols[0].children[1].range.start.line, 2);
+    /// ```
+    #[cfg(feature = "schema")]
+    pub fn schema_symbols<'b, T>(&self, doc: &schema::Document<'b, T>)
+        -> Vec<DocumentSymbol>
+        where T: schema::Text<'b>,
+    {
+        let outlines = doc.definitions.iter()
+            .filter_map(schema_outline)
+            .collect();
+        self.symbols(outlines)
+    }
+
+    /// Computes ranges of the outlines, each one ends at the token before
+    /// the next one, children end before the closing brace of the parent
+    #[cfg(any(feature = "query", feature = "schema"))]
+    fn symbols(&self, outlines: Vec<Outline>) -> Vec<DocumentSymbol> {
+        let tokens = positioned_tokens(self.source);
+        self.ranges(&tokens, outlines, tokens.len())
+    }
+
+    #[cfg(any(feature = "query", feature = "schema"))]
+    fn ranges(&self, tokens: &[(Token, Pos)], outlines: Vec<Outline>,
+        end: usize)
+        -> Vec<DocumentSymbol>
+    {
+        let starts = outlines.iter().map(|o| o.start).collect::<Vec<_>>();
+        outlines.into_iter().enumerate().map(|(idx, outline)| {
+            let last = starts.get(idx + 1).map_or(end, |&next| next) - 1;
+            DocumentSymbol {
+                name: outline.name,
+                detail: Some(outline.detail),
+                kind: outline.kind,
+                range: self.tokens_range(tokens, outline.start, last),
+                selection_range: self.tokens_range(tokens,
+                    outline.name_token, outline.name_token),
+                children: self.ranges(tokens, outline.children, last),
+            }
+        }).collect()
+    }
+
+    /// Returns the range from the start of the first token to the end of
+    /// the last one
+    #[cfg(any(feature = "query", feature = "schema"))]
+    fn tokens_range(&self, tokens: &[(Token, Pos)], first: usize,
+        last: usize)
+        -> Range
+    {
+        let (_, start) = tokens[first];
+        let (ref last_token, last_pos) = tokens[last];
+        let end = self.index.character_offset(last_pos)
+            .expect("token is in the source") + last_token.value.len();
+        Range {
+            start: self.position(start),
+            end: self.offset_position(end),
+        }
+    }
+}
+
+#[cfg(feature = "schema")]
+fn schema_outline<'a, T>(def: &schema::Definition<'a, T>) -> Option<Outline>
+    where T: schema::Text<'a>,
+{
+    use crate::schema::{Definition, TypeDefinition, TypeExtension};
+
+    // descriptions of definitions precede their positions, the ones of
+    // fields and values follow
+    let described = |pos: Pos, description: &Option<_>| {
+        pos.token - description.is_some() as usize
+    };
+    let fields = |fields: &[schema::Field<'a, T>]| {
+        fields.iter().map(|f| child(&f.name, f.position,
+            f.description.is_some(), SymbolKind::Field))
+            .collect::<Vec<_>>()
+    };
+    let input_fields = |fields: &[schema::InputValue<'a, T>]| {
+        fields.iter().map(|f| child(&f.name, f.position,
+            f.description.is_some(), SymbolKind::Field))
+            .collect::<Vec<_>>()
+    };
+    let enum_values = |values: &[schema::EnumValue<'a, T>]| {
+        values.iter().map(|v| child(&v.name, v.position,
+            v.description.is_some(), SymbolKind::EnumMember))
+            .collect::<Vec<_>>()
+    };
+    let outline = |name: &T::Value, keyword: &str, kind, pos: Pos, start,
+                   children| Outline {
+        name: name.as_ref().to_string(),
+        detail: keyword.to_string(),
+        kind, start, children,
+        name_token: pos.token + 1,
+    };
+    Some(match *def {
+        Definition::SchemaDefinition(ref s) => Outline {
+            name: "schema".to_string(),
+            detail: "schema".to_string(),
+            kind: SymbolKind::Module,
+            start: s.position.token,
+            name_token: s.position.token,
+            children: Vec::new(),
+        },
+        Definition::SchemaExtension(ref s) => Outline {
+            name: "schema".to_string(),
+            detail: "extend schema".to_string(),
+            kind: SymbolKind::Module,
+            start: s.position.token - 1,
+            name_token: s.position.token,
+            children: Vec::new(),
+        },
+        Definition::TypeDefinition(ref t) => match *t {
+            TypeDefinition::Scalar(ref t) => outline(&t.name, "scalar",
+                SymbolKind::TypeParameter, t.position,
+                described(t.position, &t.description), Vec::new()),
+            TypeDefinition::Object(ref t) => outline(&t.name, "type",
+                SymbolKind::Class, t.position,
+                described(t.position, &t.description), fields(&t.fields)),
+            TypeDefinition::Interface(ref t) => outline(&t.name, "interface",
+                SymbolKind::Interface, t.position,
+                described(t.position, &t.description), fields(&t.fields)),
+            TypeDefinition::Union(ref t) => outline(&t.name, "union",
+                SymbolKind::Enum, t.position,
+                described(t.position, &t.description), Vec::new()),
+            TypeDefinition::Enum(ref t) => outline(&t.name, "enum",
+                SymbolKind::Enum, t.position,
+                described(t.position, &t.description),
+                enum_values(&t.values)),
+            TypeDefinition::InputObject(ref t) => outline(&t.name, "input",
+                SymbolKind::Struct, t.position,
+                described(t.position, &t.description),
+                input_fields(&t.fields)),
+        },
+        Definition::TypeExtension(ref t) => match *t {
+            TypeExtension::Scalar(ref t) => outline(&t.name,
+                "extend scalar", SymbolKind::TypeParameter, t.position,
+                t.position.token - 1, Vec::new()),
+            TypeExtension::Object(ref t) => outline(&t.name, "extend type",
+                SymbolKind::Class, t.position, t.position.token - 1,
+                fields(&t.fields)),
+            TypeExtension::Interface(ref t) => outline(&t.name,
+                "extend interface", SymbolKind::Interface, t.position,
+                t.position.token - 1, fields(&t.fields)),
+            TypeExtension::Union(ref t) => outline(&t.name, "extend union",
+                SymbolKind::Enum, t.position, t.position.token - 1,
+                Vec::new()),
+            TypeExtension::Enum(ref t) => outline(&t.name, "extend enum",
+                SymbolKind::Enum, t.position, t.position.token - 1,
+                enum_values(&t.values)),
+            TypeExtension::InputObject(ref t) => outline(&t.name,
+                "extend input", SymbolKind::Struct, t.position,
+                t.position.token - 1, input_fields(&t.fields)),
+        },
+        Definition::DirectiveDefinition(ref d) => Outline {
+            name: d.name.as_ref().to_string(),
+            detail: "directive".to_string(),
+            kind: SymbolKind::Function,
+            start: described(d.position, &d.description),
+            // the name follows `@`
+            name_token: d.position.token + 2,
+            children: Vec::new(),
+        },
+        Definition::Custom(_) => return None,
+    })
+}
+
+#[cfg(feature = "schema")]
+fn child<N: AsRef<str>>(name: &N, pos: Pos, described: bool,
+    kind: SymbolKind)
+    -> Outline
+{
+    Outline {
+        name: name.as_ref().to_string(),
+        detail: match kind {
+            SymbolKind::EnumMember => "enum value",
+            _ => "field",
+        }.to_string(),
+        kind,
+        start: pos.token,
+        name_token: pos.token + described as usize,
+        children: Vec::new(),
+    }
+}
+
+fn diagnostic(range: Range, severity: DiagnosticSeverity, code: &str,
+    message: &str)
+    -> Diagnostic
+{
+    Diagnostic {
+        range, severity,
+        code: code.to_string(),
+        source: "graphql".to_string(),
+        message: message.to_string(),
+        related_information: Vec::new(),
+    }
+}
+
+#[cfg(feature = "query")]
+impl ToDiagnostic for query::ParseError {
+    fn to_diagnostic(&self, index: &LspIndex) -> Diagnostic {
+        let (start, end) = self.span();
+        diagnostic(index.range(start, end), DiagnosticSeverity::Error,
+            self.code().as_str(), self.description())
+    }
+}
+
+#[cfg(feature = "schema")]
+impl ToDiagnostic for schema::ParseError {
+    fn to_diagnostic(&self, index: &LspIndex) -> Diagnostic {
+        let (start, end) = self.span();
+        diagnostic(index.range(start, end), DiagnosticSeverity::Error,
+            self.code().as_str(), self.description())
+    }
+}
+
+/// The first position is the range of the diagnostic, the other ones are
+/// related information
+impl ToDiagnostic for ValidationError {
+    fn to_diagnostic(&self, index: &LspIndex) -> Diagnostic {
+        let mut positions = self.positions.iter()
+            .map(|&pos| index.range(pos, pos));
+        let mut result = diagnostic(positions.next().unwrap_or_default(),
+            DiagnosticSeverity::Error, self.rule.as_str(), &self.message);
+        result.related_information = positions
+            .map(|range| (range, self.message.clone()))
+            .collect();
+        result
+    }
+}
+
+#[cfg(feature = "query")]
+impl ToDiagnostic for LintWarning {
+    fn to_diagnostic(&self, index: &LspIndex) -> Diagnostic {
+        diagnostic(index.range(self.position, self.position),
+            DiagnosticSeverity::Warning, self.lint.as_str(), &self.message)
+    }
+}
+
+#[cfg(all(test, feature = "query", feature = "schema"))]
+mod test {
+    use crate::lint::Linter;
+    use crate::validation::validate;
+    use crate::{parse_query, parse_schema};
+    use super::{LspIndex, Position, Range, ToDiagnostic, DiagnosticSeverity};
+    use super::SymbolKind;
+
+    fn pos(line: u32, character: u32) -> Position {
+        Position { line, character }
+    }
+
+    #[test]
+    fn utf16_positions() {
+        let source = "# 😀 é\n{ a(s: \"😀\", x: 1) }\r\n{ b }";
+        let index = LspIndex::new(source);
+        let doc = parse_query::<&str>(source).unwrap();
+        let symbols = index.query_symbols(&doc);
+        assert_eq!(symbols[0].range, Range { start: pos(1, 0),
+                                             end: pos(1, 20) });
+        assert_eq!(symbols[1].range, Range { start: pos(2, 0),
+                                             end: pos(2, 5) });
+        let err = parse_query::<&str>("{ a(s: \"😀\", x: ) }").unwrap_err();
+        let diag = err.to_diagnostic(&LspIndex::new("{ a(s: \"😀\", x: ) }"));
+        assert_eq!(diag.range, Range { start: pos(0, 16), end: pos(0, 17) });
+        assert_eq!(diag.severity, DiagnosticSeverity::Error);
+    }
+
+    #[test]
+    fn validation_and_lints() {
+        let source = "query A { a }\nquery A { b }";
+        let index = LspIndex::new(source);
+        let doc = parse_query::<&str>(source).unwrap();
+        let diag = validate(&doc).unwrap_err()[0].to_diagnostic(&index);
+        assert_eq!(diag.code, "UniqueOperationNames");
+        assert_eq!(diag.range.start, pos(0, 0));
+        assert_eq!(diag.related_information.len(), 1);
+        assert_eq!(diag.related_information[0].0.start, pos(1, 0));
+
+        let source = "{ a a }";
+        let doc = parse_query::<&str>(source).unwrap();
+        let warnings = Linter::default().check(&doc);
+        let diag = warnings[0].to_diagnostic(&LspIndex::new(source));
+        assert_eq!(diag.severity, DiagnosticSeverity::Warning);
+    }
+
+    #[test]
+    fn schema_outline() {
+        let source = "\
+            schema { query: Q }\n\
+            \"Q\" type Q { \"A\" a: Int b(x: Int): Int }\n\
+            extend type Q { c: Int }\n\
+            enum E { X Y }\n\
+            directive @d on FIELD\n\
+            union U = Q\n";
+        let index = LspIndex::new(source);
+        let doc = parse_schema::<&str>(source).unwrap();
+        let symbols = index.schema_symbols(&doc);
+        let names = symbols.iter()
+            .map(|s| (s.name.as_str(), s.detail.as_deref().unwrap(), s.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(names, [
+            ("schema", "schema", SymbolKind::Module),
+            ("Q", "type", SymbolKind::Class),
+            ("Q", "extend type", SymbolKind::Class),
+            ("E", "enum", SymbolKind::Enum),
+            ("d", "directive", SymbolKind::Function),
+            ("U", "union", SymbolKind::Enum),
+        ]);
+        let q = &symbols[1];
+        assert_eq!(q.range, Range { start: pos(1, 0), end: pos(1, 40) });
+        assert_eq!(q.selection_range, Range { start: pos(1, 9),
+                                              end: pos(1, 10) });
+        let fields = q.children.iter()
+            .map(|c| (c.name.as_str(), c.range.start.character,
+                      c.range.end.character))
+            .collect::<Vec<_>>();
+        assert_eq!(fields, [("a", 13, 23), ("b", 24, 38)]);
+        assert_eq!(q.children[0].selection_range.start, pos(1, 17));
+        assert_eq!(symbols[2].range.start, pos(2, 0));
+        assert_eq!(symbols[3].children[1].kind, SymbolKind::EnumMember);
+        assert_eq!(symbols[4].selection_range.start, pos(4, 11));
+    }
+}
@@ -0,0 +1,265 @@
+//! Checks of the names used in the documents
+//!
+//! Names of the types, fields, arguments and other elements follow the
+//! `Name` rule of the grammar: `/[_A-Za-z][_0-9A-Za-z]*/`. The printer
+//! writes names as they are, so documents built programmatically should
+//! check or sanitize the names first.
+//!
+//! Names in the AST are `Text::Value`, so `NameExt` provides the checks
+//! for any of them, and there are conversions between the usual cases:
+//! `PascalCase` types, `camelCase` fields and `UPPER_CASE` enum values.
+//!
+//! ```rust
+//! # extern crate edb_graphql_parser;
+//! use edb_graphql_parser::name::{is_valid_name, sanitize_name, NameExt};
+//!
+//! assert!(is_valid_name("user_id"));
+//! assert!(!is_valid_name("user-id"));
+//! assert_eq!(sanitize_name("user-id"), "user_id");
+//! assert!("__typename".is_introspection_field());
+//! ```
+
+/// Names of the meta-fields of the introspection system
+pub const INTROSPECTION_FIELDS: &[&str] = &["__typename", "__schema", "__type"];
+
+
+/// Returns true if the string matches the `Name` rule of the grammar
+pub fn is_valid_name(s: &str) -> bool {
+    let mut bytes = s.bytes();
+    match bytes.next() {
+        Some(b) if b == b'_' || b.is_ascii_alphabetic() => {}
+        _ => return false,
+    }
+    bytes.all(|b| b == b'_' || b.is_ascii_alphanumeric())
+}
+
+/// Returns true if the name is reserved for the introspection system,
+/// i.e. starts with `__`
+///
+/// Such names are valid in queries (`__typename`, `__schema`), but schemas
+/// can't define types, fields, arguments or directives named like that.
+pub fn is_introspection_name(s: &str) -> bool {
+    s.starts_with("__")
+}
+
+/// Returns true for the meta-fields `__typename`, `__schema` and `__type`
+///
+/// Only the length and the first bytes are compared for other names, so
+/// this is cheap enough to call for every field.
+pub fn is_introspection_field(s: &str) -> bool {
+    let bytes = s.as_bytes();
+    bytes.len() >= 6 && bytes.len() <= 10 && bytes.starts_with(b"__") &&
+        matches!(&bytes[2..], b"typename" | b"schema" | b"type")
+}
+
+/// Checks of the names, implemented for every string type including
+/// `Text::Value`
+pub trait NameExt {
+    /// Returns true if the name is exactly `name`
+    ///
+    /// Same as comparing `as_ref()`, but reads better with the text types
+    /// that don't compare to `&str` directly.
+    fn is(&self, name: &str) -> bool;
+    /// See `is_introspection_name`
+    fn is_introspection(&self) -> bool;
+    /// See `is_introspection_field`
+    fn is_introspection_field(&self) -> bool;
+}
+
+impl<S: AsRef<str> + ?Sized> NameExt for S {
+    fn is(&self, name: &str) -> bool {
+        self.as_ref() == name
+    }
+    fn is_introspection(&self) -> bool {
+        is_introspection_name(self.as_ref())
+    }
+    fn is_introspection_field(&self) -> bool {
+        is_introspection_field(self.as_ref())
+    }
+}
+
+/// Splits the name into words at underscores and case changes
+///
+/// Acronyms are kept as a single word: `HTTPServer` is `HTTP` and
+/// `Server`.
+fn words(s: &str) -> Vec<&str> {
+    let bytes = s.as_bytes();
+    let mut words = Vec::new();
+    let mut start = 0;
+    for i in 0..bytes.len() {
+        if bytes[i] == b'_' {
+            if start < i {
+                words.push(&s[start..i]);
+            }
+            start = i + 1;
+        } else if i > start && bytes[i].is_ascii_uppercase() {
+            let prev = bytes[i - 1];
+            let next_lower = bytes.get(i + 1)
+                .map(|b| b.is_ascii_lowercase()).unwrap_or(false);
+            if !prev.is_ascii_uppercase() ||
+                (next_lower && prev.is_ascii_uppercase())
+            {
+                words.push(&s[start..i]);
+                start = i;
+            }
+        }
+    }
+    if start < bytes.len() {
+        words.push(&s[start..]);
+    }
+    words
+}
+
+fn push_capitalized(result: &mut String, word: &str) {
+    let mut chars = word.chars();
+    if let Some(first) = chars.next() {
+        result.push(first.to_ascii_uppercase());
+        result.push_str(chars.as_str());
+    }
+}
+
+/// Converts the name to `PascalCase`, as used for types
+///
+/// The first letter of every word is uppercased and the rest are kept.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::name::{to_pascal_case, to_camel_case};
+/// use edb_graphql_parser::name::to_upper_case;
+///
+/// assert_eq!(to_pascal_case("user_profile"), "UserProfile");
+/// assert_eq!(to_camel_case("HTTPServer"), "httpServer");
+/// assert_eq!(to_upper_case("userId"), "USER_ID");
+/// ```
+pub fn to_pascal_case(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for word in words(s) {
+        push_capitalized(&mut result, word);
+    }
+    result
+}
+
+/// Converts the name to `camelCase`, as used for fields and arguments
+///
+/// The first word is lowercased, the rest are as in `to_pascal_case`.
+pub fn to_camel_case(s: &str) -> String {
+    let mut words = words(s).into_iter();
+    let mut result = words.next().map(|w| w.to_ascii_lowercase())
+        .unwrap_or_default();
+    for word in words {
+        push_capitalized(&mut result, word);
+    }
+    result
+}
+
+/// Converts the name to `UPPER_CASE`, as used for enum values
+pub fn to_upper_case(s: &str) -> String {
+    words(s).iter().map(|w| w.to_ascii_uppercase())
+        .collect::<Vec<_>>().join("_")
+}
+
+/// Converts the string into a valid name which is not reserved for
+/// introspection
+///
+/// Characters not allowed in names are replaced by `_`, names starting with
+/// a digit get a `_` prefix, and a leading `__` is shortened to a single
+/// `_`. An empty string becomes `_`. Valid names without the `__` prefix
+/// are returned unchanged.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::name::sanitize_name;
+///
+/// assert_eq!(sanitize_name("first name"), "first_name");
+/// assert_eq!(sanitize_name("2fa"), "_2fa");
+/// assert_eq!(sanitize_name("__type"), "_type");
+/// assert_eq!(sanitize_name("café"), "caf_");
+/// ```
+pub fn sanitize_name(s: &str) -> String {
+    let mut result = String::with_capacity(s.len() + 1);
+    if s.is_empty() || s.as_bytes()[0].is_ascii_digit() {
+        result.push('_');
+    }
+    result.extend(s.chars().map(|c| {
+        if c == '_' || c.is_ascii_alphanumeric() { c } else { '_' }
+    }));
+    let underscores = result.bytes().take_while(|&b| b == b'_').count();
+    if underscores > 1 {
+        result.drain(..underscores - 1);
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::{is_valid_name, is_introspection_name, sanitize_name};
+    use super::{is_introspection_field, NameExt, INTROSPECTION_FIELDS};
+    use super::{to_pascal_case, to_camel_case, to_upper_case};
+
+    #[test]
+    fn valid() {
+        for name in &["a", "_", "__typename", "A1", "snake_case", "_9"] {
+            assert!(is_valid_name(name), "{:?}", name);
+        }
+        for name in &["", "1a", "a-b", "a b", "é", "a.b", "$a", "@a"] {
+            assert!(!is_valid_name(name), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn introspection() {
+        assert!(is_introspection_name("__typename"));
+        assert!(is_introspection_name("__"));
+        assert!(!is_introspection_name("_type"));
+        assert!(!is_introspection_name("type__"));
+    }
+
+    #[test]
+    fn introspection_fields() {
+        for name in INTROSPECTION_FIELDS {
+            assert!(is_introspection_field(name), "{:?}", name);
+            assert!(String::from(*name).is_introspection_field());
+        }
+        for name in &["__types", "__typ", "_type", "__", "type", "__Type"] {
+            assert!(!is_introspection_field(name), "{:?}", name);
+        }
+        assert!("__Type".is_introspection());
+        assert!(std::borrow::Cow::from("id").is("id"));
+    }
+
+    #[test]
+    fn cases() {
+        let cases = [
+            ("user", "User", "user", "USER"),
+            ("userId", "UserId", "userId", "USER_ID"),
+            ("user_id", "UserId", "userId", "USER_ID"),
+            ("USER_ID", "USERID", "userID", "USER_ID"),
+            ("HTTPServer", "HTTPServer", "httpServer", "HTTP_SERVER"),
+            ("__typename", "Typename", "typename", "TYPENAME"),
+            ("v2Api", "V2Api", "v2Api", "V2_API"),
+            ("", "", "", ""),
+        ];
+        for &(name, pascal, camel, upper) in &cases {
+            assert_eq!(to_pascal_case(name), pascal, "{:?}", name);
+            assert_eq!(to_camel_case(name), camel, "{:?}", name);
+            assert_eq!(to_upper_case(name), upper, "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn sanitize() {
+        assert_eq!(sanitize_name(""), "_");
+        assert_eq!(sanitize_name("valid_name"), "valid_name");
+        assert_eq!(sanitize_name("_private"), "_private");
+        assert_eq!(sanitize_name("-"), "_");
+        assert_eq!(sanitize_name("--x"), "_x");
+        assert_eq!(sanitize_name("123"), "_123");
+        assert_eq!(sanitize_name("a.b-c d"), "a_b_c_d");
+        assert_eq!(sanitize_name("日本"), "_");
+        for input in &["", "1", "__a", "-_-", "a b", "ü", "__"] {
+            let name = sanitize_name(input);
+            assert!(is_valid_name(&name), "{:?}", name);
+            assert!(!is_introspection_name(&name), "{:?}", name);
+        }
+    }
+}
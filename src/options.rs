@@ -0,0 +1,236 @@
+//! Parser options
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::position::Pos;
+use crate::tokenizer::TokenStream;
+
+
+/// Opaque AST node returned by a custom definition handler
+///
+/// Use `downcast_ref` to get the original value back.
+pub type CustomNode = Arc<dyn Any + Send + Sync>;
+
+/// Handler parsing a custom top-level definition
+///
+/// The handler is called with the token stream positioned at the keyword of
+/// the definition. It must consume all the tokens that make up the
+/// definition (including the keyword) and return the node representing it,
+/// or the error message. Returning a node without consuming the keyword is
+/// a parse error.
+pub type DefinitionHandler = dyn for<'a> Fn(&mut TokenStream<'a>)
+    -> Result<CustomNode, String> + Send + Sync;
+
+/// A configuration of the parser
+///
+/// By default parser accepts only the standard graphql syntax.
+#[derive(Clone, Default)]
+pub struct ParserOptions {
+    custom_definitions: BTreeMap<String, Arc<DefinitionHandler>>,
+    lone_anonymous_operation: bool,
+    unique_names: bool,
+    client_controlled_nullability: bool,
+    fragment_arguments: bool,
+    source_name: Option<String>,
+    start_position: Option<Pos>,
+    max_depth: Option<usize>,
+}
+
+impl ParserOptions {
+    /// Register a handler for unknown top-level definitions starting with
+    /// `keyword`
+    ///
+    /// The handler is only called when the definition can't be parsed as
+    /// a standard one, so it's possible to register a handler for `extend`
+    /// in order to support non-standard extensions.
+    pub fn custom_definition<F>(&mut self, keyword: &str, handler: F)
+        -> &mut Self
+        where F: for<'a> Fn(&mut TokenStream<'a>) -> Result<CustomNode, String>,
+              F: Send + Sync + 'static,
+    {
+        self.custom_definitions.insert(keyword.to_string(), Arc::new(handler));
+        self
+    }
+
+    /// Reject queries having an anonymous operation along with other
+    /// operations
+    ///
+    /// Such documents can never be executed (see `LoneAnonymousOperation`
+    /// validation rule), so enabling this makes the error reported by the
+    /// parser, with positions of both operations.
+    pub fn lone_anonymous_operation(&mut self, value: bool) -> &mut Self {
+        self.lone_anonymous_operation = value;
+        self
+    }
+
+    /// Reject queries having two operations or two fragments with the same
+    /// name
+    ///
+    /// This is the cheap part of the `UniqueOperationNames` and
+    /// `UniqueFragmentNames` validation rules. The error is reported at the
+    /// second definition, `related_positions` of the error has the first
+    /// one.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "query")] {
+    /// # extern crate edb_graphql_parser;
+    /// use edb_graphql_parser::ParserOptions;
+    /// use edb_graphql_parser::error::ErrorCode;
+    /// use edb_graphql_parser::query::parse_query_with_options;
+    ///
+    /// let mut options = ParserOptions::default();
+    /// options.unique_names(true);
+    /// let err = parse_query_with_options::<&str>(
+    ///     "query A { a }\nquery A { b }", &options).unwrap_err();
+    /// assert_eq!(err.code(), ErrorCode::DuplicateName);
+    /// assert_eq!(err.span().0.to_string(), "2:1");
+    /// assert_eq!(err.related_positions()[0].to_string(), "1:1");
+    /// # }
+    /// ```
+    pub fn unique_names(&mut self, value: bool) -> &mut Self {
+        self.unique_names = value;
+        self
+    }
+
+    /// Accept experimental client controlled nullability designators on
+    /// fields: `field!`, `field?` and the list forms like `field[!]?`
+    ///
+    /// The syntax is a proposal which may change, so by default it's
+    /// rejected.
+    pub fn client_controlled_nullability(&mut self, value: bool)
+        -> &mut Self
+    {
+        self.client_controlled_nullability = value;
+        self
+    }
+
+    /// Accept experimental fragment arguments: variable definitions on
+    /// fragments (`fragment F($x: Int) on T`) and arguments of fragment
+    /// spreads (`...F(x: 3)`)
+    pub fn fragment_arguments(&mut self, value: bool) -> &mut Self {
+        self.fragment_arguments = value;
+        self
+    }
+
+    /// Name of the parsed source, like a file name, reported in the parse
+    /// errors
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "schema")] {
+    /// # extern crate edb_graphql_parser;
+    /// use edb_graphql_parser::ParserOptions;
+    /// use edb_graphql_parser::schema::parse_schema_with_options;
+    ///
+    /// let mut options = ParserOptions::default();
+    /// options.source_name("user.graphql");
+    /// let err = parse_schema_with_options::<&str>("type User {", &options)
+    ///     .unwrap_err();
+    /// assert_eq!(err.location().unwrap().to_string(), "user.graphql:1:12");
+    /// assert!(err.to_string().contains("at user.graphql:1:12"));
+    /// # }
+    /// ```
+    pub fn source_name(&mut self, name: &str) -> &mut Self {
+        self.source_name = Some(name.to_string());
+        self
+    }
+
+    /// Position of the start of the source in a larger text, e.g. of the
+    /// GraphQL in a template literal of a JavaScript file
+    ///
+    /// All positions in the AST and in the errors are relative to the
+    /// larger text. Lines after the first one start at column 1.
+    pub fn start_position(&mut self, position: Pos) -> &mut Self {
+        self.start_position = Some(position);
+        self
+    }
+
+    /// Maximum nesting of selection sets, list and object values and list
+    /// types, deeper documents are rejected with a parse error
+    ///
+    /// By default the nesting isn't limited. Values and types are cloned
+    /// and compared without recursion, but the AST is still dropped
+    /// recursively, so a very deeply nested document may overflow the
+    /// stack. Set a limit when parsing untrusted input.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "query")] {
+    /// # extern crate edb_graphql_parser;
+    /// use edb_graphql_parser::ParserOptions;
+    /// use edb_graphql_parser::query::parse_query_with_options;
+    ///
+    /// let mut options = ParserOptions::default();
+    /// options.max_depth(2);
+    /// assert!(parse_query_with_options::<&str>("{ a { b } }", &options)
+    ///     .is_ok());
+    /// assert!(parse_query_with_options::<&str>("{ a { b { c } } }", &options)
+    ///     .is_err());
+    /// # }
+    /// ```
+    pub fn max_depth(&mut self, value: usize) -> &mut Self {
+        self.max_depth = Some(value);
+        self
+    }
+
+    #[cfg(any(feature = "query", feature = "schema"))]
+    pub(crate) fn tokens<'a>(&self, s: &'a str) -> TokenStream<'a> {
+        let mut tokens = match self.start_position {
+            Some(position) => TokenStream::starting_at(s, position),
+            None => TokenStream::new(s),
+        };
+        if let Some(max_depth) = self.max_depth {
+            tokens.max_depth(max_depth);
+        }
+        tokens
+    }
+
+    #[cfg(any(feature = "query", feature = "schema"))]
+    pub(crate) fn source(&self) -> Option<&str> {
+        self.source_name.as_ref().map(|s| &s[..])
+    }
+
+    #[cfg(feature = "query")]
+    pub(crate) fn allow_fragment_arguments(&self) -> bool {
+        self.fragment_arguments
+    }
+
+    #[cfg(feature = "query")]
+    pub(crate) fn allow_client_controlled_nullability(&self) -> bool {
+        self.client_controlled_nullability
+    }
+
+    #[cfg(feature = "query")]
+    pub(crate) fn check_lone_anonymous_operation(&self) -> bool {
+        self.lone_anonymous_operation
+    }
+
+    #[cfg(feature = "query")]
+    pub(crate) fn check_unique_names(&self) -> bool {
+        self.unique_names
+    }
+
+    #[cfg(feature = "schema")]
+    pub(crate) fn definition_handler(&self, keyword: &str)
+        -> Option<&DefinitionHandler>
+    {
+        self.custom_definitions.get(keyword).map(|h| &**h)
+    }
+}
+
+impl fmt::Debug for ParserOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ParserOptions")
+            .field("custom_definitions",
+                   &self.custom_definitions.keys().collect::<Vec<_>>())
+            .field("lone_anonymous_operation", &self.lone_anonymous_operation)
+            .field("unique_names", &self.unique_names)
+            .field("client_controlled_nullability",
+                   &self.client_controlled_nullability)
+            .field("fragment_arguments", &self.fragment_arguments)
+            .field("source_name", &self.source_name)
+            .field("start_position", &self.start_position)
+            .field("max_depth", &self.max_depth)
+            .finish()
+    }
+}
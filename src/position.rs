@@ -25,3 +25,201 @@ impl fmt::Display for Pos {
         write!(f, "{}:{}", self.line, self.column)
     }
 }
+
+/// Position in a named source, like a file
+///
+/// Displayed as `name:line:column`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Location {
+    /// Name of the source, e.g. a file name
+    pub source: String,
+    pub position: Pos,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.source, self.position)
+    }
+}
+
+/// Converts between byte offsets in the source and positions
+///
+/// The index is built once from the source, finding the line of an offset
+/// or position is a binary search. Lines are separated by `\n`, columns count
+/// characters, except that a tab counts as 8 columns and `\r` and byte order
+/// mark as none, which matches positions reported by the parser outside of
+/// string literals.
+///
+/// ```rust
+/// # #[cfg(feature = "query")] {
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::parse_query;
+/// use edb_graphql_parser::position::LineIndex;
+/// use edb_graphql_parser::query::Definition;
+///
+/// let source = "query {\n  hello\n}";
+/// let index = LineIndex::new(source);
+/// let doc = parse_query::<&str>(source).unwrap();
+/// if let Definition::Operation(ref op) = doc.definitions[0] {
+///     let end = index.offset(op.selection_set.span.1).unwrap();
+///     assert_eq!(&source[end..], "}");
+/// }
+/// assert_eq!(index.position(10).unwrap().to_string(), "2:3");
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct LineIndex<'a> {
+    source: &'a str,
+    /// Byte and character offsets of the line starts
+    lines: Vec<(usize, usize)>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(source: &'a str) -> LineIndex<'a> {
+        let mut lines = vec![(0, 0)];
+        let mut characters = 0;
+        for (offset, c) in source.char_indices() {
+            characters += 1;
+            if c == '\n' {
+                lines.push((offset + 1, characters));
+            }
+        }
+        LineIndex { source, lines }
+    }
+
+    /// Returns the position of the byte offset (with zero token index)
+    ///
+    /// Returns `None` if the offset is out of the source or not at
+    /// a character boundary.
+    pub fn position(&self, offset: usize) -> Option<Pos> {
+        if !self.source.is_char_boundary(offset) {
+            return None;
+        }
+        let line = self.lines.partition_point(|&(start, _)| start <= offset);
+        let (start, character) = self.lines[line - 1];
+        let (column, chars) = self.source[start..offset].chars()
+            .fold((1, 0), |(column, chars), c| (column + width(c), chars + 1));
+        Some(Pos { line, column, character: character + chars, token: 0 })
+    }
+
+    /// Returns the byte offset of the line and column of the position
+    ///
+    /// Characters that occupy no columns (`\r` and byte order mark) are
+    /// skipped. Returns `None` if there is no such line or column.
+    pub fn offset(&self, pos: Pos) -> Option<usize> {
+        let start = self.lines.get(pos.line.checked_sub(1)?)?.0;
+        let end = self.lines.get(pos.line).map_or(self.source.len(), |l| l.0);
+        let mut column = 1;
+        for (idx, c) in self.source[start..end].char_indices() {
+            if column == pos.column && width(c) > 0 {
+                return Some(start + idx);
+            }
+            column += width(c);
+        }
+        if column == pos.column && end == self.source.len() {
+            return Some(end);
+        }
+        None
+    }
+
+    /// Returns the byte offset of the character offset of the position
+    pub fn character_offset(&self, pos: Pos) -> Option<usize> {
+        let line = self.lines
+            .partition_point(|&(_, chars)| chars <= pos.character);
+        let (start, chars) = self.lines[line - 1];
+        let rest = &self.source[start..];
+        match rest.char_indices().nth(pos.character - chars) {
+            Some((idx, _)) => Some(start + idx),
+            None if pos.character - chars == rest.chars().count() => {
+                Some(self.source.len())
+            }
+            None => None,
+        }
+    }
+
+    /// Returns the text between the character offsets of the positions,
+    /// the end is exclusive
+    ///
+    /// Returns `None` if the positions are out of the source or in the
+    /// wrong order.
+    pub fn slice(&self, span: (Pos, Pos)) -> Option<&'a str> {
+        let start = self.character_offset(span.0)?;
+        let end = self.character_offset(span.1)?;
+        self.source.get(start..end)
+    }
+}
+
+/// Replacement of a part of the source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    /// Start and end of the replaced text
+    pub span: (Pos, Pos),
+    pub replacement: String,
+}
+
+/// Applies the edits to the source
+///
+/// Edits must not overlap, they may be in any order. Positions are found
+/// by their character offsets.
+pub fn apply_edits(source: &str, edits: &[TextEdit]) -> String {
+    let index = LineIndex::new(source);
+    let mut ranges = edits.iter().map(|edit| {
+        let start = index.character_offset(edit.span.0)
+            .expect("edit is in the source");
+        let end = index.character_offset(edit.span.1)
+            .expect("edit is in the source");
+        (start, end, &edit.replacement)
+    }).collect::<Vec<_>>();
+    ranges.sort_by_key(|&(start, _, _)| start);
+    let mut result = String::with_capacity(source.len());
+    let mut offset = 0;
+    for (start, end, replacement) in ranges {
+        result.push_str(&source[offset..start]);
+        result.push_str(replacement);
+        offset = end;
+    }
+    result.push_str(&source[offset..]);
+    result
+}
+
+pub(crate) fn width(c: char) -> usize {
+    match c {
+        '\t' => 8,
+        '\r' | '\u{feff}' => 0,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use combine::{Positioned, StreamOnce};
+
+    use crate::tokenizer::TokenStream;
+    use super::{LineIndex, Pos};
+
+    #[test]
+    fn tokens() {
+        let source = "\u{feff}query {\r\n\t\u{e9}: a # c\n  b(x: 1)\n}";
+        let index = LineIndex::new(source);
+        let mut tokens = TokenStream::new(source);
+        let mut offset = 3;  // after byte order mark
+        loop {
+            let pos = tokens.position();
+            let tok = match tokens.uncons() {
+                Ok(tok) => tok,
+                Err(_) => break,
+            };
+            offset += source[offset..].find(tok.value).unwrap();
+            assert_eq!(index.offset(pos), Some(offset), "{}", tok);
+            assert_eq!(index.character_offset(pos), Some(offset), "{}", tok);
+            assert_eq!(index.position(offset), Some(Pos { token: 0, ..pos }));
+            offset += tok.value.len();
+        }
+        let end = index.position(source.len()).unwrap();
+        assert_eq!(end.to_string(), "4:2");
+        assert_eq!(index.offset(end), Some(source.len()));
+        assert_eq!(index.character_offset(end), Some(source.len()));
+        assert_eq!(index.position(source.len() + 1), None);
+        assert_eq!(index.position(1), None);
+    }
+}
@@ -0,0 +1,42 @@
+//! Commonly used types and functions
+//!
+//! Query types are exported under their own names, schema types that
+//! clash with them have a `Schema` prefix.
+//!
+//! ```rust
+//! # #[cfg(all(feature = "query", feature = "schema"))] {
+//! # extern crate edb_graphql_parser;
+//! use edb_graphql_parser::prelude::*;
+//!
+//! let doc: Document<&str> = parse_query("{ user { name } }").unwrap();
+//! let schema: SchemaDocument<&str> = parse_schema("type User { name: String }")
+//!     .unwrap();
+//! assert_eq!(doc.definitions.len(), 1);
+//! assert_eq!(schema.definitions.len(), 1);
+//! # }
+//! ```
+pub use crate::common::{Text, Type, Value, Number, Directive};
+pub use crate::format::Style;
+pub use crate::options::ParserOptions;
+pub use crate::position::Pos;
+#[cfg(feature = "query")]
+pub use crate::query::{parse_query, ParseError as QueryParseError};
+#[cfg(feature = "query")]
+pub use crate::query::{Document, Definition, Operation, OperationKind};
+#[cfg(feature = "query")]
+pub use crate::query::FragmentDefinition;
+#[cfg(feature = "query")]
+pub use crate::query::{Field, Selection, SelectionSet};
+#[cfg(feature = "schema")]
+pub use crate::schema::{parse_schema, ParseError as SchemaParseError};
+#[cfg(feature = "schema")]
+pub use crate::schema::{Document as SchemaDocument};
+#[cfg(feature = "schema")]
+pub use crate::schema::{Definition as SchemaDefinition};
+#[cfg(feature = "schema")]
+pub use crate::schema::{Field as SchemaField, TypeDefinition};
+pub use crate::validation::ValidationError;
+#[cfg(feature = "query")]
+pub use crate::validation::validate;
+#[cfg(all(feature = "query", feature = "schema"))]
+pub use crate::validation::validate_with_schema;
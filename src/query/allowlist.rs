@@ -0,0 +1,192 @@
+use std::collections::BTreeSet;
+
+use crate::query::ast::*;
+use crate::query::error::OperationError;
+use crate::query::format::apq_hash;
+use crate::query::normalize::normalize;
+use crate::query::separate::with_fragments;
+
+
+/// Set of trusted operations, e.g. for executing only known operations
+///
+/// Operations are compared by the hash of their canonical form (see
+/// `Allowlist::hash`), so documents that differ in formatting, in the
+/// order of the arguments, variables and fragments, or in unused
+/// fragments match each other.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::parse_query;
+/// use edb_graphql_parser::query::Allowlist;
+///
+/// let trusted = parse_query::<&str>("
+///     query User($id: ID!, $full: Boolean) {
+///       user(id: $id, full: $full) { ...U }
+///     }
+///     fragment U on User { name }
+/// ").unwrap();
+/// let mut allowlist = Allowlist::new();
+/// allowlist.add_document(&trusted);
+///
+/// let incoming = parse_query::<&str>("
+///     fragment U on User { name }
+///     query User($full: Boolean, $id: ID!) {
+///       user(full: $full, id: $id) { ...U } }
+/// ").unwrap();
+/// assert_eq!(allowlist.allows(&incoming, Some("User")), Ok(true));
+/// let changed = parse_query::<&str>("query User { user { email } }")
+///     .unwrap();
+/// assert_eq!(allowlist.allows(&changed, None), Ok(false));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Allowlist {
+    hashes: BTreeSet<String>,
+}
+
+impl Allowlist {
+    /// Creates an empty allowlist allowing nothing
+    pub fn new() -> Allowlist {
+        Allowlist::default()
+    }
+
+    /// Returns the hash of the canonical form of the operation
+    ///
+    /// Canonical form is the normalized (see `normalize`) operation
+    /// followed by the fragments it uses sorted by name, formatted with
+    /// the default style. The hash is a lowercase hex-encoded SHA-256 of
+    /// this text.
+    ///
+    /// The operation is selected by name as in `Document::operation`.
+    pub fn hash<'a, T>(doc: &Document<'a, T>, operation: Option<&str>)
+        -> Result<String, OperationError>
+        where T: Text<'a> + Clone,
+    {
+        let op = doc.operation(operation)?;
+        let mut separated = with_fragments(doc, op);
+        separated.definitions[1..]
+            .sort_by(|a, b| fragment_name(a).cmp(&fragment_name(b)));
+        Ok(apq_hash(&normalize(separated).to_string()))
+    }
+
+    /// Adds every operation of the trusted document
+    ///
+    /// Panics if the document has several operations and one of them is
+    /// anonymous, as such operation can't be selected.
+    pub fn add_document<'a, T>(&mut self, doc: &Document<'a, T>)
+        -> &mut Self
+        where T: Text<'a> + Clone,
+    {
+        for op in doc.operations() {
+            let name = op.name.as_ref().map(|n| n.as_ref());
+            let hash = Allowlist::hash(doc, name)
+                .expect("anonymous operation is the only one");
+            self.hashes.insert(hash);
+        }
+        self
+    }
+
+    /// Adds the hash of a trusted operation, see `Allowlist::hash`
+    ///
+    /// Uppercase hashes are accepted too.
+    pub fn add_hash<S: AsRef<str>>(&mut self, hash: S) -> &mut Self {
+        self.hashes.insert(hash.as_ref().to_ascii_lowercase());
+        self
+    }
+
+    /// Returns the hashes of the trusted operations in lexicographical
+    /// order
+    pub fn hashes(&self) -> impl Iterator<Item=&str> {
+        self.hashes.iter().map(|h| h.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    /// Checks whether the operation of the incoming document is trusted
+    ///
+    /// The operation is selected by name as in `Document::operation`, the
+    /// error is returned if it can't be selected.
+    pub fn allows<'a, T>(&self, doc: &Document<'a, T>,
+        operation: Option<&str>)
+        -> Result<bool, OperationError>
+        where T: Text<'a> + Clone,
+    {
+        Ok(self.hashes.contains(&Allowlist::hash(doc, operation)?))
+    }
+}
+
+impl<'x, 'a, T> Extend<&'x Document<'a, T>> for Allowlist
+    where T: Text<'a> + Clone + 'x, 'a: 'x,
+{
+    fn extend<I>(&mut self, documents: I)
+        where I: IntoIterator<Item=&'x Document<'a, T>>,
+    {
+        for doc in documents {
+            self.add_document(doc);
+        }
+    }
+}
+
+fn fragment_name<'x, 'a, T>(def: &'x Definition<'a, T>) -> Option<&'x str>
+    where T: Text<'a>,
+{
+    match *def {
+        Definition::Fragment(ref frag) => Some(frag.name.as_ref()),
+        Definition::Operation(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse_query;
+    use crate::query::OperationError;
+    use super::Allowlist;
+
+    #[test]
+    fn matching() {
+        let trusted = vec![
+            parse_query::<&str>("
+                query A { ...F ...G }
+                fragment F on Query { f }
+                fragment G on Query { g(y: 2, x: 1) }
+                mutation M { m }
+            ").unwrap(),
+            parse_query::<&str>("{ x }").unwrap(),
+        ];
+        let mut allowlist = Allowlist::new();
+        allowlist.extend(&trusted);
+        assert_eq!(allowlist.len(), 3);
+
+        let allows = |source, name| {
+            allowlist.allows(&parse_query::<&str>(source).unwrap(), name)
+        };
+        assert_eq!(allows("fragment G on Query { g(x: 1, y: 2) }
+                           fragment F on Query { f }
+                           fragment Unused on Query { u }
+                           query A { ...F ...G }", Some("A")), Ok(true));
+        assert_eq!(allows("query { x }", None), Ok(true));
+        assert_eq!(allows("mutation M { m } { x }", Some("M")), Ok(true));
+        assert_eq!(allows("query A { ...G ...F }
+                           fragment F on Query { f }
+                           fragment G on Query { g(y: 2, x: 1) }", None),
+                   Ok(false));
+        assert_eq!(allows("query B { x }", None), Ok(false));
+        assert_eq!(allows("{ x } { y }", None),
+                   Err(OperationError::NameRequired));
+    }
+
+    #[test]
+    fn hashes() {
+        let doc = parse_query::<&str>("query A { a }").unwrap();
+        let hash = Allowlist::hash(&doc, None).unwrap();
+        let mut allowlist = Allowlist::new();
+        allowlist.add_hash(hash.to_uppercase());
+        assert_eq!(allowlist.hashes().collect::<Vec<_>>(), [&hash[..]]);
+        assert_eq!(allowlist.allows(&doc, Some("A")), Ok(true));
+    }
+}
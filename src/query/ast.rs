@@ -5,8 +5,12 @@
 //!
 //! [graphql grammar]: http://facebook.github.io/graphql/October2016/#sec-Appendix-Grammar-Summary
 //!
-use crate::position::Pos;
+use crate::name::is_introspection_field;
+use crate::position::{LineIndex, Pos};
+use crate::query::error::OperationError;
+use crate::tokenizer::{positioned_tokens, Kind, Token};
 pub use crate::common::{Directive, Number, Value, Text, Type};
+pub use crate::common::ArgumentsSyntax;
 
 /// Root of query data
 #[derive(Debug, Clone, PartialEq)]
@@ -15,6 +19,8 @@ pub struct Document<'a, T: Text<'a>> {
 }
 
 impl<'a> Document<'a, String> {
+    /// Drops the lifetime of the owned document, use `map_text` to get an
+    /// owned copy of a document of another text type
     pub fn into_static(self) -> Document<'static, String> {
         // To support both reference and owned values in the AST,
         // all string data is represented with the ::common::Str<'a, T: Text<'a>>
@@ -33,22 +39,142 @@ impl<'a> Document<'a, String> {
     }
 }
 
+impl<'a, T> Document<'a, T>
+    where T: Text<'a>,
+{
+    /// Returns the operations of the document
+    pub fn operations(&self) -> impl Iterator<Item=&Operation<'a, T>> {
+        self.definitions.iter().filter_map(|def| match *def {
+            Definition::Operation(ref op) => Some(op),
+            Definition::Fragment(_) => None,
+        })
+    }
+
+    /// Returns the fragment definitions of the document
+    pub fn fragments(&self)
+        -> impl Iterator<Item=&FragmentDefinition<'a, T>>
+    {
+        self.definitions.iter().filter_map(|def| match *def {
+            Definition::Operation(_) => None,
+            Definition::Fragment(ref frag) => Some(frag),
+        })
+    }
+
+    /// Returns the original text of every definition, see
+    /// `Definition::source_slice`
+    pub fn definition_sources<'s>(&self, source: &'s str)
+        -> Option<Vec<&'s str>>
+    {
+        let index = LineIndex::new(source);
+        self.definitions.iter().map(|def| def.slice_in(&index)).collect()
+    }
+
+    /// Returns the first fragment with the name
+    pub fn fragment(&self, name: &str) -> Option<&FragmentDefinition<'a, T>> {
+        self.fragments().find(|frag| frag.name.as_ref() == name)
+    }
+
+    /// Returns the operation to execute
+    ///
+    /// Same as in the GraphQL request, the name may only be omitted if the
+    /// document contains a single operation:
+    ///
+    /// ```rust
+    /// # extern crate edb_graphql_parser;
+    /// use edb_graphql_parser::parse_query;
+    /// use edb_graphql_parser::query::OperationError;
+    ///
+    /// let doc = parse_query::<&str>("query A { a } query B { b }").unwrap();
+    /// assert!(doc.operation(Some("B")).is_ok());
+    /// assert_eq!(doc.operation(None).unwrap_err(),
+    ///            OperationError::NameRequired);
+    /// ```
+    pub fn operation(&self, name: Option<&str>)
+        -> Result<&Operation<'a, T>, OperationError>
+    {
+        match name {
+            Some(name) => self.operations()
+                .find(|op| op.name.as_ref().map(|n| n.as_ref()) == Some(name))
+                .ok_or_else(|| OperationError::Unknown(name.into())),
+            None => {
+                let mut operations = self.operations();
+                match (operations.next(), operations.next()) {
+                    (Some(op), None) => Ok(op),
+                    (None, _) => Err(OperationError::NoOperations),
+                    (Some(_), Some(_)) => Err(OperationError::NameRequired),
+                }
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Definition<'a, T: Text<'a>> {
     Operation(Operation<'a, T>),
     Fragment(FragmentDefinition<'a, T>),
 }
 
+impl<'a, T> Definition<'a, T>
+    where T: Text<'a>,
+{
+    /// Returns the original text of the definition
+    ///
+    /// `source` must be the text the document is parsed from. The text
+    /// spans from the first token of the definition to its closing brace,
+    /// so comments and whitespace around it are not included. Returns
+    /// `None` if the positions don't match the source.
+    ///
+    /// ```rust
+    /// # extern crate edb_graphql_parser;
+    /// use edb_graphql_parser::parse_query;
+    ///
+    /// let source = "query A {\n  a(x:1) # first\n}\n{ b }";
+    /// let doc = parse_query::<&str>(source).unwrap();
+    /// assert_eq!(doc.definitions[0].source_slice(source),
+    ///            Some("query A {\n  a(x:1) # first\n}"));
+    /// assert_eq!(doc.definition_sources(source).unwrap()[1], "{ b }");
+    /// ```
+    pub fn source_slice<'s>(&self, source: &'s str) -> Option<&'s str> {
+        self.slice_in(&LineIndex::new(source))
+    }
+
+    fn slice_in<'s>(&self, index: &LineIndex<'s>) -> Option<&'s str> {
+        match *self {
+            Definition::Operation(ref op) => {
+                op.selection_set.slice_in(index, op.position)
+            }
+            Definition::Fragment(ref frag) => {
+                frag.selection_set.slice_in(index, frag.position)
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct FragmentDefinition<'a, T: Text<'a>> {
     pub position: Pos,
     pub name: T::Value,
+    /// Experimental fragment arguments, only parsed if enabled by
+    /// `ParserOptions::fragment_arguments`
+    pub variable_definitions: Vec<VariableDefinition<'a, T>>,
+    /// Position of the `on` keyword
+    pub on_position: Pos,
     pub type_condition: TypeCondition<'a, T>,
     pub directives: Vec<Directive<'a, T>>,
     pub selection_set: SelectionSet<'a, T>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl<'a, T> FragmentDefinition<'a, T>
+    where T: Text<'a>,
+{
+    /// Returns the original text of the fragment definition, see
+    /// `Definition::source_slice`
+    pub fn source_slice<'s>(&self, source: &'s str) -> Option<&'s str> {
+        self.selection_set.slice_in(&LineIndex::new(source), self.position)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum OperationKind {
     ImplicitQuery,
     Query,
@@ -80,12 +206,141 @@ pub struct Operation<'a, T: Text<'a>> {
     pub selection_set: SelectionSet<'a, T>,
 }
 
+impl<'a, T> Operation<'a, T>
+    where T: Text<'a>,
+{
+    /// Returns the original text of the operation, see
+    /// `Definition::source_slice`
+    pub fn source_slice<'s>(&self, source: &'s str) -> Option<&'s str> {
+        self.selection_set.slice_in(&LineIndex::new(source), self.position)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct SelectionSet<'a, T: Text<'a>> {
     pub span: (Pos, Pos),
     pub items: Vec<Selection<'a, T>>,
 }
 
+impl<'a, T> SelectionSet<'a, T>
+    where T: Text<'a>,
+{
+    /// Returns the fields of this selection set, fields of the nested
+    /// fragments are not included
+    pub fn fields(&self) -> impl Iterator<Item=&Field<'a, T>> {
+        self.items.iter().filter_map(|item| match *item {
+            Selection::Field(ref field) => Some(field),
+            _ => None,
+        })
+    }
+
+    /// Returns the first field with the response key (alias if the field
+    /// has one, otherwise name)
+    ///
+    /// ```rust
+    /// # extern crate edb_graphql_parser;
+    /// use edb_graphql_parser::parse_query;
+    ///
+    /// let doc = parse_query::<&str>("{ me: user { id } user }").unwrap();
+    /// let set = &doc.operations().next().unwrap().selection_set;
+    /// assert_eq!(set.field("me").unwrap().name, "user");
+    /// assert!(set.field("user").unwrap().alias.is_none());
+    /// assert!(set.field("id").is_none());
+    /// ```
+    pub fn field(&self, response_key: &str) -> Option<&Field<'a, T>> {
+        self.fields().find(|field| field.response_key() == response_key)
+    }
+
+    /// Returns true if `__typename` is selected by this selection set
+    /// (under any alias), not counting the fragments
+    pub fn contains_typename(&self) -> bool {
+        self.fields().any(|field| field.name.as_ref() == "__typename")
+    }
+
+    /// Returns the original text of the selection set including the
+    /// braces, see `Definition::source_slice`
+    ///
+    /// Returns `None` for the empty selection set of a leaf field.
+    pub fn source_slice<'s>(&self, source: &'s str) -> Option<&'s str> {
+        self.slice_in(&LineIndex::new(source), self.span.0)
+    }
+
+    /// Returns the text from the start to the closing brace
+    fn slice_in<'s>(&self, index: &LineIndex<'s>, start: Pos)
+        -> Option<&'s str>
+    {
+        if self.items.is_empty() {
+            return None;
+        }
+        let end = Pos { character: self.span.1.character + 1, .. self.span.1 };
+        index.slice((start, end)).filter(|text| text.ends_with('}'))
+    }
+}
+
+/// Returns the index of the token at the position if the tokens are of
+/// the source the node is parsed from
+fn token_index(tokens: &[(Token, Pos)], position: Pos) -> Option<usize> {
+    match tokens.get(position.token) {
+        Some(&(_, pos)) if pos.character == position.character => {
+            Some(position.token)
+        }
+        _ => None,
+    }
+}
+
+/// Returns the index of the `...` token before the spread or the inline
+/// fragment at the position
+fn spread_start(tokens: &[(Token, Pos)], position: Pos) -> Option<usize> {
+    let start = token_index(tokens, position)?.checked_sub(1)?;
+    match tokens[start] {
+        (token, _) if token.value == "..." => Some(start),
+        _ => None,
+    }
+}
+
+/// Returns the text of a field or fragment spread without a selection set
+/// starting at the token index `first`
+///
+/// The end of such a node isn't recorded, so its tokens are read from the
+/// start: a name only continues the node after `...`, `@` or the colon of
+/// an alias, and brackets are skipped up to the matching one.
+fn leaf_slice<'s>(source: &'s str, tokens: &[(Token, Pos)], first: usize)
+    -> Option<&'s str>
+{
+    let mut previous = None;
+    let mut depth = 0usize;
+    let mut last = None;
+    for (idx, &(token, _)) in tokens.iter().enumerate().skip(first) {
+        let punctuator = token.kind == Kind::Punctuator;
+        let continues = depth > 0 || match (token.kind, previous) {
+            (Kind::Name, None) => true,
+            (Kind::Name, Some(prev)) => matches!(prev, "..." | "@" | ":"),
+            (Kind::Punctuator, None) => token.value == "...",
+            (Kind::Punctuator, Some(_)) => match token.value {
+                ":" => idx == first + 1,
+                "(" | "[" | "!" | "?" | "@" => true,
+                _ => false,
+            },
+            _ => false,
+        };
+        if !continues {
+            break;
+        }
+        match token.value {
+            "(" | "[" if punctuator => depth += 1,
+            ")" | "]" if punctuator => depth -= 1,
+            _ => {}
+        }
+        previous = Some(token.value);
+        last = Some(idx);
+    }
+    let index = LineIndex::new(source);
+    let (_, start) = tokens[first];
+    let (last_token, end) = tokens[last?];
+    let end = index.character_offset(end)? + last_token.value.len();
+    source.get(index.character_offset(start)?..end)
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct DefaultValue<'a, T: Text<'a>> {
     pub span: (Pos, Pos),
@@ -99,6 +354,7 @@ pub struct VariableDefinition<'a, T: Text<'a>> {
     pub name: T::Value,
     pub var_type: Type<'a, T>,
     pub default_value: Option<DefaultValue<'a, T>>,
+    pub directives: Vec<Directive<'a, T>>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -114,17 +370,105 @@ pub struct Field<'a, T: Text<'a>> {
     pub alias: Option<T::Value>,
     pub name: T::Value,
     pub arguments: Vec<(T::Value, Value<'a, T>)>,
+    /// Punctuators of the parsed argument list, `None` if there is no
+    /// argument list or the node is constructed
+    pub arguments_syntax: Option<ArgumentsSyntax>,
+    /// Experimental client controlled nullability, only parsed if enabled
+    /// by `ParserOptions::client_controlled_nullability`
+    pub nullability: Option<Nullability>,
     pub directives: Vec<Directive<'a, T>>,
     pub selection_set: SelectionSet<'a, T>,
 }
 
+impl<'a, T> Field<'a, T>
+    where T: Text<'a>,
+{
+    /// Returns the key of the field in the response: alias or name
+    pub fn response_key(&self) -> &str {
+        self.alias.as_ref().unwrap_or(&self.name).as_ref()
+    }
+
+    /// Returns the first subfield with the response key
+    pub fn field(&self, response_key: &str) -> Option<&Field<'a, T>> {
+        self.selection_set.field(response_key)
+    }
+
+    /// Returns the subfields, see `SelectionSet::fields`
+    pub fn fields(&self) -> impl Iterator<Item=&Field<'a, T>> {
+        self.selection_set.fields()
+    }
+
+    /// Returns true for `__typename`, `__schema` and `__type`, see
+    /// `name::is_introspection_field`
+    pub fn is_introspection_field(&self) -> bool {
+        is_introspection_field(self.name.as_ref())
+    }
+
+    /// Returns the original text of the field from the alias or name to
+    /// the end of the selection set or the last directive, see
+    /// `Definition::source_slice`
+    ///
+    /// ```rust
+    /// # extern crate edb_graphql_parser;
+    /// use edb_graphql_parser::parse_query;
+    ///
+    /// let source = "{ me: user(id: 1) @live name }";
+    /// let doc = parse_query::<&str>(source).unwrap();
+    /// let set = &doc.operations().next().unwrap().selection_set;
+    /// assert_eq!(set.field("me").unwrap().source_slice(source),
+    ///            Some("me: user(id: 1) @live"));
+    /// ```
+    pub fn source_slice<'s>(&self, source: &'s str) -> Option<&'s str> {
+        if self.selection_set.items.is_empty() {
+            let tokens = positioned_tokens(source);
+            leaf_slice(source, &tokens, token_index(&tokens, self.position)?)
+        } else {
+            self.selection_set.slice_in(&LineIndex::new(source),
+                                        self.position)
+        }
+    }
+}
+
+/// Client controlled nullability of the field: `field!`, `list[?]!`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Nullability {
+    Designator(NullabilityDesignator),
+    /// Nullability of the list elements, followed by the designator of the
+    /// list itself
+    List(Option<Box<Nullability>>, Option<NullabilityDesignator>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NullabilityDesignator {
+    /// `!`
+    Required,
+    /// `?`
+    Optional,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct FragmentSpread<'a, T: Text<'a>> {
     pub position: Pos,
     pub fragment_name: T::Value,
+    /// Experimental fragment arguments, only parsed if enabled by
+    /// `ParserOptions::fragment_arguments`
+    pub arguments: Vec<(T::Value, Value<'a, T>)>,
+    /// Punctuators of the parsed argument list, see `Field::arguments_syntax`
+    pub arguments_syntax: Option<ArgumentsSyntax>,
     pub directives: Vec<Directive<'a, T>>,
 }
 
+impl<'a, T> FragmentSpread<'a, T>
+    where T: Text<'a>,
+{
+    /// Returns the original text of the spread from `...` to the last
+    /// directive, see `Definition::source_slice`
+    pub fn source_slice<'s>(&self, source: &'s str) -> Option<&'s str> {
+        let tokens = positioned_tokens(source);
+        leaf_slice(source, &tokens, spread_start(&tokens, self.position)?)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum TypeCondition<'a, T: Text<'a>> {
     On(T::Value),
@@ -133,7 +477,118 @@ pub enum TypeCondition<'a, T: Text<'a>> {
 #[derive(Debug, Clone, PartialEq)]
 pub struct InlineFragment<'a, T: Text<'a>> {
     pub position: Pos,
+    /// Position of the `on` keyword if there is a type condition
+    pub on_position: Option<Pos>,
     pub type_condition: Option<TypeCondition<'a, T>>,
     pub directives: Vec<Directive<'a, T>>,
     pub selection_set: SelectionSet<'a, T>,
 }
+
+impl<'a, T> InlineFragment<'a, T>
+    where T: Text<'a>,
+{
+    /// Returns the original text of the inline fragment from `...` to the
+    /// closing brace, see `Definition::source_slice`
+    pub fn source_slice<'s>(&self, source: &'s str) -> Option<&'s str> {
+        let tokens = positioned_tokens(source);
+        let (_, start) = tokens[spread_start(&tokens, self.position)?];
+        self.selection_set.slice_in(&LineIndex::new(source), start)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse_query;
+    use crate::query::OperationError;
+
+    #[test]
+    fn accessors() {
+        let doc = parse_query::<&str>("
+            query A { ...F } fragment F on Query { a } query B { b }
+        ").unwrap();
+        assert_eq!(doc.operations().count(), 2);
+        assert_eq!(doc.fragments().count(), 1);
+        assert_eq!(doc.fragment("F").unwrap().name, "F");
+        assert!(doc.fragment("G").is_none());
+        assert_eq!(doc.operation(Some("B")).unwrap().name, Some("B"));
+        assert_eq!(doc.operation(Some("C")).unwrap_err().to_string(),
+                   "Unknown operation named \"C\".");
+        assert_eq!(doc.operation(None).unwrap_err(),
+                   OperationError::NameRequired);
+        let doc = parse_query::<&str>("{ a }").unwrap();
+        assert!(doc.operation(None).unwrap().name.is_none());
+        assert!(doc.operation(Some("A")).is_err());
+        let doc = parse_query::<&str>("fragment F on Query { a }").unwrap();
+        assert_eq!(doc.operation(None).unwrap_err(),
+                   OperationError::NoOperations);
+    }
+
+    #[test]
+    fn source_slices() {
+        let source = "# \u{e9}\nquery A { e: a(s: \"\u{e9}\") { b } c } # x\n\
+                      fragment F on T @d { ... on U { z } }";
+        let doc = parse_query::<&str>(source).unwrap();
+        assert_eq!(doc.definition_sources(source).unwrap(), [
+            "query A { e: a(s: \"\u{e9}\") { b } c }",
+            "fragment F on T @d { ... on U { z } }",
+        ]);
+        let set = &doc.operations().next().unwrap().selection_set;
+        assert_eq!(set.field("e").unwrap().selection_set
+                   .source_slice(source), Some("{ b }"));
+        assert_eq!(set.field("c").unwrap().selection_set
+                   .source_slice(source), None);
+        assert_eq!(doc.definitions[1].source_slice("fragment F"), None);
+    }
+
+    #[test]
+    fn node_source_slices() {
+        use crate::ParserOptions;
+        use crate::query::{parse_query_with_options, Definition, Selection};
+
+        let source = "query Q($v: Int) @op { a: b(x: [1, 2]) @d(if: $v) \
+                      c { d } e@f(g: 1) h[!]? ...F(i: 1) @j ... on T { k } \
+                      ...G } fragment F on T { l }";
+        let mut options = ParserOptions::default();
+        options.client_controlled_nullability(true).fragment_arguments(true);
+        let doc = parse_query_with_options::<&str>(source, &options).unwrap();
+        let op = doc.operations().next().unwrap();
+        let end = source.find(" fragment").unwrap();
+        assert_eq!(op.source_slice(source), Some(&source[..end]));
+        assert_eq!(doc.fragment("F").unwrap().source_slice(source),
+                   Some("fragment F on T { l }"));
+        let slices = op.selection_set.items.iter().map(|item| match *item {
+            Selection::Field(ref f) => f.source_slice(source),
+            Selection::FragmentSpread(ref s) => s.source_slice(source),
+            Selection::InlineFragment(ref i) => i.source_slice(source),
+        }).collect::<Option<Vec<_>>>().unwrap();
+        assert_eq!(slices, [
+            "a: b(x: [1, 2]) @d(if: $v)", "c { d }", "e@f(g: 1)", "h[!]?",
+            "...F(i: 1) @j", "... on T { k }", "...G",
+        ]);
+        let field = match doc.definitions[0] {
+            Definition::Operation(ref op) => op.selection_set.field("h"),
+            Definition::Fragment(_) => None,
+        };
+        assert_eq!(field.unwrap().source_slice("{ }"), None);
+    }
+
+    #[test]
+    fn selection_lookup() {
+        let doc = parse_query::<&str>("
+            { a: b { t: __typename ... on T { c } } b { __typename } }
+        ").unwrap();
+        let set = &doc.operations().next().unwrap().selection_set;
+        assert_eq!(set.fields().map(|f| f.response_key())
+                   .collect::<Vec<_>>(), ["a", "b"]);
+        let a = set.field("a").unwrap();
+        assert_eq!(a.name, "b");
+        assert_eq!(a.fields().count(), 1);
+        assert!(a.field("c").is_none());
+        assert!(a.field("t").is_some());
+        assert!(a.selection_set.contains_typename());
+        assert!(set.field("b").unwrap().selection_set.contains_typename());
+        assert!(!set.contains_typename());
+        assert!(a.fields().next().unwrap().is_introspection_field());
+        assert!(!a.is_introspection_field());
+    }
+}
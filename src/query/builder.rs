@@ -0,0 +1,435 @@
+//! Builders for constructing query documents programmatically
+//!
+//! All the positions of the built nodes are `Pos::default()`:
+//!
+//! ```rust
+//! # extern crate edb_graphql_parser;
+//! use edb_graphql_parser::query::{Document, Field, Operation, Value};
+//! use edb_graphql_parser::query::{Directive, InlineFragment};
+//!
+//! let doc: Document<&str> = Document::build()
+//!     .definition(Operation::query()
+//!         .name("GetUser")
+//!         .select(Field::build("user")
+//!             .alias("u")
+//!             .arg("id", Value::Int(1.into()))
+//!             .select(Field::build("id"))
+//!             .select(InlineFragment::build()
+//!                 .on("Admin")
+//!                 .directive(Directive::build("include")
+//!                     .arg("if", Value::Boolean(true)))
+//!                 .select(Field::build("role")))))
+//!     .into();
+//! assert_eq!(doc.to_string(), "\
+//! query GetUser {
+//!   u: user(id: 1) {
+//!     id
+//!     ... on Admin @include(if: true) {
+//!       role
+//!     }
+//!   }
+//! }
+//! ");
+//! ```
+use crate::common::DirectiveArgument;
+use crate::position::Pos;
+use crate::query::ast::*;
+
+
+/// Builder of the `Document`, created by `Document::build`
+#[derive(Debug, Clone)]
+pub struct DocumentBuilder<'a, T: Text<'a>>(Document<'a, T>);
+
+/// Builder of the `Operation`, created by `Operation::query`,
+/// `Operation::mutation` or `Operation::subscription`
+#[derive(Debug, Clone)]
+pub struct OperationBuilder<'a, T: Text<'a>>(Operation<'a, T>);
+
+/// Builder of the `FragmentDefinition`, created by
+/// `FragmentDefinition::build`
+#[derive(Debug, Clone)]
+pub struct FragmentDefinitionBuilder<'a, T: Text<'a>>(
+    FragmentDefinition<'a, T>);
+
+/// Builder of the `VariableDefinition`, created by
+/// `VariableDefinition::build`
+#[derive(Debug, Clone)]
+pub struct VariableDefinitionBuilder<'a, T: Text<'a>>(
+    VariableDefinition<'a, T>);
+
+/// Builder of the `Field`, created by `Field::build`
+#[derive(Debug, Clone)]
+pub struct FieldBuilder<'a, T: Text<'a>>(Field<'a, T>);
+
+/// Builder of the `FragmentSpread`, created by `FragmentSpread::build`
+#[derive(Debug, Clone)]
+pub struct FragmentSpreadBuilder<'a, T: Text<'a>>(FragmentSpread<'a, T>);
+
+/// Builder of the `InlineFragment`, created by `InlineFragment::build`
+#[derive(Debug, Clone)]
+pub struct InlineFragmentBuilder<'a, T: Text<'a>>(InlineFragment<'a, T>);
+
+/// Builder of the `Directive`, created by `Directive::build`
+#[derive(Debug, Clone)]
+pub struct DirectiveBuilder<'a, T: Text<'a>>(Directive<'a, T>);
+
+fn selection_set<'a, T: Text<'a>>() -> SelectionSet<'a, T> {
+    SelectionSet {
+        span: (Pos::default(), Pos::default()),
+        items: Vec::new(),
+    }
+}
+
+impl<'a, T> Document<'a, T>
+    where T: Text<'a>
+{
+    /// Starts building an empty document
+    pub fn build() -> DocumentBuilder<'a, T> {
+        DocumentBuilder(Document { definitions: Vec::new() })
+    }
+}
+
+impl<'a, T> DocumentBuilder<'a, T>
+    where T: Text<'a>
+{
+    /// Adds an operation or fragment definition
+    pub fn definition(mut self, definition: impl Into<Definition<'a, T>>)
+        -> Self
+    {
+        self.0.definitions.push(definition.into());
+        self
+    }
+}
+
+impl<'a, T> Operation<'a, T>
+    where T: Text<'a>
+{
+    /// Starts building an anonymous `query` operation
+    pub fn query() -> OperationBuilder<'a, T> {
+        OperationBuilder::new(OperationKind::Query)
+    }
+    /// Starts building an anonymous `mutation` operation
+    pub fn mutation() -> OperationBuilder<'a, T> {
+        OperationBuilder::new(OperationKind::Mutation)
+    }
+    /// Starts building an anonymous `subscription` operation
+    pub fn subscription() -> OperationBuilder<'a, T> {
+        OperationBuilder::new(OperationKind::Subscription)
+    }
+}
+
+impl<'a, T> OperationBuilder<'a, T>
+    where T: Text<'a>
+{
+    fn new(kind: OperationKind) -> Self {
+        OperationBuilder(Operation {
+            kind,
+            position: Pos::default(),
+            name: None,
+            variable_definitions: Vec::new(),
+            insert_variables: InsertVars {
+                kind: InsertVarsKind::Parens,
+                position: Pos::default(),
+            },
+            directives: Vec::new(),
+            selection_set: selection_set(),
+        })
+    }
+    pub fn name(mut self, name: impl Into<T::Value>) -> Self {
+        self.0.name = Some(name.into());
+        self
+    }
+    pub fn variable(mut self,
+        variable: impl Into<VariableDefinition<'a, T>>)
+        -> Self
+    {
+        self.0.variable_definitions.push(variable.into());
+        self.0.insert_variables.kind = InsertVarsKind::Normal;
+        self
+    }
+    pub fn directive(mut self, directive: impl Into<Directive<'a, T>>)
+        -> Self
+    {
+        self.0.directives.push(directive.into());
+        self
+    }
+    pub fn select(mut self, selection: impl Into<Selection<'a, T>>) -> Self {
+        self.0.selection_set.items.push(selection.into());
+        self
+    }
+}
+
+impl<'a, T> FragmentDefinition<'a, T>
+    where T: Text<'a>
+{
+    /// Starts building a fragment `name` on type `type_condition`
+    pub fn build(name: impl Into<T::Value>,
+        type_condition: impl Into<T::Value>)
+        -> FragmentDefinitionBuilder<'a, T>
+    {
+        FragmentDefinitionBuilder(FragmentDefinition {
+            position: Pos::default(),
+            name: name.into(),
+            variable_definitions: Vec::new(),
+            on_position: Pos::default(),
+            type_condition: TypeCondition::On(type_condition.into()),
+            directives: Vec::new(),
+            selection_set: selection_set(),
+        })
+    }
+}
+
+impl<'a, T> FragmentDefinitionBuilder<'a, T>
+    where T: Text<'a>
+{
+    pub fn directive(mut self, directive: impl Into<Directive<'a, T>>)
+        -> Self
+    {
+        self.0.directives.push(directive.into());
+        self
+    }
+    pub fn select(mut self, selection: impl Into<Selection<'a, T>>) -> Self {
+        self.0.selection_set.items.push(selection.into());
+        self
+    }
+}
+
+impl<'a, T> VariableDefinition<'a, T>
+    where T: Text<'a>
+{
+    /// Starts building a definition of the variable `$name`
+    pub fn build(name: impl Into<T::Value>, var_type: Type<'a, T>)
+        -> VariableDefinitionBuilder<'a, T>
+    {
+        VariableDefinitionBuilder(VariableDefinition {
+            position: Pos::default(),
+            name: name.into(),
+            var_type,
+            default_value: None,
+            directives: Vec::new(),
+        })
+    }
+}
+
+impl<'a, T> VariableDefinitionBuilder<'a, T>
+    where T: Text<'a>
+{
+    pub fn default_value(mut self, value: Value<'a, T>) -> Self {
+        self.0.default_value = Some(DefaultValue {
+            span: (Pos::default(), Pos::default()),
+            value,
+        });
+        self
+    }
+    pub fn directive(mut self, directive: impl Into<Directive<'a, T>>)
+        -> Self
+    {
+        self.0.directives.push(directive.into());
+        self
+    }
+}
+
+impl<'a, T> Field<'a, T>
+    where T: Text<'a>
+{
+    /// Starts building a field without arguments and selections
+    pub fn build(name: impl Into<T::Value>) -> FieldBuilder<'a, T> {
+        FieldBuilder(Field {
+            position: Pos::default(),
+            alias: None,
+            name: name.into(),
+            arguments: Vec::new(),
+            arguments_syntax: None,
+            nullability: None,
+            directives: Vec::new(),
+            selection_set: selection_set(),
+        })
+    }
+}
+
+impl<'a, T> FieldBuilder<'a, T>
+    where T: Text<'a>
+{
+    pub fn alias(mut self, alias: impl Into<T::Value>) -> Self {
+        self.0.alias = Some(alias.into());
+        self
+    }
+    pub fn arg(mut self, name: impl Into<T::Value>, value: Value<'a, T>)
+        -> Self
+    {
+        self.0.arguments.push((name.into(), value));
+        self
+    }
+    pub fn directive(mut self, directive: impl Into<Directive<'a, T>>)
+        -> Self
+    {
+        self.0.directives.push(directive.into());
+        self
+    }
+    pub fn select(mut self, selection: impl Into<Selection<'a, T>>) -> Self {
+        self.0.selection_set.items.push(selection.into());
+        self
+    }
+}
+
+impl<'a, T> FragmentSpread<'a, T>
+    where T: Text<'a>
+{
+    /// Starts building a spread of the fragment `name`
+    pub fn build(name: impl Into<T::Value>) -> FragmentSpreadBuilder<'a, T> {
+        FragmentSpreadBuilder(FragmentSpread {
+            position: Pos::default(),
+            fragment_name: name.into(),
+            arguments: Vec::new(),
+            arguments_syntax: None,
+            directives: Vec::new(),
+        })
+    }
+}
+
+impl<'a, T> FragmentSpreadBuilder<'a, T>
+    where T: Text<'a>
+{
+    pub fn directive(mut self, directive: impl Into<Directive<'a, T>>)
+        -> Self
+    {
+        self.0.directives.push(directive.into());
+        self
+    }
+}
+
+impl<'a, T> InlineFragment<'a, T>
+    where T: Text<'a>
+{
+    /// Starts building an inline fragment without type condition
+    pub fn build() -> InlineFragmentBuilder<'a, T> {
+        InlineFragmentBuilder(InlineFragment {
+            position: Pos::default(),
+            on_position: None,
+            type_condition: None,
+            directives: Vec::new(),
+            selection_set: selection_set(),
+        })
+    }
+}
+
+impl<'a, T> InlineFragmentBuilder<'a, T>
+    where T: Text<'a>
+{
+    pub fn on(mut self, type_condition: impl Into<T::Value>) -> Self {
+        self.0.type_condition = Some(TypeCondition::On(type_condition.into()));
+        self
+    }
+    pub fn directive(mut self, directive: impl Into<Directive<'a, T>>)
+        -> Self
+    {
+        self.0.directives.push(directive.into());
+        self
+    }
+    pub fn select(mut self, selection: impl Into<Selection<'a, T>>) -> Self {
+        self.0.selection_set.items.push(selection.into());
+        self
+    }
+}
+
+impl<'a, T> Directive<'a, T>
+    where T: Text<'a>
+{
+    /// Starts building a directive without arguments
+    pub fn build(name: impl Into<T::Value>) -> DirectiveBuilder<'a, T> {
+        DirectiveBuilder(Directive {
+            position: Pos::default(),
+            name: name.into(),
+            arguments: Vec::new(),
+            arguments_syntax: None,
+        })
+    }
+}
+
+impl<'a, T> DirectiveBuilder<'a, T>
+    where T: Text<'a>
+{
+    pub fn arg(mut self, name: impl Into<T::Value>, value: Value<'a, T>)
+        -> Self
+    {
+        self.0.arguments.push(DirectiveArgument {
+            name: name.into(),
+            value,
+            value_position: Pos::default(),
+        });
+        self
+    }
+}
+
+macro_rules! impl_into {
+    ($($builder:ident => $node:ident $(=> $wrapper:ident :: $variant:ident)*;)*) => {
+        $(
+            impl<'a, T: Text<'a>> From<$builder<'a, T>> for $node<'a, T> {
+                fn from(builder: $builder<'a, T>) -> Self {
+                    builder.0
+                }
+            }
+            $(
+                impl<'a, T: Text<'a>> From<$node<'a, T>> for $wrapper<'a, T> {
+                    fn from(node: $node<'a, T>) -> Self {
+                        $wrapper::$variant(node)
+                    }
+                }
+                impl<'a, T: Text<'a>> From<$builder<'a, T>> for $wrapper<'a, T> {
+                    fn from(builder: $builder<'a, T>) -> Self {
+                        $wrapper::$variant(builder.0)
+                    }
+                }
+            )*
+        )*
+    }
+}
+
+impl_into! {
+    DocumentBuilder => Document;
+    OperationBuilder => Operation => Definition::Operation;
+    FragmentDefinitionBuilder => FragmentDefinition
+        => Definition::Fragment;
+    VariableDefinitionBuilder => VariableDefinition;
+    FieldBuilder => Field => Selection::Field;
+    FragmentSpreadBuilder => FragmentSpread => Selection::FragmentSpread;
+    InlineFragmentBuilder => InlineFragment => Selection::InlineFragment;
+    DirectiveBuilder => Directive;
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse_query;
+    use crate::query::*;
+
+    #[test]
+    fn fragments_and_variables() {
+        let doc: Document<String> = Document::build()
+            .definition(Operation::mutation()
+                .name("Rename")
+                .variable(VariableDefinition::build("name",
+                        Type::NonNullType(Box::new(
+                            Type::NamedType("String".into()))))
+                    .default_value(Value::String("x".into())))
+                .select(Field::build("rename")
+                    .arg("name", Value::Variable("name".into()))
+                    .select(FragmentSpread::build("UserFields"))))
+            .definition(FragmentDefinition::build("UserFields", "User")
+                .select(Field::build("name")
+                    .directive(Directive::build("deprecated"))))
+            .into();
+        let text = "\
+mutation Rename($name: String! = \"x\") {
+  rename(name: $name) {
+    ...UserFields
+  }
+}
+
+fragment UserFields on User {
+  name @deprecated
+}
+";
+        assert_eq!(doc.to_string(), text);
+        assert_eq!(parse_query::<String>(text).unwrap().to_string(), text);
+    }
+}
@@ -0,0 +1,453 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use thiserror::Error;
+
+use crate::format::{Displayable, Formatter, Style};
+use crate::query::ast::*;
+
+
+/// Error merging executable documents, see `merge_documents`
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum DocumentMergeError {
+    /// Fragments with the same name differ, they are in the documents at
+    /// these indexes (which may be the same)
+    #[error("fragment {name:?} is defined differently \
+             in documents {} and {}", documents.0, documents.1)]
+    FragmentConflict {
+        name: String,
+        documents: (usize, usize),
+    },
+    /// Operations with the same name differ
+    #[error("operation {name:?} is defined differently \
+             in documents {} and {}", documents.0, documents.1)]
+    OperationConflict {
+        name: String,
+        documents: (usize, usize),
+    },
+    /// Anonymous operation would not be the only operation of the merged
+    /// document
+    #[error("anonymous operation of document {document} can't be merged \
+             with other operations")]
+    AnonymousOperation {
+        document: usize,
+    },
+}
+
+/// Definition renamed by `merge_documents_renaming`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Renamed {
+    /// Index of the document the definition comes from
+    pub document: usize,
+    /// `true` for fragments, `false` for operations
+    pub fragment: bool,
+    pub old: String,
+    pub new: String,
+}
+
+/// Document merged by `merge_documents_renaming`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergedDocuments<'a, T: Text<'a>> {
+    pub document: Document<'a, T>,
+    /// Definitions that were renamed because of conflicts, in order
+    pub renamed: Vec<Renamed>,
+}
+
+struct Merger<'a, T: Text<'a>> {
+    definitions: Vec<Definition<'a, T>>,
+    /// Source document and minified text of the definitions by name
+    fragments: BTreeMap<String, (usize, String)>,
+    operations: BTreeMap<String, (usize, String)>,
+    /// All the names of the documents and the ones made up when renaming
+    taken: BTreeSet<String>,
+    renamed: Vec<Renamed>,
+}
+
+/// Combines multiple executable documents into one
+///
+/// Definitions are kept in the order of the documents. Fragments and
+/// operations with the same name that are identical (except for positions
+/// and formatting) are kept once, different ones are a conflict, see
+/// `merge_documents_renaming` to rename them instead. An anonymous
+/// operation can only be merged if there are no other operations.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::parse_query;
+/// use edb_graphql_parser::query::merge_documents;
+///
+/// let docs = vec![
+///     parse_query::<&str>("query A { ...User } \
+///                          fragment User on User { id }").unwrap(),
+///     parse_query::<&str>("query B { ...User } \
+///                          fragment User on User {\n  id\n}").unwrap(),
+/// ];
+/// assert_eq!(merge_documents(&docs).unwrap().to_string(), "\
+/// query A {
+///   ...User
+/// }
+///
+/// fragment User on User {
+///   id
+/// }
+///
+/// query B {
+///   ...User
+/// }
+/// ");
+/// ```
+pub fn merge_documents<'a, T>(documents: &[Document<'a, T>])
+    -> Result<Document<'a, T>, DocumentMergeError>
+    where T: Text<'a> + Clone,
+{
+    merge(documents, |_| None).map(|(document, _)| document)
+}
+
+/// Combines multiple executable documents into one, renaming conflicting
+/// fragments and operations
+///
+/// Works as `merge_documents`, but a fragment or operation conflicting
+/// with one of a previous document is renamed by appending `_2` (or the
+/// next number making the name unique) to its name. Spreads of the renamed
+/// fragment in its document are renamed too. New names are created from
+/// strings, so only text types that own the data (`String` and `Cow`) are
+/// supported.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::parse_query;
+/// use edb_graphql_parser::query::merge_documents_renaming;
+///
+/// let docs = vec![
+///     parse_query::<String>("query A { ...F } fragment F on T { a }")
+///         .unwrap(),
+///     parse_query::<String>("query B { ...F } fragment F on T { b }")
+///         .unwrap(),
+/// ];
+/// let merged = merge_documents_renaming(&docs).unwrap();
+/// assert_eq!(merged.renamed[0].new, "F_2");
+/// assert_eq!(merged.document.to_string(), "\
+/// query A {
+///   ...F
+/// }
+///
+/// fragment F on T {
+///   a
+/// }
+///
+/// query B {
+///   ...F_2
+/// }
+///
+/// fragment F_2 on T {
+///   b
+/// }
+/// ");
+/// ```
+pub fn merge_documents_renaming<'a, T>(documents: &[Document<'a, T>])
+    -> Result<MergedDocuments<'a, T>, DocumentMergeError>
+    where T: Text<'a> + Clone,
+          T::Value: From<String>,
+{
+    merge(documents, |name| Some(name.into()))
+        .map(|(document, renamed)| MergedDocuments { document, renamed })
+}
+
+fn merge<'a, T, F>(documents: &[Document<'a, T>], mut rename: F)
+    -> Result<(Document<'a, T>, Vec<Renamed>), DocumentMergeError>
+    where T: Text<'a> + Clone,
+          F: FnMut(String) -> Option<T::Value>,
+{
+    let mut merger = Merger {
+        definitions: Vec::new(),
+        fragments: BTreeMap::new(),
+        operations: BTreeMap::new(),
+        taken: BTreeSet::new(),
+        renamed: Vec::new(),
+    };
+    let mut count = 0;
+    for doc in documents {
+        count += doc.operations().count();
+        for def in &doc.definitions {
+            let name = match *def {
+                Definition::Operation(ref op) => op.name.as_ref(),
+                Definition::Fragment(ref frag) => Some(&frag.name),
+            };
+            merger.taken.extend(name.map(|n| n.as_ref().to_string()));
+        }
+    }
+    for (index, doc) in documents.iter().enumerate() {
+        if count > 1 && doc.operations().any(|op| op.name.is_none()) {
+            return Err(DocumentMergeError::AnonymousOperation {
+                document: index,
+            });
+        }
+        let mut doc = doc.clone();
+        merger.rename_fragments(index, &mut doc, &mut rename)?;
+        merger.add(index, doc, &mut rename)?;
+    }
+    let Merger { definitions, renamed, .. } = merger;
+    Ok((Document { definitions }, renamed))
+}
+
+impl<'a, T> Merger<'a, T>
+    where T: Text<'a> + Clone,
+{
+    /// Returns the name made unique by a numeric suffix
+    fn unique_name(&mut self, name: &str) -> String {
+        let mut suffix = 2;
+        loop {
+            let candidate = format!("{}_{}", name, suffix);
+            if self.taken.insert(candidate.clone()) {
+                return candidate;
+            }
+            suffix += 1;
+        }
+    }
+
+    /// Renames the fragments of the document conflicting with the merged
+    /// ones
+    ///
+    /// Renaming a fragment changes the fragments spreading it, so this is
+    /// repeated until there are no conflicts.
+    fn rename_fragments<F>(&mut self, index: usize, doc: &mut Document<'a, T>,
+        rename: &mut F)
+        -> Result<(), DocumentMergeError>
+        where F: FnMut(String) -> Option<T::Value>,
+    {
+        loop {
+            let conflict = doc.fragments().find_map(|frag| {
+                let name = frag.name.as_ref();
+                match self.fragments.get(name) {
+                    Some(&(source, ref text)) if *text != minified(frag) => {
+                        Some((name.to_string(), source))
+                    }
+                    _ => None,
+                }
+            });
+            let (old, source) = match conflict {
+                Some(conflict) => conflict,
+                None => return Ok(()),
+            };
+            let new = self.unique_name(&old);
+            let value = rename(new.clone()).ok_or_else(|| {
+                DocumentMergeError::FragmentConflict {
+                    name: old.clone(),
+                    documents: (source, index),
+                }
+            })?;
+            rename_fragment(doc, &old, &value);
+            self.renamed.push(Renamed {
+                document: index,
+                fragment: true,
+                old,
+                new,
+            });
+        }
+    }
+
+    fn add<F>(&mut self, index: usize, doc: Document<'a, T>, rename: &mut F)
+        -> Result<(), DocumentMergeError>
+        where F: FnMut(String) -> Option<T::Value>,
+    {
+        for def in doc.definitions {
+            match def {
+                Definition::Fragment(frag) => {
+                    let name = frag.name.as_ref().to_string();
+                    if self.fragments.contains_key(&name) {
+                        // identical, conflicting ones are renamed
+                        continue;
+                    }
+                    self.fragments.insert(name, (index, minified(&frag)));
+                    self.definitions.push(Definition::Fragment(frag));
+                }
+                Definition::Operation(mut op) => {
+                    let name = match op.name {
+                        Some(ref name) => name.as_ref().to_string(),
+                        None => {
+                            self.definitions.push(Definition::Operation(op));
+                            continue;
+                        }
+                    };
+                    let text = minified(&op);
+                    match self.operations.get(&name) {
+                        Some((_, existing)) if *existing == text => {
+                            continue;
+                        }
+                        Some(&(source, _)) => {
+                            let new = self.unique_name(&name);
+                            op.name = Some(rename(new.clone()).ok_or_else(
+                                || DocumentMergeError::OperationConflict {
+                                    name: name.clone(),
+                                    documents: (source, index),
+                                })?);
+                            self.renamed.push(Renamed {
+                                document: index,
+                                fragment: false,
+                                old: name,
+                                new: new.clone(),
+                            });
+                            self.operations.insert(new, (index, text));
+                        }
+                        None => {
+                            self.operations.insert(name, (index, text));
+                        }
+                    }
+                    self.definitions.push(Definition::Operation(op));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn minified<N: Displayable>(node: &N) -> String {
+    let mut style = Style::default();
+    style.minified(true);
+    let mut formatter = Formatter::new(&style);
+    node.display(&mut formatter);
+    formatter.into_string()
+}
+
+fn rename_fragment<'a, T>(doc: &mut Document<'a, T>, old: &str,
+    new: &T::Value)
+    where T: Text<'a>,
+{
+    for def in &mut doc.definitions {
+        let set = match *def {
+            Definition::Operation(ref mut op) => &mut op.selection_set,
+            Definition::Fragment(ref mut frag) => {
+                if frag.name.as_ref() == old {
+                    frag.name = new.clone();
+                }
+                &mut frag.selection_set
+            }
+        };
+        rename_spreads(set, old, new);
+    }
+}
+
+fn rename_spreads<'a, T>(set: &mut SelectionSet<'a, T>, old: &str,
+    new: &T::Value)
+    where T: Text<'a>,
+{
+    for item in &mut set.items {
+        match *item {
+            Selection::Field(ref mut field) => {
+                rename_spreads(&mut field.selection_set, old, new);
+            }
+            Selection::FragmentSpread(ref mut spread) => {
+                if spread.fragment_name.as_ref() == old {
+                    spread.fragment_name = new.clone();
+                }
+            }
+            Selection::InlineFragment(ref mut frag) => {
+                rename_spreads(&mut frag.selection_set, old, new);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse_query;
+    use super::{merge_documents, merge_documents_renaming};
+    use super::{DocumentMergeError, Renamed};
+
+    #[test]
+    fn conflicts() {
+        let docs = vec![
+            parse_query::<&str>("query A { ...F } fragment F on T { a }")
+                .unwrap(),
+            parse_query::<&str>("query A { ...F }\nfragment F on T { b }")
+                .unwrap(),
+        ];
+        let err = merge_documents(&docs).unwrap_err();
+        assert_eq!(err, DocumentMergeError::FragmentConflict {
+            name: "F".into(),
+            documents: (0, 1),
+        });
+        assert_eq!(err.to_string(),
+            "fragment \"F\" is defined differently in documents 0 and 1");
+        let docs = vec![
+            parse_query::<&str>("query A { a }").unwrap(),
+            parse_query::<&str>("query A { b }").unwrap(),
+        ];
+        assert_eq!(merge_documents(&docs).unwrap_err(),
+            DocumentMergeError::OperationConflict {
+                name: "A".into(),
+                documents: (0, 1),
+            });
+        let docs = vec![
+            parse_query::<&str>("query A { a }").unwrap(),
+            parse_query::<&str>("{ b }").unwrap(),
+        ];
+        assert_eq!(merge_documents(&docs).unwrap_err(),
+            DocumentMergeError::AnonymousOperation { document: 1 });
+        let docs = vec![
+            parse_query::<&str>("{ ...F }").unwrap(),
+            parse_query::<&str>("fragment F on T { a }").unwrap(),
+        ];
+        assert_eq!(merge_documents(&docs).unwrap().definitions.len(), 2);
+    }
+
+    #[test]
+    fn renaming() {
+        let docs = vec![
+            parse_query::<String>("
+                query A { ...F }
+                fragment F on T { a ...G }
+                fragment G on T { g }
+                fragment F_2 on T { x }
+            ").unwrap(),
+            parse_query::<String>("
+                query A { ...F x }
+                fragment F on T { a ...G }
+                fragment G on T { h }
+            ").unwrap(),
+        ];
+        let merged = merge_documents_renaming(&docs).unwrap();
+        let renamed = |document, fragment, old: &str, new: &str| Renamed {
+            document,
+            fragment,
+            old: old.into(),
+            new: new.into(),
+        };
+        assert_eq!(merged.renamed, [
+            renamed(1, true, "G", "G_2"),
+            renamed(1, true, "F", "F_3"),
+            renamed(1, false, "A", "A_2"),
+        ]);
+        assert_eq!(merged.document.to_string(), "\
+query A {
+  ...F
+}
+
+fragment F on T {
+  a
+  ...G
+}
+
+fragment G on T {
+  g
+}
+
+fragment F_2 on T {
+  x
+}
+
+query A_2 {
+  ...F_3
+  x
+}
+
+fragment F_3 on T {
+  a
+  ...G_2
+}
+
+fragment G_2 on T {
+  h
+}
+");
+    }
+}
@@ -0,0 +1,505 @@
+use combine::StreamOnce;
+
+use crate::common::DirectiveLocation;
+use crate::query::ast::OperationKind;
+use crate::tokenizer::{Kind, TokenStream};
+
+
+/// What the grammar expects at the cursor, see `completion_context`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompletionContext {
+    pub kind: CompletionKind,
+    /// Selection sets enclosing the cursor, outermost first
+    pub path: Vec<PathItem>,
+    /// Part of the name right before the cursor, empty if the cursor is not
+    /// at the end of a name
+    pub prefix: String,
+}
+
+/// Kind of the token expected at the cursor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompletionKind {
+    /// Keyword starting a definition or a shorthand query
+    Definition,
+    /// Field, fragment spread or inline fragment in a selection set
+    Field,
+    /// Fragment name or `on` after `...`
+    FragmentSpread,
+    /// Argument name
+    Argument(ArgumentOwner),
+    /// Name of a directive at the location
+    Directive(DirectiveLocation),
+    /// Type in a variable definition or a type condition
+    TypeName,
+    /// Name of a variable after `$`
+    Variable,
+    /// Value of an argument, list item or default value
+    Value,
+    /// Nothing to complete: inside a comment or a string, where a new name
+    /// is defined, or after invalid input
+    Nothing,
+}
+
+/// Field, directive or fragment spread the arguments are for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgumentOwner {
+    Field(String),
+    Directive(String),
+    FragmentSpread(String),
+}
+
+/// Selection set enclosing the cursor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathItem {
+    Operation(OperationKind),
+    Fragment { name: String, type_condition: String },
+    /// Selection set of the field, by name rather than alias
+    Field(String),
+    /// Inline fragment with an optional type condition
+    InlineFragment(Option<String>),
+}
+
+#[derive(Debug)]
+enum Frame {
+    Top,
+    Selection(Option<PathItem>),
+    Arguments(ArgumentOwner),
+    Variables,
+    List,
+    Object,
+}
+
+/// Bracketed region of the source: tokens at its level, with nested
+/// regions replaced by their closing bracket
+#[derive(Debug)]
+struct Level<'a> {
+    frame: Frame,
+    tokens: Vec<(Kind, &'a str)>,
+}
+
+/// Returns what the grammar expects at the byte offset of the source
+///
+/// Works on incomplete input, e.g. while the query is typed: only the
+/// source before the cursor is looked at, unclosed brackets are fine.
+/// A name that ends at the cursor is returned as the prefix, and the
+/// context is the one of the name.
+///
+/// # Panics
+///
+/// If the offset is not at a character boundary of the source.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::query::{completion_context, ArgumentOwner};
+/// use edb_graphql_parser::query::{CompletionKind, PathItem, OperationKind};
+///
+/// let source = "query { user(id: 1) { na";
+/// let context = completion_context(source, source.len());
+/// assert_eq!(context.kind, CompletionKind::Field);
+/// assert_eq!(context.prefix, "na");
+/// assert_eq!(context.path, vec![
+///     PathItem::Operation(OperationKind::Query),
+///     PathItem::Field("user".into()),
+/// ]);
+///
+/// let context = completion_context(source, 13);
+/// assert_eq!(context.kind,
+///            CompletionKind::Argument(ArgumentOwner::Field("user".into())));
+/// ```
+pub fn completion_context(source: &str, offset: usize) -> CompletionContext {
+    let source = &source[..offset];
+    let mut levels = vec![Level { frame: Frame::Top, tokens: Vec::new() }];
+    let mut path = Vec::new();
+    let mut prefix = "";
+    let mut tokens = TokenStream::with_comments(source);
+    loop {
+        let start = tokens.offset();
+        let token = match tokens.uncons() {
+            Ok(token) => token,
+            Err(ref e) if *e == combine::easy::Error::end_of_input() => break,
+            Err(_) => return nothing(path),
+        };
+        let at_end = start + token.value.len() == source.len();
+        match token.kind {
+            Kind::Comment if at_end => return nothing(path),
+            Kind::Comment => continue,
+            Kind::Name if at_end => {
+                prefix = token.value;
+                break;
+            }
+            Kind::Punctuator => {}
+            _ => {
+                level(&mut levels).tokens.push((token.kind, token.value));
+                continue;
+            }
+        }
+        let value = token.value;
+        let nested = levels.len() > 1;
+        let current = level(&mut levels);
+        let frame = match value {
+            "{" => Some(match current.frame {
+                Frame::Top | Frame::Selection(_) => {
+                    let item = selection_set_of(current);
+                    path.extend(item.clone());
+                    Frame::Selection(item)
+                }
+                _ => Frame::Object,
+            }),
+            "(" => Some(if let Some(name) = directive_name(&current.tokens) {
+                Frame::Arguments(ArgumentOwner::Directive(name.into()))
+            } else {
+                match current.frame {
+                    Frame::Top => Frame::Variables,
+                    Frame::Selection(_) => arguments_of(&current.tokens),
+                    _ => Frame::List,
+                }
+            }),
+            "[" => match current.frame {
+                // list types are kept on the level
+                Frame::Variables if last(&current.tokens) != Some("=") => None,
+                _ => Some(Frame::List),
+            },
+            "}" | ")" | "]" if nested => {
+                let closed = levels.pop().expect("level is nested");
+                if let Frame::Selection(Some(_)) = closed.frame {
+                    path.pop();
+                }
+                let parent = level(&mut levels);
+                match (&parent.frame, closed.frame) {
+                    // the definition is complete
+                    (Frame::Top, Frame::Selection(_)) => parent.tokens.clear(),
+                    _ => parent.tokens.push((Kind::Punctuator, value)),
+                }
+                continue;
+            }
+            _ => None,
+        };
+        match frame {
+            Some(frame) => levels.push(Level { frame, tokens: Vec::new() }),
+            None => current.tokens.push((Kind::Punctuator, value)),
+        }
+    }
+    let current = level(&mut levels);
+    CompletionContext {
+        kind: expected(current),
+        path,
+        prefix: prefix.to_string(),
+    }
+}
+
+fn nothing(path: Vec<PathItem>) -> CompletionContext {
+    CompletionContext {
+        kind: CompletionKind::Nothing,
+        path,
+        prefix: String::new(),
+    }
+}
+
+fn level<'l, 'a>(levels: &'l mut [Level<'a>]) -> &'l mut Level<'a> {
+    levels.last_mut().expect("top level is never removed")
+}
+
+fn last<'a>(tokens: &[(Kind, &'a str)]) -> Option<&'a str> {
+    tokens.last().map(|&(_, value)| value)
+}
+
+/// Returns the name of the directive if the tokens end with `@name`
+fn directive_name<'a>(tokens: &[(Kind, &'a str)]) -> Option<&'a str> {
+    match *tokens {
+        [.., (Kind::Punctuator, "@"), (Kind::Name, name)] => Some(name),
+        _ => None,
+    }
+}
+
+/// Returns the tokens of the selection without its directives
+fn strip_directives<'t, 'a>(mut tokens: &'t [(Kind, &'a str)])
+    -> &'t [(Kind, &'a str)]
+{
+    loop {
+        let end = match *tokens {
+            [ref rest @ .., (Kind::Punctuator, ")")] => rest,
+            _ => tokens,
+        };
+        if directive_name(end).is_none() {
+            return tokens;
+        }
+        tokens = &end[..end.len() - 2];
+    }
+}
+
+enum Selection<'a> {
+    Field(Option<&'a str>),
+    FragmentSpread(&'a str),
+    InlineFragment(Option<&'a str>),
+}
+
+/// Returns the selection the tokens of the selection set end with
+fn last_selection<'a>(tokens: &[(Kind, &'a str)]) -> Selection<'a> {
+    match *strip_directives(tokens) {
+        [.., (Kind::Punctuator, "...")] => Selection::InlineFragment(None),
+        [.., (Kind::Punctuator, "..."), (Kind::Name, "on"),
+         (Kind::Name, type_name)] => {
+            Selection::InlineFragment(Some(type_name))
+        }
+        [.., (Kind::Punctuator, "..."), (Kind::Name, name)] => {
+            Selection::FragmentSpread(name)
+        }
+        [.., (Kind::Name, name)] |
+        [.., (Kind::Name, name), (Kind::Punctuator, ")")] => {
+            Selection::Field(Some(name))
+        }
+        _ => Selection::Field(None),
+    }
+}
+
+fn arguments_of(tokens: &[(Kind, &str)]) -> Frame {
+    match last_selection(tokens) {
+        Selection::FragmentSpread(name) => {
+            Frame::Arguments(ArgumentOwner::FragmentSpread(name.into()))
+        }
+        Selection::Field(Some(name)) => {
+            Frame::Arguments(ArgumentOwner::Field(name.into()))
+        }
+        _ => Frame::List,
+    }
+}
+
+/// Returns the path item of the selection set opened at the level
+fn selection_set_of(level: &Level) -> Option<PathItem> {
+    let tokens = &level.tokens[..];
+    if let Frame::Selection(_) = level.frame {
+        return match last_selection(tokens) {
+            Selection::Field(name) => name.map(|n| PathItem::Field(n.into())),
+            Selection::InlineFragment(type_name) => {
+                Some(PathItem::InlineFragment(type_name.map(Into::into)))
+            }
+            Selection::FragmentSpread(_) => None,
+        };
+    }
+    match *tokens {
+        [] => Some(PathItem::Operation(OperationKind::ImplicitQuery)),
+        [(Kind::Name, "fragment"), (Kind::Name, name), ref rest @ ..] => {
+            rest.iter().position(|&(_, value)| value == "on")
+                .and_then(|idx| rest.get(idx + 1))
+                .map(|&(_, type_name)| PathItem::Fragment {
+                    name: name.into(),
+                    type_condition: type_name.into(),
+                })
+        }
+        [(Kind::Name, keyword), ..] => {
+            operation_kind(keyword).map(PathItem::Operation)
+        }
+        _ => None,
+    }
+}
+
+fn operation_kind(keyword: &str) -> Option<OperationKind> {
+    match keyword {
+        "query" => Some(OperationKind::Query),
+        "mutation" => Some(OperationKind::Mutation),
+        "subscription" => Some(OperationKind::Subscription),
+        _ => None,
+    }
+}
+
+/// Returns `true` if the tokens end with a complete `name: value`
+fn value_complete(tokens: &[(Kind, &str)]) -> bool {
+    let rest = match *tokens {
+        [ref rest @ .., (Kind::Punctuator, "$"), (Kind::Name, _)] => rest,
+        [ref rest @ .., (Kind::Punctuator, "]" | "}")] => rest,
+        [ref rest @ .., (kind, _)] if kind != Kind::Punctuator => rest,
+        _ => return false,
+    };
+    last(rest) == Some(":")
+}
+
+fn expected(level: &Level) -> CompletionKind {
+    use self::CompletionKind::*;
+
+    let tokens = &level.tokens;
+    let last = last(tokens);
+    match level.frame {
+        Frame::Top => match (tokens.first(), last) {
+            (None, _) => Definition,
+            (Some(&(Kind::Name, keyword)), Some("@")) => {
+                match operation_kind(keyword) {
+                    Some(OperationKind::Query) => {
+                        Directive(DirectiveLocation::Query)
+                    }
+                    Some(OperationKind::Mutation) => {
+                        Directive(DirectiveLocation::Mutation)
+                    }
+                    Some(OperationKind::Subscription) => {
+                        Directive(DirectiveLocation::Subscription)
+                    }
+                    Some(OperationKind::ImplicitQuery) => Nothing,
+                    None if keyword == "fragment" => {
+                        Directive(DirectiveLocation::FragmentDefinition)
+                    }
+                    None => Nothing,
+                }
+            }
+            (Some(&(Kind::Name, "fragment")), Some("on")) => TypeName,
+            _ => Nothing,
+        },
+        Frame::Selection(_) => match last {
+            Some("@") => {
+                let tokens = &tokens[..tokens.len() - 1];
+                Directive(match last_selection(tokens) {
+                    Selection::Field(_) => DirectiveLocation::Field,
+                    Selection::FragmentSpread(_) => {
+                        DirectiveLocation::FragmentSpread
+                    }
+                    Selection::InlineFragment(_) => {
+                        DirectiveLocation::InlineFragment
+                    }
+                })
+            }
+            Some("...") => FragmentSpread,
+            Some("on") if tokens.len() > 1 &&
+                last_selection(&tokens[..tokens.len() - 1])
+                    .is_inline_fragment() => TypeName,
+            Some("$") => Nothing,
+            _ => Field,
+        },
+        Frame::Arguments(ref owner) => match last {
+            Some("$") => Variable,
+            Some(":") => Value,
+            None | Some(",") => Argument(owner.clone()),
+            _ if value_complete(tokens) => Argument(owner.clone()),
+            _ => Nothing,
+        },
+        Frame::Variables => match last {
+            Some(":") | Some("[") => TypeName,
+            Some("=") => Value,
+            Some("@") => Directive(DirectiveLocation::VariableDefinition),
+            _ => Nothing,
+        },
+        Frame::List => match last {
+            Some("$") => Variable,
+            _ => Value,
+        },
+        Frame::Object => match last {
+            Some("$") => Variable,
+            Some(":") => Value,
+            _ => Nothing,
+        },
+    }
+}
+
+impl Selection<'_> {
+    fn is_inline_fragment(&self) -> bool {
+        matches!(*self, Selection::InlineFragment(None))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::common::DirectiveLocation;
+    use crate::query::OperationKind;
+    use super::{completion_context, ArgumentOwner, CompletionKind, PathItem};
+    use super::CompletionKind::*;
+
+    /// Context at the `|` in the source
+    fn at(source: &str) -> (CompletionKind, String) {
+        let offset = source.find('|').unwrap();
+        let source = source.replace('|', "");
+        let context = completion_context(&source, offset);
+        (context.kind, context.prefix)
+    }
+
+    fn kind(source: &str) -> CompletionKind {
+        at(source).0
+    }
+
+    fn path(source: &str) -> Vec<PathItem> {
+        let offset = source.find('|').unwrap();
+        completion_context(&source.replace('|', ""), offset).path
+    }
+
+    #[test]
+    fn definitions() {
+        assert_eq!(kind("|"), Definition);
+        assert_eq!(at("{ a } que|"), (Definition, "que".into()));
+        assert_eq!(kind("query |"), Nothing);
+        assert_eq!(kind("fragment F on |"), TypeName);
+        assert_eq!(kind("fragment F |"), Nothing);
+        assert_eq!(kind("query Q { a } # comm|"), Nothing);
+        assert_eq!(kind("{ a # comment\n  |"), Field);
+        assert_eq!(kind("query Q { a(s: \"unterminated|"), Nothing);
+    }
+
+    #[test]
+    fn selections() {
+        assert_eq!(kind("{ |"), Field);
+        assert_eq!(kind("{ a |"), Field);
+        assert_eq!(kind("{ a: |"), Field);
+        assert_eq!(kind("{ a(x: 1) { b } |"), Field);
+        assert_eq!(at("{ ...|"), (FragmentSpread, "".into()));
+        assert_eq!(at("{ ... o|"), (FragmentSpread, "o".into()));
+        assert_eq!(kind("{ ... on |"), TypeName);
+        assert_eq!(kind("{ on |"), Field);
+        assert_eq!(path("query Q { a: b(x: 1) @d { ... on T { c { |"), [
+            PathItem::Operation(OperationKind::Query),
+            PathItem::Field("b".into()),
+            PathItem::InlineFragment(Some("T".into())),
+            PathItem::Field("c".into()),
+        ]);
+        assert_eq!(path("{ a { b } c { ... @d { |"), [
+            PathItem::Operation(OperationKind::ImplicitQuery),
+            PathItem::Field("c".into()),
+            PathItem::InlineFragment(None),
+        ]);
+        assert_eq!(path("{ a } fragment F on User { |"), [
+            PathItem::Fragment {
+                name: "F".into(),
+                type_condition: "User".into(),
+            },
+        ]);
+    }
+
+    #[test]
+    fn arguments() {
+        let field = |name: &str| Argument(ArgumentOwner::Field(name.into()));
+        assert_eq!(kind("{ a(|"), field("a"));
+        assert_eq!(kind("{ x: a(b: 1, |"), field("a"));
+        assert_eq!(kind("{ a(b: [1, {c: 2}] |"), field("a"));
+        assert_eq!(kind("{ a(b: $v |"), field("a"));
+        assert_eq!(kind("{ a(b: |"), Value);
+        assert_eq!(kind("{ a(b: [|"), Value);
+        assert_eq!(kind("{ a(b: {c: |"), Value);
+        assert_eq!(kind("{ a(b: {|"), Nothing);
+        assert_eq!(kind("{ a(b: $|"), Variable);
+        assert_eq!(kind("{ a(b |"), Nothing);
+        assert_eq!(kind("{ a @d(|"),
+                   Argument(ArgumentOwner::Directive("d".into())));
+        assert_eq!(kind("{ ...F(|"),
+                   Argument(ArgumentOwner::FragmentSpread("F".into())));
+    }
+
+    #[test]
+    fn directives() {
+        use self::DirectiveLocation::*;
+        assert_eq!(at("query @sk|"), (Directive(Query), "sk".into()));
+        assert_eq!(kind("mutation M($x: Int) @|"), Directive(Mutation));
+        assert_eq!(kind("fragment F on T @|"), Directive(FragmentDefinition));
+        assert_eq!(kind("{ a @|"), Directive(Field));
+        assert_eq!(kind("{ a(x: 1) @d(y: 2) @|"), Directive(Field));
+        assert_eq!(kind("{ ...F @|"), Directive(FragmentSpread));
+        assert_eq!(kind("{ ... @|"), Directive(InlineFragment));
+        assert_eq!(kind("{ ... on T @a @|"), Directive(InlineFragment));
+        assert_eq!(kind("query($x: Int @|"), Directive(VariableDefinition));
+    }
+
+    #[test]
+    fn variables() {
+        assert_eq!(kind("query Q(|"), Nothing);
+        assert_eq!(kind("query Q($|"), Nothing);
+        assert_eq!(at("query Q($x: I|"), (TypeName, "I".into()));
+        assert_eq!(kind("query Q($x: [|"), TypeName);
+        assert_eq!(kind("query Q($x: Int = |"), Value);
+        assert_eq!(kind("query Q($x: [Int] = [|"), Value);
+        assert_eq!(kind("query Q($x: Int, $y: |"), TypeName);
+        assert_eq!(kind("query Q($x: Int) { a(b: $|"), Variable);
+    }
+}
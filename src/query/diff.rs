@@ -0,0 +1,579 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::common::{Directive, Value};
+use crate::format::{Displayable, Formatter, Style};
+use crate::query::ast::*;
+use crate::query::normalize;
+
+
+/// Kind of the element changed, see `Change`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Element {
+    Operation,
+    Fragment,
+    Variable,
+    Field,
+    FragmentSpread,
+    InlineFragment,
+    Argument,
+    Directive,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// Difference between two documents, see `diff`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub element: Element,
+    /// Location of the element, starting with the definition
+    ///
+    /// Operations are named by their name (`<anonymous>` if there is
+    /// none), fragments as `fragment Name`, fields by their response key
+    /// (`alias: name` if aliased), other elements as written: `...Name`,
+    /// `... on Type`, `$var`, `(argument)`, `@directive`.
+    pub path: Vec<String>,
+    /// Minified text of the element in the old document, `None` if added
+    ///
+    /// For changed operations this is the operation kind, for changed
+    /// fragments the type condition, for arguments the value.
+    pub old: Option<String>,
+    /// Minified text of the element in the new document, `None` if removed
+    pub new: Option<String>,
+}
+
+/// Returns the structural differences between two documents
+///
+/// Operations and fragments are matched by name, selections by response
+/// key, fragment name or type condition, arguments, variables and
+/// directives by name. The order of the elements, positions and formatting
+/// don't matter, neither does the order of the fields of input objects.
+/// Only the innermost changes are reported: a field with a changed
+/// argument is not reported as changed itself.
+///
+/// Elements removed from each selection set (and each list of arguments or
+/// directives) are reported before the ones added to it.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::parse_query;
+/// use edb_graphql_parser::query::diff;
+///
+/// let old = parse_query::<&str>("query Q { users(first: 10) { id } }")
+///     .unwrap();
+/// let new = parse_query::<&str>("
+///     query Q {
+///         users(first: 20) { name, id }
+///     }
+/// ").unwrap();
+/// let changes = diff(&old, &new).iter().map(|c| c.to_string())
+///     .collect::<Vec<_>>();
+/// assert_eq!(changes, [
+///     "~ Q/users/(first): 10 -> 20",
+///     "+ Q/users/name: name",
+/// ]);
+/// ```
+pub fn diff<'a, 'b, T, U>(old: &Document<'a, T>, new: &Document<'b, U>)
+    -> Vec<Change>
+    where T: Text<'a> + Clone,
+          U: Text<'b> + Clone,
+{
+    let mut differ = Differ { path: Vec::new(), changes: Vec::new() };
+    differ.definitions(&old.definitions, &new.definitions);
+    differ.changes
+}
+
+struct Differ {
+    path: Vec<String>,
+    changes: Vec<Change>,
+}
+
+/// Item of the old list, the new list, or both
+enum Pair<'o, 'n, O, N> {
+    Removed(&'o O),
+    Added(&'n N),
+    Both(&'o O, &'n N),
+}
+
+/// Matches the n-th old item having the key with the n-th new one
+fn pairs<'o, 'n, O, N, F, G>(old: &'o [O], new: &'n [N], old_key: F,
+    new_key: G)
+    -> Vec<(String, Pair<'o, 'n, O, N>)>
+    where F: Fn(&O) -> String,
+          G: Fn(&N) -> String,
+{
+    let mut index = BTreeMap::new();
+    let mut counts = BTreeMap::new();
+    for (idx, item) in old.iter().enumerate() {
+        let key = old_key(item);
+        let count = counts.entry(key.clone()).or_insert(0);
+        index.insert((key, *count), idx);
+        *count += 1;
+    }
+    let mut matched = vec![None; old.len()];
+    let mut counts = BTreeMap::new();
+    for (idx, item) in new.iter().enumerate() {
+        let key = new_key(item);
+        let count = counts.entry(key.clone()).or_insert(0);
+        if let Some(&old_idx) = index.get(&(key, *count)) {
+            matched[old_idx] = Some(idx);
+        }
+        *count += 1;
+    }
+    let mut result = Vec::new();
+    let mut new_matched = vec![None; new.len()];
+    for (idx, item) in old.iter().enumerate() {
+        match matched[idx] {
+            Some(new_idx) => new_matched[new_idx] = Some(idx),
+            None => result.push((old_key(item), Pair::Removed(item))),
+        }
+    }
+    for (idx, item) in new.iter().enumerate() {
+        let pair = match new_matched[idx] {
+            Some(old_idx) => Pair::Both(&old[old_idx], item),
+            None => Pair::Added(item),
+        };
+        result.push((new_key(item), pair));
+    }
+    result
+}
+
+fn minified<D: Displayable>(item: &D) -> String {
+    let mut style = Style::default();
+    style.minified(true);
+    let mut formatter = Formatter::new(&style);
+    item.display(&mut formatter);
+    formatter.into_string().trim().to_string()
+}
+
+fn value_text<'a, T>(value: &Value<'a, T>) -> String
+    where T: Text<'a> + Clone,
+{
+    let mut value = value.clone();
+    normalize::value(&mut value);
+    minified(&value)
+}
+
+fn directive_text<'a, T>(directive: &Directive<'a, T>) -> String
+    where T: Text<'a> + Clone,
+{
+    let mut directives = [directive.clone()];
+    normalize::directives(&mut directives);
+    minified(&directives[0])
+}
+
+fn variable_text<'a, T>(var: &VariableDefinition<'a, T>) -> String
+    where T: Text<'a> + Clone,
+{
+    let mut var = var.clone();
+    if let Some(ref mut default) = var.default_value {
+        normalize::value(&mut default.value);
+    }
+    normalize::directives(&mut var.directives);
+    minified(&var)
+}
+
+fn definition_key<'a, T>(def: &Definition<'a, T>) -> String
+    where T: Text<'a>,
+{
+    match *def {
+        Definition::Operation(ref op) => match op.name {
+            Some(ref name) => name.as_ref().to_string(),
+            None => "<anonymous>".to_string(),
+        },
+        Definition::Fragment(ref frag) => {
+            format!("fragment {}", frag.name.as_ref())
+        }
+    }
+}
+
+fn selection_key<'a, T>(item: &Selection<'a, T>) -> String
+    where T: Text<'a>,
+{
+    match *item {
+        Selection::Field(ref field) => match field.alias {
+            Some(ref alias) => {
+                format!("{}: {}", alias.as_ref(), field.name.as_ref())
+            }
+            None => field.name.as_ref().to_string(),
+        },
+        Selection::FragmentSpread(ref spread) => {
+            format!("...{}", spread.fragment_name.as_ref())
+        }
+        Selection::InlineFragment(ref frag) => match frag.type_condition {
+            Some(TypeCondition::On(ref name)) => {
+                format!("... on {}", name.as_ref())
+            }
+            None => "...".to_string(),
+        },
+    }
+}
+
+fn definition_element<'a, T>(def: &Definition<'a, T>) -> Element
+    where T: Text<'a>,
+{
+    match *def {
+        Definition::Operation(_) => Element::Operation,
+        Definition::Fragment(_) => Element::Fragment,
+    }
+}
+
+fn selection_element<'a, T>(item: &Selection<'a, T>) -> Element
+    where T: Text<'a>,
+{
+    match *item {
+        Selection::Field(_) => Element::Field,
+        Selection::FragmentSpread(_) => Element::FragmentSpread,
+        Selection::InlineFragment(_) => Element::InlineFragment,
+    }
+}
+
+fn kind_text(kind: &OperationKind) -> &'static str {
+    match *kind {
+        OperationKind::ImplicitQuery | OperationKind::Query => "query",
+        OperationKind::Mutation => "mutation",
+        OperationKind::Subscription => "subscription",
+    }
+}
+
+impl Differ {
+    fn push(&mut self, kind: ChangeKind, element: Element, key: String,
+        old: Option<String>, new: Option<String>)
+    {
+        let mut path = self.path.clone();
+        path.push(key);
+        self.changes.push(Change { kind, element, path, old, new });
+    }
+
+    fn added(&mut self, element: Element, key: String, new: String) {
+        self.push(ChangeKind::Added, element, key, None, Some(new));
+    }
+
+    fn removed(&mut self, element: Element, key: String, old: String) {
+        self.push(ChangeKind::Removed, element, key, Some(old), None);
+    }
+
+    fn changed(&mut self, element: Element, key: String,
+        old: String, new: String)
+    {
+        if old != new {
+            self.push(ChangeKind::Changed, element, key,
+                      Some(old), Some(new));
+        }
+    }
+
+    fn definitions<'a, 'b, T, U>(&mut self, old: &[Definition<'a, T>],
+        new: &[Definition<'b, U>])
+        where T: Text<'a> + Clone,
+              U: Text<'b> + Clone,
+    {
+        let pairs = pairs(old, new, definition_key, definition_key);
+        for (key, pair) in pairs {
+            match pair {
+                Pair::Removed(def) => {
+                    self.removed(definition_element(def), key, minified(def));
+                }
+                Pair::Added(def) => {
+                    self.added(definition_element(def), key, minified(def));
+                }
+                Pair::Both(old, new) => self.definition(key, old, new),
+            }
+        }
+    }
+
+    fn definition<'a, 'b, T, U>(&mut self, key: String,
+        old: &Definition<'a, T>, new: &Definition<'b, U>)
+        where T: Text<'a> + Clone,
+              U: Text<'b> + Clone,
+    {
+        use crate::query::ast::Definition::*;
+        match (old, new) {
+            (Operation(old), Operation(new)) => {
+                self.changed(Element::Operation, key.clone(),
+                             kind_text(&old.kind).into(),
+                             kind_text(&new.kind).into());
+                self.path.push(key);
+                self.variables(&old.variable_definitions,
+                               &new.variable_definitions);
+                self.directives(&old.directives, &new.directives);
+                self.selection_set(&old.selection_set, &new.selection_set);
+            }
+            (Fragment(old), Fragment(new)) => {
+                self.changed(Element::Fragment, key.clone(),
+                             minified(&old.type_condition),
+                             minified(&new.type_condition));
+                self.path.push(key);
+                self.variables(&old.variable_definitions,
+                               &new.variable_definitions);
+                self.directives(&old.directives, &new.directives);
+                self.selection_set(&old.selection_set, &new.selection_set);
+            }
+            // keys of operations and fragments never match
+            _ => unreachable!(),
+        }
+        self.path.pop();
+    }
+
+    fn variables<'a, 'b, T, U>(&mut self, old: &[VariableDefinition<'a, T>],
+        new: &[VariableDefinition<'b, U>])
+        where T: Text<'a> + Clone,
+              U: Text<'b> + Clone,
+    {
+        fn key<'a, T: Text<'a>>(var: &VariableDefinition<'a, T>) -> String {
+            format!("${}", var.name.as_ref())
+        }
+        for (key, pair) in pairs(old, new, key, key) {
+            match pair {
+                Pair::Removed(var) => {
+                    self.removed(Element::Variable, key, variable_text(var));
+                }
+                Pair::Added(var) => {
+                    self.added(Element::Variable, key, variable_text(var));
+                }
+                Pair::Both(old, new) => {
+                    self.changed(Element::Variable, key,
+                                 variable_text(old), variable_text(new));
+                }
+            }
+        }
+    }
+
+    fn directives<'a, 'b, T, U>(&mut self, old: &[Directive<'a, T>],
+        new: &[Directive<'b, U>])
+        where T: Text<'a> + Clone,
+              U: Text<'b> + Clone,
+    {
+        fn key<'a, T: Text<'a>>(dir: &Directive<'a, T>) -> String {
+            format!("@{}", dir.name.as_ref())
+        }
+        for (key, pair) in pairs(old, new, key, key) {
+            match pair {
+                Pair::Removed(dir) => {
+                    self.removed(Element::Directive, key, directive_text(dir));
+                }
+                Pair::Added(dir) => {
+                    self.added(Element::Directive, key, directive_text(dir));
+                }
+                Pair::Both(old, new) => {
+                    self.changed(Element::Directive, key,
+                                 directive_text(old), directive_text(new));
+                }
+            }
+        }
+    }
+
+    fn arguments<'a, 'b, T, U>(&mut self,
+        old: &[(T::Value, Value<'a, T>)],
+        new: &[(U::Value, Value<'b, U>)])
+        where T: Text<'a> + Clone,
+              U: Text<'b> + Clone,
+    {
+        let pairs = pairs(old, new,
+            |(name, _)| format!("({})", name.as_ref()),
+            |(name, _)| format!("({})", name.as_ref()));
+        for (key, pair) in pairs {
+            match pair {
+                Pair::Removed((_, val)) => {
+                    self.removed(Element::Argument, key, value_text(val));
+                }
+                Pair::Added((_, val)) => {
+                    self.added(Element::Argument, key, value_text(val));
+                }
+                Pair::Both((_, old), (_, new)) => {
+                    self.changed(Element::Argument, key,
+                                 value_text(old), value_text(new));
+                }
+            }
+        }
+    }
+
+    fn selection_set<'a, 'b, T, U>(&mut self, old: &SelectionSet<'a, T>,
+        new: &SelectionSet<'b, U>)
+        where T: Text<'a> + Clone,
+              U: Text<'b> + Clone,
+    {
+        let pairs = pairs(&old.items, &new.items,
+                          selection_key, selection_key);
+        for (key, pair) in pairs {
+            match pair {
+                Pair::Removed(item) => {
+                    self.removed(selection_element(item), key,
+                                 selection_text(item));
+                }
+                Pair::Added(item) => {
+                    self.added(selection_element(item), key,
+                               selection_text(item));
+                }
+                Pair::Both(old, new) => {
+                    self.path.push(key);
+                    self.selection(old, new);
+                    self.path.pop();
+                }
+            }
+        }
+    }
+
+    fn selection<'a, 'b, T, U>(&mut self, old: &Selection<'a, T>,
+        new: &Selection<'b, U>)
+        where T: Text<'a> + Clone,
+              U: Text<'b> + Clone,
+    {
+        use crate::query::ast::Selection::*;
+        match (old, new) {
+            (Field(old), Field(new)) => {
+                self.arguments(&old.arguments, &new.arguments);
+                self.directives(&old.directives, &new.directives);
+                self.selection_set(&old.selection_set, &new.selection_set);
+            }
+            (FragmentSpread(old), FragmentSpread(new)) => {
+                self.arguments(&old.arguments, &new.arguments);
+                self.directives(&old.directives, &new.directives);
+            }
+            (InlineFragment(old), InlineFragment(new)) => {
+                self.directives(&old.directives, &new.directives);
+                self.selection_set(&old.selection_set, &new.selection_set);
+            }
+            // keys of different kinds of selections never match
+            _ => unreachable!(),
+        }
+    }
+}
+
+fn selection_text<'a, T>(item: &Selection<'a, T>) -> String
+    where T: Text<'a>,
+{
+    match *item {
+        Selection::Field(ref field) => minified(field),
+        Selection::FragmentSpread(ref spread) => minified(spread),
+        Selection::InlineFragment(ref frag) => minified(frag),
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = match self.kind {
+            ChangeKind::Added => "+",
+            ChangeKind::Removed => "-",
+            ChangeKind::Changed => "~",
+        };
+        write!(f, "{} {}: ", sign, self.path.join("/"))?;
+        match (&self.old, &self.new) {
+            (Some(old), Some(new)) => write!(f, "{} -> {}", old, new),
+            (Some(text), None) | (None, Some(text)) => f.write_str(text),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse_query;
+    use super::{diff, ChangeKind, Element};
+
+    fn changes(old: &str, new: &str) -> Vec<String> {
+        let old = parse_query::<&str>(old).unwrap();
+        let new = parse_query::<&str>(new).unwrap();
+        diff(&old, &new).iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn same() {
+        assert!(changes("
+            query Q($a: Int = 1) @dir(x: 1, y: 2) {
+                a(x: {a: 1, b: [2]}) { b ...F ... on T { c } }
+            }
+            fragment F on T { d }
+        ", "
+            fragment F on T { d }
+            query Q($a: Int = 1) @dir(y: 2, x: 1) {
+                a(x: {b: [2], a: 1}) { ... on T { c }, ...F, b }
+            }
+        ").is_empty());
+    }
+
+    #[test]
+    fn definitions() {
+        assert_eq!(changes("
+            query A { a }
+            { b }
+            fragment F on T { f }
+            fragment G on T { g }
+        ", "
+            mutation A { a }
+            query B { b }
+            fragment F on U { f }
+            fragment A on T { a }
+        "), [
+            "- <anonymous>: {b}",
+            "- fragment G: fragment G on T{g}",
+            "~ A: query -> mutation",
+            "+ B: query B{b}",
+            "~ fragment F: on T -> on U",
+            "+ fragment A: fragment A on T{a}",
+        ]);
+    }
+
+    #[test]
+    fn selections() {
+        assert_eq!(changes("
+            query Q($id: ID!, $x: Int, $y: Int) {
+                user(id: $id, x: 1) @include(if: true) {
+                    name
+                    me: user { id }
+                    ...F
+                    ... on Admin { role }
+                    ... @skip(if: true) { a }
+                }
+            }
+        ", "
+            query Q($id: ID, $x: Int, $z: Int = 1) {
+                user(id: $id, y: 2) @include(if: false) @cached {
+                    name: fullName
+                    me: user { id uuid }
+                    ...G
+                    ... on Admin { role, level }
+                    ... @skip(if: false) { a }
+                }
+            }
+        "), [
+            "- Q/$y: $y:Int",
+            "~ Q/$id: $id:ID! -> $id:ID",
+            "+ Q/$z: $z:Int=1",
+            "- Q/user/(x): 1",
+            "+ Q/user/(y): 2",
+            "~ Q/user/@include: @include(if:true) -> @include(if:false)",
+            "+ Q/user/@cached: @cached",
+            "- Q/user/name: name",
+            "- Q/user/...F: ...F",
+            "+ Q/user/name: fullName: name:fullName",
+            "+ Q/user/me: user/uuid: uuid",
+            "+ Q/user/...G: ...G",
+            "+ Q/user/... on Admin/level: level",
+            "~ Q/user/.../@skip: @skip(if:true) -> @skip(if:false)",
+        ]);
+    }
+
+    #[test]
+    fn repeated() {
+        let old = parse_query::<&str>("{ a @tag(x: 1) a { b } }").unwrap();
+        let new = parse_query::<&str>("{ a @tag(x: 1) @tag(x: 2) a { c } }")
+            .unwrap();
+        let changes = diff(&old, &new);
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[0].kind, ChangeKind::Added);
+        assert_eq!(changes[0].element, Element::Directive);
+        assert_eq!(changes[0].path, ["<anonymous>", "a", "@tag"]);
+        assert_eq!(changes[0].new.as_deref(), Some("@tag(x:2)"));
+        assert_eq!(changes[1].kind, ChangeKind::Removed);
+        assert_eq!(changes[1].element, Element::Field);
+        assert_eq!(changes[1].path, ["<anonymous>", "a", "b"]);
+        assert_eq!(changes[2].kind, ChangeKind::Added);
+        assert_eq!(changes[2].old, None);
+    }
+}
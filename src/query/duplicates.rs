@@ -0,0 +1,276 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::format::Style;
+use crate::position::Pos;
+use crate::query::ast::*;
+use crate::query::normalize::normalize;
+use crate::query::separate::used_fragments;
+
+
+/// Operation referenced in the `DuplicateReport`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OperationRef {
+    /// Index of the document in the slice passed to `find_duplicates`
+    pub document: usize,
+    pub name: Option<String>,
+    pub position: Pos,
+}
+
+/// How operations of the `DuplicateGroup` are similar
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Similarity {
+    /// Operations are the same except for the name and formatting
+    Identical,
+    /// Operations differ only in the order of the selections
+    SelectionOrder,
+}
+
+/// Operations that can be consolidated into one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateGroup {
+    pub similarity: Similarity,
+    pub operations: Vec<OperationRef>,
+}
+
+/// Groups of duplicate operations, returned by `find_duplicates`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DuplicateReport {
+    pub groups: Vec<DuplicateGroup>,
+}
+
+/// Finds operations which are structurally the same across the documents
+///
+/// Operations are compared by their canonical form (see `normalize`) with
+/// names removed and the fragments they use appended, so operations that
+/// differ in the name, formatting or the order of arguments are reported
+/// as identical. Operations that also differ in the order of selections are
+/// reported as a separate group, as the order is visible in the response.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::parse_query;
+/// use edb_graphql_parser::query::find_duplicates;
+///
+/// let docs = vec![
+///     parse_query::<&str>("query GetUser { user(id: 1) { id name } }")
+///         .unwrap(),
+///     parse_query::<&str>("query UserById { user(id: 1) { id, name } }
+///                          query UserName { user(id: 1) { name id } }")
+///         .unwrap(),
+/// ];
+/// assert_eq!(find_duplicates(&docs).to_string(), "\
+/// identical: GetUser (document 0, 1:1), UserById (document 1, 1:1)
+/// selection order: GetUser (document 0, 1:1), UserById (document 1, 1:1), \
+///     UserName (document 1, 2:26)
+/// ");
+/// ```
+pub fn find_duplicates<'a, T>(documents: &[Document<'a, T>])
+    -> DuplicateReport
+    where T: Text<'a> + Clone,
+{
+    let mut identical = BTreeMap::new();
+    for (idx, doc) in documents.iter().enumerate() {
+        for def in &doc.definitions {
+            if let Definition::Operation(ref op) = *def {
+                let operation = OperationRef {
+                    document: idx,
+                    name: op.name.as_ref().map(|n| n.as_ref().to_string()),
+                    position: op.position,
+                };
+                let canonical = canonical(doc, op);
+                identical.entry(minified(&canonical))
+                    .or_insert_with(|| (canonical, Vec::new()))
+                    .1.push(operation);
+            }
+        }
+    }
+    let mut reordered = BTreeMap::<_, Vec<_>>::new();
+    for (doc, operations) in identical.values() {
+        reordered.entry(sorted(doc)).or_default().push(operations);
+    }
+    let mut groups = Vec::new();
+    for (_, operations) in identical.values() {
+        if operations.len() > 1 {
+            groups.push(DuplicateGroup {
+                similarity: Similarity::Identical,
+                operations: operations.clone(),
+            });
+        }
+    }
+    for classes in reordered.values() {
+        if classes.len() > 1 {
+            let mut operations = classes.iter()
+                .flat_map(|ops| ops.iter().cloned())
+                .collect::<Vec<_>>();
+            operations.sort_by_key(|op| (op.document, op.position));
+            groups.push(DuplicateGroup {
+                similarity: Similarity::SelectionOrder,
+                operations,
+            });
+        }
+    }
+    for group in &mut groups {
+        group.operations.sort_by_key(|op| (op.document, op.position));
+    }
+    groups.sort_by_key(|g| {
+        let first = &g.operations[0];
+        (first.document, first.position,
+         g.similarity == Similarity::SelectionOrder)
+    });
+    DuplicateReport { groups }
+}
+
+/// Returns the operation without name and the fragments it uses
+fn canonical<'a, T>(doc: &Document<'a, T>, op: &Operation<'a, T>)
+    -> Document<'a, T>
+    where T: Text<'a> + Clone,
+{
+    let fragments = doc.definitions.iter().filter_map(|def| match *def {
+        Definition::Fragment(ref frag) => Some((frag.name.as_ref(), frag)),
+        Definition::Operation(_) => None,
+    }).collect::<BTreeMap<_, _>>();
+    let used = used_fragments(doc, &op.selection_set);
+    let mut definitions = vec![Definition::Operation(Operation {
+        name: None,
+        .. op.clone()
+    })];
+    definitions.extend(used.iter().filter_map(|name| fragments.get(name))
+        .map(|frag| Definition::Fragment((*frag).clone())));
+    normalize(Document { definitions })
+}
+
+/// Returns the text of the document with all selections sorted
+fn sorted<'a, T>(doc: &Document<'a, T>) -> String
+    where T: Text<'a> + Clone,
+{
+    let mut doc = doc.clone();
+    for def in &mut doc.definitions {
+        match *def {
+            Definition::Operation(ref mut op) => {
+                sort_selections(&mut op.selection_set);
+            }
+            Definition::Fragment(ref mut frag) => {
+                sort_selections(&mut frag.selection_set);
+            }
+        }
+    }
+    minified(&doc)
+}
+
+fn sort_selections<'a, T>(set: &mut SelectionSet<'a, T>)
+    where T: Text<'a>,
+{
+    for item in &mut set.items {
+        match *item {
+            Selection::Field(ref mut field) => {
+                sort_selections(&mut field.selection_set);
+            }
+            Selection::InlineFragment(ref mut frag) => {
+                sort_selections(&mut frag.selection_set);
+            }
+            Selection::FragmentSpread(_) => {}
+        }
+    }
+    set.items.sort_by_cached_key(|item| match *item {
+        Selection::Field(ref field) => field.to_string(),
+        Selection::FragmentSpread(ref spread) => spread.to_string(),
+        Selection::InlineFragment(ref frag) => frag.to_string(),
+    });
+}
+
+fn minified<'a, T>(doc: &Document<'a, T>) -> String
+    where T: Text<'a>,
+{
+    let mut style = Style::default();
+    style.minified(true);
+    doc.format(&style)
+}
+
+impl fmt::Display for OperationRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name {
+            Some(ref name) => write!(f, "{} ", name)?,
+            None => f.write_str("<anonymous> ")?,
+        }
+        write!(f, "(document {}, {})", self.document, self.position)
+    }
+}
+
+impl fmt::Display for DuplicateGroup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.similarity {
+            Similarity::Identical => f.write_str("identical: ")?,
+            Similarity::SelectionOrder => f.write_str("selection order: ")?,
+        }
+        for (idx, op) in self.operations.iter().enumerate() {
+            if idx > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", op)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for DuplicateReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for group in &self.groups {
+            writeln!(f, "{}", group)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse_query;
+    use super::{find_duplicates, Similarity};
+
+    #[test]
+    fn fragments() {
+        let docs = vec![
+            parse_query::<&str>("
+                query A($b: Int, $a: Int) { x(a: $a, b: $b) { ...F } }
+                fragment F on T { y ...G }
+                fragment G on T { z }
+                fragment Unused on T { w }
+            ").unwrap(),
+            parse_query::<&str>("
+                query B($a: Int, $b: Int) { x(b: $b, a: $a) { ...F } }
+                fragment G on T { z }
+                fragment F on T { y ...G }
+            ").unwrap(),
+            parse_query::<&str>("
+                query C($a: Int, $b: Int) { x(a: $a, b: $b) { ...F } }
+                fragment F on T { ...G y }
+                fragment G on T { z }
+            ").unwrap(),
+            parse_query::<&str>("
+                query D($a: Int, $b: Int) { x(a: $a, b: $b) { ...F } }
+                fragment F on T { y ...G }
+                fragment G on T { other }
+            ").unwrap(),
+        ];
+        let report = find_duplicates(&docs);
+        assert_eq!(report.groups.len(), 2);
+        assert_eq!(report.groups[0].similarity, Similarity::Identical);
+        assert_eq!(report.to_string(), "\
+identical: A (document 0, 2:17), B (document 1, 2:17)
+selection order: A (document 0, 2:17), B (document 1, 2:17), \
+    C (document 2, 2:17)
+");
+    }
+
+    #[test]
+    fn anonymous() {
+        let docs = vec![
+            parse_query::<&str>("{ a } query Q { a } query { b }").unwrap(),
+        ];
+        assert_eq!(find_duplicates(&docs).to_string(),
+            "identical: <anonymous> (document 0, 1:1), \
+             Q (document 0, 1:7)\n");
+        assert!(find_duplicates(&[parse_query::<&str>("{ a }").unwrap()])
+            .groups.is_empty());
+    }
+}
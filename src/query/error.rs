@@ -1,22 +1,121 @@
 use combine::easy::Errors;
 use thiserror::Error;
 
-use crate::tokenizer::Token;
-use crate::position::Pos;
+use crate::error::{ErrorCode, SyntaxError};
+use crate::tokenizer::{Kind, Token, TokenStream};
+use crate::options::ParserOptions;
+use crate::position::{Location, Pos};
 
 pub type InternalError<'a> = Errors<Token<'a>, Token<'a>, Pos>;
 
 
 /// Error parsing query
 ///
-/// This structure is opaque for forward compatibility. We are exploring a
-/// way to improve both error message and API.
-#[derive(Error, Debug)]
+/// Displayed as a human-readable message, the accessors describe the error
+/// for programs, see `error::ErrorCode` for the categories of errors.
+#[derive(Error, Debug, Clone, PartialEq)]
 #[error("query parse error: {}", _0)]
-pub struct ParseError(String);
+pub struct ParseError(Box<SyntaxError>);
+
+/// Error selecting the operation to execute, see `Document::operation`
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum OperationError {
+    #[error("Must provide an operation.")]
+    NoOperations,
+    #[error("Must provide operation name if query contains \
+             multiple operations.")]
+    NameRequired,
+    #[error("Unknown operation named {:?}.", _0)]
+    Unknown(String),
+}
+
+impl ParseError {
+    pub(crate) fn at(code: ErrorCode, position: Pos, message: &str)
+        -> ParseError
+    {
+        ParseError(Box::new(SyntaxError::at(code, position, message)))
+    }
+
+    pub(crate) fn with_related(mut self, positions: Vec<Pos>) -> ParseError {
+        self.0.related = positions;
+        self
+    }
+
+    pub(crate) fn syntax(e: InternalError, tokens: &TokenStream)
+        -> ParseError
+    {
+        let rule = tokens.failed_rule(e.position);
+        ParseError(Box::new(SyntaxError::from_errors(e, rule)))
+    }
+
+    /// Category of the error
+    pub fn code(&self) -> ErrorCode {
+        self.0.code
+    }
+
+    /// Start and end of the offending token (both are the position of the
+    /// error if there is no token)
+    pub fn span(&self) -> (Pos, Pos) {
+        self.0.span
+    }
+
+    /// Kind and text of the unexpected token, `None` at the end of input and
+    /// for errors in the tokenizer
+    pub fn found(&self) -> Option<(Kind, &str)> {
+        self.0.found.as_ref().map(|&(kind, ref value)| (kind, &value[..]))
+    }
+
+    /// Descriptions of tokens that would be accepted at the position
+    pub fn expected(&self) -> &[String] {
+        &self.0.expected
+    }
+
+    /// Message without the position and expected tokens
+    pub fn description(&self) -> &str {
+        &self.0.description
+    }
+
+    /// Grammar rule that was being parsed, like `"VariableDefinitions"`
+    pub fn rule(&self) -> Option<&'static str> {
+        self.0.rule
+    }
+
+    /// Other positions the error refers to, e.g. the first definition of
+    /// a duplicate name or the other operation for an anonymous one
+    pub fn related_positions(&self) -> &[Pos] {
+        &self.0.related
+    }
+
+    /// Name of the source the error is in, set by
+    /// `ParserOptions::source_name` or `with_source`
+    pub fn source_name(&self) -> Option<&str> {
+        self.0.source.as_ref().map(|s| &s[..])
+    }
+
+    /// Start of the error in the named source
+    pub fn location(&self) -> Option<Location> {
+        self.0.source.as_ref().map(|source| Location {
+            source: source.clone(),
+            position: self.0.span.0,
+        })
+    }
+
+    /// Attributes the error to the named source, e.g. a file name
+    pub fn with_source(mut self, name: &str) -> ParseError {
+        self.0.set_source(name);
+        self
+    }
+
+    pub(crate) fn in_source(self, options: &ParserOptions) -> ParseError {
+        match options.source() {
+            Some(name) => self.with_source(name),
+            None => self,
+        }
+    }
+}
 
 impl<'a> From<InternalError<'a>> for ParseError {
     fn from(e: InternalError<'a>) -> ParseError {
-        ParseError(format!("{}", e))
+        ParseError(Box::new(SyntaxError::from_errors(e, None)))
     }
 }
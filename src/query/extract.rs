@@ -0,0 +1,229 @@
+use thiserror::Error;
+
+use crate::name::is_valid_name;
+use crate::position::Pos;
+use crate::query::ast::*;
+use crate::query::error::OperationError;
+
+
+/// Error extracting a fragment, see `extract_fragment`
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ExtractError {
+    #[error(transparent)]
+    Operation(#[from] OperationError),
+    #[error("fragment {:?} is already defined", _0)]
+    NameTaken(String),
+    #[error("{:?} is not a valid name", _0)]
+    InvalidName(String),
+    /// No field of the path, or the field has no selection set
+    #[error("no selection set at path {:?}", _0)]
+    NotFound(String),
+}
+
+/// Moves the selection set at the path into a new fragment and spreads it
+/// in place of the selections
+///
+/// `definition` is the name of the operation or fragment containing the
+/// selection set, `None` selects the only operation as in
+/// `Document::operation`. `path` is the dot-separated list of the response
+/// keys (aliases or names) of the fields from the root of the definition,
+/// fields inside inline fragments are found too (the first one in the
+/// source order if there are several), and an empty path extracts the
+/// root selection set. Types of the fields are unknown without a schema,
+/// so `type_condition` must be the type of the field at the path.
+///
+/// Selections keep their positions. The spread gets the position of the
+/// selection set and the fragment is added at the end of the document.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::parse_query;
+/// use edb_graphql_parser::query::extract_fragment;
+///
+/// let mut doc = parse_query::<&str>("
+///     query Q { viewer { friends { id name } } }
+/// ").unwrap();
+/// extract_fragment(&mut doc, Some("Q"), "viewer.friends",
+///                  "Friend", "User").unwrap();
+/// assert_eq!(doc.to_string(), "\
+/// query Q {
+///   viewer {
+///     friends {
+///       ...Friend
+///     }
+///   }
+/// }
+///
+/// fragment Friend on User {
+///   id
+///   name
+/// }
+/// ");
+/// ```
+pub fn extract_fragment<'a, T>(doc: &mut Document<'a, T>,
+    definition: Option<&str>, path: &str, name: &'a str,
+    type_condition: &'a str)
+    -> Result<(), ExtractError>
+    where T: Text<'a>,
+{
+    if !is_valid_name(name) {
+        return Err(ExtractError::InvalidName(name.into()));
+    }
+    if !is_valid_name(type_condition) {
+        return Err(ExtractError::InvalidName(type_condition.into()));
+    }
+    if doc.fragment(name).is_some() {
+        return Err(ExtractError::NameTaken(name.into()));
+    }
+    let index = definition_index(doc, definition)?;
+    let mut set = match doc.definitions[index] {
+        Definition::Operation(ref mut op) => &mut op.selection_set,
+        Definition::Fragment(ref mut frag) => &mut frag.selection_set,
+    };
+    if !path.is_empty() {
+        for key in path.split('.') {
+            set = find_field(set, key)
+                .map(|field| &mut field.selection_set)
+                .filter(|set| !set.items.is_empty())
+                .ok_or_else(|| ExtractError::NotFound(path.into()))?;
+        }
+    }
+    let spread = Selection::FragmentSpread(FragmentSpread {
+        position: set.span.0,
+        fragment_name: name.into(),
+        arguments: Vec::new(),
+        arguments_syntax: None,
+        directives: Vec::new(),
+    });
+    let items = std::mem::replace(&mut set.items, vec![spread]);
+    doc.definitions.push(Definition::Fragment(FragmentDefinition {
+        position: Pos::default(),
+        name: name.into(),
+        variable_definitions: Vec::new(),
+        on_position: Pos::default(),
+        type_condition: TypeCondition::On(type_condition.into()),
+        directives: Vec::new(),
+        selection_set: SelectionSet {
+            span: (Pos::default(), Pos::default()),
+            items,
+        },
+    }));
+    Ok(())
+}
+
+fn definition_index<'a, T>(doc: &Document<'a, T>, name: Option<&str>)
+    -> Result<usize, OperationError>
+    where T: Text<'a>,
+{
+    match name {
+        Some(name) => doc.definitions.iter()
+            .position(|def| match *def {
+                Definition::Operation(ref op) => {
+                    op.name.as_ref().map(|n| n.as_ref()) == Some(name)
+                }
+                Definition::Fragment(ref frag) => frag.name.as_ref() == name,
+            })
+            .ok_or_else(|| OperationError::Unknown(name.into())),
+        None => {
+            doc.operation(None)?;
+            Ok(doc.definitions.iter()
+                .position(|def| matches!(*def, Definition::Operation(_)))
+                .expect("operation exists"))
+        }
+    }
+}
+
+fn find_field<'x, 'a, T>(set: &'x mut SelectionSet<'a, T>, key: &str)
+    -> Option<&'x mut Field<'a, T>>
+    where T: Text<'a>,
+{
+    for item in &mut set.items {
+        match *item {
+            Selection::Field(ref mut field) => {
+                let response_key = field.alias.as_ref()
+                    .unwrap_or(&field.name);
+                if response_key.as_ref() == key {
+                    return Some(field);
+                }
+            }
+            Selection::InlineFragment(ref mut frag) => {
+                if let Some(field) = find_field(&mut frag.selection_set, key)
+                {
+                    return Some(field);
+                }
+            }
+            Selection::FragmentSpread(_) => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse_query;
+    use crate::query::OperationError;
+    use super::{extract_fragment, ExtractError};
+
+    #[test]
+    fn extract() {
+        let mut doc = parse_query::<String>("
+            query A { a: user { id } b: user { ... on User { posts { id } } } }
+            fragment F on User { friends { name } }
+        ").unwrap();
+        extract_fragment(&mut doc, Some("A"), "b.posts", "P", "Post")
+            .unwrap();
+        extract_fragment(&mut doc, Some("F"), "", "G", "User").unwrap();
+        assert_eq!(doc.to_string(), "\
+query A {
+  a: user {
+    id
+  }
+  b: user {
+    ... on User {
+      posts {
+        ...P
+      }
+    }
+  }
+}
+
+fragment F on User {
+  ...G
+}
+
+fragment P on Post {
+  id
+}
+
+fragment G on User {
+  friends {
+    name
+  }
+}
+");
+    }
+
+    #[test]
+    fn errors() {
+        let mut doc = parse_query::<&str>("
+            { a { b } c }
+            fragment F on T { x }
+        ").unwrap();
+        let mut extract = |definition, path, name| {
+            extract_fragment(&mut doc, definition, path, name, "T")
+        };
+        assert_eq!(extract(None, "a.b", "X"),
+                   Err(ExtractError::NotFound("a.b".into())));
+        assert_eq!(extract(None, "c", "X"),
+                   Err(ExtractError::NotFound("c".into())));
+        assert_eq!(extract(None, "a", "F"),
+                   Err(ExtractError::NameTaken("F".into())));
+        assert_eq!(extract(None, "a", "1x"),
+                   Err(ExtractError::InvalidName("1x".into())));
+        assert_eq!(extract(Some("Q"), "a", "X"),
+                   Err(ExtractError::Operation(
+                       OperationError::Unknown("Q".into()))));
+        assert_eq!(extract(None, "a", "X"), Ok(()));
+        assert_eq!(doc.definitions.len(), 3);
+    }
+}
@@ -1,9 +1,12 @@
 use std::fmt;
 
 use crate::format::{Displayable, Formatter, Style, format_directives};
+use crate::format::range_edits;
 
 use crate::query::ast::*;
-use crate::common::DirectiveArgument;
+use crate::query::{parse_query, ParseError};
+use crate::position::{Pos, TextEdit};
+use crate::tokenizer::positioned_tokens;
 
 
 impl<'a, T: Text<'a>> Document<'a, T>
@@ -15,6 +18,96 @@ impl<'a, T: Text<'a>> Document<'a, T>
         self.display(&mut formatter);
         formatter.into_string()
     }
+
+    /// Returns true if the documents only differ in positions
+    ///
+    /// Documents are compared by their minified text, so formatting and
+    /// the text type don't matter either.
+    pub fn semantic_eq<'b, U>(&self, other: &Document<'b, U>) -> bool
+        where U: Text<'b>,
+    {
+        let mut style = Style::default();
+        style.minified(true);
+        self.format(&style) == other.format(&style)
+    }
+
+    /// Returns the persisted query hash of the printed document
+    ///
+    /// The document is formatted with the default style, so documents that
+    /// differ only in whitespace, commas and comments have the same hash.
+    #[cfg(feature = "apq")]
+    pub fn apq_hash(&self) -> String {
+        apq_hash(&self.to_string())
+    }
+}
+
+/// Returns the hash of the query as used by Automatic Persisted Queries
+///
+/// This is a lowercase hex-encoded SHA-256 of the query text, exactly as
+/// it's sent by the client in `extensions.persistedQuery.sha256Hash`.
+#[cfg(feature = "apq")]
+pub fn apq_hash(query: &str) -> String {
+    use std::fmt::Write;
+    use sha2::{Digest, Sha256};
+
+    let digest = Sha256::digest(query.as_bytes());
+    let mut result = String::with_capacity(64);
+    for byte in digest.iter() {
+        write!(&mut result, "{:02x}", byte).unwrap();
+    }
+    result
+}
+
+/// Returns the hash of the normalized form of the query
+///
+/// The query is parsed, normalized (see `normalize`) and formatted with
+/// the default style before hashing. Use this when the client is known to
+/// send the hash of the normalized query (i.e. a query printed by this
+/// library).
+#[cfg(feature = "apq")]
+pub fn apq_hash_normalized(query: &str)
+    -> Result<String, crate::query::ParseError>
+{
+    let doc = crate::query::parse_query::<&str>(query)?;
+    Ok(crate::query::normalize(doc).apq_hash())
+}
+
+/// Formats only the definitions intersecting the range of the source
+///
+/// Returns the edits replacing each of these definitions by its formatted
+/// text, the rest of the source is untouched. Comments inside the
+/// definitions are preserved, the ones between definitions are left where
+/// they are. The range is compared by lines and columns, so its positions
+/// may be made from an editor selection with the other fields zero.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::Style;
+/// use edb_graphql_parser::position::{apply_edits, Pos};
+/// use edb_graphql_parser::query::format_range;
+///
+/// let source = "query A { a }\nquery B {b(x:1)}  # keep\n";
+/// let line = |line| Pos { line, column: 1, .. Pos::default() };
+/// let edits = format_range(source, (line(2), line(2)), &Style::default())
+///     .unwrap();
+/// assert_eq!(apply_edits(source, &edits),
+///     "query A { a }\nquery B {\n  b(x: 1)\n}  # keep\n");
+/// ```
+pub fn format_range(source: &str, range: (Pos, Pos), style: &Style)
+    -> Result<Vec<TextEdit>, ParseError>
+{
+    let doc = parse_query::<&str>(source)?;
+    let starts = doc.definitions.iter().map(|def| match *def {
+        Definition::Operation(ref op) => op.position.token,
+        Definition::Fragment(ref frag) => frag.position.token,
+    }).collect::<Vec<_>>();
+    let tokens = positioned_tokens(source);
+    Ok(range_edits(source, &tokens, &starts, range, |text| {
+        let doc = parse_query::<&str>(text).expect("definition is valid");
+        let mut style = style.clone();
+        style.preserve_comments(text);
+        doc.format(&style)
+    }))
 }
 
 fn to_string<T: Displayable>(v: &T) -> String {
@@ -31,6 +124,18 @@ impl<'a, T: Text<'a>> Displayable for Document<'a, T>
         for item in &self.definitions {
             item.display(f);
         }
+        f.remaining_comments();
+    }
+
+    fn stream(&self, f: &mut Formatter, out: &mut dyn fmt::Write)
+        -> fmt::Result
+    {
+        for item in &self.definitions {
+            item.display(f);
+            f.flush(out)?;
+        }
+        f.remaining_comments();
+        Ok(())
     }
 }
 
@@ -51,35 +156,35 @@ impl<'a, T: Text<'a>> Displayable for Operation<'a, T>
     fn display(&self, f: &mut Formatter) {
         use crate::query::OperationKind::*;
         f.margin();
+        f.leading_comments(self.position);
         f.indent();
+        // the shorthand form can't have a name, variables or directives,
+        // so the keyword is printed if the operation has any of them
+        let shorthand = self.kind == ImplicitQuery &&
+            self.name.is_none() &&
+            self.variable_definitions.is_empty() &&
+            self.directives.is_empty();
         f.write(match self.kind {
-            ImplicitQuery => "",
-            Query => "query",
+            ImplicitQuery if shorthand => "",
+            ImplicitQuery | Query => "query",
             Mutation => "mutation",
             Subscription => "subscription",
         });
         if let Some(ref name) = self.name {
-            assert!(self.kind != ImplicitQuery);
             f.write(" ");
             f.write(name.as_ref());
-            if !self.variable_definitions.is_empty() {
-                f.write("(");
-                self.variable_definitions[0].display(f);
-                for var in &self.variable_definitions[1..] {
-                    f.write(", ");
-                    var.display(f);
-                }
-                f.write(")");
-            }
         }
-        if self.kind != ImplicitQuery {
+        if !self.variable_definitions.is_empty() {
+            let vars = &self.variable_definitions;
+            f.write_list(vars.len(), |idx, f| vars[idx].display(f));
+        }
+        if !shorthand {
             format_directives(&self.directives, f);
             f.write(" ");
         }
+        f.trailing_comment(self.position);
         f.start_block();
-        for item in &self.selection_set.items {
-            item.display(f);
-        }
+        format_selections(&self.selection_set.items, f);
         f.end_block();
     }
 }
@@ -89,17 +194,21 @@ impl<'a, T: Text<'a>> Displayable for FragmentDefinition<'a, T>
 {
     fn display(&self, f: &mut Formatter) {
         f.margin();
+        f.leading_comments(self.position);
         f.indent();
         f.write("fragment ");
         f.write(self.name.as_ref());
+        if !self.variable_definitions.is_empty() {
+            let vars = &self.variable_definitions;
+            f.write_list(vars.len(), |idx, f| vars[idx].display(f));
+        }
         f.write(" ");
         self.type_condition.display(f);
         format_directives(&self.directives, f);
         f.write(" ");
+        f.trailing_comment(self.position);
         f.start_block();
-        for item in &self.selection_set.items {
-            item.display(f);
-        }
+        format_selections(&self.selection_set.items, f);
         f.end_block();
     }
 }
@@ -109,11 +218,11 @@ impl<'a, T: Text<'a>> Displayable for SelectionSet<'a, T>
 {
     fn display(&self, f: &mut Formatter) {
         f.margin();
+        f.leading_comments(self.span.0);
         f.indent();
+        f.trailing_comment(self.span.0);
         f.start_block();
-        for item in &self.items {
-            item.display(f);
-        }
+        format_selections(&self.items, f);
         f.end_block();
     }
 }
@@ -122,72 +231,121 @@ impl<'a, T: Text<'a>> Displayable for Selection<'a, T>
     where T: Text<'a>,
 {
     fn display(&self, f: &mut Formatter) {
-        match *self {
-            Selection::Field(ref fld) => fld.display(f),
-            Selection::InlineFragment(ref frag) => frag.display(f),
-            Selection::FragmentSpread(ref frag) => frag.display(f),
-        }
+        format_selections(std::slice::from_ref(self), f);
     }
 }
 
-fn format_directive_arguments<'a, T: Text<'a>>(
-    arguments: &[DirectiveArgument<'a, T>], f: &mut Formatter)
+/// Formats the selections, keeping the nested selection sets on the heap
+/// rather than recursing, so deeply nested documents can't overflow the
+/// stack
+fn format_selections<'a, T>(items: &[Selection<'a, T>], f: &mut Formatter)
     where T: Text<'a>,
 {
-    if !arguments.is_empty() {
-        f.write("(");
-        f.write(arguments[0].name.as_ref());
-        f.write(": ");
-        arguments[0].value.display(f);
-        for arg in &arguments[1..] {
-            f.write(", ");
-            f.write(arg.name.as_ref());
-            f.write(": ");
-            arg.value.display(f);
+    // iterators over the selection sets being formatted, along with
+    // whether the selection set belongs to a field
+    let mut stack = vec![(items.iter(), false)];
+    while let Some(&mut (ref mut items, _)) = stack.last_mut() {
+        match items.next() {
+            Some(Selection::Field(fld)) => {
+                format_field_head(fld, f);
+                if fld.selection_set.items.is_empty() {
+                    f.endline();
+                    f.leave_field();
+                } else {
+                    f.write(" ");
+                    f.start_block();
+                    stack.push((fld.selection_set.items.iter(), true));
+                }
+            }
+            Some(Selection::InlineFragment(frag)) => {
+                format_inline_fragment_head(frag, f);
+                f.start_block();
+                stack.push((frag.selection_set.items.iter(), false));
+            }
+            Some(Selection::FragmentSpread(frag)) => frag.display(f),
+            None => {
+                let (_, field) = stack.pop().expect("stack is not empty");
+                if stack.is_empty() {
+                    break;
+                }
+                f.end_block();
+                if field {
+                    f.leave_field();
+                }
+            }
         }
-        f.write(")");
     }
 }
 
-fn format_arguments<'a, T: Text<'a>>(arguments: &[(T::Value, Value<'a, T>)], f: &mut Formatter)
+fn format_arguments<'a, T>(arguments: &[(T::Value, Value<'a, T>)], f: &mut Formatter)
     where T: Text<'a>,
 {
-    if !arguments.is_empty() {
-        f.write("(");
-        f.write(arguments[0].0.as_ref());
+    f.write_list(arguments.len(), |idx, f| {
+        f.write(arguments[idx].0.as_ref());
+        f.write(": ");
+        arguments[idx].1.display(f);
+    });
+}
+
+fn format_field_head<'a, T>(fld: &Field<'a, T>, f: &mut Formatter)
+    where T: Text<'a>,
+{
+    f.leading_comments(fld.position);
+    f.indent();
+    if let Some(ref alias) = fld.alias {
+        f.write(alias.as_ref());
         f.write(": ");
-        arguments[0].1.display(f);
-        for arg in &arguments[1..] {
-            f.write(", ");
-            f.write(arg.0.as_ref());
-            f.write(": ");
-            arg.1.display(f);
-        }
-        f.write(")");
     }
+    f.write(fld.name.as_ref());
+    format_arguments(&fld.arguments, f);
+    if let Some(ref nullability) = fld.nullability {
+        nullability.display(f);
+    }
+    format_directives(&fld.directives, f);
+    f.enter_field(fld.name.as_ref());
+    f.annotate_field(fld.position);
 }
 
 impl<'a, T: Text<'a>> Displayable for Field<'a, T>
     where T: Text<'a>,
 {
     fn display(&self, f: &mut Formatter) {
-        f.indent();
-        if let Some(ref alias) = self.alias {
-            f.write(alias.as_ref());
-            f.write(": ");
-        }
-        f.write(self.name.as_ref());
-        format_arguments(&self.arguments, f);
-        format_directives(&self.directives, f);
-        if !self.selection_set.items.is_empty() {
+        format_field_head(self, f);
+        if self.selection_set.items.is_empty() {
+            f.endline();
+        } else {
             f.write(" ");
             f.start_block();
-            for item in &self.selection_set.items {
-                item.display(f);
-            }
+            format_selections(&self.selection_set.items, f);
             f.end_block();
-        } else {
-            f.endline();
+        }
+        f.leave_field();
+    }
+}
+
+impl Displayable for Nullability {
+    fn display(&self, f: &mut Formatter) {
+        match *self {
+            Nullability::Designator(designator) => designator.display(f),
+            Nullability::List(ref element, designator) => {
+                f.write("[");
+                if let Some(ref element) = *element {
+                    element.display(f);
+                }
+                f.write("]");
+                if let Some(designator) = designator {
+                    designator.display(f);
+                }
+            }
+        }
+    }
+}
+
+impl Displayable for NullabilityDesignator {
+    fn display(&self, f: &mut Formatter) {
+        match *self {
+            NullabilityDesignator::Required => f.write("!"),
+            NullabilityDesignator::Optional => f.write("?"),
         }
     }
 }
@@ -204,87 +362,33 @@ impl<'a, T: Text<'a>> Displayable for VariableDefinition<'a, T>
             f.write(" = ");
             default.value.display(f);
         }
+        format_directives(&self.directives, f);
     }
 }
 
-impl<'a, T: Text<'a>> Displayable for Type<'a, T>
-    where T: Text<'a>,
-{
-    fn display(&self, f: &mut Formatter) {
-        match *self {
-            Type::NamedType(ref name) => f.write(name.as_ref()),
-            Type::ListType(ref typ) => {
-                f.write("[");
-                typ.display(f);
-                f.write("]");
-            }
-            Type::NonNullType(ref typ) => {
-                typ.display(f);
-                f.write("!");
-            }
-        }
-    }
-}
-
-impl<'a, T: Text<'a>> Displayable for Value<'a, T>
+fn format_inline_fragment_head<'a, T>(frag: &InlineFragment<'a, T>,
+    f: &mut Formatter)
     where T: Text<'a>,
 {
-    fn display(&self, f: &mut Formatter) {
-        match *self {
-            Value::Variable(ref name) => { f.write("$"); f.write(name.as_ref()); },
-            Value::Int(ref num) => f.write(&format!("{}", num.0)),
-            Value::Float(ref val) => f.write(val),
-            Value::String(ref val) => f.write_quoted(val),
-            Value::Boolean(true) => f.write("true"),
-            Value::Boolean(false) => f.write("false"),
-            Value::Null => f.write("null"),
-            Value::Enum(ref name) => f.write(name.as_ref()),
-            Value::List(ref items) => {
-                f.write("[");
-                if !items.is_empty() {
-                    items[0].display(f);
-                    for item in &items[1..] {
-                        f.write(", ");
-                        item.display(f);
-                    }
-                }
-                f.write("]");
-            }
-            Value::Object(ref items) => {
-                f.write("{");
-                let mut first = true;
-                for (name, value) in items.iter() {
-                    if first {
-                        first = false;
-                    } else {
-                        f.write(", ");
-                    }
-                    f.write(name.as_ref());
-                    f.write(": ");
-                    value.display(f);
-                }
-                f.write("}");
-            }
-        }
+    f.leading_comments(frag.position);
+    f.indent();
+    f.write("...");
+    if let Some(ref cond) = frag.type_condition {
+        f.write(" ");
+        cond.display(f);
     }
+    format_directives(&frag.directives, f);
+    f.write(" ");
+    f.trailing_comment(frag.position);
 }
 
 impl<'a, T: Text<'a>> Displayable for InlineFragment<'a, T>
     where T: Text<'a>,
 {
     fn display(&self, f: &mut Formatter) {
-        f.indent();
-        f.write("...");
-        if let Some(ref cond) = self.type_condition {
-            f.write(" ");
-            cond.display(f);
-        }
-        format_directives(&self.directives, f);
-        f.write(" ");
+        format_inline_fragment_head(self, f);
         f.start_block();
-        for item in &self.selection_set.items {
-            item.display(f);
-        }
+        format_selections(&self.selection_set.items, f);
         f.end_block();
     }
 }
@@ -306,23 +410,17 @@ impl<'a, T: Text<'a>> Displayable for FragmentSpread<'a, T>
     where T: Text<'a>,
 {
     fn display(&self, f: &mut Formatter) {
+        f.leading_comments(self.position);
         f.indent();
         f.write("...");
         f.write(self.fragment_name.as_ref());
+        format_arguments(&self.arguments, f);
         format_directives(&self.directives, f);
+        f.trailing_comment(self.position);
         f.endline();
     }
 }
 
-impl<'a, T: Text<'a>> Displayable for Directive<'a, T>
-    where T: Text<'a>,
-{
-    fn display(&self, f: &mut Formatter) {
-        f.write("@");
-        f.write(self.name.as_ref());
-        format_directive_arguments(self.arguments.as_slice(), f);
-    }
-}
 
 
 impl_display!(
@@ -334,10 +432,43 @@ impl_display!(
     SelectionSet,
     Field,
     VariableDefinition,
-    Type,
-    Value,
     InlineFragment,
     TypeCondition,
     FragmentSpread,
-    Directive,
+);
+
+impl_display!(
+    Nullability,
+    NullabilityDesignator,
+);
+
+#[cfg(all(test, feature = "apq"))]
+mod test {
+    use crate::parse_query;
+    use super::{apq_hash, apq_hash_normalized};
+
+    #[test]
+    fn raw_hash() {
+        assert_eq!(apq_hash("{ hello }"),
+            "001c3174e099bd72b729d0c0a529ba9f5a740c446e2a6e1d71b283cb84ec3065");
+    }
+
+    #[test]
+    fn normalized_hash() {
+        let expected =
+            "249fb7022cd6e1af15d174091a20c827f281005da3fd67019208618dfae0f4a9";
+        assert_eq!(apq_hash_normalized("{ hello }").unwrap(), expected);
+        assert_eq!(apq_hash_normalized("{\n  hello, } # x").unwrap(),
+                   expected);
+        assert_eq!(parse_query::<String>("{hello}").unwrap().apq_hash(),
+                   expected);
+    }
+}
+
+impl_write!(
+    'a
+    Document,
+    Definition,
+    Operation,
+    FragmentDefinition,
 );
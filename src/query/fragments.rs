@@ -0,0 +1,237 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::query::ast::*;
+use crate::query::separate::spreads;
+
+
+/// Which fragments are used by the operations and the other fragments of
+/// the document
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::parse_query;
+/// use edb_graphql_parser::query::FragmentGraph;
+///
+/// let doc = parse_query::<&str>("
+///     query A { ...F }
+///     fragment F on Query { ...G }
+///     fragment G on Query { a }
+///     fragment Unused on Query { ...Unused }
+/// ").unwrap();
+/// let graph = FragmentGraph::new(&doc);
+/// let (name, used) = graph.operations().next().unwrap();
+/// assert_eq!(name, Some("A"));
+/// assert_eq!(used.iter().collect::<Vec<_>>(), [&"F", &"G"]);
+/// assert_eq!(graph.operations_using("G"), [Some("A")]);
+/// assert_eq!(graph.unused(), ["Unused"]);
+/// assert_eq!(graph.cycles(), [vec!["Unused"]]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct FragmentGraph<'x> {
+    /// Fragments spread directly by each fragment
+    fragments: BTreeMap<&'x str, BTreeSet<&'x str>>,
+    /// Names of the fragments in the order of definition
+    order: Vec<&'x str>,
+    /// Fragments used by each operation, directly or through other
+    /// fragments
+    operations: Vec<(Option<&'x str>, BTreeSet<&'x str>)>,
+}
+
+impl<'x> FragmentGraph<'x> {
+    /// Builds the graph of the document
+    ///
+    /// If several fragments have the same name, the first one is used.
+    pub fn new<'a, T>(doc: &'x Document<'a, T>) -> FragmentGraph<'x>
+        where T: Text<'a>,
+    {
+        let mut fragments = BTreeMap::new();
+        let mut order = Vec::new();
+        for frag in doc.fragments() {
+            let name = frag.name.as_ref();
+            if !fragments.contains_key(name) {
+                let mut names = Vec::new();
+                spreads(&frag.selection_set, &mut names);
+                fragments.insert(name, names.into_iter().collect());
+                order.push(name);
+            }
+        }
+        let mut graph = FragmentGraph {
+            fragments,
+            order,
+            operations: Vec::new(),
+        };
+        graph.operations = doc.operations().map(|op| {
+            let mut names = Vec::new();
+            spreads(&op.selection_set, &mut names);
+            (op.name.as_ref().map(|n| n.as_ref()), graph.reachable(names))
+        }).collect();
+        graph
+    }
+
+    /// Returns the fragments spread directly by the fragment, or `None` if
+    /// the fragment is not defined
+    pub fn dependencies(&self, fragment: &str)
+        -> Option<&BTreeSet<&'x str>>
+    {
+        self.fragments.get(fragment)
+    }
+
+    /// Returns the fragments spread by the fragment, directly or through
+    /// other fragments
+    ///
+    /// The fragment itself is only included if it is a part of a cycle.
+    pub fn transitive_dependencies(&self, fragment: &str)
+        -> BTreeSet<&'x str>
+    {
+        match self.fragments.get(fragment) {
+            Some(names) => self.reachable(names.iter().cloned()),
+            None => BTreeSet::new(),
+        }
+    }
+
+    /// Returns names of the operations with the fragments each of them
+    /// uses, directly or through other fragments, in the order of the
+    /// document
+    ///
+    /// Undefined fragments are included.
+    pub fn operations(&self)
+        -> impl Iterator<Item=(Option<&'x str>, &BTreeSet<&'x str>)>
+    {
+        self.operations.iter().map(|(name, used)| (*name, used))
+    }
+
+    /// Returns names of the operations using the fragment, directly or
+    /// through other fragments
+    pub fn operations_using(&self, fragment: &str) -> Vec<Option<&'x str>> {
+        self.operations.iter()
+            .filter(|(_, used)| used.contains(fragment))
+            .map(|(name, _)| *name)
+            .collect()
+    }
+
+    /// Returns the fragments that are not used by any operation, in the
+    /// order of definition
+    ///
+    /// Fragments only used by other unused fragments are included.
+    pub fn unused(&self) -> Vec<&'x str> {
+        self.order.iter()
+            .filter(|name| {
+                !self.operations.iter().any(|(_, used)| used.contains(*name))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the fragments which are spread but not defined
+    pub fn undefined(&self) -> BTreeSet<&'x str> {
+        self.fragments.values()
+            .chain(self.operations.iter().map(|(_, used)| used))
+            .flat_map(|names| names.iter().cloned())
+            .filter(|name| !self.fragments.contains_key(name))
+            .collect()
+    }
+
+    /// Returns the cycles of fragments spreading each other
+    ///
+    /// Each cycle is a list of fragments where each fragment spreads the
+    /// next one, and the last one spreads the first. A fragment spreading
+    /// itself is a cycle of one element.
+    pub fn cycles(&self) -> Vec<Vec<&'x str>> {
+        let mut detector = CycleDetector {
+            graph: self,
+            visited: BTreeSet::new(),
+            path: Vec::new(),
+            cycles: Vec::new(),
+        };
+        for name in &self.order {
+            detector.detect(name);
+        }
+        detector.cycles
+    }
+
+    fn reachable<I>(&self, roots: I) -> BTreeSet<&'x str>
+        where I: IntoIterator<Item=&'x str>,
+    {
+        let mut used = BTreeSet::new();
+        let mut queue = roots.into_iter().collect::<Vec<_>>();
+        while let Some(name) = queue.pop() {
+            if used.insert(name) {
+                if let Some(names) = self.fragments.get(name) {
+                    queue.extend(names.iter().cloned());
+                }
+            }
+        }
+        used
+    }
+}
+
+struct CycleDetector<'g, 'x> {
+    graph: &'g FragmentGraph<'x>,
+    visited: BTreeSet<&'x str>,
+    path: Vec<&'x str>,
+    cycles: Vec<Vec<&'x str>>,
+}
+
+impl<'g, 'x> CycleDetector<'g, 'x> {
+    fn detect(&mut self, fragment: &'x str) {
+        if !self.visited.insert(fragment) {
+            return;
+        }
+        let graph = self.graph;
+        let names = match graph.fragments.get(fragment) {
+            Some(names) => names,
+            None => return,
+        };
+        self.path.push(fragment);
+        for &name in names {
+            match self.path.iter().position(|&n| n == name) {
+                Some(idx) => self.cycles.push(self.path[idx..].to_vec()),
+                None => self.detect(name),
+            }
+        }
+        self.path.pop();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeSet;
+
+    use crate::parse_query;
+    use super::FragmentGraph;
+
+    fn set<'x>(names: &[&'x str]) -> BTreeSet<&'x str> {
+        names.iter().cloned().collect()
+    }
+
+    #[test]
+    fn graph() {
+        let doc = parse_query::<&str>("
+            query A { a { ... on T { ...F } } }
+            { ...G }
+            fragment F on T { f ...G ...Missing }
+            fragment G on T { g }
+            fragment H on T { ...F ...I }
+            fragment I on T { ...H }
+            fragment J on T { ...J }
+            fragment G on T { ...J }
+        ").unwrap();
+        let graph = FragmentGraph::new(&doc);
+        assert_eq!(graph.operations().collect::<Vec<_>>(), [
+            (Some("A"), &set(&["F", "G", "Missing"])),
+            (None, &set(&["G"])),
+        ]);
+        assert_eq!(graph.dependencies("F"), Some(&set(&["G", "Missing"])));
+        assert_eq!(graph.dependencies("G"), Some(&set(&[])));
+        assert_eq!(graph.dependencies("Missing"), None);
+        assert_eq!(graph.transitive_dependencies("H"),
+                   set(&["F", "G", "H", "I", "Missing"]));
+        assert_eq!(graph.transitive_dependencies("F"), set(&["G", "Missing"]));
+        assert_eq!(graph.operations_using("G"), [Some("A"), None]);
+        assert_eq!(graph.operations_using("F"), [Some("A")]);
+        assert!(graph.operations_using("H").is_empty());
+        assert_eq!(graph.unused(), ["H", "I", "J"]);
+        assert_eq!(graph.undefined(), set(&["Missing"]));
+        assert_eq!(graph.cycles(), [vec!["H", "I"], vec!["J"]]);
+    }
+}
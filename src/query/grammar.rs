@@ -1,81 +1,261 @@
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+use std::str::FromStr;
+
 use combine::{parser, ParseResult, Parser};
 use combine::combinator::{many1, eof, optional, position};
+use combine::easy::Errors;
+use combine::error::{Consumed, Tracked};
 
-use crate::common::{Directive};
-use crate::common::{directives, arguments, default_value, parse_type};
-use crate::tokenizer::{TokenStream};
+use crate::common::{self, Directive};
+use crate::common::{directives, field_arguments, default_value};
+use crate::tokenizer::{Token, TokenStream};
+use crate::error::ErrorCode;
 use crate::helpers::{punct, ident, name};
+use crate::options::ParserOptions;
+use crate::position::Pos;
 use crate::query::error::{ParseError};
 use crate::query::ast::*;
 
-pub fn field<'a, S>(input: &mut TokenStream<'a>)
-    -> ParseResult<Field<'a, S>, TokenStream<'a>>
+fn nullability_designator<'a>(input: &mut TokenStream<'a>)
+    -> ParseResult<NullabilityDesignator, TokenStream<'a>>
+{
+    punct("!").map(|_| NullabilityDesignator::Required)
+    .or(punct("?").map(|_| NullabilityDesignator::Optional))
+    .parse_stream(input)
+}
+
+/// Parses the nullability without recursion, like `common::parse_type`
+pub fn nullability<'a>(input: &mut TokenStream<'a>)
+    -> ParseResult<Nullability, TokenStream<'a>>
+{
+    let mut lists = 0;
+    while punct("[").parse_stream(input).is_ok() {
+        lists += 1;
+    }
+    if lists == 0 {
+        return parser(nullability_designator).map(Nullability::Designator)
+            .parse_stream(input);
+    }
+    let mut element = optional(parser(nullability_designator))
+        .parse_stream(input)
+        .map(|(designator, _)| designator.map(Nullability::Designator))
+        .map_err(|e| e.into_consumed())?;
+    for _ in 0..lists {
+        punct("]").parse_stream(input).map_err(|e| e.into_consumed())?;
+        let designator = optional(parser(nullability_designator))
+            .parse_stream(input)
+            .map(|(designator, _)| designator)
+            .map_err(|e| e.into_consumed())?;
+        element = Some(Nullability::List(element.map(Box::new), designator));
+    }
+    Ok((element.expect("list is parsed"), Consumed::Consumed(())))
+}
+
+/// Field or inline fragment whose selection set is being parsed
+enum Owner<'a, S: Text<'a>> {
+    Field(Field<'a, S>),
+    InlineFragment(InlineFragment<'a, S>),
+}
+
+/// Start of the selection, selection sets are parsed separately
+enum Head<'a, S: Text<'a>> {
+    Selection(Selection<'a, S>),
+    /// Selection set is opened, with the position of the `{`
+    Nested(Owner<'a, S>, Pos),
+}
+
+/// Selection set with the selections parsed so far
+struct Frame<'a, S: Text<'a>> {
+    owner: Option<Owner<'a, S>>,
+    start: Pos,
+    items: Vec<Selection<'a, S>>,
+}
+
+impl<'a, S: Text<'a>> Owner<'a, S> {
+    fn into_selection(self, selection_set: SelectionSet<'a, S>)
+        -> Selection<'a, S>
+    {
+        match self {
+            Owner::Field(mut field) => {
+                field.selection_set = selection_set;
+                Selection::Field(field)
+            }
+            Owner::InlineFragment(mut frag) => {
+                frag.selection_set = selection_set;
+                Selection::InlineFragment(frag)
+            }
+        }
+    }
+}
+
+fn empty_set<'a, S: Text<'a>>(position: Pos) -> SelectionSet<'a, S> {
+    SelectionSet {
+        span: (position, position),
+        items: Vec::new(),
+    }
+}
+
+fn field_head<'a, S>(input: &mut TokenStream<'a>)
+    -> ParseResult<Head<'a, S>, TokenStream<'a>>
     where S: Text<'a>
 {
     (
         position(),
         name::<'a, S>(),
         optional(punct(":").with(name::<'a, S>())),
-        parser(arguments),
+        parser(field_arguments),
+        optional(parser(nullability)),
         parser(directives),
-        optional(parser(selection_set)),
-    ).map(|(position, name_or_alias, opt_name, arguments, directives, sel)| {
+        optional(position().skip(punct("{"))),
+    ).map(|(position, name_or_alias, opt_name, (arguments, arguments_syntax),
+            nullability, directives, start)|
+    {
         let (name, alias) = match opt_name {
             Some(name) => (name, Some(name_or_alias)),
             None => (name_or_alias, None),
         };
-        Field {
-            position, name, alias, arguments, directives,
-            selection_set: sel.unwrap_or_else(|| {
-                SelectionSet {
-                    span: (position, position),
-                    items: Vec::new(),
-                }
-            }),
+        let field = Field {
+            position, name, alias, arguments, arguments_syntax, nullability,
+            directives,
+            selection_set: empty_set(position),
+        };
+        match start {
+            Some(start) => Head::Nested(Owner::Field(field), start),
+            None => Head::Selection(Selection::Field(field)),
         }
     })
     .parse_stream(input)
+    .map_err(|e| input.rule_failed("Field", e))
 }
 
-pub fn selection<'a, S>(input: &mut TokenStream<'a>)
-    -> ParseResult<Selection<'a, S>, TokenStream<'a>>
+/// Parses `{` returning its position
+fn open_selection_set<'a>(input: &mut TokenStream<'a>)
+    -> ParseResult<Pos, TokenStream<'a>>
+{
+    position().skip(punct("{")).parse_stream(input)
+        .map_err(|e| input.rule_failed("SelectionSet", e))
+}
+
+fn selection_head<'a, S>(input: &mut TokenStream<'a>)
+    -> ParseResult<Head<'a, S>, TokenStream<'a>>
     where S: Text<'a>
 {
-    parser(field).map(Selection::Field)
+    parser(field_head)
     .or(punct("...").with((
                 position(),
-                optional(ident("on").with(name::<'a, S>()).map(TypeCondition::On)),
+                optional(position().skip(ident("on"))
+                         .and(name::<'a, S>().map(TypeCondition::On))),
                 parser(directives),
-                parser(selection_set),
-            ).map(|(position, type_condition, directives, selection_set)| {
-                InlineFragment { position, type_condition,
-                                 selection_set, directives }
+                parser(open_selection_set),
+            ).map(|(position, type_condition, directives, start)| {
+                let (on_position, type_condition) = match type_condition {
+                    Some((on, cond)) => (Some(on), Some(cond)),
+                    None => (None, None),
+                };
+                let frag = InlineFragment {
+                    position, on_position, type_condition, directives,
+                    selection_set: empty_set(start),
+                };
+                Head::Nested(Owner::InlineFragment(frag), start)
             })
-            .map(Selection::InlineFragment)
         .or((position(),
              name::<'a, S>(),
+             parser(field_arguments),
              parser(directives),
-            ).map(|(position, fragment_name, directives)| {
-                FragmentSpread { position, fragment_name, directives }
-            })
-            .map(Selection::FragmentSpread))
+            ).map(|(position, fragment_name, (arguments, arguments_syntax),
+                    directives)|
+            {
+                Head::Selection(Selection::FragmentSpread(FragmentSpread {
+                    position, fragment_name, arguments, arguments_syntax,
+                    directives,
+                }))
+            }))
     ))
     .parse_stream(input)
 }
 
+/// Parses selections until the selection set at the bottom of the stack is
+/// closed, returns its owner and the selection set
+///
+/// Unfinished selection sets are kept on the heap rather than recursing,
+/// so nesting is limited by memory, not the stack.
+#[allow(clippy::type_complexity)]
+fn nested_selections<'a, S>(input: &mut TokenStream<'a>,
+    mut stack: Vec<Frame<'a, S>>)
+    -> Result<(Option<Owner<'a, S>>, SelectionSet<'a, S>),
+              Consumed<Tracked<Errors<Token<'a>, Token<'a>, Pos>>>>
+    where S: Text<'a>
+{
+    loop {
+        let frame = stack.last_mut().expect("stack is not empty");
+        match selection_head(input) {
+            Ok((Head::Selection(selection), _)) => frame.items.push(selection),
+            Ok((Head::Nested(owner, start), _)) => {
+                stack.push(Frame {
+                    owner: Some(owner),
+                    start,
+                    items: Vec::new(),
+                });
+            }
+            Err(Consumed::Empty(_)) if !frame.items.is_empty() => {
+                let end = match position().skip(punct("}")).parse_stream(input)
+                {
+                    Ok((end, _)) => end,
+                    Err(e) => {
+                        return Err(input.rule_failed("SelectionSet",
+                                                     e.into_consumed()));
+                    }
+                };
+                let frame = stack.pop().expect("stack is not empty");
+                let selection_set = SelectionSet {
+                    span: (frame.start, end),
+                    items: frame.items,
+                };
+                match (stack.last_mut(), frame.owner) {
+                    (Some(parent), Some(owner)) => {
+                        parent.items.push(owner.into_selection(selection_set));
+                    }
+                    (_, owner) => return Ok((owner, selection_set)),
+                }
+            }
+            Err(e) => {
+                return Err(input.rule_failed("SelectionSet",
+                                             e.into_consumed()));
+            }
+        }
+    }
+}
+
+pub fn selection<'a, S>(input: &mut TokenStream<'a>)
+    -> ParseResult<Selection<'a, S>, TokenStream<'a>>
+    where S: Text<'a>
+{
+    match selection_head(input)? {
+        (Head::Selection(selection), consumed) => Ok((selection, consumed)),
+        (Head::Nested(owner, start), consumed) => {
+            let frame = Frame { owner: Some(owner), start, items: Vec::new() };
+            match nested_selections(input, vec![frame])? {
+                (Some(owner), set) => {
+                    Ok((owner.into_selection(set), consumed))
+                }
+                (None, _) => unreachable!("owner is passed in"),
+            }
+        }
+    }
+}
+
 pub fn selection_set<'a, S>(input: &mut TokenStream<'a>)
     -> ParseResult<SelectionSet<'a, S>, TokenStream<'a>>
     where S: Text<'a>,
 {
-    (
-        position().skip(punct("{")),
-        many1(parser(selection)),
-        position().skip(punct("}")),
-    ).map(|(start, items, end)| SelectionSet { span: (start, end), items })
-    .parse_stream(input)
+    let (start, consumed) = open_selection_set(input)?;
+    let frame = Frame { owner: None, start, items: Vec::new() };
+    let (_, set) = nested_selections(input, vec![frame])?;
+    Ok((set, consumed))
 }
 
-pub fn query<'a, T: Text<'a>>(input: &mut TokenStream<'a>)
+pub fn query<'a, T>(input: &mut TokenStream<'a>)
     -> ParseResult<Operation<'a, T>, TokenStream<'a>>
     where T: Text<'a>,
 {
@@ -90,6 +270,50 @@ pub fn query<'a, T: Text<'a>>(input: &mut TokenStream<'a>)
             position, name, selection_set, directives,
         })
     .parse_stream(input)
+    .map_err(|e| input.rule_failed("OperationDefinition", e))
+}
+
+pub fn variable_definition<'a, T>(input: &mut TokenStream<'a>)
+    -> ParseResult<VariableDefinition<'a, T>, TokenStream<'a>>
+    where T: Text<'a>,
+{
+    (
+        position(),
+        punct("$").with(name::<'a, T>()).skip(punct(":")),
+        parser(common::parse_type),
+        optional((
+            position(),
+            punct("=")
+            .with(parser(default_value)),
+            position()
+        )),
+        parser(directives),
+    ).map(|(position, name, var_type, def, directives)| {
+        VariableDefinition {
+            position, name, var_type,
+            default_value: def.map(|(s, value, e)| DefaultValue {
+                span: (s, e),
+                value,
+            }),
+            directives,
+        }
+    })
+    .parse_stream(input)
+    .map_err(|e| input.rule_failed("VariableDefinition", e))
+}
+
+/// Parses parenthesized variable definitions, returns them along with the
+/// position of the closing paren
+pub fn variable_definitions<'a, T>(input: &mut TokenStream<'a>)
+    -> ParseResult<(Vec<VariableDefinition<'a, T>>, Pos), TokenStream<'a>>
+    where T: Text<'a>,
+{
+    punct("(")
+    .with(many1(parser(variable_definition)))
+    .and(position())
+    .skip(punct(")"))
+    .parse_stream(input)
+    .map_err(|e| input.rule_failed("VariableDefinitions", e))
 }
 
 /// A set of attributes common to a Query and a Mutation
@@ -101,37 +325,14 @@ type OperationCommon<'a, T: Text<'a>> = (
     SelectionSet<'a, T>,
 );
 
-pub fn operation_common<'a, T: Text<'a>>(input: &mut TokenStream<'a>)
+pub fn operation_common<'a, T>(input: &mut TokenStream<'a>)
     -> ParseResult<OperationCommon<'a, T>, TokenStream<'a>>
     where T: Text<'a>,
 {
     optional(name::<'a, T>())
     .and(position()
-        .and(optional(
-            punct("(")
-            .with(many1(
-                (
-                    position(),
-                    punct("$").with(name::<'a, T>()).skip(punct(":")),
-                    parser(parse_type),
-                    optional((
-                        position(),
-                        punct("=")
-                        .with(parser(default_value)),
-                        position()
-                    )),
-                ).map(|(position, name, var_type, def)| {
-                    VariableDefinition {
-                        position, name, var_type,
-                        default_value: def.map(|(s, value, e)| DefaultValue {
-                            span: (s, e),
-                            value,
-                        }),
-                    }
-                })))
-            .and(position())
-            .skip(punct(")"))
-        )).map(|(position, vars)| {
+        .and(optional(parser(variable_definitions)))
+        .map(|(position, vars)| {
             vars
             .map(|(v, position)| {
                 (v, InsertVars {
@@ -152,7 +353,7 @@ pub fn operation_common<'a, T: Text<'a>>(input: &mut TokenStream<'a>)
     .parse_stream(input)
 }
 
-pub fn mutation<'a, T: Text<'a>>(input: &mut TokenStream<'a>)
+pub fn mutation<'a, T>(input: &mut TokenStream<'a>)
     -> ParseResult<Operation<'a, T>, TokenStream<'a>>
     where T: Text<'a>,
 {
@@ -167,9 +368,10 @@ pub fn mutation<'a, T: Text<'a>>(input: &mut TokenStream<'a>)
             position, name, selection_set, directives,
         })
     .parse_stream(input)
+    .map_err(|e| input.rule_failed("OperationDefinition", e))
 }
 
-pub fn subscription<'a, T: Text<'a>>(input: &mut TokenStream<'a>)
+pub fn subscription<'a, T>(input: &mut TokenStream<'a>)
     -> ParseResult<Operation<'a, T>, TokenStream<'a>>
     where T: Text<'a>,
 {
@@ -184,6 +386,7 @@ pub fn subscription<'a, T: Text<'a>>(input: &mut TokenStream<'a>)
             position, name, selection_set, directives,
         })
     .parse_stream(input)
+    .map_err(|e| input.rule_failed("OperationDefinition", e))
 }
 
 pub fn operation_definition<'a, S>(input: &mut TokenStream<'a>)
@@ -209,22 +412,29 @@ pub fn operation_definition<'a, S>(input: &mut TokenStream<'a>)
     .parse_stream(input)
 }
 
-pub fn fragment_definition<'a, T: Text<'a>>(input: &mut TokenStream<'a>)
+pub fn fragment_definition<'a, T>(input: &mut TokenStream<'a>)
     -> ParseResult<FragmentDefinition<'a, T>, TokenStream<'a>>
     where T: Text<'a>,
 {
     (
         position().skip(ident("fragment")),
         name::<'a, T>(),
-        ident("on").with(name::<'a, T>()).map(TypeCondition::On),
+        optional(parser(variable_definitions).map(|(vars, _)| vars)),
+        position().skip(ident("on")),
+        name::<'a, T>().map(TypeCondition::On),
         parser(directives),
         parser(selection_set)
-    ).map(|(position, name, type_condition, directives, selection_set)| {
+    ).map(|(position, name, vars, on_position, type_condition, directives,
+            selection_set)|
+    {
         FragmentDefinition {
-            position, name, type_condition, directives, selection_set,
+            position, name, on_position, type_condition, directives,
+            selection_set,
+            variable_definitions: vars.unwrap_or_default(),
         }
     })
     .parse_stream(input)
+    .map_err(|e| input.rule_failed("FragmentDefinition", e))
 }
 
 pub fn definition<'a, S>(input: &mut TokenStream<'a>)
@@ -240,24 +450,357 @@ pub fn definition<'a, S>(input: &mut TokenStream<'a>)
 pub fn parse_query<'a, S>(s: &'a str) -> Result<Document<'a, S>, ParseError>
     where S: Text<'a>,
 {
-    let mut tokens = TokenStream::new(s);
+    parse_query_with_options(s, &ParserOptions::default())
+}
+
+/// Parses a piece of query language embedded in a larger text, starting
+/// at the `start` position in it
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::Pos;
+/// use edb_graphql_parser::query::{parse_query_with_offset, Definition};
+///
+/// let js = "const Q = gql`\n  query Q { a }`;";
+/// let start = Pos { line: 1, column: 15, character: 14, token: 0 };
+/// let doc = parse_query_with_offset::<&str>(&js[14..js.len() - 2], start)
+///     .unwrap();
+/// if let Definition::Operation(ref op) = doc.definitions[0] {
+///     assert_eq!(op.position.to_string(), "2:3");
+///     assert_eq!(op.position.character, 17);
+/// }
+/// ```
+pub fn parse_query_with_offset<'a, S>(s: &'a str, start: Pos)
+    -> Result<Document<'a, S>, ParseError>
+    where S: Text<'a>,
+{
+    parse_query_with_options(s,
+        ParserOptions::default().start_position(start))
+}
+
+/// Parses a piece of query language using specified options
+pub fn parse_query_with_options<'a, S>(s: &'a str, options: &ParserOptions)
+    -> Result<Document<'a, S>, ParseError>
+    where S: Text<'a>,
+{
+    parse_document(s, options).map_err(|e| e.in_source(options))
+}
+
+fn parse_document<'a, S>(s: &'a str, options: &ParserOptions)
+    -> Result<Document<'a, S>, ParseError>
+    where S: Text<'a>,
+{
+    let mut tokens = options.tokens(s);
     let (doc, _) = many1(parser(definition))
         .map(|d| Document { definitions: d })
         .skip(eof())
         .parse_stream(&mut tokens)
-        .map_err(|e| e.into_inner().error)?;
+        .map_err(|e| ParseError::syntax(e.into_inner().error, &tokens))?;
 
+    if options.check_lone_anonymous_operation() {
+        lone_anonymous_operation(&doc)?;
+    }
+    if options.check_unique_names() {
+        unique_names(&doc)?;
+    }
+    for def in &doc.definitions {
+        check_definition(def, options)?;
+    }
     Ok(doc)
 }
 
+/// Rejects the syntax of a single definition not enabled in the options
+fn check_definition<'a, S>(def: &Definition<'a, S>, options: &ParserOptions)
+    -> Result<(), ParseError>
+    where S: Text<'a>,
+{
+    let selection_set = match *def {
+        Definition::Operation(ref op) => &op.selection_set,
+        Definition::Fragment(ref frag) => {
+            if !frag.variable_definitions.is_empty() &&
+                !options.allow_fragment_arguments()
+            {
+                return Err(fragment_arguments_error(frag.position));
+            }
+            &frag.selection_set
+        }
+    };
+    experimental_syntax(selection_set, options)
+}
+
+/// Iterator over the parsed definitions of a query, see `parse_query_iter`
+#[derive(Debug)]
+pub struct QueryDefinitions<'a, S> {
+    tokens: TokenStream<'a>,
+    options: ParserOptions,
+    started: bool,
+    failed: bool,
+    text: PhantomData<S>,
+}
+
+/// Parses the definitions of a query one by one, as the iterator advances
+///
+/// Only the current definition is kept in memory, so this is suitable for
+/// scanning huge files with many operations. Syntax is checked the same
+/// way as by `parse_query`, except for the checks that need the whole
+/// document: anonymous operations and unique names are not checked, even
+/// if enabled in the options.
+///
+/// The iterator stops after the first error.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::query::{parse_query_iter, Definition};
+///
+/// let source = "query A { a } fragment F on T { b } query B { ...F }";
+/// let mut names = Vec::new();
+/// for def in parse_query_iter::<&str>(source) {
+///     if let Definition::Operation(op) = def.unwrap() {
+///         names.push(op.name.unwrap());
+///     }
+/// }
+/// assert_eq!(names, ["A", "B"]);
+///
+/// let mut definitions = parse_query_iter::<&str>("{ a } { b");
+/// assert!(definitions.next().unwrap().is_ok());
+/// assert!(definitions.next().unwrap().is_err());
+/// assert!(definitions.next().is_none());
+/// ```
+pub fn parse_query_iter<'a, S>(s: &'a str) -> QueryDefinitions<'a, S>
+    where S: Text<'a>,
+{
+    parse_query_iter_with_options(s, &ParserOptions::default())
+}
+
+/// Parses the definitions of a query one by one using specified options
+pub fn parse_query_iter_with_options<'a, S>(s: &'a str,
+    options: &ParserOptions)
+    -> QueryDefinitions<'a, S>
+    where S: Text<'a>,
+{
+    QueryDefinitions {
+        tokens: options.tokens(s),
+        options: options.clone(),
+        started: false,
+        failed: false,
+        text: PhantomData,
+    }
+}
+
+impl<'a, S: Text<'a>> QueryDefinitions<'a, S> {
+    fn parse_next(&mut self) -> Result<Definition<'a, S>, ParseError> {
+        let (def, _) = parser(definition)
+            .parse_stream(&mut self.tokens)
+            .map_err(|e| {
+                ParseError::syntax(e.into_inner().error, &self.tokens)
+            })?;
+        check_definition(&def, &self.options)?;
+        Ok(def)
+    }
+}
+
+impl<'a, S: Text<'a>> Iterator for QueryDefinitions<'a, S> {
+    type Item = Result<Definition<'a, S>, ParseError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        // an empty document is an error, same as for `parse_query`
+        if self.started && eof().parse_stream(&mut self.tokens).is_ok() {
+            return None;
+        }
+        self.started = true;
+        let result = self.parse_next();
+        self.failed = result.is_err();
+        Some(result.map_err(|e| e.in_source(&self.options)))
+    }
+}
+
+/// Parses a standalone value, such as a default value stored in the
+/// configuration
+///
+/// Variables are allowed, same as in the arguments of the query. Positions
+/// are relative to the start of the string.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::query::{parse_value, Value};
+///
+/// let value = parse_value::<&str>("[1, {a: $b}]").unwrap();
+/// assert!(matches!(value, Value::List(ref items) if items.len() == 2));
+/// assert!(parse_value::<&str>("1 2").is_err());
+/// ```
+pub fn parse_value<'a, S>(s: &'a str) -> Result<Value<'a, S>, ParseError>
+    where S: Text<'a>,
+{
+    let mut tokens = TokenStream::new(s);
+    let (value, _) = parser(common::value)
+        .skip(eof())
+        .parse_stream(&mut tokens)
+        .map_err(|e| ParseError::syntax(e.into_inner().error, &tokens))?;
+    Ok(value)
+}
+
+/// Parses a standalone type reference, such as `[ID!]!`
+pub fn parse_type<'a, S>(s: &'a str) -> Result<Type<'a, S>, ParseError>
+    where S: Text<'a>,
+{
+    let mut tokens = TokenStream::new(s);
+    let (typ, _) = parser(common::parse_type)
+        .skip(eof())
+        .parse_stream(&mut tokens)
+        .map_err(|e| ParseError::syntax(e.into_inner().error, &tokens))?;
+    Ok(typ)
+}
+
+impl FromStr for Document<'static, String> {
+    type Err = ParseError;
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        parse_query::<String>(s).map(Document::into_static)
+    }
+}
+
+impl FromStr for Value<'static, String> {
+    type Err = ParseError;
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        parse_value::<&str>(s).map(|value| value.map_text(String::from))
+    }
+}
+
+impl FromStr for Type<'static, String> {
+    type Err = ParseError;
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        parse_type::<&str>(s).map(|typ| typ.map_text(String::from))
+    }
+}
+
+/// Parses a field set, i.e. a list of selections without enclosing braces
+///
+/// This is the syntax of the `fields` argument of federation directives
+/// like `@key(fields: "id owner { id }")`. Positions are relative to the
+/// start of the string.
+pub fn parse_field_set<'a, S>(s: &'a str)
+    -> Result<SelectionSet<'a, S>, ParseError>
+    where S: Text<'a>,
+{
+    let mut tokens = TokenStream::new(s);
+    let (set, _) = (position(), many1(parser(selection)), position())
+        .map(|(start, items, end)| SelectionSet { span: (start, end), items })
+        .skip(eof())
+        .parse_stream(&mut tokens)
+        .map_err(|e| ParseError::syntax(e.into_inner().error, &tokens))?;
+    experimental_syntax(&set, &ParserOptions::default())?;
+    Ok(set)
+}
+
+fn fragment_arguments_error(position: Pos) -> ParseError {
+    ParseError::at(ErrorCode::ExperimentalSyntax, position,
+        "Fragment arguments are experimental, \
+         they must be enabled in the parser options")
+}
+
+/// Rejects experimental syntax unless it's enabled in the options
+fn experimental_syntax<'a, S>(selection_set: &SelectionSet<'a, S>,
+    options: &ParserOptions)
+    -> Result<(), ParseError>
+    where S: Text<'a>,
+{
+    let mut stack = vec![selection_set.items.iter()];
+    while let Some(items) = stack.last_mut() {
+        let item = match items.next() {
+            Some(item) => item,
+            None => {
+                stack.pop();
+                continue;
+            }
+        };
+        match *item {
+            Selection::Field(ref field) => {
+                if field.nullability.is_some() &&
+                    !options.allow_client_controlled_nullability()
+                {
+                    return Err(ParseError::at(
+                        ErrorCode::ExperimentalSyntax, field.position,
+                        "Client controlled nullability is experimental, \
+                         it must be enabled in the parser options"));
+                }
+                stack.push(field.selection_set.items.iter());
+            }
+            Selection::InlineFragment(ref frag) => {
+                stack.push(frag.selection_set.items.iter());
+            }
+            Selection::FragmentSpread(ref spread) => {
+                if !spread.arguments.is_empty() &&
+                    !options.allow_fragment_arguments()
+                {
+                    return Err(fragment_arguments_error(spread.position));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn lone_anonymous_operation<'a, S>(doc: &Document<'a, S>)
+    -> Result<(), ParseError>
+    where S: Text<'a>,
+{
+    let operations = doc.definitions.iter().filter_map(|def| match *def {
+        Definition::Operation(ref op) => Some(op),
+        Definition::Fragment(_) => None,
+    }).collect::<Vec<_>>();
+    if operations.len() < 2 {
+        return Ok(());
+    }
+    if let Some(idx) = operations.iter().position(|op| op.name.is_none()) {
+        let other = if idx == 0 { operations[1] } else { operations[0] };
+        return Err(ParseError::at(ErrorCode::AnonymousOperation,
+            operations[idx].position, &format!(
+                "Anonymous operation must be the only defined operation, \
+                 but there is another operation at {}", other.position))
+            .with_related(vec![other.position]));
+    }
+    Ok(())
+}
+
+/// Rejects operations and fragments with the names of the previous ones
+fn unique_names<'a, S>(doc: &Document<'a, S>) -> Result<(), ParseError>
+    where S: Text<'a>,
+{
+    let mut operations = BTreeMap::new();
+    let mut fragments = BTreeMap::new();
+    for def in &doc.definitions {
+        let (kind, name, position, seen) = match *def {
+            Definition::Operation(ref op) => match op.name {
+                Some(ref name) => {
+                    ("operation", name, op.position, &mut operations)
+                }
+                None => continue,
+            },
+            Definition::Fragment(ref frag) => {
+                ("fragment", &frag.name, frag.position, &mut fragments)
+            }
+        };
+        if let Some(&first) = seen.get(name.as_ref()) {
+            return Err(ParseError::at(ErrorCode::DuplicateName, position,
+                &format!("There can be only one {} named {:?}, \
+                          the first one is at {}",
+                         kind, name.as_ref(), first))
+                .with_related(vec![first]));
+        }
+        seen.insert(name.as_ref(), position);
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use crate::position::Pos;
     use crate::query::grammar::*;
     use super::parse_query;
 
-    fn ast(s: &str) -> Document<String> {
-        parse_query::<String>(&s).unwrap().to_owned()
+    fn ast(s: &str) -> Document<'_, String> {
+        parse_query::<String>(s).unwrap().to_owned()
     }
 
     #[test]
@@ -288,6 +831,8 @@ mod test {
                                 alias: None,
                                 name: "a".into(),
                                 arguments: Vec::new(),
+                                arguments_syntax: None,
+                                nullability: None,
                                 directives: Vec::new(),
                                 selection_set: SelectionSet {
                                     span: (Pos { line: 1, column: 3,
@@ -340,6 +885,23 @@ mod test {
                                         ("n".into(),
                                             Value::Null),
                                     ],
+                                    arguments_syntax: Some(ArgumentsSyntax {
+                                        parens: (
+                                            Pos { line: 1, column: 4,
+                                                  character: 3, token: 2 },
+                                            Pos { line: 1, column: 31,
+                                                  character: 30, token: 12 },
+                                        ),
+                                        colons: vec![
+                                            Pos { line: 1, column: 6,
+                                                  character: 5, token: 4 },
+                                            Pos { line: 1, column: 15,
+                                                  character: 14, token: 7 },
+                                            Pos { line: 1, column: 25,
+                                                  character: 24, token: 10 },
+                                        ],
+                                    }),
+                                    nullability: None,
                                     directives: Vec::new(),
                                     selection_set: SelectionSet {
                                         span: (Pos { line: 1, column: 3,
@@ -356,6 +918,41 @@ mod test {
             });
     }
 
+    #[test]
+    fn punctuator_positions() {
+        let doc = parse_query::<&str>("
+            fragment F on T { a @d(x: 1) ... on U { b(y: 2, z: 3) } ...G }
+        ").unwrap();
+        let frag = doc.fragment("F").unwrap();
+        assert_eq!(frag.on_position.to_string(), "2:24");
+        let items = &frag.selection_set.items;
+        let (field, inline, spread) = match items[..] {
+            [Selection::Field(ref field), Selection::InlineFragment(ref inline),
+             Selection::FragmentSpread(ref spread)] => (field, inline, spread),
+            _ => panic!("unexpected selections {:?}", items),
+        };
+        assert!(field.arguments_syntax.is_none());
+        let syntax = field.directives[0].arguments_syntax.as_ref().unwrap();
+        assert_eq!(syntax.parens.0.to_string(), "2:35");
+        assert_eq!(syntax.parens.1.to_string(), "2:40");
+        assert_eq!(syntax.colons.len(), 1);
+        assert_eq!(inline.on_position.unwrap().to_string(), "2:46");
+        let b = &inline.selection_set.fields().next().unwrap();
+        let syntax = b.arguments_syntax.as_ref().unwrap();
+        assert_eq!(syntax.colons.iter().map(|p| p.to_string())
+                   .collect::<Vec<_>>(), ["2:56", "2:62"]);
+        assert_eq!(syntax.parens.1.to_string(), "2:65");
+        assert!(spread.arguments_syntax.is_none());
+        let doc = parse_query::<&str>("{ ... @d { a } }").unwrap();
+        let op = doc.operations().next().unwrap();
+        match op.selection_set.items[0] {
+            Selection::InlineFragment(ref inline) => {
+                assert_eq!(inline.on_position, None)
+            }
+            _ => unreachable!(),
+        }
+    }
+
     #[test]
     fn one_field_roundtrip() {
         assert_eq!(ast("{ a }").to_string(), "{\n  a\n}\n");
@@ -366,4 +963,292 @@ mod test {
         assert_eq!(ast("{ a(x: 10000000000000000000000000000) }").to_string(),
             "{\n  a(x: 10000000000000000000000000000)\n}\n");
     }
+
+    #[test]
+    fn lone_anonymous_operation() {
+        use crate::options::ParserOptions;
+
+        let mut options = ParserOptions::default();
+        options.lone_anonymous_operation(true);
+        let parse = |s| parse_query_with_options::<&str>(s, &options)
+            .map_err(|e| e.to_string());
+        assert!(parse("{ a } fragment F on T { b }").is_ok());
+        assert!(parse("query A { a } query B { b }").is_ok());
+        assert_eq!(parse("query A { a }\n{ b }").unwrap_err(),
+            "query parse error: Parse error at 2:1\n\
+             Anonymous operation must be the only defined operation, \
+             but there is another operation at 1:1\n");
+        assert_eq!(parse("{ a } mutation { b }").unwrap_err(),
+            "query parse error: Parse error at 1:1\n\
+             Anonymous operation must be the only defined operation, \
+             but there is another operation at 1:7\n");
+        // disabled by default
+        assert!(parse_query::<&str>("{ a } { b }").is_ok());
+    }
+
+    #[test]
+    fn unique_names() {
+        use crate::options::ParserOptions;
+
+        let mut options = ParserOptions::default();
+        options.unique_names(true);
+        let parse = |s| parse_query_with_options::<&str>(s, &options);
+        assert!(parse("query A { ...A } fragment A on T { a }").is_ok());
+        let e = parse("{ ...F }\nfragment F on T { a }\nfragment F on T { b }")
+            .unwrap_err();
+        assert_eq!(e.to_string(),
+            "query parse error: Parse error at 3:1\n\
+             There can be only one fragment named \"F\", \
+             the first one is at 2:1\n");
+        assert_eq!(e.related_positions().len(), 1);
+        let e = parse("query Q { a } query Q { b }").unwrap_err();
+        assert_eq!(e.span().0.to_string(), "1:15");
+        // disabled by default
+        assert!(parse_query::<&str>("query Q { a } query Q { b }").is_ok());
+    }
+
+    #[test]
+    fn source_name() {
+        use crate::options::ParserOptions;
+
+        let mut options = ParserOptions::default();
+        options.source_name("q.graphql").lone_anonymous_operation(true);
+        let e = parse_query_with_options::<&str>("{ a } { b }", &options)
+            .unwrap_err();
+        assert_eq!(e.source_name(), Some("q.graphql"));
+        assert_eq!(e.to_string(),
+            "query parse error: Parse error at q.graphql:1:1\n\
+             Anonymous operation must be the only defined operation, \
+             but there is another operation at 1:7\n");
+        let e = parse_query::<&str>("{ a(x: ) }").unwrap_err();
+        assert_eq!(e.location(), None);
+        let loc = e.with_source("b.graphql").location().unwrap();
+        assert_eq!(loc.to_string(), "b.graphql:1:8");
+    }
+
+    #[test]
+    fn offset() {
+        use crate::position::Pos;
+        use super::parse_query_with_offset;
+
+        let start = Pos { line: 3, column: 10, character: 40, token: 0 };
+        let doc = parse_query_with_offset::<&str>("{ a\n\tb }", start)
+            .unwrap();
+        if let Definition::Operation(ref op) = doc.definitions[0] {
+            assert_eq!(op.position,
+                Pos { line: 3, column: 10, character: 40, token: 0 });
+            assert_eq!(op.selection_set.span.1,
+                Pos { line: 4, column: 11, character: 47, token: 3 });
+        }
+        let err = parse_query_with_offset::<&str>("{\n  a(x: ) }", start)
+            .unwrap_err();
+        assert_eq!(err.span().0.to_string(), "4:8");
+    }
+
+    #[test]
+    fn client_controlled_nullability() {
+        use crate::options::ParserOptions;
+
+        let mut options = ParserOptions::default();
+        options.client_controlled_nullability(true);
+        let query = "{ a! b: c? @skip(if: true) d[!]? { e[[?]] f[] } }";
+        let doc = parse_query_with_options::<&str>(query, &options).unwrap();
+        assert_eq!(doc.to_string(), "\
+{
+  a!
+  b: c? @skip(if: true)
+  d[!]? {
+    e[[?]]
+    f[]
+  }
+}
+");
+        match doc.definitions[0] {
+            Definition::Operation(ref op) => match op.selection_set.items[0] {
+                Selection::Field(ref f) => assert_eq!(f.nullability,
+                    Some(Nullability::Designator(
+                        NullabilityDesignator::Required))),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        }
+        assert!(parse_query_with_options::<&str>("{ a[!!] }", &options)
+            .is_err());
+        // disabled by default
+        assert_eq!(parse_query::<&str>("fragment F on T { ... { a { b? } } }")
+            .unwrap_err().to_string(),
+            "query parse error: Parse error at 1:29\n\
+             Client controlled nullability is experimental, \
+             it must be enabled in the parser options\n");
+    }
+
+    #[test]
+    fn fragment_arguments() {
+        use crate::options::ParserOptions;
+
+        let mut options = ParserOptions::default();
+        options.fragment_arguments(true);
+        let query = "
+            { ...F(x: 3, y: $y) @skip(if: false) ...F }
+            fragment F($x: Int = 1, $y: [String]) on T { a(x: $x) }
+        ";
+        let doc = parse_query_with_options::<&str>(query, &options).unwrap();
+        assert_eq!(doc.to_string(), "\
+{
+  ...F(x: 3, y: $y) @skip(if: false)
+  ...F
+}
+
+fragment F($x: Int = 1, $y: [String]) on T {
+  a(x: $x)
+}
+");
+        // disabled by default
+        let err = |s| parse_query::<&str>(s).unwrap_err().to_string();
+        assert_eq!(err("{ a { ...F(x: 1) } }"),
+            "query parse error: Parse error at 1:10\n\
+             Fragment arguments are experimental, \
+             they must be enabled in the parser options\n");
+        assert_eq!(err("{ a }\nfragment F($x: Int) on T { a }"),
+            "query parse error: Parse error at 2:1\n\
+             Fragment arguments are experimental, \
+             they must be enabled in the parser options\n");
+    }
+
+    #[test]
+    fn field_set() {
+        let set = parse_field_set::<&str>("id owner { id, ...F }").unwrap();
+        assert_eq!(set.items.len(), 2);
+        match set.items[1] {
+            Selection::Field(ref f) => {
+                assert_eq!(f.name, "owner");
+                assert_eq!(f.position, Pos { line: 1, column: 4,
+                                             character: 3, token: 1 });
+                assert_eq!(f.selection_set.items.len(), 2);
+            }
+            _ => unreachable!(),
+        }
+        assert_eq!(parse_field_set::<&str>("a { b }").unwrap().items.len(), 1);
+        assert!(parse_field_set::<&str>("").is_err());
+        assert!(parse_field_set::<&str>("{ a }").is_err());
+        assert_eq!(parse_field_set::<&str>("a\n b }").unwrap_err().to_string(),
+            "query parse error: Parse error at 2:4\n\
+             Unexpected `}[Punctuator]`\n\
+             Expected `end of input`\n");
+    }
+
+    #[test]
+    fn structured_errors() {
+        use crate::error::ErrorCode;
+        use crate::tokenizer::Kind;
+
+        let err = |s| parse_query::<&str>(s).unwrap_err();
+        let e = err("query Q($a: Int, b: Int) { a }");
+        assert_eq!(e.code(), ErrorCode::UnexpectedToken);
+        assert_eq!(e.found(), Some((Kind::Name, "b")));
+        assert_eq!(e.expected(), &[")".to_string()]);
+        assert_eq!(e.rule(), Some("VariableDefinitions"));
+        let (start, end) = e.span();
+        assert_eq!((start.line, start.column, end.column), (1, 18, 19));
+
+        let e = err("{ a(x: [1, 2) }");
+        assert_eq!(e.found(), Some((Kind::Punctuator, ")")));
+        assert_eq!(e.rule(), Some("Value"));
+
+        let e = err("{ a { b }");
+        assert_eq!(e.code(), ErrorCode::UnexpectedEndOfInput);
+        assert_eq!(e.found(), None);
+        assert_eq!(e.rule(), Some("SelectionSet"));
+
+        assert_eq!(err("{ a(x: \"abc) }").code(),
+            ErrorCode::UnterminatedString);
+        assert_eq!(err("{ a(x: 01) }").code(), ErrorCode::InvalidNumber);
+        assert_eq!(err("{ a(x: \"\\q\") }").code(), ErrorCode::InvalidEscape);
+        assert_eq!(err("{ a ^ }").code(), ErrorCode::UnexpectedCharacter);
+        assert_eq!(err("{ ...F(x: 1) }").code(),
+            ErrorCode::ExperimentalSyntax);
+
+        // failed alternatives at the start of a definition aren't a rule
+        let e = err("querry { a }");
+        assert_eq!(e.rule(), None);
+        assert_eq!(e.expected().len(), 5);
+    }
+
+    #[test]
+    fn from_str() {
+        let doc: Document<String> = "query Q { a }".parse().unwrap();
+        assert_eq!(doc, ast("query Q { a }"));
+        assert!("{ a".parse::<Document<String>>().is_err());
+
+        let value: Value<String> = " {a: [1, $b], c: \"d\"} ".parse().unwrap();
+        assert_eq!(value.to_string(), r#"{a: [1, $b], c: "d"}"#);
+        assert_eq!(parse_value::<&str>("ENUM").unwrap(), Value::Enum("ENUM"));
+        assert_eq!("1 2".parse::<Value<String>>().unwrap_err().to_string(),
+            "query parse error: Parse error at 1:3\n\
+             Unexpected `2[IntValue]`\nExpected `end of input`\n");
+
+        let typ: Type<String> = "[ID!]!".parse().unwrap();
+        assert_eq!(typ.to_string(), "[ID!]!");
+        assert_eq!(parse_type::<&str>("Int").unwrap(), Type::NamedType("Int"));
+        assert!("[Int".parse::<Type<String>>().is_err());
+        assert!("Int!!".parse::<Type<String>>().is_err());
+    }
+
+    #[test]
+    fn deep_nesting() {
+        use crate::format::Style;
+
+        let depth = 5000;
+        let mut options = ParserOptions::default();
+        options.max_depth(depth + 1).client_controlled_nullability(true);
+        let query = format!("{{ a(x: {}1{}, y: {}null{}) {}b{} }}",
+            "[".repeat(depth), "]".repeat(depth),
+            "{z: ".repeat(depth), "}".repeat(depth),
+            "{ b ".repeat(depth), " }".repeat(depth));
+        let doc = parse_query_with_options::<&str>(&query, &options).unwrap();
+        let minified = doc.format(Style::default().minified(true));
+        assert!(minified == format!("{{a(x:{}1{}y:{}null{}){} b{}}}",
+            "[".repeat(depth), "]".repeat(depth),
+            "{z:".repeat(depth), "}".repeat(depth),
+            "{b".repeat(depth), "}".repeat(depth)));
+
+        let unclosed = format!("{{ a(x: {}1) }}", "[".repeat(depth));
+        assert_eq!(parse_query_with_options::<&str>(&unclosed, &options)
+                   .unwrap_err().to_string(),
+            format!("query parse error: Parse error at 1:{}\n\
+                     Unexpected `)[Punctuator]`\nExpected `]`\n",
+                    depth + 9));
+        let unclosed = format!("{}b", "{ b ".repeat(depth));
+        assert!(parse_query_with_options::<&str>(&unclosed, &options)
+                .is_err());
+
+        let query = format!("query($x: {}Int!{}) {{ a{}!{} }}",
+            "[".repeat(depth), "]!".repeat(depth),
+            "[".repeat(depth), "]?".repeat(depth));
+        let doc = parse_query_with_options::<&str>(&query, &options).unwrap();
+        assert!(doc.format(Style::default().minified(true)) == format!(
+            "query($x:{}Int!{}){{a{}!{}}}",
+            "[".repeat(depth), "]!".repeat(depth),
+            "[".repeat(depth), "]?".repeat(depth)));
+    }
+
+    #[test]
+    fn too_deep() {
+        let depth = 1_000_000;
+        let mut options = ParserOptions::default();
+        options.max_depth(256);
+        for query in &[
+            format!("{}b{}", "{ b ".repeat(depth), " }".repeat(depth)),
+            format!("{{ a(x: {}1{}) }}", "[".repeat(depth), "]".repeat(depth)),
+            format!("query($x: {}Int{}) {{ a }}",
+                    "[".repeat(depth), "]".repeat(depth)),
+            format!("{{ a{}!{} }}", "[".repeat(depth), "]".repeat(depth)),
+        ] {
+            let err = parse_query_with_options::<&str>(query, &options)
+                .unwrap_err().to_string();
+            assert!(err.contains("nested deeper than 256 levels"), "{}", err);
+        }
+        assert!(parse_query_with_options::<&str>(&format!("{}b{}",
+                "{ b ".repeat(256), " }".repeat(256)), &options).is_ok());
+    }
 }
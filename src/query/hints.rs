@@ -0,0 +1,183 @@
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::common::{Directive, Value};
+use crate::position::Pos;
+use crate::query::ast::*;
+
+
+/// Execution hints specified by the directives of the operation
+///
+/// Returned by `HintDirectives::extract`. Hints which aren't specified are
+/// `None`, so executor applies its own defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExecutionHints {
+    pub timeout: Option<Duration>,
+    /// Priority level, larger is more important
+    pub priority: Option<i32>,
+}
+
+/// Names of the directives used to specify execution hints
+///
+/// By default hints are `@timeout(ms: Int)` and `@priority(level: Int)`:
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use std::time::Duration;
+/// use edb_graphql_parser::parse_query;
+/// use edb_graphql_parser::query::{Definition, HintDirectives};
+///
+/// let doc = parse_query::<&str>("query Q @timeout(ms: 500) { a }").unwrap();
+/// let op = match doc.definitions[0] {
+///     Definition::Operation(ref op) => op,
+///     _ => unreachable!(),
+/// };
+/// let hints = HintDirectives::default().extract(op).unwrap();
+/// assert_eq!(hints.timeout, Some(Duration::from_millis(500)));
+/// assert_eq!(hints.priority, None);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct HintDirectives {
+    timeout: (String, String),
+    priority: (String, String),
+}
+
+/// Hint directive that can't be interpreted
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum HintError {
+    /// Argument is missing or isn't an integer literal in the valid range
+    #[error("{position}: directive @{directive} requires \
+             {argument:?} argument of non-negative integer literal")]
+    InvalidArgument {
+        position: Pos,
+        directive: String,
+        argument: String,
+    },
+    /// Hint directive is specified more than once
+    #[error("{position}: directive @{directive} is specified more than once")]
+    Duplicate {
+        position: Pos,
+        directive: String,
+    },
+}
+
+impl Default for HintDirectives {
+    fn default() -> HintDirectives {
+        HintDirectives {
+            timeout: ("timeout".into(), "ms".into()),
+            priority: ("priority".into(), "level".into()),
+        }
+    }
+}
+
+impl HintDirectives {
+    /// Set directive and its argument specifying timeout in milliseconds
+    pub fn timeout(&mut self, directive: &str, argument: &str) -> &mut Self {
+        self.timeout = (directive.into(), argument.into());
+        self
+    }
+
+    /// Set directive and its argument specifying priority level
+    pub fn priority(&mut self, directive: &str, argument: &str) -> &mut Self {
+        self.priority = (directive.into(), argument.into());
+        self
+    }
+
+    /// Extracts the hints from the directives of the operation
+    ///
+    /// Values must be literals, as variables aren't known at this point.
+    /// Directives with other names are ignored.
+    pub fn extract<'a, T>(&self, operation: &Operation<'a, T>)
+        -> Result<ExecutionHints, HintError>
+        where T: Text<'a>,
+    {
+        let mut hints = ExecutionHints::default();
+        for dir in &operation.directives {
+            if dir.name.as_ref() == self.timeout.0 {
+                let ms = argument(dir, &self.timeout.1, hints.timeout)?;
+                hints.timeout = Some(Duration::from_millis(ms as u64));
+            } else if dir.name.as_ref() == self.priority.0 {
+                hints.priority = Some(
+                    argument(dir, &self.priority.1, hints.priority)?);
+            }
+        }
+        Ok(hints)
+    }
+}
+
+fn argument<'a, T, V>(dir: &Directive<'a, T>, name: &str, old: Option<V>)
+    -> Result<i32, HintError>
+    where T: Text<'a>,
+{
+    if old.is_some() {
+        return Err(HintError::Duplicate {
+            position: dir.position,
+            directive: dir.name.as_ref().into(),
+        });
+    }
+    dir.arguments.iter()
+        .find(|arg| arg.name.as_ref() == name)
+        .and_then(|arg| match arg.value {
+            Value::Int(ref num) => num.as_i64(),
+            _ => None,
+        })
+        .filter(|&value| value >= 0 && value <= i64::from(i32::MAX))
+        .map(|value| value as i32)
+        .ok_or_else(|| HintError::InvalidArgument {
+            position: dir.position,
+            directive: dir.name.as_ref().into(),
+            argument: name.into(),
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::parse_query;
+    use crate::query::Definition;
+    use super::{ExecutionHints, HintDirectives, HintError};
+
+    fn extract(hints: &HintDirectives, query: &str)
+        -> Result<ExecutionHints, HintError>
+    {
+        let doc = parse_query::<&str>(query).unwrap();
+        match doc.definitions[0] {
+            Definition::Operation(ref op) => hints.extract(op),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn custom_names() {
+        let mut hints = HintDirectives::default();
+        hints.timeout("deadline", "millis").priority("qos", "value");
+        assert_eq!(extract(&hints, "
+            query Q @deadline(millis: 30) @qos(value: 2) @timeout(ms: \"x\") {
+                a
+            }
+        ").unwrap(), ExecutionHints {
+            timeout: Some(Duration::from_millis(30)),
+            priority: Some(2),
+        });
+    }
+
+    #[test]
+    fn errors() {
+        let hints = HintDirectives::default();
+        let err = extract(&hints, "query Q($t: Int) @timeout(ms: $t) { a }")
+            .unwrap_err();
+        assert_eq!(err.to_string(), "1:18: directive @timeout requires \
+            \"ms\" argument of non-negative integer literal");
+        assert!(extract(&hints, "query @priority(level: -1) { a }").is_err());
+        match extract(&hints, "query @priority(level: 1) @priority(level: 2) {
+            a
+        }").unwrap_err() {
+            HintError::Duplicate { directive, .. } => {
+                assert_eq!(directive, "priority");
+            }
+            e => panic!("unexpected {:?}", e),
+        }
+    }
+}
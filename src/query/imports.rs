@@ -0,0 +1,305 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use thiserror::Error;
+
+use crate::query::ast::*;
+use crate::query::error::ParseError;
+use crate::query::grammar::parse_query;
+
+
+/// Error resolving the imports, see `resolve_imports`
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ImportError {
+    #[error("cannot load {path:?}{}: {message}", imported_from(.importer))]
+    Load {
+        path: String,
+        /// File containing the import, `None` for the entry file
+        importer: Option<String>,
+        /// Error returned by the loader
+        message: String,
+    },
+    #[error("{path}:{line}: invalid import, expected `#import \"<path>\"`")]
+    InvalidImport {
+        path: String,
+        /// One-based line number
+        line: usize,
+    },
+    #[error("{path}: {error}")]
+    Parse {
+        path: String,
+        error: ParseError,
+    },
+}
+
+fn imported_from(importer: &Option<String>) -> String {
+    match *importer {
+        Some(ref importer) => format!(" imported from {:?}", importer),
+        None => String::new(),
+    }
+}
+
+/// Document combined from several files, see `resolve_imports`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedDocument {
+    pub document: Document<'static, String>,
+    /// Path of the file of each definition, positions of the definition
+    /// are relative to that file
+    pub sources: Vec<String>,
+    /// Paths of all the files loaded, starting with the entry file
+    pub files: Vec<String>,
+}
+
+impl ImportedDocument {
+    /// Returns the path of the file the definition comes from
+    pub fn source_of(&self, definition: usize) -> Option<&str> {
+        self.sources.get(definition).map(|s| s.as_str())
+    }
+}
+
+/// Loads the file and everything it imports with `#import "<path>"`
+/// comments into a single document
+///
+/// Files are loaded by the `loader`, which receives the path and returns
+/// the source of the file. Paths starting with `./` or `../` are resolved
+/// relative to the directory of the importing file, other paths are passed
+/// to the loader as written. Every file is loaded once, so files importing
+/// each other are fine. Definitions of the entry file go first, followed by
+/// the imported files in the order the imports are found. A file can
+/// contain only imports.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::query::resolve_imports;
+///
+/// let files = [
+///     ("src/user.graphql", "#import \"./fragments/avatar.graphql\"\n\
+///                           query User { me { ...Avatar } }"),
+///     ("src/fragments/avatar.graphql", "fragment Avatar on User { url }"),
+/// ];
+/// let doc = resolve_imports("src/user.graphql", |path| {
+///     files.iter().find(|(name, _)| *name == path)
+///         .map(|(_, source)| source.to_string())
+///         .ok_or("not found")
+/// }).unwrap();
+/// assert_eq!(doc.document.definitions.len(), 2);
+/// assert_eq!(doc.source_of(1), Some("src/fragments/avatar.graphql"));
+/// ```
+pub fn resolve_imports<F, E>(path: &str, mut loader: F)
+    -> Result<ImportedDocument, ImportError>
+    where F: FnMut(&str) -> Result<String, E>,
+          E: fmt::Display,
+{
+    let mut result = ImportedDocument {
+        document: Document { definitions: Vec::new() },
+        sources: Vec::new(),
+        files: Vec::new(),
+    };
+    let mut seen = BTreeSet::new();
+    seen.insert(path.to_string());
+    let mut queue = vec![(path.to_string(), None)];
+    let mut next = 0;
+    while next < queue.len() {
+        let (path, importer) = queue[next].clone();
+        next += 1;
+        let source = loader(&path).map_err(|e| ImportError::Load {
+            path: path.clone(),
+            importer,
+            message: e.to_string(),
+        })?;
+        for import in imports(&path, &source)? {
+            let import = resolve_path(&path, &import);
+            if seen.insert(import.clone()) {
+                queue.push((import, Some(path.clone())));
+            }
+        }
+        if has_definitions(&source) {
+            let doc = parse_query::<String>(&source)
+                .map_err(|error| ImportError::Parse {
+                    path: path.clone(),
+                    error,
+                })?
+                .into_static();
+            result.sources.extend(
+                doc.definitions.iter().map(|_| path.clone()));
+            result.document.definitions.extend(doc.definitions);
+        }
+        result.files.push(path);
+    }
+    Ok(result)
+}
+
+/// Returns the paths of the `#import` comments as written
+fn imports(path: &str, source: &str) -> Result<Vec<String>, ImportError> {
+    let mut result = Vec::new();
+    for (idx, line) in source.lines().enumerate() {
+        let rest = match line.trim().strip_prefix("#import") {
+            Some(rest) => rest.trim(),
+            None => continue,
+        };
+        let mut chars = rest.chars();
+        let quoted = match chars.next() {
+            Some(quote @ '"') | Some(quote @ '\'') => {
+                let inner = chars.as_str();
+                inner.strip_suffix(quote).filter(|p| {
+                    !p.is_empty() && !p.contains(quote)
+                })
+            }
+            _ => None,
+        };
+        match quoted {
+            Some(import) => result.push(import.to_string()),
+            None => {
+                return Err(ImportError::InvalidImport {
+                    path: path.to_string(),
+                    line: idx + 1,
+                });
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Returns false if the source consists only of comments and whitespace
+fn has_definitions(source: &str) -> bool {
+    source.lines().any(|line| {
+        let line = line.trim_matches(|c: char| {
+            c.is_whitespace() || c == ',' || c == '\u{feff}'
+        });
+        !line.is_empty() && !line.starts_with('#')
+    })
+}
+
+/// Resolves paths starting with `./` and `../` relative to the directory of
+/// the importing file
+fn resolve_path(importer: &str, path: &str) -> String {
+    if !path.starts_with("./") && !path.starts_with("../") {
+        return path.to_string();
+    }
+    let mut parts = importer.split('/').collect::<Vec<_>>();
+    parts.pop();
+    // number of `..` that couldn't be applied to the importer's directory
+    let mut parents = 0;
+    for segment in path.split('/') {
+        match segment {
+            "." | "" => {}
+            ".." => match parts.last() {
+                Some(&last) if last != ".." && last != "." => {
+                    parts.pop();
+                }
+                _ => parents += 1,
+            },
+            _ => parts.push(segment),
+        }
+    }
+    let mut result = Vec::with_capacity(parents + parts.len());
+    result.extend(std::iter::repeat_n("..", parents));
+    result.extend(parts.iter().filter(|&&p| p != "."));
+    result.join("/")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{resolve_imports, resolve_path, ImportError};
+
+    fn load<'f>(files: &'f [(&str, &str)])
+        -> impl FnMut(&str) -> Result<String, String> + 'f
+    {
+        move |path| {
+            files.iter().find(|(name, _)| *name == path)
+                .map(|(_, source)| source.to_string())
+                .ok_or_else(|| "no such file".to_string())
+        }
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(resolve_path("a/b.graphql", "./c.graphql"), "a/c.graphql");
+        assert_eq!(resolve_path("a/b/c.graphql", "../d/./e.graphql"),
+                   "a/d/e.graphql");
+        assert_eq!(resolve_path("b.graphql", "./c.graphql"), "c.graphql");
+        assert_eq!(resolve_path("b.graphql", "../c.graphql"), "../c.graphql");
+        assert_eq!(resolve_path("../a/b.graphql", "../../c.graphql"),
+                   "../../c.graphql");
+        assert_eq!(resolve_path("/a/b.graphql", "./c.graphql"),
+                   "/a/c.graphql");
+        assert_eq!(resolve_path("a/b.graphql", "lib/c.graphql"),
+                   "lib/c.graphql");
+    }
+
+    #[test]
+    fn resolve() {
+        let files = [
+            ("main.graphql", "#import \"./lib/a.graphql\"\n\
+                              # import 'x' is a comment, not an import\n\
+                              query Q { ...A ...B }\n\
+                              #import './lib/b.graphql'"),
+            ("lib/a.graphql", "#import \"./b.graphql\"\n\
+                               fragment A on T { a }"),
+            ("lib/b.graphql", "  #import \"../main.graphql\"\n\
+                               #import \"./only_imports.graphql\"\n\
+                               fragment B on T { b } fragment C on T { c }"),
+            ("lib/only_imports.graphql", "#import \"./a.graphql\"\n\n"),
+        ];
+        let doc = resolve_imports("main.graphql", load(&files)).unwrap();
+        assert_eq!(doc.files, ["main.graphql", "lib/a.graphql",
+                               "lib/b.graphql", "lib/only_imports.graphql"]);
+        assert_eq!(doc.sources, ["main.graphql", "lib/a.graphql",
+                                 "lib/b.graphql", "lib/b.graphql"]);
+        assert_eq!(doc.document.to_string(), "\
+query Q {
+  ...A
+  ...B
+}
+
+fragment A on T {
+  a
+}
+
+fragment B on T {
+  b
+}
+
+fragment C on T {
+  c
+}
+");
+        assert_eq!(doc.source_of(3), Some("lib/b.graphql"));
+        assert_eq!(doc.source_of(4), None);
+    }
+
+    #[test]
+    fn errors() {
+        let files = [
+            ("main.graphql", "#import \"./missing.graphql\"\n{ a }"),
+            ("invalid.graphql", "{ a }\n#import missing.graphql"),
+            ("unclosed.graphql", "#import \"a.graphql\n{ a }"),
+            ("syntax.graphql", "#import \"./bad.graphql\"\n{ a }"),
+            ("bad.graphql", "fragment F on T {"),
+        ];
+        let err = resolve_imports("main.graphql", load(&files))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "cannot load \"missing.graphql\" \
+                   imported from \"main.graphql\": no such file");
+        let err = resolve_imports("other.graphql", load(&files))
+            .unwrap_err();
+        assert_eq!(err.to_string(),
+                   "cannot load \"other.graphql\": no such file");
+        assert_eq!(resolve_imports("invalid.graphql", load(&files))
+                   .unwrap_err(),
+                   ImportError::InvalidImport {
+                       path: "invalid.graphql".into(),
+                       line: 2,
+                   });
+        assert_eq!(resolve_imports("unclosed.graphql", load(&files))
+                   .unwrap_err().to_string(),
+                   "unclosed.graphql:1: invalid import, \
+                    expected `#import \"<path>\"`");
+        let err = resolve_imports("syntax.graphql", load(&files))
+            .unwrap_err();
+        assert!(matches!(err, ImportError::Parse { ref path, .. }
+                         if path == "bad.graphql"));
+        assert!(err.to_string().starts_with(
+            "bad.graphql: query parse error: Parse error at 1:18\n"));
+    }
+}
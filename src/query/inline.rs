@@ -0,0 +1,241 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::query::ast::*;
+
+
+/// Replaces fragment spreads with equivalent inline fragments
+///
+/// Every spread becomes an inline fragment with the type condition of the
+/// fragment and the directives of the spread. Fragment definitions that are
+/// no longer used are removed:
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::parse_query;
+/// use edb_graphql_parser::query::inline_fragments;
+///
+/// let doc = parse_query::<&str>("
+///     { me { ...UserFields @include(if: $full) } }
+///     fragment UserFields on User { id name }
+/// ").unwrap();
+/// assert_eq!(inline_fragments(&doc).to_string(), "\
+/// {
+///   me {
+///     ... on User @include(if: $full) {
+///       id
+///       name
+///     }
+///   }
+/// }
+/// ");
+/// ```
+///
+/// Spreads of undefined fragments, fragments that spread themselves,
+/// fragments with (experimental) arguments and fragments with directives on
+/// the definition are kept as is, along with their definitions. Directives
+/// of the definition can't be moved to an inline fragment, as they may only
+/// be allowed on fragment definitions.
+pub fn inline_fragments<'a, T>(doc: &Document<'a, T>) -> Document<'a, T>
+    where T: Text<'a> + Clone,
+{
+    let fragments = doc.definitions.iter().filter_map(|def| match *def {
+        Definition::Fragment(ref frag) => Some((frag.name.as_ref(), frag)),
+        Definition::Operation(_) => None,
+    }).collect::<BTreeMap<_, _>>();
+    let mut inliner = Inliner {
+        fragments,
+        stack: Vec::new(),
+        kept: BTreeSet::new(),
+    };
+    let mut definitions = Vec::new();
+    for def in &doc.definitions {
+        if let Definition::Operation(ref op) = *def {
+            definitions.push(Definition::Operation(Operation {
+                selection_set: inliner.selection_set(&op.selection_set),
+                .. op.clone()
+            }));
+        }
+    }
+    // kept fragments are processed until no new ones are referenced
+    let mut done = BTreeSet::new();
+    let mut kept = Vec::new();
+    loop {
+        let pending = inliner.kept.difference(&done).cloned()
+            .collect::<Vec<_>>();
+        if pending.is_empty() {
+            break;
+        }
+        for name in pending {
+            done.insert(name);
+            if let Some(frag) = inliner.fragments.get(name).copied() {
+                inliner.stack.push(name);
+                let selection_set = inliner.selection_set(&frag.selection_set);
+                inliner.stack.pop();
+                kept.push(FragmentDefinition {
+                    selection_set,
+                    .. frag.clone()
+                });
+            }
+        }
+    }
+    // fragments are kept in the original order, after the operations
+    let order = doc.definitions.iter().filter_map(|def| match *def {
+        Definition::Fragment(ref frag) => Some(frag.name.as_ref()),
+        Definition::Operation(_) => None,
+    }).collect::<Vec<_>>();
+    kept.sort_by_key(|frag| {
+        order.iter().position(|&name| name == frag.name.as_ref())
+    });
+    definitions.extend(kept.into_iter().map(Definition::Fragment));
+    Document { definitions }
+}
+
+struct Inliner<'x, 'a, T: Text<'a>> {
+    fragments: BTreeMap<&'x str, &'x FragmentDefinition<'a, T>>,
+    /// Fragments being inlined, to detect cycles
+    stack: Vec<&'x str>,
+    /// Fragments that are still spread somewhere
+    kept: BTreeSet<&'x str>,
+}
+
+impl<'x, 'a, T> Inliner<'x, 'a, T>
+    where T: Text<'a> + Clone,
+{
+    fn selection_set(&mut self, set: &'x SelectionSet<'a, T>)
+        -> SelectionSet<'a, T>
+    {
+        let items = set.items.iter().map(|item| match *item {
+            Selection::Field(ref field) => Selection::Field(Field {
+                selection_set: self.selection_set(&field.selection_set),
+                .. field.clone()
+            }),
+            Selection::InlineFragment(ref frag) => {
+                Selection::InlineFragment(InlineFragment {
+                    selection_set: self.selection_set(&frag.selection_set),
+                    .. frag.clone()
+                })
+            }
+            Selection::FragmentSpread(ref spread) => self.spread(spread),
+        }).collect();
+        SelectionSet { span: set.span, items }
+    }
+
+    fn spread(&mut self, spread: &'x FragmentSpread<'a, T>)
+        -> Selection<'a, T>
+    {
+        let name = spread.fragment_name.as_ref();
+        let frag = match self.fragments.get(name).copied() {
+            Some(frag) if spread.arguments.is_empty() &&
+                frag.variable_definitions.is_empty() &&
+                frag.directives.is_empty() &&
+                !self.stack.contains(&name)
+            => frag,
+            _ => {
+                self.kept.insert(name);
+                return Selection::FragmentSpread(spread.clone());
+            }
+        };
+        self.stack.push(name);
+        let selection_set = self.selection_set(&frag.selection_set);
+        self.stack.pop();
+        Selection::InlineFragment(InlineFragment {
+            position: spread.position,
+            on_position: Some(frag.on_position),
+            type_condition: Some(frag.type_condition.clone()),
+            directives: spread.directives.clone(),
+            selection_set,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{parse_query, ParserOptions};
+    use crate::query::parse_query_with_options;
+    use super::inline_fragments;
+
+    fn inline(query: &str) -> String {
+        inline_fragments(&parse_query::<&str>(query).unwrap()).to_string()
+    }
+
+    #[test]
+    fn nested() {
+        assert_eq!(inline("
+            query Q { ...A }
+            fragment B on T { b }
+            fragment A on Query { a { ...B @skip(if: true) } }
+            mutation { x { ...B } }
+        "), "\
+query Q {
+  ... on Query {
+    a {
+      ... on T @skip(if: true) {
+        b
+      }
+    }
+  }
+}
+
+mutation {
+  x {
+    ... on T {
+      b
+    }
+  }
+}
+");
+    }
+
+    #[test]
+    fn kept() {
+        assert_eq!(inline("
+            { ...Missing ...Cycle }
+            fragment Other on T { x }
+            fragment Cycle on T { y ...Cycle }
+        "), "\
+{
+  ...Missing
+  ... on T {
+    y
+    ...Cycle
+  }
+}
+
+fragment Cycle on T {
+  y
+  ...Cycle
+}
+");
+        assert_eq!(inline("
+            { ...A ...B }
+            fragment A on T @myDir { a }
+            fragment B on T { b }
+        "), "\
+{
+  ...A
+  ... on T {
+    b
+  }
+}
+
+fragment A on T @myDir {
+  a
+}
+");
+        let mut options = ParserOptions::default();
+        options.fragment_arguments(true);
+        let doc = parse_query_with_options::<&str>("
+            { ...A(x: 1) }
+            fragment A($x: Int) on T { f(x: $x) }
+        ", &options).unwrap();
+        assert_eq!(inline_fragments(&doc).to_string(), "\
+{
+  ...A(x: 1)
+}
+
+fragment A($x: Int) on T {
+  f(x: $x)
+}
+");
+    }
+}
@@ -0,0 +1,304 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::mem;
+
+use crate::query::ast::*;
+use crate::schema;
+use crate::schema::index::{type_name, SchemaIndex};
+
+
+/// Result of `lift_literals`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LiftedLiterals<'a, T: Text<'a>> {
+    /// Document where literals are replaced by variables
+    pub document: Document<'a, T>,
+    /// Values of the new variables, that should be sent along with the
+    /// document
+    pub values: BTreeMap<String, Value<'a, T>>,
+}
+
+/// Replaces literal values of field arguments with variables
+///
+/// Documents that only differ in argument values have the same text
+/// after lifting, so they can share cached query plans. Types of the
+/// new variables are the types of the arguments declared in the schema,
+/// arguments that are not found in the schema are left in place, as are
+/// `null` literals and values containing variables. Variables are named
+/// after the arguments, with a numeric suffix if the name is already
+/// taken.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::{parse_query, parse_schema};
+/// use edb_graphql_parser::query::lift_literals;
+///
+/// let schema = parse_schema::<&str>("
+///     type Query { user(id: ID!): User }
+///     type User { avatar(size: Int = 64): String }
+/// ").unwrap();
+/// let doc = parse_query::<String>(r#"
+///     { user(id: "1") { avatar(size: 32) } }
+/// "#).unwrap();
+/// let lifted = lift_literals(&doc, &schema);
+/// assert_eq!(lifted.document.to_string(), "\
+/// query($id: ID!, $size: Int) {
+///   user(id: $id) {
+///     avatar(size: $size)
+///   }
+/// }
+/// ");
+/// assert_eq!(lifted.values["id"].to_string(), r#""1""#);
+/// ```
+///
+/// Literals in fragments are only lifted when the document has a single
+/// operation, as the variables must be defined in every operation using
+/// the fragment. Names of the new variables are created from strings,
+/// so only text types that own the data (`String` and `Cow`) are
+/// supported.
+pub fn lift_literals<'a, 's, T, S>(doc: &Document<'a, T>,
+    schema: &schema::Document<'s, S>)
+    -> LiftedLiterals<'a, T>
+    where T: Text<'a> + Clone,
+          T::Value: From<String>,
+          S: Text<'s>,
+{
+    let mut document = doc.clone();
+    let mut lifter = Lifter {
+        index: SchemaIndex::new(schema),
+        used: BTreeSet::new(),
+        values: BTreeMap::new(),
+        variables: Vec::new(),
+    };
+    let operations = document.definitions.iter()
+        .filter(|def| matches!(def, Definition::Operation(_)))
+        .count();
+    for def in &document.definitions {
+        if let Definition::Operation(ref op) = *def {
+            lifter.used.extend(op.variable_definitions.iter()
+                .map(|var| var.name.as_ref().to_string()));
+        }
+    }
+    let mut fragment_vars = Vec::new();
+    if operations == 1 {
+        for def in &mut document.definitions {
+            if let Definition::Fragment(ref mut frag) = *def {
+                let TypeCondition::On(ref name) = frag.type_condition;
+                let name = name.as_ref().to_string();
+                lifter.selection_set(Some(&name), &mut frag.selection_set);
+            }
+        }
+        fragment_vars = mem::take(&mut lifter.variables);
+    }
+    for def in &mut document.definitions {
+        if let Definition::Operation(ref mut op) = *def {
+            let root = match op.kind {
+                OperationKind::ImplicitQuery | OperationKind::Query => {
+                    lifter.index.query_type()
+                }
+                OperationKind::Mutation => lifter.index.mutation_type(),
+                OperationKind::Subscription => {
+                    lifter.index.subscription_type()
+                }
+            }.map(String::from);
+            lifter.selection_set(root.as_deref(), &mut op.selection_set);
+            let mut variables = mem::take(&mut lifter.variables);
+            variables.append(&mut fragment_vars);
+            if !variables.is_empty() {
+                if op.kind == OperationKind::ImplicitQuery {
+                    op.kind = OperationKind::Query;
+                }
+                op.insert_variables.kind = InsertVarsKind::Normal;
+                op.variable_definitions.extend(variables);
+            }
+        }
+    }
+    LiftedLiterals { document, values: lifter.values }
+}
+
+struct Lifter<'x, 's, 'a, S: Text<'s>, T: Text<'a>> {
+    index: SchemaIndex<'x, 's, S>,
+    /// Names of the variables in the document, including new ones
+    used: BTreeSet<String>,
+    values: BTreeMap<String, Value<'a, T>>,
+    /// Definitions of the variables lifted from the current operation
+    variables: Vec<VariableDefinition<'a, T>>,
+}
+
+impl<'x, 's, 'a, S, T> Lifter<'x, 's, 'a, S, T>
+    where S: Text<'s>,
+          T: Text<'a>,
+          T::Value: From<String>,
+{
+    fn selection_set(&mut self, parent: Option<&str>,
+        set: &mut SelectionSet<'a, T>)
+    {
+        for item in &mut set.items {
+            match *item {
+                Selection::Field(ref mut field) => {
+                    let definition = parent
+                        .and_then(|name| self.index.type_by_name(name))
+                        .and_then(|entry| entry.field(field.name.as_ref()));
+                    if let Some(definition) = definition {
+                        for (name, value) in &mut field.arguments {
+                            let argument = definition.arguments.iter()
+                                .find(|a| a.name.as_ref() == name.as_ref());
+                            if let Some(argument) = argument {
+                                if is_literal(value) {
+                                    self.lift(name.as_ref(),
+                                              &argument.value_type, value);
+                                }
+                            }
+                        }
+                    }
+                    let field_type = definition
+                        .map(|f| type_name(&f.field_type).to_string());
+                    self.selection_set(field_type.as_deref(),
+                                       &mut field.selection_set);
+                }
+                Selection::InlineFragment(ref mut frag) => {
+                    let condition = frag.type_condition.as_ref()
+                        .map(|TypeCondition::On(name)| name.as_ref())
+                        .map(String::from);
+                    self.selection_set(condition.as_deref().or(parent),
+                                       &mut frag.selection_set);
+                }
+                Selection::FragmentSpread(_) => {}
+            }
+        }
+    }
+
+    fn lift(&mut self, argument: &str, var_type: &schema::Type<'s, S>,
+        value: &mut Value<'a, T>)
+    {
+        let mut name = argument.to_string();
+        let mut suffix = 1;
+        while self.used.contains(&name) {
+            suffix += 1;
+            name = format!("{}_{}", argument, suffix);
+        }
+        self.used.insert(name.clone());
+        let literal = mem::replace(value,
+            Value::Variable(name.clone().into()));
+        self.variables.push(VariableDefinition {
+            position: Default::default(),
+            name: name.clone().into(),
+            var_type: convert_type(var_type),
+            default_value: None,
+            directives: Vec::new(),
+        });
+        self.values.insert(name, literal);
+    }
+}
+
+fn is_literal<'a, T: Text<'a>>(value: &Value<'a, T>) -> bool {
+    !matches!(value, Value::Null) && !has_variables(value)
+}
+
+fn has_variables<'a, T: Text<'a>>(value: &Value<'a, T>) -> bool {
+    match *value {
+        Value::Variable(_) => true,
+        Value::List(ref items) => items.iter().any(has_variables),
+        Value::Object(ref fields) => {
+            fields.iter().any(|(_, value)| has_variables(value))
+        }
+        _ => false,
+    }
+}
+
+fn convert_type<'s, 'a, S, T>(typ: &schema::Type<'s, S>) -> Type<'a, T>
+    where S: Text<'s>,
+          T: Text<'a>,
+          T::Value: From<String>,
+{
+    match *typ {
+        schema::Type::NamedType(ref name) => {
+            Type::NamedType(name.as_ref().to_string().into())
+        }
+        schema::Type::ListType(ref inner) => {
+            Type::ListType(Box::new(convert_type(inner)))
+        }
+        schema::Type::NonNullType(ref inner) => {
+            Type::NonNullType(Box::new(convert_type(inner)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{parse_query, parse_schema};
+    use super::lift_literals;
+
+    const SCHEMA: &str = "
+        type Query { users(filter: Filter, ids: [ID!], first: Int): [User] }
+        type User { name(format: Format): String, id: ID }
+        input Filter { role: Format, name: String }
+        enum Format { SHORT, LONG }
+    ";
+
+    #[test]
+    fn values() {
+        let schema = parse_schema::<&str>(SCHEMA).unwrap();
+        let doc = parse_query::<String>("
+            query Q($first: Int) {
+                users(filter: {role: SHORT, name: null}, ids: [\"1\", \"2\"],
+                      first: $first) {
+                    name(format: LONG)
+                    ... on User { name(format: SHORT) id(x: 1) }
+                    unknown(x: 1)
+                }
+                other: users(filter: {name: $name}, first: null) { id }
+            }
+        ").unwrap();
+        let lifted = lift_literals(&doc, &schema);
+        assert_eq!(lifted.document.to_string(), "\
+query Q($first: Int, $filter: Filter, $ids: [ID!], $format: Format, \
+$format_2: Format) {
+  users(filter: $filter, ids: $ids, first: $first) {
+    name(format: $format)
+    ... on User {
+      name(format: $format_2)
+      id(x: 1)
+    }
+    unknown(x: 1)
+  }
+  other: users(filter: {name: $name}, first: null) {
+    id
+  }
+}
+");
+        let values = lifted.values.iter()
+            .map(|(name, value)| format!("{}: {}", name, value))
+            .collect::<Vec<_>>();
+        assert_eq!(values, [
+            "filter: {role: SHORT, name: null}",
+            "format: LONG",
+            "format_2: SHORT",
+            "ids: [\"1\", \"2\"]",
+        ]);
+    }
+
+    #[test]
+    fn fragments() {
+        let schema = parse_schema::<&str>(SCHEMA).unwrap();
+        let doc = parse_query::<String>("
+            { users(first: 10) { ...F } }
+            fragment F on User { name(format: LONG) }
+        ").unwrap();
+        assert_eq!(lift_literals(&doc, &schema).document.to_string(), "\
+query($first: Int, $format: Format) {
+  users(first: $first) {
+    ...F
+  }
+}
+
+fragment F on User {
+  name(format: $format)
+}
+");
+        let doc = parse_query::<String>("
+            query A { users { ...F } }
+            query B { users { ...F } }
+            fragment F on User { name(format: LONG) }
+        ").unwrap();
+        assert_eq!(lift_literals(&doc, &schema).document, doc);
+    }
+}
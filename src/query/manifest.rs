@@ -0,0 +1,213 @@
+use thiserror::Error;
+
+use crate::query::ast::*;
+use crate::query::format::apq_hash;
+use crate::query::normalize::normalize;
+use crate::query::separate::with_fragments;
+
+
+/// Operation of a persisted operations manifest
+#[derive(Debug, Clone, PartialEq)]
+pub struct PersistedOperation {
+    /// Name of the operation, `None` for an anonymous one
+    pub name: Option<String>,
+    pub kind: OperationKind,
+    /// Operation and the fragments it uses, formatted with the default
+    /// style
+    pub document: String,
+    /// Normalized document (see `normalize`), formatted with the default
+    /// style
+    pub normalized: String,
+    /// Hash of the normalized document, see `apq_hash`
+    pub hash: String,
+}
+
+/// Error building a persisted operations manifest
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ManifestError {
+    /// Operations with the same name differ, they are in the documents at
+    /// these indexes (which may be the same)
+    #[error("operation {name:?} is defined differently \
+             in documents {} and {}", documents.0, documents.1)]
+    DuplicateOperation {
+        name: String,
+        documents: (usize, usize),
+    },
+    /// Anonymous operation is not the only operation of the document
+    #[error("anonymous operation is not the only operation \
+             of document {document}")]
+    AnonymousOperation {
+        document: usize,
+    },
+}
+
+/// Returns the persisted operations of all the documents
+///
+/// Every operation is separated with the fragments it uses from its
+/// document, normalized and hashed. Operations are returned in the order of
+/// the documents. Operations with the same name are an error, unless their
+/// normalized documents are identical, then they are only listed once.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::parse_query;
+/// use edb_graphql_parser::query::{apq_hash, persisted_operations};
+///
+/// let docs = vec![
+///     parse_query::<&str>("query A { ...F } fragment F on Query { a }")
+///         .unwrap(),
+///     parse_query::<&str>("query B($y: Int, $x: Int) { b(y: $y, x: $x) }")
+///         .unwrap(),
+/// ];
+/// let operations = persisted_operations(&docs).unwrap();
+/// assert_eq!(operations.len(), 2);
+/// assert_eq!(operations[1].name.as_deref(), Some("B"));
+/// assert_eq!(operations[1].normalized,
+///     "query B($x: Int, $y: Int) {\n  b(x: $x, y: $y)\n}\n");
+/// assert_eq!(operations[1].hash, apq_hash(&operations[1].normalized));
+/// ```
+pub fn persisted_operations<'a, T>(documents: &[Document<'a, T>])
+    -> Result<Vec<PersistedOperation>, ManifestError>
+    where T: Text<'a> + Clone,
+{
+    let mut result = Vec::<PersistedOperation>::new();
+    let mut sources = Vec::new();
+    for (index, doc) in documents.iter().enumerate() {
+        let count = doc.operations().count();
+        for op in doc.operations() {
+            let name = op.name.as_ref().map(|n| n.as_ref().to_string());
+            if name.is_none() && count > 1 {
+                return Err(ManifestError::AnonymousOperation {
+                    document: index,
+                });
+            }
+            let separated = with_fragments(doc, op);
+            let document = separated.to_string();
+            let normalized = normalize(separated).to_string();
+            let existing = result.iter().position(|other| {
+                other.name.is_some() && other.name == name
+            });
+            if let Some(existing) = existing {
+                if result[existing].normalized == normalized {
+                    continue;
+                }
+                return Err(ManifestError::DuplicateOperation {
+                    name: name.unwrap_or_default(),
+                    documents: (sources[existing], index),
+                });
+            }
+            result.push(PersistedOperation {
+                name,
+                kind: op.kind.clone(),
+                document,
+                hash: apq_hash(&normalized),
+                normalized,
+            });
+            sources.push(index);
+        }
+    }
+    Ok(result)
+}
+
+/// Returns the manifest in the JSON format of Apollo persisted queries
+///
+/// Each operation has the hash as `id`, the normalized document as `body`,
+/// its `name` and `type`.
+#[cfg(feature = "serde_json")]
+pub fn manifest_json(operations: &[PersistedOperation]) -> serde_json::Value
+{
+    let operations = operations.iter().map(|op| serde_json::json!({
+        "id": op.hash,
+        "name": op.name,
+        "type": match op.kind {
+            OperationKind::ImplicitQuery | OperationKind::Query => "query",
+            OperationKind::Mutation => "mutation",
+            OperationKind::Subscription => "subscription",
+        },
+        "body": op.normalized,
+    })).collect::<Vec<_>>();
+    serde_json::json!({
+        "format": "apollo-persisted-query-manifest",
+        "version": 1,
+        "operations": operations,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse_query;
+    use super::{persisted_operations, ManifestError};
+
+    #[test]
+    fn operations() {
+        let docs = vec![
+            parse_query::<&str>("
+                query A { ...F }
+                mutation M { m }
+                fragment F on Query { a ...G }
+                fragment G on Query { g }
+                fragment Unused on Query { u }
+            ").unwrap(),
+            parse_query::<&str>("{ x }").unwrap(),
+            parse_query::<&str>("query A { ...F } \
+                                 fragment F on Query { a ...G } \
+                                 fragment G on Query { g }").unwrap(),
+        ];
+        let ops = persisted_operations(&docs).unwrap();
+        assert_eq!(ops.iter().map(|op| op.name.as_deref())
+                   .collect::<Vec<_>>(), [Some("A"), Some("M"), None]);
+        assert_eq!(ops[0].document, "\
+query A {
+  ...F
+}
+
+fragment F on Query {
+  a
+  ...G
+}
+
+fragment G on Query {
+  g
+}
+");
+        assert_eq!(ops[1].normalized, "mutation M {\n  m\n}\n");
+        assert_eq!(ops[2].hash.len(), 64);
+    }
+
+    #[test]
+    fn errors() {
+        let docs = vec![
+            parse_query::<&str>("query A { a }").unwrap(),
+            parse_query::<&str>("query A { b }").unwrap(),
+        ];
+        let err = persisted_operations(&docs).unwrap_err();
+        assert_eq!(err, ManifestError::DuplicateOperation {
+            name: "A".into(),
+            documents: (0, 1),
+        });
+        assert_eq!(err.to_string(),
+            "operation \"A\" is defined differently in documents 0 and 1");
+        let docs = vec![parse_query::<&str>("{ a } query B { b }").unwrap()];
+        assert_eq!(persisted_operations(&docs).unwrap_err(),
+            ManifestError::AnonymousOperation { document: 0 });
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn json() {
+        use super::manifest_json;
+
+        let docs = vec![parse_query::<&str>("query A { a }").unwrap()];
+        let ops = persisted_operations(&docs).unwrap();
+        assert_eq!(manifest_json(&ops), serde_json::json!({
+            "format": "apollo-persisted-query-manifest",
+            "version": 1,
+            "operations": [{
+                "id": ops[0].hash,
+                "name": "A",
+                "type": "query",
+                "body": "query A {\n  a\n}\n",
+            }],
+        }));
+    }
+}
@@ -0,0 +1,191 @@
+use std::mem;
+
+use crate::query::ast::*;
+use crate::validation::ValidationError;
+use crate::validation::rules::overlapping_fields_can_be_merged;
+use crate::validation::rules::same_arguments;
+
+
+/// Merges fields with the same response key into one and removes duplicate
+/// fragment spreads
+///
+/// Fields are merged if they have the same name, arguments and directives,
+/// their selections are merged recursively. This shrinks documents
+/// composed from the fragments of UI components, which often select the
+/// same fields:
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::parse_query;
+/// use edb_graphql_parser::query::merge_fields;
+///
+/// let doc = parse_query::<&str>("{
+///     me { id name }
+///     me { id avatar(size: 32) }
+/// }").unwrap();
+/// assert_eq!(merge_fields(&doc).unwrap().to_string(), "\
+/// {
+///   me {
+///     id
+///     name
+///     avatar(size: 32)
+///   }
+/// }
+/// ");
+/// ```
+///
+/// Returns `OverlappingFieldsCanBeMerged` errors if fields with the same
+/// response key have different names or arguments, as such document can't
+/// be executed. Same as the validation rule, fields are not compared
+/// across fragments.
+pub fn merge_fields<'a, T>(doc: &Document<'a, T>)
+    -> Result<Document<'a, T>, Vec<ValidationError>>
+    where T: Text<'a> + Clone,
+{
+    let mut errors = Vec::new();
+    overlapping_fields_can_be_merged(doc, &mut errors);
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    let mut doc = doc.clone();
+    for def in &mut doc.definitions {
+        match *def {
+            Definition::Operation(ref mut op) => merge(&mut op.selection_set),
+            Definition::Fragment(ref mut frag) => {
+                merge(&mut frag.selection_set)
+            }
+        }
+    }
+    Ok(doc)
+}
+
+fn merge<'a, T>(set: &mut SelectionSet<'a, T>)
+    where T: Text<'a> + Clone,
+{
+    let mut items = Vec::<Selection<'a, T>>::with_capacity(set.items.len());
+    for item in mem::take(&mut set.items) {
+        match item {
+            Selection::Field(fld) => {
+                let existing = items.iter_mut().find_map(|item| match *item {
+                    Selection::Field(ref mut other) if same_field(other, &fld)
+                    => Some(other),
+                    _ => None,
+                });
+                match existing {
+                    Some(other) => other.selection_set.items
+                        .extend(fld.selection_set.items),
+                    None => items.push(Selection::Field(fld)),
+                }
+            }
+            Selection::FragmentSpread(spread) => {
+                let duplicate = items.iter().any(|item| match *item {
+                    Selection::FragmentSpread(ref other) => {
+                        other.fragment_name == spread.fragment_name &&
+                        same_arguments(&other.arguments, &spread.arguments) &&
+                        same_directives(&other.directives, &spread.directives)
+                    }
+                    _ => false,
+                });
+                if !duplicate {
+                    items.push(Selection::FragmentSpread(spread));
+                }
+            }
+            frag @ Selection::InlineFragment(_) => items.push(frag),
+        }
+    }
+    for item in &mut items {
+        match *item {
+            Selection::Field(ref mut fld) => merge(&mut fld.selection_set),
+            Selection::InlineFragment(ref mut frag) => {
+                merge(&mut frag.selection_set)
+            }
+            Selection::FragmentSpread(_) => {}
+        }
+    }
+    set.items = items;
+}
+
+fn same_field<'a, T>(a: &Field<'a, T>, b: &Field<'a, T>) -> bool
+    where T: Text<'a>,
+{
+    a.alias.as_ref().unwrap_or(&a.name) == b.alias.as_ref().unwrap_or(&b.name)
+        && a.name == b.name
+        && a.nullability == b.nullability
+        && same_arguments(&a.arguments, &b.arguments)
+        && same_directives(&a.directives, &b.directives)
+}
+
+fn same_directives<'a, T>(a: &[Directive<'a, T>], b: &[Directive<'a, T>])
+    -> bool
+    where T: Text<'a>,
+{
+    a.len() == b.len() &&
+        a.iter().zip(b).all(|(a, b)| a.to_string() == b.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse_query;
+    use super::merge_fields;
+
+    fn merge(query: &str) -> String {
+        merge_fields(&parse_query::<&str>(query).unwrap()).unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn merged() {
+        assert_eq!(merge("
+            query Q {
+                a: user(id: 1, x: 2) { id friends { id } ...F }
+                a: user(x: 2, id: 1) { friends { name } ...F @skip(if: $s) }
+                user(id: 1) { ...F }
+                a: user(id: 1, x: 2) @include(if: $i) { id }
+                ... on Query { a: user(id: 2, x: 2) { id } }
+            }
+        "), "\
+query Q {
+  a: user(id: 1, x: 2) {
+    id
+    friends {
+      id
+      name
+    }
+    ...F
+    ...F @skip(if: $s)
+  }
+  user(id: 1) {
+    ...F
+  }
+  a: user(id: 1, x: 2) @include(if: $i) {
+    id
+  }
+  ... on Query {
+    a: user(id: 2, x: 2) {
+      id
+    }
+  }
+}
+");
+    }
+
+    #[test]
+    fn conflicts() {
+        let doc = parse_query::<&str>("{
+            a: b
+            a: c
+            x { y(z: 1) }
+            x { y(z: 2) }
+        }").unwrap();
+        let errors = merge_fields(&doc).unwrap_err().iter()
+            .map(|e| e.to_string()).collect::<Vec<_>>();
+        assert_eq!(errors, [
+            "Fields \"a\" conflict because \"b\" and \"c\" are different \
+             fields. Use different aliases on the fields to fetch both if \
+             this was intentional. at 2:13, 3:13",
+            "Fields \"y\" conflict because they have differing arguments. \
+             Use different aliases on the fields to fetch both if this was \
+             intentional. at 4:17, 5:17",
+        ]);
+    }
+}
@@ -1,11 +1,95 @@
 //! Query language AST and parsing utilities
 //!
+#[cfg(feature = "apq")] mod allowlist;
 mod ast;
+mod builder;
+mod bundle;
+mod completion;
+mod diff;
+mod duplicates;
 mod error;
+mod extract;
 mod format;
+mod fragments;
 mod grammar;
+mod hints;
+mod imports;
+mod inline;
+#[cfg(feature = "schema")] mod lift;
+#[cfg(feature = "apq")] mod manifest;
+mod merge;
+mod naming;
+mod nodes;
+mod normalize;
+mod pagination;
+mod project;
+mod prune;
+mod redact;
+mod referenced;
+mod separate;
+mod signature;
+#[cfg(feature = "schema")] mod simplify;
+mod structural;
+mod tolerant;
+mod watermark;
 
 
-pub use self::grammar::parse_query;
-pub use self::error::ParseError;
+pub use self::grammar::{parse_query, parse_query_with_options};
+pub use self::grammar::parse_query_with_offset;
+pub use self::grammar::{parse_query_iter, parse_query_iter_with_options};
+pub use self::grammar::QueryDefinitions;
+pub use self::builder::{DocumentBuilder, OperationBuilder, FieldBuilder};
+pub use self::builder::{FragmentDefinitionBuilder, VariableDefinitionBuilder};
+pub use self::builder::{FragmentSpreadBuilder, InlineFragmentBuilder};
+pub use self::builder::DirectiveBuilder;
+pub use self::bundle::{merge_documents, merge_documents_renaming};
+pub use self::bundle::{DocumentMergeError, MergedDocuments, Renamed};
+pub use self::completion::{completion_context, CompletionContext};
+pub use self::completion::{CompletionKind, ArgumentOwner, PathItem};
+pub use self::diff::{diff, Change, ChangeKind, Element};
+pub use self::grammar::{parse_field_set, parse_value, parse_type};
+pub use self::duplicates::{find_duplicates, DuplicateReport, DuplicateGroup};
+pub use self::duplicates::{OperationRef, Similarity};
+pub use self::error::{ParseError, OperationError};
+pub use self::extract::{extract_fragment, ExtractError};
+pub use self::format::format_range;
+pub use self::fragments::FragmentGraph;
+pub use self::hints::{ExecutionHints, HintDirectives, HintError};
+pub use self::imports::{resolve_imports, ImportedDocument, ImportError};
+pub use self::inline::inline_fragments;
+#[cfg(feature = "schema")]
+pub use self::lift::{lift_literals, LiftedLiterals};
+pub use self::merge::merge_fields;
+pub use self::naming::{name_operations, NamedOperations, GeneratedName};
+pub use self::naming::OperationNaming;
+pub use self::nodes::{NodeMap, NodeId, NodeRef, Cursor};
+pub use self::normalize::normalize;
+pub use self::pagination::{PaginationPolicy, PaginationChange};
+pub use self::project::Projection;
+pub use self::prune::prune_conditionals;
+pub use self::redact::{Redaction, REDACTED};
+pub use self::referenced::{referenced_types, operations_referencing};
+pub use self::referenced::{ReferencedType, ReferencedIn, TypeUsage};
+pub use self::separate::separate_operation;
+#[cfg(feature = "schema")]
+pub(crate) use self::separate::used_fragments;
+pub use self::signature::{OperationSignature, usage_reporting_signature};
+#[cfg(feature = "schema")]
+pub use self::simplify::simplify_fragments;
+pub use self::structural::{Structural, IgnorePositions};
+pub use self::tolerant::{parse_query_tolerant, TolerantDocument};
+pub use self::tolerant::{TolerantDefinition, ErrorNode};
+pub use self::watermark::Watermark;
 pub use self::ast::*;
+#[cfg(feature = "serde_json")]
+pub use crate::json::ValueToJsonError;
+#[cfg(feature = "apq")]
+pub use self::allowlist::Allowlist;
+#[cfg(feature = "apq")]
+pub use self::format::{apq_hash, apq_hash_normalized};
+#[cfg(feature = "apq")]
+pub use self::manifest::{persisted_operations, PersistedOperation};
+#[cfg(feature = "apq")]
+pub use self::manifest::ManifestError;
+#[cfg(all(feature = "apq", feature = "serde_json"))]
+pub use self::manifest::manifest_json;
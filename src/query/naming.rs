@@ -0,0 +1,205 @@
+use std::collections::BTreeSet;
+
+use crate::name::to_pascal_case;
+use crate::query::ast::*;
+use crate::query::error::OperationError;
+
+
+/// How `name_operations` makes up the names of anonymous operations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperationNaming {
+    /// Names of the root fields and fragments in `PascalCase` followed by
+    /// the kind of the operation, e.g. `UserPostsQuery` for
+    /// `{ user { id } posts }`
+    RootFields,
+    /// Kind of the operation and a hash of its text, e.g.
+    /// `Query_1a2b3c4d`, so the name stays the same while the operation
+    /// does
+    Hash,
+}
+
+/// Name given to an anonymous operation by `name_operations`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeneratedName {
+    /// Index of the operation in the definitions of the document
+    pub index: usize,
+    pub name: String,
+}
+
+/// Document with every operation named, see `name_operations`
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamedOperations<'a, T: Text<'a>> {
+    pub document: Document<'a, T>,
+    /// Names of the previously anonymous operations, in order
+    pub names: Vec<GeneratedName>,
+}
+
+impl<'a, T> NamedOperations<'a, T>
+    where T: Text<'a>,
+{
+    /// Returns the `operationName` to execute the named document with,
+    /// given the one of the request for the original document
+    ///
+    /// Requests without `operationName` get the name of the only
+    /// operation, which may be generated. Errors are the same as of
+    /// `Document::operation`.
+    pub fn operation_name(&self, requested: Option<&str>)
+        -> Result<&str, OperationError>
+    {
+        let op = self.document.operation(requested)?;
+        Ok(op.name.as_ref().expect("all operations are named").as_ref())
+    }
+}
+
+/// Gives deterministic names to the anonymous operations of the document
+///
+/// Names are made unique among the operations by appending `_2` (or the
+/// next number making the name unique). Shorthand queries (`{ ... }`) are
+/// turned into `query Name { ... }`. The hash of `OperationNaming::Hash`
+/// is a 32-bit FNV-1a of the operation formatted with the default style,
+/// so it doesn't depend on formatting of the source. New names are created
+/// from strings, so only text types that own the data (`String` and `Cow`)
+/// are supported.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::parse_query;
+/// use edb_graphql_parser::query::{name_operations, OperationNaming};
+///
+/// let doc = parse_query::<String>("{ user { id } recent_posts }").unwrap();
+/// let named = name_operations(doc, OperationNaming::RootFields);
+/// assert_eq!(named.names[0].name, "UserRecentPostsQuery");
+/// assert_eq!(named.operation_name(None), Ok("UserRecentPostsQuery"));
+/// assert_eq!(named.document.to_string(), "\
+/// query UserRecentPostsQuery {
+///   user {
+///     id
+///   }
+///   recent_posts
+/// }
+/// ");
+/// ```
+pub fn name_operations<'a, T>(mut doc: Document<'a, T>,
+    naming: OperationNaming)
+    -> NamedOperations<'a, T>
+    where T: Text<'a>,
+          T::Value: From<String>,
+{
+    let mut taken = doc.operations()
+        .filter_map(|op| op.name.as_ref())
+        .map(|name| name.as_ref().to_string())
+        .collect::<BTreeSet<_>>();
+    let mut names = Vec::new();
+    for (index, def) in doc.definitions.iter_mut().enumerate() {
+        let op = match *def {
+            Definition::Operation(ref mut op) if op.name.is_none() => op,
+            _ => continue,
+        };
+        if op.kind == OperationKind::ImplicitQuery {
+            op.kind = OperationKind::Query;
+        }
+        let kind = match op.kind {
+            OperationKind::ImplicitQuery | OperationKind::Query => "Query",
+            OperationKind::Mutation => "Mutation",
+            OperationKind::Subscription => "Subscription",
+        };
+        let base = match naming {
+            OperationNaming::RootFields => {
+                let mut parts = Vec::new();
+                root_names(&op.selection_set, &mut parts);
+                parts.concat() + kind
+            }
+            OperationNaming::Hash => {
+                format!("{}_{:08x}", kind, fnv1a(&op.to_string()))
+            }
+        };
+        let mut name = base.clone();
+        let mut suffix = 2;
+        while !taken.insert(name.clone()) {
+            name = format!("{}_{}", base, suffix);
+            suffix += 1;
+        }
+        op.name = Some(name.clone().into());
+        names.push(GeneratedName { index, name });
+    }
+    NamedOperations { document: doc, names }
+}
+
+/// Collects names of the fields and fragments of the selection set, and
+/// of the inline fragments in it, in `PascalCase` without duplicates
+fn root_names<'a, T>(set: &SelectionSet<'a, T>, parts: &mut Vec<String>)
+    where T: Text<'a>,
+{
+    for item in &set.items {
+        let name = match *item {
+            Selection::Field(ref field) => field.name.as_ref(),
+            Selection::FragmentSpread(ref spread) => {
+                spread.fragment_name.as_ref()
+            }
+            Selection::InlineFragment(ref frag) => {
+                root_names(&frag.selection_set, parts);
+                continue;
+            }
+        };
+        let part = to_pascal_case(name);
+        if !parts.contains(&part) {
+            parts.push(part);
+        }
+    }
+}
+
+fn fnv1a(text: &str) -> u32 {
+    text.bytes().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse_query;
+    use crate::query::OperationError;
+    use super::{name_operations, GeneratedName, OperationNaming};
+
+    #[test]
+    fn root_fields() {
+        let doc = parse_query::<String>("
+            query UserQuery { a }
+            { user { id } ... on Query { user __typename } }
+            mutation { ...create_user }
+            subscription { ... @skip(if: false) { events } }
+            query { user }
+        ").unwrap();
+        let named = name_operations(doc, OperationNaming::RootFields);
+        assert_eq!(named.names, [
+            GeneratedName { index: 1, name: "UserTypenameQuery".into() },
+            GeneratedName { index: 2, name: "CreateUserMutation".into() },
+            GeneratedName { index: 3, name: "EventsSubscription".into() },
+            GeneratedName { index: 4, name: "UserQuery_2".into() },
+        ]);
+        assert_eq!(named.operation_name(Some("UserQuery_2")),
+                   Ok("UserQuery_2"));
+        assert_eq!(named.operation_name(None),
+                   Err(OperationError::NameRequired));
+        assert!(named.document.to_string()
+            .contains("\n\nquery UserTypenameQuery {\n"));
+    }
+
+    #[test]
+    fn hash() {
+        let name = |source| {
+            let doc = parse_query::<String>(source).unwrap();
+            name_operations(doc, OperationNaming::Hash).names[0].name.clone()
+        };
+        let hashed = name("{ a(x: 1) }");
+        assert!(hashed.starts_with("Query_"));
+        assert_eq!(hashed.len(), "Query_".len() + 8);
+        assert_eq!(name("query{a(x:1)}"), hashed);
+        assert_ne!(name("{ a(x: 2) }"), hashed);
+        assert!(name("mutation { a(x: 1) }").starts_with("Mutation_"));
+
+        let doc = parse_query::<String>("query B { b }").unwrap();
+        let named = name_operations(doc, OperationNaming::Hash);
+        assert!(named.names.is_empty());
+        assert_eq!(named.operation_name(None), Ok("B"));
+    }
+}
@@ -0,0 +1,470 @@
+use std::collections::BTreeMap;
+
+use crate::common::{Directive, Text};
+use crate::query::ast::*;
+
+
+/// Identifier of a node of the executable document, see `NodeMap`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(u32);
+
+/// Reference to a node of the executable document
+#[derive(Debug)]
+pub enum NodeRef<'x, 'a, T>
+    where T: Text<'a>
+{
+    Operation(&'x Operation<'a, T>),
+    Fragment(&'x FragmentDefinition<'a, T>),
+    VariableDefinition(&'x VariableDefinition<'a, T>),
+    Field(&'x Field<'a, T>),
+    FragmentSpread(&'x FragmentSpread<'a, T>),
+    InlineFragment(&'x InlineFragment<'a, T>),
+    Directive(&'x Directive<'a, T>),
+}
+
+/// Identifiers of all the nodes of the document and their parents
+///
+/// Identifiers are numbers of the nodes in the order of the source (the
+/// order they are parsed in): each node gets its number before its
+/// variables, directives and selections. So the identifiers are the same
+/// for every map built from the same document, and nodes of the parts of
+/// the document that precede an edit keep identifiers after reparsing.
+/// `cursor` allows walking the tree from any node in every direction.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::parse_query;
+/// use edb_graphql_parser::query::{NodeMap, NodeRef};
+///
+/// let doc = parse_query::<&str>("query Q { user { id } }").unwrap();
+/// let nodes = NodeMap::new(&doc);
+/// let id = nodes.ids().last().unwrap();
+/// let field = match nodes.get(id) {
+///     Some(NodeRef::Field(field)) => field,
+///     _ => unreachable!(),
+/// };
+/// assert_eq!(field.name, "id");
+/// let parent = nodes.parent(id).unwrap();
+/// assert!(matches!(nodes.get(parent), Some(NodeRef::Field(f))
+///                  if f.name == "user"));
+/// assert_eq!(nodes.id(field), Some(id));
+/// ```
+#[derive(Debug)]
+pub struct NodeMap<'x, 'a, T>
+    where T: Text<'a>
+{
+    nodes: Vec<(NodeRef<'x, 'a, T>, Option<NodeId>)>,
+    /// Kind and address of the node
+    ids: BTreeMap<(u8, usize), NodeId>,
+    /// Children of each node, in the source order
+    children: Vec<Vec<NodeId>>,
+    /// Definitions of the document
+    roots: Vec<NodeId>,
+}
+
+/// Position in the `NodeMap` which can be moved to the parent, children
+/// and siblings of the node
+///
+/// Siblings of a definition are the other definitions of the document.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::parse_query;
+/// use edb_graphql_parser::query::{NodeMap, NodeRef};
+///
+/// let doc = parse_query::<&str>("
+///     {
+///         user @cached { id @export friends { name @export } }
+///         other { name @export }
+///     }
+/// ").unwrap();
+/// let nodes = NodeMap::new(&doc);
+/// // find `@export` directives which are not under a `@cached` field
+/// let uncached = nodes.ids().filter_map(|id| nodes.cursor(id))
+///     .filter(|c| matches!(c.node(), NodeRef::Directive(d)
+///                          if d.name == "export"))
+///     .filter(|c| !c.ancestors().any(|a| a.children().any(|child| {
+///         matches!(child.node(), NodeRef::Directive(d) if d.name == "cached")
+///     })))
+///     .count();
+/// assert_eq!(uncached, 1);
+///
+/// let user = nodes.cursor(nodes.roots()[0]).unwrap()
+///     .children().next().unwrap();
+/// let id = user.children().nth(1).unwrap();
+/// assert!(matches!(id.node(), NodeRef::Field(f) if f.name == "id"));
+/// let friends = id.next_sibling().unwrap();
+/// assert_eq!(friends.prev_sibling(), Some(id));
+/// assert_eq!(friends.parent(), Some(user));
+/// assert!(friends.next_sibling().is_none());
+/// ```
+#[derive(Debug)]
+pub struct Cursor<'m, 'x, 'a, T>
+    where T: Text<'a>
+{
+    map: &'m NodeMap<'x, 'a, T>,
+    id: NodeId,
+}
+
+impl<'x, 'a, T> Clone for NodeRef<'x, 'a, T>
+    where T: Text<'a>
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'x, 'a, T> Copy for NodeRef<'x, 'a, T> where T: Text<'a> {}
+
+impl<'x, 'a, T> NodeRef<'x, 'a, T>
+    where T: Text<'a>
+{
+    fn key(&self) -> (u8, usize) {
+        use self::NodeRef::*;
+        match *self {
+            Operation(n) => (0, n as *const _ as usize),
+            Fragment(n) => (1, n as *const _ as usize),
+            VariableDefinition(n) => (2, n as *const _ as usize),
+            Field(n) => (3, n as *const _ as usize),
+            FragmentSpread(n) => (4, n as *const _ as usize),
+            InlineFragment(n) => (5, n as *const _ as usize),
+            Directive(n) => (6, n as *const _ as usize),
+        }
+    }
+}
+
+macro_rules! node_from {
+    ($($variant:ident: $typ:ident,)*) => {$(
+        impl<'x, 'a, T> From<&'x $typ<'a, T>> for NodeRef<'x, 'a, T>
+            where T: Text<'a>
+        {
+            fn from(node: &'x $typ<'a, T>) -> NodeRef<'x, 'a, T> {
+                NodeRef::$variant(node)
+            }
+        }
+    )*}
+}
+
+node_from! {
+    Operation: Operation,
+    Fragment: FragmentDefinition,
+    VariableDefinition: VariableDefinition,
+    Field: Field,
+    FragmentSpread: FragmentSpread,
+    InlineFragment: InlineFragment,
+    Directive: Directive,
+}
+
+impl<'x, 'a, T> NodeMap<'x, 'a, T>
+    where T: Text<'a>
+{
+    pub fn new(doc: &'x Document<'a, T>) -> NodeMap<'x, 'a, T> {
+        let mut map = NodeMap {
+            nodes: Vec::new(),
+            ids: BTreeMap::new(),
+            children: Vec::new(),
+            roots: Vec::new(),
+        };
+        for def in &doc.definitions {
+            match *def {
+                Definition::Operation(ref op) => {
+                    let id = map.add(NodeRef::Operation(op), None);
+                    for var in &op.variable_definitions {
+                        let var_id = map.add(var.into(), Some(id));
+                        map.directives(&var.directives, var_id);
+                    }
+                    map.directives(&op.directives, id);
+                    map.selection_set(&op.selection_set, id);
+                }
+                Definition::Fragment(ref frag) => {
+                    let id = map.add(NodeRef::Fragment(frag), None);
+                    for var in &frag.variable_definitions {
+                        let var_id = map.add(var.into(), Some(id));
+                        map.directives(&var.directives, var_id);
+                    }
+                    map.directives(&frag.directives, id);
+                    map.selection_set(&frag.selection_set, id);
+                }
+            }
+        }
+        map
+    }
+
+    fn add(&mut self, node: NodeRef<'x, 'a, T>, parent: Option<NodeId>)
+        -> NodeId
+    {
+        let id = NodeId(self.nodes.len() as u32);
+        self.ids.insert(node.key(), id);
+        self.nodes.push((node, parent));
+        self.children.push(Vec::new());
+        match parent {
+            Some(parent) => self.children[parent.index()].push(id),
+            None => self.roots.push(id),
+        }
+        id
+    }
+
+    fn directives(&mut self, directives: &'x [Directive<'a, T>],
+        parent: NodeId)
+    {
+        for dir in directives {
+            self.add(dir.into(), Some(parent));
+        }
+    }
+
+    fn selection_set(&mut self, set: &'x SelectionSet<'a, T>,
+        parent: NodeId)
+    {
+        for item in &set.items {
+            match *item {
+                Selection::Field(ref field) => {
+                    let id = self.add(field.into(), Some(parent));
+                    self.directives(&field.directives, id);
+                    self.selection_set(&field.selection_set, id);
+                }
+                Selection::FragmentSpread(ref spread) => {
+                    let id = self.add(spread.into(), Some(parent));
+                    self.directives(&spread.directives, id);
+                }
+                Selection::InlineFragment(ref frag) => {
+                    let id = self.add(frag.into(), Some(parent));
+                    self.directives(&frag.directives, id);
+                    self.selection_set(&frag.selection_set, id);
+                }
+            }
+        }
+    }
+
+    /// Returns the node by identifier
+    pub fn get(&self, id: NodeId) -> Option<NodeRef<'x, 'a, T>> {
+        self.nodes.get(id.0 as usize).map(|&(node, _)| node)
+    }
+
+    /// Returns the identifier of the node of this document
+    pub fn id<N>(&self, node: N) -> Option<NodeId>
+        where N: Into<NodeRef<'x, 'a, T>>,
+    {
+        self.ids.get(&node.into().key()).cloned()
+    }
+
+    /// Returns the parent: a definition, a field or an inline fragment
+    /// containing the selection, or the node having the directive or the
+    /// variable
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes.get(id.0 as usize).and_then(|&(_, parent)| parent)
+    }
+
+    /// Returns the parent of the node of this document
+    pub fn parent_of<N>(&self, node: N) -> Option<NodeRef<'x, 'a, T>>
+        where N: Into<NodeRef<'x, 'a, T>>,
+    {
+        self.id(node).and_then(|id| self.parent(id))
+            .and_then(|id| self.get(id))
+    }
+
+    /// Returns the children of the node in the source order: variables,
+    /// directives and selections
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        self.children.get(id.index()).map(|c| &c[..]).unwrap_or(&[])
+    }
+
+    /// Returns identifiers of the definitions of the document
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    /// Returns the cursor pointing to the node
+    pub fn cursor(&self, id: NodeId) -> Option<Cursor<'_, 'x, 'a, T>> {
+        if id.index() < self.nodes.len() {
+            Some(Cursor { map: self, id })
+        } else {
+            None
+        }
+    }
+
+    /// Returns identifiers of all the nodes in the source order
+    pub fn ids(&self) -> impl Iterator<Item=NodeId> {
+        (0..self.nodes.len() as u32).map(NodeId)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+impl<'m, 'x, 'a, T> Clone for Cursor<'m, 'x, 'a, T>
+    where T: Text<'a>
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'m, 'x, 'a, T> Copy for Cursor<'m, 'x, 'a, T> where T: Text<'a> {}
+
+impl<'m, 'x, 'a, T> PartialEq for Cursor<'m, 'x, 'a, T>
+    where T: Text<'a>
+{
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.map, other.map) && self.id == other.id
+    }
+}
+
+impl<'m, 'x, 'a, T> Cursor<'m, 'x, 'a, T>
+    where T: Text<'a>
+{
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
+    pub fn node(&self) -> NodeRef<'x, 'a, T> {
+        self.map.nodes[self.id.index()].0
+    }
+
+    /// Moves to the parent, see `NodeMap::parent`
+    pub fn parent(&self) -> Option<Self> {
+        self.map.parent(self.id).map(|id| self.with_id(id))
+    }
+
+    /// Returns the parent, its parent and so on up to the definition
+    pub fn ancestors(&self) -> impl Iterator<Item=Self> {
+        std::iter::successors(self.parent(), |c| c.parent())
+    }
+
+    /// Returns the children in the source order, see `NodeMap::children`
+    pub fn children(&self) -> impl Iterator<Item=Self> {
+        let cursor = *self;
+        self.map.children(self.id).iter().map(move |&id| cursor.with_id(id))
+    }
+
+    pub fn next_sibling(&self) -> Option<Self> {
+        let (siblings, idx) = self.siblings();
+        siblings.get(idx + 1).map(|&id| self.with_id(id))
+    }
+
+    pub fn prev_sibling(&self) -> Option<Self> {
+        let (siblings, idx) = self.siblings();
+        idx.checked_sub(1).map(|idx| self.with_id(siblings[idx]))
+    }
+
+    /// Returns the siblings including this node and its index among them
+    fn siblings(&self) -> (&'m [NodeId], usize) {
+        let map = self.map;
+        let siblings = match map.parent(self.id) {
+            Some(parent) => map.children(parent),
+            None => map.roots(),
+        };
+        // children are added in the order of identifiers
+        let idx = siblings.binary_search(&self.id)
+            .expect("node is a child of its parent");
+        (siblings, idx)
+    }
+
+    fn with_id(&self, id: NodeId) -> Self {
+        Cursor { map: self.map, id }
+    }
+}
+
+impl NodeId {
+    /// Returns the number of the node in the source order
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse_query;
+    use crate::query::Field;
+    use crate::visit::Visit;
+    use super::{Cursor, NodeId, NodeMap, NodeRef};
+
+    fn describe<'a>(node: NodeRef<'_, 'a, &'a str>) -> String {
+        match node {
+            NodeRef::Operation(op) => format!("query {}", op.name.unwrap()),
+            NodeRef::Fragment(f) => format!("fragment {}", f.name),
+            NodeRef::VariableDefinition(v) => format!("${}", v.name),
+            NodeRef::Field(f) => f.name.to_string(),
+            NodeRef::FragmentSpread(f) => format!("...{}", f.fragment_name),
+            NodeRef::InlineFragment(_) => "...".to_string(),
+            NodeRef::Directive(d) => format!("@{}", d.name),
+        }
+    }
+
+    #[test]
+    fn parents() {
+        let source = "
+            query Q($a: Int @v) @op { a { b @skip(if: $a) ... @x { c } } }
+            fragment F on T { ...G }
+        ";
+        let doc = parse_query::<&str>(source).unwrap();
+        let nodes = NodeMap::new(&doc);
+        let tree = nodes.ids().map(|id| {
+            let parent = nodes.parent(id)
+                .map(|p| describe(nodes.get(p).unwrap()));
+            (describe(nodes.get(id).unwrap()), parent)
+        }).collect::<Vec<_>>();
+        let s = |v: &str| Some(v.to_string());
+        assert_eq!(tree, vec![
+            ("query Q".into(), None),
+            ("$a".into(), s("query Q")),
+            ("@v".into(), s("$a")),
+            ("@op".into(), s("query Q")),
+            ("a".into(), s("query Q")),
+            ("b".into(), s("a")),
+            ("@skip".into(), s("b")),
+            ("...".into(), s("a")),
+            ("@x".into(), s("...")),
+            ("c".into(), s("...")),
+            ("fragment F".into(), None),
+            ("...G".into(), s("fragment F")),
+        ]);
+
+        // identifiers don't depend on the instance of the document
+        let other = parse_query::<&str>(source).unwrap();
+        let other_nodes = NodeMap::new(&other);
+        let field = other.visit::<Field<_>>().find(|f| f.name == "c");
+        let id = other_nodes.id(field.unwrap()).unwrap();
+        assert_eq!(id.index(), 9);
+        assert_eq!(describe(nodes.get(id).unwrap()), "c");
+        assert_eq!(nodes.id(field.unwrap()), None);
+    }
+
+    fn names<'a, I>(cursors: I) -> Vec<String>
+        where I: Iterator<Item=Cursor<'a, 'a, 'a, &'a str>>,
+    {
+        cursors.map(|c| describe(c.node())).collect()
+    }
+
+    #[test]
+    fn cursor() {
+        let doc = parse_query::<&str>("
+            query Q($a: Int @v) @op { a { b @skip(if: $a) ... @x { c } } }
+            fragment F on T { ...G }
+        ").unwrap();
+        let nodes = NodeMap::new(&doc);
+        assert_eq!(nodes.roots().len(), 2);
+        let query = nodes.cursor(nodes.roots()[0]).unwrap();
+        assert!(query.parent().is_none());
+        assert!(query.prev_sibling().is_none());
+        let fragment = query.next_sibling().unwrap();
+        assert_eq!(describe(fragment.node()), "fragment F");
+        assert!(fragment.next_sibling().is_none());
+        assert_eq!(names(query.children()), ["$a", "@op", "a"]);
+        let c = nodes.ids().filter_map(|id| nodes.cursor(id))
+            .find(|c| describe(c.node()) == "c").unwrap();
+        assert_eq!(names(c.ancestors()), ["...", "a", "query Q"]);
+        assert!(c.children().next().is_none());
+        let inline = c.parent().unwrap();
+        assert_eq!(names(inline.children()), ["@x", "c"]);
+        assert_eq!(describe(inline.prev_sibling().unwrap().node()), "b");
+        assert_eq!(inline.parent().unwrap().id(), nodes.parent(inline.id())
+                   .unwrap());
+        assert!(nodes.cursor(NodeId(100)).is_none());
+        assert!(nodes.children(NodeId(100)).is_empty());
+    }
+}
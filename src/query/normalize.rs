@@ -0,0 +1,195 @@
+use crate::common::{Directive, Value};
+use crate::query::ast::*;
+
+
+/// Returns a canonical form of the document
+///
+/// Arguments of fields, fragment spreads and directives (including the ones
+/// on variable definitions), variable definitions of operations and
+/// fragments and fields of input objects are sorted by name. Anonymous
+/// queries without variables and directives are converted to the shorthand
+/// form.
+///
+/// Formatting the result drops all ignored tokens of the source, so
+/// documents that differ only in formatting and in the order of the
+/// arguments produce the same text. The canonical text is the one printed
+/// by `to_string()`, indented with the default style: it is the input of
+/// `apq_hash_normalized`, the allowlist and the persisted operations
+/// manifest, so changing it would change their hashes. Print with
+/// `Style::minified` for the single-line form.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::{parse_query, Style};
+/// use edb_graphql_parser::query::normalize;
+///
+/// let a = normalize(parse_query::<&str>("{a(y: 1, x: 2)}").unwrap());
+/// let b = normalize(parse_query::<&str>("query { a(x: 2 y: 1) }").unwrap());
+/// assert_eq!(a.to_string(), "{\n  a(x: 2, y: 1)\n}\n");
+/// assert_eq!(a.to_string(), b.to_string());
+/// assert_eq!(a.format(Style::default().minified(true)), "{a(x:2 y:1)}");
+/// ```
+///
+/// Order of selections, directives and definitions is significant and is
+/// kept intact.
+pub fn normalize<'a, T>(mut doc: Document<'a, T>) -> Document<'a, T>
+    where T: Text<'a>,
+{
+    for def in &mut doc.definitions {
+        match *def {
+            Definition::Operation(ref mut op) => operation(op),
+            Definition::Fragment(ref mut frag) => {
+                variable_definitions(&mut frag.variable_definitions);
+                directives(&mut frag.directives);
+                selection_set(&mut frag.selection_set);
+            }
+        }
+    }
+    doc
+}
+
+fn operation<'a, T>(op: &mut Operation<'a, T>)
+    where T: Text<'a>,
+{
+    if op.kind == OperationKind::Query && op.name.is_none() &&
+        op.variable_definitions.is_empty() && op.directives.is_empty()
+    {
+        op.kind = OperationKind::ImplicitQuery;
+    }
+    variable_definitions(&mut op.variable_definitions);
+    directives(&mut op.directives);
+    selection_set(&mut op.selection_set);
+}
+
+fn variable_definitions<'a, T>(vars: &mut [VariableDefinition<'a, T>])
+    where T: Text<'a>,
+{
+    vars.sort_by(|a, b| a.name.cmp(&b.name));
+    for var in vars {
+        if let Some(ref mut default) = var.default_value {
+            value(&mut default.value);
+        }
+        directives(&mut var.directives);
+    }
+}
+
+fn arguments<'a, T>(args: &mut [(T::Value, Value<'a, T>)])
+    where T: Text<'a>,
+{
+    args.sort_by(|a, b| a.0.cmp(&b.0));
+    for (_, val) in args {
+        value(val);
+    }
+}
+
+fn selection_set<'a, T>(set: &mut SelectionSet<'a, T>)
+    where T: Text<'a>,
+{
+    for item in &mut set.items {
+        match *item {
+            Selection::Field(ref mut field) => {
+                arguments(&mut field.arguments);
+                directives(&mut field.directives);
+                selection_set(&mut field.selection_set);
+            }
+            Selection::FragmentSpread(ref mut spread) => {
+                arguments(&mut spread.arguments);
+                directives(&mut spread.directives);
+            }
+            Selection::InlineFragment(ref mut frag) => {
+                directives(&mut frag.directives);
+                selection_set(&mut frag.selection_set);
+            }
+        }
+    }
+}
+
+pub(crate) fn directives<'a, T>(dirs: &mut [Directive<'a, T>])
+    where T: Text<'a>,
+{
+    for dir in dirs {
+        dir.arguments.sort_by(|a, b| a.name.cmp(&b.name));
+        for arg in &mut dir.arguments {
+            value(&mut arg.value);
+        }
+    }
+}
+
+pub(crate) fn value<'a, T>(val: &mut Value<'a, T>)
+    where T: Text<'a>,
+{
+    match *val {
+        Value::Object(ref mut fields) => {
+            fields.sort_by(|a, b| a.0.cmp(&b.0));
+            for (_, val) in fields {
+                value(val);
+            }
+        }
+        Value::List(ref mut items) => {
+            for item in items {
+                value(item);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ParserOptions;
+    use crate::query::{parse_query, parse_query_with_options};
+    use super::normalize;
+
+    fn norm(s: &str) -> String {
+        normalize(parse_query::<&str>(s).unwrap()).to_string()
+    }
+
+    fn norm_fragment_arguments(s: &str) -> String {
+        let mut options = ParserOptions::default();
+        options.fragment_arguments(true);
+        normalize(parse_query_with_options::<&str>(s, &options).unwrap())
+            .to_string()
+    }
+
+    #[test]
+    fn sorted() {
+        assert_eq!(
+            norm("query Q($b: Int, $a: Int) @d(y: 1, x: 2) {
+                f(z: [{q: 1, p: 2}], a: $a) @skip(if: $b, a: 1) { ...F }
+            }"),
+            norm("query Q(
+                $a: Int
+                $b: Int
+            ) @d(x: 2, y: 1) {
+                f(a: $a, z: [{p: 2, q: 1}]) @skip(a: 1, if: $b) {
+                    ... F
+                }
+            }"));
+        assert_eq!(norm("{ a(y: 1, x: 2) }"), "{\n  a(x: 2, y: 1)\n}\n");
+        assert_eq!(norm("query Q($a: Int @d(y: 1, x: {b: 1, a: 2})) { a }"),
+                   norm("query Q($a: Int @d(x: {a: 2, b: 1}, y: 1)) { a }"));
+    }
+
+    #[test]
+    fn sorted_fragment_arguments() {
+        assert_eq!(
+            norm_fragment_arguments("
+                { ...F(y: 1, x: {b: 2, a: 1}) }
+                fragment F($y: Int, $x: In @d(b: 1, a: 2)) on T { a }"),
+            norm_fragment_arguments("
+                { ...F(x: {a: 1, b: 2}, y: 1) }
+                fragment F($x: In @d(a: 2, b: 1), $y: Int) on T { a }"));
+    }
+
+    #[test]
+    fn shorthand_query() {
+        assert_eq!(norm("query { a, b, }"), norm("{a b}"));
+        assert_eq!(norm("query Q { a }"), "query Q {\n  a\n}\n");
+    }
+
+    #[test]
+    fn order_preserved() {
+        assert_eq!(norm("{ b a } fragment F on T { d c }"),
+            "{\n  b\n  a\n}\n\nfragment F on T {\n  d\n  c\n}\n");
+    }
+}
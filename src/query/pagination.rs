@@ -0,0 +1,236 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::mem;
+
+use crate::common::{Number, Value};
+use crate::position::Pos;
+use crate::query::ast::*;
+
+
+/// Policy applied to pagination arguments of the fields
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::parse_query;
+/// use edb_graphql_parser::query::PaginationPolicy;
+///
+/// let mut policy = PaginationPolicy::default();
+/// policy.max("first", 100).default_value("first", 10)
+///     .paginated_field("users");
+/// let mut doc = parse_query::<&str>("{ users { id } posts(first: 500) { id } }")
+///     .unwrap();
+/// let changes = policy.apply(&mut doc);
+/// assert_eq!(changes.len(), 2);
+/// assert_eq!(doc.to_string(), "\
+/// {
+///   users(first: 10) {
+///     id
+///   }
+///   posts(first: 100) {
+///     id
+///   }
+/// }
+/// ");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PaginationPolicy {
+    arguments: BTreeMap<String, ArgumentPolicy>,
+    fields: BTreeSet<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct ArgumentPolicy {
+    max: Option<i32>,
+    default: Option<i32>,
+}
+
+/// Change made to the document by `PaginationPolicy::apply`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaginationChange {
+    /// Position of the field
+    pub position: Pos,
+    pub field: String,
+    pub argument: String,
+    /// Original value of the argument, `None` if default was injected
+    pub old: Option<Number>,
+    pub new: i32,
+}
+
+impl PaginationPolicy {
+    /// Clamp the integer values of the argument to the maximum
+    ///
+    /// Applies to every field having the argument. Values out of the range
+    /// of `Int` (32-bit signed integers) are invalid, and are clamped too.
+    /// Values passed in variables are not changed, as they aren't known at
+    /// this point.
+    pub fn max(&mut self, argument: &str, max: i32) -> &mut Self {
+        self.arguments.entry(argument.into()).or_default().max = Some(max);
+        self
+    }
+
+    /// Add the argument with this value to the paginated fields which
+    /// don't have it
+    pub fn default_value(&mut self, argument: &str, value: i32) -> &mut Self {
+        self.arguments.entry(argument.into()).or_default().default =
+            Some(value);
+        self
+    }
+
+    /// Mark field name as paginated, so default values are injected into it
+    ///
+    /// Since there is no schema, parser can't know which fields return
+    /// lists, so defaults are only injected into fields listed here.
+    pub fn paginated_field(&mut self, name: &str) -> &mut Self {
+        self.fields.insert(name.into());
+        self
+    }
+
+    /// Applies the policy to all operations and fragments of the document
+    ///
+    /// Returns the list of changes made, in the order of appearance in the
+    /// document.
+    pub fn apply<'a, T>(&'a self, doc: &mut Document<'a, T>)
+        -> Vec<PaginationChange>
+        where T: Text<'a>,
+    {
+        let mut changes = Vec::new();
+        for def in &mut doc.definitions {
+            let set = match *def {
+                Definition::Operation(ref mut op) => &mut op.selection_set,
+                Definition::Fragment(ref mut frag) => &mut frag.selection_set,
+            };
+            self.selection_set(set, &mut changes);
+        }
+        changes
+    }
+
+    fn selection_set<'a, T>(&'a self, set: &mut SelectionSet<'a, T>,
+        changes: &mut Vec<PaginationChange>)
+        where T: Text<'a>,
+    {
+        for item in &mut set.items {
+            match *item {
+                Selection::Field(ref mut field) => {
+                    self.field(field, changes);
+                    self.selection_set(&mut field.selection_set, changes);
+                }
+                Selection::InlineFragment(ref mut frag) => {
+                    self.selection_set(&mut frag.selection_set, changes);
+                }
+                Selection::FragmentSpread(_) => {}
+            }
+        }
+    }
+
+    fn field<'a, T>(&'a self, field: &mut Field<'a, T>,
+        changes: &mut Vec<PaginationChange>)
+        where T: Text<'a>,
+    {
+        let paginated = self.fields.contains(field.name.as_ref());
+        for (name, policy) in &self.arguments {
+            let arg = field.arguments.iter_mut()
+                .find(|(arg, _)| arg.as_ref() == name);
+            let change = match (arg, policy.max, policy.default) {
+                (Some((_, ref mut value)), Some(max), _) => match *value {
+                    Value::Int(ref mut num) if exceeds(num, max) => {
+                        let old = mem::replace(num, Number::from(max));
+                        Some((Some(old), max))
+                    }
+                    _ => None,
+                },
+                (None, _, Some(default)) if paginated => {
+                    field.arguments.push((name.as_str().into(),
+                                          Value::Int(Number::from(default))));
+                    Some((None, default))
+                }
+                _ => None,
+            };
+            if let Some((old, new)) = change {
+                changes.push(PaginationChange {
+                    position: field.position,
+                    field: field.name.as_ref().to_string(),
+                    argument: name.clone(),
+                    old,
+                    new,
+                });
+            }
+        }
+    }
+}
+
+/// Returns true if the value is greater than `max` or out of range of `Int`
+fn exceeds(value: &Number, max: i32) -> bool {
+    match value.as_i64().and_then(|n| i32::try_from(n).ok()) {
+        Some(n) => n > max,
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse_query;
+    use crate::common::Number;
+    use super::PaginationPolicy;
+
+    #[test]
+    fn clamp() {
+        let mut policy = PaginationPolicy::default();
+        policy.max("first", 50).max("limit", 10);
+        let mut doc = parse_query::<String>("
+            query Q($n: Int) {
+                a(first: 100, limit: 5) { b(limit: 20) }
+                c(first: $n)
+            }
+            fragment F on T { d(first: 1000) }
+        ").unwrap();
+        let changes = policy.apply(&mut doc);
+        let summary = changes.iter()
+            .map(|c| (c.field.as_str(), c.argument.as_str(),
+                      c.old.as_ref().and_then(|n| n.as_i64()), c.new))
+            .collect::<Vec<_>>();
+        assert_eq!(summary, vec![
+            ("a", "first", Some(100), 50),
+            ("b", "limit", Some(20), 10),
+            ("d", "first", Some(1000), 50),
+        ]);
+        assert_eq!(changes[0].position.to_string(), "3:17");
+    }
+
+    #[test]
+    fn clamp_out_of_range() {
+        let mut policy = PaginationPolicy::default();
+        policy.max("first", 50);
+        let mut doc = parse_query::<String>("{
+            a(first: 99999999999999999999)
+            b(first: 4294967296)
+            c(first: -4294967296)
+            d(first: -1)
+        }").unwrap();
+        let changes = policy.apply(&mut doc);
+        let old = changes.into_iter()
+            .map(|c| c.old.unwrap())
+            .collect::<Vec<Number>>();
+        assert_eq!(old, vec![
+            "99999999999999999999".parse().unwrap(),
+            "4294967296".parse().unwrap(),
+            "-4294967296".parse().unwrap(),
+        ]);
+        assert_eq!(doc.to_string(), "{\n  a(first: 50)\n  b(first: 50)\n  \
+                                     c(first: 50)\n  d(first: -1)\n}\n");
+    }
+
+    #[test]
+    fn defaults() {
+        let mut policy = PaginationPolicy::default();
+        policy.default_value("first", 10).default_value("offset", 0)
+            .paginated_field("users");
+        let mut doc = parse_query::<String>(
+            "{ users(offset: 5) { id } user { id } }").unwrap();
+        let changes = policy.apply(&mut doc);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].old, None);
+        assert_eq!(doc.to_string(), "\
+            {\n  users(offset: 5, first: 10) {\n    id\n  }\n  \
+            user {\n    id\n  }\n}\n");
+    }
+}
@@ -0,0 +1,441 @@
+use std::collections::BTreeSet;
+
+use crate::common::{Directive, Value};
+use crate::query::ast::*;
+use crate::query::separate::used_fragments;
+
+
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    root: Option<OperationKind>,
+    fields: Vec<String>,
+}
+
+/// Removes from the document all the fields except the allowed ones
+///
+/// Paths are written as a dot-separated list of field names (not aliases)
+/// starting at the operation root, fragments are looked through. Path may
+/// start with the root type name (`Mutation.login`) to only match
+/// operations of this kind, and may contain `*` matching any field. The
+/// field at the path is kept with all its subfields, fields on the way to
+/// it are kept with only the allowed subfields. `__typename` is kept in
+/// every selection set having other fields kept.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::parse_query;
+/// use edb_graphql_parser::query::Projection;
+///
+/// let mut projection = Projection::default();
+/// projection.allow("user.profile.email").allow("user.id");
+/// let doc = parse_query::<&str>("{
+///     user { id name profile { email phone } }
+///     admin { secret }
+/// }").unwrap();
+/// assert_eq!(projection.apply(&doc).to_string(), "\
+/// {
+///   user {
+///     id
+///     profile {
+///       email
+///     }
+///   }
+/// }
+/// ");
+/// ```
+///
+/// Directives of the selections kept are kept too. Fragment spreads are
+/// kept if everything in the fragment is allowed. Otherwise they are
+/// replaced by inline fragments (with the directives of the spread and the
+/// type condition of the fragment) containing only the allowed fields, as
+/// the same fragment can be spread in places where different fields are
+/// allowed. Fragments that are no longer spread and variables that are no
+/// longer used are removed.
+///
+/// Operations where nothing is allowed are left with an empty selection
+/// set, which is not a valid document, so check
+/// `operation.selection_set.items.is_empty()` before sending it further.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Projection {
+    rules: Vec<Rule>,
+}
+
+enum Allowed {
+    /// Field and all its subfields are allowed
+    All,
+    /// Some of the subfields are allowed
+    Some,
+    None,
+}
+
+struct Projector<'p, 'd, 'a, T: Text<'a>> {
+    rules: Vec<&'p Rule>,
+    doc: &'d Document<'a, T>,
+    /// Fragments being projected, to stop at cycles
+    stack: Vec<&'d str>,
+}
+
+impl Projection {
+    /// Allow the field at the path with all its subfields
+    pub fn allow(&mut self, path: &str) -> &mut Self {
+        let mut fields = path.split('.').map(String::from)
+            .collect::<Vec<_>>();
+        let root = match fields.first().map(|f| f.as_str()) {
+            Some("Query") => Some(OperationKind::Query),
+            Some("Mutation") => Some(OperationKind::Mutation),
+            Some("Subscription") => Some(OperationKind::Subscription),
+            _ => None,
+        };
+        if root.is_some() {
+            fields.remove(0);
+        }
+        self.rules.push(Rule { root, fields });
+        self
+    }
+
+    /// Returns the document with only the allowed fields
+    pub fn apply<'a, T>(&self, doc: &Document<'a, T>) -> Document<'a, T>
+        where T: Text<'a> + Clone,
+    {
+        let mut operations = Vec::new();
+        for op in doc.operations() {
+            let kind = match op.kind {
+                OperationKind::ImplicitQuery => OperationKind::Query,
+                ref kind => kind.clone(),
+            };
+            let mut projector = Projector {
+                rules: self.rules.iter()
+                    .filter(|rule| rule.root.as_ref()
+                            .map(|root| *root == kind).unwrap_or(true))
+                    .collect(),
+                doc,
+                stack: Vec::new(),
+            };
+            let (selection_set, _) = projector.selection_set(
+                &op.selection_set, &mut Vec::new());
+            operations.push(Operation {
+                selection_set,
+                .. op.clone()
+            });
+        }
+        let mut fragments = BTreeSet::new();
+        for op in &operations {
+            fragments.extend(used_fragments(doc, &op.selection_set)
+                .into_iter().map(String::from));
+        }
+        let mut operations = operations.into_iter();
+        let definitions = doc.definitions.iter().filter_map(|def| match *def {
+            Definition::Operation(_) => {
+                let mut op = operations.next().expect("operation projected");
+                let mut used = BTreeSet::new();
+                variables_in_directives(&op.directives, &mut used);
+                variables_in_selections(doc, &op.selection_set,
+                                        &mut BTreeSet::new(), &mut used);
+                op.variable_definitions
+                    .retain(|var| used.contains(var.name.as_ref()));
+                Some(Definition::Operation(op))
+            }
+            Definition::Fragment(ref frag)
+                if fragments.contains(frag.name.as_ref())
+            => Some(def.clone()),
+            Definition::Fragment(_) => None,
+        }).collect();
+        Document { definitions }
+    }
+}
+
+impl Rule {
+    fn allows(&self, path: &[&str]) -> Allowed {
+        let matches = self.fields.iter().zip(path)
+            .all(|(rule, field)| rule == "*" || rule == field);
+        if !matches {
+            Allowed::None
+        } else if path.len() >= self.fields.len() {
+            Allowed::All
+        } else {
+            Allowed::Some
+        }
+    }
+}
+
+impl<'p, 'd, 'a, T> Projector<'p, 'd, 'a, T>
+    where T: Text<'a> + Clone,
+{
+    fn allows(&self, path: &[&str]) -> Allowed {
+        let mut result = Allowed::None;
+        for rule in &self.rules {
+            match rule.allows(path) {
+                Allowed::All => return Allowed::All,
+                Allowed::Some => result = Allowed::Some,
+                Allowed::None => {}
+            }
+        }
+        result
+    }
+
+    /// Returns the allowed part of the selection set, and whether it's all
+    /// of the selection set
+    fn selection_set(&mut self, set: &'d SelectionSet<'a, T>,
+        path: &mut Vec<&'d str>)
+        -> (SelectionSet<'a, T>, bool)
+    {
+        let mut items = Vec::new();
+        let mut typename = 0;
+        let mut complete = true;
+        for item in &set.items {
+            match *item {
+                Selection::Field(ref field)
+                    if field.name.as_ref() == "__typename"
+                => {
+                    typename += 1;
+                    items.push(item.clone());
+                }
+                Selection::Field(ref field) => {
+                    path.push(field.name.as_ref());
+                    match self.allows(path) {
+                        Allowed::All => items.push(item.clone()),
+                        Allowed::Some => {
+                            let (sub, all) = self.selection_set(
+                                &field.selection_set, path);
+                            if sub.items.is_empty() {
+                                complete = false;
+                            } else {
+                                complete &= all;
+                                items.push(Selection::Field(Field {
+                                    selection_set: sub,
+                                    .. field.clone()
+                                }));
+                            }
+                        }
+                        Allowed::None => complete = false,
+                    }
+                    path.pop();
+                }
+                Selection::FragmentSpread(ref spread) => {
+                    let name = spread.fragment_name.as_ref();
+                    let frag = match self.doc.fragment(name) {
+                        Some(frag) if !self.stack.contains(&name) => frag,
+                        _ => {
+                            complete = false;
+                            continue;
+                        }
+                    };
+                    self.stack.push(name);
+                    let (sub, all) = self.selection_set(&frag.selection_set,
+                                                        path);
+                    self.stack.pop();
+                    if all {
+                        items.push(item.clone());
+                    } else {
+                        complete = false;
+                        if !sub.items.is_empty() {
+                            items.push(Selection::InlineFragment(
+                                InlineFragment {
+                                    position: spread.position,
+                                    on_position: Some(frag.on_position),
+                                    type_condition: Some(
+                                        frag.type_condition.clone()),
+                                    directives: spread.directives.clone(),
+                                    selection_set: sub,
+                                }));
+                        }
+                    }
+                }
+                Selection::InlineFragment(ref frag) => {
+                    let (sub, all) = self.selection_set(&frag.selection_set,
+                                                        path);
+                    if sub.items.is_empty() {
+                        complete = false;
+                    } else {
+                        complete &= all;
+                        items.push(Selection::InlineFragment(InlineFragment {
+                            selection_set: sub,
+                            .. frag.clone()
+                        }));
+                    }
+                }
+            }
+        }
+        if typename > 0 && typename == items.len() {
+            complete = false;
+            items.clear();
+        }
+        (SelectionSet { span: set.span, items }, complete)
+    }
+}
+
+fn variables_in_selections<'x, 'a, T>(doc: &'x Document<'a, T>,
+    set: &'x SelectionSet<'a, T>, visited: &mut BTreeSet<&'x str>,
+    used: &mut BTreeSet<&'x str>)
+    where T: Text<'a>,
+{
+    for item in &set.items {
+        match *item {
+            Selection::Field(ref field) => {
+                for (_, value) in &field.arguments {
+                    variables_in_value(value, used);
+                }
+                variables_in_directives(&field.directives, used);
+                variables_in_selections(doc, &field.selection_set,
+                                        visited, used);
+            }
+            Selection::FragmentSpread(ref spread) => {
+                for (_, value) in &spread.arguments {
+                    variables_in_value(value, used);
+                }
+                variables_in_directives(&spread.directives, used);
+                let name = spread.fragment_name.as_ref();
+                if visited.insert(name) {
+                    if let Some(frag) = doc.fragment(name) {
+                        variables_in_directives(&frag.directives, used);
+                        variables_in_selections(doc, &frag.selection_set,
+                                                visited, used);
+                    }
+                }
+            }
+            Selection::InlineFragment(ref frag) => {
+                variables_in_directives(&frag.directives, used);
+                variables_in_selections(doc, &frag.selection_set,
+                                        visited, used);
+            }
+        }
+    }
+}
+
+fn variables_in_directives<'x, 'a, T>(directives: &'x [Directive<'a, T>],
+    used: &mut BTreeSet<&'x str>)
+    where T: Text<'a>,
+{
+    for dir in directives {
+        for arg in &dir.arguments {
+            variables_in_value(&arg.value, used);
+        }
+    }
+}
+
+fn variables_in_value<'x, 'a, T>(value: &'x Value<'a, T>,
+    used: &mut BTreeSet<&'x str>)
+    where T: Text<'a>,
+{
+    match *value {
+        Value::Variable(ref name) => {
+            used.insert(name.as_ref());
+        }
+        Value::List(ref items) => {
+            for item in items {
+                variables_in_value(item, used);
+            }
+        }
+        Value::Object(ref fields) => {
+            for (_, value) in fields {
+                variables_in_value(value, used);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse_query;
+    use super::Projection;
+
+    fn project(paths: &[&str], query: &str) -> String {
+        let mut projection = Projection::default();
+        for path in paths {
+            projection.allow(path);
+        }
+        projection.apply(&parse_query::<&str>(query).unwrap()).to_string()
+    }
+
+    #[test]
+    fn fields() {
+        assert_eq!(project(&["a.b", "c", "Mutation.m", "*.e.f"], "
+            query Q($v: Int, $w: Int) {
+                a { b { x } c x: d __typename }
+                c(v: $v) { d }
+                m(w: $w)
+                d { e { f g } }
+                e { __typename }
+            }
+            mutation M($w: Int) { m(w: $w) n }
+        "), "\
+query Q($v: Int) {
+  a {
+    b {
+      x
+    }
+    __typename
+  }
+  c(v: $v) {
+    d
+  }
+  d {
+    e {
+      f
+    }
+  }
+}
+
+mutation M($w: Int) {
+  m(w: $w)
+}
+");
+    }
+
+    #[test]
+    fn fragments() {
+        assert_eq!(project(&["a.x", "b"], "
+            query Q($v: Boolean) {
+                a { ...F @include(if: $v) ... on T { ...G } }
+                b { ...F ...G }
+            }
+            fragment F on T { x y }
+            fragment G on T { y }
+            fragment Unused on T { x }
+        "), "\
+query Q($v: Boolean) {
+  a {
+    ... on T @include(if: $v) {
+      x
+    }
+  }
+  b {
+    ...F
+    ...G
+  }
+}
+
+fragment F on T {
+  x
+  y
+}
+
+fragment G on T {
+  y
+}
+");
+    }
+
+    #[test]
+    fn partial_fragments() {
+        assert_eq!(project(&["a.x", "a.b.c"], "
+            { a { ...F ...G x } }
+            fragment F on T { __typename }
+            fragment G on T { b }
+        "), "\
+{
+  a {
+    x
+  }
+}
+");
+    }
+
+    #[test]
+    fn nothing_allowed() {
+        assert_eq!(project(&["other"], "
+            { a ...F } fragment F on Query { ...F b }
+        "), "{\n}\n");
+    }
+}
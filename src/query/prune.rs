@@ -0,0 +1,236 @@
+use std::borrow::Borrow;
+use std::collections::BTreeMap;
+
+use crate::query::ast::*;
+
+
+/// Evaluates `@skip` and `@include` directives and removes the selections
+/// that are not included in the response
+///
+/// The `if` argument can be either a literal or a variable. Variables are
+/// looked up in `variables`, then in the defaults of the operation (for
+/// fragments only when the document has a single operation). Directives
+/// whose condition is not known are kept as is, directives of included
+/// selections are removed:
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// # use std::collections::BTreeMap;
+/// use edb_graphql_parser::parse_query;
+/// use edb_graphql_parser::query::prune_conditionals;
+///
+/// let doc = parse_query::<&str>("query($full: Boolean, $x: Boolean) {
+///     id
+///     name @include(if: $full)
+///     email @skip(if: $full)
+///     phone @skip(if: $x)
+/// }").unwrap();
+/// let mut variables = BTreeMap::new();
+/// variables.insert("full", true);
+/// assert_eq!(prune_conditionals(&doc, &variables).to_string(), "\
+/// query($full: Boolean, $x: Boolean) {
+///   id
+///   name
+///   phone @skip(if: $x)
+/// }
+/// ");
+/// ```
+///
+/// Selection sets where all selections are removed are left empty, and
+/// fragments are kept even if they are no longer spread.
+pub fn prune_conditionals<'a, T, K>(doc: &Document<'a, T>,
+    variables: &BTreeMap<K, bool>)
+    -> Document<'a, T>
+    where T: Text<'a> + Clone,
+          K: Borrow<str> + Ord,
+{
+    let operations = doc.definitions.iter()
+        .filter(|def| matches!(def, Definition::Operation(_)))
+        .count();
+    let definitions = doc.definitions.iter().map(|def| match *def {
+        Definition::Operation(ref op) => {
+            let pruner = Pruner {
+                variables,
+                defaults: defaults(op),
+            };
+            Definition::Operation(Operation {
+                selection_set: pruner.selection_set(&op.selection_set),
+                .. op.clone()
+            })
+        }
+        Definition::Fragment(ref frag) => {
+            let op = doc.definitions.iter().find_map(|def| match *def {
+                Definition::Operation(ref op) if operations == 1 => Some(op),
+                _ => None,
+            });
+            let pruner = Pruner {
+                variables,
+                defaults: op.map(defaults).unwrap_or_default(),
+            };
+            Definition::Fragment(FragmentDefinition {
+                selection_set: pruner.selection_set(&frag.selection_set),
+                .. frag.clone()
+            })
+        }
+    }).collect();
+    Document { definitions }
+}
+
+fn defaults<'x, 'a, T>(op: &'x Operation<'a, T>) -> BTreeMap<&'x str, bool>
+    where T: Text<'a>,
+{
+    op.variable_definitions.iter().filter_map(|var| {
+        match var.default_value.as_ref().map(|d| &d.value) {
+            Some(&Value::Boolean(value)) => Some((var.name.as_ref(), value)),
+            _ => None,
+        }
+    }).collect()
+}
+
+struct Pruner<'x, K> {
+    variables: &'x BTreeMap<K, bool>,
+    defaults: BTreeMap<&'x str, bool>,
+}
+
+impl<'x, K> Pruner<'x, K>
+    where K: Borrow<str> + Ord,
+{
+    fn selection_set<'a, T>(&self, set: &SelectionSet<'a, T>)
+        -> SelectionSet<'a, T>
+        where T: Text<'a> + Clone,
+    {
+        let items = set.items.iter().filter_map(|item| match *item {
+            Selection::Field(ref field) => {
+                let directives = self.directives(&field.directives)?;
+                Some(Selection::Field(Field {
+                    directives,
+                    selection_set: self.selection_set(&field.selection_set),
+                    .. field.clone()
+                }))
+            }
+            Selection::FragmentSpread(ref spread) => {
+                let directives = self.directives(&spread.directives)?;
+                Some(Selection::FragmentSpread(FragmentSpread {
+                    directives,
+                    .. spread.clone()
+                }))
+            }
+            Selection::InlineFragment(ref frag) => {
+                let directives = self.directives(&frag.directives)?;
+                Some(Selection::InlineFragment(InlineFragment {
+                    directives,
+                    selection_set: self.selection_set(&frag.selection_set),
+                    .. frag.clone()
+                }))
+            }
+        }).collect();
+        SelectionSet { span: set.span, items }
+    }
+
+    /// Returns directives that are left, or `None` if the selection is
+    /// skipped
+    fn directives<'a, T>(&self, directives: &[Directive<'a, T>])
+        -> Option<Vec<Directive<'a, T>>>
+        where T: Text<'a> + Clone,
+    {
+        let mut result = Vec::new();
+        for dir in directives {
+            let skip_if = match dir.name.as_ref() {
+                "skip" => true,
+                "include" => false,
+                _ => {
+                    result.push(dir.clone());
+                    continue;
+                }
+            };
+            match self.condition(dir) {
+                Some(value) if value == skip_if => return None,
+                Some(_) => {}
+                None => result.push(dir.clone()),
+            }
+        }
+        Some(result)
+    }
+
+    fn condition<'a, T>(&self, dir: &Directive<'a, T>) -> Option<bool>
+        where T: Text<'a>,
+    {
+        let arg = dir.arguments.iter().find(|a| a.name.as_ref() == "if")?;
+        match arg.value {
+            Value::Boolean(value) => Some(value),
+            Value::Variable(ref name) => {
+                let name = name.as_ref();
+                self.variables.get(name).copied()
+                    .or_else(|| self.defaults.get(name).copied())
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use crate::parse_query;
+    use super::prune_conditionals;
+
+    fn prune(query: &str, variables: &[(&str, bool)]) -> String {
+        let doc = parse_query::<&str>(query).unwrap();
+        let variables = variables.iter().cloned()
+            .collect::<BTreeMap<_, _>>();
+        prune_conditionals(&doc, &variables).to_string()
+    }
+
+    #[test]
+    fn literals_and_defaults() {
+        assert_eq!(prune("query($a: Boolean = true, $b: Boolean = false) {
+            x @skip(if: $a)
+            y @include(if: $b) @skip(if: $a)
+            z @include(if: true) @tag
+            ... on T @include(if: false) { w }
+            ...F @include(if: $a)
+        }
+        fragment F on T { v @skip(if: $b) u @skip(if: true) }", &[("b", true)]),
+        "\
+query($a: Boolean = true, $b: Boolean = false) {
+  z @tag
+  ...F
+}
+
+fragment F on T {
+}
+");
+    }
+
+    #[test]
+    fn unknown() {
+        let query = "\
+query A($a: Boolean = true) {
+  x @skip(if: $a)
+  ...F
+}
+
+query B {
+  y @skip(if: $b)
+}
+
+fragment F on T {
+  v @skip(if: $a)
+}
+";
+        assert_eq!(prune(query, &[]), "\
+query A($a: Boolean = true) {
+  ...F
+}
+
+query B {
+  y @skip(if: $b)
+}
+
+fragment F on T {
+  v @skip(if: $a)
+}
+");
+    }
+}
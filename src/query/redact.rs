@@ -0,0 +1,501 @@
+use std::collections::BTreeSet;
+
+use crate::common::Value;
+use crate::query::ast::*;
+use crate::query::error::ParseError;
+use crate::query::grammar::parse_query;
+
+
+/// The text printed instead of the redacted values
+pub const REDACTED: &str = "<redacted>";
+
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    root: Option<OperationKind>,
+    fields: Vec<String>,
+    argument: Vec<String>,
+}
+
+/// Hides values of the specified arguments when printing queries
+///
+/// Paths are written as a dot-separated list of field names starting at
+/// the operation root (fragments are looked through), followed by the
+/// name of the argument in parenthesis: `login(password:)`. Nested fields
+/// of the input objects are specified with dots too:
+/// `user.update(input.password:)`. Path may start with the root type
+/// name, like schema coordinates (`Mutation.login(password:)`), to only
+/// match operations of this kind, and may contain `*` matching any field.
+/// If argument is omitted (`login`) all arguments of the field are
+/// redacted.
+///
+/// Values are replaced by `<redacted>` which makes the structure of the
+/// query visible, but the result is not parseable. Variables are not
+/// replaced as their values are sent separately from the query.
+/// `Redaction::literals` replaces every string and number instead, keeping
+/// the query parseable.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::query::Redaction;
+///
+/// let mut redaction = Redaction::default();
+/// redaction.path("Mutation.login(password:)").argument("token");
+/// let query = redaction.redact(r#"mutation {
+///     login(user: "root", password: "secret") { session(token: $t) }
+/// }"#).unwrap();
+/// assert_eq!(query, r#"mutation {
+///   login(user: "root", password: <redacted>) {
+///     session(token: $t)
+///   }
+/// }
+/// "#);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Redaction {
+    rules: Vec<Rule>,
+    arguments: BTreeSet<String>,
+    literals: bool,
+}
+
+struct Redactor<'r> {
+    redaction: &'r Redaction,
+    /// Fragments to redact: (name, operation kind, path to the spread)
+    queue: Vec<(String, OperationKind, Vec<String>)>,
+    visited: Vec<(String, OperationKind, Vec<String>)>,
+    count: usize,
+}
+
+impl Redaction {
+    /// Redact argument at the path
+    pub fn path(&mut self, path: &str) -> &mut Self {
+        let (fields, argument) = match path.split_once('(') {
+            Some((fields, argument)) => {
+                let argument = argument.trim_end_matches(')')
+                    .trim_end_matches(':');
+                (fields, argument.split('.').map(String::from).collect())
+            }
+            None => (path, Vec::new()),
+        };
+        let mut fields = fields.split('.').map(String::from)
+            .collect::<Vec<_>>();
+        let root = match fields.first().map(|f| f.as_str()) {
+            Some("Query") => Some(OperationKind::Query),
+            Some("Mutation") => Some(OperationKind::Mutation),
+            Some("Subscription") => Some(OperationKind::Subscription),
+            _ => None,
+        };
+        if root.is_some() {
+            fields.remove(0);
+        }
+        self.rules.push(Rule { root, fields, argument });
+        self
+    }
+
+    /// Redact arguments with the name in every field, directive and
+    /// fragment spread
+    pub fn argument(&mut self, name: &str) -> &mut Self {
+        self.arguments.insert(name.into());
+        self
+    }
+
+    /// Redact every string and number literal in the document
+    ///
+    /// Literals are replaced by placeholders of the same type:
+    /// `"<redacted>"`, `0` or `0.0`, so the query is still parseable and
+    /// valid. Enums, booleans, nulls and variables are kept, as well as
+    /// string arguments of `@defer` and `@stream` (labels).
+    ///
+    /// ```rust
+    /// # extern crate edb_graphql_parser;
+    /// use edb_graphql_parser::query::Redaction;
+    ///
+    /// let query = Redaction::default().literals().redact(r#"
+    ///     query($x: Float = 1.5) {
+    ///       users(name: "Ann", age: 42, sort: NAME, where: {ok: true})
+    ///     }
+    /// "#).unwrap();
+    /// assert_eq!(query, r#"query($x: Float = 0.0) {
+    ///   users(name: "<redacted>", age: 0, sort: NAME, where: {ok: true})
+    /// }
+    /// "#);
+    /// ```
+    pub fn literals(&mut self) -> &mut Self {
+        self.literals = true;
+        self
+    }
+
+    /// Replace redacted values in the document
+    ///
+    /// Returns the number of replaced values. Fragments are redacted if
+    /// they are spread in at least one place matching the path.
+    pub fn apply<'a, T>(&self, doc: &mut Document<'a, T>) -> usize
+        where T: Text<'a>,
+    {
+        let mut redactor = Redactor {
+            redaction: self,
+            queue: Vec::new(),
+            visited: Vec::new(),
+            count: 0,
+        };
+        for def in &mut doc.definitions {
+            if let Definition::Operation(ref mut op) = *def {
+                let kind = match op.kind {
+                    OperationKind::ImplicitQuery => OperationKind::Query,
+                    ref kind => kind.clone(),
+                };
+                for dir in &mut op.directives {
+                    redactor.directive(dir);
+                }
+                for var in &mut op.variable_definitions {
+                    for dir in &mut var.directives {
+                        redactor.directive(dir);
+                    }
+                }
+                redactor.selection_set(&mut op.selection_set, &kind,
+                                       &mut Vec::new());
+            }
+        }
+        if !self.arguments.is_empty() {
+            for def in &mut doc.definitions {
+                if let Definition::Fragment(ref mut frag) = *def {
+                    for dir in &mut frag.directives {
+                        redactor.directive(dir);
+                    }
+                    for var in &mut frag.variable_definitions {
+                        for dir in &mut var.directives {
+                            redactor.directive(dir);
+                        }
+                    }
+                }
+            }
+        }
+        while let Some(item) = redactor.queue.pop() {
+            if redactor.visited.contains(&item) {
+                continue;
+            }
+            redactor.visited.push(item.clone());
+            let (name, kind, mut path) = item;
+            for def in &mut doc.definitions {
+                match *def {
+                    Definition::Fragment(ref mut frag)
+                        if frag.name.as_ref() == name
+                    => {
+                        redactor.selection_set(&mut frag.selection_set,
+                                               &kind, &mut path);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if self.literals {
+            for def in &mut doc.definitions {
+                redactor.literals_in(def);
+            }
+        }
+        redactor.count
+    }
+
+    /// Parses the query and formats it with redacted values
+    pub fn redact(&self, query: &str) -> Result<String, ParseError> {
+        let mut doc = parse_query::<&str>(query)?;
+        self.apply(&mut doc);
+        Ok(doc.to_string())
+    }
+}
+
+impl Rule {
+    fn matches(&self, kind: &OperationKind, path: &[String]) -> bool {
+        self.root.as_ref().map(|root| root == kind).unwrap_or(true) &&
+            self.fields.len() == path.len() &&
+            self.fields.iter().zip(path)
+                .all(|(rule, field)| rule == "*" || rule == field)
+    }
+}
+
+impl<'r> Redactor<'r> {
+    fn selection_set<'a, T>(&mut self, set: &mut SelectionSet<'a, T>,
+        kind: &OperationKind, path: &mut Vec<String>)
+        where T: Text<'a>,
+    {
+        for item in &mut set.items {
+            match *item {
+                Selection::Field(ref mut field) => {
+                    path.push(field.name.as_ref().to_string());
+                    self.field(field, kind, path);
+                    self.selection_set(&mut field.selection_set, kind, path);
+                    path.pop();
+                }
+                Selection::FragmentSpread(ref mut spread) => {
+                    for dir in &mut spread.directives {
+                        self.directive(dir);
+                    }
+                    for (name, value) in &mut spread.arguments {
+                        if self.redaction.arguments.contains(name.as_ref()) {
+                            self.redact(value, &[]);
+                        }
+                    }
+                    self.queue.push((spread.fragment_name.as_ref().into(),
+                                     kind.clone(), path.clone()));
+                }
+                Selection::InlineFragment(ref mut frag) => {
+                    for dir in &mut frag.directives {
+                        self.directive(dir);
+                    }
+                    self.selection_set(&mut frag.selection_set, kind, path);
+                }
+            }
+        }
+    }
+
+    fn field<'a, T>(&mut self, field: &mut Field<'a, T>,
+        kind: &OperationKind, path: &[String])
+        where T: Text<'a>,
+    {
+        for dir in &mut field.directives {
+            self.directive(dir);
+        }
+        let redaction = self.redaction;
+        for (name, value) in &mut field.arguments {
+            if redaction.arguments.contains(name.as_ref()) {
+                self.redact(value, &[]);
+                continue;
+            }
+            for rule in &redaction.rules {
+                if !rule.matches(kind, path) {
+                    continue;
+                }
+                match rule.argument.split_first() {
+                    None => self.redact(value, &[]),
+                    Some((arg, rest)) if arg == name.as_ref() => {
+                        self.redact(value, rest);
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+    }
+
+    fn directive<'a, T>(&mut self, dir: &mut Directive<'a, T>)
+        where T: Text<'a>,
+    {
+        for arg in &mut dir.arguments {
+            if self.redaction.arguments.contains(arg.name.as_ref()) {
+                self.redact(&mut arg.value, &[]);
+            }
+        }
+    }
+
+    fn literals_in<'a, T>(&mut self, def: &mut Definition<'a, T>)
+        where T: Text<'a>,
+    {
+        let (vars, directives, set) = match *def {
+            Definition::Operation(ref mut op) => {
+                (&mut op.variable_definitions, &mut op.directives,
+                 &mut op.selection_set)
+            }
+            Definition::Fragment(ref mut frag) => {
+                (&mut frag.variable_definitions, &mut frag.directives,
+                 &mut frag.selection_set)
+            }
+        };
+        for var in vars {
+            if let Some(ref mut default) = var.default_value {
+                self.literal(&mut default.value);
+            }
+            self.directive_literals(&mut var.directives);
+        }
+        self.directive_literals(directives);
+        self.selection_literals(set);
+    }
+
+    fn selection_literals<'a, T>(&mut self, set: &mut SelectionSet<'a, T>)
+        where T: Text<'a>,
+    {
+        for item in &mut set.items {
+            match *item {
+                Selection::Field(ref mut field) => {
+                    for (_, value) in &mut field.arguments {
+                        self.literal(value);
+                    }
+                    self.directive_literals(&mut field.directives);
+                    self.selection_literals(&mut field.selection_set);
+                }
+                Selection::FragmentSpread(ref mut spread) => {
+                    for (_, value) in &mut spread.arguments {
+                        self.literal(value);
+                    }
+                    self.directive_literals(&mut spread.directives);
+                }
+                Selection::InlineFragment(ref mut frag) => {
+                    self.directive_literals(&mut frag.directives);
+                    self.selection_literals(&mut frag.selection_set);
+                }
+            }
+        }
+    }
+
+    fn directive_literals<'a, T>(&mut self,
+        directives: &mut [Directive<'a, T>])
+        where T: Text<'a>,
+    {
+        for dir in directives {
+            let labels = matches!(dir.name.as_ref(), "defer" | "stream");
+            for arg in &mut dir.arguments {
+                if labels && arg.name.as_ref() == "label" {
+                    continue;
+                }
+                self.literal(&mut arg.value);
+            }
+        }
+    }
+
+    /// Replaces strings and numbers in the value by placeholders
+    fn literal<'a, T>(&mut self, value: &mut Value<'a, T>)
+        where T: Text<'a>,
+    {
+        match *value {
+            Value::String(_) => *value = Value::String(REDACTED.into()),
+            Value::Int(_) => *value = Value::Int(0.into()),
+            Value::Float(_) => *value = Value::Float("0.0".into()),
+            Value::List(ref mut items) => {
+                for item in items {
+                    self.literal(item);
+                }
+                return;
+            }
+            Value::Object(ref mut fields) => {
+                for (_, value) in fields.iter_mut() {
+                    self.literal(value);
+                }
+                return;
+            }
+            Value::Variable(_) | Value::Boolean(_) | Value::Null |
+            Value::Enum(_) => return,
+        }
+        self.count += 1;
+    }
+
+    /// Redacts the value or its nested field at the path
+    fn redact<'a, T>(&mut self, value: &mut Value<'a, T>, path: &[String])
+        where T: Text<'a>,
+    {
+        match (value, path.split_first()) {
+            (&mut Value::Variable(_), _) => {}
+            (&mut Value::Enum(ref name), _) if name.as_ref() == REDACTED => {}
+            (&mut Value::List(ref mut items), Some(_)) => {
+                for item in items {
+                    self.redact(item, path);
+                }
+            }
+            (&mut Value::Object(ref mut fields), Some((name, rest))) => {
+                for (key, value) in fields.iter_mut() {
+                    if key.as_ref() == name {
+                        self.redact(value, rest);
+                    }
+                }
+            }
+            (_, Some(_)) => {}
+            (value, None) => {
+                *value = Value::Enum(REDACTED.into());
+                self.count += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{parse_query, ParserOptions};
+    use crate::query::parse_query_with_options;
+    use super::Redaction;
+
+    #[test]
+    fn literals() {
+        let mut redaction = Redaction::default();
+        redaction.literals().path("a");
+        let mut options = ParserOptions::default();
+        options.fragment_arguments(true);
+        let mut doc = parse_query_with_options::<String>(r#"
+            query($v: In = {s: """block""", n: -3}) {
+              a(x: "a") b(x: [1, $v, null, E]) @include(if: false)
+              ...F(s: "s") @defer(label: "l", if: true)
+              ... @stream(label: "k", initialCount: 2) { c @m(x: 2.5) }
+            }
+            fragment F($s: String = "d") on T @n(y: "z") { d }
+        "#, &options).unwrap();
+        assert_eq!(redaction.apply(&mut doc), 9);
+        assert_eq!(doc.to_string(), r#"query($v: In = {s: "<redacted>", n: 0}) {
+  a(x: <redacted>)
+  b(x: [0, $v, null, E]) @include(if: false)
+  ...F(s: "<redacted>") @defer(label: "l", if: true)
+  ... @stream(label: "k", initialCount: 0) {
+    c @m(x: 0.0)
+  }
+}
+
+fragment F($s: String = "<redacted>") on T @n(y: "<redacted>") {
+  d
+}
+"#);
+    }
+
+    #[test]
+    fn paths() {
+        let mut redaction = Redaction::default();
+        redaction.path("user.*(input.password:)").path("Query.search");
+        let mut doc = parse_query::<String>(r#"
+            mutation M {
+                user {
+                    create(input: {name: "a", password: "x"}) { id }
+                    bulk(input: [{password: "y"}, {name: "b"}]) { id }
+                }
+                search(q: "not a query")
+            }
+            query Q { search(q: "text", limit: 10) user { ...F } }
+            fragment F on User { update(input: {password: "z"}) }
+        "#).unwrap();
+        assert_eq!(redaction.apply(&mut doc), 5);
+        assert_eq!(doc.to_string(), r#"mutation M {
+  user {
+    create(input: {name: "a", password: <redacted>}) {
+      id
+    }
+    bulk(input: [{password: <redacted>}, {name: "b"}]) {
+      id
+    }
+  }
+  search(q: "not a query")
+}
+
+query Q {
+  search(q: <redacted>, limit: <redacted>)
+  user {
+    ...F
+  }
+}
+
+fragment F on User {
+  update(input: {password: <redacted>})
+}
+"#);
+    }
+
+    #[test]
+    fn arguments() {
+        let mut redaction = Redaction::default();
+        redaction.argument("secret");
+        let query = redaction.redact(r#"
+            query Q @auth(secret: "a") { a(secret: [1], other: 2) ...F }
+            fragment F on T @auth(secret: "b") { b(secret: $s) }
+        "#).unwrap();
+        assert_eq!(query, r#"query Q @auth(secret: <redacted>) {
+  a(secret: <redacted>, other: 2)
+  ...F
+}
+
+fragment F on T @auth(secret: <redacted>) {
+  b(secret: $s)
+}
+"#);
+    }
+}
@@ -0,0 +1,207 @@
+use crate::position::Pos;
+use crate::query::ast::*;
+use crate::query::fragments::FragmentGraph;
+
+
+/// How a type is referenced, see `referenced_types`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypeUsage {
+    /// Type of a variable definition, list and non-null wrappers removed
+    Variable,
+    /// Type condition of a fragment definition
+    FragmentCondition,
+    /// Type condition of an inline fragment
+    InlineFragmentCondition,
+}
+
+/// Definition having the reference to a type, see `referenced_types`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferencedIn<'x> {
+    /// Operation, `None` for the anonymous one
+    Operation(Option<&'x str>),
+    Fragment(&'x str),
+}
+
+/// Reference to a type in an executable document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReferencedType<'x> {
+    pub name: &'x str,
+    pub usage: TypeUsage,
+    pub definition: ReferencedIn<'x>,
+    /// Position of the variable definition or of the `on` keyword
+    pub position: Pos,
+    /// Whether the selection set of a type condition selects `__typename`
+    /// directly, so the response depends on the name of the concrete type
+    pub selects_typename: bool,
+}
+
+/// Returns the types referenced by the document in the order of the source
+///
+/// Only the syntax is looked at, so the types of the fields are unknown,
+/// and the result doesn't need a schema.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::parse_query;
+/// use edb_graphql_parser::query::{referenced_types, TypeUsage};
+///
+/// let doc = parse_query::<&str>("
+///     query Q($ids: [ID!]!) { nodes(ids: $ids) { ...F } }
+///     fragment F on Node { ... on User { __typename name } }
+/// ").unwrap();
+/// let types = referenced_types(&doc);
+/// let names = types.iter().map(|t| t.name).collect::<Vec<_>>();
+/// assert_eq!(names, ["ID", "Node", "User"]);
+/// assert_eq!(types[2].usage, TypeUsage::InlineFragmentCondition);
+/// assert_eq!(types[2].position.to_string(), "3:30");
+/// assert!(types[2].selects_typename);
+/// ```
+pub fn referenced_types<'x, 'a, T>(doc: &'x Document<'a, T>)
+    -> Vec<ReferencedType<'x>>
+    where T: Text<'a>,
+{
+    let mut types = Vec::new();
+    for def in &doc.definitions {
+        match *def {
+            Definition::Operation(ref op) => {
+                let definition = ReferencedIn::Operation(
+                    op.name.as_ref().map(|n| n.as_ref()));
+                for var in &op.variable_definitions {
+                    types.push(ReferencedType {
+                        name: var.var_type.innermost_name(),
+                        usage: TypeUsage::Variable,
+                        definition,
+                        position: var.position,
+                        selects_typename: false,
+                    });
+                }
+                conditions(&op.selection_set, definition, &mut types);
+            }
+            Definition::Fragment(ref frag) => {
+                let definition = ReferencedIn::Fragment(frag.name.as_ref());
+                let TypeCondition::On(ref name) = frag.type_condition;
+                types.push(ReferencedType {
+                    name: name.as_ref(),
+                    usage: TypeUsage::FragmentCondition,
+                    definition,
+                    position: frag.on_position,
+                    selects_typename: selects_typename(&frag.selection_set),
+                });
+                conditions(&frag.selection_set, definition, &mut types);
+            }
+        }
+    }
+    types
+}
+
+/// Returns the operations that reference the type, directly or through
+/// fragments, e.g. the ones that break if the type is deleted
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::parse_query;
+/// use edb_graphql_parser::query::operations_referencing;
+///
+/// let doc = parse_query::<&str>("
+///     query A { ...F }
+///     query B($id: ID) { node(id: $id) { id } }
+///     { ... on Query { ...F } }
+///     fragment F on Query { a }
+/// ").unwrap();
+/// assert_eq!(operations_referencing(&doc, "Query"), [Some("A"), None]);
+/// assert_eq!(operations_referencing(&doc, "ID"), [Some("B")]);
+/// ```
+pub fn operations_referencing<'x, 'a, T>(doc: &'x Document<'a, T>,
+    type_name: &str)
+    -> Vec<Option<&'x str>>
+    where T: Text<'a>,
+{
+    let types = referenced_types(doc);
+    let graph = FragmentGraph::new(doc);
+    let mut result = Vec::new();
+    for (name, used) in graph.operations() {
+        let referencing = types.iter()
+            .filter(|t| t.name == type_name)
+            .any(|t| match t.definition {
+                ReferencedIn::Operation(op) => op == name,
+                ReferencedIn::Fragment(frag) => used.contains(frag),
+            });
+        if referencing {
+            result.push(name);
+        }
+    }
+    result
+}
+
+fn conditions<'x, 'a, T>(set: &'x SelectionSet<'a, T>,
+    definition: ReferencedIn<'x>, types: &mut Vec<ReferencedType<'x>>)
+    where T: Text<'a>,
+{
+    for item in &set.items {
+        match *item {
+            Selection::Field(ref field) => {
+                conditions(&field.selection_set, definition, types);
+            }
+            Selection::FragmentSpread(_) => {}
+            Selection::InlineFragment(ref frag) => {
+                if let (Some(TypeCondition::On(ref name)), Some(position)) =
+                    (&frag.type_condition, frag.on_position)
+                {
+                    types.push(ReferencedType {
+                        name: name.as_ref(),
+                        usage: TypeUsage::InlineFragmentCondition,
+                        definition,
+                        position,
+                        selects_typename:
+                            selects_typename(&frag.selection_set),
+                    });
+                }
+                conditions(&frag.selection_set, definition, types);
+            }
+        }
+    }
+}
+
+fn selects_typename<'a, T>(set: &SelectionSet<'a, T>) -> bool
+    where T: Text<'a>,
+{
+    set.items.iter().any(|item| match *item {
+        Selection::Field(ref field) => field.name.as_ref() == "__typename",
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse_query;
+    use super::{referenced_types, ReferencedIn, TypeUsage};
+
+    #[test]
+    fn references() {
+        let doc = parse_query::<&str>("
+            query Q($a: Int, $b: [In!] = []) { x { ... on A { y } } }
+            mutation { ... @skip(if: true) { ... on B { __typename } } }
+            fragment F on C { __typename ... { z } }
+        ").unwrap();
+        let types = referenced_types(&doc);
+        let summary = types.iter()
+            .map(|t| (t.name, t.usage, t.definition, t.selects_typename))
+            .collect::<Vec<_>>();
+        assert_eq!(summary, [
+            ("Int", TypeUsage::Variable,
+             ReferencedIn::Operation(Some("Q")), false),
+            ("In", TypeUsage::Variable,
+             ReferencedIn::Operation(Some("Q")), false),
+            ("A", TypeUsage::InlineFragmentCondition,
+             ReferencedIn::Operation(Some("Q")), false),
+            ("B", TypeUsage::InlineFragmentCondition,
+             ReferencedIn::Operation(None), true),
+            ("C", TypeUsage::FragmentCondition,
+             ReferencedIn::Fragment("F"), true),
+        ]);
+        let positions = types.iter()
+            .map(|t| t.position.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(positions, ["2:21", "2:30", "2:56", "3:50", "4:24"]);
+    }
+}
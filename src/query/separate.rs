@@ -0,0 +1,138 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::query::ast::*;
+
+
+/// Returns a document with just the named operation and the fragments it
+/// uses, directly or through other fragments
+///
+/// Fragments are kept in the order of the original document. Returns
+/// `None` if there is no operation with that name.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::parse_query;
+/// use edb_graphql_parser::query::separate_operation;
+///
+/// let doc = parse_query::<&str>("
+///     query A { ...F }
+///     query B { ...G }
+///     fragment G on Query { b }
+///     fragment F on Query { ...G }
+/// ").unwrap();
+/// assert_eq!(separate_operation(&doc, "A").unwrap().to_string(), "\
+/// query A {
+///   ...F
+/// }
+///
+/// fragment G on Query {
+///   b
+/// }
+///
+/// fragment F on Query {
+///   ...G
+/// }
+/// ");
+/// ```
+pub fn separate_operation<'a, T>(doc: &Document<'a, T>, name: &str)
+    -> Option<Document<'a, T>>
+    where T: Text<'a> + Clone,
+{
+    let op = doc.operation(Some(name)).ok()?;
+    Some(with_fragments(doc, op))
+}
+
+/// Returns a document with the operation and the fragments of `doc` it
+/// uses
+pub(crate) fn with_fragments<'a, T>(doc: &Document<'a, T>,
+    op: &Operation<'a, T>)
+    -> Document<'a, T>
+    where T: Text<'a> + Clone,
+{
+    let used = used_fragments(doc, &op.selection_set);
+    let mut definitions = vec![Definition::Operation(op.clone())];
+    definitions.extend(doc.definitions.iter().filter(|def| match **def {
+        Definition::Fragment(ref frag) => used.contains(frag.name.as_ref()),
+        Definition::Operation(_) => false,
+    }).cloned());
+    Document { definitions }
+}
+
+/// Returns names of the fragments spread in the selection set, directly or
+/// through other fragments of the document
+///
+/// Undefined fragments are included.
+pub(crate) fn used_fragments<'x, 'a, T>(doc: &'x Document<'a, T>,
+    selection_set: &'x SelectionSet<'a, T>)
+    -> BTreeSet<&'x str>
+    where T: Text<'a>,
+{
+    let fragments = doc.definitions.iter().filter_map(|def| match *def {
+        Definition::Fragment(ref frag) => Some((frag.name.as_ref(), frag)),
+        Definition::Operation(_) => None,
+    }).collect::<BTreeMap<_, _>>();
+    let mut used = BTreeSet::new();
+    let mut queue = Vec::new();
+    spreads(selection_set, &mut queue);
+    while let Some(name) = queue.pop() {
+        if used.insert(name) {
+            if let Some(frag) = fragments.get(name) {
+                spreads(&frag.selection_set, &mut queue);
+            }
+        }
+    }
+    used
+}
+
+/// Appends names of the fragments spread in the selection set
+pub(crate) fn spreads<'x, 'a, T>(set: &'x SelectionSet<'a, T>,
+    names: &mut Vec<&'x str>)
+    where T: Text<'a>,
+{
+    for item in &set.items {
+        match *item {
+            Selection::Field(ref field) => spreads(&field.selection_set, names),
+            Selection::FragmentSpread(ref spread) => {
+                names.push(spread.fragment_name.as_ref());
+            }
+            Selection::InlineFragment(ref frag) => {
+                spreads(&frag.selection_set, names);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse_query;
+    use super::separate_operation;
+
+    #[test]
+    fn separate() {
+        let doc = parse_query::<&str>("
+            query A { a { ... on T { ...F } } }
+            fragment Unused on T { x }
+            query B { ...G }
+            fragment F on T { f ...F ...Missing }
+            fragment G on Query { g }
+        ").unwrap();
+        assert_eq!(separate_operation(&doc, "A").unwrap().to_string(), "\
+query A {
+  a {
+    ... on T {
+      ...F
+    }
+  }
+}
+
+fragment F on T {
+  f
+  ...F
+  ...Missing
+}
+");
+        assert!(separate_operation(&doc, "C").is_none());
+        let anonymous = parse_query::<&str>("{ a }").unwrap();
+        assert!(separate_operation(&anonymous, "").is_none());
+    }
+}
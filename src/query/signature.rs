@@ -0,0 +1,363 @@
+use std::collections::BTreeSet;
+
+use crate::common::Directive;
+use crate::query::ast::*;
+use crate::query::error::OperationError;
+use crate::query::normalize::normalize;
+use crate::query::separate::used_fragments;
+
+
+/// Summary of the operation used for tagging requests in logs and metrics
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::parse_query;
+/// use edb_graphql_parser::query::{OperationKind, OperationSignature};
+///
+/// let doc = parse_query::<&str>("
+///     query Users($first: Int = 10, $role: Role!) {
+///         users(first: $first, role: $role) { ...U }
+///         ... on Query { total: count }
+///     }
+///     fragment U on User { id }
+/// ").unwrap();
+/// let sig = &OperationSignature::all(&doc)[0];
+/// assert_eq!(sig.kind, OperationKind::Query);
+/// assert_eq!(sig.name, Some("Users"));
+/// assert_eq!(sig.variables,
+///            [("first", "Int".to_string()), ("role", "Role!".to_string())]);
+/// assert_eq!(sig.root_fields, ["users", "count"]);
+/// assert_eq!(sig.fragments, ["U"]);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct OperationSignature<'x> {
+    pub kind: OperationKind,
+    pub name: Option<&'x str>,
+    /// Names of the variables (without `$`) with their types
+    pub variables: Vec<(&'x str, String)>,
+    /// Names (not aliases) of the fields selected at the root, including
+    /// the ones selected through fragments, in the order of appearance
+    pub root_fields: Vec<&'x str>,
+    /// Names of the fragments used, directly or through other fragments,
+    /// sorted by name
+    pub fragments: Vec<&'x str>,
+}
+
+impl<'x> OperationSignature<'x> {
+    /// Returns the signature of the operation of the document
+    pub fn new<'a, T>(doc: &'x Document<'a, T>,
+        operation: &'x Operation<'a, T>)
+        -> OperationSignature<'x>
+        where T: Text<'a>,
+    {
+        let mut root_fields = Vec::new();
+        let mut visited = BTreeSet::new();
+        collect_fields(doc, &operation.selection_set, &mut visited,
+                       &mut root_fields);
+        OperationSignature {
+            kind: operation.kind.clone(),
+            name: operation.name.as_ref().map(|n| n.as_ref()),
+            variables: operation.variable_definitions.iter()
+                .map(|var| (var.name.as_ref(), var.var_type.to_string()))
+                .collect(),
+            root_fields,
+            fragments: used_fragments(doc, &operation.selection_set)
+                .into_iter().collect(),
+        }
+    }
+
+    /// Returns signatures of all the operations of the document
+    pub fn all<'a, T>(doc: &'x Document<'a, T>) -> Vec<OperationSignature<'x>>
+        where T: Text<'a>,
+    {
+        doc.operations().map(|op| OperationSignature::new(doc, op)).collect()
+    }
+}
+
+fn collect_fields<'x, 'a, T>(doc: &'x Document<'a, T>,
+    set: &'x SelectionSet<'a, T>, visited: &mut BTreeSet<&'x str>,
+    fields: &mut Vec<&'x str>)
+    where T: Text<'a>,
+{
+    for item in &set.items {
+        match *item {
+            Selection::Field(ref field) => {
+                let name = field.name.as_ref();
+                if !fields.contains(&name) {
+                    fields.push(name);
+                }
+            }
+            Selection::FragmentSpread(ref spread) => {
+                let name = spread.fragment_name.as_ref();
+                if !visited.insert(name) {
+                    continue;
+                }
+                if let Some(frag) = doc.fragment(name) {
+                    collect_fields(doc, &frag.selection_set, visited, fields);
+                }
+            }
+            Selection::InlineFragment(ref frag) => {
+                collect_fields(doc, &frag.selection_set, visited, fields);
+            }
+        }
+    }
+}
+
+/// Returns the operation signature used by Apollo usage reporting
+///
+/// This is the same as `defaultUsageReportingSignature` of Apollo's JS
+/// libraries, so metrics of Rust services aggregate with the ones reported
+/// by JS services: only the operation and the fragments it uses are kept,
+/// numbers are replaced by `0`, strings by `""`, lists and input objects by
+/// `[]` and `{}`, aliases are dropped, fragments are sorted by name before
+/// the operation, selections, variables, arguments and directives of the
+/// fragments are sorted too, and all the whitespace that isn't needed to
+/// separate names is removed.
+///
+/// The operation is selected by name, as in `Document::operation`.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::parse_query;
+/// use edb_graphql_parser::query::usage_reporting_signature;
+///
+/// let doc = parse_query::<&str>(r#"
+///     query User($id: ID!) {
+///         me: user(id: $id, greeting: "hi") { name ...Avatar }
+///     }
+///     fragment Avatar on User { avatar(size: 64) }
+/// "#).unwrap();
+/// assert_eq!(usage_reporting_signature(&doc, Some("User")).unwrap(),
+///     r#"fragment Avatar on User{avatar(size:0)}"#.to_string() +
+///     r#"query User($id:ID!){user(greeting:"",id:$id){name...Avatar}}"#);
+/// ```
+pub fn usage_reporting_signature<'a, T>(doc: &Document<'a, T>,
+    operation_name: Option<&str>)
+    -> Result<String, OperationError>
+    where T: Text<'a> + Clone,
+{
+    let operation = doc.operation(operation_name)?;
+    let mut fragments = used_fragments(doc, &operation.selection_set)
+        .into_iter()
+        .filter_map(|name| doc.fragment(name))
+        .cloned()
+        .collect::<Vec<_>>();
+    fragments.sort_by(|a, b| a.name.cmp(&b.name));
+    let mut definitions = fragments.into_iter()
+        .map(Definition::Fragment)
+        .collect::<Vec<_>>();
+    definitions.push(Definition::Operation(operation.clone()));
+    for def in &mut definitions {
+        match *def {
+            Definition::Operation(ref mut op) => {
+                hide_variables(&mut op.variable_definitions);
+                hide_directives(&mut op.directives);
+                reduce_selections(&mut op.selection_set);
+            }
+            Definition::Fragment(ref mut frag) => {
+                hide_variables(&mut frag.variable_definitions);
+                hide_directives(&mut frag.directives);
+                sort_directives(&mut frag.directives);
+                frag.variable_definitions.sort_by(|a, b| a.name.cmp(&b.name));
+                reduce_selections(&mut frag.selection_set);
+            }
+        }
+    }
+    let doc = normalize(Document { definitions });
+    Ok(reduce_whitespace(&doc.to_string()))
+}
+
+fn hide_literals<'a, T>(value: &mut Value<'a, T>)
+    where T: Text<'a>,
+{
+    match *value {
+        Value::Int(_) | Value::Float(_) => *value = Value::Int(0.into()),
+        Value::String(_) => *value = Value::String("".into()),
+        Value::List(ref mut items) => items.clear(),
+        Value::Object(ref mut fields) => fields.clear(),
+        Value::Variable(_) | Value::Boolean(_) | Value::Null
+        | Value::Enum(_) => {}
+    }
+}
+
+fn hide_directives<'a, T>(directives: &mut [Directive<'a, T>])
+    where T: Text<'a>,
+{
+    for dir in directives {
+        for arg in &mut dir.arguments {
+            hide_literals(&mut arg.value);
+        }
+    }
+}
+
+fn hide_variables<'a, T>(vars: &mut [VariableDefinition<'a, T>])
+    where T: Text<'a>,
+{
+    for var in vars {
+        if let Some(ref mut default) = var.default_value {
+            hide_literals(&mut default.value);
+        }
+        hide_directives(&mut var.directives);
+    }
+}
+
+fn sort_directives<'a, T>(directives: &mut [Directive<'a, T>])
+    where T: Text<'a>,
+{
+    directives.sort_by(|a, b| a.name.cmp(&b.name));
+}
+
+/// Hides literals, drops aliases and sorts the selections by kind (fields,
+/// then spreads, then inline fragments) and name
+fn reduce_selections<'a, T>(set: &mut SelectionSet<'a, T>)
+    where T: Text<'a>,
+{
+    for item in &mut set.items {
+        match *item {
+            Selection::Field(ref mut field) => {
+                field.alias = None;
+                for (_, value) in &mut field.arguments {
+                    hide_literals(value);
+                }
+                hide_directives(&mut field.directives);
+                reduce_selections(&mut field.selection_set);
+            }
+            Selection::FragmentSpread(ref mut spread) => {
+                for (_, value) in &mut spread.arguments {
+                    hide_literals(value);
+                }
+                hide_directives(&mut spread.directives);
+                sort_directives(&mut spread.directives);
+            }
+            Selection::InlineFragment(ref mut frag) => {
+                hide_directives(&mut frag.directives);
+                sort_directives(&mut frag.directives);
+                reduce_selections(&mut frag.selection_set);
+            }
+        }
+    }
+    set.items.sort_by(|a, b| selection_order(a).cmp(&selection_order(b)));
+}
+
+fn selection_order<'x, 'a, T>(item: &'x Selection<'a, T>)
+    -> (u8, Option<&'x str>)
+    where T: Text<'a>,
+{
+    match *item {
+        Selection::Field(ref field) => (0, Some(field.name.as_ref())),
+        Selection::FragmentSpread(ref spread) => {
+            (1, Some(spread.fragment_name.as_ref()))
+        }
+        Selection::InlineFragment(_) => (2, None),
+    }
+}
+
+/// Collapses whitespace, keeping single spaces only between names
+fn reduce_whitespace(text: &str) -> String {
+    fn is_word(c: char) -> bool {
+        c == '_' || c.is_ascii_alphanumeric()
+    }
+    let mut result = String::with_capacity(text.len());
+    let mut space = false;
+    for c in text.trim().chars() {
+        if c.is_whitespace() {
+            space = true;
+            continue;
+        }
+        if space && is_word(c) && result.ends_with(is_word) {
+            result.push(' ');
+        }
+        space = false;
+        result.push(c);
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse_query;
+    use crate::query::OperationKind;
+    use super::{OperationSignature, usage_reporting_signature};
+
+    #[test]
+    fn signatures() {
+        let doc = parse_query::<&str>("
+            { a b: a ...F }
+            mutation M($x: [ID!]) { c }
+            fragment F on Query { d ...G ...F }
+            fragment G on Query { ... @skip(if: true) { e a } }
+        ").unwrap();
+        let sigs = OperationSignature::all(&doc);
+        assert_eq!(sigs.len(), 2);
+        assert_eq!(sigs[0], OperationSignature {
+            kind: OperationKind::ImplicitQuery,
+            name: None,
+            variables: vec![],
+            root_fields: vec!["a", "d", "e"],
+            fragments: vec!["F", "G"],
+        });
+        assert_eq!(sigs[1], OperationSignature {
+            kind: OperationKind::Mutation,
+            name: Some("M"),
+            variables: vec![("x", "[ID!]".into())],
+            root_fields: vec!["c"],
+            fragments: vec![],
+        });
+    }
+
+    fn usage(query: &str, name: Option<&str>) -> String {
+        let doc = parse_query::<&str>(query).unwrap();
+        usage_reporting_signature(&doc, name).unwrap()
+    }
+
+    #[test]
+    fn usage_reporting() {
+        assert_eq!(usage("{ user { name } }", None), "{user{name}}");
+        assert_eq!(usage("query { user { name } }", None), "{user{name}}");
+        assert_eq!(usage("query OpName { user { name } }", None),
+                   "query OpName{user{name}}");
+        assert_eq!(usage(r#"query OpName {
+                user {
+                    name(apple: [[10]], cat: ENUM_VALUE, bag: {input: "v"})
+                }
+            }"#, None),
+            "query OpName{user{name(apple:[],bag:{},cat:ENUM_VALUE)}}");
+        assert_eq!(usage("
+            query OpName($c: Int! = 1, $a: [[Boolean!]!], $b: EnumType) {
+                user { name(apple: $a, cat: $c, bag: $b, x: 1.5) }
+            }", None),
+            "query OpName($a:[[Boolean!]!],$b:EnumType,$c:Int!=0)\
+             {user{name(apple:$a,bag:$b,cat:$c,x:0)}}");
+        assert_eq!(usage("
+            { user { name ...Bar } }
+            fragment Bar on User { asd }
+            fragment Baz on User { jkl }
+        ", None), "fragment Bar on User{asd}{user{name...Bar}}");
+    }
+
+    #[test]
+    fn usage_reporting_full() {
+        let query = r#"
+            query Foo($b: Int, $a: Boolean) {
+                user(name: "hello", age: 5) {
+                    ...Bar
+                    ... on User { hello bee }
+                    tz
+                    aliased: name
+                }
+            }
+            query Other { other }
+            fragment Baz on User { asd }
+            fragment Bar on User { age @skip(if: $a) ...Nested }
+            fragment Nested on User { blah }
+        "#;
+        assert_eq!(usage(query, Some("Foo")),
+            "fragment Bar on User{age@skip(if:$a)...Nested}\
+             fragment Nested on User{blah}\
+             query Foo($a:Boolean,$b:Int)\
+             {user(age:0,name:\"\"){name tz...Bar...on User{bee hello}}}");
+        assert_eq!(usage(query, Some("Other")), "query Other{other}");
+        let doc = parse_query::<&str>(query).unwrap();
+        assert!(usage_reporting_signature(&doc, None).is_err());
+    }
+}
@@ -0,0 +1,199 @@
+use std::mem;
+
+use crate::query::ast::*;
+use crate::schema;
+use crate::schema::index::{type_name, SchemaIndex};
+
+
+/// Removes redundant inline fragments, merging their selections into the
+/// enclosing selection set
+///
+/// Inline fragment is redundant if it has no directives and either has no
+/// type condition, or the condition is the enclosing type or its supertype
+/// (an interface it implements or a union containing it), so it always
+/// matches:
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::{parse_query, parse_schema};
+/// use edb_graphql_parser::query::simplify_fragments;
+///
+/// let schema = parse_schema::<&str>("
+///     interface Node { id: ID }
+///     type User implements Node { id: ID, name: String }
+///     type Query { me: User, node: Node }
+/// ").unwrap();
+/// let doc = parse_query::<&str>("{
+///     me { ... on Node { id } ... on User { name } }
+///     node { ... on User { name } }
+/// }").unwrap();
+/// assert_eq!(simplify_fragments(doc, &schema).to_string(), "\
+/// {
+///   me {
+///     id
+///     name
+///   }
+///   node {
+///     ... on User {
+///       name
+///     }
+///   }
+/// }
+/// ");
+/// ```
+///
+/// Selections are not deduplicated, and fragments within fields unknown to
+/// the schema are only removed if they have no type condition.
+pub fn simplify_fragments<'a, 's, T, S>(mut doc: Document<'a, T>,
+    schema: &schema::Document<'s, S>)
+    -> Document<'a, T>
+    where T: Text<'a>,
+          S: Text<'s>,
+{
+    let index = SchemaIndex::new(schema);
+    for def in &mut doc.definitions {
+        match *def {
+            Definition::Operation(ref mut op) => {
+                let root = match op.kind {
+                    OperationKind::ImplicitQuery | OperationKind::Query => {
+                        index.query_type()
+                    }
+                    OperationKind::Mutation => index.mutation_type(),
+                    OperationKind::Subscription => index.subscription_type(),
+                };
+                selection_set(&index, root, &mut op.selection_set);
+            }
+            Definition::Fragment(ref mut frag) => {
+                let TypeCondition::On(ref name) = frag.type_condition;
+                selection_set(&index, Some(name.as_ref()),
+                              &mut frag.selection_set);
+            }
+        }
+    }
+    doc
+}
+
+fn selection_set<'a, 's, T, S>(index: &SchemaIndex<'_, 's, S>,
+    parent: Option<&str>, set: &mut SelectionSet<'a, T>)
+    where T: Text<'a>,
+          S: Text<'s>,
+{
+    for item in mem::take(&mut set.items) {
+        match item {
+            Selection::Field(mut field) => {
+                let field_type = parent
+                    .and_then(|name| index.type_by_name(name))
+                    .and_then(|entry| entry.field(field.name.as_ref()))
+                    .map(|f| type_name(&f.field_type));
+                selection_set(index, field_type, &mut field.selection_set);
+                set.items.push(Selection::Field(field));
+            }
+            Selection::InlineFragment(mut frag) => {
+                let condition = frag.type_condition.as_ref()
+                    .map(|TypeCondition::On(name)| name.as_ref());
+                let redundant = frag.directives.is_empty() &&
+                    always_matches(index, parent, condition);
+                // selections of the redundant fragment are merged into the
+                // parent, so they are checked against the narrower type
+                let inner = if redundant { parent } else {
+                    condition.or(parent)
+                };
+                selection_set(index, inner, &mut frag.selection_set);
+                if redundant {
+                    set.items.extend(frag.selection_set.items);
+                } else {
+                    set.items.push(Selection::InlineFragment(frag));
+                }
+            }
+            spread @ Selection::FragmentSpread(_) => set.items.push(spread),
+        }
+    }
+}
+
+fn always_matches<'s, S>(index: &SchemaIndex<'_, 's, S>,
+    parent: Option<&str>, condition: Option<&str>)
+    -> bool
+    where S: Text<'s>,
+{
+    let (parent, condition) = match (parent, condition) {
+        (_, None) => return true,
+        (Some(parent), Some(condition)) => (parent, condition),
+        (None, Some(_)) => return false,
+    };
+    let entry = match index.type_by_name(parent) {
+        Some(entry) => entry,
+        None => return false,
+    };
+    // types which happen to have the same possible types are not
+    // considered, as adding a type to the schema changes that
+    parent == condition || entry.interfaces.contains(&condition) ||
+        index.type_by_name(condition)
+            .map(|c| c.members.contains(&parent))
+            .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{parse_query, parse_schema};
+    use super::simplify_fragments;
+
+    const SCHEMA: &str = "
+        interface Node { id: ID }
+        interface Named { name: String }
+        type User implements Node & Named {
+            id: ID, name: String, friends: [User]
+        }
+        type Group implements Node { id: ID }
+        union Entity = User | Group
+        type Query { me: User, node: Node, entity: Entity }
+        type Mutation { rename: User }
+    ";
+
+    fn simplify(query: &str) -> String {
+        let schema = parse_schema::<&str>(SCHEMA).unwrap();
+        simplify_fragments(parse_query::<&str>(query).unwrap(), &schema)
+            .to_string()
+    }
+
+    #[test]
+    fn redundant() {
+        assert_eq!(simplify("{
+            ... { me { id } }
+            ... on Query { node { ... on Node { ... on Node { id } } } }
+            me { ... on Entity { ... on Named { name } } }
+        }"), simplify("{ me { id } node { id } me { name } }"));
+        assert_eq!(simplify("mutation { ... on Mutation { rename { id } } }"),
+                   "mutation {\n  rename {\n    id\n  }\n}\n");
+        assert_eq!(simplify("fragment F on User { ... on Node { id } }"),
+                   "fragment F on User {\n  id\n}\n");
+    }
+
+    #[test]
+    fn kept() {
+        let query = "\
+{
+  node {
+    ... on User {
+      friends {
+        ... on Named @include(if: true) {
+          name
+        }
+      }
+    }
+  }
+  entity {
+    ... on Node {
+      id
+    }
+    ...F
+  }
+  unknown {
+    ... on User {
+      id
+    }
+  }
+}
+";
+        assert_eq!(simplify(query), query);
+    }
+}
@@ -0,0 +1,474 @@
+use std::hash::{Hash, Hasher};
+
+use crate::common::DirectiveArgument;
+use crate::query::ast::*;
+
+
+/// Node compared and hashed by its contents, ignoring the positions
+///
+/// Nodes that differ only in the positions are the ones that print the
+/// same, i.e. the same query written with different formatting and
+/// comments. Names of operations and of fragments, the order of fields,
+/// arguments and definitions are all significant.
+pub trait Structural {
+    /// Feeds the contents of the node into the hasher
+    fn structural_hash<H: Hasher>(&self, state: &mut H);
+    /// Compares the contents of the nodes
+    fn structural_eq(&self, other: &Self) -> bool;
+}
+
+/// Wrapper making a node usable as a key of `HashMap` and `HashSet`, see
+/// `Structural`
+///
+/// Works both for owned nodes and references, e.g. for caching plans of
+/// the selection sets:
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use std::collections::HashMap;
+/// use edb_graphql_parser::parse_query;
+/// use edb_graphql_parser::query::IgnorePositions;
+///
+/// let mut plans = HashMap::new();
+/// let doc = parse_query::<String>("{ user { id } }").unwrap();
+/// plans.insert(IgnorePositions(doc), "plan");
+///
+/// let other = parse_query::<String>("# cached\n{\n  user {\n    id\n  }\n}")
+///     .unwrap();
+/// assert_eq!(plans.get(&IgnorePositions(other)), Some(&"plan"));
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct IgnorePositions<N>(pub N);
+
+impl<N: Structural> PartialEq for IgnorePositions<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.structural_eq(&other.0)
+    }
+}
+
+impl<N: Structural> Eq for IgnorePositions<N> {}
+
+impl<N: Structural> Hash for IgnorePositions<N> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.structural_hash(state)
+    }
+}
+
+impl<N: Structural> Structural for &N {
+    fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        (**self).structural_hash(state)
+    }
+    fn structural_eq(&self, other: &Self) -> bool {
+        (**self).structural_eq(*other)
+    }
+}
+
+impl<N: Structural> Structural for Box<N> {
+    fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        (**self).structural_hash(state)
+    }
+    fn structural_eq(&self, other: &Self) -> bool {
+        (**self).structural_eq(other)
+    }
+}
+
+impl<N: Structural> Structural for Vec<N> {
+    fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        self.len().hash(state);
+        for item in self {
+            item.structural_hash(state);
+        }
+    }
+    fn structural_eq(&self, other: &Self) -> bool {
+        self.len() == other.len() &&
+            self.iter().zip(other).all(|(a, b)| a.structural_eq(b))
+    }
+}
+
+impl<N: Structural> Structural for Option<N> {
+    fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        match *self {
+            Some(ref value) => {
+                1u8.hash(state);
+                value.structural_hash(state);
+            }
+            None => 0u8.hash(state),
+        }
+    }
+    fn structural_eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Some(a), Some(b)) => a.structural_eq(b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Names are bare `T::Value`, so they are hashed by these helpers
+fn hash_name<N: AsRef<str>, H: Hasher>(name: &N, state: &mut H) {
+    name.as_ref().hash(state);
+}
+
+fn hash_opt_name<N: AsRef<str>, H: Hasher>(name: &Option<N>, state: &mut H) {
+    name.as_ref().map(|n| n.as_ref()).hash(state);
+}
+
+/// Default value without its position
+fn default_value<'b, 'a, T>(var: &'b VariableDefinition<'a, T>)
+    -> Option<&'b Value<'a, T>>
+    where T: Text<'a>,
+{
+    var.default_value.as_ref().map(|d| &d.value)
+}
+
+impl<'a, T> Structural for (T::Value, Value<'a, T>)
+    where T: Text<'a>,
+{
+    fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        hash_name(&self.0, state);
+        self.1.structural_hash(state);
+    }
+    fn structural_eq(&self, other: &Self) -> bool {
+        self.0 == other.0 && self.1.structural_eq(&other.1)
+    }
+}
+
+impl<'a, T> Structural for Value<'a, T>
+    where T: Text<'a>,
+{
+    fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match *self {
+            Value::Variable(ref name) | Value::Enum(ref name) => {
+                hash_name(name, state)
+            }
+            Value::Int(ref num) => num.as_bigint().hash(state),
+            Value::Float(ref num) => num.hash(state),
+            Value::String(ref value) => value.as_ref().hash(state),
+            Value::Boolean(value) => value.hash(state),
+            Value::Null => {}
+            Value::List(ref items) => items.structural_hash(state),
+            Value::Object(ref fields) => fields.structural_hash(state),
+        }
+    }
+    fn structural_eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Variable(a), Value::Variable(b)) |
+            (Value::Enum(a), Value::Enum(b)) => a == b,
+            (Value::Int(a), Value::Int(b)) => a == b,
+            (Value::Float(a), Value::Float(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Boolean(a), Value::Boolean(b)) => a == b,
+            (Value::Null, Value::Null) => true,
+            (Value::List(a), Value::List(b)) => a.structural_eq(b),
+            (Value::Object(a), Value::Object(b)) => a.structural_eq(b),
+            _ => false,
+        }
+    }
+}
+
+impl<'a, T> Structural for Type<'a, T>
+    where T: Text<'a>,
+{
+    fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match *self {
+            Type::NamedType(ref name) => hash_name(name, state),
+            Type::ListType(ref inner) | Type::NonNullType(ref inner) => {
+                inner.structural_hash(state)
+            }
+        }
+    }
+    fn structural_eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Type::NamedType(a), Type::NamedType(b)) => a == b,
+            (Type::ListType(a), Type::ListType(b)) |
+            (Type::NonNullType(a), Type::NonNullType(b)) => {
+                a.structural_eq(b)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<'a, T> Structural for DirectiveArgument<'a, T>
+    where T: Text<'a>,
+{
+    fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        hash_name(&self.name, state);
+        self.value.structural_hash(state);
+    }
+    fn structural_eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.value.structural_eq(&other.value)
+    }
+}
+
+impl<'a, T> Structural for Directive<'a, T>
+    where T: Text<'a>,
+{
+    fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        hash_name(&self.name, state);
+        self.arguments.structural_hash(state);
+    }
+    fn structural_eq(&self, other: &Self) -> bool {
+        self.name == other.name &&
+            self.arguments.structural_eq(&other.arguments)
+    }
+}
+
+impl<'a, T> Structural for Document<'a, T>
+    where T: Text<'a>,
+{
+    fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        self.definitions.structural_hash(state);
+    }
+    fn structural_eq(&self, other: &Self) -> bool {
+        self.definitions.structural_eq(&other.definitions)
+    }
+}
+
+impl<'a, T> Structural for Definition<'a, T>
+    where T: Text<'a>,
+{
+    fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match *self {
+            Definition::Operation(ref op) => op.structural_hash(state),
+            Definition::Fragment(ref frag) => frag.structural_hash(state),
+        }
+    }
+    fn structural_eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Definition::Operation(a), Definition::Operation(b)) => {
+                a.structural_eq(b)
+            }
+            (Definition::Fragment(a), Definition::Fragment(b)) => {
+                a.structural_eq(b)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<'a, T> Structural for Operation<'a, T>
+    where T: Text<'a>,
+{
+    /// The place to insert variables (`insert_variables`) is derived from
+    /// the other fields and is ignored
+    fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        self.kind.hash(state);
+        hash_opt_name(&self.name, state);
+        self.variable_definitions.structural_hash(state);
+        self.directives.structural_hash(state);
+        self.selection_set.structural_hash(state);
+    }
+    fn structural_eq(&self, other: &Self) -> bool {
+        self.kind == other.kind &&
+            self.name == other.name &&
+            self.variable_definitions
+                .structural_eq(&other.variable_definitions) &&
+            self.directives.structural_eq(&other.directives) &&
+            self.selection_set.structural_eq(&other.selection_set)
+    }
+}
+
+impl<'a, T> Structural for FragmentDefinition<'a, T>
+    where T: Text<'a>,
+{
+    fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        hash_name(&self.name, state);
+        self.variable_definitions.structural_hash(state);
+        self.type_condition.structural_hash(state);
+        self.directives.structural_hash(state);
+        self.selection_set.structural_hash(state);
+    }
+    fn structural_eq(&self, other: &Self) -> bool {
+        self.name == other.name &&
+            self.variable_definitions
+                .structural_eq(&other.variable_definitions) &&
+            self.type_condition.structural_eq(&other.type_condition) &&
+            self.directives.structural_eq(&other.directives) &&
+            self.selection_set.structural_eq(&other.selection_set)
+    }
+}
+
+impl<'a, T> Structural for VariableDefinition<'a, T>
+    where T: Text<'a>,
+{
+    fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        hash_name(&self.name, state);
+        self.var_type.structural_hash(state);
+        default_value(self).structural_hash(state);
+        self.directives.structural_hash(state);
+    }
+    fn structural_eq(&self, other: &Self) -> bool {
+        self.name == other.name &&
+            self.var_type.structural_eq(&other.var_type) &&
+            default_value(self).structural_eq(&default_value(other)) &&
+            self.directives.structural_eq(&other.directives)
+    }
+}
+
+impl<'a, T> Structural for SelectionSet<'a, T>
+    where T: Text<'a>,
+{
+    fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        self.items.structural_hash(state);
+    }
+    fn structural_eq(&self, other: &Self) -> bool {
+        self.items.structural_eq(&other.items)
+    }
+}
+
+impl<'a, T> Structural for Selection<'a, T>
+    where T: Text<'a>,
+{
+    fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match *self {
+            Selection::Field(ref field) => field.structural_hash(state),
+            Selection::FragmentSpread(ref spread) => {
+                spread.structural_hash(state)
+            }
+            Selection::InlineFragment(ref frag) => {
+                frag.structural_hash(state)
+            }
+        }
+    }
+    fn structural_eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Selection::Field(a), Selection::Field(b)) => a.structural_eq(b),
+            (Selection::FragmentSpread(a), Selection::FragmentSpread(b)) => {
+                a.structural_eq(b)
+            }
+            (Selection::InlineFragment(a), Selection::InlineFragment(b)) => {
+                a.structural_eq(b)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<'a, T> Structural for Field<'a, T>
+    where T: Text<'a>,
+{
+    fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        hash_opt_name(&self.alias, state);
+        hash_name(&self.name, state);
+        self.arguments.structural_hash(state);
+        self.nullability.hash(state);
+        self.directives.structural_hash(state);
+        self.selection_set.structural_hash(state);
+    }
+    fn structural_eq(&self, other: &Self) -> bool {
+        self.alias == other.alias &&
+            self.name == other.name &&
+            self.arguments.structural_eq(&other.arguments) &&
+            self.nullability == other.nullability &&
+            self.directives.structural_eq(&other.directives) &&
+            self.selection_set.structural_eq(&other.selection_set)
+    }
+}
+
+impl<'a, T> Structural for FragmentSpread<'a, T>
+    where T: Text<'a>,
+{
+    fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        hash_name(&self.fragment_name, state);
+        self.arguments.structural_hash(state);
+        self.directives.structural_hash(state);
+    }
+    fn structural_eq(&self, other: &Self) -> bool {
+        self.fragment_name == other.fragment_name &&
+            self.arguments.structural_eq(&other.arguments) &&
+            self.directives.structural_eq(&other.directives)
+    }
+}
+
+impl<'a, T> Structural for InlineFragment<'a, T>
+    where T: Text<'a>,
+{
+    fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        self.type_condition.structural_hash(state);
+        self.directives.structural_hash(state);
+        self.selection_set.structural_hash(state);
+    }
+    fn structural_eq(&self, other: &Self) -> bool {
+        self.type_condition.structural_eq(&other.type_condition) &&
+            self.directives.structural_eq(&other.directives) &&
+            self.selection_set.structural_eq(&other.selection_set)
+    }
+}
+
+impl<'a, T> Structural for TypeCondition<'a, T>
+    where T: Text<'a>,
+{
+    fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        match *self {
+            TypeCondition::On(ref name) => hash_name(name, state),
+        }
+    }
+    fn structural_eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (TypeCondition::On(a), TypeCondition::On(b)) => a == b,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    use crate::parse_query;
+    use crate::query::Document;
+    use super::IgnorePositions;
+
+    fn hash<'a>(doc: &Document<'a, &'a str>) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        IgnorePositions(doc).hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn positions_ignored() {
+        let a = parse_query::<&str>("query Q($x: Int = 1) @d(a: 1) {
+            a: f(x: $x, y: {z: [1, \"s\"]}) {
+                ...F ... on T @skip(if: $x) { b }
+            }
+        } fragment F on T { c }").unwrap();
+        let b = parse_query::<&str>("
+            # comment
+            query Q($x:Int=1)@d(a:1){a:f(x:$x,y:{z:[1,\"s\"]}){...F,
+            ...on T@skip(if:$x){b}}}
+            fragment F on T{c}").unwrap();
+        assert_ne!(a, b);
+        assert_eq!(IgnorePositions(&a), IgnorePositions(&b));
+        assert_eq!(hash(&a), hash(&b));
+        let op_a = &a.operations().next().unwrap().selection_set;
+        let op_b = &b.operations().next().unwrap().selection_set;
+        assert_eq!(IgnorePositions(op_a), IgnorePositions(op_b));
+    }
+
+    #[test]
+    fn differences() {
+        let sources = [
+            "{ a }", "query { a }", "query Q { a }", "{ b: a }", "{ a(x: 1) }",
+            "{ a(x: 2) }", "{ a(y: 1) }", "{ a @d }", "{ a { b } }",
+            "{ ... on T { a } }", "{ ... { a } }", "{ ...F }", "{ a b }",
+            "{ b a }", "query($x: Int) { a }", "query($x: Int = 1) { a }",
+            "fragment F on T { a }", "{ a } fragment F on T { a }",
+        ];
+        let docs = sources.iter()
+            .map(|s| parse_query::<&str>(s).unwrap())
+            .collect::<Vec<_>>();
+        for (i, a) in docs.iter().enumerate() {
+            for (j, b) in docs.iter().enumerate() {
+                let message = format!("{} vs {}", sources[i], sources[j]);
+                assert_eq!(IgnorePositions(a) == IgnorePositions(b), i == j,
+                           "{}", message);
+                assert_eq!(hash(a) == hash(b), i == j, "{}", message);
+            }
+        }
+    }
+}
@@ -0,0 +1,264 @@
+use combine::{Positioned, StreamOnce};
+use combine::easy::Error;
+
+use crate::options::ParserOptions;
+use crate::position::{LineIndex, Pos};
+use crate::query::ast::*;
+use crate::query::error::ParseError;
+use crate::query::grammar::parse_query_with_options;
+use crate::tokenizer::{Kind, TokenStream};
+
+
+const KEYWORDS: &[&str] = &["query", "mutation", "subscription", "fragment"];
+
+/// Region of the source that can't be parsed, see `parse_query_tolerant`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorNode {
+    /// Start and end of the region
+    pub span: (Pos, Pos),
+    /// The first error in the region
+    pub error: ParseError,
+}
+
+/// Definition of a document parsed by `parse_query_tolerant`
+#[derive(Debug, Clone, PartialEq)]
+pub enum TolerantDefinition<'a, T: Text<'a>> {
+    Definition(Definition<'a, T>),
+    Error(ErrorNode),
+}
+
+/// Document parsed by `parse_query_tolerant`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TolerantDocument<'a, T: Text<'a>> {
+    /// Definitions and error nodes in the order of the source
+    pub definitions: Vec<TolerantDefinition<'a, T>>,
+}
+
+impl<'a, T> TolerantDocument<'a, T>
+    where T: Text<'a>,
+{
+    /// Returns the error nodes
+    pub fn errors(&self) -> Vec<&ErrorNode> {
+        self.definitions.iter().filter_map(|def| match *def {
+            TolerantDefinition::Error(ref error) => Some(error),
+            TolerantDefinition::Definition(_) => None,
+        }).collect()
+    }
+
+    /// Returns `true` if the whole source was parsed
+    pub fn is_complete(&self) -> bool {
+        self.definitions.iter()
+            .all(|def| matches!(*def, TolerantDefinition::Definition(_)))
+    }
+
+    /// Returns the document consisting of the parsed definitions
+    pub fn into_document(self) -> Document<'a, T> {
+        let definitions = self.definitions.into_iter()
+            .filter_map(|def| match def {
+                TolerantDefinition::Definition(def) => Some(def),
+                TolerantDefinition::Error(_) => None,
+            })
+            .collect();
+        Document { definitions }
+    }
+}
+
+/// Parses a query that may be invalid, e.g. while it's being edited
+///
+/// Never fails. The source is split into regions at the tokens that start
+/// definitions: `query`, `mutation`, `subscription`, `fragment` and `{`
+/// outside of any brackets, and also at these tokens at the start of
+/// a line anywhere, so a definition that is not closed yet doesn't swallow
+/// the following ones. A region that doesn't parse becomes an error node,
+/// the rest of the document is still available. Positions are the same as
+/// for `parse_query` of the whole source.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::query::parse_query_tolerant;
+///
+/// let doc = parse_query_tolerant::<&str>("
+/// query A { user(id: ) { name
+/// fragment F on User { id }
+/// ");
+/// let errors = doc.errors();
+/// assert_eq!(errors.len(), 1);
+/// assert_eq!(errors[0].span.0.to_string(), "2:1");
+/// assert_eq!(errors[0].error.span().0.to_string(), "2:20");
+/// let valid = doc.into_document();
+/// assert_eq!(valid.to_string(), "fragment F on User {\n  id\n}\n");
+/// ```
+pub fn parse_query_tolerant<'a, T>(source: &'a str) -> TolerantDocument<'a, T>
+    where T: Text<'a>,
+{
+    let index = LineIndex::new(source);
+    let mut definitions = Vec::new();
+    for (start, end, position, token) in regions(source, &index) {
+        let text = &source[start..end];
+        let mut options = ParserOptions::default();
+        options.start_position(position);
+        match parse_query_with_options(text, &options) {
+            Ok(doc) => {
+                definitions.extend(doc.definitions.into_iter()
+                    .map(TolerantDefinition::Definition));
+            }
+            Err(error) => {
+                let end = start + text.trim_end().len();
+                let end = Pos {
+                    token,
+                    .. index.position(end).expect("valid offset")
+                };
+                definitions.push(TolerantDefinition::Error(ErrorNode {
+                    span: (position, end),
+                    error,
+                }));
+            }
+        }
+    }
+    TolerantDocument { definitions }
+}
+
+/// Returns byte ranges, start positions and token index at the end of the
+/// regions having tokens
+fn regions(source: &str, index: &LineIndex)
+    -> Vec<(usize, usize, Pos, usize)>
+{
+    let mut starts = Vec::new();
+    let mut offset = 0;
+    let mut position = Pos { line: 1, column: 1, character: 0, token: 0 };
+    let end_token = loop {
+        let mut tokens = TokenStream::starting_at(&source[offset..], position);
+        let mut depth = 0usize;
+        let mut previous = None;
+        let segment = starts.len();
+        let failed = loop {
+            let pos = tokens.position();
+            let at = offset + tokens.offset();
+            let tok = match tokens.uncons() {
+                Ok(tok) => tok,
+                Err(ref e) if *e == Error::end_of_input() => break None,
+                Err(_) => break Some(at),
+            };
+            let starts_definition = match tok.kind {
+                Kind::Name => KEYWORDS.contains(&tok.value),
+                Kind::Punctuator => tok.value == "{",
+                _ => false,
+            };
+            let at_top = depth == 0 && (tok.value != "{" ||
+                                        matches!(previous, None | Some("}")));
+            if starts.len() == segment ||
+                starts_definition && (at_top || pos.column == 1)
+            {
+                starts.push((at, pos));
+                depth = 0;
+            }
+            match tok.value {
+                "{" | "(" | "[" if tok.kind == Kind::Punctuator => depth += 1,
+                "}" | ")" | "]" if tok.kind == Kind::Punctuator => {
+                    depth = depth.saturating_sub(1)
+                }
+                _ => {}
+            }
+            previous = Some(tok.value);
+        };
+        let failed = match failed {
+            Some(failed) => failed,
+            None => break tokens.position().token,
+        };
+        let token = tokens.position().token;
+        if starts.len() == segment || depth == 0 && previous == Some("}") {
+            // the invalid token is not a part of a definition
+            starts.push((failed, Pos {
+                token,
+                .. index.position(failed).expect("valid offset")
+            }));
+        }
+        // continue at the next line that starts a definition
+        let restart = source[failed..].match_indices('\n')
+            .map(|(idx, _)| failed + idx + 1)
+            .find(|&line| starts_definition(&source[line..]));
+        match restart {
+            Some(restart) => {
+                offset = restart;
+                position = Pos {
+                    token,
+                    .. index.position(restart).expect("valid offset")
+                };
+            }
+            None => break token,
+        }
+    };
+    let mut regions = Vec::with_capacity(starts.len());
+    for (idx, &(start, position)) in starts.iter().enumerate() {
+        let (end, token) = starts.get(idx + 1)
+            .map_or((source.len(), end_token), |&(end, pos)| (end, pos.token));
+        regions.push((start, end, position, token));
+    }
+    regions
+}
+
+/// Returns `true` if the line starts with a token starting a definition
+fn starts_definition(line: &str) -> bool {
+    line.starts_with('{') || KEYWORDS.iter().any(|keyword| {
+        line.strip_prefix(keyword).is_some_and(|rest| {
+            !rest.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_')
+        })
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse_query;
+    use crate::query::Definition;
+    use super::{parse_query_tolerant, TolerantDefinition};
+
+    fn kinds(source: &str) -> Vec<String> {
+        parse_query_tolerant::<&str>(source).definitions.iter()
+            .map(|def| match *def {
+                TolerantDefinition::Definition(ref def) => {
+                    def.to_string().lines().next().unwrap().to_string()
+                }
+                TolerantDefinition::Error(ref err) => {
+                    format!("error {}-{}", err.span.0, err.span.1)
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn valid() {
+        let source = "query A($x: In = {a: 1}) @d(x: {y: 2}) { a }
+                      { b } fragment F on T { c } mutation { d }";
+        let doc = parse_query_tolerant::<&str>(source);
+        assert!(doc.is_complete());
+        assert_eq!(doc.into_document(), parse_query(source).unwrap());
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(kinds("query A { a\nquery B { b }\n{ c } { d( }"), [
+            "error 1:1-1:12", "query B {", "{", "error 3:7-3:13",
+        ]);
+        assert_eq!(kinds("{ a \"unterminated }\n{ x } ^\n{ b }"), [
+            "error 1:1-1:20", "{", "error 2:7-2:8", "{",
+        ]);
+        assert_eq!(kinds("^^ query Q { a }"), ["error 1:1-1:17"]);
+        assert_eq!(kinds("# only comments\n"), Vec::<String>::new());
+        assert_eq!(kinds("{ a { b }"), ["error 1:1-1:10"]);
+    }
+
+    #[test]
+    fn positions() {
+        let source = "query A { a(x: ) }\n{\n  b\n}";
+        let doc = parse_query_tolerant::<&str>(source);
+        let err = doc.errors()[0];
+        assert_eq!(err.span.1.token, 9);
+        let doc = doc.into_document();
+        if let Definition::Operation(ref op) = doc.definitions[0] {
+            assert_eq!(op.position.to_string(), "2:1");
+            assert_eq!(op.position.token, 9);
+            assert_eq!(op.selection_set.span.1.to_string(), "4:1");
+            assert_eq!(op.selection_set.span.1.token, 11);
+        }
+    }
+}
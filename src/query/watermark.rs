@@ -0,0 +1,250 @@
+use std::collections::BTreeMap;
+
+use combine::{parser, Parser};
+
+use crate::common::{directives, Directive, DirectiveArgument, Value};
+use crate::position::Pos;
+use crate::query::ast::*;
+use crate::query::error::ParseError;
+use crate::query::grammar::parse_query;
+use crate::tokenizer::TokenStream;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Carrier {
+    Directive,
+    Comment,
+}
+
+/// Identifying information injected into operations
+///
+/// The watermark is a set of string fields (e.g. client name and build
+/// hash) which is either added as a directive on every operation:
+///
+/// ```graphql
+/// query Q @client(build: "1a2b3c", name: "ios") { ... }
+/// ```
+///
+/// Or as a comment at the start of the document, using the same syntax:
+///
+/// ```graphql
+/// # @client(build: "1a2b3c", name: "ios")
+/// query Q { ... }
+/// ```
+///
+/// Directive survives any proxies that reformat the query, but requires
+/// the server to accept the directive. Comments are ignored by every
+/// server but are lost on reformatting.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::query::Watermark;
+///
+/// let mut mark = Watermark::directive("client");
+/// mark.field("name", "ios").field("build", "1a2b3c");
+/// let query = mark.inject("query Q { a }").unwrap();
+/// assert_eq!(query,
+///     "query Q @client(build: \"1a2b3c\", name: \"ios\") {\n  a\n}\n");
+/// let fields = mark.extract(&query).unwrap();
+/// assert_eq!(fields["build"], "1a2b3c");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Watermark {
+    carrier: Carrier,
+    name: String,
+    fields: BTreeMap<String, String>,
+}
+
+impl Watermark {
+    /// Create a watermark carried by directive `@name`
+    pub fn directive(name: &str) -> Watermark {
+        Watermark {
+            carrier: Carrier::Directive,
+            name: name.into(),
+            fields: BTreeMap::new(),
+        }
+    }
+
+    /// Create a watermark carried by a `# @name(...)` comment
+    pub fn comment(name: &str) -> Watermark {
+        Watermark {
+            carrier: Carrier::Comment,
+            name: name.into(),
+            fields: BTreeMap::new(),
+        }
+    }
+
+    /// Set the field of the watermark
+    pub fn field(&mut self, key: &str, value: &str) -> &mut Self {
+        self.fields.insert(key.into(), value.into());
+        self
+    }
+
+    fn make_directive<'a, T>(&'a self) -> Directive<'a, T>
+        where T: Text<'a>,
+    {
+        Directive {
+            position: Pos::default(),
+            name: self.name.as_str().into(),
+            arguments: self.fields.iter().map(|(key, value)| {
+                DirectiveArgument {
+                    name: key.as_str().into(),
+                    value: Value::String(value.clone().into()),
+                    value_position: Pos::default(),
+                }
+            }).collect(),
+            arguments_syntax: None,
+        }
+    }
+
+    /// Add the watermark directive to every operation of the document
+    ///
+    /// Existing directive with the same name is replaced. Does nothing for
+    /// comment watermarks, as comments aren't stored in the AST.
+    pub fn apply<'a, T>(&'a self, doc: &mut Document<'a, T>)
+        where T: Text<'a>,
+    {
+        if self.carrier != Carrier::Directive {
+            return;
+        }
+        for def in &mut doc.definitions {
+            if let Definition::Operation(ref mut op) = *def {
+                if op.kind == OperationKind::ImplicitQuery {
+                    // shorthand syntax can't have directives
+                    op.kind = OperationKind::Query;
+                }
+                op.directives.retain(|d| d.name.as_ref() != self.name);
+                op.directives.push(self.make_directive());
+            }
+        }
+    }
+
+    /// Formats the document with the watermark
+    ///
+    /// For directive watermarks `apply` must be called on the document
+    /// first.
+    pub fn format<'a, T>(&self, doc: &Document<'a, T>) -> String
+        where T: Text<'a>,
+    {
+        match self.carrier {
+            Carrier::Directive => doc.to_string(),
+            Carrier::Comment => {
+                format!("# {}\n{}", self.make_directive::<&str>(), doc)
+            }
+        }
+    }
+
+    /// Parses the query, adds the watermark and formats it
+    pub fn inject(&self, query: &str) -> Result<String, ParseError> {
+        let mut doc = parse_query::<&str>(query)?;
+        self.apply(&mut doc);
+        Ok(self.format(&doc))
+    }
+
+    /// Returns fields of the watermark found in the document
+    ///
+    /// Only directive watermarks can be found in the AST, for comment
+    /// watermarks use `extract`.
+    pub fn extract_from<'a, T>(&self, doc: &Document<'a, T>)
+        -> Option<BTreeMap<String, String>>
+        where T: Text<'a>,
+    {
+        if self.carrier != Carrier::Directive {
+            return None;
+        }
+        doc.definitions.iter()
+            .filter_map(|def| match *def {
+                Definition::Operation(ref op) => Some(op),
+                Definition::Fragment(_) => None,
+            })
+            .flat_map(|op| &op.directives)
+            .find(|dir| dir.name.as_ref() == self.name)
+            .map(fields)
+    }
+
+    /// Returns fields of the watermark found in the query text
+    ///
+    /// Comment is only looked for in the leading comments of the query.
+    /// Returns `None` if the query contains no watermark or, for directive
+    /// watermarks, if the query can't be parsed.
+    pub fn extract(&self, query: &str) -> Option<BTreeMap<String, String>> {
+        match self.carrier {
+            Carrier::Directive => {
+                self.extract_from(&parse_query::<&str>(query).ok()?)
+            }
+            Carrier::Comment => {
+                query.lines()
+                    .map(|line| line.trim())
+                    .take_while(|line| line.is_empty() || line.starts_with('#'))
+                    .filter_map(|line| line.strip_prefix('#'))
+                    .filter_map(|comment| {
+                        let mut stream = TokenStream::new(comment);
+                        parser(directives::<&str>).parse_stream(&mut stream)
+                            .ok()
+                            .map(|(dirs, _)| dirs)
+                    })
+                    .flatten()
+                    .find(|dir| dir.name == self.name)
+                    .map(|dir| fields(&dir))
+            }
+        }
+    }
+}
+
+fn fields<'a, T>(dir: &Directive<'a, T>) -> BTreeMap<String, String>
+    where T: Text<'a>,
+{
+    dir.arguments.iter().map(|arg| {
+        let value = match arg.value {
+            Value::String(ref s) => s.as_ref().to_string(),
+            ref other => other.to_string(),
+        };
+        (arg.name.as_ref().to_string(), value)
+    }).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse_query;
+    use super::Watermark;
+
+    #[test]
+    fn directive() {
+        let mut mark = Watermark::directive("client");
+        mark.field("id", "web \"beta\"");
+        let query = mark.inject("{ a } mutation M @client(id: 1) { b }")
+            .unwrap();
+        assert_eq!(query, "\
+            query @client(id: \"web \\\"beta\\\"\") {\n  a\n}\n\n\
+            mutation M @client(id: \"web \\\"beta\\\"\") {\n  b\n}\n");
+        let fields = mark.extract(&query).unwrap();
+        assert_eq!(fields["id"], "web \"beta\"");
+        assert_eq!(mark.extract("{ a }"), None);
+    }
+
+    #[test]
+    fn apply_to_ast() {
+        let mut mark = Watermark::directive("client");
+        mark.field("build", "abc");
+        let mut doc = parse_query::<String>("query Q @live { a }").unwrap();
+        mark.apply(&mut doc);
+        assert_eq!(mark.extract_from(&doc).unwrap()["build"], "abc");
+        assert_eq!(doc.to_string(),
+                   "query Q @live @client(build: \"abc\") {\n  a\n}\n");
+    }
+
+    #[test]
+    fn comment() {
+        let mut mark = Watermark::comment("client");
+        mark.field("id", "ios").field("build", "abc");
+        let query = mark.inject("query Q { a }").unwrap();
+        assert_eq!(query,
+            "# @client(build: \"abc\", id: \"ios\")\nquery Q {\n  a\n}\n");
+        let fields = mark.extract(&query).unwrap();
+        assert_eq!(fields["id"], "ios");
+        assert_eq!(fields["build"], "abc");
+        assert_eq!(mark.extract("query Q {\n# @client(id: \"x\")\n a }"),
+                   None);
+        assert_eq!(Watermark::comment("other").extract(&query), None);
+    }
+}
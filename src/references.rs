@@ -0,0 +1,461 @@
+//! Finding the references to fragments, variables and schema types
+//!
+//! These are the lookups behind find-references and the checks that an
+//! element can be safely deleted, see `rename` for changing the names.
+//!
+//! ```rust
+//! # extern crate edb_graphql_parser;
+//! use edb_graphql_parser::parse_query;
+//! use edb_graphql_parser::references::{fragment_spreads, variable_usages};
+//!
+//! let doc = parse_query::<&str>("
+//!     query Q($id: ID) { user(id: $id) { ...F } }
+//!     fragment F on User { friends(of: $id) { ...F } }
+//! ").unwrap();
+//! let spreads = fragment_spreads(&doc, "F");
+//! assert_eq!(spreads.len(), 2);
+//! assert_eq!(spreads[0].position.to_string(), "2:43");
+//! let usages = variable_usages(&doc, Some("Q"), "id").unwrap();
+//! assert_eq!(usages.len(), 2);
+//! assert_eq!(usages[1].argument, "of");
+//! ```
+use crate::common::{Directive, Text, Value};
+use crate::position::Pos;
+use crate::query::{Definition, Document, Field, FragmentSpread};
+use crate::query::{OperationError, Selection, SelectionSet};
+use crate::query::used_fragments;
+use crate::schema::{self, SchemaCoordinate};
+
+
+/// Node having the argument a variable is used in
+#[derive(Debug)]
+pub enum ArgumentOf<'x, 'a, T>
+    where T: Text<'a>
+{
+    Field(&'x Field<'a, T>),
+    Directive(&'x Directive<'a, T>),
+    /// Experimental fragment arguments
+    FragmentSpread(&'x FragmentSpread<'a, T>),
+}
+
+/// Usage of a variable, see `variable_usages`
+#[derive(Debug)]
+pub struct VariableUsage<'x, 'a, T>
+    where T: Text<'a>
+{
+    /// Name of the argument having the variable in its value
+    pub argument: &'x str,
+    pub node: ArgumentOf<'x, 'a, T>,
+    /// Position of the value of a directive argument and of the field or
+    /// spread otherwise, as their arguments have no positions
+    pub position: Pos,
+}
+
+/// Kind of the reference to a type, see `type_references`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferenceKind {
+    /// `extend type T`
+    Extension,
+    /// Interface in the `implements` clause
+    Implements,
+    UnionMember,
+    FieldType,
+    /// Type of a field or directive argument
+    ArgumentType,
+    InputFieldType,
+    /// Root operation type in `schema` or `extend schema`
+    SchemaOperation,
+}
+
+/// Reference to a type in a schema, see `type_references`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeReference {
+    pub kind: ReferenceKind,
+    /// Position of the field, argument or definition having the reference
+    pub position: Pos,
+    /// Element having the reference, `None` for the schema definitions
+    pub coordinate: Option<SchemaCoordinate>,
+}
+
+/// Returns the spreads of the fragment in the operations and fragments of
+/// the document, in the document order
+pub fn fragment_spreads<'x, 'a, T>(doc: &'x Document<'a, T>, fragment: &str)
+    -> Vec<&'x FragmentSpread<'a, T>>
+    where T: Text<'a>,
+{
+    let mut result = Vec::new();
+    for def in &doc.definitions {
+        let set = match *def {
+            Definition::Operation(ref op) => &op.selection_set,
+            Definition::Fragment(ref frag) => &frag.selection_set,
+        };
+        spreads(set, fragment, &mut result);
+    }
+    result
+}
+
+/// Returns the usages of the variable in the operation and the fragments
+/// it spreads
+///
+/// `operation` selects the operation as in `Document::operation`. Each
+/// fragment is searched once, however many times it's spread.
+pub fn variable_usages<'x, 'a, T>(doc: &'x Document<'a, T>,
+    operation: Option<&str>, variable: &str)
+    -> Result<Vec<VariableUsage<'x, 'a, T>>, OperationError>
+    where T: Text<'a>,
+{
+    let op = doc.operation(operation)?;
+    let mut usages = Usages { variable, result: Vec::new() };
+    usages.directives(&op.directives);
+    usages.selection_set(&op.selection_set);
+    let fragments = used_fragments(doc, &op.selection_set);
+    for frag in doc.fragments() {
+        if fragments.contains(frag.name.as_ref()) {
+            usages.directives(&frag.directives);
+            usages.selection_set(&frag.selection_set);
+        }
+    }
+    Ok(usages.result)
+}
+
+/// Returns the references to the type in the schema, in the document
+/// order
+///
+/// The definition of the type itself is not included, its extensions are.
+pub fn type_references<'a, T>(doc: &schema::Document<'a, T>, name: &str)
+    -> Vec<TypeReference>
+    where T: Text<'a>,
+{
+    use crate::schema::Definition::*;
+    use crate::schema::TypeDefinition as D;
+    use crate::schema::TypeExtension as E;
+
+    let mut refs = TypeReferences { name, result: Vec::new() };
+    for def in &doc.definitions {
+        match *def {
+            SchemaDefinition(ref s) => {
+                refs.schema(s.position,
+                    &[&s.query, &s.mutation, &s.subscription]);
+            }
+            SchemaExtension(ref s) => {
+                refs.schema(s.position,
+                    &[&s.query, &s.mutation, &s.subscription]);
+            }
+            TypeDefinition(D::Object(ref t)) => {
+                refs.names(ReferenceKind::Implements, t.position, &t.name,
+                           &t.implements_interfaces);
+                refs.fields(&t.name, &t.fields);
+            }
+            TypeDefinition(D::Interface(ref t)) => {
+                refs.names(ReferenceKind::Implements, t.position, &t.name,
+                           &t.implements_interfaces);
+                refs.fields(&t.name, &t.fields);
+            }
+            TypeDefinition(D::Union(ref t)) => {
+                refs.names(ReferenceKind::UnionMember, t.position, &t.name,
+                           &t.types);
+            }
+            TypeDefinition(D::InputObject(ref t)) => {
+                refs.input_fields(&t.name, &t.fields);
+            }
+            TypeDefinition(D::Scalar(_)) | TypeDefinition(D::Enum(_)) => {}
+            TypeExtension(ref ext) => {
+                refs.names(ReferenceKind::Extension, ext.position(),
+                           ext.name(), std::slice::from_ref(ext.name()));
+                match *ext {
+                    E::Object(ref t) => {
+                        refs.names(ReferenceKind::Implements, t.position,
+                                   &t.name, &t.implements_interfaces);
+                        refs.fields(&t.name, &t.fields);
+                    }
+                    E::Interface(ref t) => {
+                        refs.names(ReferenceKind::Implements, t.position,
+                                   &t.name, &t.implements_interfaces);
+                        refs.fields(&t.name, &t.fields);
+                    }
+                    E::Union(ref t) => {
+                        refs.names(ReferenceKind::UnionMember, t.position,
+                                   &t.name, &t.types);
+                    }
+                    E::InputObject(ref t) => {
+                        refs.input_fields(&t.name, &t.fields);
+                    }
+                    E::Scalar(_) | E::Enum(_) => {}
+                }
+            }
+            DirectiveDefinition(ref d) => {
+                for arg in &d.arguments {
+                    refs.push(ReferenceKind::ArgumentType, arg.position,
+                        arg.value_type.innermost_name(),
+                        || SchemaCoordinate::DirectiveArgument {
+                            directive: d.name.as_ref().into(),
+                            argument: arg.name.as_ref().into(),
+                        });
+                }
+            }
+            Custom(_) => {}
+        }
+    }
+    refs.result
+}
+
+fn spreads<'x, 'a, T>(set: &'x SelectionSet<'a, T>, fragment: &str,
+    result: &mut Vec<&'x FragmentSpread<'a, T>>)
+    where T: Text<'a>,
+{
+    for item in &set.items {
+        match *item {
+            Selection::Field(ref field) => {
+                spreads(&field.selection_set, fragment, result);
+            }
+            Selection::FragmentSpread(ref spread) => {
+                if spread.fragment_name.as_ref() == fragment {
+                    result.push(spread);
+                }
+            }
+            Selection::InlineFragment(ref frag) => {
+                spreads(&frag.selection_set, fragment, result);
+            }
+        }
+    }
+}
+
+struct Usages<'v, 'x, 'a, T: Text<'a>> {
+    variable: &'v str,
+    result: Vec<VariableUsage<'x, 'a, T>>,
+}
+
+impl<'v, 'x, 'a, T> Usages<'v, 'x, 'a, T>
+    where T: Text<'a>,
+{
+    fn selection_set(&mut self, set: &'x SelectionSet<'a, T>) {
+        for item in &set.items {
+            match *item {
+                Selection::Field(ref field) => {
+                    for (name, value) in &field.arguments {
+                        for _ in 0..self.count(value) {
+                            self.result.push(VariableUsage {
+                                argument: name.as_ref(),
+                                node: ArgumentOf::Field(field),
+                                position: field.position,
+                            });
+                        }
+                    }
+                    self.directives(&field.directives);
+                    self.selection_set(&field.selection_set);
+                }
+                Selection::FragmentSpread(ref spread) => {
+                    for (name, value) in &spread.arguments {
+                        for _ in 0..self.count(value) {
+                            self.result.push(VariableUsage {
+                                argument: name.as_ref(),
+                                node: ArgumentOf::FragmentSpread(spread),
+                                position: spread.position,
+                            });
+                        }
+                    }
+                    self.directives(&spread.directives);
+                }
+                Selection::InlineFragment(ref frag) => {
+                    self.directives(&frag.directives);
+                    self.selection_set(&frag.selection_set);
+                }
+            }
+        }
+    }
+
+    fn directives(&mut self, directives: &'x [Directive<'a, T>]) {
+        for dir in directives {
+            for arg in &dir.arguments {
+                for _ in 0..self.count(&arg.value) {
+                    self.result.push(VariableUsage {
+                        argument: arg.name.as_ref(),
+                        node: ArgumentOf::Directive(dir),
+                        position: arg.value_position,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Returns the number of usages of the variable in the value
+    fn count(&self, value: &Value<'a, T>) -> usize {
+        match *value {
+            Value::Variable(ref name) => {
+                (name.as_ref() == self.variable) as usize
+            }
+            Value::List(ref items) => {
+                items.iter().map(|item| self.count(item)).sum()
+            }
+            Value::Object(ref fields) => {
+                fields.iter().map(|(_, value)| self.count(value)).sum()
+            }
+            _ => 0,
+        }
+    }
+}
+
+struct TypeReferences<'n> {
+    name: &'n str,
+    result: Vec<TypeReference>,
+}
+
+impl<'n> TypeReferences<'n> {
+    fn push<F>(&mut self, kind: ReferenceKind, position: Pos, name: &str,
+        coordinate: F)
+        where F: FnOnce() -> SchemaCoordinate,
+    {
+        if name == self.name {
+            self.result.push(TypeReference {
+                kind,
+                position,
+                coordinate: Some(coordinate()),
+            });
+        }
+    }
+
+    fn names<N: AsRef<str>>(&mut self, kind: ReferenceKind, position: Pos,
+        type_name: &N, names: &[N])
+    {
+        for name in names {
+            self.push(kind, position, name.as_ref(),
+                      || SchemaCoordinate::Type(type_name.as_ref().into()));
+        }
+    }
+
+    fn schema<N: AsRef<str>>(&mut self, position: Pos,
+        operations: &[&Option<N>])
+    {
+        for name in operations.iter().filter_map(|name| name.as_ref()) {
+            if name.as_ref() == self.name {
+                self.result.push(TypeReference {
+                    kind: ReferenceKind::SchemaOperation,
+                    position,
+                    coordinate: None,
+                });
+            }
+        }
+    }
+
+    fn fields<'a, T>(&mut self, type_name: &T::Value,
+        fields: &[schema::Field<'a, T>])
+        where T: Text<'a>,
+    {
+        for field in fields {
+            self.push(ReferenceKind::FieldType, field.position,
+                field.field_type.innermost_name(),
+                || SchemaCoordinate::Member {
+                    type_name: type_name.as_ref().into(),
+                    member: field.name.as_ref().into(),
+                });
+            for arg in &field.arguments {
+                self.push(ReferenceKind::ArgumentType, arg.position,
+                    arg.value_type.innermost_name(),
+                    || SchemaCoordinate::Argument {
+                        type_name: type_name.as_ref().into(),
+                        field: field.name.as_ref().into(),
+                        argument: arg.name.as_ref().into(),
+                    });
+            }
+        }
+    }
+
+    fn input_fields<'a, T>(&mut self, type_name: &T::Value,
+        fields: &[schema::InputValue<'a, T>])
+        where T: Text<'a>,
+    {
+        for field in fields {
+            self.push(ReferenceKind::InputFieldType, field.position,
+                field.value_type.innermost_name(),
+                || SchemaCoordinate::Member {
+                    type_name: type_name.as_ref().into(),
+                    member: field.name.as_ref().into(),
+                });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{parse_query, parse_schema};
+    use super::{fragment_spreads, variable_usages, type_references};
+    use super::{ArgumentOf, ReferenceKind};
+
+    #[test]
+    fn spreads() {
+        let doc = parse_query::<&str>("
+            { ...F a { ... on T { ...F ...G } } }
+            fragment G on T { ...F }
+            fragment F on T { x }
+        ").unwrap();
+        let positions = fragment_spreads(&doc, "F").iter()
+            .map(|s| s.position.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(positions, ["2:18", "2:38", "3:34"]);
+        assert!(fragment_spreads(&doc, "X").is_empty());
+    }
+
+    #[test]
+    fn variables() {
+        let doc = parse_query::<&str>("
+            query A($v: Int) @d(x: $v) {
+                a(x: $v, y: [$v, {z: $v}], w: $w) @skip(if: $v) ...F ...F
+            }
+            query B($v: Int) { b(x: $v) ...G }
+            fragment F on T { f(x: $v) ...G }
+            fragment G on T { g @include(if: $v) }
+        ").unwrap();
+        let usages = variable_usages(&doc, Some("A"), "v").unwrap();
+        let summary = usages.iter().map(|u| {
+            let node = match u.node {
+                ArgumentOf::Field(f) => f.name,
+                ArgumentOf::Directive(d) => d.name,
+                ArgumentOf::FragmentSpread(s) => s.fragment_name,
+            };
+            format!("{} {}({}:)", u.position, node, u.argument)
+        }).collect::<Vec<_>>();
+        assert_eq!(summary, [
+            "2:36 d(x:)",
+            "3:17 a(x:)", "3:17 a(y:)", "3:17 a(y:)", "3:61 skip(if:)",
+            "6:31 f(x:)",
+            "7:46 include(if:)",
+        ]);
+        assert_eq!(variable_usages(&doc, Some("B"), "v").unwrap().len(), 2);
+        assert!(variable_usages(&doc, None, "v").is_err());
+    }
+
+    #[test]
+    fn types() {
+        let doc = parse_schema::<&str>("
+            schema { query: T mutation: T }
+            type T implements I { a(t: T): [T!] b: String }
+            interface J implements T { c: T }
+            union U = A | T
+            input In { t: T }
+            extend type T implements T
+            extend union V = T
+            directive @d(t: T) on FIELD
+            scalar T
+        ").unwrap();
+        let refs = type_references(&doc, "T").into_iter()
+            .map(|r| format!("{} {:?} {}", r.position, r.kind,
+                r.coordinate.map(|c| c.to_string()).unwrap_or_default()))
+            .collect::<Vec<_>>();
+        assert_eq!(refs, [
+            "2:13 SchemaOperation ",
+            "2:13 SchemaOperation ",
+            "3:35 FieldType T.a",
+            "3:37 ArgumentType T.a(t:)",
+            "4:13 Implements J",
+            "4:40 FieldType J.c",
+            "5:13 UnionMember U",
+            "6:24 InputFieldType In.t",
+            "7:20 Extension T",
+            "7:20 Implements T",
+            "8:20 UnionMember V",
+            "9:26 ArgumentType @d(t:)",
+        ]);
+        assert!(type_references(&doc, "I").iter()
+                .all(|r| r.kind == ReferenceKind::Implements));
+    }
+}
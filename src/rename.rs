@@ -0,0 +1,739 @@
+//! Renaming fragments, variables and schema types
+//!
+//! Each rename is available both on the AST (`rename_fragment`,
+//! `rename_variable`, `rename_type`) and as a list of text edits of the
+//! original source (`fragment_edits`, `variable_edits`, `type_edits`),
+//! which keep the formatting and comments of the rest of the document.
+//!
+//! ```rust
+//! # extern crate edb_graphql_parser;
+//! use edb_graphql_parser::position::apply_edits;
+//! use edb_graphql_parser::rename::fragment_edits;
+//!
+//! let source = "{ user { ...UserFields } }  # comment
+//! fragment UserFields on User { id }";
+//! let edits = fragment_edits(source, "UserFields", "Basic").unwrap();
+//! assert_eq!(edits.len(), 2);
+//! assert_eq!(edits[0].span.0.to_string(), "1:13");
+//! assert_eq!(apply_edits(source, &edits), "{ user { ...Basic } }  # comment
+//! fragment Basic on User { id }");
+//! ```
+use std::collections::BTreeSet;
+
+use thiserror::Error;
+
+use crate::common::{Directive, Text, Type, Value};
+use crate::name::is_valid_name;
+use crate::position::{Pos, TextEdit};
+use crate::query::{self, parse_query, Definition, Document};
+use crate::query::{OperationError, Selection, SelectionSet};
+use crate::query::used_fragments;
+use crate::references::fragment_spreads;
+use crate::schema::{self, parse_schema};
+use crate::tokenizer::{positioned_tokens, Kind, Token};
+
+
+/// Error renaming an element
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum RenameError {
+    #[error("invalid name {:?}", _0)]
+    InvalidName(String),
+    #[error("{} {:?} is not defined", kind, name)]
+    NotDefined { kind: &'static str, name: String },
+    #[error("{} {:?} is already defined", kind, name)]
+    AlreadyDefined { kind: &'static str, name: String },
+    #[error(transparent)]
+    Query(#[from] query::ParseError),
+    #[error(transparent)]
+    Schema(#[from] schema::ParseError),
+    #[error(transparent)]
+    Operation(#[from] OperationError),
+}
+
+/// Where a type name is in the schema source, see `type_references`
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Context {
+    /// Fields of a type, values of an enum or operation types of a schema
+    Body,
+    /// Argument definitions
+    Arguments,
+    /// List type
+    TypeList,
+    /// Default value or directive arguments
+    Value,
+}
+
+/// Keywords followed by a type name
+const TYPE_KEYWORDS: &[&str] = &[
+    "type", "interface", "union", "enum", "input", "scalar", "implements",
+];
+
+const KEYWORDS: &[&str] = &[
+    "type", "interface", "union", "enum", "input", "scalar", "implements",
+    "schema", "extend", "directive", "repeatable", "on",
+];
+
+/// Renames the fragment definition and all its spreads
+///
+/// Returns the number of names replaced.
+pub fn rename_fragment<'a, T>(doc: &mut Document<'a, T>, old: &str,
+    new: &'a str)
+    -> Result<usize, RenameError>
+    where T: Text<'a>,
+{
+    check_fragment(doc, old, new)?;
+    let mut count = 0;
+    for def in &mut doc.definitions {
+        let set = match *def {
+            Definition::Operation(ref mut op) => &mut op.selection_set,
+            Definition::Fragment(ref mut frag) => {
+                if frag.name.as_ref() == old {
+                    frag.name = new.into();
+                    count += 1;
+                }
+                &mut frag.selection_set
+            }
+        };
+        rename_spreads(set, old, new, &mut count);
+    }
+    Ok(count)
+}
+
+/// Returns the edits renaming the fragment definition and all its spreads
+pub fn fragment_edits(source: &str, old: &str, new: &str)
+    -> Result<Vec<TextEdit>, RenameError>
+{
+    let doc = parse_query::<&str>(source)?;
+    check_fragment(&doc, old, new)?;
+    let frag = doc.fragment(old).expect("fragment is defined");
+    // after the `fragment` keyword
+    let mut names = vec![frag.position.token + 1];
+    // position of a spread is the one of the name
+    names.extend(fragment_spreads(&doc, old).iter()
+                 .map(|spread| spread.position.token));
+    let tokens = positioned_tokens(source);
+    names.sort_unstable();
+    Ok(names.into_iter().map(|idx| edit(&tokens[idx], new)).collect())
+}
+
+/// Renames the variable of the operation
+///
+/// `operation` selects the operation as in `Document::operation`. The
+/// variable is renamed in the fragments spread by the operation too, so
+/// other operations spreading them should define a variable with the new
+/// name.
+///
+/// Returns the number of names replaced.
+pub fn rename_variable<'a, T>(doc: &mut Document<'a, T>,
+    operation: Option<&str>, old: &str, new: &'a str)
+    -> Result<usize, RenameError>
+    where T: Text<'a>,
+{
+    let idx = operation_index(doc, operation)?;
+    let fragments = match doc.definitions[idx] {
+        Definition::Operation(ref op) => {
+            check_variable(op, old, new)?;
+            used_fragments(doc, &op.selection_set).into_iter()
+                .map(String::from).collect::<BTreeSet<_>>()
+        }
+        Definition::Fragment(_) => unreachable!(),
+    };
+    let mut count = 0;
+    for (i, def) in doc.definitions.iter_mut().enumerate() {
+        match *def {
+            Definition::Operation(ref mut op) if i == idx => {
+                for var in &mut op.variable_definitions {
+                    if var.name.as_ref() == old {
+                        var.name = new.into();
+                        count += 1;
+                    }
+                }
+                rename_in_directives(&mut op.directives, old, new,
+                                     &mut count);
+                rename_in_selections(&mut op.selection_set, old, new,
+                                     &mut count);
+            }
+            Definition::Fragment(ref mut frag)
+                if fragments.contains(frag.name.as_ref())
+            => {
+                rename_in_directives(&mut frag.directives, old, new,
+                                     &mut count);
+                rename_in_selections(&mut frag.selection_set, old, new,
+                                     &mut count);
+            }
+            _ => {}
+        }
+    }
+    Ok(count)
+}
+
+/// Returns the edits renaming the variable of the operation
+///
+/// Renames the same occurrences as `rename_variable`.
+pub fn variable_edits(source: &str, operation: Option<&str>, old: &str,
+    new: &str)
+    -> Result<Vec<TextEdit>, RenameError>
+{
+    let doc = parse_query::<&str>(source)?;
+    let op = doc.operation(operation)?;
+    check_variable(op, old, new)?;
+    let used = used_fragments(&doc, &op.selection_set);
+    let mut ranges = vec![(op.position, op.selection_set.span.1)];
+    ranges.extend(doc.fragments()
+        .filter(|frag| used.contains(frag.name))
+        .map(|frag| (frag.position, frag.selection_set.span.1)));
+    let tokens = positioned_tokens(source);
+    let mut edits = Vec::new();
+    for (start, end) in ranges {
+        for idx in start.token + 1..end.token {
+            let (ref prev, _) = tokens[idx - 1];
+            let (ref tok, _) = tokens[idx];
+            if prev.kind == Kind::Punctuator && prev.value == "$" &&
+                tok.value == old
+            {
+                edits.push(edit(&tokens[idx], new));
+            }
+        }
+    }
+    edits.sort_by_key(|edit| edit.span.0);
+    Ok(edits)
+}
+
+/// Renames the type: its definition, extensions and all the references
+/// to it in fields, arguments, interfaces, union members and operation
+/// types
+///
+/// Returns the number of names replaced.
+pub fn rename_type<'a, T>(doc: &mut schema::Document<'a, T>, old: &str,
+    new: &'a str)
+    -> Result<usize, RenameError>
+    where T: Text<'a>,
+{
+    use crate::schema::{Definition::*, TypeDefinition as D};
+    use crate::schema::TypeExtension as E;
+
+    check_type(doc, old, new)?;
+    let mut count = 0;
+    let names = |names: &mut [T::Value], count: &mut usize| {
+        rename_names(names, old, new, count)
+    };
+    for def in &mut doc.definitions {
+        match *def {
+            SchemaDefinition(ref mut s) => {
+                names(s.query.as_mut_slice(), &mut count);
+                names(s.mutation.as_mut_slice(), &mut count);
+                names(s.subscription.as_mut_slice(), &mut count);
+            }
+            SchemaExtension(ref mut s) => {
+                names(s.query.as_mut_slice(), &mut count);
+                names(s.mutation.as_mut_slice(), &mut count);
+                names(s.subscription.as_mut_slice(), &mut count);
+            }
+            TypeDefinition(D::Scalar(ref mut t)) => {
+                rename_name(&mut t.name, old, new, &mut count);
+            }
+            TypeDefinition(D::Object(ref mut t)) => {
+                rename_name(&mut t.name, old, new, &mut count);
+                names(&mut t.implements_interfaces, &mut count);
+                rename_in_fields(&mut t.fields, old, new, &mut count);
+            }
+            TypeDefinition(D::Interface(ref mut t)) => {
+                rename_name(&mut t.name, old, new, &mut count);
+                names(&mut t.implements_interfaces, &mut count);
+                rename_in_fields(&mut t.fields, old, new, &mut count);
+            }
+            TypeDefinition(D::Union(ref mut t)) => {
+                rename_name(&mut t.name, old, new, &mut count);
+                names(&mut t.types, &mut count);
+            }
+            TypeDefinition(D::Enum(ref mut t)) => {
+                rename_name(&mut t.name, old, new, &mut count);
+            }
+            TypeDefinition(D::InputObject(ref mut t)) => {
+                rename_name(&mut t.name, old, new, &mut count);
+                rename_in_inputs(&mut t.fields, old, new, &mut count);
+            }
+            TypeExtension(E::Scalar(ref mut t)) => {
+                rename_name(&mut t.name, old, new, &mut count);
+            }
+            TypeExtension(E::Object(ref mut t)) => {
+                rename_name(&mut t.name, old, new, &mut count);
+                names(&mut t.implements_interfaces, &mut count);
+                rename_in_fields(&mut t.fields, old, new, &mut count);
+            }
+            TypeExtension(E::Interface(ref mut t)) => {
+                rename_name(&mut t.name, old, new, &mut count);
+                names(&mut t.implements_interfaces, &mut count);
+                rename_in_fields(&mut t.fields, old, new, &mut count);
+            }
+            TypeExtension(E::Union(ref mut t)) => {
+                rename_name(&mut t.name, old, new, &mut count);
+                names(&mut t.types, &mut count);
+            }
+            TypeExtension(E::Enum(ref mut t)) => {
+                rename_name(&mut t.name, old, new, &mut count);
+            }
+            TypeExtension(E::InputObject(ref mut t)) => {
+                rename_name(&mut t.name, old, new, &mut count);
+                rename_in_inputs(&mut t.fields, old, new, &mut count);
+            }
+            DirectiveDefinition(ref mut d) => {
+                rename_in_inputs(&mut d.arguments, old, new, &mut count);
+            }
+            Custom(_) => {}
+        }
+    }
+    Ok(count)
+}
+
+/// Returns the edits renaming the type, see `rename_type`
+pub fn type_edits(source: &str, old: &str, new: &str)
+    -> Result<Vec<TextEdit>, RenameError>
+{
+    let doc = parse_schema::<&str>(source)?;
+    check_type(&doc, old, new)?;
+    let tokens = positioned_tokens(source);
+    Ok(type_references(&tokens).into_iter()
+        .filter(|&idx| tokens[idx].0.value == old)
+        .map(|idx| edit(&tokens[idx], new))
+        .collect())
+}
+
+fn check_name(new: &str) -> Result<(), RenameError> {
+    if is_valid_name(new) {
+        Ok(())
+    } else {
+        Err(RenameError::InvalidName(new.into()))
+    }
+}
+
+fn check_fragment<'a, T>(doc: &Document<'a, T>, old: &str, new: &str)
+    -> Result<(), RenameError>
+    where T: Text<'a>,
+{
+    check_name(new)?;
+    if doc.fragment(old).is_none() {
+        return Err(RenameError::NotDefined {
+            kind: "fragment",
+            name: old.into(),
+        });
+    }
+    if old != new && doc.fragment(new).is_some() {
+        return Err(RenameError::AlreadyDefined {
+            kind: "fragment",
+            name: new.into(),
+        });
+    }
+    Ok(())
+}
+
+fn check_variable<'a, T>(op: &query::Operation<'a, T>, old: &str, new: &str)
+    -> Result<(), RenameError>
+    where T: Text<'a>,
+{
+    check_name(new)?;
+    let defined = |name: &str| op.variable_definitions.iter()
+        .any(|var| var.name.as_ref() == name);
+    if !defined(old) {
+        return Err(RenameError::NotDefined {
+            kind: "variable",
+            name: old.into(),
+        });
+    }
+    if old != new && defined(new) {
+        return Err(RenameError::AlreadyDefined {
+            kind: "variable",
+            name: new.into(),
+        });
+    }
+    Ok(())
+}
+
+fn check_type<'a, T>(doc: &schema::Document<'a, T>, old: &str, new: &str)
+    -> Result<(), RenameError>
+    where T: Text<'a>,
+{
+    use crate::schema::Definition::{TypeDefinition, TypeExtension};
+
+    check_name(new)?;
+    let defined = |name: &str| doc.definitions.iter().any(|def| match *def {
+        TypeDefinition(ref t) => t.name().as_ref() == name,
+        TypeExtension(ref t) => t.name().as_ref() == name,
+        _ => false,
+    });
+    if !defined(old) {
+        return Err(RenameError::NotDefined {
+            kind: "type",
+            name: old.into(),
+        });
+    }
+    if old != new && defined(new) {
+        return Err(RenameError::AlreadyDefined {
+            kind: "type",
+            name: new.into(),
+        });
+    }
+    Ok(())
+}
+
+fn operation_index<'a, T>(doc: &Document<'a, T>, name: Option<&str>)
+    -> Result<usize, OperationError>
+    where T: Text<'a>,
+{
+    let selected = doc.operation(name)?;
+    Ok(doc.definitions.iter()
+        .position(|def| match *def {
+            Definition::Operation(ref op) => std::ptr::eq(op, selected),
+            Definition::Fragment(_) => false,
+        })
+        .expect("operation is in the document"))
+}
+
+fn rename_name<'a, V>(name: &mut V, old: &str, new: &'a str,
+    count: &mut usize)
+    where V: AsRef<str> + From<&'a str>,
+{
+    if name.as_ref() == old {
+        *name = new.into();
+        *count += 1;
+    }
+}
+
+fn rename_names<'a, V>(names: &mut [V], old: &str, new: &'a str,
+    count: &mut usize)
+    where V: AsRef<str> + From<&'a str>,
+{
+    for name in names {
+        rename_name(name, old, new, count);
+    }
+}
+
+fn rename_spreads<'a, T>(set: &mut SelectionSet<'a, T>, old: &str,
+    new: &'a str, count: &mut usize)
+    where T: Text<'a>,
+{
+    for item in &mut set.items {
+        match *item {
+            Selection::Field(ref mut field) => {
+                rename_spreads(&mut field.selection_set, old, new, count);
+            }
+            Selection::FragmentSpread(ref mut spread) => {
+                rename_name(&mut spread.fragment_name, old, new, count);
+            }
+            Selection::InlineFragment(ref mut frag) => {
+                rename_spreads(&mut frag.selection_set, old, new, count);
+            }
+        }
+    }
+}
+
+fn rename_in_selections<'a, T>(set: &mut SelectionSet<'a, T>, old: &str,
+    new: &'a str, count: &mut usize)
+    where T: Text<'a>,
+{
+    for item in &mut set.items {
+        match *item {
+            Selection::Field(ref mut field) => {
+                for (_, value) in &mut field.arguments {
+                    rename_in_value(value, old, new, count);
+                }
+                rename_in_directives(&mut field.directives, old, new, count);
+                rename_in_selections(&mut field.selection_set, old, new,
+                                     count);
+            }
+            Selection::FragmentSpread(ref mut spread) => {
+                for (_, value) in &mut spread.arguments {
+                    rename_in_value(value, old, new, count);
+                }
+                rename_in_directives(&mut spread.directives, old, new, count);
+            }
+            Selection::InlineFragment(ref mut frag) => {
+                rename_in_directives(&mut frag.directives, old, new, count);
+                rename_in_selections(&mut frag.selection_set, old, new,
+                                     count);
+            }
+        }
+    }
+}
+
+fn rename_in_directives<'a, T>(directives: &mut [Directive<'a, T>],
+    old: &str, new: &'a str, count: &mut usize)
+    where T: Text<'a>,
+{
+    for dir in directives {
+        for arg in &mut dir.arguments {
+            rename_in_value(&mut arg.value, old, new, count);
+        }
+    }
+}
+
+fn rename_in_value<'a, T>(value: &mut Value<'a, T>, old: &str,
+    new: &'a str, count: &mut usize)
+    where T: Text<'a>,
+{
+    match *value {
+        Value::Variable(ref mut name) => rename_name(name, old, new, count),
+        Value::List(ref mut items) => {
+            for item in items {
+                rename_in_value(item, old, new, count);
+            }
+        }
+        Value::Object(ref mut fields) => {
+            for (_, value) in fields {
+                rename_in_value(value, old, new, count);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn rename_in_type<'a, T>(typ: &mut Type<'a, T>, old: &str, new: &'a str,
+    count: &mut usize)
+    where T: Text<'a>,
+{
+    match *typ {
+        Type::NamedType(ref mut name) => rename_name(name, old, new, count),
+        Type::ListType(ref mut inner) | Type::NonNullType(ref mut inner) => {
+            rename_in_type(inner, old, new, count);
+        }
+    }
+}
+
+fn rename_in_fields<'a, T>(fields: &mut [schema::Field<'a, T>], old: &str,
+    new: &'a str, count: &mut usize)
+    where T: Text<'a>,
+{
+    for field in fields {
+        rename_in_inputs(&mut field.arguments, old, new, count);
+        rename_in_type(&mut field.field_type, old, new, count);
+    }
+}
+
+fn rename_in_inputs<'a, T>(values: &mut [schema::InputValue<'a, T>],
+    old: &str, new: &'a str, count: &mut usize)
+    where T: Text<'a>,
+{
+    for value in values {
+        rename_in_type(&mut value.value_type, old, new, count);
+    }
+}
+
+fn edit((tok, start): &(Token, Pos), new: &str) -> TextEdit {
+    // names are ASCII
+    let len = tok.value.len();
+    let end = Pos {
+        column: start.column + len,
+        character: start.character + len,
+        .. *start
+    };
+    TextEdit { span: (*start, end), replacement: new.into() }
+}
+
+/// Returns the indices of the tokens of a schema naming a type
+///
+/// The schema must be valid, the names are found by the tokens preceding
+/// them and the kind of the brackets around them.
+fn type_references(tokens: &[(Token, Pos)]) -> Vec<usize> {
+    use self::Context::*;
+
+    let mut result = Vec::new();
+    let mut stack = Vec::new();
+    // keyword of the current definition
+    let mut definition = "";
+    let mut after_keyword = None;
+    for (idx, (tok, _)) in tokens.iter().enumerate() {
+        let value = |back: usize| idx.checked_sub(back)
+            .map(|i| tokens[i].0.value).unwrap_or("");
+        let keyword = after_keyword.take();
+        let top = stack.last().cloned();
+        match tok.kind {
+            Kind::Punctuator => match tok.value {
+                "{" => stack.push(if top.is_none() { Body } else { Value }),
+                "(" => {
+                    let directive = value(2) == "@";
+                    stack.push(match top {
+                        None if directive && value(3) == "directive" => {
+                            Arguments
+                        }
+                        Some(Body) if !directive => Arguments,
+                        _ => Value,
+                    });
+                }
+                "[" => stack.push(match top {
+                    Some(Body) | Some(Arguments) if value(1) == ":" => {
+                        TypeList
+                    }
+                    Some(TypeList) => TypeList,
+                    _ => Value,
+                }),
+                "}" | ")" | "]" => {
+                    stack.pop();
+                }
+                _ => {}
+            },
+            Kind::Name => match top {
+                None if value(1) == "@" => {}
+                None if keyword.is_some_and(|k| TYPE_KEYWORDS.contains(&k))
+                    || value(1) == "&"
+                    || (value(1) == "=" || value(1) == "|")
+                        && definition == "union"
+                => result.push(idx),
+                None if KEYWORDS.contains(&tok.value) => {
+                    after_keyword = Some(tok.value);
+                    if tok.value != "extend" && tok.value != "implements" &&
+                        tok.value != "repeatable" && tok.value != "on"
+                    {
+                        definition = tok.value;
+                    }
+                }
+                Some(Body) | Some(Arguments) if value(1) == ":" => {
+                    result.push(idx);
+                }
+                Some(TypeList) => result.push(idx),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{parse_query, parse_schema};
+    use crate::position::apply_edits;
+    use crate::tokenizer::positioned_tokens;
+    use super::{fragment_edits, variable_edits, type_edits};
+    use super::{rename_fragment, rename_variable, rename_type};
+    use super::{type_references, RenameError};
+
+    #[test]
+    fn fragment() {
+        let source = "
+            query Q { ...F a { ... F, ... on T { ...G } } }
+            fragment F on T { ...G }
+            fragment G on T { F: f, ...F }
+        ";
+        let edits = fragment_edits(source, "F", "Renamed").unwrap();
+        assert_eq!(edits.len(), 4);
+        let result = apply_edits(source, &edits);
+        assert_eq!(result, "
+            query Q { ...Renamed a { ... Renamed, ... on T { ...G } } }
+            fragment Renamed on T { ...G }
+            fragment G on T { F: f, ...Renamed }
+        ");
+        let mut doc = parse_query::<&str>(source).unwrap();
+        assert_eq!(rename_fragment(&mut doc, "F", "Renamed").unwrap(), 4);
+        assert!(doc.semantic_eq(&parse_query::<&str>(&result).unwrap()));
+    }
+
+    #[test]
+    fn variable() {
+        let source = "
+            query A($id: ID, $x: Int = 1) @d(x: $x) {
+                a(id: $id, x: {list: [$x]}) ...F
+            }
+            query B($x: Int) { b(x: $x) }
+            fragment F on T { f(x: $x) @include(if: $x) }
+            fragment G on T { g(x: $x) }
+        ";
+        let edits = variable_edits(source, Some("A"), "x", "y").unwrap();
+        let result = apply_edits(source, &edits);
+        assert_eq!(result, "
+            query A($id: ID, $y: Int = 1) @d(x: $y) {
+                a(id: $id, x: {list: [$y]}) ...F
+            }
+            query B($x: Int) { b(x: $x) }
+            fragment F on T { f(x: $y) @include(if: $y) }
+            fragment G on T { g(x: $x) }
+        ");
+        let mut doc = parse_query::<&str>(source).unwrap();
+        assert_eq!(rename_variable(&mut doc, Some("A"), "x", "y").unwrap(),
+                   edits.len());
+        assert!(doc.semantic_eq(&parse_query::<&str>(&result).unwrap()));
+        assert!(matches!(variable_edits(source, None, "x", "y"),
+                         Err(RenameError::Operation(_))));
+    }
+
+    #[test]
+    fn schema_type() {
+        let source = r#"
+            schema { query: User }
+            "User type" type User implements Node & User @d(x: User) {
+                User(User: [User!] = [User] @d(x: {User: User})): User!
+                friends: [[User]]
+            }
+            extend type User implements User
+            interface Node implements User { id: ID }
+            union Search = | User | Post
+            union Other @d(x: User) = Post | User
+            input Filter { user: User = User, list: [User] = [] }
+            enum Kind { User }
+            directive @d(x: User = User) repeatable on OBJECT | FIELD
+            scalar Post
+            extend schema { mutation: User }
+        "#;
+        let edits = type_edits(source, "User", "Person").unwrap();
+        let result = apply_edits(source, &edits);
+        assert_eq!(result, r#"
+            schema { query: Person }
+            "User type" type Person implements Node & Person @d(x: User) {
+                User(User: [Person!] = [User] @d(x: {User: User})): Person!
+                friends: [[Person]]
+            }
+            extend type Person implements Person
+            interface Node implements Person { id: ID }
+            union Search = | Person | Post
+            union Other @d(x: User) = Post | Person
+            input Filter { user: Person = User, list: [Person] = [] }
+            enum Kind { User }
+            directive @d(x: Person = User) repeatable on OBJECT | FIELD
+            scalar Post
+            extend schema { mutation: Person }
+        "#);
+        let mut doc = parse_schema::<&str>(source).unwrap();
+        assert_eq!(rename_type(&mut doc, "User", "Person").unwrap(),
+                   edits.len());
+        assert!(doc.semantic_eq(&parse_schema::<&str>(&result).unwrap()));
+    }
+
+    #[test]
+    fn keyword_names() {
+        let source = "
+            type type implements on { on: type, type: [on] }
+            interface on { type: type }
+            union union = type | on
+            extend type type @d
+            directive @d on OBJECT
+        ";
+        let tokens = positioned_tokens(source);
+        let names = type_references(&tokens).into_iter()
+            .map(|idx| tokens[idx].1.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(names, [
+            "2:18", "2:34", "2:43", "2:56", "3:23", "3:34",
+            "4:19", "4:27", "4:34", "5:25",
+        ]);
+    }
+
+    #[test]
+    fn errors() {
+        let query = "query Q($a: Int, $b: Int) { ...F } fragment F on T { a }";
+        assert_eq!(fragment_edits(query, "G", "H").unwrap_err().to_string(),
+                   "fragment \"G\" is not defined");
+        assert_eq!(fragment_edits(query, "F", "F").unwrap().len(), 2);
+        assert_eq!(variable_edits(query, None, "a", "b").unwrap_err()
+                   .to_string(),
+                   "variable \"b\" is already defined");
+        assert_eq!(variable_edits(query, None, "a", "$c").unwrap_err(),
+                   RenameError::InvalidName("$c".into()));
+        assert!(matches!(fragment_edits("{", "F", "G"),
+                         Err(RenameError::Query(_))));
+        let schema = "type A { b: B } type B { a: A }";
+        assert_eq!(type_edits(schema, "A", "B").unwrap_err().to_string(),
+                   "type \"B\" is already defined");
+        assert_eq!(type_edits(schema, "String", "Text").unwrap_err()
+                   .to_string(),
+                   "type \"String\" is not defined");
+    }
+}
@@ -0,0 +1,238 @@
+//! GraphQL-over-HTTP request bodies
+//!
+//! Enabled by the `serde` feature.
+//!
+//! ```rust
+//! # extern crate edb_graphql_parser;
+//! use edb_graphql_parser::request::Request;
+//!
+//! let body = br#"{"query": "query A { a } query B { b }",
+//!                 "operationName": "B", "variables": null}"#;
+//! let request = Request::from_slice(body).unwrap();
+//! let parsed = request.parse().unwrap();
+//! assert_eq!(parsed.operation().name, Some("B"));
+//! assert!(request.variables.is_empty());
+//! ```
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{json, Map, Value as Json};
+use thiserror::Error;
+
+use crate::query::{parse_query, Document, Definition, Operation};
+use crate::query::{OperationError, ParseError};
+
+
+/// Body of a GraphQL request: `{query, operationName, variables,
+/// extensions}`
+///
+/// Absent and `null` members are the same. An empty `operationName` is the
+/// same as none, as some clients send `""` for anonymous operations.
+/// `query` may be absent, in requests using persisted queries.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Request {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    #[serde(default, deserialize_with = "operation_name",
+            skip_serializing_if = "Option::is_none")]
+    pub operation_name: Option<String>,
+    #[serde(default, deserialize_with = "object_or_null",
+            skip_serializing_if = "Map::is_empty")]
+    pub variables: Map<String, Json>,
+    #[serde(default, deserialize_with = "object_or_null",
+            skip_serializing_if = "Map::is_empty")]
+    pub extensions: Map<String, Json>,
+}
+
+/// Request with the query parsed and the operation selected, see
+/// `Request::parse`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedRequest<'r> {
+    pub document: Document<'r, &'r str>,
+    /// Index of the operation in the definitions of the document
+    operation: usize,
+}
+
+/// Error handling the request, see `RequestError::to_response`
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum RequestError {
+    /// Body is not valid JSON or has members of the wrong type
+    #[error("Invalid request body: {}", _0)]
+    Body(String),
+    #[error("Must provide query string.")]
+    MissingQuery,
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+    #[error(transparent)]
+    Operation(#[from] OperationError),
+}
+
+fn operation_name<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+    where D: Deserializer<'de>,
+{
+    let name = Option::<String>::deserialize(deserializer)?;
+    Ok(name.filter(|name| !name.is_empty()))
+}
+
+fn object_or_null<'de, D>(deserializer: D)
+    -> Result<Map<String, Json>, D::Error>
+    where D: Deserializer<'de>,
+{
+    let map = Option::<Map<String, Json>>::deserialize(deserializer)?;
+    Ok(map.unwrap_or_default())
+}
+
+impl Request {
+    /// Deserializes the JSON body of a POST request
+    ///
+    /// The body must be an object, batched requests (arrays) are rejected.
+    pub fn from_slice(body: &[u8]) -> Result<Request, RequestError> {
+        let body = serde_json::from_slice::<Json>(body)
+            .map_err(|e| RequestError::Body(e.to_string()))?;
+        if !body.is_object() {
+            return Err(RequestError::Body("expected an object".into()));
+        }
+        serde_json::from_value(body)
+            .map_err(|e| RequestError::Body(e.to_string()))
+    }
+
+    /// Parses the query and selects the operation to execute
+    ///
+    /// The operation is selected by `operationName` as in
+    /// `Document::operation`.
+    pub fn parse(&self) -> Result<ParsedRequest<'_>, RequestError> {
+        let query = self.query.as_ref().ok_or(RequestError::MissingQuery)?;
+        let document = parse_query::<&str>(query)?;
+        let selected = document.operation(self.operation_name.as_deref())?;
+        let operation = document.definitions.iter()
+            .position(|def| match *def {
+                Definition::Operation(ref op) => std::ptr::eq(op, selected),
+                Definition::Fragment(_) => false,
+            })
+            .expect("operation is in the document");
+        Ok(ParsedRequest { document, operation })
+    }
+}
+
+impl<'r> ParsedRequest<'r> {
+    /// Returns the operation to execute
+    pub fn operation(&self) -> &Operation<'r, &'r str> {
+        match self.document.definitions[self.operation] {
+            Definition::Operation(ref op) => op,
+            Definition::Fragment(_) => unreachable!(),
+        }
+    }
+}
+
+impl RequestError {
+    /// Returns the error as a GraphQL response: `{"errors": [{"message":
+    /// ..., "locations": [{"line": ..., "column": ...}]}]}`
+    ///
+    /// Locations are only included for the syntax errors. Per the
+    /// GraphQL-over-HTTP spec, the response should have a `400` status
+    /// if sent as `application/graphql-response+json`, and `200` if sent
+    /// as `application/json`.
+    pub fn to_response(&self) -> Json {
+        let error = match *self {
+            RequestError::Parse(ref e) => {
+                let (start, _) = e.span();
+                json!({
+                    "message": format!("Syntax Error: {}", e.description()),
+                    "locations": [{
+                        "line": start.line,
+                        "column": start.column,
+                    }],
+                })
+            }
+            _ => json!({ "message": self.to_string() }),
+        };
+        json!({ "errors": [error] })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::{Request, RequestError};
+
+    fn request(body: &str) -> Result<Request, RequestError> {
+        Request::from_slice(body.as_bytes())
+    }
+
+    #[test]
+    fn deserialize() {
+        let req = request(r#"{"query": "{ a }"}"#).unwrap();
+        assert_eq!(req.query.as_deref(), Some("{ a }"));
+        assert_eq!(req.operation_name, None);
+        assert!(req.variables.is_empty());
+        assert!(req.extensions.is_empty());
+        for body in &[
+            r#"{"query": "{ a }", "operationName": null}"#,
+            r#"{"query": "{ a }", "operationName": ""}"#,
+            r#"{"query": "{ a }", "variables": null, "extensions": null}"#,
+        ] {
+            assert_eq!(request(body).unwrap(), req);
+        }
+        let req = request(r#"{
+            "operationName": "A",
+            "variables": {"x": [1]},
+            "extensions": {"persistedQuery": {"version": 1}}
+        }"#).unwrap();
+        assert_eq!(req.query, None);
+        assert_eq!(req.operation_name.as_deref(), Some("A"));
+        assert_eq!(req.variables["x"], json!([1]));
+        assert_eq!(req.extensions["persistedQuery"]["version"], 1);
+        assert_eq!(serde_json::to_value(&req).unwrap(), json!({
+            "operationName": "A",
+            "variables": {"x": [1]},
+            "extensions": {"persistedQuery": {"version": 1}},
+        }));
+    }
+
+    #[test]
+    fn invalid_body() {
+        for body in &["", "[]", r#"{"query": 1}"#, r#"{"variables": []}"#] {
+            let err = request(body).unwrap_err();
+            assert!(matches!(err, RequestError::Body(_)), "{:?}", err);
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let req = request(r#"{
+            "query": "query A { a }\nfragment F on T { f }\nquery B { b }",
+            "operationName": "B"
+        }"#).unwrap();
+        let parsed = req.parse().unwrap();
+        assert_eq!(parsed.operation().name, Some("B"));
+        assert_eq!(parsed.document.definitions.len(), 3);
+        let req = request(r#"{"query": "fragment F on T { f } { a }"}"#)
+            .unwrap();
+        assert_eq!(req.parse().unwrap().operation().name, None);
+    }
+
+    #[test]
+    fn errors() {
+        let response = |body: &str| {
+            request(body).unwrap().parse().unwrap_err().to_response()
+        };
+        assert_eq!(response(r#"{}"#), json!({"errors": [
+            {"message": "Must provide query string."},
+        ]}));
+        assert_eq!(response(r#"{"query": "{ a } { b }"}"#), json!({
+            "errors": [{
+                "message": "Must provide operation name if query contains \
+                            multiple operations.",
+            }],
+        }));
+        assert_eq!(response(r#"{"query": "{ a }", "operationName": "X"}"#),
+                   json!({"errors": [
+                       {"message": "Unknown operation named \"X\"."},
+                   ]}));
+        let response = response(r#"{"query": "{ a(x: ) }"}"#);
+        assert_eq!(response["errors"][0]["locations"],
+                   json!([{"line": 1, "column": 8}]));
+        assert!(response["errors"][0]["message"].as_str().unwrap()
+                .starts_with("Syntax Error: "));
+    }
+}
@@ -0,0 +1,935 @@
+//! Checking that printed documents parse back into the same AST
+//!
+//! Printing a document and parsing the output is guaranteed to produce
+//! the same document, except for the positions of the nodes. So the printed
+//! form can be used as a serialization format for the AST.
+//!
+//! This module exposes the check used to verify this guarantee, which is
+//! useful for testing code that builds or transforms documents:
+//!
+//! ```rust
+//! # #[cfg(feature = "query")] {
+//! # extern crate edb_graphql_parser;
+//! use edb_graphql_parser::parse_query;
+//! use edb_graphql_parser::roundtrip::assert_roundtrip;
+//!
+//! let doc = parse_query::<&str>("query Q($id: ID!) { user(id: $id) { name } }")
+//!     .unwrap();
+//! assert_roundtrip(&doc).unwrap();
+//! # }
+//! ```
+//!
+//! Tools that reprint the source text (formatters, proxies) can check it
+//! with `check_query` and `check_schema`. These also report the formatting
+//! of the source that is lost in the printed form, like comments and the
+//! spelling of the literals:
+//!
+//! ```rust
+//! # #[cfg(feature = "query")] {
+//! # extern crate edb_graphql_parser;
+//! use edb_graphql_parser::{ParserOptions, Style};
+//! use edb_graphql_parser::roundtrip::{check_query, Loss};
+//!
+//! let report = check_query("{ a # first\n b }",
+//!     &ParserOptions::default(), &Style::default()).unwrap();
+//! assert!(report.is_ok());
+//! assert!(matches!(report.losses[..], [Loss::Comment { .. }]));
+//! # }
+//! ```
+use std::fmt::{self, Debug};
+
+use thiserror::Error;
+
+#[cfg(any(feature = "query", feature = "schema"))]
+use crate::common::{Directive, DirectiveArgument, Text};
+#[cfg(feature = "query")]
+use crate::common::Value;
+use crate::format::Style;
+use crate::options::ParserOptions;
+use crate::position::Pos;
+#[cfg(any(feature = "query", feature = "schema"))]
+use crate::tokenizer::{Kind, Token, TokenStream};
+#[cfg(feature = "query")]
+use crate::query;
+#[cfg(feature = "schema")]
+use crate::schema;
+
+
+/// Document that can be checked by `assert_roundtrip`
+pub trait Roundtrip: Debug {
+    /// Prints the document with the style, parses it back and compares
+    /// with the original
+    ///
+    /// The output is parsed with the experimental syntax enabled, use
+    /// `roundtrip_with_options` for documents with custom definitions.
+    fn roundtrip(&self, style: &Style) -> Result<(), Mismatch> {
+        let mut options = ParserOptions::default();
+        options.client_controlled_nullability(true).fragment_arguments(true);
+        self.roundtrip_with_options(style, &options)
+    }
+
+    /// Same as `roundtrip`, but parses the output with the options
+    fn roundtrip_with_options(&self, style: &Style, options: &ParserOptions)
+        -> Result<(), Mismatch>;
+}
+
+/// The result of printing and parsing a document doesn't match original
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum Mismatch {
+    /// Printed document can't be parsed
+    #[error("printed document can't be parsed: {error}\n{printed}")]
+    Parse {
+        printed: String,
+        error: String,
+    },
+    /// Printed document is parsed as a different document
+    #[error("re-parsed document differs at {path}:\n  \
+             expected: {expected}\n  found: {found}\n{printed}")]
+    Differs {
+        printed: String,
+        /// Path to the first differing node,
+        /// e.g. `definitions[0] > Operation > selection_set > items[1]`
+        path: String,
+        /// Debug representation of the original node
+        expected: String,
+        /// Debug representation of the re-parsed node
+        found: String,
+    },
+}
+
+/// Checks that printing document with the default style and parsing it
+/// back produces the same document
+///
+/// Positions and the syntax details (like positions of the parentheses)
+/// are not compared. The names are compared as strings, so documents with
+/// different text types are equal if their names are.
+pub fn assert_roundtrip<D: Roundtrip>(doc: &D) -> Result<(), Mismatch> {
+    doc.roundtrip(&Style::default())
+}
+
+#[cfg(feature = "query")]
+impl<'a, T> Roundtrip for query::Document<'a, T>
+    where T: Text<'a> + Debug,
+{
+    fn roundtrip_with_options(&self, style: &Style, options: &ParserOptions)
+        -> Result<(), Mismatch>
+    {
+        let printed = self.format(style);
+        let result = query::parse_query_with_options::<String>(
+                &printed, options)
+            .map(|doc| doc.without_positions())
+            .map_err(|e| e.to_string());
+        compare(&self.without_positions(), result, printed)
+    }
+}
+
+#[cfg(feature = "schema")]
+impl<'a, T> Roundtrip for schema::Document<'a, T>
+    where T: Text<'a> + Debug,
+{
+    fn roundtrip_with_options(&self, style: &Style, options: &ParserOptions)
+        -> Result<(), Mismatch>
+    {
+        let printed = self.format(style);
+        let result = schema::parse_schema_with_options::<String>(
+                &printed, options)
+            .map(|doc| doc.without_positions())
+            .map_err(|e| e.to_string());
+        compare(&self.without_positions(), result, printed)
+    }
+}
+
+/// Formatting of the source that isn't kept in the printed document
+///
+/// Whitespace and commas are not significant, so they are not reported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Loss {
+    /// Comment of the source is not printed
+    Comment {
+        position: Pos,
+        text: String,
+    },
+    /// String or number is written differently, e.g. a block string is
+    /// printed as a regular string or escape sequences are replaced
+    Literal {
+        position: Pos,
+        source: String,
+        printed: String,
+    },
+    /// Tokens of the output differ from the source starting at the
+    /// position, e.g. the `query` keyword of an anonymous operation is
+    /// omitted or definitions are sorted
+    ///
+    /// Tokens after this one are not compared.
+    Tokens {
+        position: Pos,
+        source: String,
+        printed: String,
+    },
+}
+
+/// Result of `check_query` and `check_schema`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    /// The source document printed with the style
+    pub printed: String,
+    /// Difference between the parsed source and the re-parsed output
+    pub mismatch: Option<Mismatch>,
+    /// Formatting of the source lost in the output, in source order
+    pub losses: Vec<Loss>,
+}
+
+impl Report {
+    /// Returns true if the printed document has the same meaning as the
+    /// source
+    pub fn is_ok(&self) -> bool {
+        self.mismatch.is_none()
+    }
+
+    /// Returns true if the printed document has the same meaning and
+    /// differs from the source only in whitespace and commas
+    pub fn is_lossless(&self) -> bool {
+        self.mismatch.is_none() && self.losses.is_empty()
+    }
+}
+
+impl fmt::Display for Loss {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Loss::Comment { position, ref text } => {
+                write!(f, "{}: comment is not printed: {}", position, text)
+            }
+            Loss::Literal { position, ref source, ref printed } => {
+                write!(f, "{}: literal {} is printed as {}",
+                       position, source, printed)
+            }
+            Loss::Tokens { position, ref source, ref printed } => {
+                write!(f, "{}: token {} is printed as {}",
+                       position, source, printed)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref mismatch) = self.mismatch {
+            writeln!(f, "{}", mismatch)?;
+        }
+        for loss in &self.losses {
+            writeln!(f, "{}", loss)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses the query, prints it with the style and checks the output
+///
+/// The output is parsed back with the same options and compared with the
+/// source document as in `assert_roundtrip`, and the tokens of the output
+/// are compared with the tokens of the source to find formatting losses. Use
+/// `Style::preserve_comments` to keep the comments.
+#[cfg(feature = "query")]
+pub fn check_query(source: &str, options: &ParserOptions, style: &Style)
+    -> Result<Report, query::ParseError>
+{
+    let doc = query::parse_query_with_options::<&str>(source, options)?;
+    let result = doc.roundtrip_with_options(style, options);
+    Ok(check(source, doc.format(style), result))
+}
+
+/// Parses the schema, prints it with the style and checks the output
+///
+/// See `check_query`. Use `Style::raw_descriptions` to keep the spelling
+/// of the descriptions.
+#[cfg(feature = "schema")]
+pub fn check_schema(source: &str, options: &ParserOptions, style: &Style)
+    -> Result<Report, schema::ParseError>
+{
+    let doc = schema::parse_schema_with_options::<&str>(source, options)?;
+    let result = doc.roundtrip_with_options(style, options);
+    Ok(check(source, doc.format(style), result))
+}
+
+#[cfg(any(feature = "query", feature = "schema"))]
+fn check(source: &str, printed: String, result: Result<(), Mismatch>)
+    -> Report
+{
+    let (source_tokens, source_comments) = tokens(source);
+    let (printed_tokens, mut printed_comments) = tokens(&printed);
+    let mut losses = Vec::new();
+    for (text, position) in source_comments {
+        match printed_comments.iter().position(|&(c, _)| c == text) {
+            Some(idx) => { printed_comments.remove(idx); }
+            None => losses.push(Loss::Comment {
+                position,
+                text: text.to_string(),
+            }),
+        }
+    }
+    let mut printed_tokens = printed_tokens.into_iter();
+    for (token, position) in source_tokens {
+        let printed = match printed_tokens.next() {
+            Some((printed, _)) if printed == token => continue,
+            Some((printed, _)) => Some(printed),
+            None => None,
+        };
+        let kind = printed.and_then(|t| literal_kind(t.kind));
+        let source = token.value.to_string();
+        let printed = printed.map(|t| t.value)
+            .unwrap_or("<end of document>").to_string();
+        if kind.is_some() && kind == literal_kind(token.kind) {
+            losses.push(Loss::Literal { position, source, printed });
+        } else {
+            losses.push(Loss::Tokens { position, source, printed });
+            break;
+        }
+    }
+    losses.sort_by_key(|loss| match *loss {
+        Loss::Comment { position, .. } |
+        Loss::Literal { position, .. } |
+        Loss::Tokens { position, .. } => position.character,
+    });
+    Report {
+        printed,
+        mismatch: result.err(),
+        losses,
+    }
+}
+
+/// Returns the same kind for tokens that can spell the same value
+#[cfg(any(feature = "query", feature = "schema"))]
+fn literal_kind(kind: Kind) -> Option<Kind> {
+    match kind {
+        Kind::StringValue | Kind::BlockString => Some(Kind::StringValue),
+        Kind::IntValue | Kind::FloatValue => Some(Kind::IntValue),
+        _ => None,
+    }
+}
+
+/// Splits the source into the tokens and the comments, up to the end or
+/// the first invalid token
+#[cfg(any(feature = "query", feature = "schema"))]
+#[allow(clippy::type_complexity)]
+fn tokens(source: &str) -> (Vec<(Token<'_>, Pos)>, Vec<(&str, Pos)>) {
+    use combine::{Positioned, StreamOnce};
+
+    let mut stream = TokenStream::with_comments(source);
+    let mut tokens = Vec::new();
+    let mut comments = Vec::new();
+    loop {
+        let pos = stream.position();
+        match stream.uncons() {
+            Ok(tok) if tok.kind == Kind::Comment => {
+                comments.push((tok.value.trim_end(), pos));
+            }
+            Ok(tok) => tokens.push((tok, pos)),
+            Err(_) => return (tokens, comments),
+        }
+    }
+}
+
+/// Compares documents without positions, the path to the first difference
+/// is found by walking both documents
+#[cfg(any(feature = "query", feature = "schema"))]
+fn compare<D: Diff>(original: &D, reparsed: Result<D, String>,
+    printed: String)
+    -> Result<(), Mismatch>
+{
+    let reparsed = match reparsed {
+        Ok(reparsed) => reparsed,
+        Err(error) => return Err(Mismatch::Parse { printed, error }),
+    };
+    match original.diff(&reparsed) {
+        None => Ok(()),
+        Some(mut difference) => {
+            difference.path.reverse();
+            Err(Mismatch::Differs {
+                printed,
+                path: difference.path.join(" > "),
+                expected: difference.expected,
+                found: difference.found,
+            })
+        }
+    }
+}
+
+/// The first difference between two nodes
+#[cfg(any(feature = "query", feature = "schema"))]
+struct Difference {
+    /// Labels of the nodes from the differing one up to the document
+    path: Vec<String>,
+    expected: String,
+    found: String,
+}
+
+/// Node of the document that can be compared by walking its children
+///
+/// Children are walked in the order of the source, so that the path points
+/// to the first differing one. Nodes that differ only in the fields that
+/// aren't walked, like descriptions, are reported as a whole.
+#[cfg(any(feature = "query", feature = "schema"))]
+trait Diff: PartialEq + Debug {
+    fn diff(&self, other: &Self) -> Option<Difference>;
+}
+
+/// Reports the whole node if the nodes differ
+#[cfg(any(feature = "query", feature = "schema"))]
+fn node<D: PartialEq + Debug>(a: &D, b: &D) -> Option<Difference> {
+    if a == b {
+        return None;
+    }
+    Some(Difference {
+        path: Vec::new(),
+        expected: format!("{:?}", a),
+        found: format!("{:?}", b),
+    })
+}
+
+/// Reports the field of the node if its values differ
+#[cfg(any(feature = "query", feature = "schema"))]
+fn value<D: PartialEq + Debug>(label: &str, a: &D, b: &D)
+    -> Option<Difference>
+{
+    if a == b {
+        return None;
+    }
+    Some(Difference {
+        path: vec![label.to_string()],
+        expected: format!("{}: {:?}", label, a),
+        found: format!("{}: {:?}", label, b),
+    })
+}
+
+/// Walks the child node stored in the field
+#[cfg(any(feature = "query", feature = "schema"))]
+fn child<D: Diff>(label: &str, a: &D, b: &D) -> Option<Difference> {
+    within(label, a.diff(b))
+}
+
+#[cfg(any(feature = "query", feature = "schema"))]
+fn within(label: &str, difference: Option<Difference>)
+    -> Option<Difference>
+{
+    difference.map(|mut difference| {
+        difference.path.push(label.to_string());
+        difference
+    })
+}
+
+/// Walks the first differing item of the list, or reports the first item
+/// missing in one of the lists
+#[cfg(any(feature = "query", feature = "schema"))]
+fn list<D: Diff>(label: &str, a: &[D], b: &[D]) -> Option<Difference> {
+    let idx = match a.iter().zip(b).position(|(x, y)| x != y) {
+        Some(idx) => idx,
+        None if a.len() == b.len() => return None,
+        None => a.len().min(b.len()),
+    };
+    let label = format!("{}[{}]", label, idx);
+    match (a.get(idx), b.get(idx)) {
+        (Some(x), Some(y)) => child(&label, x, y),
+        (x, y) => {
+            let show = |item: Option<&D>| {
+                item.map_or_else(|| "<none>".to_string(),
+                                 |item| format!("{:?}", item))
+            };
+            Some(Difference {
+                path: vec![label],
+                expected: show(x),
+                found: show(y),
+            })
+        }
+    }
+}
+
+#[cfg(any(feature = "query", feature = "schema"))]
+impl<'a> Diff for Directive<'a, String> {
+    fn diff(&self, other: &Self) -> Option<Difference> {
+        value("name", &self.name, &other.name)
+            .or_else(|| list("arguments", &self.arguments, &other.arguments))
+            .or_else(|| node(self, other))
+    }
+}
+
+#[cfg(any(feature = "query", feature = "schema"))]
+impl<'a> Diff for DirectiveArgument<'a, String> {
+    fn diff(&self, other: &Self) -> Option<Difference> {
+        value("name", &self.name, &other.name)
+            .or_else(|| value("value", &self.value, &other.value))
+            .or_else(|| node(self, other))
+    }
+}
+
+#[cfg(feature = "query")]
+impl<'a> Diff for query::Document<'a, String> {
+    fn diff(&self, other: &Self) -> Option<Difference> {
+        list("definitions", &self.definitions, &other.definitions)
+    }
+}
+
+#[cfg(feature = "query")]
+impl<'a> Diff for query::Definition<'a, String> {
+    fn diff(&self, other: &Self) -> Option<Difference> {
+        use crate::query::Definition::*;
+        match (self, other) {
+            (Operation(a), Operation(b)) => child("Operation", a, b),
+            (Fragment(a), Fragment(b)) => child("Fragment", a, b),
+            _ => node(self, other),
+        }
+    }
+}
+
+#[cfg(feature = "query")]
+impl<'a> Diff for query::Operation<'a, String> {
+    fn diff(&self, other: &Self) -> Option<Difference> {
+        value("kind", &self.kind, &other.kind)
+            .or_else(|| value("name", &self.name, &other.name))
+            .or_else(|| list("variable_definitions",
+                             &self.variable_definitions,
+                             &other.variable_definitions))
+            .or_else(|| list("directives",
+                             &self.directives, &other.directives))
+            .or_else(|| child("selection_set",
+                              &self.selection_set, &other.selection_set))
+            .or_else(|| node(self, other))
+    }
+}
+
+#[cfg(feature = "query")]
+impl<'a> Diff for query::FragmentDefinition<'a, String> {
+    fn diff(&self, other: &Self) -> Option<Difference> {
+        value("name", &self.name, &other.name)
+            .or_else(|| list("variable_definitions",
+                             &self.variable_definitions,
+                             &other.variable_definitions))
+            .or_else(|| value("type_condition",
+                              &self.type_condition, &other.type_condition))
+            .or_else(|| list("directives",
+                             &self.directives, &other.directives))
+            .or_else(|| child("selection_set",
+                              &self.selection_set, &other.selection_set))
+            .or_else(|| node(self, other))
+    }
+}
+
+#[cfg(feature = "query")]
+impl<'a> Diff for query::VariableDefinition<'a, String> {
+    fn diff(&self, other: &Self) -> Option<Difference> {
+        value("name", &self.name, &other.name)
+            .or_else(|| value("var_type", &self.var_type, &other.var_type))
+            .or_else(|| value("default_value",
+                              &self.default_value, &other.default_value))
+            .or_else(|| list("directives",
+                             &self.directives, &other.directives))
+            .or_else(|| node(self, other))
+    }
+}
+
+#[cfg(feature = "query")]
+impl<'a> Diff for query::SelectionSet<'a, String> {
+    fn diff(&self, other: &Self) -> Option<Difference> {
+        list("items", &self.items, &other.items)
+            .or_else(|| node(self, other))
+    }
+}
+
+#[cfg(feature = "query")]
+impl<'a> Diff for query::Selection<'a, String> {
+    fn diff(&self, other: &Self) -> Option<Difference> {
+        use crate::query::Selection::*;
+        match (self, other) {
+            (Field(a), Field(b)) => child("Field", a, b),
+            (FragmentSpread(a), FragmentSpread(b)) => {
+                child("FragmentSpread", a, b)
+            }
+            (InlineFragment(a), InlineFragment(b)) => {
+                child("InlineFragment", a, b)
+            }
+            _ => node(self, other),
+        }
+    }
+}
+
+#[cfg(feature = "query")]
+impl<'a> Diff for query::Field<'a, String> {
+    fn diff(&self, other: &Self) -> Option<Difference> {
+        value("alias", &self.alias, &other.alias)
+            .or_else(|| value("name", &self.name, &other.name))
+            .or_else(|| list("arguments", &self.arguments, &other.arguments))
+            .or_else(|| value("nullability",
+                              &self.nullability, &other.nullability))
+            .or_else(|| list("directives",
+                             &self.directives, &other.directives))
+            .or_else(|| child("selection_set",
+                              &self.selection_set, &other.selection_set))
+            .or_else(|| node(self, other))
+    }
+}
+
+#[cfg(feature = "query")]
+impl<'a> Diff for query::FragmentSpread<'a, String> {
+    fn diff(&self, other: &Self) -> Option<Difference> {
+        value("fragment_name", &self.fragment_name, &other.fragment_name)
+            .or_else(|| list("arguments", &self.arguments, &other.arguments))
+            .or_else(|| list("directives",
+                             &self.directives, &other.directives))
+            .or_else(|| node(self, other))
+    }
+}
+
+#[cfg(feature = "query")]
+impl<'a> Diff for query::InlineFragment<'a, String> {
+    fn diff(&self, other: &Self) -> Option<Difference> {
+        value("type_condition", &self.type_condition, &other.type_condition)
+            .or_else(|| list("directives",
+                             &self.directives, &other.directives))
+            .or_else(|| child("selection_set",
+                              &self.selection_set, &other.selection_set))
+            .or_else(|| node(self, other))
+    }
+}
+
+/// Argument of a field or of a fragment spread
+#[cfg(feature = "query")]
+impl<'a> Diff for (String, Value<'a, String>) {
+    fn diff(&self, other: &Self) -> Option<Difference> {
+        value("name", &self.0, &other.0)
+            .or_else(|| value("value", &self.1, &other.1))
+    }
+}
+
+#[cfg(feature = "schema")]
+impl<'a> Diff for schema::Document<'a, String> {
+    fn diff(&self, other: &Self) -> Option<Difference> {
+        list("definitions", &self.definitions, &other.definitions)
+    }
+}
+
+#[cfg(feature = "schema")]
+impl<'a> Diff for schema::Definition<'a, String> {
+    fn diff(&self, other: &Self) -> Option<Difference> {
+        use crate::schema::Definition::*;
+        match (self, other) {
+            (TypeDefinition(a), TypeDefinition(b)) => {
+                child("TypeDefinition", a, b)
+            }
+            (TypeExtension(a), TypeExtension(b)) => {
+                child("TypeExtension", a, b)
+            }
+            (DirectiveDefinition(a), DirectiveDefinition(b)) => {
+                within("DirectiveDefinition",
+                    value("name", &a.name, &b.name)
+                    .or_else(|| list("arguments", &a.arguments, &b.arguments))
+                    .or_else(|| node(a, b)))
+            }
+            _ => node(self, other),
+        }
+    }
+}
+
+#[cfg(feature = "schema")]
+impl<'a> Diff for schema::TypeDefinition<'a, String> {
+    fn diff(&self, other: &Self) -> Option<Difference> {
+        use crate::schema::TypeDefinition::*;
+        let (label, difference) = match (self, other) {
+            (Scalar(a), Scalar(b)) => ("Scalar",
+                value("name", &a.name, &b.name)
+                .or_else(|| list("directives", &a.directives, &b.directives))
+                .or_else(|| node(a, b))),
+            (Object(a), Object(b)) => ("Object",
+                value("name", &a.name, &b.name)
+                .or_else(|| list("directives", &a.directives, &b.directives))
+                .or_else(|| list("fields", &a.fields, &b.fields))
+                .or_else(|| node(a, b))),
+            (Interface(a), Interface(b)) => ("Interface",
+                value("name", &a.name, &b.name)
+                .or_else(|| list("directives", &a.directives, &b.directives))
+                .or_else(|| list("fields", &a.fields, &b.fields))
+                .or_else(|| node(a, b))),
+            (Union(a), Union(b)) => ("Union",
+                value("name", &a.name, &b.name)
+                .or_else(|| list("directives", &a.directives, &b.directives))
+                .or_else(|| node(a, b))),
+            (Enum(a), Enum(b)) => ("Enum",
+                value("name", &a.name, &b.name)
+                .or_else(|| list("directives", &a.directives, &b.directives))
+                .or_else(|| list("values", &a.values, &b.values))
+                .or_else(|| node(a, b))),
+            (InputObject(a), InputObject(b)) => ("InputObject",
+                value("name", &a.name, &b.name)
+                .or_else(|| list("directives", &a.directives, &b.directives))
+                .or_else(|| list("fields", &a.fields, &b.fields))
+                .or_else(|| node(a, b))),
+            _ => return node(self, other),
+        };
+        within(label, difference)
+    }
+}
+
+#[cfg(feature = "schema")]
+impl<'a> Diff for schema::TypeExtension<'a, String> {
+    fn diff(&self, other: &Self) -> Option<Difference> {
+        use crate::schema::TypeExtension::*;
+        let (label, difference) = match (self, other) {
+            (Scalar(a), Scalar(b)) => ("Scalar",
+                value("name", &a.name, &b.name)
+                .or_else(|| list("directives", &a.directives, &b.directives))
+                .or_else(|| node(a, b))),
+            (Object(a), Object(b)) => ("Object",
+                value("name", &a.name, &b.name)
+                .or_else(|| list("directives", &a.directives, &b.directives))
+                .or_else(|| list("fields", &a.fields, &b.fields))
+                .or_else(|| node(a, b))),
+            (Interface(a), Interface(b)) => ("Interface",
+                value("name", &a.name, &b.name)
+                .or_else(|| list("directives", &a.directives, &b.directives))
+                .or_else(|| list("fields", &a.fields, &b.fields))
+                .or_else(|| node(a, b))),
+            (Union(a), Union(b)) => ("Union",
+                value("name", &a.name, &b.name)
+                .or_else(|| list("directives", &a.directives, &b.directives))
+                .or_else(|| node(a, b))),
+            (Enum(a), Enum(b)) => ("Enum",
+                value("name", &a.name, &b.name)
+                .or_else(|| list("directives", &a.directives, &b.directives))
+                .or_else(|| list("values", &a.values, &b.values))
+                .or_else(|| node(a, b))),
+            (InputObject(a), InputObject(b)) => ("InputObject",
+                value("name", &a.name, &b.name)
+                .or_else(|| list("directives", &a.directives, &b.directives))
+                .or_else(|| list("fields", &a.fields, &b.fields))
+                .or_else(|| node(a, b))),
+            _ => return node(self, other),
+        };
+        within(label, difference)
+    }
+}
+
+#[cfg(feature = "schema")]
+impl<'a> Diff for schema::Field<'a, String> {
+    fn diff(&self, other: &Self) -> Option<Difference> {
+        value("name", &self.name, &other.name)
+            .or_else(|| list("arguments", &self.arguments, &other.arguments))
+            .or_else(|| value("field_type",
+                              &self.field_type, &other.field_type))
+            .or_else(|| list("directives",
+                             &self.directives, &other.directives))
+            .or_else(|| node(self, other))
+    }
+}
+
+#[cfg(feature = "schema")]
+impl<'a> Diff for schema::InputValue<'a, String> {
+    fn diff(&self, other: &Self) -> Option<Difference> {
+        value("name", &self.name, &other.name)
+            .or_else(|| value("value_type",
+                              &self.value_type, &other.value_type))
+            .or_else(|| value("default_value",
+                              &self.default_value, &other.default_value))
+            .or_else(|| list("directives",
+                             &self.directives, &other.directives))
+            .or_else(|| node(self, other))
+    }
+}
+
+#[cfg(feature = "schema")]
+impl<'a> Diff for schema::EnumValue<'a, String> {
+    fn diff(&self, other: &Self) -> Option<Difference> {
+        value("name", &self.name, &other.name)
+            .or_else(|| list("directives",
+                             &self.directives, &other.directives))
+            .or_else(|| node(self, other))
+    }
+}
+
+#[cfg(all(test, feature = "query", feature = "schema"))]
+mod test {
+    use crate::{parse_query, parse_schema, schema};
+    use crate::query::{Definition, Document, OperationKind, Selection};
+    use crate::{ParserOptions, Style};
+    use crate::position::Pos;
+    use super::{assert_roundtrip, check_query, check_schema, compare};
+    use super::{Loss, Mismatch};
+
+    #[test]
+    fn ok() {
+        assert_roundtrip(&parse_query::<&str>("
+            query ($a: [Int!] = [1, 2]) @dir { a(x: $a) ...F ... on T { b } }
+            fragment F on T { c: d(obj: {x: \"y\"}) }
+        ").unwrap()).unwrap();
+        assert_roundtrip(&parse_schema::<String>("
+            \"\"\"Desc\"\"\"
+            type T implements I @key(fields: \"id\") { f(a: Int = 1): [T!]! }
+            enum E { A B } union U = A | B
+        ").unwrap()).unwrap();
+    }
+
+    fn rename_first<'a>(doc: &mut Document<'a, String>, name: &str) {
+        if let Definition::Operation(ref mut op) = doc.definitions[0] {
+            if let Selection::Field(ref mut field) = op.selection_set.items[0] {
+                field.name = name.into();
+            }
+        }
+    }
+
+    #[test]
+    fn differs() {
+        let mut doc = parse_query::<String>("{ a }").unwrap();
+        rename_first(&mut doc, "x y");
+        match assert_roundtrip(&doc).unwrap_err() {
+            Mismatch::Differs { path, expected, found, .. } => {
+                assert_eq!(path, "definitions[0] > Operation > \
+                                  selection_set > items[0] > Field > name");
+                assert_eq!(expected, "name: \"x y\"");
+                assert_eq!(found, "name: \"x\"");
+            }
+            e => panic!("unexpected {:?}", e),
+        }
+    }
+
+    #[test]
+    fn differs_after_string() {
+        let mut doc = parse_query::<String>("{ a(s: \"Pos(\") b }").unwrap();
+        if let Definition::Operation(ref mut op) = doc.definitions[0] {
+            if let Selection::Field(ref mut field) = op.selection_set.items[1] {
+                field.name = "x y".into();
+            }
+        }
+        match assert_roundtrip(&doc).unwrap_err() {
+            Mismatch::Differs { path, expected, found, .. } => {
+                assert_eq!(path, "definitions[0] > Operation > \
+                                  selection_set > items[1] > Field > name");
+                assert_eq!(expected, "name: \"x y\"");
+                assert_eq!(found, "name: \"x\"");
+            }
+            e => panic!("unexpected {:?}", e),
+        }
+    }
+
+    #[test]
+    fn differs_in_length() {
+        let doc = parse_query::<String>("{ a }").unwrap();
+        let reparsed = parse_query::<String>("{ a } { b }").unwrap();
+        let result = compare(&doc.without_positions(),
+                             Ok(reparsed.without_positions()), String::new());
+        match result.unwrap_err() {
+            Mismatch::Differs { path, expected, found, .. } => {
+                assert_eq!(path, "definitions[1]");
+                assert_eq!(expected, "<none>");
+                assert!(found.starts_with("Operation("), "{}", found);
+            }
+            e => panic!("unexpected {:?}", e),
+        }
+    }
+
+    #[test]
+    fn differs_in_schema() {
+        let mut doc = parse_schema::<String>("enum E { A } type T { f: E }")
+            .unwrap();
+        if let schema::Definition::TypeDefinition(
+            schema::TypeDefinition::Enum(ref mut enum_type)
+        ) = doc.definitions[0] {
+            enum_type.values[0].name = "A B".into();
+        }
+        match assert_roundtrip(&doc).unwrap_err() {
+            Mismatch::Differs { path, expected, found, .. } => {
+                assert_eq!(path, "definitions[0] > TypeDefinition > Enum > \
+                                  values[0] > name");
+                assert_eq!(expected, "name: \"A B\"");
+                assert_eq!(found, "name: \"A\"");
+            }
+            e => panic!("unexpected {:?}", e),
+        }
+    }
+
+    #[test]
+    fn implicit_query_with_variables() {
+        let mut doc = parse_query::<String>("query($x: Int) @d { a(x: $x) }")
+            .unwrap();
+        if let Definition::Operation(ref mut op) = doc.definitions[0] {
+            op.kind = OperationKind::ImplicitQuery;
+        }
+        assert_eq!(doc.to_string(), "query($x: Int) @d {\n  a(x: $x)\n}\n");
+    }
+
+    #[test]
+    fn unparseable() {
+        let mut doc = parse_query::<String>("{ a }").unwrap();
+        rename_first(&mut doc, "a(");
+        match assert_roundtrip(&doc).unwrap_err() {
+            Mismatch::Parse { printed, .. } => {
+                assert_eq!(printed, "{\n  a(\n}\n");
+            }
+            e => panic!("unexpected {:?}", e),
+        }
+    }
+
+    fn pos(line: usize, column: usize, character: usize, token: usize)
+        -> Pos
+    {
+        Pos { line, column, character, token }
+    }
+
+    #[test]
+    fn check_losses() {
+        let options = ParserOptions::default();
+        let style = Style::default();
+        let source = "{ a(s: \"\"\"s\"\"\") # a\n}";
+        let report = check_query(source, &options, &style).unwrap();
+        assert!(report.is_ok());
+        assert_eq!(report.losses, vec![
+            Loss::Literal {
+                position: pos(1, 8, 7, 5),
+                source: "\"\"\"s\"\"\"".into(),
+                printed: "\"\"\"\n    s\n  \"\"\"".into(),
+            },
+            Loss::Comment { position: pos(1, 17, 16, 7), text: "# a".into() },
+        ]);
+        assert_eq!(report.to_string(), "\
+            1:8: literal \"\"\"s\"\"\" is printed as \"\"\"\n    s\n  \"\"\"\n\
+            1:17: comment is not printed: # a\n");
+
+        let source = "# t\ntype T { f: Int }";
+        let mut style = Style::default();
+        style.preserve_comments(source);
+        let report = check_schema(source, &options, &style).unwrap();
+        assert!(report.is_lossless(), "{}", report);
+
+        let source = "scalar Int type T { f: Int }";
+        let mut style = Style::default();
+        style.omit_builtin_scalars(true);
+        let report = check_schema(source, &options, &style).unwrap();
+        assert!(matches!(report.mismatch, Some(Mismatch::Differs { .. })));
+        assert_eq!(report.losses, vec![
+            Loss::Tokens {
+                position: pos(1, 1, 0, 0),
+                source: "scalar".into(),
+                printed: "type".into(),
+            },
+        ]);
+        assert!(check_schema("type", &options, &style).is_err());
+    }
+
+    #[test]
+    fn check_with_options() {
+        use std::sync::Arc;
+        use combine::StreamOnce;
+
+        let mut options = ParserOptions::default();
+        options.custom_definition("vendor", |tokens| {
+            tokens.uncons().map_err(|e| e.to_string())?;
+            tokens.uncons().map_err(|e| e.to_string())?;
+            Ok(Arc::new(()))
+        });
+        let style = Style::default();
+        let report = check_schema("vendor thing\ntype T { f: Int }",
+                                  &options, &style).unwrap();
+        assert!(report.is_ok(), "{}", report);
+
+        options.client_controlled_nullability(true);
+        assert!(check_query("{ a! }", &options, &style).unwrap().is_ok());
+        assert!(check_query("query($x: Int) { ...F(x: $x) }",
+                            &options, &style).is_err());
+    }
+}
@@ -1,8 +1,8 @@
-use std::str::FromStr;
-
-use thiserror::Error;
-
 pub use crate::common::{Directive, Type, Value, Text};
+pub use crate::common::{Description, DirectiveLocation};
+pub use crate::common::InvalidDirectiveLocation;
+pub use crate::common::ArgumentsSyntax;
+use crate::options::CustomNode;
 use crate::position::Pos;
 
 #[derive(Debug, Clone, Default, PartialEq)]
@@ -12,12 +12,19 @@ pub struct Document<'a, T: Text<'a>>
     pub definitions: Vec<Definition<'a, T>>,
 }
 
+/// Top-level definition of the schema document
+///
+/// New kinds of definitions may be added in minor releases, like `Custom`
+/// ones were, so matches must have a wildcard arm.
 #[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
 pub enum Definition<'a, T: Text<'a>> {
     SchemaDefinition(SchemaDefinition<'a, T>),
+    SchemaExtension(SchemaExtension<'a, T>),
     TypeDefinition(TypeDefinition<'a, T>),
     TypeExtension(TypeExtension<'a, T>),
     DirectiveDefinition(DirectiveDefinition<'a, T>),
+    Custom(CustomDefinition<'a, T>),
 }
 
 #[derive(Debug, Clone, Default, PartialEq)]
@@ -29,6 +36,41 @@ pub struct SchemaDefinition<'a, T: Text<'a>> {
     pub subscription: Option<T::Value>,
 }
 
+/// The `extend schema` definition
+///
+/// Either directives or at least one operation type is present.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SchemaExtension<'a, T: Text<'a>> {
+    pub position: Pos,
+    pub directives: Vec<Directive<'a, T>>,
+    pub query: Option<T::Value>,
+    pub mutation: Option<T::Value>,
+    pub subscription: Option<T::Value>,
+}
+
+/// Definition parsed by a handler registered in `ParserOptions`
+///
+/// Two custom definitions are equal if their position and source text are
+/// equal, the opaque node isn't compared.
+#[derive(Debug, Clone)]
+pub struct CustomDefinition<'a, T: Text<'a>> {
+    pub position: Pos,
+    pub keyword: T::Value,
+    /// Original source text of the definition, used for formatting
+    pub source: T::Value,
+    pub node: CustomNode,
+}
+
+impl<'a, T> PartialEq for CustomDefinition<'a, T>
+    where T: Text<'a>
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.position == other.position &&
+            self.keyword == other.keyword &&
+            self.source == other.source
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum TypeDefinition<'a, T: Text<'a>> {
     Scalar(ScalarType<'a, T>),
@@ -39,6 +81,46 @@ pub enum TypeDefinition<'a, T: Text<'a>> {
     InputObject(InputObjectType<'a, T>),
 }
 
+impl<'a, T> TypeDefinition<'a, T>
+    where T: Text<'a>
+{
+    /// Returns the name of the type
+    pub fn name(&self) -> &T::Value {
+        match *self {
+            TypeDefinition::Scalar(ref t) => &t.name,
+            TypeDefinition::Object(ref t) => &t.name,
+            TypeDefinition::Interface(ref t) => &t.name,
+            TypeDefinition::Union(ref t) => &t.name,
+            TypeDefinition::Enum(ref t) => &t.name,
+            TypeDefinition::InputObject(ref t) => &t.name,
+        }
+    }
+
+    /// Returns the position of the type
+    pub fn position(&self) -> Pos {
+        match *self {
+            TypeDefinition::Scalar(ref t) => t.position,
+            TypeDefinition::Object(ref t) => t.position,
+            TypeDefinition::Interface(ref t) => t.position,
+            TypeDefinition::Union(ref t) => t.position,
+            TypeDefinition::Enum(ref t) => t.position,
+            TypeDefinition::InputObject(ref t) => t.position,
+        }
+    }
+
+    /// Returns the directives of the type
+    pub fn directives(&self) -> &[Directive<'a, T>] {
+        match *self {
+            TypeDefinition::Scalar(ref t) => &t.directives,
+            TypeDefinition::Object(ref t) => &t.directives,
+            TypeDefinition::Interface(ref t) => &t.directives,
+            TypeDefinition::Union(ref t) => &t.directives,
+            TypeDefinition::Enum(ref t) => &t.directives,
+            TypeDefinition::InputObject(ref t) => &t.directives,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum TypeExtension<'a, T: Text<'a>> {
     Scalar(ScalarTypeExtension<'a, T>),
@@ -49,10 +131,50 @@ pub enum TypeExtension<'a, T: Text<'a>> {
     InputObject(InputObjectTypeExtension<'a, T>),
 }
 
+impl<'a, T> TypeExtension<'a, T>
+    where T: Text<'a>
+{
+    /// Returns the name of the extended type
+    pub fn name(&self) -> &T::Value {
+        match *self {
+            TypeExtension::Scalar(ref t) => &t.name,
+            TypeExtension::Object(ref t) => &t.name,
+            TypeExtension::Interface(ref t) => &t.name,
+            TypeExtension::Union(ref t) => &t.name,
+            TypeExtension::Enum(ref t) => &t.name,
+            TypeExtension::InputObject(ref t) => &t.name,
+        }
+    }
+
+    /// Returns the position of the extension
+    pub fn position(&self) -> Pos {
+        match *self {
+            TypeExtension::Scalar(ref t) => t.position,
+            TypeExtension::Object(ref t) => t.position,
+            TypeExtension::Interface(ref t) => t.position,
+            TypeExtension::Union(ref t) => t.position,
+            TypeExtension::Enum(ref t) => t.position,
+            TypeExtension::InputObject(ref t) => t.position,
+        }
+    }
+
+    /// Returns the directives of the extension
+    pub fn directives(&self) -> &[Directive<'a, T>] {
+        match *self {
+            TypeExtension::Scalar(ref t) => &t.directives,
+            TypeExtension::Object(ref t) => &t.directives,
+            TypeExtension::Interface(ref t) => &t.directives,
+            TypeExtension::Union(ref t) => &t.directives,
+            TypeExtension::Enum(ref t) => &t.directives,
+            TypeExtension::InputObject(ref t) => &t.directives,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct ScalarType<'a, T: Text<'a>> {
     pub position: Pos,
-    pub description: Option<String>,
+    pub description: Option<Description>,
     pub name: T::Value,
     pub directives: Vec<Directive<'a, T>>,
 }
@@ -92,7 +214,7 @@ impl<'a, T> ScalarTypeExtension<'a, T>
 #[derive(Debug, Clone, PartialEq)]
 pub struct ObjectType<'a, T: Text<'a>> {
     pub position: Pos,
-    pub description: Option<String>,
+    pub description: Option<Description>,
     pub name: T::Value,
     pub implements_interfaces: Vec<T::Value>,
     pub directives: Vec<Directive<'a, T>>,
@@ -140,7 +262,7 @@ impl<'a, T> ObjectTypeExtension<'a, T>
 #[derive(Debug, Clone, PartialEq)]
 pub struct Field<'a, T: Text<'a>> {
     pub position: Pos,
-    pub description: Option<String>,
+    pub description: Option<Description>,
     pub name: T::Value,
     pub arguments: Vec<InputValue<'a, T>>,
     pub field_type: Type<'a, T>,
@@ -150,18 +272,84 @@ pub struct Field<'a, T: Text<'a>> {
 #[derive(Debug, Clone, PartialEq)]
 pub struct InputValue<'a, T: Text<'a>> {
     pub position: Pos,
-    pub description: Option<String>,
+    pub description: Option<Description>,
     pub name: T::Value,
     pub value_type: Type<'a, T>,
     pub default_value: Option<Value<'a, T>>,
     pub directives: Vec<Directive<'a, T>>,
 }
 
+impl<'a, T> Field<'a, T>
+    where T: Text<'a>
+{
+    /// Returns the deprecation set by the `@deprecated` directive
+    ///
+    /// ```rust
+    /// # extern crate edb_graphql_parser;
+    /// use edb_graphql_parser::parse_schema;
+    /// use edb_graphql_parser::schema::{Definition, TypeDefinition};
+    ///
+    /// let doc = parse_schema::<&str>(r#"type Query {
+    ///     a: Int @deprecated(reason: "Use b")
+    ///     b: Int
+    ///     c: Int @deprecated
+    /// }"#).unwrap();
+    /// if let Definition::TypeDefinition(TypeDefinition::Object(ref t))
+    ///     = doc.definitions[0]
+    /// {
+    ///     let reasons = t.fields.iter()
+    ///         .map(|f| f.deprecation().and_then(|d| d.reason))
+    ///         .collect::<Vec<_>>();
+    ///     assert_eq!(reasons,
+    ///                [Some("Use b"), None, Some("No longer supported")]);
+    /// }
+    /// ```
+    pub fn deprecation(&self) -> Option<Deprecation<'_>> {
+        deprecation(&self.directives)
+    }
+}
+
+impl<'a, T> InputValue<'a, T>
+    where T: Text<'a>
+{
+    /// Returns the deprecation of the argument or input field, see
+    /// `Field::deprecation`
+    pub fn deprecation(&self) -> Option<Deprecation<'_>> {
+        deprecation(&self.directives)
+    }
+}
+
+/// Deprecation set by the `@deprecated(reason:)` directive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deprecation<'x> {
+    /// The `reason` argument, or `DEFAULT_DEPRECATION_REASON` if it's not
+    /// specified (or is not a string). `None` if the reason is `null`.
+    pub reason: Option<&'x str>,
+}
+
+/// Default value of the `reason` argument of `@deprecated`
+pub const DEFAULT_DEPRECATION_REASON: &str = "No longer supported";
+
+fn deprecation<'x, 'a, T>(directives: &'x [Directive<'a, T>])
+    -> Option<Deprecation<'x>>
+    where T: Text<'a>,
+{
+    let directive = directives.iter()
+        .find(|d| d.name.as_ref() == "deprecated")?;
+    let reason = match directive.argument("reason").map(|arg| &arg.value) {
+        Some(Value::String(ref reason)) => Some(reason.as_ref()),
+        Some(Value::Null) => None,
+        _ => Some(DEFAULT_DEPRECATION_REASON),
+    };
+    Some(Deprecation { reason })
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct InterfaceType<'a, T: Text<'a>> {
     pub position: Pos,
-    pub description: Option<String>,
+    pub description: Option<Description>,
     pub name: T::Value,
+    pub implements_interfaces: Vec<T::Value>,
     pub directives: Vec<Directive<'a, T>>,
     pub fields: Vec<Field<'a, T>>,
 }
@@ -174,6 +362,7 @@ impl<'a, T> InterfaceType<'a, T>
             position: Pos::default(),
             description: None,
             name,
+            implements_interfaces: vec![],
             directives: vec![],
             fields: vec![],
         }
@@ -184,6 +373,7 @@ impl<'a, T> InterfaceType<'a, T>
 pub struct InterfaceTypeExtension<'a, T: Text<'a>> {
     pub position: Pos,
     pub name: T::Value,
+    pub implements_interfaces: Vec<T::Value>,
     pub directives: Vec<Directive<'a, T>>,
     pub fields: Vec<Field<'a, T>>,
 }
@@ -195,6 +385,7 @@ where T: Text<'a>
         Self {
             position: Pos::default(),
             name,
+            implements_interfaces: vec![],
             directives: vec![],
             fields: vec![],
         }
@@ -204,7 +395,7 @@ where T: Text<'a>
 #[derive(Debug, Clone, PartialEq)]
 pub struct UnionType<'a, T: Text<'a>> {
     pub position: Pos,
-    pub description: Option<String>,
+    pub description: Option<Description>,
     pub name: T::Value,
     pub directives: Vec<Directive<'a, T>>,
     pub types: Vec<T::Value>,
@@ -248,7 +439,7 @@ where T: Text<'a>
 #[derive(Debug, Clone, PartialEq)]
 pub struct EnumType<'a, T: Text<'a>> {
     pub position: Pos,
-    pub description: Option<String>,
+    pub description: Option<Description>,
     pub name: T::Value,
     pub directives: Vec<Directive<'a, T>>,
     pub values: Vec<EnumValue<'a, T>>,
@@ -271,7 +462,7 @@ where T: Text<'a>
 #[derive(Debug, Clone, PartialEq)]
 pub struct EnumValue<'a, T: Text<'a>> {
     pub position: Pos,
-    pub description: Option<String>,
+    pub description: Option<Description>,
     pub name: T::Value,
     pub directives: Vec<Directive<'a, T>>,
 }
@@ -287,6 +478,11 @@ where T: Text<'a>
             directives: vec![],
         }
     }
+
+    /// Returns the deprecation of the value, see `Field::deprecation`
+    pub fn deprecation(&self) -> Option<Deprecation<'_>> {
+        deprecation(&self.directives)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -313,7 +509,7 @@ where T: Text<'a>
 #[derive(Debug, Clone, PartialEq)]
 pub struct InputObjectType<'a, T: Text<'a>> {
     pub position: Pos,
-    pub description: Option<String>,
+    pub description: Option<Description>,
     pub name: T::Value,
     pub directives: Vec<Directive<'a, T>>,
     pub fields: Vec<InputValue<'a, T>>,
@@ -354,37 +550,14 @@ where T: Text<'a>
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub enum DirectiveLocation {
-    // executable
-    Query,
-    Mutation,
-    Subscription,
-    Field,
-    FragmentDefinition,
-    FragmentSpread,
-    InlineFragment,
-
-    // type_system
-    Schema,
-    Scalar,
-    Object,
-    FieldDefinition,
-    ArgumentDefinition,
-    Interface,
-    Union,
-    Enum,
-    EnumValue,
-    InputObject,
-    InputFieldDefinition,
-}
-
 #[derive(Debug, Clone, PartialEq)]
 pub struct DirectiveDefinition<'a, T: Text<'a>> {
     pub position: Pos,
-    pub description: Option<String>,
+    pub description: Option<Description>,
     pub name: T::Value,
     pub arguments: Vec<InputValue<'a, T>>,
+    /// Directive may be used more than once at the same location
+    pub repeatable: bool,
     pub locations: Vec<DirectiveLocation>,
 }
 
@@ -397,103 +570,8 @@ where T: Text<'a>
             description: None,
             name,
             arguments: vec![],
+            repeatable: false,
             locations: vec![],
         }
     }
 }
-
-impl DirectiveLocation {
-    /// Returns GraphQL syntax compatible name of the directive
-    pub fn as_str(&self) -> &'static str {
-        use self::DirectiveLocation::*;
-        match *self {
-            Query => "QUERY",
-            Mutation => "MUTATION",
-            Subscription => "SUBSCRIPTION",
-            Field => "FIELD",
-            FragmentDefinition => "FRAGMENT_DEFINITION",
-            FragmentSpread => "FRAGMENT_SPREAD",
-            InlineFragment => "INLINE_FRAGMENT",
-            Schema => "SCHEMA",
-            Scalar => "SCALAR",
-            Object => "OBJECT",
-            FieldDefinition => "FIELD_DEFINITION",
-            ArgumentDefinition => "ARGUMENT_DEFINITION",
-            Interface => "INTERFACE",
-            Union => "UNION",
-            Enum => "ENUM",
-            EnumValue => "ENUM_VALUE",
-            InputObject => "INPUT_OBJECT",
-            InputFieldDefinition => "INPUT_FIELD_DEFINITION",
-        }
-    }
-
-    /// Returns `true` if this location is for queries (execution)
-    pub fn is_query(&self) -> bool {
-        use self::DirectiveLocation::*;
-        match *self {
-            Query
-            | Mutation
-            | Subscription
-            | Field
-            | FragmentDefinition
-            | FragmentSpread
-            | InlineFragment
-                => true,
-
-            Schema
-            | Scalar
-            | Object
-            | FieldDefinition
-            | ArgumentDefinition
-            | Interface
-            | Union
-            | Enum
-            | EnumValue
-            | InputObject
-            | InputFieldDefinition
-                => false,
-        }
-    }
-
-    /// Returns `true` if this location is for schema
-    pub fn is_schema(&self) -> bool {
-        !self.is_query()
-    }
-}
-
-#[derive(Debug, Error)]
-#[error("invalid directive location")]
-pub struct InvalidDirectiveLocation;
-
-
-impl FromStr for DirectiveLocation {
-    type Err = InvalidDirectiveLocation;
-    fn from_str(s: &str) -> Result<DirectiveLocation, InvalidDirectiveLocation>
-    {
-        use self::DirectiveLocation::*;
-        let val = match s {
-            "QUERY" => Query,
-            "MUTATION" => Mutation,
-            "SUBSCRIPTION" => Subscription,
-            "FIELD" => Field,
-            "FRAGMENT_DEFINITION" => FragmentDefinition,
-            "FRAGMENT_SPREAD" => FragmentSpread,
-            "INLINE_FRAGMENT" => InlineFragment,
-            "SCHEMA" => Schema,
-            "SCALAR" => Scalar,
-            "OBJECT" => Object,
-            "FIELD_DEFINITION" => FieldDefinition,
-            "ARGUMENT_DEFINITION" => ArgumentDefinition,
-            "INTERFACE" => Interface,
-            "UNION" => Union,
-            "ENUM" => Enum,
-            "ENUM_VALUE" => EnumValue,
-            "INPUT_OBJECT" => InputObject,
-            "INPUT_FIELD_DEFINITION" => InputFieldDefinition,
-            _ => return Err(InvalidDirectiveLocation),
-        };
-
-        Ok(val)
-    }
-}
@@ -0,0 +1,335 @@
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::name::is_valid_name;
+use crate::schema::ast::*;
+
+
+/// Schema coordinate, a reference to an element of the schema
+///
+/// Coordinates are parsed from and printed as strings like `User`,
+/// `User.name`, `Query.users(first:)`, `Role.ADMIN`, `@deprecated` and
+/// `@deprecated(reason:)`:
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::parse_schema;
+/// use edb_graphql_parser::schema::{SchemaCoordinate, CoordinateTarget};
+///
+/// let schema = parse_schema::<&str>("
+///     type Query { users(first: Int): [String] }
+/// ").unwrap();
+/// let coord: SchemaCoordinate = "Query.users(first:)".parse().unwrap();
+/// match coord.resolve(&schema) {
+///     Some(CoordinateTarget::Argument(arg)) => assert_eq!(arg.name, "first"),
+///     _ => unreachable!(),
+/// }
+/// assert_eq!(coord.to_string(), "Query.users(first:)");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SchemaCoordinate {
+    /// `Type`
+    Type(String),
+    /// `Type.field`, `Input.field` or `Enum.VALUE`
+    Member { type_name: String, member: String },
+    /// `Type.field(argument:)`
+    Argument { type_name: String, field: String, argument: String },
+    /// `@directive`
+    Directive(String),
+    /// `@directive(argument:)`
+    DirectiveArgument { directive: String, argument: String },
+}
+
+/// Element of the schema document a coordinate refers to
+#[derive(Debug)]
+pub enum CoordinateTarget<'x, 'a, T>
+    where T: Text<'a>
+{
+    Type(&'x TypeDefinition<'a, T>),
+    Field(&'x Field<'a, T>),
+    InputField(&'x InputValue<'a, T>),
+    EnumValue(&'x EnumValue<'a, T>),
+    /// Argument of a field or a directive
+    Argument(&'x InputValue<'a, T>),
+    Directive(&'x DirectiveDefinition<'a, T>),
+}
+
+#[derive(Debug, Error, Clone, PartialEq)]
+#[error("invalid schema coordinate {coordinate:?}: {reason}")]
+pub struct InvalidSchemaCoordinate {
+    coordinate: String,
+    reason: &'static str,
+}
+
+impl FromStr for SchemaCoordinate {
+    type Err = InvalidSchemaCoordinate;
+    fn from_str(s: &str) -> Result<SchemaCoordinate, InvalidSchemaCoordinate> {
+        let error = |reason| InvalidSchemaCoordinate {
+            coordinate: s.into(),
+            reason,
+        };
+        let name = |name: &str| if is_valid_name(name) {
+            Ok(name.to_string())
+        } else {
+            Err(error("expected a name"))
+        };
+        let (path, argument) = match s.split_once('(') {
+            Some((path, rest)) => {
+                let argument = rest.strip_suffix(":)")
+                    .ok_or_else(|| error("argument must end with `:)`"))?;
+                (path, Some(name(argument)?))
+            }
+            None => (s, None),
+        };
+        if let Some(directive) = path.strip_prefix('@') {
+            let directive = name(directive)?;
+            return Ok(match argument {
+                Some(argument) => {
+                    SchemaCoordinate::DirectiveArgument { directive, argument }
+                }
+                None => SchemaCoordinate::Directive(directive),
+            });
+        }
+        match (path.split_once('.'), argument) {
+            (None, None) => Ok(SchemaCoordinate::Type(name(path)?)),
+            (None, Some(_)) => Err(error("argument requires a field")),
+            (Some((type_name, member)), None) => {
+                Ok(SchemaCoordinate::Member {
+                    type_name: name(type_name)?,
+                    member: name(member)?,
+                })
+            }
+            (Some((type_name, field)), Some(argument)) => {
+                Ok(SchemaCoordinate::Argument {
+                    type_name: name(type_name)?,
+                    field: name(field)?,
+                    argument,
+                })
+            }
+        }
+    }
+}
+
+impl fmt::Display for SchemaCoordinate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::SchemaCoordinate::*;
+        match *self {
+            Type(ref name) => f.write_str(name),
+            Member { ref type_name, ref member } => {
+                write!(f, "{}.{}", type_name, member)
+            }
+            Argument { ref type_name, ref field, ref argument } => {
+                write!(f, "{}.{}({}:)", type_name, field, argument)
+            }
+            Directive(ref name) => write!(f, "@{}", name),
+            DirectiveArgument { ref directive, ref argument } => {
+                write!(f, "@{}({}:)", directive, argument)
+            }
+        }
+    }
+}
+
+impl SchemaCoordinate {
+    /// Looks up the element in the schema
+    ///
+    /// Members are searched in the type definition and then in its
+    /// extensions. Returns `None` if there is no such element, including
+    /// built-in scalars and directives which are not defined in the
+    /// document.
+    pub fn resolve<'x, 'a, T>(&self, doc: &'x Document<'a, T>)
+        -> Option<CoordinateTarget<'x, 'a, T>>
+        where T: Text<'a>,
+    {
+        use self::SchemaCoordinate::*;
+        match *self {
+            Type(ref name) => type_definition(doc, name)
+                .map(CoordinateTarget::Type),
+            Member { ref type_name, ref member } => {
+                members(doc, type_name).into_iter().find_map(|m| match m {
+                    Members::Fields(fields) => fields.iter()
+                        .find(|f| f.name.as_ref() == member)
+                        .map(CoordinateTarget::Field),
+                    Members::InputFields(fields) => fields.iter()
+                        .find(|f| f.name.as_ref() == member)
+                        .map(CoordinateTarget::InputField),
+                    Members::Values(values) => values.iter()
+                        .find(|v| v.name.as_ref() == member)
+                        .map(CoordinateTarget::EnumValue),
+                })
+            }
+            Argument { ref type_name, ref field, ref argument } => {
+                members(doc, type_name).into_iter().find_map(|m| match m {
+                    Members::Fields(fields) => fields.iter()
+                        .find(|f| f.name.as_ref() == field),
+                    _ => None,
+                })
+                .and_then(|f| find_input(&f.arguments, argument))
+                .map(CoordinateTarget::Argument)
+            }
+            Directive(ref name) => directive(doc, name)
+                .map(CoordinateTarget::Directive),
+            DirectiveArgument { ref directive, ref argument } => {
+                self::directive(doc, directive)
+                    .and_then(|d| find_input(&d.arguments, argument))
+                    .map(CoordinateTarget::Argument)
+            }
+        }
+    }
+}
+
+enum Members<'x, 'a, T>
+    where T: Text<'a>
+{
+    Fields(&'x [Field<'a, T>]),
+    InputFields(&'x [InputValue<'a, T>]),
+    Values(&'x [EnumValue<'a, T>]),
+}
+
+fn type_definition<'x, 'a, T>(doc: &'x Document<'a, T>, name: &str)
+    -> Option<&'x TypeDefinition<'a, T>>
+    where T: Text<'a>,
+{
+    doc.definitions.iter().find_map(|def| match *def {
+        Definition::TypeDefinition(ref t) if t.name().as_ref() == name => {
+            Some(t)
+        }
+        _ => None,
+    })
+}
+
+fn directive<'x, 'a, T>(doc: &'x Document<'a, T>, name: &str)
+    -> Option<&'x DirectiveDefinition<'a, T>>
+    where T: Text<'a>,
+{
+    doc.definitions.iter().find_map(|def| match *def {
+        Definition::DirectiveDefinition(ref d) if d.name.as_ref() == name => {
+            Some(d)
+        }
+        _ => None,
+    })
+}
+
+fn find_input<'x, 'a, T>(inputs: &'x [InputValue<'a, T>], name: &str)
+    -> Option<&'x InputValue<'a, T>>
+    where T: Text<'a>,
+{
+    inputs.iter().find(|i| i.name.as_ref() == name)
+}
+
+/// Returns members of the type definition followed by its extensions
+fn members<'x, 'a, T>(doc: &'x Document<'a, T>, type_name: &str)
+    -> Vec<Members<'x, 'a, T>>
+    where T: Text<'a>,
+{
+    let definitions = doc.definitions.iter().filter_map(|def| {
+        match *def {
+            Definition::TypeDefinition(ref t)
+                if t.name().as_ref() == type_name
+            => match *t {
+                TypeDefinition::Object(ref o) => Some(Members::Fields(&o.fields)),
+                TypeDefinition::Interface(ref i) => {
+                    Some(Members::Fields(&i.fields))
+                }
+                TypeDefinition::InputObject(ref i) => {
+                    Some(Members::InputFields(&i.fields))
+                }
+                TypeDefinition::Enum(ref e) => Some(Members::Values(&e.values)),
+                TypeDefinition::Scalar(_) | TypeDefinition::Union(_) => None,
+            },
+            _ => None,
+        }
+    });
+    let extensions = doc.definitions.iter().filter_map(|def| {
+        match *def {
+            Definition::TypeExtension(ref t)
+                if t.name().as_ref() == type_name
+            => match *t {
+                TypeExtension::Object(ref o) => Some(Members::Fields(&o.fields)),
+                TypeExtension::Interface(ref i) => {
+                    Some(Members::Fields(&i.fields))
+                }
+                TypeExtension::InputObject(ref i) => {
+                    Some(Members::InputFields(&i.fields))
+                }
+                TypeExtension::Enum(ref e) => Some(Members::Values(&e.values)),
+                TypeExtension::Scalar(_) | TypeExtension::Union(_) => None,
+            },
+            _ => None,
+        }
+    });
+    definitions.chain(extensions).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse_schema;
+    use super::{SchemaCoordinate, CoordinateTarget};
+
+    #[test]
+    fn parse_and_print() {
+        for s in &["User", "User.name", "Query.users(first:)", "Role.ADMIN",
+                   "@deprecated", "@deprecated(reason:)", "_T._f(_a:)"] {
+            let coord = s.parse::<SchemaCoordinate>().unwrap();
+            assert_eq!(coord.to_string(), *s);
+        }
+        assert_eq!("Query.users(first:)".parse::<SchemaCoordinate>().unwrap(),
+            SchemaCoordinate::Argument {
+                type_name: "Query".into(),
+                field: "users".into(),
+                argument: "first".into(),
+            });
+        for s in &["", "User.", ".name", "User.name.x", "User(a:)",
+                   "User.name(a)", "User.name(a:", "@dir.x", "@", "1Type",
+                   "User .name", "@dir(a:)x"] {
+            assert!(s.parse::<SchemaCoordinate>().is_err(), "{:?}", s);
+        }
+        assert_eq!("Query.f(a)".parse::<SchemaCoordinate>()
+                   .unwrap_err().to_string(),
+                   "invalid schema coordinate \"Query.f(a)\": \
+                    argument must end with `:)`");
+    }
+
+    #[test]
+    fn resolve() {
+        let schema = parse_schema::<&str>("
+            type Query { users(first: Int): [User] }
+            type User { name: String }
+            extend type User { age: Int }
+            enum Role { ADMIN }
+            input Filter { role: Role }
+            directive @auth(role: Role) on FIELD_DEFINITION
+        ").unwrap();
+        let resolve = |s: &str| {
+            s.parse::<SchemaCoordinate>().unwrap().resolve(&schema)
+        };
+        match resolve("User") {
+            Some(CoordinateTarget::Type(t)) => assert_eq!(*t.name(), "User"),
+            r => panic!("unexpected {:?}", r),
+        }
+        match resolve("User.age") {
+            Some(CoordinateTarget::Field(f)) => assert_eq!(f.name, "age"),
+            r => panic!("unexpected {:?}", r),
+        }
+        match resolve("Role.ADMIN") {
+            Some(CoordinateTarget::EnumValue(v)) => assert_eq!(v.name, "ADMIN"),
+            r => panic!("unexpected {:?}", r),
+        }
+        match resolve("Filter.role") {
+            Some(CoordinateTarget::InputField(f)) => assert_eq!(f.name, "role"),
+            r => panic!("unexpected {:?}", r),
+        }
+        match resolve("@auth(role:)") {
+            Some(CoordinateTarget::Argument(a)) => assert_eq!(a.name, "role"),
+            r => panic!("unexpected {:?}", r),
+        }
+        assert!(resolve("@auth").is_some());
+        assert!(resolve("Query.users(first:)").is_some());
+        assert!(resolve("Query.users(last:)").is_none());
+        assert!(resolve("User.email").is_none());
+        assert!(resolve("Filter.role(x:)").is_none());
+        assert!(resolve("String").is_none());
+        assert!(resolve("@skip").is_none());
+    }
+}
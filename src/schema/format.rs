@@ -1,9 +1,14 @@
 use std::fmt;
 
 use crate::format::{Displayable, Formatter, Style, format_directives};
+use crate::format::{range_edits, token_ranges};
 use crate::common::Text;
+use crate::position::{LineIndex, Pos, TextEdit};
+use crate::tokenizer::positioned_tokens;
 
 use crate::schema::ast::*;
+use crate::schema::index::{BUILTIN_DIRECTIVES, BUILTIN_SCALARS};
+use crate::schema::{parse_schema, ParseError};
 
 
 impl<'a, T> Document<'a, T> 
@@ -15,6 +20,139 @@ impl<'a, T> Document<'a, T>
         self.display(&mut formatter);
         formatter.into_string()
     }
+
+    /// Returns true if the documents only differ in positions
+    ///
+    /// Documents are compared by their minified text, so formatting and
+    /// the text type don't matter either.
+    pub fn semantic_eq<'b, U>(&self, other: &Document<'b, U>) -> bool
+        where U: Text<'b>,
+    {
+        let mut style = Style::default();
+        style.minified(true);
+        self.format(&style) == other.format(&style)
+    }
+}
+
+/// Returns a hash of the schema that doesn't depend on the order of the
+/// definitions, formatting and the form of the descriptions
+///
+/// This is a lowercase hex-encoded SHA-256 of the document printed with
+/// the `sorted` and `minified` style, so it changes whenever anything
+/// printed changes, including descriptions and directives, but not when
+/// the schema is just reformatted or reordered. Whitespace around the
+/// descriptions is ignored, as block strings end with a newline.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::parse_schema;
+/// use edb_graphql_parser::schema::schema_hash;
+///
+/// let a = parse_schema::<&str>("type B { y: Int x: Int }
+///                               \"A type\" type A { a: B }").unwrap();
+/// let b = parse_schema::<&str>("\"\"\"\n  A type\n\"\"\"
+///                               type A {\n  a: B\n}
+///                               type B { x: Int, y: Int }").unwrap();
+/// assert_eq!(schema_hash(&a), schema_hash(&b));
+/// ```
+#[cfg(feature = "apq")]
+pub fn schema_hash<'a, T>(doc: &Document<'a, T>) -> String
+    where T: Text<'a>,
+{
+    let mut style = Style::default();
+    style.sorted(true).minified(true).trim_descriptions(true);
+    crate::query::apq_hash(&doc.format(&style))
+}
+
+/// Formats only the definitions intersecting the range of the source
+///
+/// Works as `query::format_range`, definitions start at their
+/// descriptions.
+pub fn format_range(source: &str, range: (Pos, Pos), style: &Style)
+    -> Result<Vec<TextEdit>, ParseError>
+{
+    let doc = parse_schema::<&str>(source)?;
+    let tokens = positioned_tokens(source);
+    Ok(range_edits(source, &tokens, &definition_starts(&doc), range, |text| {
+        let doc = parse_schema::<&str>(text).expect("definition is valid");
+        let mut style = style.clone();
+        style.preserve_comments(text);
+        doc.format(&style)
+    }))
+}
+
+impl<'a, T> Document<'a, T>
+    where T: Text<'a>,
+{
+    /// Returns the original text of every definition
+    ///
+    /// `source` must be the text the document is parsed from. The text of
+    /// a definition spans from its first token, which may be the
+    /// description, to its last token, so comments and whitespace around
+    /// it are not included. Returns `None` if the positions don't match the
+    /// source.
+    ///
+    /// Schema nodes only record where they start, so a definition ends
+    /// where the next one starts, and there are no slices of the nodes
+    /// inside the definitions.
+    ///
+    /// ```rust
+    /// # extern crate edb_graphql_parser;
+    /// use edb_graphql_parser::parse_schema;
+    ///
+    /// let source = "\"A\" scalar A @x # comment\ntype B { b: A }\n";
+    /// let doc = parse_schema::<&str>(source).unwrap();
+    /// assert_eq!(doc.definition_sources(source).unwrap(),
+    ///            ["\"A\" scalar A @x", "type B { b: A }"]);
+    /// ```
+    pub fn definition_sources<'s>(&self, source: &'s str)
+        -> Option<Vec<&'s str>>
+    {
+        let tokens = positioned_tokens(source);
+        let starts = definition_starts(self);
+        let valid = starts.windows(2).all(|pair| pair[0] < pair[1]) &&
+            starts.iter().all(|&start| start < tokens.len());
+        if !valid {
+            return None;
+        }
+        let index = LineIndex::new(source);
+        Some(token_ranges(&index, &tokens, &starts).into_iter()
+            .map(|(start, end)| &source[start..end])
+            .collect())
+    }
+}
+
+/// Returns the token indices of the starts of the definitions
+fn definition_starts<'a, T>(doc: &Document<'a, T>) -> Vec<usize>
+    where T: Text<'a>,
+{
+    doc.definitions.iter().map(|def| {
+        // positions are of the keywords after `extend` and descriptions
+        let (position, before) = match *def {
+            Definition::SchemaDefinition(ref s) => (s.position, 0),
+            Definition::SchemaExtension(ref s) => (s.position, 1),
+            Definition::TypeDefinition(ref t) => {
+                (t.position(), has_description(t) as usize)
+            }
+            Definition::TypeExtension(ref t) => (t.position(), 1),
+            Definition::DirectiveDefinition(ref d) => {
+                (d.position, d.description.is_some() as usize)
+            }
+            Definition::Custom(ref c) => (c.position, 0),
+        };
+        position.token - before
+    }).collect()
+}
+
+fn has_description<'a, T: Text<'a>>(def: &TypeDefinition<'a, T>) -> bool {
+    match *def {
+        TypeDefinition::Scalar(ref t) => t.description.is_some(),
+        TypeDefinition::Object(ref t) => t.description.is_some(),
+        TypeDefinition::Interface(ref t) => t.description.is_some(),
+        TypeDefinition::Union(ref t) => t.description.is_some(),
+        TypeDefinition::Enum(ref t) => t.description.is_some(),
+        TypeDefinition::InputObject(ref t) => t.description.is_some(),
+    }
 }
 
 fn to_string<T: Displayable>(v: &T) -> String {
@@ -24,10 +162,10 @@ fn to_string<T: Displayable>(v: &T) -> String {
     formatter.into_string()
 }
 
-fn description<'a>(description: &Option<String>, f: &mut Formatter) {
+fn description(description: &Option<Description>, f: &mut Formatter) {
     if let Some(ref descr) = *description {
         f.indent();
-        f.write_quoted(descr.as_ref());
+        f.write_description(descr);
         f.endline();
     }
 }
@@ -37,9 +175,132 @@ impl<'a, T> Displayable for Document<'a, T>
     where T: Text<'a>,
 {
     fn display(&self, f: &mut Formatter) {
-        for item in &self.definitions {
+        for item in printed_definitions(self, f) {
             item.display(f);
         }
+        f.remaining_comments();
+    }
+
+    fn stream(&self, f: &mut Formatter, out: &mut dyn fmt::Write)
+        -> fmt::Result
+    {
+        for item in printed_definitions(self, f) {
+            item.display(f);
+            f.flush(out)?;
+        }
+        f.remaining_comments();
+        Ok(())
+    }
+}
+
+/// Types defined by federation libraries and the `@link` specification
+const FEDERATION_TYPES: &[&str] = &[
+    "_Any", "_Entity", "_Service", "_FieldSet", "FieldSet",
+    "link__Import", "link__Purpose", "federation__FieldSet",
+    "federation__Scope", "federation__Policy",
+    "federation__ContextFieldValue",
+];
+
+/// Directives defined by federation libraries and the `@link` specification
+const FEDERATION_DIRECTIVES: &[&str] = &[
+    "key", "external", "requires", "provides", "extends", "shareable",
+    "inaccessible", "override", "tag", "link", "composeDirective",
+    "interfaceObject", "authenticated", "requiresScopes", "policy", "cost",
+    "listSize", "context", "fromContext",
+];
+
+/// Root fields added to the query type by federation libraries
+const FEDERATION_FIELDS: &[&str] = &["_entities", "_service"];
+
+fn is_federation_builtin<'a, T>(def: &Definition<'a, T>) -> bool
+    where T: Text<'a>,
+{
+    match *def {
+        Definition::TypeDefinition(ref t) => {
+            FEDERATION_TYPES.contains(&t.name().as_ref())
+        }
+        Definition::TypeExtension(ref e) => {
+            FEDERATION_TYPES.contains(&e.name().as_ref())
+        }
+        Definition::DirectiveDefinition(ref d) => {
+            FEDERATION_DIRECTIVES.contains(&d.name.as_ref())
+        }
+        // `extend schema @link(...)` is empty without the `@link`
+        Definition::SchemaExtension(ref e) => {
+            e.query.is_none() && e.mutation.is_none() &&
+                e.subscription.is_none() &&
+                e.directives.iter().all(|d| d.name.as_ref() == "link")
+        }
+        Definition::SchemaDefinition(_) | Definition::Custom(_) => false,
+    }
+}
+
+fn printed_definitions<'x, 'a, T>(doc: &'x Document<'a, T>, f: &Formatter)
+    -> Vec<&'x Definition<'a, T>>
+    where T: Text<'a>,
+{
+    let mut definitions = f.ordered(&doc.definitions, definition_key);
+    if f.omit_federation_builtins() {
+        definitions.retain(|def| !is_federation_builtin(def));
+    }
+    if f.omit_builtin_scalars() {
+        definitions.retain(|def| match **def {
+            Definition::TypeDefinition(TypeDefinition::Scalar(ref s)) => {
+                !BUILTIN_SCALARS.contains(&s.name.as_ref())
+            }
+            _ => true,
+        });
+    }
+    if f.omit_builtin_directives() {
+        definitions.retain(|def| match **def {
+            Definition::DirectiveDefinition(ref d) => {
+                !BUILTIN_DIRECTIVES.contains(&d.name.as_ref())
+            }
+            _ => true,
+        });
+    }
+    if f.omit_default_schema() {
+        definitions.retain(|def| match **def {
+            Definition::SchemaDefinition(ref s) => !is_default_schema(doc, s),
+            _ => true,
+        });
+    }
+    definitions
+}
+
+/// Returns true if the schema definition is the one implied by the names
+/// of the types
+fn is_default_schema<'a, T>(doc: &Document<'a, T>,
+    schema: &SchemaDefinition<'a, T>)
+    -> bool
+    where T: Text<'a>,
+{
+    let defined = |name: &str| doc.definitions.iter().any(|def| match *def {
+        Definition::TypeDefinition(ref t) => t.name().as_ref() == name,
+        _ => false,
+    });
+    let conventional = |root: &Option<T::Value>, name: &str| match *root {
+        Some(ref root) => root.as_ref() == name,
+        None => !defined(name),
+    };
+    schema.directives.is_empty() &&
+        conventional(&schema.query, "Query") &&
+        conventional(&schema.mutation, "Mutation") &&
+        conventional(&schema.subscription, "Subscription")
+}
+
+/// Sort key of the definition: schema with extensions, directives, types
+/// with extensions and custom definitions in original order
+fn definition_key<'x, 'a, T>(def: &'x Definition<'a, T>) -> (u8, &'x str, u8)
+    where T: Text<'a>,
+{
+    match *def {
+        Definition::SchemaDefinition(_) => (0, "", 0),
+        Definition::SchemaExtension(_) => (0, "", 1),
+        Definition::DirectiveDefinition(ref d) => (1, d.name.as_ref(), 0),
+        Definition::TypeDefinition(ref t) => (2, t.name().as_ref(), 0),
+        Definition::TypeExtension(ref e) => (2, e.name().as_ref(), 1),
+        Definition::Custom(_) => (3, "", 0),
     }
 }
 
@@ -50,41 +311,87 @@ impl<'a, T> Displayable for Definition<'a, T>
         f.margin();
         match *self {
             Definition::SchemaDefinition(ref s) => s.display(f),
+            Definition::SchemaExtension(ref s) => s.display(f),
             Definition::TypeDefinition(ref t) => t.display(f),
             Definition::TypeExtension(ref e) => e.display(f),
             Definition::DirectiveDefinition(ref d) => d.display(f),
+            Definition::Custom(ref c) => c.display(f),
+        }
+    }
+}
+
+fn schema_directives<'a, T>(directives: &[Directive<'a, T>],
+    f: &mut Formatter)
+    where T: Text<'a>,
+{
+    for dir in directives {
+        if f.omit_federation_builtins() && dir.name.as_ref() == "link" {
+            continue;
         }
+        f.write(" ");
+        dir.display(f);
     }
 }
 
+fn operation_types<'a, T>(query: &Option<T::Value>,
+    mutation: &Option<T::Value>, subscription: &Option<T::Value>,
+    f: &mut Formatter)
+    where T: Text<'a>,
+{
+    f.write(" ");
+    f.start_block();
+    if let Some(ref q) = *query {
+        f.indent();
+        f.write("query: ");
+        f.write(q.as_ref());
+        f.endline();
+    }
+    if let Some(ref m) = *mutation {
+        f.indent();
+        f.write("mutation: ");
+        f.write(m.as_ref());
+        f.endline();
+    }
+    if let Some(ref s) = *subscription {
+        f.indent();
+        f.write("subscription: ");
+        f.write(s.as_ref());
+        f.endline();
+    }
+    f.end_block();
+}
+
 impl<'a, T> Displayable for SchemaDefinition<'a, T> 
     where T: Text<'a>,
 {
     fn display(&self, f: &mut Formatter) {
+        f.leading_comments(self.position);
         f.indent();
         f.write("schema");
-        format_directives(&self.directives, f);
-        f.write(" ");
-        f.start_block();
-        if let Some(ref q) = self.query {
-            f.indent();
-            f.write("query: ");
-            f.write(q.as_ref());
-            f.endline();
-        }
-        if let Some(ref m) = self.mutation {
-            f.indent();
-            f.write("mutation: ");
-            f.write(m.as_ref());
-            f.endline();
-        }
-        if let Some(ref s) = self.subscription {
-            f.indent();
-            f.write("subscription: ");
-            f.write(s.as_ref());
+        schema_directives(&self.directives, f);
+        f.trailing_comment(self.position);
+        operation_types::<T>(&self.query, &self.mutation, &self.subscription,
+                             f);
+    }
+}
+
+impl<'a, T> Displayable for SchemaExtension<'a, T> 
+    where T: Text<'a>,
+{
+    fn display(&self, f: &mut Formatter) {
+        f.leading_comments(self.position);
+        f.indent();
+        f.write("extend schema");
+        schema_directives(&self.directives, f);
+        f.trailing_comment(self.position);
+        if self.query.is_some() || self.mutation.is_some() ||
+            self.subscription.is_some()
+        {
+            operation_types::<T>(&self.query, &self.mutation,
+                                 &self.subscription, f);
+        } else {
             f.endline();
         }
-        f.end_block();
     }
 }
 
@@ -107,11 +414,13 @@ impl<'a, T> Displayable for ScalarType<'a, T>
     where T: Text<'a>,
 {
     fn display(&self, f: &mut Formatter) {
+        f.leading_comments(self.position);
         description(&self.description, f);
         f.indent();
         f.write("scalar ");
         f.write(self.name.as_ref());
         format_directives(&self.directives, f);
+        f.annotate_schema(&[self.name.as_ref()], self.position);
         f.endline();
     }
 }
@@ -120,22 +429,39 @@ impl<'a, T> Displayable for ScalarTypeExtension<'a, T>
     where T: Text<'a>,
 {
     fn display(&self, f: &mut Formatter) {
+        f.leading_comments(self.position);
         f.indent();
         f.write("extend scalar ");
         f.write(self.name.as_ref());
         format_directives(&self.directives, f);
+        f.annotate_schema(&[self.name.as_ref()], self.position);
         f.endline();
     }
 }
 
-fn format_fields<'a, T>(fields: &[Field<'a, T>], f: &mut Formatter) 
+fn format_names<'a, T>(prefix: &str, separator: &str, names: &[T::Value],
+    f: &mut Formatter)
+    where T: Text<'a>,
+{
+    for (idx, name) in f.ordered(names, |n| n.as_ref()).iter().enumerate() {
+        f.write(if idx == 0 { prefix } else { separator });
+        f.write(name.as_ref());
+    }
+}
+
+fn format_fields<'a, T>(type_name: &str, fields: &[Field<'a, T>],
+    f: &mut Formatter)
     where T: Text<'a>,
 {
+    let mut fields = f.ordered(fields, |fld| fld.name.as_ref());
+    if f.omit_federation_builtins() {
+        fields.retain(|fld| !FEDERATION_FIELDS.contains(&fld.name.as_ref()));
+    }
     if !fields.is_empty() {
         f.write(" ");
         f.start_block();
         for fld in fields {
-            fld.display(f);
+            format_field(fld, Some(type_name), f);
         }
         f.end_block();
     } else {
@@ -147,20 +473,18 @@ impl<'a, T> Displayable for ObjectType<'a, T>
     where T: Text<'a>,
 {
     fn display(&self, f: &mut Formatter) {
-        description(&self.description, f);
+        f.leading_comments(self.position);
+        let extended = f.is_extended(self.name.as_ref());
+        if !extended {
+            description(&self.description, f);
+        }
         f.indent();
-        f.write("type ");
+        f.write(if extended { "extend type " } else { "type " });
         f.write(self.name.as_ref());
-        if !self.implements_interfaces.is_empty() {
-            f.write(" implements ");
-            f.write(self.implements_interfaces[0].as_ref());
-            for name in &self.implements_interfaces[1..] {
-                f.write(" & ");
-                f.write(name.as_ref());
-            }
-        }
+        format_names::<T>(" implements ", " & ", &self.implements_interfaces, f);
         format_directives(&self.directives, f);
-        format_fields(&self.fields, f);
+        f.annotate_schema(&[self.name.as_ref()], self.position);
+        format_fields(self.name.as_ref(), &self.fields, f);
     }
 }
 
@@ -168,19 +492,14 @@ impl<'a, T> Displayable for ObjectTypeExtension<'a, T>
     where T: Text<'a>,
 {
     fn display(&self, f: &mut Formatter) {
+        f.leading_comments(self.position);
         f.indent();
         f.write("extend type ");
         f.write(self.name.as_ref());
-        if !self.implements_interfaces.is_empty() {
-            f.write(" implements ");
-            f.write(self.implements_interfaces[0].as_ref());
-            for name in &self.implements_interfaces[1..] {
-                f.write(" & ");
-                f.write(name.as_ref());
-            }
-        }
+        format_names::<T>(" implements ", " & ", &self.implements_interfaces, f);
         format_directives(&self.directives, f);
-        format_fields(&self.fields, f);
+        f.annotate_schema(&[self.name.as_ref()], self.position);
+        format_fields(self.name.as_ref(), &self.fields, f);
     }
 }
 
@@ -189,15 +508,19 @@ impl<'a, T> Displayable for InputValue<'a, T>
 {
     fn display(&self, f: &mut Formatter) {
         if let Some(ref descr) = self.description {
-            f.write_quoted(descr.as_ref());
+            f.write_description(descr);
             f.write(" ");
         }
         f.write(self.name.as_ref());
         f.write(": ");
         self.value_type.display(f);
-        if let Some(ref def) = self.default_value {
-            f.write(" = ");
-            def.display(f);
+        match self.default_value {
+            Some(Value::Null) if f.omit_null_defaults() => {}
+            Some(ref def) => {
+                f.write(" = ");
+                f.write_default(def);
+            }
+            None => {}
         }
         format_directives(&self.directives, f);
     }
@@ -206,42 +529,54 @@ impl<'a, T> Displayable for InputValue<'a, T>
 fn format_arguments<'a, T>(arguments: &[InputValue<'a, T>], f: &mut Formatter) 
     where T: Text<'a>,
 {
-    if !arguments.is_empty() {
-        f.write("(");
-        arguments[0].display(f);
-        for arg in &arguments[1..] {
-            f.write(", ");
-            arg.display(f);
-        }
-        f.write(")");
-    }
+    let arguments = f.ordered(arguments, |arg| arg.name.as_ref());
+    f.write_list(arguments.len(), |idx, f| arguments[idx].display(f));
 }
 
 impl<'a, T> Displayable for Field<'a, T> 
     where T: Text<'a>,
 {
     fn display(&self, f: &mut Formatter) {
-        description(&self.description, f);
-        f.indent();
-        f.write(self.name.as_ref());
-        format_arguments(&self.arguments, f);
-        f.write(": ");
-        self.field_type.display(f);
-        format_directives(&self.directives, f);
-        f.endline();
+        format_field(self, None, f);
     }
 }
 
+fn format_field<'a, T>(fld: &Field<'a, T>, type_name: Option<&str>,
+    f: &mut Formatter)
+    where T: Text<'a>,
+{
+    f.leading_comments(fld.position);
+    description(&fld.description, f);
+    f.indent();
+    f.write(fld.name.as_ref());
+    format_arguments(&fld.arguments, f);
+    f.write(": ");
+    fld.field_type.display(f);
+    format_directives(&fld.directives, f);
+    if let Some(type_name) = type_name {
+        f.annotate_schema(&[type_name, ".", fld.name.as_ref()], fld.position);
+    } else {
+        f.trailing_comment(fld.position);
+    }
+    f.endline();
+}
+
 impl<'a, T> Displayable for InterfaceType<'a, T> 
     where T: Text<'a>,
 {
     fn display(&self, f: &mut Formatter) {
-        description(&self.description, f);
+        f.leading_comments(self.position);
+        let extended = f.is_extended(self.name.as_ref());
+        if !extended {
+            description(&self.description, f);
+        }
         f.indent();
-        f.write("interface ");
+        f.write(if extended { "extend interface " } else { "interface " });
         f.write(self.name.as_ref());
+        format_names::<T>(" implements ", " & ", &self.implements_interfaces, f);
         format_directives(&self.directives, f);
-        format_fields(&self.fields, f);
+        f.annotate_schema(&[self.name.as_ref()], self.position);
+        format_fields(self.name.as_ref(), &self.fields, f);
     }
 }
 
@@ -249,11 +584,14 @@ impl<'a, T> Displayable for InterfaceTypeExtension<'a, T>
     where T: Text<'a>,
 {
     fn display(&self, f: &mut Formatter) {
+        f.leading_comments(self.position);
         f.indent();
         f.write("extend interface ");
         f.write(self.name.as_ref());
+        format_names::<T>(" implements ", " & ", &self.implements_interfaces, f);
         format_directives(&self.directives, f);
-        format_fields(&self.fields, f);
+        f.annotate_schema(&[self.name.as_ref()], self.position);
+        format_fields(self.name.as_ref(), &self.fields, f);
     }
 }
 
@@ -261,19 +599,14 @@ impl<'a, T> Displayable for UnionType<'a, T>
     where T: Text<'a>,
 {
     fn display(&self, f: &mut Formatter) {
+        f.leading_comments(self.position);
         description(&self.description, f);
         f.indent();
         f.write("union ");
         f.write(self.name.as_ref());
         format_directives(&self.directives, f);
-        if !self.types.is_empty() {
-            f.write(" = ");
-            f.write(self.types[0].as_ref());
-            for typ in &self.types[1..] {
-                f.write(" | ");
-                f.write(typ.as_ref());
-            }
-        }
+        format_names::<T>(" = ", " | ", &self.types, f);
+        f.annotate_schema(&[self.name.as_ref()], self.position);
         f.endline();
     }
 }
@@ -282,18 +615,13 @@ impl<'a, T> Displayable for UnionTypeExtension<'a, T>
     where T: Text<'a>,
 {
     fn display(&self, f: &mut Formatter) {
+        f.leading_comments(self.position);
         f.indent();
         f.write("extend union ");
         f.write(self.name.as_ref());
         format_directives(&self.directives, f);
-        if !self.types.is_empty() {
-            f.write(" = ");
-            f.write(self.types[0].as_ref());
-            for typ in &self.types[1..] {
-                f.write(" | ");
-                f.write(typ.as_ref());
-            }
-        }
+        format_names::<T>(" = ", " | ", &self.types, f);
+        f.annotate_schema(&[self.name.as_ref()], self.position);
         f.endline();
     }
 }
@@ -302,22 +630,28 @@ impl<'a, T> Displayable for EnumType<'a, T>
     where T: Text<'a>,
 {
     fn display(&self, f: &mut Formatter) {
+        f.leading_comments(self.position);
         description(&self.description, f);
         f.indent();
         f.write("enum ");
         f.write(self.name.as_ref());
         format_directives(&self.directives, f);
+        f.annotate_schema(&[self.name.as_ref()], self.position);
         if !self.values.is_empty() {
             f.write(" ");
             f.start_block();
-            for val in &self.values {
+            for val in f.ordered(&self.values, |val| val.name.as_ref()) {
+                f.leading_comments(val.position);
                 f.indent();
                 if let Some(ref descr) = val.description {
-                    f.write_quoted(descr.as_ref());
+                    f.write_description(descr);
                     f.write(" ");
                 }
                 f.write(val.name.as_ref());
                 format_directives(&val.directives, f);
+                f.annotate_schema(
+                    &[self.name.as_ref(), ".", val.name.as_ref()],
+                    val.position);
                 f.endline();
             }
             f.end_block();
@@ -331,21 +665,27 @@ impl<'a, T> Displayable for EnumTypeExtension<'a, T>
     where T: Text<'a>,
 {
     fn display(&self, f: &mut Formatter) {
+        f.leading_comments(self.position);
         f.indent();
         f.write("extend enum ");
         f.write(self.name.as_ref());
         format_directives(&self.directives, f);
+        f.annotate_schema(&[self.name.as_ref()], self.position);
         if !self.values.is_empty() {
             f.write(" ");
             f.start_block();
-            for val in &self.values {
+            for val in f.ordered(&self.values, |val| val.name.as_ref()) {
+                f.leading_comments(val.position);
                 f.indent();
                 if let Some(ref descr) = val.description {
-                    f.write_quoted(descr.as_ref());
+                    f.write_description(descr);
                     f.write(" ");
                 }
                 f.write(val.name.as_ref());
                 format_directives(&val.directives, f);
+                f.annotate_schema(
+                    &[self.name.as_ref(), ".", val.name.as_ref()],
+                    val.position);
                 f.endline();
             }
             f.end_block();
@@ -355,15 +695,19 @@ impl<'a, T> Displayable for EnumTypeExtension<'a, T>
     }
 }
 
-fn format_inputs<'a, T>(fields: &[InputValue<'a, T>], f: &mut Formatter) 
+fn format_inputs<'a, T>(type_name: &str, fields: &[InputValue<'a, T>],
+    f: &mut Formatter)
     where T: Text<'a>,
 {
     if !fields.is_empty() {
         f.write(" ");
         f.start_block();
-        for fld in fields {
+        for fld in f.ordered(fields, |fld| fld.name.as_ref()) {
+            f.leading_comments(fld.position);
             f.indent();
             fld.display(f);
+            f.annotate_schema(&[type_name, ".", fld.name.as_ref()],
+                              fld.position);
             f.endline();
         }
         f.end_block();
@@ -376,12 +720,14 @@ impl<'a, T> Displayable for InputObjectType<'a, T>
     where T: Text<'a>,
 {
     fn display(&self, f: &mut Formatter) {
+        f.leading_comments(self.position);
         description(&self.description, f);
         f.indent();
         f.write("input ");
         f.write(self.name.as_ref());
         format_directives(&self.directives, f);
-        format_inputs(&self.fields, f);
+        f.annotate_schema(&[self.name.as_ref()], self.position);
+        format_inputs(self.name.as_ref(), &self.fields, f);
     }
 }
 
@@ -389,11 +735,13 @@ impl<'a, T> Displayable for InputObjectTypeExtension<'a, T>
     where T: Text<'a>,
 {
     fn display(&self, f: &mut Formatter) {
+        f.leading_comments(self.position);
         f.indent();
         f.write("extend input ");
         f.write(self.name.as_ref());
         format_directives(&self.directives, f);
-        format_inputs(&self.fields, f);
+        f.annotate_schema(&[self.name.as_ref()], self.position);
+        format_inputs(self.name.as_ref(), &self.fields, f);
     }
 }
 
@@ -416,11 +764,15 @@ impl<'a, T> Displayable for DirectiveDefinition<'a, T>
     where T: Text<'a>,
 {
     fn display(&self, f: &mut Formatter) {
+        f.leading_comments(self.position);
         description(&self.description, f);
         f.indent();
         f.write("directive @");
         f.write(self.name.as_ref());
         format_arguments(&self.arguments, f);
+        if self.repeatable {
+            f.write(" repeatable");
+        }
         if !self.locations.is_empty() {
             f.write(" on ");
             let mut first = true;
@@ -433,6 +785,18 @@ impl<'a, T> Displayable for DirectiveDefinition<'a, T>
                 f.write(loc.as_str());
             }
         }
+        f.annotate_schema(&["@", self.name.as_ref()], self.position);
+        f.endline();
+    }
+}
+
+impl<'a, T> Displayable for CustomDefinition<'a, T>
+    where T: Text<'a>,
+{
+    fn display(&self, f: &mut Formatter) {
+        f.leading_comments(self.position);
+        f.indent();
+        f.write(self.source.as_ref());
         f.endline();
     }
 }
@@ -442,6 +806,7 @@ impl_display!(
     Document,
     Definition,
     SchemaDefinition,
+    SchemaExtension,
     TypeDefinition,
     TypeExtension,
     ScalarType,
@@ -459,4 +824,16 @@ impl_display!(
     InputObjectType,
     InputObjectTypeExtension,
     DirectiveDefinition,
+    CustomDefinition,
+);
+
+impl_write!(
+    'a
+    Document,
+    Definition,
+    SchemaDefinition,
+    SchemaExtension,
+    TypeDefinition,
+    TypeExtension,
+    DirectiveDefinition,
 );
@@ -1,16 +1,73 @@
-use combine::{parser, ParseResult, Parser};
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+use combine::{parser, ParseResult, Parser, Positioned};
 use combine::easy::{Error, Errors};
-use combine::error::StreamError;
+use combine::error::{Consumed, StreamError, Tracked};
+use combine::stream::Resetable;
 use combine::combinator::{many, many1, eof, optional, position, choice};
 use combine::combinator::{sep_by1};
 
 use crate::tokenizer::{Kind as T, Token, TokenStream};
 use crate::helpers::{punct, ident, kind, name};
-use crate::common::{directives, string, default_value, parse_type, Text};
+use crate::common::{directives, description, default_value, parse_type};
+use crate::common::Text;
+use crate::options::ParserOptions;
+use crate::position::Pos;
 use crate::schema::error::{ParseError};
 use crate::schema::ast::*;
 
 
+/// Root operation types of the schema definition or extension
+type Operations<S> = (Option<S>, Option<S>, Option<S>);
+
+fn operation_types<'a, S>(position: Pos, operations: Vec<(Token<'a>, S)>)
+    -> Result<Operations<S>, Errors<Token<'a>, Token<'a>, Pos>>
+{
+    let mut query = None;
+    let mut mutation = None;
+    let mut subscription = None;
+    let mut err = Errors::empty(position);
+    for (oper, type_name) in operations {
+        match oper.value {
+            "query" if query.is_some() => {
+                err.add_error(Error::unexpected_static_message(
+                    "duplicate `query` operation"));
+            }
+            "query" => {
+                query = Some(type_name);
+            }
+            "mutation" if mutation.is_some() => {
+                err.add_error(Error::unexpected_static_message(
+                    "duplicate `mutation` operation"));
+            }
+            "mutation" => {
+                mutation = Some(type_name);
+            }
+            "subscription" if subscription.is_some() => {
+                err.add_error(Error::unexpected_static_message(
+                    "duplicate `subscription` operation"));
+            }
+            "subscription" => {
+                subscription = Some(type_name);
+            }
+            _ => {
+                err.add_error(Error::unexpected_token(oper));
+                err.add_error(
+                    Error::expected_static_message("query"));
+                err.add_error(
+                    Error::expected_static_message("mutation"));
+                err.add_error(
+                    Error::expected_static_message("subscription"));
+            }
+        }
+    }
+    if !err.errors.is_empty() {
+        return Err(err);
+    }
+    Ok((query, mutation, subscription))
+}
+
 pub fn schema<'a, S>(input: &mut TokenStream<'a>)
     -> ParseResult<SchemaDefinition<'a, S>, TokenStream<'a>>
     where S: Text<'a>,
@@ -26,52 +83,48 @@ pub fn schema<'a, S>(input: &mut TokenStream<'a>)
             .skip(punct("}")),
     )
     .flat_map(|(position, directives, operations): (_, _, Vec<(Token, _)>)| {
-        let mut query = None;
-        let mut mutation = None;
-        let mut subscription = None;
-        let mut err = Errors::empty(position);
-        for (oper, type_name) in operations {
-            match oper.value {
-                "query" if query.is_some() => {
-                    err.add_error(Error::unexpected_static_message(
-                        "duplicate `query` operation"));
-                }
-                "query" => {
-                    query = Some(type_name);
-                }
-                "mutation" if mutation.is_some() => {
-                    err.add_error(Error::unexpected_static_message(
-                        "duplicate `mutation` operation"));
-                }
-                "mutation" => {
-                    mutation = Some(type_name);
-                }
-                "subscription" if subscription.is_some() => {
-                    err.add_error(Error::unexpected_static_message(
-                        "duplicate `subscription` operation"));
-                }
-                "subscription" => {
-                    subscription = Some(type_name);
-                }
-                _ => {
-                    err.add_error(Error::unexpected_token(oper));
-                    err.add_error(
-                        Error::expected_static_message("query"));
-                    err.add_error(
-                        Error::expected_static_message("mutation"));
-                    err.add_error(
-                        Error::expected_static_message("subscription"));
-                }
-            }
-        }
-        if !err.errors.is_empty() {
+        let (query, mutation, subscription) =
+            operation_types(position, operations)?;
+        Ok(SchemaDefinition {
+            position, directives, query, mutation, subscription,
+        })
+    })
+    .parse_stream(input)
+    .map_err(|e| input.rule_failed("SchemaDefinition", e))
+}
+
+/// Parses `schema` part of the `extend schema`
+pub fn schema_extension<'a, S>(input: &mut TokenStream<'a>)
+    -> ParseResult<SchemaExtension<'a, S>, TokenStream<'a>>
+    where S: Text<'a>,
+{
+    (
+        position().skip(ident("schema")),
+        parser(directives),
+        optional(punct("{")
+            .with(many1((
+                kind(T::Name).skip(punct(":")),
+                name::<'a, S>(),
+            )))
+            .skip(punct("}"))),
+    )
+    .flat_map(|(position, directives, operations):
+                (_, Vec<_>, Option<Vec<(Token, _)>>)|
+    {
+        if directives.is_empty() && operations.is_none() {
+            let mut err = Errors::empty(position);
+            err.add_error(Error::unexpected_static_message(
+                "schema extension must have directives or operation types"));
             return Err(err);
         }
-        Ok(SchemaDefinition {
+        let (query, mutation, subscription) =
+            operation_types(position, operations.unwrap_or_default())?;
+        Ok(SchemaExtension {
             position, directives, query, mutation, subscription,
         })
     })
     .parse_stream(input)
+    .map_err(|e| input.rule_failed("SchemaExtension", e))
 }
 
 pub fn scalar_type<'a, T>(input: &mut TokenStream<'a>)
@@ -87,6 +140,7 @@ pub fn scalar_type<'a, T>(input: &mut TokenStream<'a>)
             ScalarType { position, description: None, name, directives }
         })
         .parse_stream(input)
+        .map_err(|e| input.rule_failed("ScalarTypeDefinition", e))
 }
 
 pub fn scalar_type_extension<'a, T>(input: &mut TokenStream<'a>)
@@ -109,6 +163,7 @@ pub fn scalar_type_extension<'a, T>(input: &mut TokenStream<'a>)
         Ok(ScalarTypeExtension { position, name, directives })
     })
     .parse_stream(input)
+    .map_err(|e| input.rule_failed("ScalarTypeExtension", e))
 }
 
 pub fn implements_interfaces<'a, X>(input: &mut TokenStream<'a>)
@@ -122,6 +177,7 @@ pub fn implements_interfaces<'a, X>(input: &mut TokenStream<'a>)
     )
         .map(|opt| opt.unwrap_or_else(Vec::new))
         .parse_stream(input)
+        .map_err(|e| input.rule_failed("ImplementsInterfaces", e))
 }
 
 pub fn input_value<'a, X>(input: &mut TokenStream<'a>)
@@ -130,7 +186,7 @@ pub fn input_value<'a, X>(input: &mut TokenStream<'a>)
 {
     (
         position(),
-        optional(parser(string)),
+        optional(parser(description)),
         name::<'a, X>(),
         punct(":").with(parser(parse_type)),
         optional(punct("=").with(parser(default_value))),
@@ -143,6 +199,7 @@ pub fn input_value<'a, X>(input: &mut TokenStream<'a>)
         }
     })
     .parse_stream(input)
+    .map_err(|e| input.rule_failed("InputValueDefinition", e))
 }
 
 pub fn arguments_definition<'a, T>(input: &mut TokenStream<'a>)
@@ -152,6 +209,7 @@ pub fn arguments_definition<'a, T>(input: &mut TokenStream<'a>)
     optional(punct("(").with(many1(parser(input_value))).skip(punct(")")))
     .map(|v| v.unwrap_or_else(Vec::new))
     .parse_stream(input)
+    .map_err(|e| input.rule_failed("ArgumentsDefinition", e))
 }
 
 pub fn field<'a, S>(input: &mut TokenStream<'a>)
@@ -160,7 +218,7 @@ pub fn field<'a, S>(input: &mut TokenStream<'a>)
 {
     (
         position(),
-        optional(parser(string)),
+        optional(parser(description)),
         name::<'a, S>(),
         parser(arguments_definition),
         punct(":").with(parser(parse_type)),
@@ -172,6 +230,7 @@ pub fn field<'a, S>(input: &mut TokenStream<'a>)
         }
     })
     .parse_stream(input)
+    .map_err(|e| input.rule_failed("FieldDefinition", e))
 }
 
 pub fn fields<'a, S>(input: &mut TokenStream<'a>)
@@ -181,6 +240,7 @@ pub fn fields<'a, S>(input: &mut TokenStream<'a>)
     optional(punct("{").with(many1(parser(field))).skip(punct("}")))
     .map(|v| v.unwrap_or_else(Vec::new))
     .parse_stream(input)
+    .map_err(|e| input.rule_failed("FieldsDefinition", e))
 }
 
 
@@ -203,6 +263,7 @@ pub fn object_type<'a, S>(input: &mut TokenStream<'a>)
             }
         })
         .parse_stream(input)
+        .map_err(|e| input.rule_failed("ObjectTypeDefinition", e))
 }
 
 pub fn object_type_extension<'a, S>(input: &mut TokenStream<'a>)
@@ -232,6 +293,7 @@ pub fn object_type_extension<'a, S>(input: &mut TokenStream<'a>)
             })
         })
         .parse_stream(input)
+        .map_err(|e| input.rule_failed("ObjectTypeExtension", e))
 }
 
 pub fn interface_type<'a, T>(input: &mut TokenStream<'a>)
@@ -241,16 +303,19 @@ pub fn interface_type<'a, T>(input: &mut TokenStream<'a>)
     (
         position(),
         ident("interface").with(name::<'a, T>()),
+        parser(implements_interfaces::<T>),
         parser(directives),
         parser(fields),
     )
-        .map(|(position, name, directives, fields)| {
+        .map(|(position, name, interfaces, directives, fields)| {
             InterfaceType {
                 position, name, directives, fields,
+                implements_interfaces: interfaces,
                 description: None,  // is filled in described_definition
             }
         })
         .parse_stream(input)
+        .map_err(|e| input.rule_failed("InterfaceTypeDefinition", e))
 }
 
 pub fn interface_type_extension<'a, T>(input: &mut TokenStream<'a>)
@@ -260,22 +325,27 @@ pub fn interface_type_extension<'a, T>(input: &mut TokenStream<'a>)
     (
         position(),
         ident("interface").with(name::<'a, T>()),
+        parser(implements_interfaces::<T>),
         parser(directives),
         parser(fields),
     )
-        .flat_map(|(position, name, directives, fields)| {
-            if directives.is_empty() && fields.is_empty() {
+        .flat_map(|(position, name, interfaces, directives, fields)| {
+            if interfaces.is_empty() && directives.is_empty() &&
+                fields.is_empty()
+            {
                 let mut e = Errors::empty(position);
                 e.add_error(Error::expected_static_message(
                     "Interface type extension should contain at least \
-                     one directive or field."));
+                     one interface, directive or field."));
                 return Err(e);
             }
             Ok(InterfaceTypeExtension {
                 position, name, directives, fields,
+                implements_interfaces: interfaces,
             })
         })
         .parse_stream(input)
+        .map_err(|e| input.rule_failed("InterfaceTypeExtension", e))
 }
 
 pub fn union_members<'a, T>(input: &mut TokenStream<'a>)
@@ -285,6 +355,7 @@ pub fn union_members<'a, T>(input: &mut TokenStream<'a>)
     optional(punct("|"))
     .with(sep_by1(name::<'a, T>(), punct("|")))
     .parse_stream(input)
+    .map_err(|e| input.rule_failed("UnionMemberTypes", e))
 }
 
 pub fn union_type<'a, T>(input: &mut TokenStream<'a>)
@@ -305,6 +376,7 @@ pub fn union_type<'a, T>(input: &mut TokenStream<'a>)
         }
     })
     .parse_stream(input)
+    .map_err(|e| input.rule_failed("UnionTypeDefinition", e))
 }
 
 pub fn union_type_extension<'a, T>(input: &mut TokenStream<'a>)
@@ -331,6 +403,7 @@ pub fn union_type_extension<'a, T>(input: &mut TokenStream<'a>)
         })
     })
     .parse_stream(input)
+    .map_err(|e| input.rule_failed("UnionTypeExtension", e))
 }
 
 pub fn enum_values<'a, T>(input: &mut TokenStream<'a>)
@@ -341,7 +414,7 @@ pub fn enum_values<'a, T>(input: &mut TokenStream<'a>)
     .with(many1(
         (
             position(),
-            optional(parser(string)),
+            optional(parser(description)),
             name::<'a, T>(),
             parser(directives),
         )
@@ -351,6 +424,7 @@ pub fn enum_values<'a, T>(input: &mut TokenStream<'a>)
     ))
     .skip(punct("}"))
     .parse_stream(input)
+    .map_err(|e| input.rule_failed("EnumValuesDefinition", e))
 }
 
 pub fn enum_type<'a, T>(input: &mut TokenStream<'a>)
@@ -371,6 +445,7 @@ pub fn enum_type<'a, T>(input: &mut TokenStream<'a>)
         }
     })
     .parse_stream(input)
+    .map_err(|e| input.rule_failed("EnumTypeDefinition", e))
 }
 
 pub fn enum_type_extension<'a, T>(input: &mut TokenStream<'a>)
@@ -397,6 +472,7 @@ pub fn enum_type_extension<'a, T>(input: &mut TokenStream<'a>)
         })
     })
     .parse_stream(input)
+    .map_err(|e| input.rule_failed("EnumTypeExtension", e))
 }
 
 pub fn input_fields<'a, T>(input: &mut TokenStream<'a>)
@@ -406,6 +482,7 @@ pub fn input_fields<'a, T>(input: &mut TokenStream<'a>)
     optional(punct("{").with(many1(parser(input_value))).skip(punct("}")))
     .map(|v| v.unwrap_or_else(Vec::new))
     .parse_stream(input)
+    .map_err(|e| input.rule_failed("InputFieldsDefinition", e))
 }
 
 pub fn input_object_type<'a, T>(input: &mut TokenStream<'a>)
@@ -425,6 +502,7 @@ pub fn input_object_type<'a, T>(input: &mut TokenStream<'a>)
             }
         })
         .parse_stream(input)
+        .map_err(|e| input.rule_failed("InputObjectTypeDefinition", e))
 }
 
 pub fn input_object_type_extension<'a, T>(input: &mut TokenStream<'a>)
@@ -450,6 +528,7 @@ pub fn input_object_type_extension<'a, T>(input: &mut TokenStream<'a>)
             })
         })
         .parse_stream(input)
+        .map_err(|e| input.rule_failed("InputObjectTypeExtension", e))
 }
 
 pub fn directive_locations<'a>(input: &mut TokenStream<'a>)
@@ -464,6 +543,7 @@ pub fn directive_locations<'a>(input: &mut TokenStream<'a>)
     )
         .map(|opt| opt.unwrap_or_else(Vec::new))
         .parse_stream(input)
+        .map_err(|e| input.rule_failed("DirectiveLocations", e))
 }
 
 pub fn directive_definition<'a, T>(input: &mut TokenStream<'a>)
@@ -474,15 +554,17 @@ pub fn directive_definition<'a, T>(input: &mut TokenStream<'a>)
         position(),
         ident("directive").and(punct("@")).with(name::<'a, T>()),
         parser(arguments_definition),
+        optional(ident("repeatable")).map(|r| r.is_some()),
         ident("on").with(parser(directive_locations)),
     )
-        .map(|(position, name, arguments, locations)| {
+        .map(|(position, name, arguments, repeatable, locations)| {
             DirectiveDefinition {
-                position, name, arguments, locations,
+                position, name, arguments, repeatable, locations,
                 description: None,  // is filled in described_definition
             }
         })
         .parse_stream(input)
+        .map_err(|e| input.rule_failed("DirectiveDefinition", e))
 }
 
 pub fn described_definition<'a, T>(input: &mut TokenStream<'a>)
@@ -491,7 +573,7 @@ pub fn described_definition<'a, T>(input: &mut TokenStream<'a>)
 {
     use self::TypeDefinition::*;
     (
-        optional(parser(string)),
+        optional(parser(description)),
         choice((
             choice((
                 parser(scalar_type).map(Scalar),
@@ -520,26 +602,28 @@ pub fn described_definition<'a, T>(input: &mut TokenStream<'a>)
                 T(InputObject(ref mut o)) => o.description = descr,
                 DirectiveDefinition(ref mut d) => d.description = descr,
                 SchemaDefinition(_) => unreachable!(),
+                SchemaExtension(_) => unreachable!(),
                 TypeExtension(_) => unreachable!(),
+                Custom(_) => unreachable!(),
             }
             def
         })
         .parse_stream(input)
 }
 
+/// Parses type extension following the `extend` keyword
 pub fn type_extension<'a, T>(input: &mut TokenStream<'a>)
     -> ParseResult<TypeExtension<'a, T>, TokenStream<'a>>
     where T: Text<'a>,
 {
-    ident("extend")
-    .with(choice((
+    choice((
         parser(scalar_type_extension).map(TypeExtension::Scalar),
         parser(object_type_extension).map(TypeExtension::Object),
         parser(interface_type_extension).map(TypeExtension::Interface),
         parser(union_type_extension).map(TypeExtension::Union),
         parser(enum_type_extension).map(TypeExtension::Enum),
         parser(input_object_type_extension).map(TypeExtension::InputObject),
-    )))
+    ))
     .parse_stream(input)
 }
 
@@ -550,34 +634,229 @@ pub fn definition<'a, T>(input: &mut TokenStream<'a>)
 {
     choice((
         parser(schema).map(Definition::SchemaDefinition),
-        parser(type_extension).map(Definition::TypeExtension),
+        ident("extend").with(choice((
+            parser(schema_extension).map(Definition::SchemaExtension),
+            parser(type_extension).map(Definition::TypeExtension),
+        ))),
         parser(described_definition),
     )).parse_stream(input)
 }
 
+/// Parses a standard definition, or a custom one if there is a handler
+/// registered for the keyword
+pub fn definition_with_options<'a, S>(input: &mut TokenStream<'a>,
+    options: &ParserOptions)
+    -> ParseResult<Definition<'a, S>, TokenStream<'a>>
+    where S: Text<'a>,
+{
+    let checkpoint = input.checkpoint();
+    let err = match parser(definition).parse_stream(input) {
+        Ok(result) => return Ok(result),
+        Err(err) => err,
+    };
+    let failed_at = input.checkpoint();
+    input.reset(checkpoint);
+    let handler = match input.peek() {
+        Some(tok) if tok.kind == T::Name => {
+            options.definition_handler(tok.value)
+        }
+        _ => None,
+    };
+    let handler = match handler {
+        Some(handler) => handler,
+        None => {
+            input.reset(failed_at);
+            return Err(err);
+        }
+    };
+    let position = input.position();
+    let start = input.offset();
+    let keyword = input.peek().expect("token is peeked above").value;
+    match handler(input) {
+        // `many1` would call the handler again and again at the same token
+        Ok(_) if input.offset() == start => {
+            let mut err = Errors::empty(position);
+            err.add_error(Error::Message(format!(
+                "handler of the {:?} definition consumed no tokens",
+                keyword).into()));
+            Err(Consumed::Consumed(Tracked::from(err)))
+        }
+        Ok(node) => Ok((Definition::Custom(CustomDefinition {
+            position,
+            keyword: keyword.into(),
+            source: input.source_since(start).into(),
+            node,
+        }), Consumed::Consumed(()))),
+        Err(message) => {
+            let mut err = Errors::empty(input.position());
+            err.add_error(Error::Message(message.into()));
+            Err(Consumed::Consumed(Tracked::from(err)))
+        }
+    }
+}
+
 /// Parses a piece of schema language and returns an AST
 pub fn parse_schema<'a, T>(s: &'a str) -> Result<Document<'a, T>, ParseError>
     where T: Text<'a>,
 {
-    let mut tokens = TokenStream::new(s);
-    let (doc, _) = many1(parser(definition))
+    parse_schema_with_options(s, &ParserOptions::default())
+}
+
+/// Parses a piece of schema language embedded in a larger text, starting
+/// at the `start` position in it
+pub fn parse_schema_with_offset<'a, T>(s: &'a str, start: Pos)
+    -> Result<Document<'a, T>, ParseError>
+    where T: Text<'a>,
+{
+    parse_schema_with_options(s,
+        ParserOptions::default().start_position(start))
+}
+
+/// Parses a piece of schema language using specified options
+pub fn parse_schema_with_options<'a, T>(s: &'a str, options: &ParserOptions)
+    -> Result<Document<'a, T>, ParseError>
+    where T: Text<'a>,
+{
+    let mut tokens = options.tokens(s);
+    let (doc, _) = many1(parser(|input| definition_with_options(input, options)))
         .map(|d| Document { definitions: d })
         .skip(eof())
         .parse_stream(&mut tokens)
-        .map_err(|e| e.into_inner().error)?;
+        .map_err(|e| ParseError::syntax(e.into_inner().error, &tokens))
+        .map_err(|e| e.in_source(options))?;
 
     Ok(doc)
 }
 
+/// Iterator over the parsed definitions of a schema, see `parse_schema_iter`
+#[derive(Debug)]
+pub struct SchemaDefinitions<'a, T> {
+    tokens: TokenStream<'a>,
+    options: ParserOptions,
+    started: bool,
+    failed: bool,
+    text: PhantomData<T>,
+}
+
+/// Parses the definitions of a schema one by one, as the iterator advances
+///
+/// Only the current definition is kept in memory, so this is suitable for
+/// scanning huge schemas. To find a few definitions without parsing the
+/// rest, see `split_schema`.
+///
+/// The iterator stops after the first error.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::schema::{parse_schema_iter, Definition};
+///
+/// let source = "type Query { a: A } scalar A directive @b on FIELD";
+/// let kinds = parse_schema_iter::<&str>(source)
+///     .map(|def| match def.unwrap() {
+///         Definition::TypeDefinition(_) => "type",
+///         Definition::DirectiveDefinition(_) => "directive",
+///         _ => "other",
+///     })
+///     .collect::<Vec<_>>();
+/// assert_eq!(kinds, ["type", "type", "directive"]);
+/// ```
+pub fn parse_schema_iter<'a, T>(s: &'a str) -> SchemaDefinitions<'a, T>
+    where T: Text<'a>,
+{
+    parse_schema_iter_with_options(s, &ParserOptions::default())
+}
+
+/// Parses the definitions of a schema one by one using specified options
+pub fn parse_schema_iter_with_options<'a, T>(s: &'a str,
+    options: &ParserOptions)
+    -> SchemaDefinitions<'a, T>
+    where T: Text<'a>,
+{
+    SchemaDefinitions {
+        tokens: options.tokens(s),
+        options: options.clone(),
+        started: false,
+        failed: false,
+        text: PhantomData,
+    }
+}
+
+impl<'a, T: Text<'a>> Iterator for SchemaDefinitions<'a, T> {
+    type Item = Result<Definition<'a, T>, ParseError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        // an empty document is an error, same as for `parse_schema`
+        if self.started && eof().parse_stream(&mut self.tokens).is_ok() {
+            return None;
+        }
+        self.started = true;
+        let options = &self.options;
+        let result = parser(|input| definition_with_options(input, options))
+            .parse_stream(&mut self.tokens)
+            .map(|(def, _)| def)
+            .map_err(|e| {
+                ParseError::syntax(e.into_inner().error, &self.tokens)
+            })
+            .map_err(|e| e.in_source(options));
+        self.failed = result.is_err();
+        Some(result)
+    }
+}
+
+impl FromStr for Document<'static, String> {
+    type Err = ParseError;
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        parse_schema::<&str>(s).map(|doc| doc.map_text(String::from))
+    }
+}
+
 
 #[cfg(test)]
 mod test {
     use crate::position::Pos;
     use crate::schema::grammar::*;
-    use super::parse_schema;
+    use super::{parse_schema, parse_schema_with_options};
+    use crate::options::ParserOptions;
+
+    fn ast(s: &str) -> Document<'_, String> {
+        parse_schema::<String>(s).unwrap().to_owned()
+    }
 
-    fn ast(s: &str) -> Document<String> {
-        parse_schema::<String>(&s).unwrap().to_owned()
+    #[test]
+    fn from_str() {
+        let doc: Document<String> = "type A { b: Int }".parse().unwrap();
+        assert_eq!(doc, ast("type A { b: Int }"));
+        assert!("type A {".parse::<Document<String>>().is_err());
+    }
+
+    #[test]
+    fn raw_descriptions() {
+        let doc = parse_schema::<&str>(r#"
+            """
+              Indented
+            Text
+            """
+            scalar A
+            "Escaped\tvalue" scalar B
+        "#).unwrap();
+        let descr = |i: usize| match doc.definitions[i] {
+            Definition::TypeDefinition(TypeDefinition::Scalar(ref s)) => {
+                s.description.clone().unwrap()
+            }
+            _ => unreachable!(),
+        };
+        let a = descr(0);
+        assert_eq!(a, "  Indented\nText\n");
+        assert_eq!(a.raw.as_deref(), Some("\"\"\"\n              Indented\n\
+            \x20           Text\n            \"\"\""));
+        assert_eq!(a.indent, 12);
+        assert!(a.is_block());
+        let b = descr(1);
+        assert_eq!(b, "Escaped\tvalue");
+        assert_eq!(b.raw.as_deref(), Some(r#""Escaped\tvalue""#));
+        assert!(!b.is_block());
     }
 
     #[test]
@@ -597,4 +876,74 @@ mod test {
             ],
         });
     }
+
+    #[test]
+    fn custom_definition() {
+        use std::sync::Arc;
+        use combine::StreamOnce;
+
+        let mut options = ParserOptions::default();
+        options.custom_definition("vendor", |tokens| {
+            tokens.uncons().map_err(|e| e.to_string())?;  // keyword
+            let name = tokens.uncons().map_err(|e| e.to_string())?;
+            Ok(Arc::new(name.value.to_string()))
+        });
+        let doc = parse_schema_with_options::<String>(
+            "vendor thing # comment\ntype A", &options).unwrap();
+        match &doc.definitions[0] {
+            Definition::Custom(c) => {
+                assert_eq!(c.keyword, "vendor");
+                assert_eq!(c.source, "vendor thing");
+                assert_eq!(c.node.downcast_ref::<String>().unwrap(), "thing");
+            }
+            _ => panic!("custom definition expected"),
+        }
+        assert_eq!(doc.to_string(), "vendor thing\n\ntype A\n");
+        assert!(parse_schema::<String>("vendor thing").is_err());
+        assert!(parse_schema_with_options::<String>("other thing", &options)
+            .is_err());
+    }
+
+    #[test]
+    fn custom_definition_consuming_nothing() {
+        use std::sync::Arc;
+
+        let mut options = ParserOptions::default();
+        options.custom_definition("vendor", |_| Ok(Arc::new(())));
+        let err = parse_schema_with_options::<String>("type A vendor thing",
+                                                      &options).unwrap_err();
+        assert_eq!(err.to_string(), "schema parse error: Parse error at 1:8\n\
+            handler of the \"vendor\" definition consumed no tokens\n");
+    }
+
+    #[test]
+    fn schema_extension() {
+        assert_eq!(ast("extend schema @link(url: \"x\")\n\
+                        extend schema { mutation: M }").definitions[1],
+            Definition::SchemaExtension(SchemaExtension {
+                position: Pos { line: 2, column: 8, character: 37, token: 10 },
+                directives: vec![],
+                query: None,
+                mutation: Some("M".into()),
+                subscription: None,
+            }));
+        assert!(parse_schema::<String>("extend schema").is_err());
+        assert!(parse_schema::<String>("extend schema {}").is_err());
+        assert!(parse_schema::<String>(
+            "extend schema { query: A query: B }").is_err());
+    }
+
+    #[test]
+    fn structured_errors() {
+        use crate::error::ErrorCode;
+
+        let err = |s| parse_schema::<&str>(s).unwrap_err();
+        let e = err("type A {\n  f(x: ): Int\n}");
+        assert_eq!(e.code(), ErrorCode::UnexpectedToken);
+        assert_eq!(e.rule(), Some("InputValueDefinition"));
+        assert_eq!(e.span().0.line, 2);
+        let e = err("schema { query: A query: B }");
+        assert_eq!(e.code(), ErrorCode::InvalidDefinition);
+        assert_eq!(e.found(), None);
+    }
 }
@@ -0,0 +1,384 @@
+//! Lookups over a schema document with the extensions applied
+use std::collections::BTreeMap;
+
+use crate::common::Text;
+use crate::schema::ast::*;
+
+
+/// Names of the scalars that exist in every schema
+pub(crate) const BUILTIN_SCALARS: &[&str] = &[
+    "Int", "Float", "String", "Boolean", "ID",
+];
+
+/// Directives defined by the specification
+pub(crate) const BUILTIN_DIRECTIVES: &[&str] = &[
+    "skip", "include", "deprecated", "specifiedBy",
+];
+
+/// Definitions of the directives from `BUILTIN_DIRECTIVES`
+#[cfg(any(feature = "query", feature = "introspection"))]
+pub(crate) const BUILTIN_DIRECTIVE_DEFINITIONS: &str = r#"
+directive @skip(if: Boolean!) on FIELD | FRAGMENT_SPREAD | INLINE_FRAGMENT
+directive @include(if: Boolean!) on FIELD | FRAGMENT_SPREAD | INLINE_FRAGMENT
+directive @deprecated(reason: String = "No longer supported")
+    on FIELD_DEFINITION | ARGUMENT_DEFINITION | INPUT_FIELD_DEFINITION
+    | ENUM_VALUE
+directive @specifiedBy(url: String!) on SCALAR
+"#;
+
+/// Kind of the named type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypeKind {
+    Scalar,
+    Object,
+    Interface,
+    Union,
+    Enum,
+    InputObject,
+}
+
+/// A type with all the extensions applied
+///
+/// Built-in scalars which are not defined in the document have neither
+/// definition nor extensions.
+#[derive(Debug)]
+pub struct TypeEntry<'x, 'a, T>
+    where T: Text<'a>
+{
+    pub name: &'x str,
+    pub kind: TypeKind,
+    pub definition: Option<&'x TypeDefinition<'a, T>>,
+    pub extensions: Vec<&'x TypeExtension<'a, T>>,
+    /// Fields of objects and interfaces
+    pub fields: Vec<&'x Field<'a, T>>,
+    pub input_fields: Vec<&'x InputValue<'a, T>>,
+    /// Names of enum values
+    pub values: Vec<&'x str>,
+    /// Member types of unions
+    pub members: Vec<&'x str>,
+    /// Interfaces implemented by objects and interfaces
+    pub interfaces: Vec<&'x str>,
+}
+
+/// Lookup tables over a schema document
+///
+/// Definitions are merged with their extensions, so fields added by
+/// `extend type` are found same as the ones of the type definition:
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::parse_schema;
+/// use edb_graphql_parser::schema::{SchemaIndex, TypeKind};
+///
+/// let doc = parse_schema::<&str>("
+///     interface Node { id: ID! }
+///     type User implements Node { id: ID! }
+///     type Query { node: Node }
+///     extend type Query { me: User }
+///     directive @auth on FIELD_DEFINITION
+/// ").unwrap();
+/// let index = SchemaIndex::new(&doc);
+/// assert_eq!(index.type_by_name("Node").unwrap().kind, TypeKind::Interface);
+/// assert_eq!(index.query_type(), Some("Query"));
+/// assert!(index.field("Query", "me").is_some());
+/// assert_eq!(index.possible_types("Node"), ["User"]);
+/// assert!(index.directive("auth").is_some());
+/// ```
+#[derive(Debug)]
+pub struct SchemaIndex<'x, 'a, T>
+    where T: Text<'a>
+{
+    types: BTreeMap<&'x str, TypeEntry<'x, 'a, T>>,
+    directives: BTreeMap<&'x str, &'x DirectiveDefinition<'a, T>>,
+    query: Option<&'x str>,
+    mutation: Option<&'x str>,
+    subscription: Option<&'x str>,
+}
+
+/// Returns the name of the type with list and non-null wrappers removed
+pub fn type_name<'x, 'a, T>(typ: &'x Type<'a, T>) -> &'x str
+    where T: Text<'a>,
+{
+    typ.innermost_name()
+}
+
+impl TypeKind {
+    /// Objects, interfaces and unions, i.e. types having selection sets
+    pub fn is_composite(self) -> bool {
+        use self::TypeKind::*;
+        matches!(self, Object | Interface | Union)
+    }
+    /// Scalars and enums
+    pub fn is_leaf(self) -> bool {
+        use self::TypeKind::*;
+        matches!(self, Scalar | Enum)
+    }
+    /// Types allowed for arguments and variables
+    pub fn is_input(self) -> bool {
+        use self::TypeKind::*;
+        matches!(self, Scalar | Enum | InputObject)
+    }
+}
+
+impl<'x, 'a, T> TypeEntry<'x, 'a, T>
+    where T: Text<'a>
+{
+    fn new(name: &'x str, kind: TypeKind) -> Self {
+        TypeEntry {
+            name,
+            kind,
+            definition: None,
+            extensions: Vec::new(),
+            fields: Vec::new(),
+            input_fields: Vec::new(),
+            values: Vec::new(),
+            members: Vec::new(),
+            interfaces: Vec::new(),
+        }
+    }
+
+    /// Returns the field of the object or interface
+    pub fn field(&self, name: &str) -> Option<&'x Field<'a, T>> {
+        self.fields.iter().find(|f| f.name.as_ref() == name).copied()
+    }
+}
+
+impl<'x, 'a, T> SchemaIndex<'x, 'a, T>
+    where T: Text<'a>
+{
+    pub fn new(doc: &'x Document<'a, T>) -> Self {
+        use crate::schema::ast::Definition::*;
+
+        let mut index = SchemaIndex {
+            types: BTreeMap::new(),
+            directives: BTreeMap::new(),
+            query: None,
+            mutation: None,
+            subscription: None,
+        };
+        for name in BUILTIN_SCALARS {
+            index.types.insert(name, TypeEntry::new(name, TypeKind::Scalar));
+        }
+        let mut schema = None;
+        let mut extensions = Vec::new();
+        for def in &doc.definitions {
+            match *def {
+                SchemaDefinition(ref s) => schema = Some(s),
+                SchemaExtension(ref e) => extensions.push(e),
+                TypeDefinition(ref t) => index.add_type(t),
+                TypeExtension(ref e) => index.add_extension(e),
+                DirectiveDefinition(ref d) => {
+                    index.directives.entry(d.name.as_ref()).or_insert(d);
+                }
+                Custom(_) => {}
+            }
+        }
+        match schema {
+            Some(s) => {
+                index.query = s.query.as_ref().map(|n| n.as_ref());
+                index.mutation = s.mutation.as_ref().map(|n| n.as_ref());
+                index.subscription =
+                    s.subscription.as_ref().map(|n| n.as_ref());
+            }
+            None => {
+                let types = &index.types;
+                let get = |name| types.get_key_value(name).map(|(&k, _)| k);
+                index.query = get("Query");
+                index.mutation = get("Mutation");
+                index.subscription = get("Subscription");
+            }
+        }
+        for e in extensions {
+            if let Some(ref q) = e.query {
+                index.query = Some(q.as_ref());
+            }
+            if let Some(ref m) = e.mutation {
+                index.mutation = Some(m.as_ref());
+            }
+            if let Some(ref s) = e.subscription {
+                index.subscription = Some(s.as_ref());
+            }
+        }
+        index
+    }
+
+    fn entry(&mut self, name: &'x str, kind: TypeKind)
+        -> &mut TypeEntry<'x, 'a, T>
+    {
+        self.types.entry(name).or_insert_with(|| TypeEntry::new(name, kind))
+    }
+
+    fn add_type(&mut self, def: &'x TypeDefinition<'a, T>) {
+        use crate::schema::ast::TypeDefinition::*;
+        match *def {
+            Scalar(ref s) => {
+                self.entry(s.name.as_ref(), TypeKind::Scalar);
+            }
+            Object(ref o) => {
+                let entry = self.entry(o.name.as_ref(), TypeKind::Object);
+                entry.fields.extend(&o.fields);
+                entry.interfaces.extend(
+                    o.implements_interfaces.iter().map(|n| n.as_ref()));
+            }
+            Interface(ref i) => {
+                let entry = self.entry(i.name.as_ref(), TypeKind::Interface);
+                entry.fields.extend(&i.fields);
+                entry.interfaces.extend(
+                    i.implements_interfaces.iter().map(|n| n.as_ref()));
+            }
+            Union(ref u) => {
+                let entry = self.entry(u.name.as_ref(), TypeKind::Union);
+                entry.members.extend(u.types.iter().map(|n| n.as_ref()));
+            }
+            Enum(ref e) => {
+                let entry = self.entry(e.name.as_ref(), TypeKind::Enum);
+                entry.values.extend(e.values.iter().map(|v| v.name.as_ref()));
+            }
+            InputObject(ref i) => {
+                let entry = self.entry(i.name.as_ref(), TypeKind::InputObject);
+                entry.input_fields.extend(&i.fields);
+            }
+        }
+        if let Some(entry) = self.types.get_mut(def.name().as_ref()) {
+            entry.definition.get_or_insert(def);
+        }
+    }
+
+    fn add_extension(&mut self, ext: &'x TypeExtension<'a, T>) {
+        use crate::schema::ast::TypeExtension::*;
+        match *ext {
+            Scalar(ref s) => {
+                self.entry(s.name.as_ref(), TypeKind::Scalar);
+            }
+            Object(ref o) => {
+                let entry = self.entry(o.name.as_ref(), TypeKind::Object);
+                entry.fields.extend(&o.fields);
+                entry.interfaces.extend(
+                    o.implements_interfaces.iter().map(|n| n.as_ref()));
+            }
+            Interface(ref i) => {
+                let entry = self.entry(i.name.as_ref(), TypeKind::Interface);
+                entry.fields.extend(&i.fields);
+                entry.interfaces.extend(
+                    i.implements_interfaces.iter().map(|n| n.as_ref()));
+            }
+            Union(ref u) => {
+                let entry = self.entry(u.name.as_ref(), TypeKind::Union);
+                entry.members.extend(u.types.iter().map(|n| n.as_ref()));
+            }
+            Enum(ref e) => {
+                let entry = self.entry(e.name.as_ref(), TypeKind::Enum);
+                entry.values.extend(e.values.iter().map(|v| v.name.as_ref()));
+            }
+            InputObject(ref i) => {
+                let entry = self.entry(i.name.as_ref(), TypeKind::InputObject);
+                entry.input_fields.extend(&i.fields);
+            }
+        }
+        if let Some(entry) = self.types.get_mut(ext.name().as_ref()) {
+            entry.extensions.push(ext);
+        }
+    }
+
+    /// Returns the type, built-in scalars are included
+    pub fn type_by_name(&self, name: &str) -> Option<&TypeEntry<'x, 'a, T>> {
+        self.types.get(name)
+    }
+
+    /// Returns the field of the object or interface type
+    pub fn field(&self, type_name: &str, field_name: &str)
+        -> Option<&'x Field<'a, T>>
+    {
+        self.types.get(type_name).and_then(|entry| entry.field(field_name))
+    }
+
+    /// Returns the directive definition, built-in directives are only
+    /// found if they are defined in the document
+    pub fn directive(&self, name: &str)
+        -> Option<&'x DirectiveDefinition<'a, T>>
+    {
+        self.directives.get(name).copied()
+    }
+
+    /// Returns all the types ordered by name
+    pub fn types(&self)
+        -> impl Iterator<Item=(&'x str, &TypeEntry<'x, 'a, T>)>
+    {
+        self.types.iter().map(|(&name, entry)| (name, entry))
+    }
+
+    /// Returns the name of the root query type, set by the schema
+    /// definition or extensions, or `Query` if there is no schema
+    /// definition and the type exists
+    pub fn query_type(&self) -> Option<&'x str> {
+        self.query
+    }
+
+    /// Returns the name of the root mutation type, see `query_type`
+    pub fn mutation_type(&self) -> Option<&'x str> {
+        self.mutation
+    }
+
+    /// Returns the name of the root subscription type, see `query_type`
+    pub fn subscription_type(&self) -> Option<&'x str> {
+        self.subscription
+    }
+
+    /// Returns object types that can be returned for the named type
+    pub fn possible_types(&self, name: &str) -> Vec<&'x str> {
+        match self.types.get_key_value(name) {
+            Some((&key, entry)) => match entry.kind {
+                TypeKind::Object => vec![key],
+                TypeKind::Union => entry.members.clone(),
+                TypeKind::Interface => {
+                    self.types.iter()
+                        .filter(|(_, t)| t.kind == TypeKind::Object &&
+                                         t.interfaces.contains(&name))
+                        .map(|(&n, _)| n)
+                        .collect()
+                }
+                _ => Vec::new(),
+            },
+            None => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse_schema;
+    use super::{SchemaIndex, TypeKind};
+
+    #[test]
+    fn extensions() {
+        let doc = parse_schema::<&str>("
+            schema { query: Root }
+            type Root { a: Int }
+            extend type Root implements Node { b: Int }
+            union U = Root
+            extend union U = Other
+            extend schema { mutation: Root }
+            directive @d on FIELD
+            directive @d on OBJECT
+        ").unwrap();
+        let index = SchemaIndex::new(&doc);
+        let root = index.type_by_name("Root").unwrap();
+        assert!(root.definition.is_some());
+        assert_eq!(root.extensions.len(), 1);
+        assert_eq!(root.interfaces, ["Node"]);
+        assert_eq!(index.field("Root", "b").unwrap().name, "b");
+        assert!(index.field("Root", "c").is_none());
+        assert!(index.field("Missing", "a").is_none());
+        assert_eq!(index.query_type(), Some("Root"));
+        assert_eq!(index.mutation_type(), Some("Root"));
+        assert_eq!(index.subscription_type(), None);
+        assert_eq!(index.possible_types("U"), ["Root", "Other"]);
+        assert_eq!(index.possible_types("Root"), ["Root"]);
+        let int = index.type_by_name("Int").unwrap();
+        assert_eq!(int.kind, TypeKind::Scalar);
+        assert!(int.definition.is_none());
+        // the first definition wins
+        assert_eq!(index.directive("d").unwrap().locations.len(), 1);
+        assert!(index.types().any(|(name, _)| name == "U"));
+    }
+}
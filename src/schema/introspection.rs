@@ -0,0 +1,781 @@
+use std::collections::BTreeMap;
+
+use combine::{eof, parser, Parser};
+use serde_json::{json, Value as Json};
+use thiserror::Error;
+
+use crate::common::{default_value, DirectiveArgument};
+use crate::position::Pos;
+use crate::schema::ast::*;
+use crate::schema::index::{BUILTIN_DIRECTIVES, BUILTIN_SCALARS};
+use crate::schema::index::BUILTIN_DIRECTIVE_DEFINITIONS;
+use crate::schema::merge::merge_schemas;
+use crate::tokenizer::TokenStream;
+
+
+type Result<T> = std::result::Result<T, IntrospectionError>;
+
+/// Error converting introspection result
+///
+/// This structure is opaque for forward compatibility, same as
+/// `ParseError`.
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("introspection error: {}", _0)]
+pub struct IntrospectionError(String);
+
+/// Converts the result of the introspection query into a schema document
+///
+/// Accepts both the full response (`{"data": {"__schema": ...}}`) and the
+/// `data` object alone, as saved by most tools. Built-in scalars and
+/// directives, and introspection types are not included in the document.
+/// Deprecations are converted to `@deprecated` directives.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::schema::from_introspection;
+///
+/// let doc = from_introspection(r#"{"__schema": {
+///     "queryType": {"name": "Query"},
+///     "types": [{"kind": "OBJECT", "name": "Query", "fields": [{
+///         "name": "hello", "args": [],
+///         "type": {"kind": "SCALAR", "name": "String"}
+///     }]}],
+///     "directives": []
+/// }}"#).unwrap();
+/// assert_eq!(doc.to_string(), "type Query {\n  hello: String\n}\n");
+/// ```
+pub fn from_introspection(json: &str)
+    -> Result<Document<'static, String>>
+{
+    let json: Json = serde_json::from_str(json)
+        .map_err(|e| IntrospectionError(e.to_string()))?;
+    from_introspection_json(&json)
+}
+
+/// Converts the already parsed result of the introspection query into a
+/// schema document
+///
+/// Same as `from_introspection`, for results received from an HTTP client
+/// or embedded into other JSON.
+pub fn from_introspection_json(json: &Json)
+    -> Result<Document<'static, String>>
+{
+    let data = json.get("data").unwrap_or(json);
+    let schema = data.get("__schema")
+        .ok_or_else(|| IntrospectionError("no `__schema` object".into()))?;
+
+    let mut definitions = Vec::new();
+    let roots = [
+        ("queryType", "Query"),
+        ("mutationType", "Mutation"),
+        ("subscriptionType", "Subscription"),
+    ];
+    let mut names = Vec::new();
+    for &(key, _) in &roots {
+        names.push(match schema.get(key) {
+            Some(root) if !root.is_null() => Some(string(root, "name", key)?),
+            _ => None,
+        });
+    }
+    // schema definition is only required if root types are renamed
+    let renamed = roots.iter().zip(&names)
+        .any(|(&(_, default), name)| {
+            name.as_ref().map(|n| n != default).unwrap_or(false)
+        });
+    if renamed {
+        definitions.push(Definition::SchemaDefinition(SchemaDefinition {
+            position: Pos::default(),
+            directives: Vec::new(),
+            query: names[0].take(),
+            mutation: names[1].take(),
+            subscription: names[2].take(),
+        }));
+    }
+    for dir in list(schema, "directives", "__schema")? {
+        let name = string(dir, "name", "directive")?;
+        if BUILTIN_DIRECTIVES.contains(&name.as_str()) {
+            continue;
+        }
+        let path = format!("@{}", name);
+        let locations = list(dir, "locations", &path)?.iter()
+            .map(|loc| {
+                loc.as_str().and_then(|loc| loc.parse().ok())
+                    .ok_or_else(|| invalid(&path, "locations"))
+            })
+            .collect::<Result<_>>()?;
+        definitions.push(Definition::DirectiveDefinition(DirectiveDefinition {
+            description: opt_description(dir),
+            arguments: input_values(dir, "args", &path)?,
+            repeatable: dir.get("isRepeatable")
+                .and_then(Json::as_bool).unwrap_or(false),
+            locations,
+            .. DirectiveDefinition::new(name)
+        }));
+    }
+    for typ in list(schema, "types", "__schema")? {
+        let name = string(typ, "name", "type")?;
+        if name.starts_with("__") || BUILTIN_SCALARS.contains(&name.as_str())
+        {
+            continue;
+        }
+        definitions.push(Definition::TypeDefinition(type_definition(typ, name)?));
+    }
+    Ok(Document { definitions })
+}
+
+fn invalid(path: &str, key: &str) -> IntrospectionError {
+    IntrospectionError(format!("`{}` of {} is missing or invalid", key, path))
+}
+
+fn string(obj: &Json, key: &str, path: &str) -> Result<String> {
+    obj.get(key).and_then(Json::as_str).map(String::from)
+        .ok_or_else(|| invalid(path, key))
+}
+
+fn opt_string(obj: &Json, key: &str) -> Option<String> {
+    obj.get(key).and_then(Json::as_str).map(String::from)
+}
+
+fn opt_description(obj: &Json) -> Option<Description> {
+    opt_string(obj, "description").map(Description::from)
+}
+
+/// Returns the list at the key, missing and `null` lists are empty
+fn list<'j>(obj: &'j Json, key: &str, path: &str) -> Result<&'j [Json]> {
+    match obj.get(key) {
+        None | Some(Json::Null) => Ok(&[]),
+        Some(Json::Array(items)) => Ok(items),
+        Some(_) => Err(invalid(path, key)),
+    }
+}
+
+fn names(obj: &Json, key: &str, path: &str) -> Result<Vec<String>> {
+    list(obj, key, path)?.iter().map(|item| string(item, "name", path))
+        .collect()
+}
+
+fn type_definition(typ: &Json, name: String)
+    -> Result<TypeDefinition<'static, String>>
+{
+    let description = opt_description(typ);
+    let kind = string(typ, "kind", &name)?;
+    let def = match kind.as_str() {
+        "SCALAR" => TypeDefinition::Scalar(ScalarType {
+            description,
+            directives: opt_string(typ, "specifiedByURL")
+                .map(|url| vec![directive("specifiedBy", "url", url)])
+                .unwrap_or_default(),
+            .. ScalarType::new(name)
+        }),
+        "OBJECT" => TypeDefinition::Object(ObjectType {
+            description,
+            implements_interfaces: names(typ, "interfaces", &name)?,
+            fields: fields(typ, &name)?,
+            .. ObjectType::new(name)
+        }),
+        "INTERFACE" => TypeDefinition::Interface(InterfaceType {
+            description,
+            implements_interfaces: names(typ, "interfaces", &name)?,
+            fields: fields(typ, &name)?,
+            .. InterfaceType::new(name)
+        }),
+        "UNION" => TypeDefinition::Union(UnionType {
+            description,
+            types: names(typ, "possibleTypes", &name)?,
+            .. UnionType::new(name)
+        }),
+        "ENUM" => TypeDefinition::Enum(EnumType {
+            description,
+            values: list(typ, "enumValues", &name)?.iter()
+                .map(|val| Ok(EnumValue {
+                    description: opt_description(val),
+                    directives: deprecation(val),
+                    .. EnumValue::new(string(val, "name", &name)?)
+                }))
+                .collect::<Result<_>>()?,
+            .. EnumType::new(name)
+        }),
+        "INPUT_OBJECT" => TypeDefinition::InputObject(InputObjectType {
+            description,
+            fields: input_values(typ, "inputFields", &name)?,
+            .. InputObjectType::new(name)
+        }),
+        _ => return Err(invalid(&name, "kind")),
+    };
+    Ok(def)
+}
+
+fn fields(typ: &Json, type_name: &str) -> Result<Vec<Field<'static, String>>>
+{
+    list(typ, "fields", type_name)?.iter().map(|field| {
+        let name = string(field, "name", type_name)?;
+        let path = format!("{}.{}", type_name, name);
+        Ok(Field {
+            position: Pos::default(),
+            description: opt_description(field),
+            arguments: input_values(field, "args", &path)?,
+            field_type: type_ref(field.get("type"), &path)?,
+            directives: deprecation(field),
+            name,
+        })
+    }).collect()
+}
+
+fn input_values(obj: &Json, key: &str, path: &str)
+    -> Result<Vec<InputValue<'static, String>>>
+{
+    list(obj, key, path)?.iter().map(|value| {
+        let name = string(value, "name", path)?;
+        let path = format!("{}({}:)", path, name);
+        let default_value = match value.get("defaultValue") {
+            Some(Json::String(text)) => Some(parse_value(text, &path)?),
+            None | Some(Json::Null) => None,
+            Some(_) => return Err(invalid(&path, "defaultValue")),
+        };
+        Ok(InputValue {
+            position: Pos::default(),
+            description: opt_description(value),
+            value_type: type_ref(value.get("type"), &path)?,
+            default_value,
+            directives: deprecation(value),
+            name,
+        })
+    }).collect()
+}
+
+fn type_ref(typ: Option<&Json>, path: &str) -> Result<Type<'static, String>> {
+    let typ = typ.ok_or_else(|| invalid(path, "type"))?;
+    match typ.get("kind").and_then(Json::as_str) {
+        Some("NON_NULL") => {
+            Ok(Type::NonNullType(Box::new(type_ref(typ.get("ofType"), path)?)))
+        }
+        Some("LIST") => {
+            Ok(Type::ListType(Box::new(type_ref(typ.get("ofType"), path)?)))
+        }
+        _ => Ok(Type::NamedType(string(typ, "name", path)?)),
+    }
+}
+
+fn deprecation(obj: &Json) -> Vec<Directive<'static, String>> {
+    if obj.get("isDeprecated").and_then(Json::as_bool) != Some(true) {
+        return Vec::new();
+    }
+    match opt_string(obj, "deprecationReason") {
+        Some(reason) if reason != DEFAULT_DEPRECATION_REASON => {
+            vec![directive("deprecated", "reason", reason)]
+        }
+        _ => vec![Directive {
+            position: Pos::default(),
+            name: "deprecated".into(),
+            arguments: Vec::new(),
+            arguments_syntax: None,
+        }],
+    }
+}
+
+fn directive(name: &str, argument: &str, value: String)
+    -> Directive<'static, String>
+{
+    Directive {
+        position: Pos::default(),
+        name: name.into(),
+        arguments: vec![DirectiveArgument {
+            name: argument.into(),
+            value: Value::String(value),
+            value_position: Pos::default(),
+        }],
+        arguments_syntax: None,
+    }
+}
+
+/// Parses default value, which introspection returns as GraphQL literal
+fn parse_value(text: &str, path: &str) -> Result<Value<'static, String>> {
+    let mut stream = TokenStream::new(text);
+    parser(default_value::<String>).skip(eof())
+        .parse_stream(&mut stream)
+        .map(|(value, _)| owned(value))
+        .map_err(|_| IntrospectionError(format!(
+            "invalid default value {:?} of {}", text, path)))
+}
+
+fn owned(value: Value<'_, String>) -> Value<'static, String> {
+    match value {
+        Value::Variable(name) => Value::Variable(name),
+        Value::Int(num) => Value::Int(num),
+        Value::Float(num) => Value::Float(num),
+        Value::String(s) => Value::String(s),
+        Value::Boolean(b) => Value::Boolean(b),
+        Value::Null => Value::Null,
+        Value::Enum(name) => Value::Enum(name),
+        Value::List(items) => {
+            Value::List(items.into_iter().map(owned).collect())
+        }
+        Value::Object(fields) => {
+            Value::Object(fields.into_iter()
+                .map(|(name, value)| (name, owned(value))).collect())
+        }
+    }
+}
+
+/// Converts the schema document into the result of the introspection query
+///
+/// Returns the `data` object of the response, i.e. `{"__schema": ...}`,
+/// with all the fields of the standard introspection query. Extensions
+/// are applied to the types (see `merge_schemas`). Built-in scalars and
+/// directives are added unless they are defined in the document, and
+/// introspection types (`__Type` and others) are not included.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::parse_schema;
+/// use edb_graphql_parser::schema::to_introspection;
+///
+/// let doc = parse_schema::<&str>("type Query { hello: String! }").unwrap();
+/// let json = to_introspection(&doc).unwrap();
+/// assert_eq!(json["__schema"]["queryType"]["name"], "Query");
+/// assert_eq!(json["__schema"]["types"][0]["fields"][0]["type"]["kind"],
+///            "NON_NULL");
+/// ```
+pub fn to_introspection<'a, T>(doc: &Document<'a, T>) -> Result<Json>
+    where T: Text<'a> + Clone,
+{
+    let doc = merge_schemas(std::slice::from_ref(doc))
+        .map_err(|e| IntrospectionError(e.to_string()))?;
+    let mut kinds = BTreeMap::new();
+    let mut schema = None;
+    for def in &doc.definitions {
+        match *def {
+            Definition::TypeDefinition(ref t) => {
+                kinds.insert(t.name().as_ref(), type_kind(t));
+            }
+            Definition::SchemaDefinition(ref s) => schema = Some(s),
+            _ => {}
+        }
+    }
+    let roots = match schema {
+        Some(s) => [
+            s.query.as_ref().map(|n| n.as_ref()),
+            s.mutation.as_ref().map(|n| n.as_ref()),
+            s.subscription.as_ref().map(|n| n.as_ref()),
+        ],
+        None => ["Query", "Mutation", "Subscription"]
+            .map(|name| kinds.get_key_value(name).map(|(&k, _)| k)),
+    };
+    let root = |name: Option<&str>| {
+        name.map(|name| json!({"name": name})).unwrap_or(Json::Null)
+    };
+
+    let mut types = Vec::new();
+    for def in &doc.definitions {
+        if let Definition::TypeDefinition(ref t) = *def {
+            types.push(type_json(t, &doc, &kinds)?);
+        }
+    }
+    for name in BUILTIN_SCALARS {
+        if !kinds.contains_key(name) {
+            types.push(json!({
+                "kind": "SCALAR", "name": name, "description": null,
+                "specifiedByURL": null, "fields": null, "inputFields": null,
+                "interfaces": null, "enumValues": null, "possibleTypes": null,
+            }));
+        }
+    }
+
+    let mut directives = Vec::new();
+    let mut defined = Vec::new();
+    for def in &doc.definitions {
+        if let Definition::DirectiveDefinition(ref d) = *def {
+            defined.push(d.name.as_ref());
+            directives.push(directive_json(d, &kinds)?);
+        }
+    }
+    let builtins = crate::parse_schema::<&str>(BUILTIN_DIRECTIVE_DEFINITIONS)
+        .expect("valid builtin directives");
+    for def in &builtins.definitions {
+        if let Definition::DirectiveDefinition(ref d) = *def {
+            if !defined.contains(&d.name) {
+                directives.push(directive_json(d, &kinds)?);
+            }
+        }
+    }
+    Ok(json!({"__schema": {
+        "description": null,
+        "queryType": root(roots[0]),
+        "mutationType": root(roots[1]),
+        "subscriptionType": root(roots[2]),
+        "types": types,
+        "directives": directives,
+    }}))
+}
+
+fn type_kind<'a, T>(def: &TypeDefinition<'a, T>) -> &'static str
+    where T: Text<'a>,
+{
+    match *def {
+        TypeDefinition::Scalar(_) => "SCALAR",
+        TypeDefinition::Object(_) => "OBJECT",
+        TypeDefinition::Interface(_) => "INTERFACE",
+        TypeDefinition::Union(_) => "UNION",
+        TypeDefinition::Enum(_) => "ENUM",
+        TypeDefinition::InputObject(_) => "INPUT_OBJECT",
+    }
+}
+
+fn type_json<'a, T>(def: &TypeDefinition<'a, T>, doc: &Document<'a, T>,
+    kinds: &BTreeMap<&str, &'static str>)
+    -> Result<Json>
+    where T: Text<'a>,
+{
+    let name = def.name().as_ref();
+    let mut typ = json!({
+        "kind": type_kind(def), "name": name, "description": null,
+        "specifiedByURL": null, "fields": null, "inputFields": null,
+        "interfaces": null, "enumValues": null, "possibleTypes": null,
+    });
+    let named = |names: &[T::Value]| names.iter()
+        .map(|n| named_type_ref(n.as_ref(), name, kinds))
+        .collect::<Result<Vec<_>>>();
+    match *def {
+        TypeDefinition::Scalar(ref t) => {
+            typ["description"] = json!(t.description.as_deref());
+            typ["specifiedByURL"] = json!(
+                argument(&t.directives, "specifiedBy", "url"));
+        }
+        TypeDefinition::Object(ref t) => {
+            typ["description"] = json!(t.description.as_deref());
+            typ["fields"] = fields_json(&t.fields, name, kinds)?;
+            typ["interfaces"] = json!(named(&t.implements_interfaces)?);
+        }
+        TypeDefinition::Interface(ref t) => {
+            typ["description"] = json!(t.description.as_deref());
+            typ["fields"] = fields_json(&t.fields, name, kinds)?;
+            typ["interfaces"] = json!(named(&t.implements_interfaces)?);
+            let implementations = doc.definitions.iter()
+                .filter_map(|def| match *def {
+                    Definition::TypeDefinition(TypeDefinition::Object(ref o))
+                    if o.implements_interfaces.iter()
+                        .any(|i| i.as_ref() == name)
+                    => Some(o.name.clone()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            typ["possibleTypes"] = json!(named(&implementations)?);
+        }
+        TypeDefinition::Union(ref t) => {
+            typ["description"] = json!(t.description.as_deref());
+            typ["possibleTypes"] = json!(named(&t.types)?);
+        }
+        TypeDefinition::Enum(ref t) => {
+            typ["description"] = json!(t.description.as_deref());
+            typ["enumValues"] = t.values.iter().map(|val| {
+                let mut value = json!({
+                    "name": val.name.as_ref(),
+                    "description": val.description.as_deref(),
+                });
+                add_deprecation(&mut value, val.deprecation());
+                value
+            }).collect();
+        }
+        TypeDefinition::InputObject(ref t) => {
+            typ["description"] = json!(t.description.as_deref());
+            typ["inputFields"] = input_values_json(&t.fields, name, kinds)?;
+        }
+    }
+    Ok(typ)
+}
+
+fn fields_json<'a, T>(fields: &[Field<'a, T>], type_name: &str,
+    kinds: &BTreeMap<&str, &'static str>)
+    -> Result<Json>
+    where T: Text<'a>,
+{
+    fields.iter().map(|field| {
+        let path = format!("{}.{}", type_name, field.name.as_ref());
+        let mut value = json!({
+            "name": field.name.as_ref(),
+            "description": field.description.as_deref(),
+            "args": input_values_json(&field.arguments, &path, kinds)?,
+            "type": type_ref_json(&field.field_type, &path, kinds)?,
+        });
+        add_deprecation(&mut value, field.deprecation());
+        Ok(value)
+    }).collect()
+}
+
+fn input_values_json<'a, T>(values: &[InputValue<'a, T>], path: &str,
+    kinds: &BTreeMap<&str, &'static str>)
+    -> Result<Json>
+    where T: Text<'a>,
+{
+    values.iter().map(|input| {
+        let path = format!("{}({}:)", path, input.name.as_ref());
+        let mut value = json!({
+            "name": input.name.as_ref(),
+            "description": input.description.as_deref(),
+            "type": type_ref_json(&input.value_type, &path, kinds)?,
+            "defaultValue": input.default_value.as_ref()
+                .map(|v| v.to_string()),
+        });
+        add_deprecation(&mut value, input.deprecation());
+        Ok(value)
+    }).collect()
+}
+
+fn directive_json<'a, T>(def: &DirectiveDefinition<'a, T>,
+    kinds: &BTreeMap<&str, &'static str>)
+    -> Result<Json>
+    where T: Text<'a>,
+{
+    let path = format!("@{}", def.name.as_ref());
+    Ok(json!({
+        "name": def.name.as_ref(),
+        "description": def.description.as_deref(),
+        "isRepeatable": def.repeatable,
+        "locations": def.locations.iter()
+            .map(|loc| loc.as_str()).collect::<Vec<_>>(),
+        "args": input_values_json(&def.arguments, &path, kinds)?,
+    }))
+}
+
+fn type_ref_json<'a, T>(typ: &Type<'a, T>, path: &str,
+    kinds: &BTreeMap<&str, &'static str>)
+    -> Result<Json>
+    where T: Text<'a>,
+{
+    match *typ {
+        Type::NamedType(ref name) => named_type_ref(name.as_ref(), path, kinds),
+        Type::ListType(ref inner) => Ok(json!({
+            "kind": "LIST", "name": null,
+            "ofType": type_ref_json(inner, path, kinds)?,
+        })),
+        Type::NonNullType(ref inner) => Ok(json!({
+            "kind": "NON_NULL", "name": null,
+            "ofType": type_ref_json(inner, path, kinds)?,
+        })),
+    }
+}
+
+fn named_type_ref(name: &str, path: &str,
+    kinds: &BTreeMap<&str, &'static str>)
+    -> Result<Json>
+{
+    let kind = match kinds.get(name) {
+        Some(kind) => kind,
+        None if BUILTIN_SCALARS.contains(&name) => "SCALAR",
+        None => return Err(IntrospectionError(format!(
+            "unknown type {} of {}", name, path))),
+    };
+    Ok(json!({"kind": kind, "name": name, "ofType": null}))
+}
+
+fn add_deprecation(value: &mut Json, deprecation: Option<Deprecation>) {
+    value["isDeprecated"] = json!(deprecation.is_some());
+    value["deprecationReason"] = json!(deprecation.and_then(|d| d.reason));
+}
+
+/// Returns the string argument of the directive
+fn argument<'x, 'a, T>(directives: &'x [Directive<'a, T>], name: &str,
+    argument: &str)
+    -> Option<&'x str>
+    where T: Text<'a>,
+{
+    directives.iter()
+        .filter(|d| d.name.as_ref() == name)
+        .flat_map(|d| &d.arguments)
+        .find(|a| a.name.as_ref() == argument)
+        .and_then(|a| match a.value {
+            Value::String(ref s) => Some(s.as_ref()),
+            _ => None,
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse_schema;
+    use super::{from_introspection, from_introspection_json};
+    use super::to_introspection;
+
+    const INTROSPECTION: &str = r#"{"data": {"__schema": {
+        "queryType": {"name": "Root"},
+        "mutationType": null,
+        "subscriptionType": null,
+        "types": [
+            {"kind": "OBJECT", "name": "Root", "description": "Entry",
+             "interfaces": [], "fields": [
+                {"name": "users", "args": [
+                    {"name": "first", "defaultValue": "10",
+                     "type": {"kind": "SCALAR", "name": "Int"}},
+                    {"name": "filter", "defaultValue": "{role: ADMIN}",
+                     "type": {"kind": "INPUT_OBJECT", "name": "Filter"}}
+                 ],
+                 "type": {"kind": "NON_NULL", "ofType": {"kind": "LIST",
+                          "ofType": {"kind": "OBJECT", "name": "User"}}}},
+                {"name": "old", "args": [], "isDeprecated": true,
+                 "deprecationReason": "Use users",
+                 "type": {"kind": "SCALAR", "name": "String"}}
+             ]},
+            {"kind": "OBJECT", "name": "User", "interfaces": [
+                {"kind": "INTERFACE", "name": "Node"}],
+             "fields": [{"name": "id", "args": [],
+                         "type": {"kind": "SCALAR", "name": "ID"}}]},
+            {"kind": "INTERFACE", "name": "Node", "interfaces": [],
+             "fields": [{"name": "id", "args": [],
+                         "type": {"kind": "SCALAR", "name": "ID"}}]},
+            {"kind": "INPUT_OBJECT", "name": "Filter", "inputFields": [
+                {"name": "role", "type": {"kind": "ENUM", "name": "Role"}}]},
+            {"kind": "ENUM", "name": "Role", "enumValues": [
+                {"name": "ADMIN"},
+                {"name": "GUEST", "isDeprecated": true,
+                 "deprecationReason": "No longer supported"}]},
+            {"kind": "UNION", "name": "Search",
+             "possibleTypes": [{"kind": "OBJECT", "name": "User"}]},
+            {"kind": "SCALAR", "name": "Date",
+             "specifiedByURL": "https://example.com/date"},
+            {"kind": "SCALAR", "name": "String"},
+            {"kind": "OBJECT", "name": "__Type", "fields": []}
+        ],
+        "directives": [
+            {"name": "skip", "locations": ["FIELD"], "args": []},
+            {"name": "tag", "locations": ["FIELD", "QUERY"],
+             "isRepeatable": true, "args": [
+                {"name": "name", "type": {"kind": "NON_NULL",
+                 "ofType": {"kind": "SCALAR", "name": "String"}}}]}
+        ]
+    }}}"#;
+
+    #[test]
+    fn convert() {
+        let doc = from_introspection(INTROSPECTION).unwrap();
+        assert_eq!(doc.to_string(), r#"schema {
+  query: Root
+}
+
+directive @tag(name: String!) repeatable on FIELD | QUERY
+
+"Entry"
+type Root {
+  users(first: Int = 10, filter: Filter = {role: ADMIN}): [User]!
+  old: String @deprecated(reason: "Use users")
+}
+
+type User implements Node {
+  id: ID
+}
+
+interface Node {
+  id: ID
+}
+
+input Filter {
+  role: Role
+}
+
+enum Role {
+  ADMIN
+  GUEST @deprecated
+}
+
+union Search = User
+
+scalar Date @specifiedBy(url: "https://example.com/date")
+"#);
+    }
+
+    #[test]
+    fn parsed_json() {
+        let json = serde_json::json!({"response": {"data": {"__schema": {
+            "types": [{"kind": "ENUM", "name": "E", "description": "Kind",
+                       "enumValues": [{"name": "A"}]}],
+        }}}});
+        assert_eq!(from_introspection_json(&json["response"]).unwrap()
+                   .to_string(), "\"Kind\"\nenum E {\n  A\n}\n");
+    }
+
+    #[test]
+    fn to_json_and_back() {
+        let sdl = r#"schema {
+  query: Root
+}
+
+directive @tag(name: String! = "x") repeatable on FIELD | QUERY
+
+"Entry"
+type Root {
+  users(first: Int = 10, filter: Filter = {role: ADMIN}): [User!]!
+  old: String @deprecated(reason: "Use users")
+}
+
+type User implements Node {
+  id: ID
+}
+
+interface Node {
+  id: ID
+}
+
+input Filter {
+  role: Role
+}
+
+enum Role {
+  ADMIN
+  GUEST @deprecated
+}
+
+union Search = User
+
+scalar Date @specifiedBy(url: "https://example.com/date")
+"#;
+        let json = to_introspection(&parse_schema::<&str>(sdl).unwrap())
+            .unwrap();
+        let schema = &json["__schema"];
+        assert_eq!(schema["mutationType"], serde_json::Value::Null);
+        assert_eq!(schema["types"][2]["possibleTypes"][0]["name"], "User");
+        assert_eq!(schema["types"][4]["enumValues"][1]["deprecationReason"],
+                   "No longer supported");
+        assert_eq!(schema["types"][0]["fields"][0]["args"][1]["defaultValue"],
+                   "{role: ADMIN}");
+        assert_eq!(schema["directives"].as_array().unwrap().len(), 5);
+        assert_eq!(from_introspection_json(&json).unwrap().to_string(), sdl);
+
+        let err = to_introspection(&parse_schema::<&str>("
+            type Query { a(x: Missing): Int }
+        ").unwrap()).unwrap_err();
+        assert_eq!(err.to_string(),
+                   "introspection error: unknown type Missing of Query.a(x:)");
+    }
+
+    #[test]
+    fn deprecations() {
+        let json = to_introspection(&parse_schema::<&str>(r#"
+            type Query {
+                a(x: Int @deprecated(reason: "Use y"), y: Int): Int
+                b: Int @deprecated(reason: null)
+            }
+        "#).unwrap()).unwrap();
+        let fields = &json["__schema"]["types"][0]["fields"];
+        assert_eq!(fields[0]["isDeprecated"], false);
+        assert_eq!(fields[0]["deprecationReason"], serde_json::Value::Null);
+        assert_eq!(fields[0]["args"][0]["deprecationReason"], "Use y");
+        assert_eq!(fields[0]["args"][1]["isDeprecated"], false);
+        assert_eq!(fields[1]["isDeprecated"], true);
+        assert_eq!(fields[1]["deprecationReason"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(from_introspection("{").unwrap_err().to_string(),
+            "introspection error: EOF while parsing an object \
+             at line 1 column 1");
+        assert_eq!(from_introspection(r#"{"data": {}}"#).unwrap_err()
+            .to_string(), "introspection error: no `__schema` object");
+        let err = from_introspection(r#"{"__schema": {"types": [
+            {"kind": "OBJECT", "name": "Q", "fields": [
+                {"name": "a", "args": [{"name": "x", "defaultValue": "{",
+                 "type": {"kind": "SCALAR", "name": "Int"}}],
+                 "type": {"kind": "SCALAR", "name": "Int"}}]}
+        ]}}"#).unwrap_err();
+        assert_eq!(err.to_string(),
+            "introspection error: invalid default value \"{\" of Q.a(x:)");
+    }
+}
@@ -0,0 +1,529 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use thiserror::Error;
+
+use crate::common::Directive;
+use crate::position::{Location, Pos};
+use crate::schema::ast::*;
+
+
+/// Position in one of the documents passed to `merge_schemas`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DocumentPos {
+    /// Index of the document in the slice
+    pub document: usize,
+    pub position: Pos,
+}
+
+/// Error merging schema documents
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum MergeError {
+    /// Element is defined twice with different definitions
+    ///
+    /// Name is either the name of the type, a schema coordinate of the
+    /// member added by an extension (`User.name`), `@directive` or `schema`.
+    #[error("{name} is defined at {first} and at {second}")]
+    Conflict {
+        name: String,
+        first: DocumentPos,
+        second: DocumentPos,
+    },
+    /// Extension of a type or schema that isn't defined in any document
+    #[error("{extension}: extension of undefined {name}")]
+    Undefined {
+        name: String,
+        extension: DocumentPos,
+    },
+    /// Extension is of a different kind than the type definition,
+    /// e.g. `extend input` of an object type
+    #[error("{extension}: extension of {name} doesn't match the kind \
+             of definition at {definition}")]
+    KindMismatch {
+        name: String,
+        definition: DocumentPos,
+        extension: DocumentPos,
+    },
+}
+
+impl DocumentPos {
+    /// Returns the location in the named document, where `names` are the
+    /// names of the documents passed to `merge_schemas`, in the same order
+    pub fn location<S: AsRef<str>>(&self, names: &[S]) -> Option<Location> {
+        names.get(self.document).map(|name| Location {
+            source: name.as_ref().to_string(),
+            position: self.position,
+        })
+    }
+
+    fn describe<S: AsRef<str>>(&self, names: &[S]) -> String {
+        match self.location(names) {
+            Some(location) => location.to_string(),
+            None => self.to_string(),
+        }
+    }
+}
+
+impl MergeError {
+    /// Returns the message with the positions in the named documents, like
+    /// `User is defined at a.graphql:1:1 and at b.graphql:3:5`
+    ///
+    /// ```rust
+    /// # extern crate edb_graphql_parser;
+    /// use edb_graphql_parser::parse_schema;
+    /// use edb_graphql_parser::schema::merge_schemas;
+    ///
+    /// let names = ["user.graphql", "extra.graphql"];
+    /// let docs = vec![
+    ///     parse_schema::<&str>("type User { id: ID }").unwrap(),
+    ///     parse_schema::<&str>("\n  type User { id: String }").unwrap(),
+    /// ];
+    /// let err = merge_schemas(&docs).unwrap_err();
+    /// assert_eq!(err.describe(&names), "User is defined at \
+    ///     user.graphql:1:1 and at extra.graphql:2:3");
+    /// ```
+    pub fn describe<S: AsRef<str>>(&self, names: &[S]) -> String {
+        match *self {
+            MergeError::Conflict { ref name, ref first, ref second } => {
+                format!("{} is defined at {} and at {}", name,
+                        first.describe(names), second.describe(names))
+            }
+            MergeError::Undefined { ref name, ref extension } => {
+                format!("{}: extension of undefined {}",
+                        extension.describe(names), name)
+            }
+            MergeError::KindMismatch {
+                ref name, ref definition, ref extension,
+            } => {
+                format!("{}: extension of {} doesn't match the kind \
+                         of definition at {}", extension.describe(names),
+                        name, definition.describe(names))
+            }
+        }
+    }
+}
+
+impl fmt::Display for DocumentPos {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "document {}, {}", self.document, self.position)
+    }
+}
+
+/// Combines multiple schema documents into one
+///
+/// Definitions from all the documents are collected in order, then type
+/// and schema extensions are applied to them, so an extension may be in
+/// any document. Definitions of the same type or directive that are
+/// identical (except for positions) are only kept once, different ones
+/// are a conflict, as are the members and root operation types added by
+/// extensions which are already defined. Directives applied by extensions
+/// are added unless the same directive with the same arguments is already
+/// applied. Custom definitions are kept as is.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::parse_schema;
+/// use edb_graphql_parser::schema::merge_schemas;
+///
+/// let docs = vec![
+///     parse_schema::<&str>("type User { id: ID }").unwrap(),
+///     parse_schema::<&str>("extend type User @key(fields: \"id\") {
+///         name: String
+///     }").unwrap(),
+/// ];
+/// assert_eq!(merge_schemas(&docs).unwrap().to_string(), "\
+/// type User @key(fields: \"id\") {
+///   id: ID
+///   name: String
+/// }
+/// ");
+/// ```
+pub fn merge_schemas<'a, T>(documents: &[Document<'a, T>])
+    -> Result<Document<'a, T>, MergeError>
+    where T: Text<'a> + Clone,
+{
+    let mut merged = Merged {
+        definitions: Vec::new(),
+        names: BTreeMap::new(),
+    };
+    let mut extensions = Vec::new();
+    for (document, doc) in documents.iter().enumerate() {
+        for def in &doc.definitions {
+            let at = |position| DocumentPos { document, position };
+            match *def {
+                Definition::SchemaDefinition(ref s) => {
+                    merged.add("schema".into(), def, at(s.position))?;
+                }
+                Definition::TypeDefinition(ref t) => {
+                    merged.add(t.name().as_ref().into(), def,
+                               at(type_position(t)))?;
+                }
+                Definition::DirectiveDefinition(ref d) => {
+                    merged.add(format!("@{}", d.name.as_ref()), def,
+                               at(d.position))?;
+                }
+                Definition::SchemaExtension(ref e) => {
+                    extensions.push((def, at(e.position)));
+                }
+                Definition::TypeExtension(ref e) => {
+                    extensions.push((def, at(extension_position(e))));
+                }
+                Definition::Custom(_) => merged.definitions.push(def.clone()),
+            }
+        }
+    }
+    for (ext, at) in extensions {
+        match *ext {
+            Definition::SchemaExtension(ref e) => merged.extend_schema(e, at)?,
+            Definition::TypeExtension(ref e) => merged.extend_type(e, at)?,
+            _ => unreachable!(),
+        }
+    }
+    Ok(Document { definitions: merged.definitions })
+}
+
+/// Applies the type and schema extensions of a document to the types and
+/// the schema they extend
+///
+/// The result is the effective schema: it has no extensions, members,
+/// interfaces and directives added by them are in the definitions. This
+/// is `merge_schemas` of a single document, so the errors are the same,
+/// with `document` being always `0`.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::parse_schema;
+/// use edb_graphql_parser::schema::apply_extensions;
+///
+/// let doc = parse_schema::<&str>("
+///     extend enum Role { ADMIN }
+///     enum Role { USER }
+/// ").unwrap();
+/// assert_eq!(apply_extensions(&doc).unwrap().to_string(), "\
+/// enum Role {
+///   USER
+///   ADMIN
+/// }
+/// ");
+/// ```
+pub fn apply_extensions<'a, T>(document: &Document<'a, T>)
+    -> Result<Document<'a, T>, MergeError>
+    where T: Text<'a> + Clone,
+{
+    merge_schemas(std::slice::from_ref(document))
+}
+
+struct Merged<'a, T: Text<'a>> {
+    definitions: Vec<Definition<'a, T>>,
+    /// Index of the definition and where it's first defined
+    names: BTreeMap<String, (usize, DocumentPos)>,
+}
+
+impl<'a, T> Merged<'a, T>
+    where T: Text<'a> + Clone,
+{
+    fn add(&mut self, name: String, def: &Definition<'a, T>, at: DocumentPos)
+        -> Result<(), MergeError>
+    {
+        if let Some(&(idx, first)) = self.names.get(&name) {
+            if self.definitions[idx].to_string() == def.to_string() {
+                return Ok(());
+            }
+            return Err(MergeError::Conflict { name, first, second: at });
+        }
+        self.names.insert(name, (self.definitions.len(), at));
+        self.definitions.push(def.clone());
+        Ok(())
+    }
+
+    fn get(&mut self, name: &str, extension: DocumentPos)
+        -> Result<(&mut Definition<'a, T>, DocumentPos), MergeError>
+    {
+        match self.names.get(name) {
+            Some(&(idx, at)) => Ok((&mut self.definitions[idx], at)),
+            None => Err(MergeError::Undefined {
+                name: name.into(),
+                extension,
+            }),
+        }
+    }
+
+    fn extend_schema(&mut self, ext: &SchemaExtension<'a, T>, at: DocumentPos)
+        -> Result<(), MergeError>
+    {
+        let (def, first) = self.get("schema", at)?;
+        let schema = match *def {
+            Definition::SchemaDefinition(ref mut s) => s,
+            _ => unreachable!(),
+        };
+        let operations = [
+            ("schema.query", &mut schema.query, &ext.query),
+            ("schema.mutation", &mut schema.mutation, &ext.mutation),
+            ("schema.subscription", &mut schema.subscription,
+             &ext.subscription),
+        ];
+        for (name, target, value) in operations {
+            match (target.is_some(), value) {
+                (true, Some(_)) => return Err(MergeError::Conflict {
+                    name: name.into(),
+                    first,
+                    second: at,
+                }),
+                (false, Some(value)) => *target = Some(value.clone()),
+                (_, None) => {}
+            }
+        }
+        add_directives(&mut schema.directives, &ext.directives);
+        Ok(())
+    }
+
+    fn extend_type(&mut self, ext: &TypeExtension<'a, T>, at: DocumentPos)
+        -> Result<(), MergeError>
+    {
+        use self::TypeDefinition as D;
+        use self::TypeExtension as E;
+
+        let name = ext.name().as_ref();
+        let (def, first) = self.get(name, at)?;
+        let def = match *def {
+            Definition::TypeDefinition(ref mut def) => def,
+            _ => unreachable!(),
+        };
+        let conflict = |member: &str, position| MergeError::Conflict {
+            name: format!("{}.{}", name, member),
+            first,
+            second: DocumentPos { document: at.document, position },
+        };
+        match (def, ext) {
+            (D::Scalar(d), E::Scalar(e)) => {
+                add_directives(&mut d.directives, &e.directives);
+            }
+            (D::Object(d), E::Object(e)) => {
+                add_names::<T>(&mut d.implements_interfaces,
+                               &e.implements_interfaces);
+                add_directives(&mut d.directives, &e.directives);
+                add_members(&mut d.fields, &e.fields,
+                            |f| (f.name.as_ref(), f.position), conflict)?;
+            }
+            (D::Interface(d), E::Interface(e)) => {
+                add_names::<T>(&mut d.implements_interfaces,
+                               &e.implements_interfaces);
+                add_directives(&mut d.directives, &e.directives);
+                add_members(&mut d.fields, &e.fields,
+                            |f| (f.name.as_ref(), f.position), conflict)?;
+            }
+            (D::Union(d), E::Union(e)) => {
+                add_directives(&mut d.directives, &e.directives);
+                add_names::<T>(&mut d.types, &e.types);
+            }
+            (D::Enum(d), E::Enum(e)) => {
+                add_directives(&mut d.directives, &e.directives);
+                add_members(&mut d.values, &e.values,
+                            |v| (v.name.as_ref(), v.position), conflict)?;
+            }
+            (D::InputObject(d), E::InputObject(e)) => {
+                add_directives(&mut d.directives, &e.directives);
+                add_members(&mut d.fields, &e.fields,
+                            |f| (f.name.as_ref(), f.position), conflict)?;
+            }
+            _ => return Err(MergeError::KindMismatch {
+                name: name.into(),
+                definition: first,
+                extension: at,
+            }),
+        }
+        Ok(())
+    }
+}
+
+fn add_directives<'a, T>(target: &mut Vec<Directive<'a, T>>,
+    directives: &[Directive<'a, T>])
+    where T: Text<'a> + Clone,
+{
+    for dir in directives {
+        let text = dir.to_string();
+        if !target.iter().any(|d| d.to_string() == text) {
+            target.push(dir.clone());
+        }
+    }
+}
+
+fn add_names<'a, T>(target: &mut Vec<T::Value>, names: &[T::Value])
+    where T: Text<'a>,
+{
+    for name in names {
+        if !target.contains(name) {
+            target.push(name.clone());
+        }
+    }
+}
+
+fn add_members<M, K, E>(target: &mut Vec<M>, members: &[M], key: K,
+    conflict: E)
+    -> Result<(), MergeError>
+    where M: Clone,
+          K: Fn(&M) -> (&str, Pos),
+          E: Fn(&str, Pos) -> MergeError,
+{
+    for member in members {
+        let (name, position) = key(member);
+        if target.iter().any(|m| key(m).0 == name) {
+            return Err(conflict(name, position));
+        }
+        target.push(member.clone());
+    }
+    Ok(())
+}
+
+fn type_position<'a, T>(def: &TypeDefinition<'a, T>) -> Pos
+    where T: Text<'a>,
+{
+    match *def {
+        TypeDefinition::Scalar(ref t) => t.position,
+        TypeDefinition::Object(ref t) => t.position,
+        TypeDefinition::Interface(ref t) => t.position,
+        TypeDefinition::Union(ref t) => t.position,
+        TypeDefinition::Enum(ref t) => t.position,
+        TypeDefinition::InputObject(ref t) => t.position,
+    }
+}
+
+fn extension_position<'a, T>(ext: &TypeExtension<'a, T>) -> Pos
+    where T: Text<'a>,
+{
+    match *ext {
+        TypeExtension::Scalar(ref t) => t.position,
+        TypeExtension::Object(ref t) => t.position,
+        TypeExtension::Interface(ref t) => t.position,
+        TypeExtension::Union(ref t) => t.position,
+        TypeExtension::Enum(ref t) => t.position,
+        TypeExtension::InputObject(ref t) => t.position,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse_schema;
+    use super::{apply_extensions, merge_schemas, MergeError};
+
+    fn merge(sources: &[&str]) -> Result<String, MergeError> {
+        let docs = sources.iter()
+            .map(|s| parse_schema::<&str>(s).unwrap())
+            .collect::<Vec<_>>();
+        merge_schemas(&docs).map(|doc| doc.to_string())
+    }
+
+    #[test]
+    fn extensions() {
+        assert_eq!(merge(&[
+            "extend schema @link(url: \"a\") { mutation: M }
+             extend enum Role @tag { USER }
+             extend type Query implements Node @tag",
+            "schema @link(url: \"a\") { query: Query }
+             type Query @tag { a: Int }
+             enum Role { ADMIN }
+             interface Node { id: ID }",
+            "interface Node { id: ID }
+             extend union U = B | C
+             union U = A | B
+             extend scalar S @a
+             scalar S",
+        ]).unwrap(), "\
+schema @link(url: \"a\") {
+  query: Query
+  mutation: M
+}
+
+type Query implements Node @tag {
+  a: Int
+}
+
+enum Role @tag {
+  ADMIN
+  USER
+}
+
+interface Node {
+  id: ID
+}
+
+union U = A | B | C
+
+scalar S @a
+");
+    }
+
+    #[test]
+    fn errors() {
+        let err = |sources: &[&str]| merge(sources).unwrap_err().to_string();
+        assert_eq!(err(&["type A { a: Int }", "\n type A { a: String }"]),
+                   "A is defined at document 0, 1:1 and at document 1, 2:2");
+        assert_eq!(err(&["type A { a: Int }", "extend type A { a: Int }"]),
+                   "A.a is defined at document 0, 1:1 \
+                    and at document 1, 1:17");
+        assert_eq!(err(&["extend input A { a: Int }"]),
+                   "document 0, 1:8: extension of undefined A");
+        assert_eq!(err(&["extend schema { query: Q }"]),
+                   "document 0, 1:8: extension of undefined schema");
+        assert_eq!(err(&["schema { query: Q }", "extend schema { query: R }"]),
+                   "schema.query is defined at document 0, 1:1 \
+                    and at document 1, 1:8");
+        assert_eq!(err(&["type A", "extend input A { a: Int }"]),
+                   "document 1, 1:8: extension of A doesn't match \
+                    the kind of definition at document 0, 1:1");
+        assert_eq!(err(&["directive @a on FIELD",
+                         "directive @a on FIELD | QUERY"]),
+                   "@a is defined at document 0, 1:1 \
+                    and at document 1, 1:1");
+        assert!(merge(&["directive @a on FIELD", "directive @a on FIELD"])
+                .is_ok());
+    }
+
+    #[test]
+    fn named_errors() {
+        let names = ["a.graphql"];
+        let err = |sources: &[&str]| {
+            let docs = sources.iter()
+                .map(|s| parse_schema::<&str>(s).unwrap())
+                .collect::<Vec<_>>();
+            merge_schemas(&docs).unwrap_err().describe(&names)
+        };
+        assert_eq!(err(&["type A", "extend input A { a: Int }"]),
+                   "document 1, 1:8: extension of A doesn't match \
+                    the kind of definition at a.graphql:1:1");
+        assert_eq!(err(&["extend union U = A"]),
+                   "a.graphql:1:8: extension of undefined U");
+    }
+
+    #[test]
+    fn apply() {
+        let doc = parse_schema::<&str>("
+            type Query { a: Int }
+            extend type Query implements Node @tag { id: ID }
+            interface Node { id: ID }
+            extend schema { query: Query }
+            schema @link(url: \"a\") { mutation: Query }
+        ").unwrap();
+        assert_eq!(apply_extensions(&doc).unwrap().to_string(), "\
+type Query implements Node @tag {
+  a: Int
+  id: ID
+}
+
+interface Node {
+  id: ID
+}
+
+schema @link(url: \"a\") {
+  query: Query
+  mutation: Query
+}
+");
+        let doc = parse_schema::<&str>("type A { a: Int }\n\
+                                        extend type A { a: String }\n\
+                                        extend type B @tag").unwrap();
+        assert_eq!(apply_extensions(&doc).unwrap_err().to_string(),
+                   "A.a is defined at document 0, 1:1 \
+                    and at document 0, 2:17");
+    }
+}
@@ -1,10 +1,35 @@
 //! Schema definition language AST and utility
 //!
 mod ast;
+mod coordinate;
 mod grammar;
 mod error;
 mod format;
+mod merge;
+mod prune;
+mod split;
+#[cfg(feature = "introspection")]
+mod introspection;
+pub mod index;
 
 pub use self::ast::*;
+pub use self::coordinate::{SchemaCoordinate, CoordinateTarget};
+pub use self::coordinate::InvalidSchemaCoordinate;
 pub use self::error::ParseError;
-pub use self::grammar::parse_schema;
+pub use self::format::format_range;
+#[cfg(feature = "apq")]
+pub use self::format::schema_hash;
+pub use self::index::{SchemaIndex, TypeEntry, TypeKind};
+pub use self::grammar::{parse_schema, parse_schema_with_options};
+pub use self::grammar::parse_schema_with_offset;
+pub use self::grammar::{parse_schema_iter, parse_schema_iter_with_options};
+pub use self::grammar::SchemaDefinitions;
+pub use self::merge::{merge_schemas, MergeError, DocumentPos};
+pub use self::merge::apply_extensions;
+pub use self::prune::SchemaPruning;
+pub use self::split::{split_schema, SchemaSplitter};
+pub use self::split::{DefinitionSource, DefinitionKind};
+#[cfg(feature = "introspection")]
+pub use self::introspection::{from_introspection, from_introspection_json};
+#[cfg(feature = "introspection")]
+pub use self::introspection::{to_introspection, IntrospectionError};
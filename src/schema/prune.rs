@@ -0,0 +1,189 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::common::Text;
+use crate::schema::ast::*;
+use crate::schema::index::{SchemaIndex, TypeKind};
+
+
+/// Removes the types not reachable from the root operation types
+///
+/// Types are reachable through the fields, their arguments, interfaces,
+/// union members and input fields. Objects and interfaces implementing a
+/// reachable interface are reachable too, as they can be returned by its
+/// fields. Types used by the arguments of directive definitions are kept,
+/// as are the types having one of the directives set by
+/// `keep_types_with` (on the definition or on an extension), with
+/// everything reachable from them.
+///
+/// Extensions of the removed types are removed too, all the other
+/// definitions are kept.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::parse_schema;
+/// use edb_graphql_parser::schema::SchemaPruning;
+///
+/// let doc = parse_schema::<&str>("
+///     type Query { me: User }
+///     type User { id: ID! }
+///     type Unused { id: ID! }
+///     type Product @key(fields: \"id\") { id: ID! }
+///     directive @key(fields: String!) on OBJECT
+/// ").unwrap();
+/// let pruned = SchemaPruning::default().keep_types_with("key").apply(&doc);
+/// assert_eq!(pruned.to_string(), "\
+/// type Query {
+///   me: User
+/// }
+///
+/// type User {
+///   id: ID!
+/// }
+///
+/// type Product @key(fields: \"id\") {
+///   id: ID!
+/// }
+///
+/// directive @key(fields: String!) on OBJECT
+/// ");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SchemaPruning {
+    directives: BTreeSet<String>,
+}
+
+impl SchemaPruning {
+    /// Keep the types having the directive, e.g. federation entities
+    /// marked with `@key`, which are only reachable through `_entities`
+    pub fn keep_types_with(&mut self, directive: &str) -> &mut Self {
+        self.directives.insert(directive.into());
+        self
+    }
+
+    /// Returns the names of the types that are kept, including the used
+    /// built-in scalars
+    pub fn reachable<'x, 'a, T>(&self, doc: &'x Document<'a, T>)
+        -> BTreeSet<&'x str>
+        where T: Text<'a>,
+    {
+        let index = SchemaIndex::new(doc);
+        let mut implementations = BTreeMap::new();
+        for (name, entry) in index.types() {
+            for &iface in &entry.interfaces {
+                implementations.entry(iface).or_insert_with(Vec::new)
+                    .push(name);
+            }
+        }
+        let mut queue = Vec::new();
+        queue.extend(index.query_type());
+        queue.extend(index.mutation_type());
+        queue.extend(index.subscription_type());
+        let directive = |dirs: &[Directive<'a, T>]| dirs.iter()
+            .any(|d| self.directives.contains(d.name.as_ref()));
+        for def in &doc.definitions {
+            match *def {
+                Definition::DirectiveDefinition(ref dir) => {
+                    queue.extend(dir.arguments.iter()
+                        .map(|arg| arg.value_type.innermost_name()));
+                }
+                Definition::TypeDefinition(ref typ)
+                    if directive(typ.directives())
+                => queue.push(typ.name().as_ref()),
+                Definition::TypeExtension(ref ext)
+                    if directive(ext.directives())
+                => queue.push(ext.name().as_ref()),
+                _ => {}
+            }
+        }
+        let mut reachable = BTreeSet::new();
+        while let Some(name) = queue.pop() {
+            if !reachable.insert(name) {
+                continue;
+            }
+            let entry = match index.type_by_name(name) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            for field in &entry.fields {
+                queue.push(field.field_type.innermost_name());
+                queue.extend(field.arguments.iter()
+                    .map(|arg| arg.value_type.innermost_name()));
+            }
+            queue.extend(entry.input_fields.iter()
+                .map(|field| field.value_type.innermost_name()));
+            queue.extend(&entry.members);
+            queue.extend(&entry.interfaces);
+            if entry.kind == TypeKind::Interface {
+                if let Some(names) = implementations.get(name) {
+                    queue.extend(names);
+                }
+            }
+        }
+        reachable
+    }
+
+    /// Returns the document without the unreachable types
+    pub fn apply<'a, T>(&self, doc: &Document<'a, T>) -> Document<'a, T>
+        where T: Text<'a> + Clone,
+    {
+        let reachable = self.reachable(doc);
+        let definitions = doc.definitions.iter().filter(|def| match **def {
+            Definition::TypeDefinition(ref typ) => {
+                reachable.contains(typ.name().as_ref())
+            }
+            Definition::TypeExtension(ref ext) => {
+                reachable.contains(ext.name().as_ref())
+            }
+            _ => true,
+        }).cloned().collect();
+        Document { definitions }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse_schema;
+    use super::SchemaPruning;
+
+    #[test]
+    fn reachable() {
+        let doc = parse_schema::<&str>("
+            schema { query: Root }
+            type Root { node(id: ID!, filter: Filter): Node, search: Result }
+            interface Node { id: ID! }
+            interface Named implements Node { id: ID! name: String }
+            type User implements Named & Node { id: ID! name: String }
+            extend type User { avatar: Image }
+            type Image { url: Url }
+            scalar Url
+            union Result = Post
+            type Post { id: ID! }
+            input Filter { tag: Tag, nested: [Filter!] }
+            enum Tag { A }
+            type Query { unused: Unused }
+            type Unused { x: Int }
+            extend type Unused { y: Int }
+            scalar Extra @specifiedBy(url: \"x\")
+            directive @deprecated(reason: Reason) on FIELD_DEFINITION
+            scalar Reason
+            type Entity @key { x: Int }
+            type Other { x: Int }
+            extend type Other @key
+        ").unwrap();
+        let pruning = SchemaPruning::default();
+        let reachable = pruning.reachable(&doc).into_iter()
+            .collect::<Vec<_>>();
+        assert_eq!(reachable, [
+            "Filter", "ID", "Image", "Named", "Node", "Post",
+            "Reason", "Result", "Root", "String", "Tag", "Url", "User",
+        ]);
+        let pruned = pruning.apply(&doc);
+        assert_eq!(pruned.definitions.len(), 14);
+        let pruned = SchemaPruning::default().keep_types_with("key")
+            .apply(&doc);
+        assert_eq!(pruned.definitions.len(), 17);
+        assert!(pruned.to_string().contains("type Entity @key {"));
+        assert!(pruned.to_string().contains("extend type Other @key\n"));
+        assert!(!pruned.to_string().contains("Unused"));
+    }
+}
@@ -0,0 +1,355 @@
+use combine::{Positioned, StreamOnce};
+use combine::easy::Error;
+
+use crate::common::Text;
+use crate::options::ParserOptions;
+use crate::position::{width, Pos};
+use crate::schema::ast::Document;
+use crate::schema::error::ParseError;
+use crate::schema::grammar::parse_schema_with_options;
+use crate::schema::index::TypeKind;
+use crate::tokenizer::{Kind, Token, TokenStream};
+
+
+/// Keywords starting the standard definitions
+const KEYWORDS: &[&str] = &[
+    "schema", "scalar", "type", "interface", "union", "enum", "input",
+    "directive", "extend",
+];
+
+/// Tokens after which a keyword is a name rather than a new definition
+const NAME_CONTEXTS: &[&str] = &["=", "|", "&", "@", "implements", "on"];
+
+/// Token with its position and byte offset
+type Located<'a> = (Token<'a>, Pos, usize);
+
+/// Kind of the definition found by `split_schema`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefinitionKind {
+    Schema,
+    Type(TypeKind),
+    Directive,
+    /// Definition starting with a keyword that is not in the specification,
+    /// see `ParserOptions::custom_definition`
+    Custom,
+}
+
+/// Top-level definition found by `split_schema`, not parsed yet
+#[derive(Debug, Clone, PartialEq)]
+pub struct DefinitionSource<'a> {
+    pub kind: DefinitionKind,
+    /// Whether this is an extension, e.g. `extend type`
+    pub extension: bool,
+    /// Name of the type or directive, `None` for the schema and custom
+    /// definitions
+    pub name: Option<&'a str>,
+    /// Start of the definition (of the description if there is one) and
+    /// the end of its last token
+    pub span: (Pos, Pos),
+    /// Original text of the definition
+    pub text: &'a str,
+}
+
+impl<'a> DefinitionSource<'a> {
+    /// Parses the text of the definition
+    ///
+    /// Positions in the document are the same as for `parse_schema` of the
+    /// whole source, except that token indices are counted from the start
+    /// of the definition.
+    pub fn parse<T>(&self) -> Result<Document<'a, T>, ParseError>
+        where T: Text<'a>,
+    {
+        self.parse_with_options(&ParserOptions::default())
+    }
+
+    /// Parses the text of the definition using specified options
+    ///
+    /// The start position of the options is replaced by the start of the
+    /// definition.
+    pub fn parse_with_options<T>(&self, options: &ParserOptions)
+        -> Result<Document<'a, T>, ParseError>
+        where T: Text<'a>,
+    {
+        let mut options = options.clone();
+        options.start_position(Pos { token: 0, .. self.span.0 });
+        parse_schema_with_options(self.text, &options)
+    }
+}
+
+/// Iterator over the definitions of a schema, see `split_schema`
+#[derive(Debug)]
+pub struct SchemaSplitter<'a> {
+    source: &'a str,
+    tokens: TokenStream<'a>,
+    peeked: Option<Located<'a>>,
+    failed: bool,
+}
+
+/// Splits the schema into top-level definitions without parsing them
+///
+/// Only the tokens are scanned and nothing but the current definition is
+/// kept in memory, so this is a cheap way to locate a few definitions in
+/// a very large schema, and to parse just them with
+/// `DefinitionSource::parse`. Definitions are only checked to start with
+/// a keyword and a name, the rest of their syntax is checked when they are
+/// parsed. Custom definitions are only found at the start of the source
+/// or after a description, otherwise they are a part of the previous
+/// definition.
+///
+/// The iterator stops after the first error, e.g. an invalid token.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::schema::{split_schema, DefinitionKind, TypeKind};
+///
+/// let source = "
+///     type Query { user: User }
+///     \"A user\" type User { name: String }
+///     union Node = User | Query
+///     extend type Query { node: Node }
+/// ";
+/// let definitions = split_schema(source).collect::<Result<Vec<_>, _>>()
+///     .unwrap();
+/// let user = &definitions[1];
+/// assert_eq!(user.name, Some("User"));
+/// assert_eq!(user.kind, DefinitionKind::Type(TypeKind::Object));
+/// assert_eq!(user.text, "\"A user\" type User { name: String }");
+/// assert_eq!(user.span.0.to_string(), "3:5");
+/// assert!(definitions[3].extension);
+/// let doc = user.parse::<&str>().unwrap();
+/// assert_eq!(doc.to_string(),
+///            "\"A user\"\ntype User {\n  name: String\n}\n");
+/// ```
+pub fn split_schema(source: &str) -> SchemaSplitter<'_> {
+    SchemaSplitter {
+        source,
+        tokens: TokenStream::new(source),
+        peeked: None,
+        failed: false,
+    }
+}
+
+impl<'a> SchemaSplitter<'a> {
+    /// Returns the next token, or the byte offset and position of the
+    /// invalid one
+    fn peek(&mut self) -> Result<Option<Located<'a>>, (usize, Pos)> {
+        if self.peeked.is_none() {
+            let pos = self.tokens.position();
+            let offset = self.tokens.offset();
+            match self.tokens.uncons() {
+                Ok(tok) => self.peeked = Some((tok, pos, offset)),
+                Err(ref e) if *e == Error::end_of_input() => {}
+                Err(_) => return Err((offset, pos)),
+            }
+        }
+        Ok(self.peeked)
+    }
+
+    /// Consumes the next token, which must exist
+    fn bump(&mut self) -> Result<Located<'a>, (usize, Pos)> {
+        match self.peek()? {
+            Some(tok) => {
+                self.peeked = None;
+                Ok(tok)
+            }
+            None => Err((self.source.len(), self.tokens.position())),
+        }
+    }
+
+    /// Returns the next definition, or the byte offset and position of
+    /// the start of the invalid one
+    fn definition(&mut self)
+        -> Result<Option<DefinitionSource<'a>>, (usize, Pos)>
+    {
+        let (_, start, start_offset) = match self.peek()? {
+            Some(tok) => tok,
+            None => return Ok(None),
+        };
+        self.scan(start, start_offset)
+            .map(Some)
+            .map_err(|_| (start_offset, start))
+    }
+
+    fn scan(&mut self, start: Pos, start_offset: usize)
+        -> Result<DefinitionSource<'a>, (usize, Pos)>
+    {
+        let invalid = Err((start_offset, start));
+        let mut tok = self.bump()?;
+        if matches!(tok.0.kind, Kind::StringValue | Kind::BlockString) {
+            tok = self.bump()?;
+        }
+        let extension = tok.0.kind == Kind::Name && tok.0.value == "extend";
+        if extension {
+            tok = self.bump()?;
+        }
+        if tok.0.kind != Kind::Name {
+            return invalid;
+        }
+        let kind = match tok.0.value {
+            "schema" => DefinitionKind::Schema,
+            "scalar" => DefinitionKind::Type(TypeKind::Scalar),
+            "type" => DefinitionKind::Type(TypeKind::Object),
+            "interface" => DefinitionKind::Type(TypeKind::Interface),
+            "union" => DefinitionKind::Type(TypeKind::Union),
+            "enum" => DefinitionKind::Type(TypeKind::Enum),
+            "input" => DefinitionKind::Type(TypeKind::InputObject),
+            "directive" => DefinitionKind::Directive,
+            _ => DefinitionKind::Custom,
+        };
+        let name = match kind {
+            DefinitionKind::Schema | DefinitionKind::Custom => None,
+            DefinitionKind::Type(_) | DefinitionKind::Directive => {
+                if kind == DefinitionKind::Directive {
+                    tok = self.bump()?;
+                    if tok.0.kind != Kind::Punctuator || tok.0.value != "@" {
+                        return invalid;
+                    }
+                }
+                tok = self.bump()?;
+                if tok.0.kind != Kind::Name {
+                    return invalid;
+                }
+                Some(tok.0.value)
+            }
+        };
+        let mut depth = 0usize;
+        let mut last = tok;
+        while let Some(next) = self.peek()? {
+            if depth == 0 && starts_definition(next.0, last.0) {
+                break;
+            }
+            match next.0.value {
+                "{" | "(" | "[" if next.0.kind == Kind::Punctuator => {
+                    depth += 1;
+                }
+                "}" | ")" | "]" if next.0.kind == Kind::Punctuator => {
+                    depth = depth.saturating_sub(1);
+                }
+                _ => {}
+            }
+            last = self.bump()?;
+        }
+        let (last_token, last_pos, last_offset) = last;
+        let end_offset = last_offset + last_token.value.len();
+        Ok(DefinitionSource {
+            kind,
+            extension,
+            name,
+            span: (start, end_of(last_pos, last_token.value)),
+            text: &self.source[start_offset..end_offset],
+        })
+    }
+}
+
+impl<'a> Iterator for SchemaSplitter<'a> {
+    type Item = Result<DefinitionSource<'a>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        match self.definition() {
+            Ok(def) => def.map(Ok),
+            Err((offset, start)) => {
+                self.failed = true;
+                let mut options = ParserOptions::default();
+                options.start_position(Pos { token: 0, .. start });
+                let rest = &self.source[offset..];
+                Some(Err(parse_schema_with_options::<&str>(rest, &options)
+                    .expect_err("definition is invalid")))
+            }
+        }
+    }
+}
+
+fn starts_definition(tok: Token, previous: Token) -> bool {
+    match tok.kind {
+        Kind::StringValue | Kind::BlockString => true,
+        Kind::Name => {
+            KEYWORDS.contains(&tok.value) &&
+                !NAME_CONTEXTS.contains(&previous.value)
+        }
+        _ => false,
+    }
+}
+
+/// Returns the position after the token
+fn end_of(pos: Pos, value: &str) -> Pos {
+    let mut end = Pos { token: pos.token + 1, .. pos };
+    for c in value.chars() {
+        end.character += 1;
+        if c == '\n' {
+            end.line += 1;
+            end.column = 1;
+        } else {
+            end.column += width(c);
+        }
+    }
+    end
+}
+
+#[cfg(test)]
+mod test {
+    use crate::schema::{parse_schema, TypeKind};
+    use super::{split_schema, DefinitionKind};
+
+    #[test]
+    fn split() {
+        let source = r#"
+            schema @a(x: "type") { query: Query }
+            directive @type(if: Boolean = true) on FIELD | OBJECT
+            union U = type | schema
+            interface I implements type & enum { f(x: [In] = [{a: 1}]): U }
+            """
+            Block
+            """
+            enum E { type union }
+            extend schema @b
+            scalar S @c(x: """
+              multi-line
+            """)
+            input In { type: String }
+        "#;
+        let parts = split_schema(source).collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let summary = parts.iter()
+            .map(|part| (part.kind, part.extension, part.name))
+            .collect::<Vec<_>>();
+        assert_eq!(summary, [
+            (DefinitionKind::Schema, false, None),
+            (DefinitionKind::Directive, false, Some("type")),
+            (DefinitionKind::Type(TypeKind::Union), false, Some("U")),
+            (DefinitionKind::Type(TypeKind::Interface), false, Some("I")),
+            (DefinitionKind::Type(TypeKind::Enum), false, Some("E")),
+            (DefinitionKind::Schema, true, None),
+            (DefinitionKind::Type(TypeKind::Scalar), false, Some("S")),
+            (DefinitionKind::Type(TypeKind::InputObject), false, Some("In")),
+        ]);
+        assert_eq!(parts[6].span.0.to_string(), "11:13");
+        assert_eq!(parts[6].span.1.to_string(), "13:17");
+        assert!(parts[4].text.starts_with("\"\"\"\n"));
+
+        let whole = parse_schema::<&str>(source).unwrap();
+        for (part, def) in parts.iter().zip(&whole.definitions) {
+            let doc = part.parse::<&str>().unwrap();
+            assert_eq!(doc.definitions.len(), 1);
+            assert_eq!(doc.to_string(), def.to_string());
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let mut parts = split_schema("type A { a: Int }\ntype { b: Int }");
+        assert_eq!(parts.next().unwrap().unwrap().name, Some("A"));
+        let err = parts.next().unwrap().unwrap_err();
+        assert_eq!(err.span().0.to_string(), "2:6");
+        assert!(parts.next().is_none());
+
+        let mut parts = split_schema("scalar A ^ scalar B");
+        assert_eq!(parts.next().unwrap().unwrap_err().span().0.to_string(),
+                   "1:10");
+        assert!(parts.next().is_none());
+        assert!(split_schema(" # comment\n").next().is_none());
+        assert!(split_schema("\"dangling\"").next().unwrap().is_err());
+    }
+}
@@ -0,0 +1,515 @@
+//! Compact single-line summaries of AST nodes
+//!
+//! Derived `Debug` output of documents is huge and the formatted document
+//! is often too verbose for logs. The summary of a node contains only its
+//! name, directives and the number of children:
+//!
+//! ```rust
+//! # extern crate edb_graphql_parser;
+//! use edb_graphql_parser::parse_query;
+//! use edb_graphql_parser::query::{Definition, Selection};
+//! use edb_graphql_parser::summary::Summary;
+//!
+//! let doc = parse_query::<&str>("{ u: user(id: 1) @skip(if: true) { a b c } }")
+//!     .unwrap();
+//! assert_eq!(doc.summary(), "Document(1 definition)");
+//! if let Definition::Operation(ref op) = doc.definitions[0] {
+//!     assert_eq!(op.selection_set.items[0].summary(),
+//!                "Field(u: user(id) @skip, 3 children)");
+//! }
+//! ```
+use crate::common::{Directive, Text, Type, Value};
+use crate::query;
+use crate::schema;
+
+
+/// Single-line description of a node
+pub trait Summary {
+    /// Returns a short description of the node, such as
+    /// `Field(user @skip, 3 children)`
+    fn summary(&self) -> String;
+}
+
+/// Builder of the summary string
+struct Node {
+    buf: String,
+    has_head: bool,
+}
+
+impl Node {
+    fn new(kind: &str) -> Node {
+        let mut buf = String::with_capacity(32);
+        buf.push_str(kind);
+        buf.push('(');
+        Node { buf, has_head: false }
+    }
+    fn head(mut self, text: &str) -> Node {
+        self.buf.push_str(text);
+        self.has_head = true;
+        self
+    }
+    fn directives<'a, T>(mut self, dirs: &[Directive<'a, T>]) -> Node
+        where T: Text<'a>,
+    {
+        for dir in dirs {
+            if self.has_head {
+                self.buf.push(' ');
+            }
+            self.buf.push('@');
+            self.buf.push_str(dir.name.as_ref());
+            self.has_head = true;
+        }
+        self
+    }
+    fn count(mut self, num: usize, singular: &str, plural: &str) -> Node {
+        if num == 0 {
+            return self;
+        }
+        if self.has_head {
+            self.buf.push_str(", ");
+        }
+        self.buf.push_str(&num.to_string());
+        self.buf.push(' ');
+        self.buf.push_str(if num == 1 { singular } else { plural });
+        self.has_head = true;
+        self
+    }
+    fn finish(mut self) -> String {
+        self.buf.push(')');
+        self.buf
+    }
+}
+
+fn names<'x, I>(names: I) -> String
+    where I: IntoIterator<Item=&'x str>,
+{
+    names.into_iter().collect::<Vec<_>>().join(", ")
+}
+
+impl<'a, T> Summary for Directive<'a, T>
+    where T: Text<'a>,
+{
+    fn summary(&self) -> String {
+        Node::new("Directive")
+            .head(&format!("@{}", self.name.as_ref()))
+            .count(self.arguments.len(), "argument", "arguments")
+            .finish()
+    }
+}
+
+impl<'a, T> Summary for Value<'a, T>
+    where T: Text<'a>,
+{
+    fn summary(&self) -> String {
+        match *self {
+            Value::List(ref items) => {
+                Node::new("List").count(items.len(), "item", "items").finish()
+            }
+            Value::Object(ref fields) => {
+                Node::new("Object").count(fields.len(), "field", "fields")
+                    .finish()
+            }
+            ref scalar => {
+                let text = scalar.to_string();
+                if text.chars().count() > 40 {
+                    let prefix: String = text.chars().take(40).collect();
+                    format!("{}…", prefix)
+                } else {
+                    text
+                }
+            }
+        }
+    }
+}
+
+impl<'a, T> Summary for Type<'a, T>
+    where T: Text<'a>,
+{
+    fn summary(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl<'a, T> Summary for query::Document<'a, T>
+    where T: Text<'a>,
+{
+    fn summary(&self) -> String {
+        Node::new("Document")
+            .count(self.definitions.len(), "definition", "definitions")
+            .finish()
+    }
+}
+
+impl<'a, T> Summary for query::Definition<'a, T>
+    where T: Text<'a>,
+{
+    fn summary(&self) -> String {
+        match *self {
+            query::Definition::Operation(ref op) => op.summary(),
+            query::Definition::Fragment(ref frag) => frag.summary(),
+        }
+    }
+}
+
+impl<'a, T> Summary for query::Operation<'a, T>
+    where T: Text<'a>,
+{
+    fn summary(&self) -> String {
+        use crate::query::OperationKind::*;
+        let kind = match self.kind {
+            ImplicitQuery | Query => "Query",
+            Mutation => "Mutation",
+            Subscription => "Subscription",
+        };
+        let mut node = Node::new(kind);
+        if let Some(ref name) = self.name {
+            node = node.head(name.as_ref());
+        }
+        node.directives(&self.directives)
+            .count(self.variable_definitions.len(), "variable", "variables")
+            .count(self.selection_set.items.len(), "child", "children")
+            .finish()
+    }
+}
+
+impl<'a, T> Summary for query::FragmentDefinition<'a, T>
+    where T: Text<'a>,
+{
+    fn summary(&self) -> String {
+        let query::TypeCondition::On(ref cond) = self.type_condition;
+        Node::new("Fragment")
+            .head(&format!("{} on {}", self.name.as_ref(), cond.as_ref()))
+            .directives(&self.directives)
+            .count(self.selection_set.items.len(), "child", "children")
+            .finish()
+    }
+}
+
+impl<'a, T> Summary for query::VariableDefinition<'a, T>
+    where T: Text<'a>,
+{
+    fn summary(&self) -> String {
+        let mut head = format!("${}: {}", self.name.as_ref(), self.var_type);
+        if let Some(ref default) = self.default_value {
+            head.push_str(" = ");
+            head.push_str(&default.value.summary());
+        }
+        Node::new("Variable").head(&head).directives(&self.directives)
+            .finish()
+    }
+}
+
+impl<'a, T> Summary for query::SelectionSet<'a, T>
+    where T: Text<'a>,
+{
+    fn summary(&self) -> String {
+        Node::new("SelectionSet")
+            .count(self.items.len(), "item", "items")
+            .finish()
+    }
+}
+
+impl<'a, T> Summary for query::Selection<'a, T>
+    where T: Text<'a>,
+{
+    fn summary(&self) -> String {
+        match *self {
+            query::Selection::Field(ref f) => f.summary(),
+            query::Selection::FragmentSpread(ref f) => f.summary(),
+            query::Selection::InlineFragment(ref f) => f.summary(),
+        }
+    }
+}
+
+impl<'a, T> Summary for query::Field<'a, T>
+    where T: Text<'a>,
+{
+    fn summary(&self) -> String {
+        let mut head = String::new();
+        if let Some(ref alias) = self.alias {
+            head.push_str(alias.as_ref());
+            head.push_str(": ");
+        }
+        head.push_str(self.name.as_ref());
+        if !self.arguments.is_empty() {
+            head.push('(');
+            head.push_str(&names(self.arguments.iter().map(|(n, _)| n.as_ref())));
+            head.push(')');
+        }
+        Node::new("Field")
+            .head(&head)
+            .directives(&self.directives)
+            .count(self.selection_set.items.len(), "child", "children")
+            .finish()
+    }
+}
+
+impl<'a, T> Summary for query::FragmentSpread<'a, T>
+    where T: Text<'a>,
+{
+    fn summary(&self) -> String {
+        Node::new("FragmentSpread")
+            .head(&format!("...{}", self.fragment_name.as_ref()))
+            .directives(&self.directives)
+            .finish()
+    }
+}
+
+impl<'a, T> Summary for query::InlineFragment<'a, T>
+    where T: Text<'a>,
+{
+    fn summary(&self) -> String {
+        let mut node = Node::new("InlineFragment");
+        if let Some(query::TypeCondition::On(ref cond)) = self.type_condition {
+            node = node.head(&format!("on {}", cond.as_ref()));
+        }
+        node.directives(&self.directives)
+            .count(self.selection_set.items.len(), "child", "children")
+            .finish()
+    }
+}
+
+impl<'a, T> Summary for schema::Document<'a, T>
+    where T: Text<'a>,
+{
+    fn summary(&self) -> String {
+        Node::new("Document")
+            .count(self.definitions.len(), "definition", "definitions")
+            .finish()
+    }
+}
+
+impl<'a, T> Summary for schema::Definition<'a, T>
+    where T: Text<'a>,
+{
+    fn summary(&self) -> String {
+        use crate::schema::Definition::*;
+        match *self {
+            SchemaDefinition(ref s) => {
+                let roots = [&s.query, &s.mutation, &s.subscription];
+                let head = names(roots.iter()
+                    .filter_map(|v| v.as_ref().map(|v| v.as_ref())));
+                Node::new("Schema")
+                    .head(&head)
+                    .directives(&s.directives)
+                    .finish()
+            }
+            SchemaExtension(ref s) => {
+                let roots = [&s.query, &s.mutation, &s.subscription];
+                let head = names(roots.iter()
+                    .filter_map(|v| v.as_ref().map(|v| v.as_ref())));
+                Node::new("SchemaExtension")
+                    .head(&head)
+                    .directives(&s.directives)
+                    .finish()
+            }
+            TypeDefinition(ref t) => t.summary(),
+            TypeExtension(ref t) => t.summary(),
+            DirectiveDefinition(ref d) => d.summary(),
+            Custom(ref c) => {
+                Node::new("Custom").head(c.keyword.as_ref()).finish()
+            }
+        }
+    }
+}
+
+fn object_head<'a, T>(name: &T::Value, interfaces: &[T::Value]) -> String
+    where T: Text<'a>,
+{
+    if interfaces.is_empty() {
+        name.as_ref().to_string()
+    } else {
+        format!("{} implements {}", name.as_ref(),
+            interfaces.iter().map(|i| i.as_ref())
+                .collect::<Vec<_>>().join(" & "))
+    }
+}
+
+impl<'a, T> Summary for schema::TypeDefinition<'a, T>
+    where T: Text<'a>,
+{
+    fn summary(&self) -> String {
+        use crate::schema::TypeDefinition::*;
+        match *self {
+            Scalar(ref s) => Node::new("Scalar")
+                .head(s.name.as_ref())
+                .directives(&s.directives)
+                .finish(),
+            Object(ref o) => Node::new("Object")
+                .head(&object_head::<T>(&o.name, &o.implements_interfaces))
+                .directives(&o.directives)
+                .count(o.fields.len(), "field", "fields")
+                .finish(),
+            Interface(ref i) => Node::new("Interface")
+                .head(&object_head::<T>(&i.name, &i.implements_interfaces))
+                .directives(&i.directives)
+                .count(i.fields.len(), "field", "fields")
+                .finish(),
+            Union(ref u) => Node::new("Union")
+                .head(u.name.as_ref())
+                .directives(&u.directives)
+                .count(u.types.len(), "member", "members")
+                .finish(),
+            Enum(ref e) => Node::new("Enum")
+                .head(e.name.as_ref())
+                .directives(&e.directives)
+                .count(e.values.len(), "value", "values")
+                .finish(),
+            InputObject(ref i) => Node::new("InputObject")
+                .head(i.name.as_ref())
+                .directives(&i.directives)
+                .count(i.fields.len(), "field", "fields")
+                .finish(),
+        }
+    }
+}
+
+impl<'a, T> Summary for schema::TypeExtension<'a, T>
+    where T: Text<'a>,
+{
+    fn summary(&self) -> String {
+        use crate::schema::TypeExtension::*;
+        match *self {
+            Scalar(ref s) => Node::new("ExtendScalar")
+                .head(s.name.as_ref())
+                .directives(&s.directives)
+                .finish(),
+            Object(ref o) => Node::new("ExtendObject")
+                .head(&object_head::<T>(&o.name, &o.implements_interfaces))
+                .directives(&o.directives)
+                .count(o.fields.len(), "field", "fields")
+                .finish(),
+            Interface(ref i) => Node::new("ExtendInterface")
+                .head(&object_head::<T>(&i.name, &i.implements_interfaces))
+                .directives(&i.directives)
+                .count(i.fields.len(), "field", "fields")
+                .finish(),
+            Union(ref u) => Node::new("ExtendUnion")
+                .head(u.name.as_ref())
+                .directives(&u.directives)
+                .count(u.types.len(), "member", "members")
+                .finish(),
+            Enum(ref e) => Node::new("ExtendEnum")
+                .head(e.name.as_ref())
+                .directives(&e.directives)
+                .count(e.values.len(), "value", "values")
+                .finish(),
+            InputObject(ref i) => Node::new("ExtendInputObject")
+                .head(i.name.as_ref())
+                .directives(&i.directives)
+                .count(i.fields.len(), "field", "fields")
+                .finish(),
+        }
+    }
+}
+
+impl<'a, T> Summary for schema::DirectiveDefinition<'a, T>
+    where T: Text<'a>,
+{
+    fn summary(&self) -> String {
+        Node::new("DirectiveDefinition")
+            .head(&format!("@{}{} on {}", self.name.as_ref(),
+                if self.repeatable { " repeatable" } else { "" },
+                self.locations.iter().map(|l| l.as_str())
+                    .collect::<Vec<_>>().join(" | ")))
+            .count(self.arguments.len(), "argument", "arguments")
+            .finish()
+    }
+}
+
+impl<'a, T> Summary for schema::Field<'a, T>
+    where T: Text<'a>,
+{
+    fn summary(&self) -> String {
+        Node::new("Field")
+            .head(&format!("{}: {}", self.name.as_ref(), self.field_type))
+            .directives(&self.directives)
+            .count(self.arguments.len(), "argument", "arguments")
+            .finish()
+    }
+}
+
+impl<'a, T> Summary for schema::InputValue<'a, T>
+    where T: Text<'a>,
+{
+    fn summary(&self) -> String {
+        let mut head = format!("{}: {}", self.name.as_ref(), self.value_type);
+        if let Some(ref default) = self.default_value {
+            head.push_str(" = ");
+            head.push_str(&default.summary());
+        }
+        Node::new("InputValue")
+            .head(&head)
+            .directives(&self.directives)
+            .finish()
+    }
+}
+
+impl<'a, T> Summary for schema::EnumValue<'a, T>
+    where T: Text<'a>,
+{
+    fn summary(&self) -> String {
+        Node::new("EnumValue")
+            .head(self.name.as_ref())
+            .directives(&self.directives)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{parse_query, parse_schema};
+    use crate::query::{Definition, Selection};
+    use super::Summary;
+
+    #[test]
+    fn query() {
+        let doc = parse_query::<&str>(r#"
+            query Q($id: ID = "some-very-long-identifier-that-is-truncated")
+                @live
+            {
+                user(id: $id) { ...F ... on User @skip(if: true) { name } }
+            }
+            fragment F on User { name }
+        "#).unwrap();
+        assert_eq!(doc.summary(), "Document(2 definitions)");
+        let op = match doc.definitions[0] {
+            Definition::Operation(ref op) => op,
+            _ => unreachable!(),
+        };
+        assert_eq!(op.summary(), "Query(Q @live, 1 variable, 1 child)");
+        assert_eq!(op.variable_definitions[0].summary(),
+            "Variable($id: ID = \"some-very-long-identifier-that-is-trunc…)");
+        let user = match op.selection_set.items[0] {
+            Selection::Field(ref f) => f,
+            _ => unreachable!(),
+        };
+        assert_eq!(user.summary(), "Field(user(id), 2 children)");
+        assert_eq!(user.selection_set.items[0].summary(),
+            "FragmentSpread(...F)");
+        assert_eq!(user.selection_set.items[1].summary(),
+            "InlineFragment(on User @skip, 1 child)");
+        assert_eq!(doc.definitions[1].summary(),
+            "Fragment(F on User, 1 child)");
+    }
+
+    #[test]
+    fn schema() {
+        let doc = parse_schema::<&str>(r#"
+            schema { query: Query }
+            type User implements Node & Entity @key { id: ID! name: String }
+            extend union Search = User
+            interface Resource implements Node { id: ID! }
+            directive @key(fields: String) on OBJECT | INTERFACE
+        "#).unwrap();
+        let summaries = doc.definitions.iter()
+            .map(|d| d.summary()).collect::<Vec<_>>();
+        assert_eq!(summaries, vec![
+            "Schema(Query)",
+            "Object(User implements Node & Entity @key, 2 fields)",
+            "ExtendUnion(Search, 1 member)",
+            "Interface(Resource implements Node, 1 field)",
+            "DirectiveDefinition(@key on OBJECT | INTERFACE, 1 argument)",
+        ]);
+    }
+}
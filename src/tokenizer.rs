@@ -1,7 +1,7 @@
 use std::fmt;
 
 use combine::{StreamOnce, Positioned};
-use combine::error::{StreamError};
+use combine::error::{Consumed, StreamError, Tracked};
 use combine::stream::{Resetable};
 use combine::easy::{Error, Errors};
 
@@ -16,6 +16,9 @@ pub enum Kind {
     FloatValue,
     StringValue,
     BlockString,
+    /// Comment including the `#`, only emitted by
+    /// `TokenStream::with_comments`
+    Comment,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -29,13 +32,20 @@ pub struct TokenStream<'a> {
     buf: &'a str,
     position: Pos,
     off: usize,
+    token_end: usize,
     next_state: Option<(usize, Token<'a>, usize, Pos)>,
+    comments: bool,
+    failed_rule: Option<(&'static str, Pos)>,
+    depth: usize,
+    max_depth: usize,
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Checkpoint {
     position: Pos,
     off: usize,
+    token_end: usize,
+    depth: usize,
 }
 
 impl<'a> StreamOnce for TokenStream<'a> {
@@ -47,14 +57,24 @@ impl<'a> StreamOnce for TokenStream<'a> {
     fn uncons(&mut self) -> Result<Self::Item, Error<Token<'a>, Token<'a>>> {
         if let Some((at, tok, off, pos)) = self.next_state {
             if at == self.off {
+                self.nest(tok)?;
                 self.off = off;
+                self.token_end = at + tok.value.len();
                 self.position = pos;
                 return Ok(tok);
             }
         }
         let old_pos = self.off;
+        let old_position = self.position;
         let (kind, len) = self.peek_token()?;
         let value = &self.buf[self.off-len..self.off];
+        if let Err(e) = self.nest(Token { kind, value }) {
+            // the token is left unconsumed
+            self.off = old_pos;
+            self.position = old_position;
+            return Err(e);
+        }
+        self.token_end = self.off;
         self.skip_whitespace();
         let token = Token { kind, value };
         // TODO(tailhook) optimize this better
@@ -77,11 +97,15 @@ impl<'a> Resetable for TokenStream<'a> {
         Checkpoint {
             position: self.position,
             off: self.off,
+            token_end: self.token_end,
+            depth: self.depth,
         }
     }
     fn reset(&mut self, checkpoint: Checkpoint) {
         self.position = checkpoint.position;
         self.off = checkpoint.off;
+        self.token_end = checkpoint.token_end;
+        self.depth = checkpoint.depth;
     }
 }
 
@@ -90,11 +114,11 @@ impl<'a> Resetable for TokenStream<'a> {
 fn check_int(value: &str) -> bool {
     value == "0" || value == "-0" ||
        (!value.starts_with('0') && value != "-" && !value.starts_with("-0")
-       && value[1..].chars().all(|x| x >= '0' && x <= '9'))
+       && value[1..].chars().all(|x| x.is_ascii_digit()))
 }
 
 fn check_dec(value: &str) -> bool {
-    !value.is_empty() && value.chars().all(|x| x >= '0' && x <= '9')
+    !value.is_empty() && value.chars().all(|x| x.is_ascii_digit())
 }
 
 fn check_exp(value: &str) -> bool {
@@ -106,7 +130,7 @@ fn check_exp(value: &str) -> bool {
         return false;
     }
 
-    value[1..].chars().all(|x| x >= '0' && x <= '9')
+    value[1..].chars().all(|x| x.is_ascii_digit())
 }
 
 fn check_float(value: &str, exponent: Option<usize>, real: Option<usize>)
@@ -126,18 +150,132 @@ fn check_float(value: &str, exponent: Option<usize>, real: Option<usize>)
     }
 }
 
+const START: Pos = Pos { line: 1, column: 1, character: 0, token: 0 };
+
 impl<'a> TokenStream<'a> {
-    pub fn new(s: &str) -> TokenStream {
+    pub fn new(s: &str) -> TokenStream<'_> {
+        TokenStream::create(s, false, START)
+    }
+
+    /// Creates a stream of the source embedded in a larger text at the
+    /// `start` position, so positions of tokens are relative to the text
+    ///
+    /// Lines after the first one start at column 1.
+    pub fn starting_at(s: &str, start: Pos) -> TokenStream<'_> {
+        TokenStream::create(s, false, start)
+    }
+
+    /// Creates a stream that emits comments as `Kind::Comment` tokens
+    /// instead of skipping them
+    ///
+    /// The token is the text from `#` to the end of the line, not including
+    /// the line break, and its position is the position of the stream
+    /// before the token, as for other tokens. Parsers don't accept comment
+    /// tokens, so this is for tools working with the tokens directly.
+    pub fn with_comments(s: &str) -> TokenStream<'_> {
+        TokenStream::create(s, true, START)
+    }
+
+    fn create(s: &str, comments: bool, position: Pos) -> TokenStream<'_> {
         let mut me = TokenStream {
             buf: s,
-            position: Pos { line: 1, column: 1, character: 0, token: 0 },
+            position,
             off: 0,
+            token_end: 0,
             next_state: None,
+            comments,
+            failed_rule: None,
+            depth: 0,
+            max_depth: usize::MAX,
         };
         me.skip_whitespace();
         me
     }
 
+    /// Limits the nesting of `{` and `[` brackets
+    ///
+    /// A bracket opened deeper than the limit is an invalid token, so
+    /// the parsers return an error for it. This limits the nesting of
+    /// selection sets, list and object values and list types. By default
+    /// the nesting isn't limited.
+    pub fn max_depth(&mut self, value: usize) -> &mut Self {
+        self.max_depth = value;
+        self
+    }
+
+    /// Updates the nesting of the brackets with the consumed token
+    fn nest(&mut self, token: Token<'a>)
+        -> Result<(), Error<Token<'a>, Token<'a>>>
+    {
+        if token.kind != Kind::Punctuator {
+            return Ok(());
+        }
+        match token.value {
+            "{" | "[" if self.depth >= self.max_depth => {
+                Err(Error::unexpected_message(format_args!(
+                    "{:?} nested deeper than {} levels",
+                    token.value, self.max_depth)))
+            }
+            "{" | "[" => {
+                self.depth += 1;
+                Ok(())
+            }
+            "}" | "]" => {
+                self.depth = self.depth.saturating_sub(1);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns next token without consuming it
+    ///
+    /// Returns `None` at the end of input or if the next token is invalid.
+    pub fn peek(&mut self) -> Option<Token<'a>> {
+        let checkpoint = self.checkpoint();
+        let token = self.uncons().ok();
+        self.reset(checkpoint);
+        token
+    }
+
+    /// Records the grammar rule that failed after consuming input
+    ///
+    /// Used as `.map_err(|e| input.rule_failed("Rule", e))` on the result
+    /// of the rule. Rules are recorded from the innermost one, so outer rules
+    /// failing at the same position don't replace it.
+    pub(crate) fn rule_failed(&mut self, rule: &'static str,
+        err: Consumed<Tracked<Errors<Token<'a>, Token<'a>, Pos>>>)
+        -> Consumed<Tracked<Errors<Token<'a>, Token<'a>, Pos>>>
+    {
+        if let Consumed::Consumed(ref e) = err {
+            match self.failed_rule {
+                Some((_, pos)) if pos == e.error.position => {}
+                _ => self.failed_rule = Some((rule, e.error.position)),
+            }
+        }
+        err
+    }
+
+    /// Returns the innermost rule that failed at the position of the error
+    #[cfg(any(feature = "query", feature = "schema"))]
+    pub(crate) fn failed_rule(&self, position: Pos) -> Option<&'static str> {
+        self.failed_rule.filter(|&(_, pos)| pos == position)
+            .map(|(rule, _)| rule)
+    }
+
+    /// Returns byte offset of the next token in the source
+    #[cfg(any(feature = "query", feature = "schema"))]
+    pub(crate) fn offset(&self) -> usize {
+        self.off
+    }
+
+    /// Returns source text between `start` byte offset and the end of the
+    /// last consumed token
+    #[cfg(feature = "schema")]
+    pub(crate) fn source_since(&self, start: usize) -> &'a str {
+        &self.buf[start..self.token_end.max(start)]
+    }
+
     fn peek_token(&mut self)
         -> Result<(Kind, usize), Error<Token<'a>, Token<'a>>>
     {
@@ -149,7 +287,7 @@ impl<'a> TokenStream<'a> {
         };
 
         match cur_char {
-            '!' | '$' | ':' | '=' | '@' | '|' |
+            '!' | '$' | ':' | '=' | '@' | '|' | '?' |
             '(' | ')' | '[' | ']' | '{' | '}' | '&' => {
                 self.position.column += 1;
                 self.off += 1;
@@ -172,17 +310,10 @@ impl<'a> TokenStream<'a> {
                 }
             }
             '_' | 'a'..='z' | 'A'..='Z' => {
-                while let Some((idx, cur_char)) = iter.next() {
-                    match cur_char {
-                        '_' | 'a'..='z' | 'A'..='Z' | '0'..='9' => continue,
-                        _ => {
-                            self.position.column += idx;
-                            self.off += idx;
-                            return Ok((Name, idx));
-                        }
-                    }
-                }
-                let len = self.buf.len() - self.off;
+                let rest = iter.as_str();
+                let len = 1 + rest.bytes()
+                    .position(|b| !(b == b'_' || b.is_ascii_alphanumeric()))
+                    .unwrap_or(rest.len());
                 self.position.column += len;
                 self.off += len;
 
@@ -199,7 +330,7 @@ impl<'a> TokenStream<'a> {
                     match cur_char {
                         // just scan for now, will validate later on
                         ' ' | '\n' | '\r' | '\t' | ',' | '#' |
-                        '!' | '$' | ':' | '=' | '@' | '|' | '&' |
+                        '!' | '$' | ':' | '=' | '@' | '|' | '&' | '?' |
                         '(' | ')' | '[' | ']' | '{' | '}'
                         => break idx,
                         '.' => real = Some(idx),
@@ -243,10 +374,19 @@ impl<'a> TokenStream<'a> {
                     Ok((IntValue, len))
                 }
             }
+            '#' => {
+                let rest = iter.as_str().as_bytes();
+                let len = 1 + find_line_end(rest).unwrap_or(rest.len());
+                self.position.column +=
+                    self.buf[self.off..][..len].chars().count();
+                self.off += len;
+
+                Ok((Comment, len))
+            }
             '"' => {
                 if iter.as_str().starts_with("\"\"") {
                     let tail = &iter.as_str()[2..];
-                    for (end_idx, _) in tail.match_indices("\"\"\"") {
+                    for end_idx in block_string_ends(tail) {
                         if !tail[..end_idx].ends_with('\\') {
                             self.update_position(end_idx + 6);
                             return Ok((BlockString, end_idx + 6));
@@ -259,32 +399,28 @@ impl<'a> TokenStream<'a> {
                         )
                     )
                 } else {
-                    let mut nchars = 1;
-                    let mut escaped = false;
-                    for (idx, cur_char) in iter {
-                        nchars += 1;
-                        match cur_char {
-                            '"' if escaped => {}
-                            '"' => {
-                                self.position.column += nchars;
-                                self.off += idx+1;
-                                return Ok((StringValue, idx+1));
+                    let tail = iter.as_str().as_bytes();
+                    let mut idx = 0;
+                    while let Some(found) = find_string_special(&tail[idx..]) {
+                        idx += found;
+                        match tail[idx] {
+                            b'"' => {
+                                let len = idx + 2;
+                                self.position.column +=
+                                    self.buf[self.off..][..len].chars().count();
+                                self.off += len;
+                                return Ok((StringValue, len));
                             }
-                            '\n' => {
-                                return Err(
-                                    Error::unexpected_message(
-                                        "unterminated string value"
-                                    )
-                                );
-                            }
-
-                            _ => {
-
+                            // skip escaped character unless it's a newline
+                            b'\\' if tail.get(idx + 1) != Some(&b'\n') => {
+                                idx += 2;
                             }
+                            b'\\' => idx += 1,
+                            _ => break,  // newline
+                        }
+                        if idx >= tail.len() {
+                            break;
                         }
-
-                        // if we aren't escaped and the current char is a \, we are now escaped
-                        escaped = !escaped && cur_char == '\\';
                     }
                     Err(
                         Error::unexpected_message(
@@ -302,42 +438,38 @@ impl<'a> TokenStream<'a> {
     }
 
     fn skip_whitespace(&mut self) {
-        let mut iter = self.buf[self.off..].char_indices();
-        let idx = loop {
-            let (idx, cur_char) = match iter.next() {
-                Some(pair) => pair,
-                None => break self.buf.len() - self.off,
-            };
-            match cur_char {
-                '\u{feff}' | '\r' => continue,
-                '\t' => self.position.column += 8,
-                '\n' => {
+        let bytes = self.buf.as_bytes();
+        let mut idx = self.off;
+        while idx < bytes.len() {
+            match bytes[idx] {
+                b'\r' => {}
+                b'\t' => self.position.column += 8,
+                b'\n' => {
                     self.position.column = 1;
                     self.position.line += 1;
                 }
                 // comma is also entirely ignored in spec
-                ' ' | ',' => {
-                    self.position.column += 1;
-                    continue;
-                }
+                b' ' | b',' => self.position.column += 1,
                 //comment
-                '#' => {
-                    while let Some((_, cur_char)) = iter.next() {
-                        // TODO(tailhook) ensure SourceCharacter
-                        if cur_char == '\r' || cur_char == '\n' {
-                            self.position.column = 1;
-                            self.position.line += 1;
-                            break;
-                        }
-                    }
+                b'#' if self.comments => break,
+                b'#' => {
+                    // TODO(tailhook) ensure SourceCharacter
+                    // the line break is handled on the next iteration
+                    idx += 1 + find_line_end(&bytes[idx + 1..])
+                        .unwrap_or(bytes.len() - idx - 1);
                     continue;
                 }
-                _ => break idx,
+                // byte order mark
+                0xEF if bytes[idx..].starts_with("\u{feff}".as_bytes()) => {
+                    idx += 2;
+                }
+                _ => break,
             }
-        };
+            idx += 1;
+        }
         // TODO(tailhook) optimize this better
-        self.position.character += self.buf[self.off..][..idx].chars().count();
-        self.off += idx;
+        self.position.character += self.buf[self.off..idx].chars().count();
+        self.off = idx;
     }
 
     fn update_position(&mut self, len: usize) {
@@ -356,12 +488,60 @@ impl<'a> TokenStream<'a> {
     }
 }
 
+/// Returns offset of the first `\r` or `\n`
+#[cfg(feature = "simd")]
+fn find_line_end(s: &[u8]) -> Option<usize> {
+    memchr::memchr2(b'\r', b'\n', s)
+}
+
+#[cfg(not(feature = "simd"))]
+fn find_line_end(s: &[u8]) -> Option<usize> {
+    s.iter().position(|&b| b == b'\r' || b == b'\n')
+}
+
+/// Returns offset of the first quote, backslash or newline
+#[cfg(feature = "simd")]
+fn find_string_special(s: &[u8]) -> Option<usize> {
+    memchr::memchr3(b'"', b'\\', b'\n', s)
+}
+
+#[cfg(not(feature = "simd"))]
+fn find_string_special(s: &[u8]) -> Option<usize> {
+    s.iter().position(|&b| b == b'"' || b == b'\\' || b == b'\n')
+}
+
+/// Returns offsets of the (non-overlapping) triple quotes
+#[cfg(feature = "simd")]
+fn block_string_ends(s: &str) -> impl Iterator<Item=usize> + '_ {
+    memchr::memmem::find_iter(s.as_bytes(), b"\"\"\"")
+}
+
+#[cfg(not(feature = "simd"))]
+fn block_string_ends(s: &str) -> impl Iterator<Item=usize> + '_ {
+    s.match_indices("\"\"\"").map(|(idx, _)| idx)
+}
+
 impl<'a> fmt::Display for Token<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}[{:?}]", self.value, self.kind)
     }
 }
 
+/// Returns the tokens of the source with their positions, up to the end
+/// or the first invalid token
+#[cfg(any(feature = "query", feature = "schema"))]
+pub(crate) fn positioned_tokens(source: &str) -> Vec<(Token<'_>, Pos)> {
+    let mut stream = TokenStream::new(source);
+    let mut tokens = Vec::new();
+    loop {
+        let pos = stream.position();
+        match stream.uncons() {
+            Ok(tok) => tokens.push((tok, pos)),
+            Err(_) => return tokens,
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::{Kind, TokenStream};
@@ -380,7 +560,7 @@ mod test {
                 Err(e) => panic!("Parse error at {}: {}", s.position(), e),
             }
         }
-        return r;
+        r
     }
     fn tok_typ(s: &str) -> Vec<Kind> {
         let mut r = Vec::new();
@@ -392,7 +572,7 @@ mod test {
                 Err(e) => panic!("Parse error at {}: {}", s.position(), e),
             }
         }
-        return r;
+        r
     }
 
     #[test]
@@ -525,4 +705,55 @@ mod test {
         assert_eq!(tok_str(r#""""\"""quote" """"#), [r#""""\"""quote" """"#]);
         assert_eq!(tok_typ(r#""""\"""quote" """"#), [BlockString]);
     }
+
+    #[test]
+    fn unterminated_strings() {
+        for bad in &["\"abc", "\"a\\\"", "\"a\nb\"", "\"a\\\nb\""] {
+            let mut s = TokenStream::new(bad);
+            assert!(s.uncons().is_err(), "{:?} must fail", bad);
+        }
+    }
+
+    #[test]
+    fn positions() {
+        let mut s = TokenStream::new(
+            "\u{feff}# comment\r\n\"\u{e9}\\u00e9\" \t\"\"\"a\nb\"\"\" n # end");
+        let mut positions = Vec::new();
+        while let Ok(tok) = s.uncons() {
+            let pos = s.position();
+            positions.push((tok.value, pos.line, pos.column, pos.character));
+        }
+        // positions of the next tokens
+        assert_eq!(positions, [
+            ("\"\u{e9}\\u00e9\"", 2, 19, 23),
+            ("\"\"\"a\nb\"\"\"", 3, 6, 33),
+            ("n", 3, 8, 40),
+        ]);
+    }
+
+    #[test]
+    fn comments() {
+        let mut s = TokenStream::with_comments(
+            "# first\r\nquery { # second\n  a #\n}#last");
+        let mut tokens = Vec::new();
+        loop {
+            let pos = s.position();
+            match s.uncons() {
+                Ok(tok) => tokens.push((tok.kind, tok.value, pos.line,
+                                        pos.column)),
+                Err(ref e) if e == &Error::end_of_input() => break,
+                Err(e) => panic!("Parse error at {}: {}", s.position(), e),
+            }
+        }
+        assert_eq!(tokens, [
+            (Comment, "# first", 1, 1),
+            (Name, "query", 2, 1),
+            (Punctuator, "{", 2, 7),
+            (Comment, "# second", 2, 9),
+            (Name, "a", 3, 3),
+            (Comment, "#", 3, 5),
+            (Punctuator, "}", 4, 1),
+            (Comment, "#last", 4, 2),
+        ]);
+    }
 }
@@ -0,0 +1,449 @@
+//! Executable documents bound to the schema
+//!
+//! `TypedDocument` resolves every field of the query against the schema:
+//! the type it is selected on, its definition and the type of its result.
+//! This is the step code generators and execution planners start from.
+//!
+//! ```rust
+//! # extern crate edb_graphql_parser;
+//! use edb_graphql_parser::{parse_query, parse_schema};
+//! use edb_graphql_parser::typed::{TypedDocument, TypedSelection};
+//!
+//! let schema = parse_schema::<&str>("
+//!     type Query { users(first: Int): [User!]! }
+//!     type User { id: ID! name: String }
+//! ").unwrap();
+//! let query = parse_query::<&str>("{ users(first: 10) { id } }").unwrap();
+//! let doc = TypedDocument::new(&schema, &query).unwrap();
+//! let op = doc.operation(None).unwrap();
+//! assert_eq!(op.root_type, "Query");
+//! let users = match op.selections[0] {
+//!     TypedSelection::Field(ref field) => field,
+//!     _ => unreachable!(),
+//! };
+//! assert_eq!(users.parent_type, "Query");
+//! assert_eq!(users.result_type.to_string(), "[User!]!");
+//! assert_eq!(users.definition.unwrap().arguments[0].name, "first");
+//! let id = match users.selections[0] {
+//!     TypedSelection::Field(ref field) => field,
+//!     _ => unreachable!(),
+//! };
+//! assert_eq!((id.parent_type, id.result_type.to_string()),
+//!            ("User", "ID!".to_string()));
+//! ```
+use crate::common::{Text, Type};
+use crate::position::Pos;
+use crate::query::{self, Definition, OperationKind, Selection, SelectionSet};
+use crate::schema;
+use crate::schema::index::{type_name, SchemaIndex};
+use crate::validation::{Rule, ValidationError};
+
+
+/// Query document with the fields bound to the schema, see the module
+/// documentation
+#[derive(Debug)]
+pub struct TypedDocument<'x, 's, 'q, S, Q>
+    where S: Text<'s>,
+          Q: Text<'q>,
+{
+    /// Operations in the order of the document
+    pub operations: Vec<TypedOperation<'x, 's, 'q, S, Q>>,
+    /// Fragments in the order of the document
+    pub fragments: Vec<TypedFragment<'x, 's, 'q, S, Q>>,
+}
+
+#[derive(Debug)]
+pub struct TypedOperation<'x, 's, 'q, S, Q>
+    where S: Text<'s>,
+          Q: Text<'q>,
+{
+    pub operation: &'x query::Operation<'q, Q>,
+    /// Name of the root type of the operation
+    pub root_type: &'x str,
+    pub selections: Vec<TypedSelection<'x, 's, 'q, S, Q>>,
+}
+
+#[derive(Debug)]
+pub struct TypedFragment<'x, 's, 'q, S, Q>
+    where S: Text<'s>,
+          Q: Text<'q>,
+{
+    pub fragment: &'x query::FragmentDefinition<'q, Q>,
+    pub type_condition: &'x str,
+    pub selections: Vec<TypedSelection<'x, 's, 'q, S, Q>>,
+}
+
+#[derive(Debug)]
+pub enum TypedSelection<'x, 's, 'q, S, Q>
+    where S: Text<'s>,
+          Q: Text<'q>,
+{
+    Field(TypedField<'x, 's, 'q, S, Q>),
+    /// Spread of the fragment, see `TypedDocument::fragment`
+    FragmentSpread(&'x query::FragmentSpread<'q, Q>),
+    InlineFragment(TypedInlineFragment<'x, 's, 'q, S, Q>),
+}
+
+#[derive(Debug)]
+pub struct TypedField<'x, 's, 'q, S, Q>
+    where S: Text<'s>,
+          Q: Text<'q>,
+{
+    pub field: &'x query::Field<'q, Q>,
+    /// Name of the type the field is selected on
+    pub parent_type: &'x str,
+    /// Definition of the field in the schema, `None` for the meta-fields
+    /// `__typename`, `__schema` and `__type`
+    pub definition: Option<&'x schema::Field<'s, S>>,
+    pub result_type: Type<'x, &'x str>,
+    /// Selections of the field, empty for the meta-fields: introspection
+    /// types are not a part of the schema document
+    pub selections: Vec<TypedSelection<'x, 's, 'q, S, Q>>,
+}
+
+#[derive(Debug)]
+pub struct TypedInlineFragment<'x, 's, 'q, S, Q>
+    where S: Text<'s>,
+          Q: Text<'q>,
+{
+    pub fragment: &'x query::InlineFragment<'q, Q>,
+    /// Name of the type condition, or of the parent type if the fragment
+    /// has no condition
+    pub type_condition: &'x str,
+    pub selections: Vec<TypedSelection<'x, 's, 'q, S, Q>>,
+}
+
+struct Binder<'i, 'x, 's, S>
+    where S: Text<'s>
+{
+    index: &'i SchemaIndex<'x, 's, S>,
+    errors: Vec<ValidationError>,
+}
+
+impl<'x, 's, 'q, S, Q> TypedDocument<'x, 's, 'q, S, Q>
+    where S: Text<'s>,
+          Q: Text<'q>,
+{
+    /// Binds the document to the schema
+    ///
+    /// Types and fields are looked up in the schema document, including
+    /// its extensions. Returns errors for the fields that don't exist, the
+    /// unknown or non-composite type conditions and the operations whose
+    /// root type isn't defined. The document is otherwise expected to be
+    /// valid, see `validation::validate_with_schema`.
+    pub fn new(schema: &'x schema::Document<'s, S>,
+        doc: &'x query::Document<'q, Q>)
+        -> Result<Self, Vec<ValidationError>>
+    {
+        let index = SchemaIndex::new(schema);
+        let mut binder = Binder { index: &index, errors: Vec::new() };
+        let mut operations = Vec::new();
+        let mut fragments = Vec::new();
+        for def in &doc.definitions {
+            match *def {
+                Definition::Operation(ref operation) => {
+                    if let Some(op) = binder.operation(operation) {
+                        operations.push(op);
+                    }
+                }
+                Definition::Fragment(ref fragment) => {
+                    let query::TypeCondition::On(ref cond) =
+                        fragment.type_condition;
+                    if let Some(cond) = binder.condition(cond.as_ref(),
+                                                         fragment.position)
+                    {
+                        fragments.push(TypedFragment {
+                            fragment,
+                            type_condition: cond,
+                            selections: binder.selection_set(cond,
+                                &fragment.selection_set),
+                        });
+                    }
+                }
+            }
+        }
+        if binder.errors.is_empty() {
+            Ok(TypedDocument { operations, fragments })
+        } else {
+            Err(binder.errors)
+        }
+    }
+
+    /// Returns the operation by name, same as `query::Document::operation`
+    pub fn operation(&self, name: Option<&str>)
+        -> Option<&TypedOperation<'x, 's, 'q, S, Q>>
+    {
+        match name {
+            Some(name) => self.operations.iter().find(|op| {
+                op.operation.name.as_ref().map(|n| n.as_ref()) == Some(name)
+            }),
+            None if self.operations.len() == 1 => self.operations.first(),
+            None => None,
+        }
+    }
+
+    /// Returns the first fragment with the name
+    pub fn fragment(&self, name: &str)
+        -> Option<&TypedFragment<'x, 's, 'q, S, Q>>
+    {
+        self.fragments.iter()
+            .find(|frag| frag.fragment.name.as_ref() == name)
+    }
+}
+
+impl<'i, 'x, 's, S> Binder<'i, 'x, 's, S>
+    where S: Text<'s>
+{
+    fn error(&mut self, rule: Rule, message: String, position: Pos) {
+        self.errors.push(ValidationError::new(rule, message, vec![position]));
+    }
+
+    fn operation<'q, Q>(&mut self, operation: &'x query::Operation<'q, Q>)
+        -> Option<TypedOperation<'x, 's, 'q, S, Q>>
+        where Q: Text<'q>,
+    {
+        let root = match operation.kind {
+            OperationKind::ImplicitQuery | OperationKind::Query => {
+                self.index.query_type()
+            }
+            OperationKind::Mutation => self.index.mutation_type(),
+            OperationKind::Subscription => self.index.subscription_type(),
+        };
+        match root {
+            Some(root) => Some(TypedOperation {
+                operation,
+                root_type: root,
+                selections: self.selection_set(root,
+                                               &operation.selection_set),
+            }),
+            None => {
+                self.error(Rule::KnownOperationTypes,
+                    "Schema does not define the root type for \
+                     this operation.".into(),
+                    operation.position);
+                None
+            }
+        }
+    }
+
+    /// Returns the name of the composite type of the condition
+    fn condition(&mut self, name: &str, position: Pos)
+        -> Option<&'x str>
+    {
+        match self.index.type_by_name(name) {
+            Some(entry) if entry.kind.is_composite() => Some(entry.name),
+            Some(_) => {
+                self.error(Rule::FragmentsOnCompositeTypes,
+                    format!("Fragment cannot condition on non composite \
+                             type {:?}.", name), position);
+                None
+            }
+            None => {
+                self.error(Rule::KnownTypeNames,
+                    format!("Unknown type {:?}.", name), position);
+                None
+            }
+        }
+    }
+
+    fn selection_set<'q, Q>(&mut self, parent: &'x str,
+        set: &'x SelectionSet<'q, Q>)
+        -> Vec<TypedSelection<'x, 's, 'q, S, Q>>
+        where Q: Text<'q>,
+    {
+        let mut selections = Vec::with_capacity(set.items.len());
+        for item in &set.items {
+            match *item {
+                Selection::Field(ref field) => {
+                    if let Some(field) = self.field(parent, field) {
+                        selections.push(TypedSelection::Field(field));
+                    }
+                }
+                Selection::FragmentSpread(ref spread) => {
+                    selections.push(TypedSelection::FragmentSpread(spread));
+                }
+                Selection::InlineFragment(ref fragment) => {
+                    let cond = match fragment.type_condition {
+                        Some(query::TypeCondition::On(ref name)) => {
+                            match self.condition(name.as_ref(),
+                                                 fragment.position)
+                            {
+                                Some(cond) => cond,
+                                None => continue,
+                            }
+                        }
+                        None => parent,
+                    };
+                    selections.push(TypedSelection::InlineFragment(
+                        TypedInlineFragment {
+                            fragment,
+                            type_condition: cond,
+                            selections: self.selection_set(cond,
+                                &fragment.selection_set),
+                        }));
+                }
+            }
+        }
+        selections
+    }
+
+    fn field<'q, Q>(&mut self, parent: &'x str,
+        field: &'x query::Field<'q, Q>)
+        -> Option<TypedField<'x, 's, 'q, S, Q>>
+        where Q: Text<'q>,
+    {
+        let name = field.name.as_ref();
+        let root = self.index.query_type() == Some(parent);
+        let meta = match name {
+            "__typename" => Some("String"),
+            "__schema" if root => Some("__Schema"),
+            "__type" if root => Some("__Type"),
+            _ => None,
+        };
+        if let Some(type_name) = meta {
+            let named = Type::NamedType(type_name);
+            let result_type = if name == "__type" {
+                named
+            } else {
+                Type::NonNullType(Box::new(named))
+            };
+            return Some(TypedField {
+                field,
+                parent_type: parent,
+                definition: None,
+                result_type,
+                selections: Vec::new(),
+            });
+        }
+        let definition = match self.index.field(parent, name) {
+            Some(definition) => definition,
+            None => {
+                self.error(Rule::FieldsOnCorrectType,
+                    format!("Cannot query field {:?} on type {:?}.",
+                            name, parent),
+                    field.position);
+                return None;
+            }
+        };
+        let result = type_name(&definition.field_type);
+        Some(TypedField {
+            field,
+            parent_type: parent,
+            definition: Some(definition),
+            result_type: definition.field_type.map_text(|n| n),
+            selections: self.selection_set(result, &field.selection_set),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{parse_query, parse_schema};
+    use crate::common::Text;
+    use crate::validation::Rule;
+    use super::{TypedDocument, TypedField, TypedSelection};
+
+    fn fields<'t, 'x, 's, 'q, S, Q>(
+        selections: &'t [TypedSelection<'x, 's, 'q, S, Q>])
+        -> Vec<&'t TypedField<'x, 's, 'q, S, Q>>
+        where S: Text<'s>,
+              Q: Text<'q>,
+    {
+        let mut fields = Vec::new();
+        for item in selections {
+            match *item {
+                TypedSelection::Field(ref field) => fields.push(field),
+                TypedSelection::InlineFragment(ref frag) => {
+                    fields.extend(self::fields(&frag.selections));
+                }
+                TypedSelection::FragmentSpread(_) => {}
+            }
+        }
+        fields
+    }
+
+    fn summary<'s, 'q>(fields: Vec<&TypedField<'_, 's, 'q, &'s str, &'q str>>)
+        -> Vec<String>
+    {
+        fields.iter().map(|f| {
+            format!("{}.{}: {}", f.parent_type, f.field.name, f.result_type)
+        }).collect()
+    }
+
+    #[test]
+    fn bind() {
+        let schema = parse_schema::<&str>("
+            schema { query: Root, mutation: Mutation }
+            interface Node { id: ID! }
+            type User implements Node { id: ID! friends: [User] }
+            type Root { node(id: ID!): Node }
+            extend type Root { me: User }
+            type Mutation { ping: Boolean }
+        ").unwrap();
+        let query = parse_query::<&str>("
+            query Q {
+                node(id: 1) { __typename ... on User { friends { id } } }
+                ...F
+                __schema { types { name } }
+            }
+            mutation { ping }
+            fragment F on Root { me { ... { id } } }
+        ").unwrap();
+        let doc = TypedDocument::new(&schema, &query).unwrap();
+        assert!(doc.operation(None).is_none());
+        assert!(doc.operation(Some("R")).is_none());
+        let op = doc.operation(Some("Q")).unwrap();
+        assert_eq!(op.root_type, "Root");
+        let root = fields(&op.selections);
+        assert_eq!(summary(root.clone()),
+                   ["Root.node: Node", "Root.__schema: __Schema!"]);
+        assert!(root[0].definition.is_some());
+        assert!(root[1].definition.is_none());
+        assert!(root[1].selections.is_empty());
+        assert!(matches!(op.selections[1],
+                         TypedSelection::FragmentSpread(s)
+                         if s.fragment_name == "F"));
+        let node = fields(&root[0].selections);
+        assert_eq!(summary(node.clone()),
+                   ["Node.__typename: String!", "User.friends: [User]"]);
+        assert_eq!(summary(fields(&node[1].selections)), ["User.id: ID!"]);
+        let frag = doc.fragment("F").unwrap();
+        assert_eq!(frag.type_condition, "Root");
+        let me = fields(&frag.selections);
+        assert_eq!(summary(me.clone()), ["Root.me: User"]);
+        assert_eq!(summary(fields(&me[0].selections)), ["User.id: ID!"]);
+        let mutation = &doc.operations[1];
+        assert_eq!(mutation.root_type, "Mutation");
+        assert_eq!(summary(fields(&mutation.selections)),
+                   ["Mutation.ping: Boolean"]);
+    }
+
+    #[test]
+    fn errors() {
+        let schema = parse_schema::<&str>("
+            type Query { a: Int b: T }
+            type T { c: Int }
+        ").unwrap();
+        let query = parse_query::<&str>("
+            { x b { ... on Int { c } ... on Missing { c } d } }
+            subscription { a }
+            fragment F on T { __schema { a } }
+        ").unwrap();
+        let errors = TypedDocument::new(&schema, &query).unwrap_err();
+        assert_eq!(errors.iter()
+                   .map(|e| (e.rule, e.message.as_str()))
+                   .collect::<Vec<_>>(), [
+            (Rule::FieldsOnCorrectType,
+             "Cannot query field \"x\" on type \"Query\"."),
+            (Rule::FragmentsOnCompositeTypes,
+             "Fragment cannot condition on non composite type \"Int\"."),
+            (Rule::KnownTypeNames, "Unknown type \"Missing\"."),
+            (Rule::FieldsOnCorrectType,
+             "Cannot query field \"d\" on type \"T\"."),
+            (Rule::KnownOperationTypes,
+             "Schema does not define the root type for this operation."),
+            (Rule::FieldsOnCorrectType,
+             "Cannot query field \"__schema\" on type \"T\"."),
+        ]);
+    }
+}
@@ -0,0 +1,380 @@
+use crate::common::{Directive, Text};
+use crate::position::Pos;
+use crate::query::{Document, Definition, Operation, OperationKind};
+use crate::query::{FragmentDefinition, VariableDefinition};
+use crate::query::{Field, FragmentSpread, InlineFragment, TypeCondition};
+use crate::query::{Selection, SelectionSet};
+use crate::schema::SchemaIndex;
+use crate::validation::{Rule, ValidationError};
+
+
+/// State of the traversal passed to the `ValidationRule`
+#[derive(Debug)]
+pub struct RuleContext<'c, 'x, 's, S>
+    where S: Text<'s>
+{
+    schema: Option<&'c SchemaIndex<'x, 's, S>>,
+    types: Vec<Option<&'x str>>,
+    rule: &'static str,
+    errors: &'c mut Vec<ValidationError>,
+}
+
+/// Custom validation rule, see `validate_with_rules`
+///
+/// The rule receives an event for every node of the document, in the
+/// order of the source. Fragments are visited where they are defined, not
+/// where they are spread. All methods do nothing by default:
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::parse_query;
+/// use edb_graphql_parser::common::Text;
+/// use edb_graphql_parser::query::Operation;
+/// use edb_graphql_parser::validation::{validate_with_rules, Rule};
+/// use edb_graphql_parser::validation::{RuleContext, ValidationRule};
+///
+/// struct RequiredNames;
+///
+/// impl<'q, 's, Q: Text<'q>, S: Text<'s>> ValidationRule<'q, 's, Q, S>
+///     for RequiredNames
+/// {
+///     fn name(&self) -> &'static str { "RequiredOperationNames" }
+///     fn enter_operation(&mut self, ctx: &mut RuleContext<'_, '_, 's, S>,
+///         op: &Operation<'q, Q>)
+///     {
+///         if op.name.is_none() {
+///             ctx.report("Operation must be named.", vec![op.position]);
+///         }
+///     }
+/// }
+///
+/// let doc = parse_query::<&str>("{ a }").unwrap();
+/// let errors = validate_with_rules(&doc, &mut [&mut RequiredNames])
+///     .unwrap_err();
+/// assert_eq!(errors[0].rule, Rule::Custom("RequiredOperationNames"));
+/// assert_eq!(errors[0].to_string(), "Operation must be named. at 1:1");
+/// ```
+#[allow(unused_variables)]
+pub trait ValidationRule<'q, 's, Q, S>
+    where Q: Text<'q>,
+          S: Text<'s>,
+{
+    /// Name of the rule, used as `Rule::Custom` in the errors
+    fn name(&self) -> &'static str;
+
+    fn enter_operation(&mut self, ctx: &mut RuleContext<'_, '_, 's, S>,
+        op: &Operation<'q, Q>) {}
+    fn leave_operation(&mut self, ctx: &mut RuleContext<'_, '_, 's, S>,
+        op: &Operation<'q, Q>) {}
+    fn enter_fragment(&mut self, ctx: &mut RuleContext<'_, '_, 's, S>,
+        frag: &FragmentDefinition<'q, Q>) {}
+    fn leave_fragment(&mut self, ctx: &mut RuleContext<'_, '_, 's, S>,
+        frag: &FragmentDefinition<'q, Q>) {}
+    fn enter_variable_definition(&mut self,
+        ctx: &mut RuleContext<'_, '_, 's, S>,
+        var: &VariableDefinition<'q, Q>) {}
+    fn enter_directive(&mut self, ctx: &mut RuleContext<'_, '_, 's, S>,
+        dir: &Directive<'q, Q>) {}
+    /// Called before the directives and the selections of the field,
+    /// `RuleContext::parent_type` is the type having the field
+    fn enter_field(&mut self, ctx: &mut RuleContext<'_, '_, 's, S>,
+        field: &Field<'q, Q>) {}
+    fn leave_field(&mut self, ctx: &mut RuleContext<'_, '_, 's, S>,
+        field: &Field<'q, Q>) {}
+    fn enter_fragment_spread(&mut self,
+        ctx: &mut RuleContext<'_, '_, 's, S>,
+        spread: &FragmentSpread<'q, Q>) {}
+    fn enter_inline_fragment(&mut self,
+        ctx: &mut RuleContext<'_, '_, 's, S>,
+        frag: &InlineFragment<'q, Q>) {}
+    fn leave_inline_fragment(&mut self,
+        ctx: &mut RuleContext<'_, '_, 's, S>,
+        frag: &InlineFragment<'q, Q>) {}
+    /// Called after all the definitions are visited
+    fn leave_document(&mut self, ctx: &mut RuleContext<'_, '_, 's, S>,
+        doc: &Document<'q, Q>) {}
+}
+
+impl<'c, 'x, 's, S> RuleContext<'c, 'x, 's, S>
+    where S: Text<'s>
+{
+    /// Returns the schema if the document is validated against one
+    pub fn schema(&self) -> Option<&'c SchemaIndex<'x, 's, S>> {
+        self.schema
+    }
+
+    /// Returns the name of the type of the selection set being visited
+    ///
+    /// Always `None` without a schema, and for selections of the fields
+    /// which are not defined in the schema.
+    pub fn parent_type(&self) -> Option<&'x str> {
+        self.types.last().cloned().flatten()
+    }
+
+    /// Adds an error of the rule
+    pub fn report(&mut self, message: impl Into<String>,
+        positions: Vec<Pos>)
+    {
+        self.errors.push(ValidationError::new(Rule::Custom(self.rule),
+            message.into(), positions));
+    }
+
+    fn field_type(&self, name: &str) -> Option<&'x str> {
+        let parent = self.parent_type()?;
+        let field = self.schema?.field(parent, name)?;
+        Some(field.field_type.innermost_name())
+    }
+
+    fn root_type(&self, kind: &OperationKind) -> Option<&'x str> {
+        let schema = self.schema?;
+        match *kind {
+            OperationKind::ImplicitQuery | OperationKind::Query => {
+                schema.query_type()
+            }
+            OperationKind::Mutation => schema.mutation_type(),
+            OperationKind::Subscription => schema.subscription_type(),
+        }
+    }
+
+    fn type_named<'q, Q>(&self, cond: &TypeCondition<'q, Q>)
+        -> Option<&'x str>
+        where Q: Text<'q>,
+    {
+        let TypeCondition::On(ref name) = *cond;
+        self.schema?.type_by_name(name.as_ref()).map(|entry| entry.name)
+    }
+}
+
+struct Walker<'r, 'c, 'x, 'q, 's, Q, S>
+    where Q: Text<'q>,
+          S: Text<'s>,
+{
+    rule: &'r mut dyn ValidationRule<'q, 's, Q, S>,
+    ctx: RuleContext<'c, 'x, 's, S>,
+}
+
+impl<'r, 'c, 'x, 'q, 's, Q, S> Walker<'r, 'c, 'x, 'q, 's, Q, S>
+    where Q: Text<'q>,
+          S: Text<'s>,
+{
+    fn directives(&mut self, directives: &[Directive<'q, Q>]) {
+        for dir in directives {
+            self.rule.enter_directive(&mut self.ctx, dir);
+        }
+    }
+
+    fn variables(&mut self, vars: &[VariableDefinition<'q, Q>]) {
+        for var in vars {
+            self.rule.enter_variable_definition(&mut self.ctx, var);
+            self.directives(&var.directives);
+        }
+    }
+
+    fn document(&mut self, doc: &Document<'q, Q>) {
+        for def in &doc.definitions {
+            match *def {
+                Definition::Operation(ref op) => {
+                    let root = self.ctx.root_type(&op.kind);
+                    self.rule.enter_operation(&mut self.ctx, op);
+                    self.variables(&op.variable_definitions);
+                    self.directives(&op.directives);
+                    self.selection_set(&op.selection_set, root);
+                    self.rule.leave_operation(&mut self.ctx, op);
+                }
+                Definition::Fragment(ref frag) => {
+                    let typ = self.ctx.type_named(&frag.type_condition);
+                    self.rule.enter_fragment(&mut self.ctx, frag);
+                    self.variables(&frag.variable_definitions);
+                    self.directives(&frag.directives);
+                    self.selection_set(&frag.selection_set, typ);
+                    self.rule.leave_fragment(&mut self.ctx, frag);
+                }
+            }
+        }
+        self.rule.leave_document(&mut self.ctx, doc);
+    }
+
+    fn selection_set(&mut self, set: &SelectionSet<'q, Q>,
+        typ: Option<&'x str>)
+    {
+        self.ctx.types.push(typ);
+        for item in &set.items {
+            match *item {
+                Selection::Field(ref field) => {
+                    self.rule.enter_field(&mut self.ctx, field);
+                    self.directives(&field.directives);
+                    if !field.selection_set.items.is_empty() {
+                        let typ = self.ctx.field_type(field.name.as_ref());
+                        self.selection_set(&field.selection_set, typ);
+                    }
+                    self.rule.leave_field(&mut self.ctx, field);
+                }
+                Selection::FragmentSpread(ref spread) => {
+                    self.rule.enter_fragment_spread(&mut self.ctx, spread);
+                    self.directives(&spread.directives);
+                }
+                Selection::InlineFragment(ref frag) => {
+                    let typ = match frag.type_condition {
+                        Some(ref cond) => self.ctx.type_named(cond),
+                        None => self.ctx.parent_type(),
+                    };
+                    self.rule.enter_inline_fragment(&mut self.ctx, frag);
+                    self.directives(&frag.directives);
+                    self.selection_set(&frag.selection_set, typ);
+                    self.rule.leave_inline_fragment(&mut self.ctx, frag);
+                }
+            }
+        }
+        self.ctx.types.pop();
+    }
+}
+
+/// Validates the document with the custom rules
+///
+/// Returns errors of all the rules, in the order of the rules. Use
+/// `validate` to check the rules of the specification.
+pub fn validate_with_rules<'q, Q>(doc: &Document<'q, Q>,
+    rules: &mut [&mut dyn ValidationRule<'q, 'static, Q, &'static str>])
+    -> Result<(), Vec<ValidationError>>
+    where Q: Text<'q>,
+{
+    run(doc, None, rules)
+}
+
+/// Validates the document with the custom rules which may look up types in
+/// the schema, see `validate_with_rules`
+pub fn validate_with_rules_and_schema<'x, 'q, 's, Q, S>(
+    doc: &Document<'q, Q>, schema: &SchemaIndex<'x, 's, S>,
+    rules: &mut [&mut dyn ValidationRule<'q, 's, Q, S>])
+    -> Result<(), Vec<ValidationError>>
+    where Q: Text<'q>,
+          S: Text<'s>,
+{
+    run(doc, Some(schema), rules)
+}
+
+fn run<'x, 'q, 's, Q, S>(doc: &Document<'q, Q>,
+    schema: Option<&SchemaIndex<'x, 's, S>>,
+    rules: &mut [&mut dyn ValidationRule<'q, 's, Q, S>])
+    -> Result<(), Vec<ValidationError>>
+    where Q: Text<'q>,
+          S: Text<'s>,
+{
+    let mut errors = Vec::new();
+    for rule in rules {
+        let name = rule.name();
+        let mut walker = Walker {
+            rule: &mut **rule,
+            ctx: RuleContext {
+                schema,
+                types: Vec::new(),
+                rule: name,
+                errors: &mut errors,
+            },
+        };
+        walker.document(doc);
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::common::Text;
+    use crate::query::{Field, InlineFragment};
+    use crate::schema::SchemaIndex;
+    use crate::validation::{Rule, ValidationError};
+    use crate::{parse_query, parse_schema};
+    use super::{validate_with_rules, validate_with_rules_and_schema};
+    use super::{RuleContext, ValidationRule};
+
+    /// Reports fields with the type they are selected on
+    struct FieldTypes;
+
+    /// Forbids introspection, except `__typename`
+    struct NoIntrospection;
+
+    impl<'q, 's, Q: Text<'q>, S: Text<'s>> ValidationRule<'q, 's, Q, S>
+        for FieldTypes
+    {
+        fn name(&self) -> &'static str { "FieldTypes" }
+        fn enter_field(&mut self, ctx: &mut RuleContext<'_, '_, 's, S>,
+            field: &Field<'q, Q>)
+        {
+            let message = format!("{} on {}", field.name.as_ref(),
+                                  ctx.parent_type().unwrap_or("?"));
+            ctx.report(message, vec![field.position]);
+        }
+        fn enter_inline_fragment(&mut self,
+            ctx: &mut RuleContext<'_, '_, 's, S>,
+            frag: &InlineFragment<'q, Q>)
+        {
+            let message = format!("... in {}",
+                                  ctx.parent_type().unwrap_or("?"));
+            ctx.report(message, vec![frag.position]);
+        }
+    }
+
+    impl<'q, 's, Q: Text<'q>, S: Text<'s>> ValidationRule<'q, 's, Q, S>
+        for NoIntrospection
+    {
+        fn name(&self) -> &'static str { "NoIntrospection" }
+        fn enter_field(&mut self, ctx: &mut RuleContext<'_, '_, 's, S>,
+            field: &Field<'q, Q>)
+        {
+            let name = field.name.as_ref();
+            if name.starts_with("__") && name != "__typename" {
+                ctx.report("Introspection is disabled.",
+                           vec![field.position]);
+            }
+        }
+    }
+
+    fn messages(errors: Vec<ValidationError>) -> Vec<String> {
+        errors.into_iter().map(|e| e.message).collect()
+    }
+
+    #[test]
+    fn without_schema() {
+        let doc = parse_query::<&str>("
+            { a { __typename } __schema { types { name } } }
+        ").unwrap();
+        let errors = validate_with_rules(&doc,
+            &mut [&mut NoIntrospection, &mut FieldTypes]).unwrap_err();
+        assert_eq!(errors[0].rule, Rule::Custom("NoIntrospection"));
+        assert_eq!(errors[0].to_string(),
+                   "Introspection is disabled. at 2:32");
+        assert_eq!(messages(errors)[1..].to_vec(), [
+            "a on ?", "__typename on ?", "__schema on ?", "types on ?",
+            "name on ?",
+        ]);
+        let doc = parse_query::<&str>("{ a }").unwrap();
+        assert!(validate_with_rules(&doc, &mut [&mut NoIntrospection])
+                .is_ok());
+    }
+
+    #[test]
+    fn with_schema() {
+        let schema = parse_schema::<&str>("
+            schema { query: Root }
+            type Root { node: Node }
+            interface Node { id: ID }
+            type User implements Node { id: ID name: String }
+        ").unwrap();
+        let index = SchemaIndex::new(&schema);
+        let doc = parse_query::<&str>("
+            { node { id ... on User { name } ... { id } } }
+            fragment F on User { name missing { x } }
+        ").unwrap();
+        let errors = validate_with_rules_and_schema(&doc, &index,
+            &mut [&mut FieldTypes]).unwrap_err();
+        assert_eq!(messages(errors), [
+            "node on Root", "id on Node", "... in Node", "name on User",
+            "... in Node", "id on Node",
+            "name on User", "missing on User", "x on ?",
+        ]);
+    }
+}
@@ -0,0 +1,414 @@
+use std::collections::BTreeMap;
+
+use crate::coercion;
+use crate::common::{Directive, DirectiveArgument, DirectiveLocation};
+use crate::common::{Text, Type, Value};
+use crate::position::Pos;
+use crate::query::{self, OperationKind};
+use crate::schema::{self, DirectiveDefinition, TypeDefinition, TypeExtension};
+use crate::schema::index::{SchemaIndex, BUILTIN_DIRECTIVE_DEFINITIONS};
+use crate::validation::rules::{selection_directives, walk_selections};
+use crate::validation::typed::first_variable;
+use crate::validation::{Rule, ValidationError};
+
+
+struct Checker<'i, 'x, 's, S>
+    where S: Text<'s>
+{
+    index: &'i SchemaIndex<'x, 's, S>,
+    builtins: SchemaIndex<'i, 'static, &'static str>,
+    errors: Vec<ValidationError>,
+}
+
+impl<'i, 'x, 's, S> Checker<'i, 'x, 's, S>
+    where S: Text<'s>
+{
+    fn directives<'a, T>(&mut self, directives: &[Directive<'a, T>],
+        location: DirectiveLocation)
+        where T: Text<'a>,
+    {
+        let mut used = Vec::new();
+        for dir in directives {
+            let name = dir.name.as_ref();
+            let repeatable = if let Some(def) = self.index.directive(name) {
+                self.directive(dir, def, location)
+            } else if let Some(def) = self.builtins.directive(name) {
+                self.directive(dir, def, location)
+            } else {
+                self.error(Rule::KnownDirectives,
+                    format!("Unknown directive \"@{}\".", name),
+                    vec![dir.position]);
+                continue;
+            };
+            if !repeatable && used.contains(&name) {
+                self.error(Rule::UniqueDirectivesPerLocation,
+                    format!("The directive \"@{}\" can only be used once \
+                             at this location.", name),
+                    vec![dir.position]);
+            }
+            used.push(name);
+        }
+    }
+
+    /// Checks the application of the defined directive, returns whether
+    /// it is repeatable
+    fn directive<'a, 'd, T, D>(&mut self, dir: &Directive<'a, T>,
+        def: &DirectiveDefinition<'d, D>, location: DirectiveLocation)
+        -> bool
+        where T: Text<'a>,
+              D: Text<'d>,
+    {
+        let name = dir.name.as_ref();
+        if !def.locations.contains(&location) {
+            self.error(Rule::KnownDirectives,
+                format!("Directive \"@{}\" may not be used on {}.",
+                        name, location),
+                vec![dir.position]);
+        }
+        for arg in &dir.arguments {
+            let arg_def = def.arguments.iter()
+                .find(|a| a.name.as_ref() == arg.name.as_ref());
+            match arg_def {
+                Some(arg_def) => {
+                    self.value(dir, arg, &arg_def.value_type);
+                }
+                None => {
+                    self.error(Rule::KnownArgumentNames,
+                        format!("Unknown argument {:?} on directive \
+                                 \"@{}\".", arg.name.as_ref(), name),
+                        vec![arg.value_position]);
+                }
+            }
+        }
+        for arg_def in &def.arguments {
+            let required = arg_def.value_type.is_non_null() &&
+                arg_def.default_value.is_none();
+            if required && !dir.arguments.iter()
+                .any(|arg| arg.name.as_ref() == arg_def.name.as_ref())
+            {
+                self.error(Rule::ProvidedRequiredArguments,
+                    format!("Directive \"@{}\" argument {:?} of type \
+                             \"{}\" is required, but it was not provided.",
+                             name, arg_def.name.as_ref(),
+                             arg_def.value_type),
+                    vec![dir.position]);
+            }
+        }
+        def.repeatable
+    }
+
+    fn value<'a, 'd, T, D>(&mut self, dir: &Directive<'a, T>,
+        arg: &DirectiveArgument<'a, T>,
+        expected: &Type<'d, D>)
+        where T: Text<'a>,
+              D: Text<'d>,
+    {
+        // types of the variables are checked against their usages
+        if first_variable(&arg.value).is_some() {
+            return;
+        }
+        let no_variables = BTreeMap::<&str, Value<&str>>::new();
+        let result = coercion::coerce(self.index, &arg.value, expected,
+                                      &no_variables, arg.value_position);
+        for err in result.err().unwrap_or_default() {
+            let message = if err.path.is_empty() {
+                err.message
+            } else {
+                format!("{}: {}", err.path, err.message)
+            };
+            self.error(Rule::ValuesOfCorrectType,
+                format!("Invalid value of argument {:?} of directive \
+                         \"@{}\": {}", arg.name.as_ref(), dir.name.as_ref(),
+                         message),
+                vec![arg.value_position]);
+        }
+    }
+
+    fn error(&mut self, rule: Rule, message: String,
+        positions: Vec<Pos>)
+    {
+        self.errors.push(ValidationError::new(rule, message, positions));
+    }
+
+    fn query<'q, Q>(&mut self, doc: &query::Document<'q, Q>)
+        where Q: Text<'q>,
+    {
+        for def in &doc.definitions {
+            let set = match *def {
+                query::Definition::Operation(ref op) => {
+                    self.directives(&op.directives, match op.kind {
+                        OperationKind::ImplicitQuery | OperationKind::Query
+                        => DirectiveLocation::Query,
+                        OperationKind::Mutation => DirectiveLocation::Mutation,
+                        OperationKind::Subscription
+                        => DirectiveLocation::Subscription,
+                    });
+                    for var in &op.variable_definitions {
+                        self.directives(&var.directives,
+                                        DirectiveLocation::VariableDefinition);
+                    }
+                    &op.selection_set
+                }
+                query::Definition::Fragment(ref frag) => {
+                    self.directives(&frag.directives,
+                                    DirectiveLocation::FragmentDefinition);
+                    &frag.selection_set
+                }
+            };
+            walk_selections(set, &mut |item| {
+                let (directives, location) = selection_directives(item);
+                self.directives(directives, location);
+            });
+        }
+    }
+
+    fn schema<'d, D>(&mut self, doc: &schema::Document<'d, D>)
+        where D: Text<'d>,
+    {
+        use crate::common::DirectiveLocation::*;
+
+        for def in &doc.definitions {
+            match *def {
+                schema::Definition::SchemaDefinition(ref s) => {
+                    self.directives(&s.directives, Schema);
+                }
+                schema::Definition::SchemaExtension(ref s) => {
+                    self.directives(&s.directives, Schema);
+                }
+                schema::Definition::TypeDefinition(ref t) => {
+                    self.directives(t.directives(), match *t {
+                        TypeDefinition::Scalar(_) => Scalar,
+                        TypeDefinition::Object(_) => Object,
+                        TypeDefinition::Interface(_) => Interface,
+                        TypeDefinition::Union(_) => Union,
+                        TypeDefinition::Enum(_) => Enum,
+                        TypeDefinition::InputObject(_) => InputObject,
+                    });
+                    match *t {
+                        TypeDefinition::Object(ref t) => self.fields(&t.fields),
+                        TypeDefinition::Interface(ref t) => {
+                            self.fields(&t.fields)
+                        }
+                        TypeDefinition::Enum(ref t) => self.values(&t.values),
+                        TypeDefinition::InputObject(ref t) => {
+                            self.input_values(&t.fields, InputFieldDefinition)
+                        }
+                        TypeDefinition::Scalar(_) |
+                        TypeDefinition::Union(_) => {}
+                    }
+                }
+                schema::Definition::TypeExtension(ref t) => {
+                    self.directives(t.directives(), match *t {
+                        TypeExtension::Scalar(_) => Scalar,
+                        TypeExtension::Object(_) => Object,
+                        TypeExtension::Interface(_) => Interface,
+                        TypeExtension::Union(_) => Union,
+                        TypeExtension::Enum(_) => Enum,
+                        TypeExtension::InputObject(_) => InputObject,
+                    });
+                    match *t {
+                        TypeExtension::Object(ref t) => self.fields(&t.fields),
+                        TypeExtension::Interface(ref t) => {
+                            self.fields(&t.fields)
+                        }
+                        TypeExtension::Enum(ref t) => self.values(&t.values),
+                        TypeExtension::InputObject(ref t) => {
+                            self.input_values(&t.fields, InputFieldDefinition)
+                        }
+                        TypeExtension::Scalar(_) |
+                        TypeExtension::Union(_) => {}
+                    }
+                }
+                schema::Definition::DirectiveDefinition(ref d) => {
+                    self.input_values(&d.arguments, ArgumentDefinition);
+                }
+                schema::Definition::Custom(_) => {}
+            }
+        }
+    }
+
+    fn fields<'d, D>(&mut self, fields: &[schema::Field<'d, D>])
+        where D: Text<'d>,
+    {
+        for field in fields {
+            self.input_values(&field.arguments,
+                              DirectiveLocation::ArgumentDefinition);
+            self.directives(&field.directives,
+                            DirectiveLocation::FieldDefinition);
+        }
+    }
+
+    fn input_values<'d, D>(&mut self, values: &[schema::InputValue<'d, D>],
+        location: DirectiveLocation)
+        where D: Text<'d>,
+    {
+        for value in values {
+            self.directives(&value.directives, location);
+        }
+    }
+
+    fn values<'d, D>(&mut self, values: &[schema::EnumValue<'d, D>])
+        where D: Text<'d>,
+    {
+        for value in values {
+            self.directives(&value.directives, DirectiveLocation::EnumValue);
+        }
+    }
+}
+
+fn check<'s, S, F>(schema: &schema::Document<'s, S>, walk: F)
+    -> Result<(), Vec<ValidationError>>
+    where S: Text<'s>,
+          F: FnOnce(&mut Checker<'_, '_, 's, S>),
+{
+    let builtins = schema::parse_schema(BUILTIN_DIRECTIVE_DEFINITIONS)
+        .expect("valid builtin directives");
+    let index = SchemaIndex::new(schema);
+    let mut checker = Checker {
+        index: &index,
+        builtins: SchemaIndex::new(&builtins),
+        errors: Vec::new(),
+    };
+    walk(&mut checker);
+    if checker.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(checker.errors)
+    }
+}
+
+/// Validates the directives used in the query against their definitions
+/// in the schema
+///
+/// Directives must be defined (built-in directives don't need to be) and
+/// allowed at the location, their arguments must be defined and have
+/// values of correct types, required arguments must be provided, and
+/// only repeatable directives may be used more than once at the same
+/// location. Values having variables are not checked, as their types
+/// depend on the variable definitions.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::{parse_query, parse_schema};
+/// use edb_graphql_parser::validation::{validate_directives, Rule};
+///
+/// let schema = parse_schema::<&str>("
+///     directive @cached(ttl: Int!) on QUERY
+///     type Query { a: Int }
+/// ").unwrap();
+/// let doc = parse_query::<&str>("
+///     query @cached(ttl: \"long\") { a @cached(ttl: 1) @skip(if: $x) }
+/// ").unwrap();
+/// let errors = validate_directives(&schema, &doc).unwrap_err();
+/// assert_eq!(errors[0].rule, Rule::ValuesOfCorrectType);
+/// assert_eq!(errors[1].to_string(),
+///     "Directive \"@cached\" may not be used on FIELD. at 2:36");
+/// ```
+pub fn validate_directives<'s, 'q, S, Q>(schema: &schema::Document<'s, S>,
+    doc: &query::Document<'q, Q>)
+    -> Result<(), Vec<ValidationError>>
+    where S: Text<'s>,
+          Q: Text<'q>,
+{
+    check(schema, |checker| checker.query(doc))
+}
+
+/// Validates the directives used in the schema document against their
+/// definitions in the `schema`, which may be the same document
+///
+/// The checks are the same as of `validate_directives`.
+pub fn validate_schema_directives<'s, 'd, S, D>(
+    schema: &schema::Document<'s, S>, doc: &schema::Document<'d, D>)
+    -> Result<(), Vec<ValidationError>>
+    where S: Text<'s>,
+          D: Text<'d>,
+{
+    check(schema, |checker| checker.schema(doc))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{parse_query, parse_schema};
+    use super::{validate_directives, validate_schema_directives};
+
+    fn errors(schema: &str, query: &str) -> Vec<String> {
+        let schema = parse_schema::<&str>(schema).unwrap();
+        let doc = parse_query::<&str>(query).unwrap();
+        validate_directives(&schema, &doc).err().unwrap_or_default()
+            .into_iter().map(|e| e.to_string()).collect()
+    }
+
+    #[test]
+    fn query() {
+        let schema = "
+            directive @tag(name: String!) repeatable on FIELD | QUERY
+            directive @once(max: Int = 1, filter: In) on FIELD
+            input In { ids: [ID!]! }
+        ";
+        assert_eq!(errors(schema, r#"
+            query Q($v: Boolean) @tag(name: "q") {
+              a @tag(name: "a") @tag(name: "b") @once @skip(if: $v)
+              b @once(filter: {ids: [1, "2"]}) @include(if: true)
+            }
+        "#), Vec::<String>::new());
+        assert_eq!(errors(schema, r#"
+            mutation @tag(name: "m") {
+              a @unknown @once @once
+              b @tag @once(max: 1.5, min: 2) @skip(if: "yes")
+              c @once(filter: {ids: null})
+              ...F @include
+            }
+            fragment F on T @once { x }
+        "#), [
+            "Directive \"@tag\" may not be used on MUTATION. at 2:22",
+            "Unknown directive \"@unknown\". at 3:17",
+            "The directive \"@once\" can only be used once \
+             at this location. at 3:32",
+            "Directive \"@tag\" argument \"name\" of type \"String!\" is \
+             required, but it was not provided. at 4:17",
+            "Invalid value of argument \"max\" of directive \"@once\": \
+             Int cannot represent value: 1.5 at 4:33",
+            "Unknown argument \"min\" on directive \"@once\". at 4:43",
+            "Invalid value of argument \"if\" of directive \"@skip\": \
+             Boolean cannot represent value: \"yes\" at 4:56",
+            "Invalid value of argument \"filter\" of directive \"@once\": \
+             ids: Expected non-nullable type \"[ID!]!\" not to be null. \
+             at 5:31",
+            "Directive \"@include\" argument \"if\" of type \"Boolean!\" is \
+             required, but it was not provided. at 6:20",
+            "Directive \"@once\" may not be used on FRAGMENT_DEFINITION. \
+             at 8:29",
+        ]);
+    }
+
+    #[test]
+    fn schema() {
+        let doc = parse_schema::<&str>(r#"
+            directive @key(fields: String!) repeatable on OBJECT | INTERFACE
+            directive @meta(x: Int @deprecated) on FIELD_DEFINITION | ENUM_VALUE
+            scalar Date @specifiedBy(url: "https://example.com")
+            type T @key(fields: "id") @key(fields: "name") {
+              id: ID @meta(x: 1)
+              name(arg: Int @deprecated(reason: 1)): String @key
+            }
+            enum E { A @meta B @deprecated @deprecated }
+            extend type T @meta(x: 2)
+            input In { f: Int @deprecated @meta }
+        "#).unwrap();
+        let errors = validate_schema_directives(&doc, &doc).unwrap_err()
+            .into_iter().map(|e| e.to_string()).collect::<Vec<_>>();
+        assert_eq!(errors, [
+            "Invalid value of argument \"reason\" of directive \
+             \"@deprecated\": String cannot represent value: 1 at 7:49",
+            "Directive \"@key\" may not be used on FIELD_DEFINITION. \
+             at 7:61",
+            "Directive \"@key\" argument \"fields\" of type \"String!\" is \
+             required, but it was not provided. at 7:61",
+            "The directive \"@deprecated\" can only be used once \
+             at this location. at 9:44",
+            "Directive \"@meta\" may not be used on OBJECT. at 10:27",
+            "Directive \"@meta\" may not be used on INPUT_FIELD_DEFINITION. \
+             at 11:43",
+        ]);
+    }
+}
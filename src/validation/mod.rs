@@ -0,0 +1,637 @@
+//! Validation of executable documents
+//!
+//! This module implements the validation rules of the specification that
+//! don't require a schema:
+//!
+//! 1. Unique operation names
+//! 2. Lone anonymous operation
+//! 3. Unique fragment names
+//! 4. Known fragment names (no undefined fragments)
+//! 5. No unused fragments
+//! 6. No fragment cycles
+//! 7. Unique argument names
+//! 8. Unique variable names
+//! 9. Unique directives per location
+//! 10. Overlapping fields can be merged (fields of the same selection set
+//!     and of the fields merged with them, without following fragments)
+//! 11. Unique input field names (of object values)
+//! 12. Labels of `@defer` and `@stream` are static strings, unique in the
+//!     document
+//! 13. `@defer` is only used on fragments and `@stream` on fields
+//! 14. `@defer` and `@stream` are not used in subscriptions, unless
+//!     disabled by the `if` argument
+//!
+//! Rules 12-14 are of the incremental delivery proposal.
+//!
+//! Since there is no schema, every directive is considered non-repeatable.
+//! `validate_with_schema` allows repeating directives defined as
+//! `repeatable` in the schema.
+//!
+//! When schema is available, `validate_with_schema` additionally checks
+//! that fields and arguments exist, argument values, variables and their
+//! default values have correct types, fragments are spread on correct
+//! types, leaf fields have no selections while composite ones have, and
+//! `@stream` is only used on list fields.
+//!
+//! `validate_directives` checks the directives of the query against their
+//! definitions in the schema: directives are known and allowed at the
+//! location, their arguments are known, have values of correct types and
+//! required ones are provided, and only repeatable directives are
+//! repeated. `validate_schema_directives` does the same for the
+//! directives used in a schema document.
+//!
+//! Organization-specific policies can be implemented as `ValidationRule`
+//! and checked by `validate_with_rules`, which reports errors with
+//! `Rule::Custom` in the same form as the rules of the specification.
+//!
+//! `validate_schema` checks the schema document itself: names of types,
+//! directives, fields, arguments and enum values are unique, referenced
+//! types exist, interfaces are implemented correctly, unions contain only
+//! object types, input objects don't reference themselves through non-null
+//! fields and directives don't reference themselves.
+//!
+//! With the `introspection` feature enabled, `validate_against_introspection`
+//! validates the query against the introspection result, e.g. downloaded
+//! from the server, in a single call.
+//!
+//! ```rust
+//! # #[cfg(feature = "query")] {
+//! # extern crate edb_graphql_parser;
+//! use edb_graphql_parser::parse_query;
+//! use edb_graphql_parser::validation::{validate, Rule};
+//!
+//! let doc = parse_query::<&str>("{ a } { b }").unwrap();
+//! let errors = validate(&doc).unwrap_err();
+//! assert_eq!(errors.len(), 2);
+//! assert_eq!(errors[0].rule, Rule::LoneAnonymousOperation);
+//! # }
+//! ```
+#[cfg(feature = "query")] pub(crate) mod rules;
+#[cfg(all(feature = "query", feature = "schema"))] mod custom;
+#[cfg(all(feature = "query", feature = "schema"))] mod directives;
+#[cfg(feature = "schema")] mod type_system;
+#[cfg(all(feature = "query", feature = "schema"))] mod typed;
+
+#[cfg(feature = "query")]
+use std::collections::BTreeSet;
+use std::fmt;
+
+use thiserror::Error;
+
+#[cfg(any(feature = "query", feature = "schema"))]
+use crate::common::Text;
+use crate::position::Pos;
+#[cfg(feature = "query")]
+use crate::query::Document;
+#[cfg(feature = "schema")]
+use crate::schema;
+#[cfg(all(feature = "introspection", feature = "query"))]
+use crate::query;
+
+#[cfg(all(feature = "query", feature = "schema"))]
+pub use self::custom::{validate_with_rules, validate_with_rules_and_schema};
+#[cfg(all(feature = "query", feature = "schema"))]
+pub use self::directives::{validate_directives, validate_schema_directives};
+#[cfg(all(feature = "query", feature = "schema"))]
+pub use self::custom::{RuleContext, ValidationRule};
+
+/// A validation rule that was violated
+///
+/// Rules may be added in minor releases, so matches must have a wildcard
+/// arm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Rule {
+    UniqueOperationNames,
+    LoneAnonymousOperation,
+    UniqueFragmentNames,
+    KnownFragmentNames,
+    NoUnusedFragments,
+    NoFragmentCycles,
+    UniqueArgumentNames,
+    UniqueVariableNames,
+    UniqueDirectivesPerLocation,
+    OverlappingFieldsCanBeMerged,
+    UniqueInputFieldNames,
+    KnownOperationTypes,
+    FieldsOnCorrectType,
+    KnownArgumentNames,
+    ProvidedRequiredArguments,
+    ValuesOfCorrectType,
+    KnownTypeNames,
+    FragmentsOnCompositeTypes,
+    PossibleFragmentSpreads,
+    VariablesAreInputTypes,
+    VariablesInAllowedPosition,
+    ScalarLeafs,
+    UniqueTypeNames,
+    UniqueDirectiveNames,
+    UniqueFieldDefinitionNames,
+    UniqueEnumValueNames,
+    UniqueArgumentDefinitionNames,
+    InterfaceImplementations,
+    UnionMemberTypes,
+    InputObjectCircularReferences,
+    NoDirectiveSelfReference,
+    DeferStreamDirectiveLabel,
+    DeferStreamDirectiveLocations,
+    DeferStreamDirectiveOnValidOperations,
+    StreamDirectiveOnListField,
+    KnownDirectives,
+    /// Rule implemented by `ValidationRule`, with its name
+    Custom(&'static str),
+}
+
+/// Error found while validating a document
+///
+/// Positions are sorted in the order of appearance in the document, i.e.
+/// for duplicate names the first definition goes first.
+#[derive(Error, Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    pub rule: Rule,
+    pub message: String,
+    pub positions: Vec<Pos>,
+}
+
+impl Rule {
+    /// Returns the name of the rule as used in the specification
+    pub fn as_str(&self) -> &'static str {
+        use self::Rule::*;
+        match *self {
+            UniqueOperationNames => "UniqueOperationNames",
+            LoneAnonymousOperation => "LoneAnonymousOperation",
+            UniqueFragmentNames => "UniqueFragmentNames",
+            KnownFragmentNames => "KnownFragmentNames",
+            NoUnusedFragments => "NoUnusedFragments",
+            NoFragmentCycles => "NoFragmentCycles",
+            UniqueArgumentNames => "UniqueArgumentNames",
+            UniqueVariableNames => "UniqueVariableNames",
+            UniqueDirectivesPerLocation => "UniqueDirectivesPerLocation",
+            OverlappingFieldsCanBeMerged => "OverlappingFieldsCanBeMerged",
+            UniqueInputFieldNames => "UniqueInputFieldNames",
+            KnownOperationTypes => "KnownOperationTypes",
+            FieldsOnCorrectType => "FieldsOnCorrectType",
+            KnownArgumentNames => "KnownArgumentNames",
+            ProvidedRequiredArguments => "ProvidedRequiredArguments",
+            ValuesOfCorrectType => "ValuesOfCorrectType",
+            KnownTypeNames => "KnownTypeNames",
+            FragmentsOnCompositeTypes => "FragmentsOnCompositeTypes",
+            PossibleFragmentSpreads => "PossibleFragmentSpreads",
+            VariablesAreInputTypes => "VariablesAreInputTypes",
+            VariablesInAllowedPosition => "VariablesInAllowedPosition",
+            ScalarLeafs => "ScalarLeafs",
+            UniqueTypeNames => "UniqueTypeNames",
+            UniqueDirectiveNames => "UniqueDirectiveNames",
+            UniqueFieldDefinitionNames => "UniqueFieldDefinitionNames",
+            UniqueEnumValueNames => "UniqueEnumValueNames",
+            UniqueArgumentDefinitionNames => "UniqueArgumentDefinitionNames",
+            InterfaceImplementations => "InterfaceImplementations",
+            UnionMemberTypes => "UnionMemberTypes",
+            InputObjectCircularReferences => "InputObjectCircularReferences",
+            NoDirectiveSelfReference => "NoDirectiveSelfReference",
+            DeferStreamDirectiveLabel => "DeferStreamDirectiveLabel",
+            DeferStreamDirectiveLocations => "DeferStreamDirectiveLocations",
+            DeferStreamDirectiveOnValidOperations
+            => "DeferStreamDirectiveOnValidOperations",
+            StreamDirectiveOnListField => "StreamDirectiveOnListField",
+            KnownDirectives => "KnownDirectives",
+            Custom(name) => name,
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        let mut first = true;
+        for pos in &self.positions {
+            if first {
+                write!(f, " at {}", pos)?;
+                first = false;
+            } else {
+                write!(f, ", {}", pos)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(any(feature = "query", feature = "schema"))]
+impl ValidationError {
+    pub(crate) fn new(rule: Rule, message: String, positions: Vec<Pos>)
+        -> ValidationError
+    {
+        ValidationError { rule, message, positions }
+    }
+}
+
+/// Validates the document against all schema-independent rules
+///
+/// Returns all the errors found, in the order of the rules listed in the
+/// module documentation.
+#[cfg(feature = "query")]
+pub fn validate<'a, T>(doc: &Document<'a, T>)
+    -> Result<(), Vec<ValidationError>>
+    where T: Text<'a>,
+{
+    let mut errors = Vec::new();
+    validate_rules(doc, &BTreeSet::new(), &mut errors);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(feature = "query")]
+fn validate_rules<'a, T>(doc: &Document<'a, T>, repeatable: &BTreeSet<&str>,
+    errors: &mut Vec<ValidationError>)
+    where T: Text<'a>,
+{
+    rules::unique_operation_names(doc, errors);
+    rules::lone_anonymous_operation(doc, errors);
+    rules::unique_fragment_names(doc, errors);
+    rules::known_fragment_names(doc, errors);
+    rules::no_unused_fragments(doc, errors);
+    rules::no_fragment_cycles(doc, errors);
+    rules::unique_argument_names(doc, errors);
+    rules::unique_variable_names(doc, errors);
+    rules::unique_directives_per_location(doc, repeatable, errors);
+    rules::overlapping_fields_can_be_merged(doc, errors);
+    rules::unique_input_field_names(doc, errors);
+    rules::defer_stream_directive_label(doc, errors);
+    rules::defer_stream_directive_locations(doc, errors);
+    rules::defer_stream_directive_on_valid_operations(doc, errors);
+}
+
+/// Validates the document against the schema
+///
+/// This runs all the rules of `validate` and then the rules that need
+/// a schema. Types used by the document are looked up in the schema
+/// document, including its extensions; built-in scalars don't need to be
+/// defined.
+#[cfg(all(feature = "query", feature = "schema"))]
+pub fn validate_with_schema<'s, 'q, S, Q>(schema: &schema::Document<'s, S>,
+    doc: &Document<'q, Q>)
+    -> Result<(), Vec<ValidationError>>
+    where S: Text<'s>,
+          Q: Text<'q>,
+{
+    let repeatable = schema.definitions.iter()
+        .filter_map(|def| match *def {
+            schema::Definition::DirectiveDefinition(ref d) if d.repeatable => {
+                Some(d.name.as_ref())
+            }
+            _ => None,
+        })
+        .collect();
+    let mut errors = Vec::new();
+    validate_rules(doc, &repeatable, &mut errors);
+    typed::validate(schema, doc, &mut errors);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Validates the schema document against the rules of the type system
+///
+/// Types and directives are looked up with the extensions applied, so
+/// e.g. a field duplicated by `extend type` is reported too. Built-in
+/// scalars don't need to be defined.
+#[cfg(feature = "schema")]
+pub fn validate_schema<'a, T>(doc: &schema::Document<'a, T>)
+    -> Result<(), Vec<ValidationError>>
+    where T: Text<'a>,
+{
+    let mut errors = Vec::new();
+    type_system::validate(doc, &mut errors);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Error returned by `validate_against_introspection`
+#[cfg(all(feature = "introspection", feature = "query"))]
+#[derive(Error, Debug)]
+pub enum IntrospectionValidationError {
+    #[error(transparent)]
+    Introspection(#[from] schema::IntrospectionError),
+    #[error(transparent)]
+    Parse(#[from] query::ParseError),
+    #[error("{}", .0.iter().map(|e| e.to_string())
+                  .collect::<Vec<_>>().join("\n"))]
+    Invalid(Vec<ValidationError>),
+}
+
+/// Parses the query and validates it against the schema in the result of
+/// the introspection query
+///
+/// This is a shortcut for `schema::from_introspection` followed by
+/// `validate_with_schema`. When validating many queries, convert the
+/// introspection once instead.
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::validation::validate_against_introspection;
+///
+/// let introspection = r#"{"data": {"__schema": {
+///     "queryType": {"name": "Query"},
+///     "types": [{"kind": "OBJECT", "name": "Query", "fields": [{
+///         "name": "hello", "args": [],
+///         "type": {"kind": "SCALAR", "name": "String"}
+///     }]}]
+/// }}}"#;
+/// assert!(validate_against_introspection("{ hello }", introspection).is_ok());
+/// assert!(validate_against_introspection("{ bye }", introspection).is_err());
+/// ```
+#[cfg(all(feature = "introspection", feature = "query"))]
+pub fn validate_against_introspection(query: &str, introspection: &str)
+    -> Result<(), IntrospectionValidationError>
+{
+    let schema = schema::from_introspection(introspection)?;
+    let doc = query::parse_query::<&str>(query)?;
+    validate_with_schema(&schema, &doc)
+        .map_err(IntrospectionValidationError::Invalid)
+}
+
+#[cfg(all(test, feature = "query", feature = "schema"))]
+mod test {
+    use crate::{parse_query, parse_schema};
+    use crate::query::{Definition, Document, Value};
+    use super::{validate, validate_with_schema, Rule, ValidationError};
+
+    fn errors(s: &str) -> Vec<ValidationError> {
+        let doc = parse_query::<&str>(s).unwrap();
+        validate(&doc).err().unwrap_or_else(Vec::new)
+    }
+
+    fn rules(s: &str) -> Vec<Rule> {
+        errors(s).into_iter().map(|e| e.rule).collect()
+    }
+
+    #[test]
+    fn valid() {
+        assert_eq!(rules("
+            query A($x: Int) { a(x: $x) @skip(if: true) { ...F } }
+            query B { ...F }
+            fragment F on T { b ... on U { c } }
+        "), vec![]);
+    }
+
+    #[test]
+    fn operation_names() {
+        let errs = errors("query A { a } query A { b }");
+        assert_eq!(errs.len(), 1);
+        assert_eq!(errs[0].rule, Rule::UniqueOperationNames);
+        assert_eq!(errs[0].to_string(),
+            "There can be only one operation named \"A\". at 1:1, 1:15");
+        assert_eq!(rules("{ a } query A { b }"),
+            vec![Rule::LoneAnonymousOperation]);
+    }
+
+    #[test]
+    fn fragments() {
+        assert_eq!(rules("{ ...F } fragment F on T { a } fragment F on T { b }"),
+            vec![Rule::UniqueFragmentNames]);
+        assert_eq!(rules("{ ...F }"), vec![Rule::KnownFragmentNames]);
+        assert_eq!(rules("{ a } fragment F on T { a }"),
+            vec![Rule::NoUnusedFragments]);
+        assert_eq!(rules("{ ...A } fragment A on T { ...B }
+                          fragment B on T { ... on T { ...A } }"),
+            vec![Rule::NoFragmentCycles]);
+        assert_eq!(errors("{ ...A } fragment A on T { ...A }")[0].message,
+            "Cannot spread fragment \"A\" within itself.");
+    }
+
+    #[test]
+    fn unique_names() {
+        assert_eq!(rules("{ a(x: 1, x: 2) }"),
+            vec![Rule::UniqueArgumentNames]);
+        assert_eq!(rules("{ a @dir(x: 1, x: 2) }"),
+            vec![Rule::UniqueArgumentNames]);
+        assert_eq!(rules("query Q($x: Int, $x: Int) { a }"),
+            vec![Rule::UniqueVariableNames]);
+        assert_eq!(rules("{ a @skip(if: true) @skip(if: false) }"),
+            vec![Rule::UniqueDirectivesPerLocation]);
+        assert_eq!(rules("query Q @dir @dir { a }"),
+            vec![Rule::UniqueDirectivesPerLocation]);
+        assert_eq!(rules("query Q($x: Int @dir @dir) { a(x: $x) }"),
+            vec![Rule::UniqueDirectivesPerLocation]);
+        assert_eq!(rules("{ a(x: [{y: 1, z: {y: 1}}]) }"), vec![]);
+        let errs = errors("query Q($v: In = {a: {b: 1, b: 2}}) {
+            a(x: [{y: 1}, {y: 1, y: 2}]) @dir(x: {z: 1, z: 2})
+        }");
+        assert_eq!(errs.iter().map(|e| e.to_string()).collect::<Vec<_>>(), [
+            "There can be only one input field named \"b\". at 1:16",
+            "There can be only one input field named \"y\". at 2:13",
+            "There can be only one input field named \"z\". at 2:50",
+        ]);
+        assert!(errs.iter().all(|e| e.rule == Rule::UniqueInputFieldNames));
+    }
+
+    #[test]
+    fn overlapping_fields() {
+        assert_eq!(rules("{ a: b a: c }"),
+            vec![Rule::OverlappingFieldsCanBeMerged]);
+        assert_eq!(rules("{ x { a(y: 1) } x { a(y: 2) } }"),
+            vec![Rule::OverlappingFieldsCanBeMerged]);
+        assert_eq!(rules("{ a(x: 1, y: $y) a(y: $y, x: 1) @skip(if: $s)
+                           ... on T { a } y: a ... on T { y: b } }"),
+            vec![]);
+    }
+
+    #[test]
+    fn defer_stream() {
+        assert_eq!(rules("
+            query Q($if: Boolean) {
+                a { ... @defer(label: \"a\") { b } c @stream(label: \"c\") }
+                ...F @defer(if: $if)
+            }
+            subscription S($if: Boolean) {
+                a { ... @defer(if: false) { b } ...F @defer(if: $if) }
+            }
+            fragment F on T { d @stream(if: false) }
+        "), vec![]);
+        let errs = errors("
+            query Q($l: String) {
+                a @defer(label: \"x\") { ... @defer(label: $l) { b } }
+                ... @defer(label: \"x\") { c @stream(label: \"x\") }
+                ... @stream { d }
+            }
+        ");
+        assert_eq!(errs.iter().map(|e| e.to_string()).collect::<Vec<_>>(), [
+            "Defer/Stream directive label argument must be \
+             a static string. at 3:58",
+            "Defer/Stream directive label argument must be unique. \
+             at 3:33, 4:35, 4:59",
+            "Directive \"@defer\" may not be used on FIELD. at 3:19",
+            "Directive \"@stream\" may not be used on INLINE_FRAGMENT. \
+             at 5:21",
+        ]);
+        let errs = errors("
+            subscription S { a { ...F ... @defer { b } } }
+            fragment F on T { c @stream(if: true) }
+        ");
+        assert_eq!(errs.iter().map(|e| e.to_string()).collect::<Vec<_>>(), [
+            "Defer directive not supported on subscription operations. \
+             Disable `@defer` by setting the `if` argument to `false`. \
+             at 2:43",
+            "Stream directive not supported on subscription operations. \
+             Disable `@stream` by setting the `if` argument to `false`. \
+             at 3:33",
+        ]);
+        assert!(errs.iter()
+            .all(|e| e.rule == Rule::DeferStreamDirectiveOnValidOperations));
+    }
+
+    const SCHEMA: &str = "
+        type Query {
+            user(id: ID!): User
+            users(filter: Filter, first: Int = 10): [User!]!
+            node(id: ID!): Node
+            search(kind: Kind): [SearchResult]
+        }
+        interface Node { id: ID! }
+        type User implements Node { id: ID! name: String friends: [User] }
+        type Post implements Node { id: ID! title: String }
+        union SearchResult = User | Post
+        enum Kind { USER POST }
+        input Filter { name: String! limit: Int }
+        directive @tag(name: String!) repeatable on FIELD
+        directive @once on FIELD
+    ";
+
+    fn typed(s: &str) -> Vec<Rule> {
+        let schema = parse_schema::<&str>(SCHEMA).unwrap();
+        let doc = parse_query::<&str>(s).unwrap();
+        validate_with_schema(&schema, &doc).err().unwrap_or_else(Vec::new)
+            .into_iter().map(|e| e.rule).collect()
+    }
+
+    #[test]
+    fn typed_valid() {
+        assert_eq!(typed(r#"
+            query Q($id: ID!, $f: Filter) {
+                user(id: $id) { id name friends { ...F } }
+                users(filter: $f) { __typename }
+                node(id: "1") { id ... on User { name } ...F }
+                search(kind: USER) { ... on Post { title } }
+                __schema { types { name } }
+            }
+            fragment F on User { name }
+        "#), vec![]);
+    }
+
+    #[test]
+    fn typed_fields() {
+        assert_eq!(typed("{ user(id: 1) { email } }"),
+            vec![Rule::FieldsOnCorrectType]);
+        assert_eq!(typed("{ user(id: 1) { name { x } } }"),
+            vec![Rule::ScalarLeafs]);
+        assert_eq!(typed("{ user(id: 1) }"), vec![Rule::ScalarLeafs]);
+        assert_eq!(typed("{ search { id } }"),
+            vec![Rule::FieldsOnCorrectType]);
+        assert_eq!(typed("mutation { a }"),
+            vec![Rule::KnownOperationTypes]);
+    }
+
+    #[test]
+    fn typed_stream() {
+        assert_eq!(typed("{ users @stream(initialCount: 1) { id } }"),
+            vec![]);
+        assert_eq!(typed("{ user(id: 1) @stream { id } }"),
+            vec![Rule::StreamDirectiveOnListField]);
+    }
+
+    #[test]
+    fn typed_arguments() {
+        assert_eq!(typed("{ user { id } }"),
+            vec![Rule::ProvidedRequiredArguments]);
+        assert_eq!(typed("{ user(id: 1, x: 2) { id } }"),
+            vec![Rule::KnownArgumentNames]);
+        assert_eq!(typed("{ user(id: true) { id } }"),
+            vec![Rule::ValuesOfCorrectType]);
+        assert_eq!(typed("{ user(id: null) { id } }"),
+            vec![Rule::ValuesOfCorrectType]);
+        assert_eq!(typed("{ search(kind: OTHER) { __typename } }"),
+            vec![Rule::ValuesOfCorrectType]);
+        assert_eq!(typed(r#"{ users(filter: {limit: 1}) { id } }"#),
+            vec![Rule::ValuesOfCorrectType]);
+        assert_eq!(typed(r#"{ users(filter: {name: "x", y: 1}) { id } }"#),
+            vec![Rule::ValuesOfCorrectType]);
+        assert_eq!(typed(r#"{ users(first: 10000000000) { id } }"#),
+            vec![Rule::ValuesOfCorrectType]);
+    }
+
+    #[test]
+    fn typed_fragments() {
+        assert_eq!(typed("{ node(id: 1) { ... on Unknown { id } } }"),
+            vec![Rule::KnownTypeNames]);
+        assert_eq!(typed("{ node(id: 1) { ...F } } fragment F on Kind { a }"),
+            vec![Rule::FragmentsOnCompositeTypes]);
+        assert_eq!(typed("{ user(id: 1) { ... on Post { id } } }"),
+            vec![Rule::PossibleFragmentSpreads]);
+        assert_eq!(typed("{ user(id: 1) { ...F } } fragment F on Post { id }"),
+            vec![Rule::PossibleFragmentSpreads]);
+    }
+
+    #[test]
+    fn typed_variables() {
+        assert_eq!(typed("query Q($u: User) { a }"),
+            vec![Rule::VariablesAreInputTypes, Rule::FieldsOnCorrectType]);
+        assert_eq!(typed("query Q($id: ID) { user(id: $id) { id } }"),
+            vec![Rule::VariablesInAllowedPosition]);
+        assert_eq!(typed("query Q($id: ID = 1) { user(id: $id) { id } }"),
+            vec![]);
+        assert_eq!(typed("query Q($id: Int!) { user(id: $id) { id } }"),
+            vec![Rule::VariablesInAllowedPosition]);
+        assert_eq!(typed("query Q($id: String) { ...F }
+                          fragment F on Query { user(id: $id) { id } }"),
+            vec![Rule::VariablesInAllowedPosition]);
+    }
+
+    #[test]
+    fn typed_default_values() {
+        fn errors<'a>(doc: &Document<'a, &'a str>) -> Vec<String> {
+            let schema = parse_schema::<&str>(SCHEMA).unwrap();
+            validate_with_schema(&schema, doc).err().unwrap_or_else(Vec::new)
+                .into_iter().map(|e| e.to_string()).collect()
+        }
+        assert_eq!(typed(r#"query Q($f: Filter = {name: "x"}, $k: [Kind] = USER)
+                            { users(filter: $f) { id } }"#), vec![]);
+        let mut doc = parse_query::<&str>(r#"
+            query Q($f: Filter = {limit: 1.5}, $k: Kind = "USER") { a }
+        "#).unwrap();
+        assert_eq!(errors(&doc), [
+            "Invalid default value of variable \"$f\": Field \"Filter.name\" \
+             of required type \"String!\" was not provided. at 2:21",
+            "Invalid default value of variable \"$f\": limit: Int cannot \
+             represent value: 1.5 at 2:21",
+            "Invalid default value of variable \"$k\": Enum \"Kind\" cannot \
+             represent non-enum value: \"USER\". at 2:48",
+            "Cannot query field \"a\" on type \"Query\". at 2:69",
+        ]);
+        if let Definition::Operation(ref mut op) = doc.definitions[0] {
+            let var = &mut op.variable_definitions[0];
+            var.default_value.as_mut().unwrap().value =
+                Value::Object(vec![("name", Value::Variable("n"))]);
+        }
+        assert_eq!(errors(&doc)[0],
+            "Default value of variable \"$f\" must be constant, found \
+             variable \"$n\". at 2:21");
+    }
+
+    #[test]
+    fn typed_repeatable_directives() {
+        assert_eq!(typed(r#"{ a: user(id: 1) @tag(name: "a") @tag(name: "b") {
+            id @once @once
+        } }"#),
+            vec![Rule::UniqueDirectivesPerLocation]);
+        assert_eq!(rules(r#"{ user @tag(name: "a") @tag(name: "b") }"#),
+            vec![Rule::UniqueDirectivesPerLocation]);
+    }
+}
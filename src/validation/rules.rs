@@ -0,0 +1,667 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::common::{Directive, DirectiveLocation, Text, Value};
+use crate::position::Pos;
+use crate::query::{Document, Definition, Operation, FragmentDefinition};
+use crate::query::{OperationKind, Selection, SelectionSet};
+use crate::validation::{Rule, ValidationError};
+
+
+pub(crate) fn walk_selections<'x, 'a, T, F>(set: &'x SelectionSet<'a, T>,
+    f: &mut F)
+    where T: Text<'a>,
+          F: FnMut(&'x Selection<'a, T>),
+{
+    for item in &set.items {
+        f(item);
+        match *item {
+            Selection::Field(ref fld) => walk_selections(&fld.selection_set, f),
+            Selection::InlineFragment(ref frag)
+            => walk_selections(&frag.selection_set, f),
+            Selection::FragmentSpread(..) => {}
+        }
+    }
+}
+
+fn operations<'x, 'a, T>(doc: &'x Document<'a, T>)
+    -> impl Iterator<Item=&'x Operation<'a, T>>
+    where T: Text<'a>,
+{
+    doc.definitions.iter().filter_map(|def| match *def {
+        Definition::Operation(ref op) => Some(op),
+        Definition::Fragment(..) => None,
+    })
+}
+
+fn fragments<'x, 'a, T>(doc: &'x Document<'a, T>)
+    -> impl Iterator<Item=&'x FragmentDefinition<'a, T>>
+    where T: Text<'a>,
+{
+    doc.definitions.iter().filter_map(|def| match *def {
+        Definition::Operation(..) => None,
+        Definition::Fragment(ref frag) => Some(frag),
+    })
+}
+
+fn definition_selections<'x, 'a, T>(def: &'x Definition<'a, T>)
+    -> &'x SelectionSet<'a, T>
+    where T: Text<'a>,
+{
+    match *def {
+        Definition::Operation(ref op) => &op.selection_set,
+        Definition::Fragment(ref frag) => &frag.selection_set,
+    }
+}
+
+/// Returns fragment spreads of a selection set (not following fragments)
+fn spreads<'x, 'a, T>(set: &'x SelectionSet<'a, T>) -> Vec<(&'x str, Pos)>
+    where T: Text<'a>,
+{
+    let mut result = Vec::new();
+    walk_selections(set, &mut |item| {
+        if let Selection::FragmentSpread(ref spread) = *item {
+            result.push((spread.fragment_name.as_ref(), spread.position));
+        }
+    });
+    result
+}
+
+/// Pushes an error for each name that is seen more than once
+fn report_duplicates<'x, I, F>(items: I, rule: Rule,
+    errors: &mut Vec<ValidationError>, message: F)
+    where I: IntoIterator<Item=(&'x str, Pos)>,
+          F: Fn(&str) -> String,
+{
+    let mut seen = BTreeMap::<&str, Vec<Pos>>::new();
+    let mut order = Vec::new();
+    for (name, pos) in items {
+        let positions = seen.entry(name).or_default();
+        if positions.len() == 1 {
+            order.push(name);
+        }
+        positions.push(pos);
+    }
+    for name in order {
+        errors.push(ValidationError::new(rule, message(name),
+            seen.remove(name).unwrap()));
+    }
+}
+
+fn check_directives<'a, T>(directives: &[Directive<'a, T>],
+    errors: &mut Vec<ValidationError>)
+    where T: Text<'a>,
+{
+    for dir in directives {
+        report_duplicates(
+            dir.arguments.iter()
+                .map(|arg| (arg.name.as_ref(), arg.value_position)),
+            Rule::UniqueArgumentNames, errors,
+            |name| format!("There can be only one argument named {:?}.",
+                           name));
+    }
+}
+
+fn check_directive_names<'a, T>(directives: &[Directive<'a, T>],
+    repeatable: &BTreeSet<&str>, errors: &mut Vec<ValidationError>)
+    where T: Text<'a>,
+{
+    report_duplicates(
+        directives.iter()
+            .filter(|dir| !repeatable.contains(dir.name.as_ref()))
+            .map(|dir| (dir.name.as_ref(), dir.position)),
+        Rule::UniqueDirectivesPerLocation, errors,
+        |name| format!("The directive \"@{}\" can only be used once \
+                        at this location.", name));
+}
+
+/// Returns `@defer` and `@stream` directives of the list
+fn incremental<'x, 'a, T>(directives: &'x [Directive<'a, T>])
+    -> impl Iterator<Item=&'x Directive<'a, T>>
+    where T: Text<'a>,
+{
+    directives.iter()
+        .filter(|dir| matches!(dir.name.as_ref(), "defer" | "stream"))
+}
+
+/// Returns directives of the selection and its location
+pub(crate) fn selection_directives<'x, 'a, T>(item: &'x Selection<'a, T>)
+    -> (&'x [Directive<'a, T>], DirectiveLocation)
+    where T: Text<'a>,
+{
+    match *item {
+        Selection::Field(ref fld) => {
+            (&fld.directives, DirectiveLocation::Field)
+        }
+        Selection::FragmentSpread(ref spread) => {
+            (&spread.directives, DirectiveLocation::FragmentSpread)
+        }
+        Selection::InlineFragment(ref frag) => {
+            (&frag.directives, DirectiveLocation::InlineFragment)
+        }
+    }
+}
+
+/// Returns `false` if the `if` argument of the directive is `false` or
+/// a variable which may be `false`
+fn if_argument_cannot_be_false<'a, T>(dir: &Directive<'a, T>) -> bool
+    where T: Text<'a>,
+{
+    !matches!(dir.argument("if").map(|arg| &arg.value),
+              Some(&Value::Boolean(false)) | Some(&Value::Variable(_)))
+}
+
+pub fn unique_operation_names<'a, T>(doc: &Document<'a, T>,
+    errors: &mut Vec<ValidationError>)
+    where T: Text<'a>,
+{
+    report_duplicates(
+        operations(doc)
+            .filter_map(|op| op.name.as_ref().map(|n| (n.as_ref(), op.position))),
+        Rule::UniqueOperationNames, errors,
+        |name| format!("There can be only one operation named {:?}.", name));
+}
+
+pub fn lone_anonymous_operation<'a, T>(doc: &Document<'a, T>,
+    errors: &mut Vec<ValidationError>)
+    where T: Text<'a>,
+{
+    if operations(doc).count() < 2 {
+        return;
+    }
+    for op in operations(doc).filter(|op| op.name.is_none()) {
+        errors.push(ValidationError::new(Rule::LoneAnonymousOperation,
+            "This anonymous operation must be the only defined operation."
+                .into(),
+            vec![op.position]));
+    }
+}
+
+pub fn unique_fragment_names<'a, T>(doc: &Document<'a, T>,
+    errors: &mut Vec<ValidationError>)
+    where T: Text<'a>,
+{
+    report_duplicates(
+        fragments(doc).map(|frag| (frag.name.as_ref(), frag.position)),
+        Rule::UniqueFragmentNames, errors,
+        |name| format!("There can be only one fragment named {:?}.", name));
+}
+
+pub fn known_fragment_names<'a, T>(doc: &Document<'a, T>,
+    errors: &mut Vec<ValidationError>)
+    where T: Text<'a>,
+{
+    let known = fragments(doc)
+        .map(|frag| frag.name.as_ref())
+        .collect::<BTreeSet<_>>();
+    for def in &doc.definitions {
+        for (name, pos) in spreads(definition_selections(def)) {
+            if !known.contains(name) {
+                errors.push(ValidationError::new(Rule::KnownFragmentNames,
+                    format!("Unknown fragment {:?}.", name),
+                    vec![pos]));
+            }
+        }
+    }
+}
+
+pub fn no_unused_fragments<'a, T>(doc: &Document<'a, T>,
+    errors: &mut Vec<ValidationError>)
+    where T: Text<'a>,
+{
+    let mut fragment_spreads = BTreeMap::new();
+    for frag in fragments(doc) {
+        fragment_spreads.entry(frag.name.as_ref())
+            .or_insert_with(|| spreads(&frag.selection_set));
+    }
+    let mut used = BTreeSet::new();
+    let mut queue = Vec::new();
+    for op in operations(doc) {
+        queue.extend(spreads(&op.selection_set).into_iter().map(|(n, _)| n));
+    }
+    while let Some(name) = queue.pop() {
+        if used.insert(name) {
+            if let Some(next) = fragment_spreads.get(name) {
+                queue.extend(next.iter().map(|&(n, _)| n));
+            }
+        }
+    }
+    for frag in fragments(doc) {
+        if !used.contains(frag.name.as_ref()) {
+            errors.push(ValidationError::new(Rule::NoUnusedFragments,
+                format!("Fragment {:?} is never used.", frag.name.as_ref()),
+                vec![frag.position]));
+        }
+    }
+}
+
+struct CycleDetector<'x> {
+    spreads: BTreeMap<&'x str, Vec<(&'x str, Pos)>>,
+    visited: BTreeSet<&'x str>,
+    path: Vec<(&'x str, Pos)>,
+    path_index: BTreeMap<&'x str, usize>,
+}
+
+impl<'x> CycleDetector<'x> {
+    fn detect(&mut self, fragment: &'x str, errors: &mut Vec<ValidationError>)
+    {
+        if !self.visited.insert(fragment) {
+            return;
+        }
+        let spreads = match self.spreads.get(fragment) {
+            Some(spreads) if !spreads.is_empty() => spreads.clone(),
+            _ => return,
+        };
+        self.path_index.insert(fragment, self.path.len());
+        for (name, pos) in spreads {
+            match self.path_index.get(name) {
+                None => {
+                    self.path.push((name, pos));
+                    if !self.visited.contains(name) {
+                        self.detect(name, errors);
+                    }
+                    self.path.pop();
+                }
+                Some(&idx) => {
+                    let cycle = &self.path[idx..];
+                    let via = cycle.iter()
+                        .map(|&(n, _)| format!("{:?}", n))
+                        .collect::<Vec<_>>();
+                    let message = if via.is_empty() {
+                        format!("Cannot spread fragment {:?} within itself.",
+                                name)
+                    } else {
+                        format!("Cannot spread fragment {:?} within itself \
+                                 via {}.", name, via.join(", "))
+                    };
+                    let mut positions = cycle.iter()
+                        .map(|&(_, p)| p).collect::<Vec<_>>();
+                    positions.push(pos);
+                    errors.push(ValidationError::new(Rule::NoFragmentCycles,
+                        message, positions));
+                }
+            }
+        }
+        self.path_index.remove(fragment);
+    }
+}
+
+pub fn no_fragment_cycles<'a, T>(doc: &Document<'a, T>,
+    errors: &mut Vec<ValidationError>)
+    where T: Text<'a>,
+{
+    let mut detector = CycleDetector {
+        spreads: BTreeMap::new(),
+        visited: BTreeSet::new(),
+        path: Vec::new(),
+        path_index: BTreeMap::new(),
+    };
+    for frag in fragments(doc) {
+        detector.spreads.entry(frag.name.as_ref())
+            .or_insert_with(|| spreads(&frag.selection_set));
+    }
+    for frag in fragments(doc) {
+        detector.detect(frag.name.as_ref(), errors);
+    }
+}
+
+pub fn unique_argument_names<'a, T>(doc: &Document<'a, T>,
+    errors: &mut Vec<ValidationError>)
+    where T: Text<'a>,
+{
+    for def in &doc.definitions {
+        match *def {
+            Definition::Operation(ref op) => {
+                check_directives(&op.directives, errors)
+            }
+            Definition::Fragment(ref frag) => {
+                check_directives(&frag.directives, errors)
+            }
+        }
+        walk_selections(definition_selections(def), &mut |item| match *item {
+            Selection::Field(ref fld) => {
+                report_duplicates(
+                    fld.arguments.iter()
+                        .map(|(name, _)| (name.as_ref(), fld.position)),
+                    Rule::UniqueArgumentNames, errors,
+                    |name| format!("There can be only one argument \
+                                    named {:?}.", name));
+                check_directives(&fld.directives, errors);
+            }
+            Selection::FragmentSpread(ref spread) => {
+                check_directives(&spread.directives, errors);
+            }
+            Selection::InlineFragment(ref frag) => {
+                check_directives(&frag.directives, errors);
+            }
+        });
+    }
+}
+
+pub fn unique_variable_names<'a, T>(doc: &Document<'a, T>,
+    errors: &mut Vec<ValidationError>)
+    where T: Text<'a>,
+{
+    for op in operations(doc) {
+        report_duplicates(
+            op.variable_definitions.iter()
+                .map(|var| (var.name.as_ref(), var.position)),
+            Rule::UniqueVariableNames, errors,
+            |name| format!("There can be only one variable named \"${}\".",
+                           name));
+    }
+}
+
+pub fn unique_directives_per_location<'a, T>(doc: &Document<'a, T>,
+    repeatable: &BTreeSet<&str>, errors: &mut Vec<ValidationError>)
+    where T: Text<'a>,
+{
+    for def in &doc.definitions {
+        match *def {
+            Definition::Operation(ref op) => {
+                check_directive_names(&op.directives, repeatable, errors);
+                for var in &op.variable_definitions {
+                    check_directive_names(&var.directives, repeatable, errors);
+                }
+            }
+            Definition::Fragment(ref frag) => {
+                check_directive_names(&frag.directives, repeatable, errors)
+            }
+        }
+        walk_selections(definition_selections(def), &mut |item| match *item {
+            Selection::Field(ref fld) => {
+                check_directive_names(&fld.directives, repeatable, errors)
+            }
+            Selection::FragmentSpread(ref spread) => {
+                check_directive_names(&spread.directives, repeatable, errors)
+            }
+            Selection::InlineFragment(ref frag) => {
+                check_directive_names(&frag.directives, repeatable, errors)
+            }
+        });
+    }
+}
+
+pub fn overlapping_fields_can_be_merged<'a, T>(doc: &Document<'a, T>,
+    errors: &mut Vec<ValidationError>)
+    where T: Text<'a>,
+{
+    for def in &doc.definitions {
+        check_overlapping(&[definition_selections(def)], errors);
+    }
+}
+
+/// Checks fields of the selection sets that are merged in the response
+///
+/// Fields are compared within the selection set and the sets of the fields
+/// with the same response key, fragments are not followed.
+fn check_overlapping<'a, T>(sets: &[&SelectionSet<'a, T>],
+    errors: &mut Vec<ValidationError>)
+    where T: Text<'a>,
+{
+    let mut keys = Vec::new();
+    let mut groups = BTreeMap::<_, Vec<_>>::new();
+    for set in sets {
+        for item in &set.items {
+            match *item {
+                Selection::Field(ref fld) => {
+                    let key = fld.alias.as_ref().unwrap_or(&fld.name).as_ref();
+                    if !groups.contains_key(key) {
+                        keys.push(key);
+                    }
+                    groups.entry(key).or_default().push(fld);
+                }
+                Selection::InlineFragment(ref frag) => {
+                    check_overlapping(&[&frag.selection_set], errors);
+                }
+                Selection::FragmentSpread(..) => {}
+            }
+        }
+    }
+    for key in keys {
+        let fields = &groups[key];
+        let first = fields[0];
+        let mut children = Vec::new();
+        for fld in fields {
+            let reason = if fld.name != first.name {
+                format!("{:?} and {:?} are different fields",
+                        first.name.as_ref(), fld.name.as_ref())
+            } else if !same_arguments(&first.arguments, &fld.arguments) {
+                "they have differing arguments".into()
+            } else {
+                children.push(&fld.selection_set);
+                continue;
+            };
+            errors.push(ValidationError::new(
+                Rule::OverlappingFieldsCanBeMerged,
+                format!("Fields {:?} conflict because {}. Use different \
+                         aliases on the fields to fetch both if this was \
+                         intentional.", key, reason),
+                vec![first.position, fld.position]));
+        }
+        if children.len() > 1 {
+            check_overlapping(&children, errors);
+        }
+    }
+}
+
+pub fn unique_input_field_names<'a, T>(doc: &Document<'a, T>,
+    errors: &mut Vec<ValidationError>)
+    where T: Text<'a>,
+{
+    for def in &doc.definitions {
+        match *def {
+            Definition::Operation(ref op) => {
+                for var in &op.variable_definitions {
+                    if let Some(ref default) = var.default_value {
+                        check_input_fields(&default.value, default.span.0,
+                                           errors);
+                    }
+                    check_directive_values(&var.directives, errors);
+                }
+                check_directive_values(&op.directives, errors);
+            }
+            Definition::Fragment(ref frag) => {
+                check_directive_values(&frag.directives, errors);
+            }
+        }
+        walk_selections(definition_selections(def), &mut |item| match *item {
+            Selection::Field(ref fld) => {
+                for (_, value) in &fld.arguments {
+                    check_input_fields(value, fld.position, errors);
+                }
+                check_directive_values(&fld.directives, errors);
+            }
+            Selection::FragmentSpread(ref spread) => {
+                for (_, value) in &spread.arguments {
+                    check_input_fields(value, spread.position, errors);
+                }
+                check_directive_values(&spread.directives, errors);
+            }
+            Selection::InlineFragment(ref frag) => {
+                check_directive_values(&frag.directives, errors);
+            }
+        });
+    }
+}
+
+fn check_directive_values<'a, T>(directives: &[Directive<'a, T>],
+    errors: &mut Vec<ValidationError>)
+    where T: Text<'a>,
+{
+    for arg in directives.iter().flat_map(|dir| &dir.arguments) {
+        check_input_fields(&arg.value, arg.value_position, errors);
+    }
+}
+
+/// Reports duplicate fields of the object values once, at the position of
+/// the argument, as values don't store positions
+fn check_input_fields<'a, T>(value: &Value<'a, T>, position: Pos,
+    errors: &mut Vec<ValidationError>)
+    where T: Text<'a>,
+{
+    match *value {
+        Value::Object(ref fields) => {
+            let mut seen = BTreeSet::new();
+            let mut reported = BTreeSet::new();
+            for (name, _) in fields {
+                let name = name.as_ref();
+                if !seen.insert(name) && reported.insert(name) {
+                    errors.push(ValidationError::new(
+                        Rule::UniqueInputFieldNames,
+                        format!("There can be only one input field \
+                                 named {:?}.", name),
+                        vec![position]));
+                }
+            }
+            for (_, value) in fields {
+                check_input_fields(value, position, errors);
+            }
+        }
+        Value::List(ref items) => {
+            for item in items {
+                check_input_fields(item, position, errors);
+            }
+        }
+        _ => {}
+    }
+}
+
+pub(crate) fn same_arguments<'a, T>(a: &[(T::Value, Value<'a, T>)],
+    b: &[(T::Value, Value<'a, T>)])
+    -> bool
+    where T: Text<'a>,
+{
+    a.len() == b.len() && sorted_arguments(a) == sorted_arguments(b)
+}
+
+fn sorted_arguments<'x, 'a, T>(args: &'x [(T::Value, Value<'a, T>)])
+    -> Vec<(&'x str, String)>
+    where T: Text<'a>,
+{
+    let mut args = args.iter()
+        .map(|(name, value)| (name.as_ref(), value.to_string()))
+        .collect::<Vec<_>>();
+    args.sort();
+    args
+}
+
+pub fn defer_stream_directive_label<'a, T>(doc: &Document<'a, T>,
+    errors: &mut Vec<ValidationError>)
+    where T: Text<'a>,
+{
+    let mut labels = Vec::new();
+    for def in &doc.definitions {
+        walk_selections(definition_selections(def), &mut |item| {
+            let (directives, _) = selection_directives(item);
+            for dir in incremental(directives) {
+                let arg = match dir.argument("label") {
+                    Some(arg) => arg,
+                    None => continue,
+                };
+                match arg.value {
+                    Value::String(ref label) => {
+                        labels.push((label.as_ref(), arg.value_position));
+                    }
+                    Value::Variable(_) => {
+                        errors.push(ValidationError::new(
+                            Rule::DeferStreamDirectiveLabel,
+                            "Defer/Stream directive label argument must be \
+                             a static string.".into(),
+                            vec![arg.value_position]));
+                    }
+                    _ => {}
+                }
+            }
+        });
+    }
+    report_duplicates(labels, Rule::DeferStreamDirectiveLabel, errors,
+        |_| "Defer/Stream directive label argument must be unique.".into());
+}
+
+pub fn defer_stream_directive_locations<'a, T>(doc: &Document<'a, T>,
+    errors: &mut Vec<ValidationError>)
+    where T: Text<'a>,
+{
+    let mut check = |directives: &[Directive<'a, T>], location| {
+        for dir in incremental(directives) {
+            let allowed = match dir.name.as_ref() {
+                "defer" => matches!(location,
+                    DirectiveLocation::FragmentSpread |
+                    DirectiveLocation::InlineFragment),
+                _ => location == DirectiveLocation::Field,
+            };
+            if !allowed {
+                errors.push(ValidationError::new(
+                    Rule::DeferStreamDirectiveLocations,
+                    format!("Directive \"@{}\" may not be used on {}.",
+                            dir.name.as_ref(), location),
+                    vec![dir.position]));
+            }
+        }
+    };
+    for def in &doc.definitions {
+        match *def {
+            Definition::Operation(ref op) => {
+                check(&op.directives, match op.kind {
+                    OperationKind::ImplicitQuery | OperationKind::Query
+                    => DirectiveLocation::Query,
+                    OperationKind::Mutation => DirectiveLocation::Mutation,
+                    OperationKind::Subscription
+                    => DirectiveLocation::Subscription,
+                });
+                for var in &op.variable_definitions {
+                    check(&var.directives,
+                          DirectiveLocation::VariableDefinition);
+                }
+            }
+            Definition::Fragment(ref frag) => {
+                check(&frag.directives, DirectiveLocation::FragmentDefinition)
+            }
+        }
+        walk_selections(definition_selections(def), &mut |item| {
+            let (directives, location) = selection_directives(item);
+            check(directives, location);
+        });
+    }
+}
+
+pub fn defer_stream_directive_on_valid_operations<'a, T>(
+    doc: &Document<'a, T>, errors: &mut Vec<ValidationError>)
+    where T: Text<'a>,
+{
+    let subscriptions = operations(doc)
+        .filter(|op| op.kind == OperationKind::Subscription);
+    for op in subscriptions {
+        let mut visited = BTreeSet::new();
+        let mut queue = vec![&op.selection_set];
+        while let Some(set) = queue.pop() {
+            walk_selections(set, &mut |item| {
+                let (directives, _) = selection_directives(item);
+                let disabled = incremental(directives)
+                    .filter(|dir| if_argument_cannot_be_false(dir));
+                for dir in disabled {
+                    let name = if dir.name.as_ref() == "defer" {
+                        "Defer"
+                    } else {
+                        "Stream"
+                    };
+                    errors.push(ValidationError::new(
+                        Rule::DeferStreamDirectiveOnValidOperations,
+                        format!("{} directive not supported on subscription \
+                                 operations. Disable `@{}` by setting the \
+                                 `if` argument to `false`.",
+                                 name, dir.name.as_ref()),
+                        vec![dir.position]));
+                }
+                if let Selection::FragmentSpread(ref spread) = *item {
+                    let name = spread.fragment_name.as_ref();
+                    if visited.insert(name) {
+                        if let Some(frag) = doc.fragment(name) {
+                            queue.push(&frag.selection_set);
+                        }
+                    }
+                }
+            });
+        }
+    }
+}
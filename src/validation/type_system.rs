@@ -0,0 +1,765 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::common::{Directive, Text, Type};
+use crate::position::Pos;
+use crate::schema::{Definition, Document, DirectiveDefinition, EnumValue};
+use crate::schema::{Field, InputValue, TypeDefinition, TypeExtension};
+use crate::schema::index::{SchemaIndex, TypeEntry, TypeKind};
+use crate::schema::index::BUILTIN_SCALARS;
+use crate::validation::{Rule, ValidationError};
+
+
+struct Checker<'i, 'x, 'a, T>
+    where T: Text<'a>
+{
+    doc: &'x Document<'a, T>,
+    index: &'i SchemaIndex<'x, 'a, T>,
+    errors: Vec<ValidationError>,
+}
+
+/// Search for the uses of the directive in the definitions reachable from
+/// another directive
+struct Reach<'c, 'x, 'a, T>
+    where T: Text<'a>
+{
+    index: &'c SchemaIndex<'x, 'a, T>,
+    target: &'x str,
+    types: BTreeSet<&'x str>,
+    directives: BTreeSet<&'x str>,
+}
+
+fn entry_position<'x, 'a, T>(entry: &TypeEntry<'x, 'a, T>) -> Pos
+    where T: Text<'a>,
+{
+    entry.definition.map(|def| def.position())
+        .or_else(|| entry.extensions.first().map(|ext| ext.position()))
+        .unwrap_or_default()
+}
+
+fn enum_values<'x, 'a, T>(entry: &TypeEntry<'x, 'a, T>)
+    -> Vec<&'x EnumValue<'a, T>>
+    where T: Text<'a>,
+{
+    let mut values = Vec::new();
+    if let Some(TypeDefinition::Enum(e)) = entry.definition {
+        values.extend(&e.values);
+    }
+    for ext in &entry.extensions {
+        if let TypeExtension::Enum(ref e) = **ext {
+            values.extend(&e.values);
+        }
+    }
+    values
+}
+
+fn type_directives<'x, 'a, T>(entry: &TypeEntry<'x, 'a, T>)
+    -> Vec<&'x Directive<'a, T>>
+    where T: Text<'a>,
+{
+    let mut result = Vec::new();
+    if let Some(def) = entry.definition {
+        result.extend(def.directives());
+    }
+    for ext in &entry.extensions {
+        result.extend(ext.directives());
+    }
+    result
+}
+
+fn same_type<'a, T>(a: &Type<'a, T>, b: &Type<'a, T>) -> bool
+    where T: Text<'a>,
+{
+    match (a, b) {
+        (Type::NamedType(a), Type::NamedType(b)) => a == b,
+        (Type::ListType(a), Type::ListType(b)) => same_type(a, b),
+        (Type::NonNullType(a), Type::NonNullType(b)) => same_type(a, b),
+        _ => false,
+    }
+}
+
+/// Reports the items having the same name as a preceding one
+fn duplicates<'x, I, F>(items: I, mut report: F)
+    where I: IntoIterator<Item=(&'x str, Pos)>,
+          F: FnMut(&'x str, Pos, Pos),
+{
+    let mut seen = BTreeMap::new();
+    for (name, position) in items {
+        match seen.get(name) {
+            Some(&first) => report(name, first, position),
+            None => {
+                seen.insert(name, position);
+            }
+        }
+    }
+}
+
+pub(crate) fn validate<'a, T>(doc: &Document<'a, T>,
+    errors: &mut Vec<ValidationError>)
+    where T: Text<'a>,
+{
+    let index = SchemaIndex::new(doc);
+    let mut checker = Checker {
+        doc,
+        index: &index,
+        errors: Vec::new(),
+    };
+    checker.unique_type_names();
+    checker.unique_directive_names();
+    checker.unique_field_definition_names();
+    checker.unique_enum_value_names();
+    checker.unique_argument_definition_names();
+    checker.known_type_names();
+    checker.interface_implementations();
+    checker.union_member_types();
+    checker.input_object_circular_references();
+    checker.no_directive_self_reference();
+    errors.extend(checker.errors);
+}
+
+impl<'i, 'x, 'a, T> Checker<'i, 'x, 'a, T>
+    where T: Text<'a>
+{
+    fn error(&mut self, rule: Rule, message: String, positions: Vec<Pos>) {
+        self.errors.push(ValidationError::new(rule, message, positions));
+    }
+
+    fn types(&self) -> Vec<&'x TypeDefinition<'a, T>> {
+        self.doc.definitions.iter().filter_map(|def| match *def {
+            Definition::TypeDefinition(ref t) => Some(t),
+            _ => None,
+        }).collect()
+    }
+
+    fn directives(&self) -> Vec<&'x DirectiveDefinition<'a, T>> {
+        self.doc.definitions.iter().filter_map(|def| match *def {
+            Definition::DirectiveDefinition(ref d) => Some(d),
+            _ => None,
+        }).collect()
+    }
+
+    /// Returns the type if it's built-in or defined (not only extended)
+    fn defined(&self, name: &str) -> Option<&'i TypeEntry<'x, 'a, T>> {
+        self.index.type_by_name(name).filter(|entry| {
+            entry.definition.is_some() || BUILTIN_SCALARS.contains(&name)
+        })
+    }
+
+    fn unique_type_names(&mut self) {
+        let types = self.types();
+        let mut found = Vec::new();
+        duplicates(types.iter().map(|t| (t.name().as_ref(), t.position())),
+            |name, first, dup| found.push((name, first, dup)));
+        for (name, first, dup) in found {
+            self.error(Rule::UniqueTypeNames,
+                format!("There can be only one type named {:?}.", name),
+                vec![first, dup]);
+        }
+    }
+
+    fn unique_directive_names(&mut self) {
+        let directives = self.directives();
+        let mut found = Vec::new();
+        duplicates(directives.iter().map(|d| (d.name.as_ref(), d.position)),
+            |name, first, dup| found.push((name, first, dup)));
+        for (name, first, dup) in found {
+            self.error(Rule::UniqueDirectiveNames,
+                format!("There can be only one directive named \"@{}\".",
+                        name),
+                vec![first, dup]);
+        }
+    }
+
+    fn unique_field_definition_names(&mut self) {
+        let mut found = Vec::new();
+        for (type_name, entry) in self.index.types() {
+            let fields = entry.fields.iter()
+                .map(|f| (f.name.as_ref(), f.position))
+                .chain(entry.input_fields.iter()
+                       .map(|f| (f.name.as_ref(), f.position)));
+            duplicates(fields,
+                |name, first, dup| found.push((type_name, name, first, dup)));
+        }
+        for (type_name, name, first, dup) in found {
+            self.error(Rule::UniqueFieldDefinitionNames,
+                format!("Field \"{}.{}\" can only be defined once.",
+                        type_name, name),
+                vec![first, dup]);
+        }
+    }
+
+    fn unique_enum_value_names(&mut self) {
+        let mut found = Vec::new();
+        for (type_name, entry) in self.index.types() {
+            let values = enum_values(entry);
+            duplicates(values.iter().map(|v| (v.name.as_ref(), v.position)),
+                |name, first, dup| found.push((type_name, name, first, dup)));
+        }
+        for (type_name, name, first, dup) in found {
+            self.error(Rule::UniqueEnumValueNames,
+                format!("Enum value \"{}.{}\" can only be defined once.",
+                        type_name, name),
+                vec![first, dup]);
+        }
+    }
+
+    fn unique_argument_definition_names(&mut self) {
+        let mut found = Vec::new();
+        for (type_name, entry) in self.index.types() {
+            for field in &entry.fields {
+                let owner = format!("{}.{}", type_name, field.name.as_ref());
+                duplicates(field.arguments.iter()
+                           .map(|a| (a.name.as_ref(), a.position)),
+                    |name, first, dup| {
+                        found.push((owner.clone(), name, first, dup))
+                    });
+            }
+        }
+        for dir in self.directives() {
+            let owner = format!("@{}", dir.name.as_ref());
+            duplicates(dir.arguments.iter()
+                       .map(|a| (a.name.as_ref(), a.position)),
+                |name, first, dup| {
+                    found.push((owner.clone(), name, first, dup))
+                });
+        }
+        for (owner, name, first, dup) in found {
+            self.error(Rule::UniqueArgumentDefinitionNames,
+                format!("Argument \"{}({}:)\" can only be defined once.",
+                        owner, name),
+                vec![first, dup]);
+        }
+    }
+
+    fn check_known(&mut self, name: &str, position: Pos) {
+        if self.defined(name).is_none() {
+            self.error(Rule::KnownTypeNames,
+                format!("Unknown type {:?}.", name), vec![position]);
+        }
+    }
+
+    fn check_arguments<I>(&mut self, arguments: I)
+        where I: IntoIterator<Item=&'x InputValue<'a, T>>,
+    {
+        for arg in arguments {
+            self.check_known(arg.value_type.innermost_name(), arg.position);
+        }
+    }
+
+    fn known_type_names(&mut self) {
+        let index = self.index;
+        let entries = index.types().map(|(_, e)| e).collect::<Vec<_>>();
+        for entry in entries {
+            if entry.definition.is_none() {
+                if let Some(ext) = entry.extensions.first() {
+                    self.error(Rule::KnownTypeNames,
+                        format!("Cannot extend type {:?} because it is not \
+                                 defined.", entry.name),
+                        vec![ext.position()]);
+                }
+                continue;
+            }
+            let position = entry_position(entry);
+            for name in entry.interfaces.iter().chain(&entry.members) {
+                self.check_known(name, position);
+            }
+            for field in &entry.fields {
+                self.check_known(field.field_type.innermost_name(),
+                                 field.position);
+                self.check_arguments(&field.arguments);
+            }
+            self.check_arguments(entry.input_fields.iter().copied());
+        }
+        for dir in self.directives() {
+            self.check_arguments(&dir.arguments);
+        }
+        for def in &self.doc.definitions {
+            let (position, query, mutation, subscription) = match *def {
+                Definition::SchemaDefinition(ref s) => {
+                    (s.position, &s.query, &s.mutation, &s.subscription)
+                }
+                Definition::SchemaExtension(ref s) => {
+                    (s.position, &s.query, &s.mutation, &s.subscription)
+                }
+                _ => continue,
+            };
+            for name in query.iter().chain(mutation).chain(subscription) {
+                self.check_known(name.as_ref(), position);
+            }
+        }
+    }
+
+    /// Field type of the implementation is the subtype of the one of the
+    /// interface
+    fn is_valid_field_type(&self, field: &Type<'a, T>, iface: &Type<'a, T>)
+        -> bool
+    {
+        match (field, iface) {
+            (Type::NonNullType(f), Type::NonNullType(i)) => {
+                self.is_valid_field_type(f, i)
+            }
+            (_, Type::NonNullType(_)) => false,
+            (Type::NonNullType(f), i) => self.is_valid_field_type(f, i),
+            (Type::ListType(f), Type::ListType(i)) => {
+                self.is_valid_field_type(f, i)
+            }
+            (_, Type::ListType(_)) | (Type::ListType(_), _) => false,
+            (Type::NamedType(f), Type::NamedType(i)) => {
+                f == i || self.index.type_by_name(f.as_ref())
+                    .is_some_and(|entry| {
+                        entry.interfaces.contains(&i.as_ref())
+                    })
+                    || self.index.type_by_name(i.as_ref())
+                    .is_some_and(|entry| {
+                        entry.kind == TypeKind::Union &&
+                        entry.members.contains(&f.as_ref())
+                    })
+            }
+        }
+    }
+
+    fn check_field(&mut self, type_name: &str, field: &'x Field<'a, T>,
+        iface_name: &str, iface_field: &'x Field<'a, T>)
+    {
+        let name = field.name.as_ref();
+        if !self.is_valid_field_type(&field.field_type,
+                                     &iface_field.field_type)
+        {
+            self.error(Rule::InterfaceImplementations,
+                format!("Interface field {}.{} expects type {} but {}.{} is \
+                         type {}.", iface_name, name, iface_field.field_type,
+                         type_name, name, field.field_type),
+                vec![iface_field.position, field.position]);
+        }
+        for iface_arg in &iface_field.arguments {
+            let arg_name = iface_arg.name.as_ref();
+            let arg = field.arguments.iter()
+                .find(|a| a.name.as_ref() == arg_name);
+            match arg {
+                None => {
+                    self.error(Rule::InterfaceImplementations,
+                        format!("Interface field argument {}.{}({}:) \
+                                 expected but {}.{} does not provide it.",
+                                 iface_name, name, arg_name, type_name, name),
+                        vec![iface_arg.position, field.position]);
+                }
+                Some(arg) if !same_type(&arg.value_type,
+                                        &iface_arg.value_type) => {
+                    self.error(Rule::InterfaceImplementations,
+                        format!("Interface field argument {}.{}({}:) \
+                                 expects type {} but {}.{}({}:) is type {}.",
+                                 iface_name, name, arg_name,
+                                 iface_arg.value_type,
+                                 type_name, name, arg_name, arg.value_type),
+                        vec![iface_arg.position, arg.position]);
+                }
+                Some(_) => {}
+            }
+        }
+        for arg in &field.arguments {
+            let arg_name = arg.name.as_ref();
+            let required = arg.value_type.is_non_null() &&
+                arg.default_value.is_none();
+            let in_iface = iface_field.arguments.iter()
+                .any(|a| a.name.as_ref() == arg_name);
+            if required && !in_iface {
+                self.error(Rule::InterfaceImplementations,
+                    format!("Object field {}.{} includes required argument \
+                             {} that is missing from the Interface field \
+                             {}.{}.", type_name, name, arg_name,
+                             iface_name, name),
+                    vec![arg.position, iface_field.position]);
+            }
+        }
+    }
+
+    fn interface_implementations(&mut self) {
+        let index = self.index;
+        let entries = index.types().map(|(_, e)| e)
+            .filter(|e| e.definition.is_some())
+            .collect::<Vec<_>>();
+        for entry in entries {
+            let position = entry_position(entry);
+            let mut seen = BTreeSet::new();
+            for &iface_name in &entry.interfaces {
+                let iface = match self.defined(iface_name) {
+                    Some(iface) => iface,
+                    None => continue,  // reported by known_type_names
+                };
+                if iface.kind != TypeKind::Interface {
+                    self.error(Rule::InterfaceImplementations,
+                        format!("Type {} must only implement Interface \
+                                 types, it cannot implement {}.",
+                                 entry.name, iface_name),
+                        vec![position]);
+                    continue;
+                }
+                if iface_name == entry.name {
+                    self.error(Rule::InterfaceImplementations,
+                        format!("Type {} cannot implement itself because \
+                                 it would create a circular reference.",
+                                 entry.name),
+                        vec![position]);
+                    continue;
+                }
+                if !seen.insert(iface_name) {
+                    self.error(Rule::InterfaceImplementations,
+                        format!("Type {} can only implement {} once.",
+                                entry.name, iface_name),
+                        vec![position]);
+                    continue;
+                }
+                for &transitive in &iface.interfaces {
+                    if transitive != entry.name &&
+                        !entry.interfaces.contains(&transitive)
+                    {
+                        self.error(Rule::InterfaceImplementations,
+                            format!("Type {} must implement {} because it \
+                                     is implemented by {}.",
+                                     entry.name, transitive, iface_name),
+                            vec![position]);
+                    }
+                }
+                for iface_field in &iface.fields {
+                    match entry.field(iface_field.name.as_ref()) {
+                        Some(field) => {
+                            self.check_field(entry.name, field,
+                                             iface_name, iface_field);
+                        }
+                        None => {
+                            self.error(Rule::InterfaceImplementations,
+                                format!("Interface field {}.{} expected but \
+                                         {} does not provide it.",
+                                         iface_name,
+                                         iface_field.name.as_ref(),
+                                         entry.name),
+                                vec![iface_field.position, position]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn union_member_types(&mut self) {
+        let index = self.index;
+        let unions = index.types().map(|(_, e)| e)
+            .filter(|e| e.kind == TypeKind::Union && e.definition.is_some())
+            .collect::<Vec<_>>();
+        for entry in unions {
+            let position = entry_position(entry);
+            let mut seen = BTreeSet::new();
+            for &member in &entry.members {
+                if !seen.insert(member) {
+                    self.error(Rule::UnionMemberTypes,
+                        format!("Union type {} can only include type {} \
+                                 once.", entry.name, member),
+                        vec![position]);
+                    continue;
+                }
+                match self.defined(member) {
+                    Some(m) if m.kind != TypeKind::Object => {
+                        self.error(Rule::UnionMemberTypes,
+                            format!("Union type {} can only include Object \
+                                     types, it cannot include {}.",
+                                     entry.name, member),
+                            vec![position]);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn input_object_circular_references(&mut self) {
+        let mut visited = BTreeSet::new();
+        let mut path = Vec::new();
+        let mut path_index = BTreeMap::new();
+        let mut cycles = Vec::new();
+        let index = self.index;
+        for (name, entry) in index.types() {
+            if entry.kind == TypeKind::InputObject {
+                find_cycles(index, name, &mut visited, &mut path,
+                            &mut path_index, &mut cycles);
+            }
+        }
+        for (name, fields) in cycles {
+            let names = fields.iter()
+                .map(|f: &&InputValue<'a, T>| f.name.as_ref())
+                .collect::<Vec<_>>();
+            self.error(Rule::InputObjectCircularReferences,
+                format!("Cannot reference Input Object {:?} within itself \
+                         through a series of non-null fields: \"{}\".",
+                         name, names.join(".")),
+                fields.iter().map(|f| f.position).collect());
+        }
+    }
+
+    fn no_directive_self_reference(&mut self) {
+        for dir in self.directives() {
+            let mut reach = Reach {
+                index: self.index,
+                target: dir.name.as_ref(),
+                types: BTreeSet::new(),
+                directives: BTreeSet::new(),
+            };
+            if reach.directive(dir) {
+                self.error(Rule::NoDirectiveSelfReference,
+                    format!("Directive \"@{}\" cannot reference itself, \
+                             directly or through the types of its \
+                             arguments.", dir.name.as_ref()),
+                    vec![dir.position]);
+            }
+        }
+    }
+}
+
+/// Finds cycles of input objects referencing each other through non-null
+/// fields, reporting each cycle once
+fn find_cycles<'x, 'a, T>(index: &SchemaIndex<'x, 'a, T>, name: &'x str,
+    visited: &mut BTreeSet<&'x str>,
+    path: &mut Vec<&'x InputValue<'a, T>>,
+    path_index: &mut BTreeMap<&'x str, usize>,
+    cycles: &mut Vec<(&'x str, Vec<&'x InputValue<'a, T>>)>)
+    where T: Text<'a>,
+{
+    if !visited.insert(name) {
+        return;
+    }
+    let entry = match index.type_by_name(name) {
+        Some(entry) => entry,
+        None => return,
+    };
+    path_index.insert(name, path.len());
+    for field in &entry.input_fields {
+        let inner = match field.value_type {
+            Type::NonNullType(ref inner) => &**inner,
+            _ => continue,
+        };
+        let field_type = match *inner {
+            Type::NamedType(ref name) => name.as_ref(),
+            _ => continue,
+        };
+        let is_input = index.type_by_name(field_type)
+            .is_some_and(|t| t.kind == TypeKind::InputObject);
+        if !is_input {
+            continue;
+        }
+        path.push(field);
+        match path_index.get(field_type) {
+            Some(&start) => {
+                cycles.push((field_type, path[start..].to_vec()));
+            }
+            None => {
+                find_cycles(index, field_type, visited, path, path_index,
+                            cycles);
+            }
+        }
+        path.pop();
+    }
+    path_index.remove(name);
+}
+
+impl<'c, 'x, 'a, T> Reach<'c, 'x, 'a, T>
+    where T: Text<'a>
+{
+    fn directive(&mut self, dir: &'x DirectiveDefinition<'a, T>) -> bool {
+        dir.arguments.iter().any(|arg| self.input_value(arg))
+    }
+
+    fn input_value(&mut self, value: &'x InputValue<'a, T>) -> bool {
+        self.directives(value.directives.iter()) ||
+            self.type_name(value.value_type.innermost_name())
+    }
+
+    fn directives<I>(&mut self, directives: I) -> bool
+        where I: IntoIterator<Item=&'x Directive<'a, T>>,
+    {
+        for dir in directives {
+            let name = dir.name.as_ref();
+            if name == self.target {
+                return true;
+            }
+            if self.directives.insert(name) {
+                if let Some(def) = self.index.directive(name) {
+                    if self.directive(def) {
+                        return true;
+                    }
+                }
+            }
+        }
+        false
+    }
+
+    fn type_name(&mut self, name: &'x str) -> bool {
+        if !self.types.insert(name) {
+            return false;
+        }
+        let entry = match self.index.type_by_name(name) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        let input_fields = entry.input_fields.clone();
+        let values = enum_values(entry);
+        self.directives(type_directives(entry)) ||
+            values.iter().any(|v| self.directives(v.directives.iter())) ||
+            input_fields.iter().any(|f| self.input_value(f))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse_schema;
+    use crate::validation::{validate_schema, Rule};
+
+    fn errors(s: &str) -> Vec<(Rule, String)> {
+        let doc = parse_schema::<&str>(s).unwrap();
+        match validate_schema(&doc) {
+            Ok(()) => Vec::new(),
+            Err(errs) => errs.into_iter()
+                .map(|e| (e.rule, e.to_string())).collect(),
+        }
+    }
+
+    fn rules(s: &str) -> Vec<Rule> {
+        errors(s).into_iter().map(|(rule, _)| rule).collect()
+    }
+
+    #[test]
+    fn valid() {
+        assert_eq!(errors("
+            schema { query: Query }
+            interface Node { id: ID! }
+            interface Entity implements Node { id: ID! name: String }
+            type User implements Entity & Node {
+                id: ID!
+                name: String!
+                friends(first: Int, after: String): [User!]
+            }
+            union Search = User
+            type Query { node(id: ID!): Node search: [Search] }
+            extend type Query { me: User }
+            input Filter { and: [Filter!] not: Filter name: String }
+            enum Color { RED GREEN }
+            extend enum Color { BLUE }
+            directive @auth(role: Role) on FIELD_DEFINITION
+            enum Role { ADMIN USER }
+        "), []);
+    }
+
+    #[test]
+    fn unique_names() {
+        assert_eq!(errors("type A { a: Int } scalar A"), [
+            (Rule::UniqueTypeNames,
+             "There can be only one type named \"A\". at 1:1, 1:19".into()),
+        ]);
+        assert_eq!(rules("directive @a on FIELD directive @a on QUERY"),
+            [Rule::UniqueDirectiveNames]);
+        assert_eq!(errors("type A { a: Int } extend type A { a: String }"), [
+            (Rule::UniqueFieldDefinitionNames,
+             "Field \"A.a\" can only be defined once. at 1:10, 1:35".into()),
+        ]);
+        assert_eq!(rules("input A { a: Int a: Int }"),
+            [Rule::UniqueFieldDefinitionNames]);
+        assert_eq!(rules("enum E { A B } extend enum E { A }"),
+            [Rule::UniqueEnumValueNames]);
+        assert_eq!(errors("type A { a(x: Int, x: Int): Int }"), [
+            (Rule::UniqueArgumentDefinitionNames,
+             "Argument \"A.a(x:)\" can only be defined once. at 1:12, 1:20"
+             .into()),
+        ]);
+        assert_eq!(rules("directive @d(x: Int, x: Int) on FIELD"),
+            [Rule::UniqueArgumentDefinitionNames]);
+    }
+
+    #[test]
+    fn known_types() {
+        assert_eq!(errors("type A { a: [B!] }"), [
+            (Rule::KnownTypeNames, "Unknown type \"B\". at 1:10".into()),
+        ]);
+        assert_eq!(rules("type A implements I { a(x: X): Int } union U = V"),
+            [Rule::KnownTypeNames, Rule::KnownTypeNames,
+             Rule::KnownTypeNames]);
+        assert_eq!(rules("schema { query: Q }"), [Rule::KnownTypeNames]);
+        assert_eq!(errors("extend type A { a: Int }"), [
+            (Rule::KnownTypeNames,
+             "Cannot extend type \"A\" because it is not defined. at 1:8"
+             .into()),
+        ]);
+    }
+
+    #[test]
+    fn interfaces() {
+        assert_eq!(rules("scalar S type A implements S { a: Int }"),
+            [Rule::InterfaceImplementations]);
+        assert_eq!(errors("interface I { a: Int } type A implements I { b: Int }"),
+            [(Rule::InterfaceImplementations,
+              "Interface field I.a expected but A does not provide it. \
+               at 1:15, 1:24".into())]);
+        assert_eq!(errors("interface I { a: Int! } type A implements I { a: Int }"),
+            [(Rule::InterfaceImplementations,
+              "Interface field I.a expects type Int! but A.a is type Int. \
+               at 1:15, 1:47".into())]);
+        assert_eq!(rules("
+            interface I { a: I }
+            type A implements I { a: A! }
+            union U = A
+            interface J { u: U }
+            type B implements J { u: A }
+        "), []);
+        assert_eq!(rules("interface I { a(x: Int): Int }
+                          type A implements I { a: Int }
+                          type B implements I { a(x: Int!): Int }
+                          type C implements I { a(x: Int, y: Int!): Int }
+                          type D implements I { a(x: Int, y: Int! = 1): Int }"),
+            [Rule::InterfaceImplementations, Rule::InterfaceImplementations,
+             Rule::InterfaceImplementations]);
+        assert_eq!(errors("interface I { a: Int }
+                           interface J implements I { a: Int }
+                           type A implements J { a: Int }"),
+            [(Rule::InterfaceImplementations,
+              "Type A must implement I because it is implemented by J. \
+               at 3:28".into())]);
+    }
+
+    #[test]
+    fn unions() {
+        assert_eq!(errors("interface I { a: Int } type A { a: Int }
+                           union U = A | I | A"), [
+            (Rule::UnionMemberTypes,
+             "Union type U can only include Object types, it cannot \
+              include I. at 2:28".into()),
+            (Rule::UnionMemberTypes,
+             "Union type U can only include type A once. at 2:28".into()),
+        ]);
+    }
+
+    #[test]
+    fn input_cycles() {
+        assert_eq!(errors("input A { b: B! } input B { c: C! } input C { a: A! }"),
+            [(Rule::InputObjectCircularReferences,
+              "Cannot reference Input Object \"A\" within itself through a \
+               series of non-null fields: \"b.c.a\". at 1:11, 1:29, 1:47"
+              .into())]);
+        assert_eq!(rules("input A { a: A! }"),
+            [Rule::InputObjectCircularReferences]);
+        assert_eq!(rules("input A { a: [A!]! b: A }"), []);
+    }
+
+    #[test]
+    fn directive_cycles() {
+        assert_eq!(errors("directive @a(x: Int @a) on ARGUMENT_DEFINITION"),
+            [(Rule::NoDirectiveSelfReference,
+              "Directive \"@a\" cannot reference itself, directly or through \
+               the types of its arguments. at 1:1".into())]);
+        assert_eq!(rules("
+            directive @a(x: In) on ARGUMENT_DEFINITION | ENUM_VALUE
+            input In { e: E }
+            enum E { X @b }
+            directive @b(y: Int @a) on ENUM_VALUE
+        "), [Rule::NoDirectiveSelfReference, Rule::NoDirectiveSelfReference]);
+        assert_eq!(rules("
+            directive @a(x: Int @b) on ARGUMENT_DEFINITION
+            directive @b on ARGUMENT_DEFINITION
+        "), []);
+    }
+}
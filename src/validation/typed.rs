@@ -0,0 +1,494 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::coercion;
+use crate::common::{Text, Type, Value};
+use crate::position::Pos;
+use crate::query::{self, Definition, Selection, SelectionSet};
+use crate::query::{Operation, OperationKind, VariableDefinition};
+use crate::schema;
+use crate::schema::index::{type_name, SchemaIndex, TypeKind};
+use crate::validation::{Rule, ValidationError};
+
+
+/// Returns the name of the first variable found in the value
+pub(crate) fn first_variable<'x, 'a, T>(value: &'x Value<'a, T>)
+    -> Option<&'x str>
+    where T: Text<'a>,
+{
+    match *value {
+        Value::Variable(ref name) => Some(name.as_ref()),
+        Value::List(ref items) => items.iter().find_map(first_variable),
+        Value::Object(ref fields) => {
+            fields.iter().find_map(|(_, v)| first_variable(v))
+        }
+        _ => None,
+    }
+}
+
+/// Location a variable is used in
+struct VariableUsage<'x, 's, S>
+    where S: Text<'s>
+{
+    name: &'x str,
+    expected: &'x Type<'s, S>,
+    has_default: bool,
+    position: Pos,
+}
+
+struct Validator<'i, 'x, 's, S>
+    where S: Text<'s>
+{
+    index: &'i SchemaIndex<'x, 's, S>,
+    fragment_types: BTreeMap<&'x str, &'x str>,
+    errors: Vec<ValidationError>,
+    usages: Vec<VariableUsage<'x, 's, S>>,
+    spreads: Vec<&'x str>,
+}
+
+impl<'i, 'x, 's, S> Validator<'i, 'x, 's, S>
+    where S: Text<'s>
+{
+    fn error(&mut self, rule: Rule, message: String, position: Pos) {
+        self.errors.push(ValidationError::new(rule, message, vec![position]));
+    }
+
+    fn check_type_condition(&mut self, name: &str, position: Pos) -> bool {
+        match self.index.type_by_name(name) {
+            None => {
+                self.error(Rule::KnownTypeNames,
+                    format!("Unknown type {:?}.", name), position);
+                false
+            }
+            Some(entry) if !entry.kind.is_composite() => {
+                self.error(Rule::FragmentsOnCompositeTypes,
+                    format!("Fragment cannot condition on non composite \
+                             type {:?}.", name), position);
+                false
+            }
+            Some(_) => true,
+        }
+    }
+
+    fn check_spread_possible(&mut self, parent: &str, condition: &str,
+        position: Pos)
+    {
+        let parent_types = self.index.possible_types(parent);
+        let frag_types = self.index.possible_types(condition);
+        if !parent_types.iter().any(|t| frag_types.contains(t)) {
+            self.error(Rule::PossibleFragmentSpreads,
+                format!("Fragment cannot be spread here as objects of \
+                         type {:?} can never be of type {:?}.",
+                         parent, condition), position);
+        }
+    }
+
+    fn variable_definitions<'q, Q>(&mut self,
+        vars: &[VariableDefinition<'q, Q>])
+        where Q: Text<'q>,
+    {
+        for var in vars {
+            let name = type_name(&var.var_type);
+            match self.index.type_by_name(name) {
+                None => {
+                    self.error(Rule::KnownTypeNames,
+                        format!("Unknown type {:?}.", name), var.position);
+                }
+                Some(entry) if !entry.kind.is_input() => {
+                    self.error(Rule::VariablesAreInputTypes,
+                        format!("Variable \"${}\" cannot be non-input \
+                                 type \"{}\".",
+                                 var.name.as_ref(), var.var_type),
+                        var.position);
+                }
+                Some(_) => {
+                    if let Some(ref default) = var.default_value {
+                        self.default_value(var, &default.value);
+                    }
+                }
+            }
+        }
+    }
+
+    fn default_value<'q, Q>(&mut self, var: &VariableDefinition<'q, Q>,
+        value: &Value<'q, Q>)
+        where Q: Text<'q>,
+    {
+        if let Some(name) = first_variable(value) {
+            self.error(Rule::ValuesOfCorrectType,
+                format!("Default value of variable \"${}\" must be constant, \
+                         found variable \"${}\".", var.name.as_ref(), name),
+                var.position);
+            return;
+        }
+        let no_variables = BTreeMap::<&str, Value<&str>>::new();
+        let result = coercion::coerce(self.index, value, &var.var_type,
+                                      &no_variables, var.position);
+        for err in result.err().unwrap_or_default() {
+            let message = if err.path.is_empty() {
+                err.message
+            } else {
+                format!("{}: {}", err.path, err.message)
+            };
+            self.error(Rule::ValuesOfCorrectType,
+                format!("Invalid default value of variable \"${}\": {}",
+                        var.name.as_ref(), message),
+                var.position);
+        }
+    }
+
+    fn selection_set<'q, Q>(&mut self, parent: &'x str,
+        set: &'x SelectionSet<'q, Q>)
+        where Q: Text<'q>,
+    {
+        let entry = match self.index.type_by_name(parent) {
+            Some(entry) => entry,
+            None => return,
+        };
+        for item in &set.items {
+            match *item {
+                Selection::Field(ref fld) => {
+                    let name = fld.name.as_ref();
+                    if name == "__typename" {
+                        continue;
+                    }
+                    if (name == "__schema" || name == "__type") &&
+                        self.index.query_type() == Some(parent)
+                    {
+                        // introspection types aren't present in the
+                        // schema document
+                        continue;
+                    }
+                    match entry.field(name) {
+                        Some(def) => self.field(parent, fld, def),
+                        None => {
+                            self.error(Rule::FieldsOnCorrectType,
+                                format!("Cannot query field {:?} on \
+                                         type {:?}.", name, parent),
+                                fld.position);
+                        }
+                    }
+                }
+                Selection::InlineFragment(ref frag) => {
+                    let condition = match frag.type_condition {
+                        Some(query::TypeCondition::On(ref name)) => {
+                            name.as_ref()
+                        }
+                        None => parent,
+                    };
+                    if !self.check_type_condition(condition, frag.position) {
+                        continue;
+                    }
+                    self.check_spread_possible(parent, condition,
+                        frag.position);
+                    self.selection_set(condition, &frag.selection_set);
+                }
+                Selection::FragmentSpread(ref spread) => {
+                    let name = spread.fragment_name.as_ref();
+                    self.spreads.push(name);
+                    let condition = match self.fragment_types.get(name) {
+                        Some(&condition) => condition,
+                        None => continue,
+                    };
+                    let composite = self.index.type_by_name(condition)
+                        .map(|e| e.kind.is_composite())
+                        .unwrap_or(false);
+                    if composite {
+                        self.check_spread_possible(parent, condition,
+                            spread.position);
+                    }
+                }
+            }
+        }
+    }
+
+    fn field<'q, Q>(&mut self, parent: &str, fld: &'x query::Field<'q, Q>,
+        def: &'x schema::Field<'s, S>)
+        where Q: Text<'q>,
+    {
+        for (name, value) in &fld.arguments {
+            match def.arguments.iter().find(|a| a.name.as_ref() == name.as_ref())
+            {
+                Some(arg) => {
+                    self.value(value, &arg.value_type,
+                        arg.default_value.is_some(), fld.position);
+                }
+                None => {
+                    self.error(Rule::KnownArgumentNames,
+                        format!("Unknown argument {:?} on field \"{}.{}\".",
+                                name.as_ref(), parent, def.name.as_ref()),
+                        fld.position);
+                }
+            }
+        }
+        for arg in &def.arguments {
+            let required = matches!(arg.value_type, Type::NonNullType(_)) &&
+                arg.default_value.is_none();
+            if required && !fld.arguments.iter()
+                .any(|(name, _)| name.as_ref() == arg.name.as_ref())
+            {
+                self.error(Rule::ProvidedRequiredArguments,
+                    format!("Field {:?} argument {:?} of type \"{}\" is \
+                             required, but it was not provided.",
+                             def.name.as_ref(), arg.name.as_ref(),
+                             arg.value_type),
+                    fld.position);
+            }
+        }
+        let stream = fld.directives.iter()
+            .find(|dir| dir.name.as_ref() == "stream");
+        if let Some(dir) = stream {
+            if !def.field_type.is_list() {
+                self.error(Rule::StreamDirectiveOnListField,
+                    format!("Stream directive cannot be used on non-list \
+                             field {:?} on type \"{}\".",
+                             def.name.as_ref(), parent),
+                    dir.position);
+            }
+        }
+        let result = type_name(&def.field_type);
+        let kind = match self.index.type_by_name(result) {
+            Some(entry) => entry.kind,
+            None => return,
+        };
+        let has_selection = !fld.selection_set.items.is_empty();
+        if kind.is_leaf() && has_selection {
+            self.error(Rule::ScalarLeafs,
+                format!("Field {:?} must not have a selection since type \
+                         \"{}\" has no subfields.",
+                         fld.name.as_ref(), def.field_type),
+                fld.position);
+        } else if kind.is_composite() && !has_selection {
+            self.error(Rule::ScalarLeafs,
+                format!("Field {:?} of type \"{}\" must have a selection \
+                         of subfields. Did you mean \"{} {{ ... }}\"?",
+                         fld.name.as_ref(), def.field_type,
+                         fld.name.as_ref()),
+                fld.position);
+        } else {
+            self.selection_set(result, &fld.selection_set);
+        }
+    }
+
+    fn value<'q, Q>(&mut self, value: &'x Value<'q, Q>,
+        expected: &'x Type<'s, S>, has_default: bool, position: Pos)
+        where Q: Text<'q>,
+    {
+        if let Value::Variable(ref name) = *value {
+            self.usages.push(VariableUsage {
+                name: name.as_ref(), expected, has_default, position,
+            });
+            return;
+        }
+        let ok = match (expected, value) {
+            (Type::NonNullType(_), Value::Null) => false,
+            (Type::NonNullType(inner), _) => {
+                self.value(value, inner, false, position);
+                true
+            }
+            (_, Value::Null) => true,
+            (Type::ListType(inner), Value::List(items)) => {
+                for item in items {
+                    self.value(item, inner, false, position);
+                }
+                true
+            }
+            (Type::ListType(inner), _) => {
+                self.value(value, inner, false, position);
+                true
+            }
+            (Type::NamedType(name), _) => {
+                self.named_value(value, name.as_ref(), position)
+            }
+        };
+        if !ok {
+            self.error(Rule::ValuesOfCorrectType,
+                format!("Expected value of type \"{}\", found {}.",
+                        expected, value),
+                position);
+        }
+    }
+
+    fn named_value<'q, Q>(&mut self, value: &'x Value<'q, Q>, name: &str,
+        position: Pos)
+        -> bool
+        where Q: Text<'q>,
+    {
+        let entry = match self.index.type_by_name(name) {
+            Some(entry) => entry,
+            None => return true,
+        };
+        match (entry.kind, value) {
+            (TypeKind::Scalar, _) => match (name, value) {
+                ("Int", Value::Int(num)) => {
+                    num.as_i64().map(|v| v as i32 as i64 == v)
+                        .unwrap_or(false)
+                }
+                ("Int", _) => false,
+                ("Float", Value::Int(_)) | ("Float", Value::Float(_)) => true,
+                ("Float", _) => false,
+                ("String", Value::String(_)) => true,
+                ("String", _) => false,
+                ("Boolean", Value::Boolean(_)) => true,
+                ("Boolean", _) => false,
+                ("ID", Value::String(_)) | ("ID", Value::Int(_)) => true,
+                ("ID", _) => false,
+                // custom scalars accept any literal
+                _ => true,
+            },
+            (TypeKind::Enum, Value::Enum(val)) => {
+                if !entry.values.contains(&val.as_ref()) {
+                    self.error(Rule::ValuesOfCorrectType,
+                        format!("Value {:?} does not exist in {:?} enum.",
+                                val.as_ref(), name),
+                        position);
+                }
+                true
+            }
+            (TypeKind::InputObject, Value::Object(fields)) => {
+                let input_fields = entry.input_fields.clone();
+                for (field_name, field_value) in fields {
+                    match input_fields.iter()
+                        .find(|f| f.name.as_ref() == field_name.as_ref())
+                    {
+                        Some(def) => {
+                            self.value(field_value, &def.value_type,
+                                def.default_value.is_some(), position);
+                        }
+                        None => {
+                            self.error(Rule::ValuesOfCorrectType,
+                                format!("Field {:?} is not defined by \
+                                         type {:?}.",
+                                         field_name.as_ref(), name),
+                                position);
+                        }
+                    }
+                }
+                for def in input_fields {
+                    let required =
+                        matches!(def.value_type, Type::NonNullType(_)) &&
+                        def.default_value.is_none();
+                    if required && !fields.iter()
+                        .any(|(k, _)| k.as_ref() == def.name.as_ref())
+                    {
+                        self.error(Rule::ValuesOfCorrectType,
+                            format!("Field \"{}.{}\" of required type \
+                                     \"{}\" was not provided.",
+                                     name, def.name.as_ref(), def.value_type),
+                            position);
+                    }
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn check_usages<'q, Q>(&mut self, op: &Operation<'q, Q>,
+        usages: &[VariableUsage<'x, 's, S>])
+        where Q: Text<'q>,
+    {
+        for usage in usages {
+            let var = match op.variable_definitions.iter()
+                .find(|v| v.name.as_ref() == usage.name)
+            {
+                Some(var) => var,
+                None => continue,
+            };
+            let var_default = match var.default_value {
+                Some(ref d) => !matches!(d.value, Value::Null),
+                None => false,
+            };
+            let ok = match *usage.expected {
+                Type::NonNullType(ref inner)
+                if !matches!(var.var_type, Type::NonNullType(_)) => {
+                    (var_default || usage.has_default) &&
+                        var.var_type.is_compatible_with(inner)
+                }
+                ref expected => var.var_type.is_compatible_with(expected),
+            };
+            if !ok {
+                self.error(Rule::VariablesInAllowedPosition,
+                    format!("Variable \"${}\" of type \"{}\" used in \
+                             position expecting type \"{}\".",
+                             usage.name, var.var_type, usage.expected),
+                    usage.position);
+            }
+        }
+    }
+}
+
+pub fn validate<'x, 's, 'q, S, Q>(schema: &'x schema::Document<'s, S>,
+    doc: &'x query::Document<'q, Q>,
+    errors: &mut Vec<ValidationError>)
+    where S: Text<'s>,
+          Q: Text<'q>,
+{
+    let index = SchemaIndex::new(schema);
+    let mut validator = Validator {
+        index: &index,
+        fragment_types: BTreeMap::new(),
+        errors: Vec::new(),
+        usages: Vec::new(),
+        spreads: Vec::new(),
+    };
+    for def in &doc.definitions {
+        if let Definition::Fragment(ref frag) = *def {
+            let query::TypeCondition::On(ref cond) = frag.type_condition;
+            validator.fragment_types.entry(frag.name.as_ref())
+                .or_insert_with(|| cond.as_ref());
+        }
+    }
+    // usages and spreads of each fragment, used to check variables of the
+    // operations spreading that fragment
+    let mut fragments = BTreeMap::new();
+    for def in &doc.definitions {
+        if let Definition::Fragment(ref frag) = *def {
+            let query::TypeCondition::On(ref cond) = frag.type_condition;
+            if validator.check_type_condition(cond.as_ref(), frag.position) {
+                validator.selection_set(cond.as_ref(), &frag.selection_set);
+            }
+            let usages = std::mem::take(&mut validator.usages);
+            let spreads = std::mem::take(&mut validator.spreads);
+            fragments.entry(frag.name.as_ref()).or_insert((usages, spreads));
+        }
+    }
+    for def in &doc.definitions {
+        let op = match *def {
+            Definition::Operation(ref op) => op,
+            Definition::Fragment(_) => continue,
+        };
+        validator.variable_definitions(&op.variable_definitions);
+        let root = match op.kind {
+            OperationKind::ImplicitQuery | OperationKind::Query => {
+                index.query_type()
+            }
+            OperationKind::Mutation => index.mutation_type(),
+            OperationKind::Subscription => index.subscription_type(),
+        };
+        let root = match root {
+            Some(root) => root,
+            None => {
+                validator.error(Rule::KnownOperationTypes,
+                    "Schema does not define the root type for \
+                     this operation.".into(),
+                    op.position);
+                continue;
+            }
+        };
+        validator.selection_set(root, &op.selection_set);
+        let usages = std::mem::take(&mut validator.usages);
+        validator.check_usages(op, &usages);
+        let mut queue = std::mem::take(&mut validator.spreads);
+        let mut visited = BTreeSet::new();
+        while let Some(name) = queue.pop() {
+            if !visited.insert(name) {
+                continue;
+            }
+            if let Some((frag_usages, frag_spreads)) = fragments.get(name) {
+                validator.check_usages(op, frag_usages);
+                queue.extend(frag_spreads.iter().copied());
+            }
+        }
+    }
+    errors.extend(validator.errors);
+}
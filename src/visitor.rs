@@ -1,8 +1,11 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
 use std::slice;
 
-use crate::common::Text;
+use crate::common::{Text, Value};
+use crate::position::Pos;
 use crate::query::{SelectionSet, Directive, Selection, Field};
-use crate::query::{Document, Definition};
+use crate::query::{Document, Definition, TypeCondition};
 
 
 pub trait Visit {
@@ -25,6 +28,10 @@ pub struct FieldIter<'a, T>
     where T: Text<'a>
 {
     stack: Vec<slice::Iter<'a, Selection<'a, T>>>,
+    /// Type condition in effect for each level of the stack
+    conditions: Vec<Option<&'a str>>,
+    /// Type condition of the last returned field
+    current: Option<&'a str>,
 }
 
 pub trait CreateData<'a, S: ?Sized, D: ?Sized> {
@@ -36,8 +43,21 @@ impl<'a, T> CreateData<'a, &'a SelectionSet<'a, T>, &'a Field<'a, T>>
     where T: Text<'a>,
 {
     fn new(v: &'a SelectionSet<'a, T>) -> Self {
+        FieldIter::with_condition(v, None)
+    }
+}
+
+impl<'a, T> FieldIter<'a, T>
+    where T: Text<'a>,
+{
+    fn with_condition(set: &'a SelectionSet<'a, T>,
+        condition: Option<&'a str>)
+        -> Self
+    {
         FieldIter {
-            stack: vec![v.items.iter()],
+            stack: vec![set.items.iter()],
+            conditions: vec![condition],
+            current: None,
         }
     }
 }
@@ -53,24 +73,37 @@ impl<'a, T: 'a> Iterator for FieldIter<'a, T>
 {
     type Item = &'a Field<'a, T>;
     fn next(&mut self) -> Option<&'a Field<'a, T>> {
-        let ref mut stack = self.stack;
+        let stack = &mut self.stack;
         while !stack.is_empty() {
             match stack.last_mut().and_then(|iter| iter.next()) {
                 Some(Selection::Field(f)) => {
+                    self.current = self.conditions.last().cloned()
+                        .unwrap_or(None);
                     stack.push(f.selection_set.items.iter());
+                    // subfields are selected on the type of the field
+                    self.conditions.push(None);
                     return Some(f);
                 }
                 Some(Selection::InlineFragment(f)) => {
+                    let condition = match f.type_condition {
+                        Some(TypeCondition::On(ref name)) => {
+                            Some(name.as_ref())
+                        }
+                        None => self.conditions.last().cloned()
+                            .unwrap_or(None),
+                    };
                     stack.push(f.selection_set.items.iter());
+                    self.conditions.push(condition);
                     continue;
                 }
                 Some(Selection::FragmentSpread(..)) => {}
                 None => {
                     stack.pop();
+                    self.conditions.pop();
                 }
             }
         }
-        return None;
+        None
     }
 }
 
@@ -113,16 +146,192 @@ impl<'a, T: 'a> Iterator for DocumentFieldIter<'a, T>
                 }
             }
             self.field_iter.take();
-            let ss = match self.doc_iter.next() {
-                Some(Operation(def)) => &def.selection_set,
-                Some(Fragment(def)) => &def.selection_set,
+            self.field_iter = match self.doc_iter.next() {
+                Some(Operation(def)) => {
+                    Some(FieldIter::with_condition(&def.selection_set, None))
+                }
+                Some(Fragment(def)) => {
+                    let TypeCondition::On(ref cond) = def.type_condition;
+                    Some(FieldIter::with_condition(&def.selection_set,
+                                                   Some(cond.as_ref())))
+                }
                 None => return None,
             };
-            self.field_iter = Some(ss.visit::<Field<'a, T>>());
         }
     }
 }
 
+/// Filters of the field iterators returned by `visit::<Field<_>>()`
+///
+/// Filters can be combined, every one of them must match:
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::parse_query;
+/// use edb_graphql_parser::query::Field;
+/// use edb_graphql_parser::visit::{FieldFilters, Visit};
+///
+/// let doc = parse_query::<&str>("
+///     { node { ... on User { id name @defer } id } }
+///     fragment F on User { name @defer }
+/// ").unwrap();
+/// let deferred = doc.visit::<Field<_>>()
+///     .in_fragments_on("User")
+///     .with_directive("defer")
+///     .map(|m| m.position.to_string())
+///     .collect::<Vec<_>>();
+/// // positions of the directives
+/// assert_eq!(deferred, ["2:36", "3:31"]);
+/// assert_eq!(doc.visit::<Field<_>>().named("id").count(), 2);
+/// ```
+pub trait FieldFilters<'a, T>: Iterator<Item=&'a Field<'a, T>> + Sized
+    where T: Text<'a>
+{
+    /// Returns the type condition of the fragment the last returned field
+    /// is selected in: of the enclosing inline fragments or fragment
+    /// definition, not counting the fragments outside of the parent field
+    fn type_condition(&self) -> Option<&'a str>;
+
+    /// Fields with the name (not alias)
+    fn named(self, name: &str) -> FieldMatches<'a, T, Self> {
+        FieldMatches::new(self).named(name)
+    }
+
+    /// Fields having the directive, the position of the match is the
+    /// position of the directive
+    fn with_directive(self, name: &str) -> FieldMatches<'a, T, Self> {
+        FieldMatches::new(self).with_directive(name)
+    }
+
+    /// Fields selected in the fragments (inline or defined) on the type, see
+    /// `type_condition`
+    fn in_fragments_on(self, type_name: &str) -> FieldMatches<'a, T, Self> {
+        FieldMatches::new(self).in_fragments_on(type_name)
+    }
+}
+
+impl<'a, T> FieldFilters<'a, T> for FieldIter<'a, T>
+    where T: Text<'a>,
+{
+    fn type_condition(&self) -> Option<&'a str> {
+        self.current
+    }
+}
+
+impl<'a, T> FieldFilters<'a, T> for DocumentFieldIter<'a, T>
+    where T: Text<'a>,
+{
+    fn type_condition(&self) -> Option<&'a str> {
+        self.field_iter.as_ref().and_then(|iter| iter.current)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum FieldFilter {
+    Named(String),
+    Directive(String),
+    On(String),
+}
+
+/// Field matching the filters of the `FieldMatches`
+#[derive(Debug)]
+pub struct FieldMatch<'a, T>
+    where T: Text<'a>
+{
+    pub field: &'a Field<'a, T>,
+    /// Position of the field, or of the directive if the iterator is
+    /// filtered by a directive
+    pub position: Pos,
+    /// See `FieldFilters::type_condition`
+    pub type_condition: Option<&'a str>,
+}
+
+/// Iterator over the fields matching the filters, see `FieldFilters`
+#[derive(Debug)]
+pub struct FieldMatches<'a, T, I>
+    where T: Text<'a>,
+          I: FieldFilters<'a, T>,
+{
+    iter: I,
+    filters: Vec<FieldFilter>,
+    phantom: std::marker::PhantomData<&'a T>,
+}
+
+impl<'a, T, I> FieldMatches<'a, T, I>
+    where T: Text<'a>,
+          I: FieldFilters<'a, T>,
+{
+    fn new(iter: I) -> Self {
+        FieldMatches {
+            iter,
+            filters: Vec::new(),
+            phantom: std::marker::PhantomData,
+        }
+    }
+
+    /// See `FieldFilters::named`
+    pub fn named(mut self, name: &str) -> Self {
+        self.filters.push(FieldFilter::Named(name.into()));
+        self
+    }
+
+    /// See `FieldFilters::with_directive`
+    pub fn with_directive(mut self, name: &str) -> Self {
+        self.filters.push(FieldFilter::Directive(name.into()));
+        self
+    }
+
+    /// See `FieldFilters::in_fragments_on`
+    pub fn in_fragments_on(mut self, type_name: &str) -> Self {
+        self.filters.push(FieldFilter::On(type_name.into()));
+        self
+    }
+
+    fn matches(&self, field: &'a Field<'a, T>) -> Option<FieldMatch<'a, T>> {
+        let type_condition = self.iter.type_condition();
+        let mut position = None;
+        for filter in &self.filters {
+            match *filter {
+                FieldFilter::Named(ref name) => {
+                    if field.name.as_ref() != name {
+                        return None;
+                    }
+                }
+                FieldFilter::Directive(ref name) => {
+                    let dir = field.directives.iter()
+                        .find(|d| d.name.as_ref() == name)?;
+                    position.get_or_insert(dir.position);
+                }
+                FieldFilter::On(ref name) => {
+                    if type_condition != Some(name.as_str()) {
+                        return None;
+                    }
+                }
+            }
+        }
+        Some(FieldMatch {
+            field,
+            position: position.unwrap_or(field.position),
+            type_condition,
+        })
+    }
+}
+
+impl<'a, T, I> Iterator for FieldMatches<'a, T, I>
+    where T: Text<'a>,
+          I: FieldFilters<'a, T>,
+{
+    type Item = FieldMatch<'a, T>;
+    fn next(&mut self) -> Option<FieldMatch<'a, T>> {
+        while let Some(field) = self.iter.next() {
+            if let Some(found) = self.matches(field) {
+                return Some(found);
+            }
+        }
+        None
+    }
+}
+
 
 #[derive(Debug)]
 pub struct SetDirectiveIter<'a, T>
@@ -162,7 +371,7 @@ impl<'a, T: 'a> Iterator for SetDirectiveIter<'a, T>
                 }
             }
             self.directive_iter.take();
-            let ref mut stack = self.stack;
+            let stack = &mut self.stack;
             while !stack.is_empty() {
                 match stack.last_mut().and_then(|iter| iter.next()) {
                     Some(Selection::Field(f)) => {
@@ -189,6 +398,203 @@ impl<'a, T: 'a> Iterator for SetDirectiveIter<'a, T>
     }
 }
 
+/// Node of the document passed to the predicates of the `Collector`
+#[derive(Debug)]
+pub enum Node<'x, 'a, T>
+    where T: Text<'a>
+{
+    Field(&'x Field<'a, T>),
+    Directive(&'x Directive<'a, T>),
+    /// Literal value, including the nested items of lists and objects
+    Value(&'x Value<'a, T>),
+}
+
+type Predicate<'c, 'a, T> = Box<dyn Fn(&Node<'_, 'a, T>) -> bool + 'c>;
+type Extractor<'c, 'a, T> =
+    Box<dyn Fn(&Node<'_, 'a, T>) -> Option<String> + 'c>;
+
+/// Collects ad-hoc statistics of the executable document
+///
+/// Counters and sets are declared by name with a predicate over the nodes,
+/// then all of them are computed in a single pass over the document:
+///
+/// ```rust
+/// # extern crate edb_graphql_parser;
+/// use edb_graphql_parser::parse_query;
+/// use edb_graphql_parser::common::Value;
+/// use edb_graphql_parser::visit::{Collector, Node};
+///
+/// let doc = parse_query::<&str>(r#"
+///     { users(order: ASC) { id } admins: users(role: ADMIN) { id } }
+/// "#).unwrap();
+/// let stats = Collector::<&str>::new()
+///     .count("users", |node| match *node {
+///         Node::Field(f) => f.name == "users",
+///         _ => false,
+///     })
+///     .collect("enums", |node| match *node {
+///         Node::Value(Value::Enum(e)) => Some(e.to_string()),
+///         _ => None,
+///     })
+///     .run(&doc);
+/// assert_eq!(stats.count("users"), 2);
+/// assert_eq!(stats.set("enums").collect::<Vec<_>>(), ["ADMIN", "ASC"]);
+/// ```
+///
+/// Fragments are visited where they are defined, not where they are spread.
+pub struct Collector<'c, 'a, T>
+    where T: Text<'a>
+{
+    counters: Vec<(String, Predicate<'c, 'a, T>)>,
+    sets: Vec<(String, Extractor<'c, 'a, T>)>,
+}
+
+/// Results of the `Collector`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Statistics {
+    counts: BTreeMap<String, usize>,
+    sets: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl<'c, 'a, T> Collector<'c, 'a, T>
+    where T: Text<'a>
+{
+    pub fn new() -> Collector<'c, 'a, T> {
+        Collector {
+            counters: Vec::new(),
+            sets: Vec::new(),
+        }
+    }
+
+    /// Count nodes matching the predicate
+    pub fn count<F>(&mut self, name: &str, predicate: F) -> &mut Self
+        where F: Fn(&Node<'_, 'a, T>) -> bool + 'c,
+    {
+        self.counters.push((name.into(), Box::new(predicate)));
+        self
+    }
+
+    /// Collect distinct strings returned for the nodes
+    pub fn collect<F>(&mut self, name: &str, extractor: F) -> &mut Self
+        where F: Fn(&Node<'_, 'a, T>) -> Option<String> + 'c,
+    {
+        self.sets.push((name.into(), Box::new(extractor)));
+        self
+    }
+
+    /// Visits all the nodes of the document
+    pub fn run(&self, doc: &'a Document<'a, T>) -> Statistics {
+        let mut stats = Statistics::default();
+        for (name, _) in &self.counters {
+            stats.counts.insert(name.clone(), 0);
+        }
+        for (name, _) in &self.sets {
+            stats.sets.insert(name.clone(), BTreeSet::new());
+        }
+        for def in &doc.definitions {
+            let (directives, selection_set) = match *def {
+                Definition::Operation(ref op) => {
+                    for var in &op.variable_definitions {
+                        for dir in &var.directives {
+                            self.directive(dir, &mut stats);
+                        }
+                        if let Some(ref default) = var.default_value {
+                            self.value(&default.value, &mut stats);
+                        }
+                    }
+                    (&op.directives, &op.selection_set)
+                }
+                Definition::Fragment(ref frag) => {
+                    (&frag.directives, &frag.selection_set)
+                }
+            };
+            for dir in directives {
+                self.directive(dir, &mut stats);
+            }
+            for dir in selection_set.visit::<Directive<'a, T>>() {
+                self.directive(dir, &mut stats);
+            }
+            for field in selection_set.visit::<Field<'a, T>>() {
+                self.node(&Node::Field(field), &mut stats);
+                for (_, value) in &field.arguments {
+                    self.value(value, &mut stats);
+                }
+            }
+        }
+        stats
+    }
+
+    fn directive(&self, dir: &Directive<'a, T>, stats: &mut Statistics) {
+        self.node(&Node::Directive(dir), stats);
+        for arg in &dir.arguments {
+            self.value(&arg.value, stats);
+        }
+    }
+
+    fn value(&self, value: &Value<'a, T>, stats: &mut Statistics) {
+        self.node(&Node::Value(value), stats);
+        match *value {
+            Value::List(ref items) => {
+                for item in items {
+                    self.value(item, stats);
+                }
+            }
+            Value::Object(ref fields) => {
+                for (_, value) in fields {
+                    self.value(value, stats);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn node(&self, node: &Node<'_, 'a, T>, stats: &mut Statistics) {
+        for (name, predicate) in &self.counters {
+            if predicate(node) {
+                *stats.counts.get_mut(name).expect("counter exists") += 1;
+            }
+        }
+        for (name, extractor) in &self.sets {
+            if let Some(item) = extractor(node) {
+                stats.sets.get_mut(name).expect("set exists").insert(item);
+            }
+        }
+    }
+}
+
+impl<'c, 'a, T> Default for Collector<'c, 'a, T>
+    where T: Text<'a>
+{
+    fn default() -> Collector<'c, 'a, T> {
+        Collector::new()
+    }
+}
+
+impl<'c, 'a, T> fmt::Debug for Collector<'c, 'a, T>
+    where T: Text<'a>
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Collector")
+            .field("counters", &self.counters.iter()
+                .map(|(name, _)| name).collect::<Vec<_>>())
+            .field("sets", &self.sets.iter()
+                .map(|(name, _)| name).collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Statistics {
+    /// Returns the value of the counter, zero if it isn't declared
+    pub fn count(&self, name: &str) -> usize {
+        self.counts.get(name).copied().unwrap_or(0)
+    }
+
+    /// Returns sorted items of the set, empty if it isn't declared
+    pub fn set(&self, name: &str) -> impl Iterator<Item=&str> {
+        self.sets.get(name).into_iter().flatten().map(|s| s.as_str())
+    }
+}
+
 #[test]
 fn test_field_iter() {
     use crate::parse_query;
@@ -229,7 +635,7 @@ fn test_dir_iter() {
             }
         }
     "#).expect("Failed to parse query");
-    let def = match doc.definitions.iter().next().unwrap() {
+    let def = match doc.definitions.first().unwrap() {
         Operation(op) => &op.selection_set,
         _ => unreachable!(),
     };
@@ -241,3 +647,73 @@ fn test_dir_iter() {
 }
 
 
+
+
+#[test]
+fn test_collector() {
+    use crate::parse_query;
+
+    let doc = parse_query::<&str>(r#"
+        query Q($x: [Color] = [RED, {c: GREEN}]) @tag(name: ONE) {
+            a(color: BLUE) { b @skip(if: true) ...F }
+            ... on T @include(if: false) { a(list: [{c: RED}]) }
+        }
+        fragment F on T { a }
+    "#).expect("Failed to parse query");
+    let stats = Collector::<&str>::new()
+        .count("a", |node| match *node {
+            Node::Field(f) => f.name == "a",
+            _ => false,
+        })
+        .count("directives", |node| matches!(*node, Node::Directive(_)))
+        .collect("enums", |node| match *node {
+            Node::Value(Value::Enum(e)) => Some(e.to_string()),
+            _ => None,
+        })
+        .run(&doc);
+    assert_eq!(stats.count("a"), 3);
+    assert_eq!(stats.count("directives"), 3);
+    assert_eq!(stats.count("undeclared"), 0);
+    assert_eq!(stats.set("enums").collect::<Vec<_>>(),
+               ["BLUE", "GREEN", "ONE", "RED"]);
+}
+
+#[test]
+fn test_field_filters() {
+    use crate::parse_query;
+
+    let doc = parse_query::<&str>(r#"
+        {
+            a { ... on User { id b { id } ... { c @x } } }
+            ... on Query { d @x @y e { f } }
+        }
+        fragment F on User { id ... on Admin { id @x } }
+    "#).expect("Failed to parse query");
+    fn names<'a, I>(matches: I) -> Vec<String>
+        where I: Iterator<Item=FieldMatch<'a, &'a str>>,
+    {
+        matches.map(|m| format!("{}@{}", m.field.name, m.position)).collect()
+    }
+
+    assert_eq!(names(doc.visit::<Field<_>>().in_fragments_on("User")),
+               ["id@3:31", "b@3:34", "c@3:49", "id@6:30"]);
+    assert_eq!(names(doc.visit::<Field<_>>().named("id")),
+               ["id@3:31", "id@3:38", "id@6:30", "id@6:48"]);
+    assert_eq!(names(doc.visit::<Field<_>>().with_directive("x")),
+               ["c@3:51", "d@4:30", "id@6:51"]);
+    assert_eq!(names(doc.visit::<Field<_>>()
+                     .with_directive("y").with_directive("x")),
+               ["d@4:33"]);
+    assert_eq!(names(doc.visit::<Field<_>>()
+                     .in_fragments_on("Admin").named("id")),
+               ["id@6:48"]);
+    let set = match doc.definitions[0] {
+        Definition::Operation(ref op) => &op.selection_set,
+        _ => unreachable!(),
+    };
+    let found = set.visit::<Field<_>>().in_fragments_on("Query")
+        .collect::<Vec<_>>();
+    assert_eq!(found.iter().map(|m| m.field.name).collect::<Vec<_>>(),
+               ["d", "e"]);
+    assert_eq!(found[0].type_condition, Some("Query"));
+}
@@ -0,0 +1,69 @@
+#![cfg(all(feature = "query", feature = "schema"))]
+extern crate edb_graphql_parser;
+#[cfg(test)] #[macro_use] extern crate pretty_assertions;
+
+use std::io::Read;
+use std::fs::{File, read_dir};
+
+use edb_graphql_parser::{parse_query, parse_schema, Style};
+
+fn minified() -> Style {
+    let mut style = Style::default();
+    style.minified(true);
+    style
+}
+
+fn read(path: &std::path::Path) -> String {
+    let mut buf = String::with_capacity(1024);
+    let mut f = File::open(path).unwrap();
+    f.read_to_string(&mut buf).unwrap();
+    buf
+}
+
+#[test]
+fn queries_reparse() {
+    for entry in read_dir("tests/queries").unwrap() {
+        let path = entry.unwrap().path();
+        let buf = read(&path);
+        let ast = parse_query::<String>(&buf).unwrap();
+        let min = ast.format(&minified());
+        assert_eq!(parse_query::<String>(&min).unwrap().to_string(),
+                   ast.to_string(), "{:?}", path);
+    }
+}
+
+#[test]
+fn schemas_reparse() {
+    for entry in read_dir("tests/schemas").unwrap() {
+        let path = entry.unwrap().path();
+        let buf = read(&path);
+        let ast = parse_schema::<String>(&buf).unwrap();
+        let min = ast.format(&minified());
+        assert_eq!(parse_schema::<String>(&min).unwrap().to_string(),
+                   ast.to_string(), "{:?}", path);
+    }
+}
+
+#[test]
+fn query() {
+    let ast = parse_query::<&str>(r#"
+        query Q($a: Int = 1, $b: [String] = ["", ""]) @live {
+            x: node(id: 1, list: [1, -2, "x"]) { id ... on User { name } ...F }
+        }
+    "#).unwrap();
+    assert_eq!(ast.format(&minified()),
+        r#"query Q($a:Int=1$b:[String]=["" ""])@live{x:node(id:1 list:[1 -2 "x"]){id...on User{name}...F}}"#);
+}
+
+#[test]
+fn schema() {
+    let ast = parse_schema::<&str>(r#"
+        """
+        Multiline
+        description
+        """
+        type User implements Node & Entity { id: ID! }
+    "#).unwrap();
+    assert_eq!(ast.format(&minified()),
+        r#""Multiline\ndescription\n"type User implements Node&Entity{id:ID!}"#);
+}
@@ -1,3 +1,4 @@
+#![cfg(feature = "query")]
 extern crate edb_graphql_parser;
 #[cfg(test)] #[macro_use] extern crate pretty_assertions;
 
@@ -1,3 +1,4 @@
+#![cfg(feature = "query")]
 extern crate edb_graphql_parser;
 #[cfg(test)] #[macro_use] extern crate pretty_assertions;
 
@@ -27,6 +28,7 @@ fn roundtrip2(filename: &str) {
     let mut f = File::open(&target).unwrap();
     f.read_to_string(&mut buf).unwrap();
     assert_eq!(ast.to_string(), buf);
+    assert!(ast.semantic_eq(&parse_query::<&str>(&buf).unwrap()));
 }
 
 #[test] fn minimal() { roundtrip("minimal"); }
@@ -38,8 +40,10 @@ fn roundtrip2(filename: &str) {
 #[test] fn query_var_defaults2() { roundtrip("query_var_default_float"); }
 #[test] fn query_var_defaults3() { roundtrip("query_var_default_list"); }
 #[test] fn query_var_defaults4() { roundtrip("query_var_default_object"); }
+#[test] fn query_var_directives() { roundtrip("query_var_directives"); }
 #[test] fn query_aliases() { roundtrip("query_aliases"); }
 #[test] fn query_arguments() { roundtrip("query_arguments"); }
+#[test] fn query_big_numbers() { roundtrip("query_big_numbers"); }
 #[test] fn query_directive() { roundtrip("query_directive"); }
 #[test] fn mutation_directive() { roundtrip("mutation_directive"); }
 #[test] fn subscription_directive() { roundtrip("subscription_directive"); }
@@ -55,3 +59,16 @@ fn roundtrip2(filename: &str) {
 #[test] fn fragment() { roundtrip("fragment"); }
 #[test] fn directive_args() { roundtrip("directive_args"); }
 #[test] fn kitchen_sink() { roundtrip2("kitchen-sink"); }
+
+#[test]
+fn semantic_equality() {
+    let ast = parse_query::<&str>("{ a(x: \"1\") { b } }").unwrap();
+    let same = parse_query::<String>("\n\n{\n  a(x: \"1\",) {\n b } }")
+        .unwrap();
+    assert!(ast != parse_query::<&str>(" { a(x: \"1\") { b } }").unwrap());
+    assert!(ast.semantic_eq(&same));
+    assert!(!ast.semantic_eq(&parse_query::<&str>("{ a(x: 1) { b } }")
+                              .unwrap()));
+    assert!(!ast.semantic_eq(&parse_query::<&str>("{ a(x: \"1\") { c } }")
+                              .unwrap()));
+}
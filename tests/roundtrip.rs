@@ -0,0 +1,66 @@
+#![cfg(all(feature = "query", feature = "schema"))]
+extern crate edb_graphql_parser;
+#[cfg(feature = "arbitrary")] extern crate arbitrary;
+
+use std::fs::{read_dir, read_to_string};
+
+use edb_graphql_parser::{parse_query, parse_schema};
+use edb_graphql_parser::roundtrip::assert_roundtrip;
+
+#[cfg(feature = "arbitrary")]
+mod generated {
+    use arbitrary::{Arbitrary, Unstructured};
+
+    use edb_graphql_parser::{query, schema, Style};
+    use edb_graphql_parser::roundtrip::Roundtrip;
+
+    /// Pseudo-random bytes (xorshift), so failures reproduce
+    fn data(mut state: u64) -> Vec<u8> {
+        (0..4096).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        }).collect()
+    }
+
+    fn check<D: Roundtrip>(doc: &D) {
+        let mut minified = Style::default();
+        minified.minified(true);
+        for style in &[Style::default(), minified] {
+            if let Err(e) = doc.roundtrip(style) {
+                panic!("{}\nAST: {:#?}", e, doc);
+            }
+        }
+    }
+
+    #[test]
+    fn queries() {
+        for seed in 0..500 {
+            let data = data(0x2545_F491_4F6C_DD1D + seed);
+            let mut u = Unstructured::new(&data);
+            check(&query::Document::<String>::arbitrary(&mut u).unwrap());
+        }
+    }
+
+    #[test]
+    fn schemas() {
+        for seed in 0..300 {
+            let data = data(0x9E37_79B9_7F4A_7C15 + seed);
+            let mut u = Unstructured::new(&data);
+            check(&schema::Document::<String>::arbitrary(&mut u).unwrap());
+        }
+    }
+}
+
+#[test]
+fn corpus() {
+    for entry in read_dir("tests/queries").unwrap() {
+        let text = read_to_string(entry.unwrap().path()).unwrap();
+        assert_roundtrip(&parse_query::<&str>(&text).unwrap()).unwrap();
+    }
+    for entry in read_dir("tests/schemas").unwrap() {
+        let text = read_to_string(entry.unwrap().path()).unwrap();
+        assert_roundtrip(&parse_schema::<&str>(&text).unwrap()).unwrap();
+    }
+}
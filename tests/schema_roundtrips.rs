@@ -1,3 +1,4 @@
+#![cfg(feature = "schema")]
 extern crate edb_graphql_parser;
 #[cfg(test)] #[macro_use] extern crate pretty_assertions;
 
@@ -27,6 +28,7 @@ fn roundtrip2(filename: &str) {
     let mut f = File::open(&target).unwrap();
     f.read_to_string(&mut buf).unwrap();
     assert_eq!(ast.to_string(), buf);
+    assert!(ast.semantic_eq(&parse_schema::<&str>(&buf).unwrap()));
 }
 
 #[test] fn minimal() { roundtrip("minimal"); }
@@ -47,5 +49,9 @@ fn roundtrip2(filename: &str) {
 #[test] fn input_type() { roundtrip("input_type"); }
 #[test] fn extend_input() { roundtrip2("extend_input"); }
 #[test] fn directive() { roundtrip("directive"); }
+#[test] fn directive_repeatable() { roundtrip("directive_repeatable"); }
+#[test] fn extend_schema() { roundtrip("extend_schema"); }
+#[test] fn interface_implements() { roundtrip("interface_implements"); }
+#[test] fn default_values() { roundtrip("default_values"); }
 #[test] fn kitchen_sink() { roundtrip2("kitchen-sink"); }
 #[test] fn directive_descriptions() { roundtrip2("directive_descriptions"); }
@@ -0,0 +1,139 @@
+#![cfg(all(feature = "query", feature = "schema"))]
+extern crate edb_graphql_parser;
+#[cfg(test)] #[macro_use] extern crate pretty_assertions;
+
+use std::fmt;
+use std::fs::{read_dir, read_to_string};
+use std::io;
+
+use edb_graphql_parser::{parse_query, parse_schema, Style};
+use edb_graphql_parser::{query, schema};
+
+/// Records every chunk written
+#[derive(Default)]
+struct Chunks(Vec<String>);
+
+impl fmt::Write for Chunks {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.push(s.to_string());
+        Ok(())
+    }
+}
+
+/// Accepts only the specified number of bytes
+struct Limited(usize);
+
+impl io::Write for Limited {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.0 < buf.len() {
+            return Err(io::Error::new(io::ErrorKind::WriteZero, "full"));
+        }
+        self.0 -= buf.len();
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn styles() -> Vec<Style> {
+    let mut minified = Style::default();
+    minified.minified(true);
+    let mut narrow = Style::default();
+    narrow.max_width(Some(20));
+    vec![Style::default(), minified, narrow]
+}
+
+#[test]
+fn same_as_format() {
+    for style in &styles() {
+        for entry in read_dir("tests/queries").unwrap() {
+            let text = read_to_string(entry.unwrap().path()).unwrap();
+            let doc = parse_query::<&str>(&text).unwrap();
+            let mut out = String::new();
+            doc.fmt_to(&mut out, style).unwrap();
+            assert_eq!(out, doc.format(style));
+        }
+        for entry in read_dir("tests/schemas").unwrap() {
+            let text = read_to_string(entry.unwrap().path()).unwrap();
+            let doc = parse_schema::<&str>(&text).unwrap();
+            let mut out = Vec::new();
+            doc.write_to(&mut out, style).unwrap();
+            assert_eq!(String::from_utf8(out).unwrap(), doc.format(style));
+        }
+    }
+}
+
+#[test]
+fn written_by_definition() {
+    let doc = parse_schema::<&str>("
+        type A { a: Int }
+        type B { b: Int }
+        scalar C
+    ").unwrap();
+    let mut chunks = Chunks::default();
+    doc.fmt_to(&mut chunks, &Style::default()).unwrap();
+    assert_eq!(chunks.0, vec![
+        "type A {\n  a: Int\n}",
+        "\n\ntype B {\n  b: Int\n}",
+        "\n\nscalar C",
+        "\n",
+    ]);
+}
+
+#[test]
+fn nodes() {
+    let doc = parse_schema::<&str>("type A { a: Int }").unwrap();
+    let mut out = String::new();
+    match doc.definitions[0] {
+        schema::Definition::TypeDefinition(ref def) => {
+            def.fmt_to(&mut out, &Style::default()).unwrap();
+        }
+        _ => unreachable!(),
+    }
+    assert_eq!(out, "type A {\n  a: Int\n}\n");
+}
+
+#[test]
+fn io_error() {
+    let doc = parse_query::<&str>("{ a } { b }").unwrap();
+    let err = doc.write_to(&mut Limited(8), &Style::default()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::WriteZero);
+}
+
+#[test]
+fn parsed_by_definition() {
+    for entry in read_dir("tests/queries").unwrap() {
+        let text = read_to_string(entry.unwrap().path()).unwrap();
+        let doc = parse_query::<&str>(&text).unwrap();
+        let definitions = query::parse_query_iter::<&str>(&text)
+            .collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(definitions, doc.definitions);
+    }
+    for entry in read_dir("tests/schemas").unwrap() {
+        let text = read_to_string(entry.unwrap().path()).unwrap();
+        let doc = parse_schema::<&str>(&text).unwrap();
+        let definitions = schema::parse_schema_iter::<&str>(&text)
+            .collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(definitions, doc.definitions);
+    }
+}
+
+#[test]
+fn parse_error_by_definition() {
+    let mut definitions = query::parse_query_iter::<&str>("{ a } { b } !");
+    assert!(definitions.next().unwrap().is_ok());
+    assert!(definitions.next().unwrap().is_ok());
+    let err = definitions.next().unwrap().unwrap_err();
+    assert_eq!(err.to_string(), "query parse error: \
+        Parse error at 1:13\n\
+        Unexpected `![Punctuator]`\n\
+        Expected `{`, `query`, `mutation`, `subscription` or `fragment`\n");
+    assert!(definitions.next().is_none());
+
+    assert!(query::parse_query_iter::<&str>("").next().unwrap().is_err());
+    let mut definitions = schema::parse_schema_iter::<&str>("scalar A type");
+    assert!(definitions.next().unwrap().is_ok());
+    assert!(definitions.next().unwrap().is_err());
+    assert!(definitions.next().is_none());
+}
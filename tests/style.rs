@@ -0,0 +1,595 @@
+#![cfg(all(feature = "query", feature = "schema"))]
+extern crate edb_graphql_parser;
+#[cfg(test)] #[macro_use] extern crate pretty_assertions;
+
+use edb_graphql_parser::{parse_query, parse_schema, Style};
+use edb_graphql_parser::format::StringStyle;
+
+#[test]
+fn tabs() {
+    let ast = parse_query::<&str>("{ a { b } }").unwrap();
+    let mut style = Style::default();
+    style.indent(1).indent_char('\t');
+    assert_eq!(ast.format(&style), "{\n\ta {\n\t\tb\n\t}\n}\n");
+}
+
+#[test]
+fn wrap_query_arguments() {
+    let ast = parse_query::<&str>(r#"
+        query Query($first: Int, $after: String) {
+            users(first: $first, after: $after, filter: "active") { id }
+            short(a: 1) @include(if: true) { id }
+        }
+    "#).unwrap();
+    let mut style = Style::default();
+    style.max_width(Some(32));
+    assert_eq!(ast.format(&style), r#"query Query(
+  $first: Int
+  $after: String
+) {
+  users(
+    first: $first
+    after: $after
+    filter: "active"
+  ) {
+    id
+  }
+  short(a: 1) @include(if: true) {
+    id
+  }
+}
+"#);
+}
+
+#[test]
+fn wrap_schema_arguments() {
+    let ast = parse_schema::<&str>(r#"
+        type Query {
+            users(first: Int = 10, after: String, filter: UserFilter): [User]
+            user(id: ID!): User
+        }
+    "#).unwrap();
+    let mut style = Style::default();
+    style.max_width(Some(40));
+    assert_eq!(ast.format(&style), "\
+type Query {
+  users(
+    first: Int = 10
+    after: String
+    filter: UserFilter
+  ): [User]
+  user(id: ID!): User
+}
+");
+}
+
+#[test]
+fn single_line_descriptions() {
+    let ast = parse_schema::<&str>(r#"
+        """
+        Multiline
+        description
+        """
+        type User {
+            "Field description"
+            id: ID
+        }
+    "#).unwrap();
+    let mut style = Style::default();
+    style.block_descriptions(false);
+    assert_eq!(ast.format(&style), "\
+\"Multiline\\ndescription\\n\"
+type User {
+  \"Field description\"
+  id: ID
+}
+");
+}
+
+#[test]
+fn raw_descriptions() {
+    let ast = parse_schema::<&str>(r#"
+        """
+        Markdown list:
+          * with `\"""`
+        """
+        type User {
+            "Escaped \u0041"
+            id: ID
+        }
+    "#).unwrap();
+    let mut style = Style::default();
+    style.raw_descriptions(true);
+    assert_eq!(ast.format(&style), r#""""
+  Markdown list:
+    * with `\"""`
+"""
+type User {
+  "Escaped \u0041"
+  id: ID
+}
+"#);
+    let mut style = Style::default();
+    style.raw_descriptions(true).minified(true);
+    assert_eq!(ast.format(&style),
+        r#""Markdown list:\n  * with `\"\"\"`\n"type User{"Escaped A"id:ID}"#);
+}
+
+#[test]
+fn string_styles() {
+    let ast = parse_schema::<&str>(r#"
+        "Ünïcode 😀"
+        type User {
+            "Two\nlines"
+            name(format: String = "Zoë\n"): String
+        }
+    "#).unwrap();
+    let mut style = Style::default();
+    assert_eq!(ast.format(&style), r#""Ünïcode 😀"
+type User {
+  "Two\nlines"
+  name(format: String = """
+    Zoë
+  """): String
+}
+"#);
+    style.strings(StringStyle::Ascii);
+    let ascii = ast.format(&style);
+    assert_eq!(ascii, r#""\u00dcn\u00efcode \ud83d\ude00"
+type User {
+  "Two\nlines"
+  name(format: String = "Zo\u00eb\n"): String
+}
+"#);
+    assert_eq!(parse_schema::<&str>(&ascii).unwrap().to_string(),
+               ast.to_string());
+    style.strings(StringStyle::Block);
+    assert_eq!(ast.format(&style), r#""Ünïcode 😀"
+type User {
+  """
+    Two
+    lines
+  """
+  name(format: String = """
+    Zoë
+  """): String
+}
+"#);
+}
+
+#[test]
+fn sorted_schema() {
+    let source = r#"
+        type Query { users(last: Int, first: Int): [User] node(id: ID!): Node }
+        extend type User @b @a { b: Int }
+        union Item = User | Admin
+        enum Role { USER ADMIN }
+        type User implements Node & Entity @key(fields: "id") { name: String id: ID! }
+        directive @b on OBJECT
+        input Filter { z: Int a: Int }
+        schema { query: Query }
+    "#;
+    let ast = parse_schema::<&str>(source).unwrap();
+    let mut style = Style::default();
+    style.sorted(true);
+    let expected = r#"schema {
+  query: Query
+}
+
+directive @b on OBJECT
+
+input Filter {
+  a: Int
+  z: Int
+}
+
+union Item = Admin | User
+
+type Query {
+  node(id: ID!): Node
+  users(first: Int, last: Int): [User]
+}
+
+enum Role {
+  ADMIN
+  USER
+}
+
+type User implements Entity & Node @key(fields: "id") {
+  id: ID!
+  name: String
+}
+
+extend type User @b @a {
+  b: Int
+}
+"#;
+    assert_eq!(ast.format(&style), expected);
+    // output doesn't depend on the order in the source
+    let mut reversed = parse_schema::<&str>(source).unwrap();
+    reversed.definitions.reverse();
+    assert_eq!(reversed.format(&style), expected);
+    let mut out = String::new();
+    reversed.fmt_to(&mut out, &style).unwrap();
+    assert_eq!(out, expected);
+}
+
+#[test]
+fn federation_subgraph() {
+    let ast = parse_schema::<&str>(r#"
+        schema @link(url: "https://specs.apollo.dev/federation/v2.0", import: ["@key"]) @custom {
+          query: Query
+        }
+        extend schema @link(url: "https://specs.apollo.dev/tag/v0.3")
+        extend schema @link(url: "https://example.com/custom") @custom
+        directive @key(fields: FieldSet!) on OBJECT | INTERFACE
+        directive @external on FIELD_DEFINITION
+        directive @link(url: String, import: [link__Import]) on SCHEMA
+        directive @custom on SCHEMA
+        scalar link__Import
+        scalar _Any
+        scalar FieldSet
+        union _Entity = User
+        type _Service { sdl: String }
+        "Root"
+        type Query {
+          me: User
+          _entities(representations: [_Any!]!): [_Entity]!
+          _service: _Service!
+        }
+        "A user"
+        type User @key(fields: "id") {
+          id: ID! @external
+          name: String
+        }
+    "#).unwrap();
+    let mut style = Style::default();
+    style.omit_federation_builtins(true).extend_types(vec!["Query"]);
+    assert_eq!(ast.format(&style), r#"schema @custom {
+  query: Query
+}
+
+extend schema @custom
+
+directive @custom on SCHEMA
+
+extend type Query {
+  me: User
+}
+
+"A user"
+type User @key(fields: "id") {
+  id: ID! @external
+  name: String
+}
+"#);
+}
+
+#[test]
+fn builtins() {
+    let ast = parse_schema::<&str>(r#"
+        schema { query: Query mutation: Mutation }
+        directive @skip(if: Boolean!) on FIELD | FRAGMENT_SPREAD | INLINE_FRAGMENT
+        directive @deprecated(reason: String = "No longer supported") on FIELD_DEFINITION | ENUM_VALUE
+        directive @custom on FIELD
+        scalar Int
+        scalar ID
+        scalar Date
+        extend scalar ID @custom
+        type Query { a: Int }
+        type Mutation { b: ID }
+    "#).unwrap();
+    let mut style = Style::default();
+    style.omit_builtin_scalars(true)
+        .omit_builtin_directives(true)
+        .omit_default_schema(true);
+    assert_eq!(ast.format(&style), r#"directive @custom on FIELD
+
+scalar Date
+
+extend scalar ID @custom
+
+type Query {
+  a: Int
+}
+
+type Mutation {
+  b: ID
+}
+"#);
+
+    // `Mutation` is not a root type, so the definition is not implied
+    let ast = parse_schema::<&str>(r#"
+        schema { query: Query }
+        type Query { a: Int }
+        type Mutation { b: ID }
+    "#).unwrap();
+    assert!(ast.format(&style).starts_with("schema {\n  query: Query\n}\n"));
+    let ast = parse_schema::<&str>(r#"
+        schema { query: Root }
+        type Root { a: Int }
+    "#).unwrap();
+    assert!(ast.format(&style).starts_with("schema {\n  query: Root\n}\n"));
+}
+
+#[test]
+fn default_values() {
+    let ast = parse_schema::<&str>(r#"
+        type Query {
+          a(x: Float = 1.50e3, y: String = null, z: [Float] = [-0.0, 2E-5]): A
+        }
+        input In {
+          text: String = """
+            block
+          """
+          nested: Obj = {b: [{c: 600E-2}], a: null}
+        }
+    "#).unwrap();
+    let mut style = Style::default();
+    style.omit_null_defaults(true);
+    assert_eq!(ast.format(&style), r#"type Query {
+  a(x: Float = 1.50e3, y: String, z: [Float] = [-0.0, 2E-5]): A
+}
+
+input In {
+  text: String = """
+    block
+  """
+  nested: Obj = {b: [{c: 600E-2}], a: null}
+}
+"#);
+    style.normalize_defaults(true);
+    assert_eq!(ast.format(&style), r#"type Query {
+  a(x: Float = 1500.0, y: String, z: [Float] = [-0.0, 2e-5]): A
+}
+
+input In {
+  text: String = "block\n"
+  nested: Obj = {a: null, b: [{c: 6.0}]}
+}
+"#);
+}
+
+#[test]
+fn annotations() {
+    use edb_graphql_parser::format::Annotated;
+
+    let ast = parse_schema::<&str>(r#"
+        "Node" interface Node { id: ID! }
+        type User implements Node {
+            id: ID!
+            name(full: Boolean
+                 = false): String @deprecated
+        }
+        enum Role { ADMIN USER }
+        input Filter { role: Role }
+        union Entity = User
+        scalar Date
+        directive @auth on FIELD_DEFINITION
+    "#).unwrap();
+    let mut style = Style::default();
+    style.annotate(|node| match *node {
+        Annotated::Schema { coordinate: "User.name", .. } => {
+            Some("deprecated\nsince v2".into())
+        }
+        Annotated::Schema { coordinate: "Role", .. } => None,
+        Annotated::Schema { coordinate, position } => {
+            Some(format!("{} at {}", coordinate, position.line))
+        }
+        Annotated::Field { .. } => unreachable!(),
+    });
+    assert_eq!(ast.format(&style), r#""Node"
+interface Node { # Node at 2
+  id: ID! # Node.id at 2
+}
+
+type User implements Node { # User at 3
+  id: ID! # User.id at 4
+  name(full: Boolean = false): String @deprecated # deprecated since v2
+}
+
+enum Role {
+  ADMIN # Role.ADMIN at 8
+  USER # Role.USER at 8
+}
+
+input Filter { # Filter at 9
+  role: Role # Filter.role at 9
+}
+
+union Entity = User # Entity at 10
+
+scalar Date # Date at 11
+
+directive @auth on FIELD_DEFINITION # @auth at 12
+"#);
+    style.minified(true);
+    assert!(!ast.format(&style).contains('#'));
+
+    let ast = parse_query::<&str>("
+        query Q { me { ...F friends { id } } }
+        fragment F on User { name }
+    ").unwrap();
+    let mut style = Style::default();
+    style.annotate(|node| match *node {
+        Annotated::Field { path, position } => {
+            Some(format!("{} {}", path.join("."), position))
+        }
+        _ => None,
+    });
+    assert_eq!(ast.format(&style), "\
+query Q {
+  me { # me 2:19
+    ...F
+    friends { # me.friends 2:29
+      id # me.friends.id 2:39
+    }
+  }
+}
+
+fragment F on User {
+  name # name 3:30
+}
+");
+}
+
+#[test]
+fn comments() {
+    let source = r#"
+        # Root type
+        type Query { # fields
+            # the user
+            me: User
+            nodes: [Node] # all
+            # end of fields
+        }
+
+        "A user"
+        # after description
+        type User { id: ID # identifier
+        }
+        # trailer
+    "#;
+    let ast = parse_schema::<&str>(source).unwrap();
+    let mut style = Style::default();
+    style.preserve_comments(source);
+    assert_eq!(ast.format(&style), r#"# Root type
+type Query { # fields
+  # the user
+  me: User
+  nodes: [Node] # all
+}
+
+# end of fields
+# after description
+"A user"
+type User {
+  id: ID # identifier
+}
+
+# trailer
+"#);
+    style.sorted(true);
+    assert_eq!(ast.format(&style), r#"# Root type
+type Query { # fields
+  # the user
+  me: User
+  nodes: [Node] # all
+}
+
+# after description
+"A user"
+type User {
+  id: ID # identifier
+}
+
+# end of fields
+# trailer
+"#);
+    style.minified(true);
+    assert!(!ast.format(&style).contains('#'));
+}
+
+#[test]
+fn format_query_range() {
+    use edb_graphql_parser::position::{apply_edits, Pos};
+    use edb_graphql_parser::query::format_range;
+
+    let source = "\
+# first
+query A {a}
+# second
+query B {
+      b # field
+  c @skip(if:true)
+}   fragment F on T {f}
+";
+    let at = |line, column| Pos { line, column, .. Pos::default() };
+    let style = Style::default();
+    let edits = format_range(source, (at(5, 3), at(6, 1)), &style).unwrap();
+    assert_eq!(edits.len(), 1);
+    assert_eq!(edits[0].span.0.to_string(), "4:1");
+    assert_eq!(edits[0].span.1.to_string(), "7:2");
+    assert_eq!(apply_edits(source, &edits), "\
+# first
+query A {a}
+# second
+query B {
+  b # field
+  c @skip(if: true)
+}   fragment F on T {f}
+");
+    let edits = format_range(source, (at(7, 1), at(7, 5)), &style).unwrap();
+    assert_eq!(edits.len(), 2);
+    let edits = format_range(source, (at(1, 1), at(1, 7)), &style).unwrap();
+    assert!(edits.is_empty());
+    let formatted = parse_query::<&str>(source).unwrap().format(&style);
+    assert!(format_range(&formatted, (at(1, 1), at(100, 1)), &style)
+            .unwrap().is_empty());
+}
+
+#[test]
+fn format_schema_range() {
+    use edb_graphql_parser::position::{apply_edits, Pos};
+    use edb_graphql_parser::schema::format_range;
+
+    let source = "\
+\"A type\" type A {a:Int}
+extend   type A @d
+\"\"\"
+Directive
+\"\"\"
+directive @d(x:Int) on OBJECT
+";
+    let at = |line, column| Pos { line, column, .. Pos::default() };
+    let style = Style::default();
+    let edits = format_range(source, (at(2, 1), at(4, 1)), &style).unwrap();
+    assert_eq!(apply_edits(source, &edits), "\
+\"A type\" type A {a:Int}
+extend type A @d
+\"\"\"
+  Directive
+\"\"\"
+directive @d(x: Int) on OBJECT
+");
+    let edits = format_range(source, (at(1, 1), at(1, 1)), &style).unwrap();
+    assert_eq!(apply_edits(source, &edits), "\
+\"A type\"
+type A {
+  a: Int
+}
+extend   type A @d
+\"\"\"
+Directive
+\"\"\"
+directive @d(x:Int) on OBJECT
+");
+}
+
+#[cfg(feature = "apq")]
+#[test]
+fn schema_hash() {
+    use edb_graphql_parser::schema::schema_hash;
+
+    let hash = |s| schema_hash(&parse_schema::<&str>(s).unwrap());
+    let base = hash("schema { query: Q }
+                     \"Root\" type Q { b: Int, a(y: Int, x: Int): Int }
+                     directive @d on FIELD");
+    assert_eq!(base, hash("directive @d on FIELD
+
+                           \"\"\"
+                           Root
+                           \"\"\"
+                           type Q {
+                             a(x: Int, y: Int): Int
+                             b: Int
+                           }
+                           schema { query: Q }"));
+    assert_ne!(base, hash("schema { query: Q }
+                           \"Query\" type Q { b: Int, a(y: Int, x: Int): Int }
+                           directive @d on FIELD"));
+    assert_ne!(base, hash("schema { query: Q }
+                           \"Root\" type Q { b: Int, a(y: Int, x: Int): ID }
+                           directive @d on FIELD"));
+    assert_eq!(base.len(), 64);
+}